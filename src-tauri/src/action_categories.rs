@@ -0,0 +1,168 @@
+//! The in-game keybinding screen's grouping of actions
+//!
+//! defaultProfile.xml (shipped as AllBinds.xml) tags each actionmap with a UICategory (the
+//! top-level tab, e.g. "@ui_CCSpaceFlight") and a UILabel (the group heading inside it,
+//! e.g. "@ui_CGFPSMovement"). Several actionmaps can share a heading ("player" and "prone"
+//! both show under FPS movement), and actionmaps or actions without a UILabel aren't shown
+//! in game at all. This rebuilds that tree, in the file's order, so bindings and options
+//! can be presented the way players know them from the game.
+
+use crate::keybindings::AllBinds;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Category of actionmaps without a UICategory
+pub const UNCATEGORIZED: &str = "";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ActionInfo {
+    pub action_map: String,
+    pub name: String,
+    pub label: String,
+    pub description: String,
+    /// Finer grouping some actions carry (e.g. "Emotes", "ShipSystems")
+    pub category: String,
+    pub shown_in_game: bool,
+}
+
+/// One heading on the keybinding screen
+#[derive(Debug, Serialize, Clone)]
+pub struct ActionGroup {
+    /// UILabel localization key, or the actionmap name when it has none
+    pub label: String,
+    pub action_maps: Vec<String>,
+    pub actions: Vec<ActionInfo>,
+    pub shown_in_game: bool,
+}
+
+/// One tab of the keybinding screen
+#[derive(Debug, Serialize, Clone)]
+pub struct ActionCategory {
+    /// UICategory localization key, UNCATEGORIZED for actionmaps without one
+    pub label: String,
+    pub groups: Vec<ActionGroup>,
+}
+
+/// Where an actionmap sits in the tree
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Placement {
+    pub category: String,
+    pub group: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ActionCategories {
+    pub categories: Vec<ActionCategory>,
+    /// Actionmap name -> its place, for grouping bindings by actionmap
+    pub placements: BTreeMap<String, Placement>,
+}
+
+/// Build the category tree from AllBinds.xml
+pub fn build(all_binds: &AllBinds) -> ActionCategories {
+    let mut tree = ActionCategories::default();
+
+    for action_map in &all_binds.action_maps {
+        let shown = !action_map.ui_label.is_empty();
+        let group_label = if shown {
+            action_map.ui_label.clone()
+        } else {
+            action_map.name.clone()
+        };
+
+        let category_index = match tree
+            .categories
+            .iter()
+            .position(|c| c.label == action_map.ui_category)
+        {
+            Some(index) => index,
+            None => {
+                tree.categories.push(ActionCategory {
+                    label: action_map.ui_category.clone(),
+                    groups: Vec::new(),
+                });
+                tree.categories.len() - 1
+            }
+        };
+        let groups = &mut tree.categories[category_index].groups;
+        let group_index = match groups.iter().position(|g| g.label == group_label) {
+            Some(index) => index,
+            None => {
+                groups.push(ActionGroup {
+                    label: group_label.clone(),
+                    action_maps: Vec::new(),
+                    actions: Vec::new(),
+                    shown_in_game: shown,
+                });
+                groups.len() - 1
+            }
+        };
+
+        let group = &mut groups[group_index];
+        group.action_maps.push(action_map.name.clone());
+        group
+            .actions
+            .extend(action_map.actions.iter().map(|action| ActionInfo {
+                action_map: action_map.name.clone(),
+                name: action.name.clone(),
+                label: action.ui_label.clone(),
+                description: action.ui_description.clone(),
+                category: action.category.clone(),
+                shown_in_game: shown && !action.ui_label.is_empty(),
+            }));
+
+        tree.placements.insert(
+            action_map.name.clone(),
+            Placement {
+                category: action_map.ui_category.clone(),
+                group: group_label,
+            },
+        );
+    }
+
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_maps_grouped_like_the_game() {
+        let xml = r#"<profile version="1">
+ <actionmap name="player" version="27" UILabel="@ui_CGFPSMovement" UICategory="@ui_CCFPS">
+  <action name="v_move_forward" UILabel="@ui_CIMoveForward" UIDescription="@ui_CIMoveForwardDesc"/>
+ </actionmap>
+ <actionmap name="spaceship_movement" version="3" UILabel="@ui_CGSpaceFlight" UICategory="@ui_CCSpaceFlight">
+  <action name="v_pitch" UILabel="@ui_CIPitch"/>
+ </actionmap>
+ <actionmap name="prone" version="1" UILabel="@ui_CGFPSMovement" UICategory="@ui_CCFPS">
+  <action name="v_prone_roll_left" UILabel="@ui_CIProneRollLeft"/>
+  <action name="v_prone_internal"/>
+ </actionmap>
+ <actionmap name="spaceship_auto_weapons" version="1">
+  <action name="v_auto_fire"/>
+ </actionmap>
+</profile>"#;
+        let tree = build(&AllBinds::from_xml(xml).unwrap());
+
+        let labels: Vec<&str> = tree.categories.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec!["@ui_CCFPS", "@ui_CCSpaceFlight", UNCATEGORIZED]
+        );
+
+        let movement = &tree.categories[0].groups[0];
+        assert_eq!(movement.action_maps, vec!["player", "prone"]);
+        assert_eq!(movement.actions.len(), 3);
+        assert!(!movement.actions[2].shown_in_game);
+
+        assert!(!tree.categories[2].groups[0].shown_in_game);
+        assert_eq!(
+            tree.placements["prone"],
+            Placement {
+                category: "@ui_CCFPS".to_string(),
+                group: "@ui_CGFPSMovement".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Salvage a damaged actionmaps.xml
+//!
+//! When the game or the PC crashes while SC is writing actionmaps.xml, the file can end
+//! part way through an element or have garbage (often NUL bytes) where the rest should
+//! be, and the normal parsers refuse it entirely. Recovery reads up to the damage and
+//! keeps everything that was complete by then: whole `<options>` blocks (a half-written
+//! one is dropped, its options can't be trusted) and every rebind, with the actions and
+//! actionmaps that were still open closed again. The result is a well-formed file that
+//! can be loaded or written back in place of the damaged one.
+
+use crate::controls;
+use crate::keybindings::ActionMaps;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::Serialize;
+
+/// What recovery found and kept
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RecoveryReport {
+    /// The file was well-formed; nothing was dropped
+    pub intact: bool,
+    /// Byte offset where the damage starts
+    pub damage_offset: Option<usize>,
+    /// e.g. "file ends inside <options>"
+    pub problem: Option<String>,
+    /// Bytes after the last intact element that were left out
+    pub dropped_bytes: usize,
+    pub options_blocks: usize,
+    pub options: usize,
+    pub action_maps: usize,
+    pub rebinds: usize,
+}
+
+/// A repaired file and what it holds
+#[derive(Debug, Clone)]
+pub struct Recovery {
+    pub xml: String,
+    pub report: RecoveryReport,
+}
+
+/// Where the file can be cut without losing anything half-written: the end offset of
+/// the last complete element and the elements still open there
+struct SafePoint {
+    offset: usize,
+    open: Vec<String>,
+}
+
+fn element_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.name().as_ref()).into_owned()
+}
+
+/// Inside an `<options>` block nothing is kept until the block closes
+fn inside_options(open: &[String]) -> bool {
+    open.iter().any(|name| name == "options")
+}
+
+fn malformed_attribute(e: &BytesStart) -> Option<String> {
+    e.attributes()
+        .any(|attr| attr.is_err())
+        .then(|| format!("malformed attribute in <{}>", element_name(e)))
+}
+
+/// Keep everything intact in `xml` and close what was left open
+pub fn recover(xml: &str) -> Result<Recovery, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut open: Vec<String> = Vec::new();
+    let mut safe = SafePoint {
+        offset: 0,
+        open: Vec::new(),
+    };
+    let mut damage: Option<(usize, String)> = None;
+
+    loop {
+        let event_start = reader.buffer_position() as usize;
+        let event = reader.read_event_into(&mut buf);
+        let event_end = reader.buffer_position() as usize;
+
+        match event {
+            Ok(Event::Start(ref e)) => {
+                if let Some(problem) = malformed_attribute(e) {
+                    damage = Some((event_start, problem));
+                    break;
+                }
+                open.push(element_name(e));
+            }
+            Ok(Event::Empty(ref e)) => {
+                if let Some(problem) = malformed_attribute(e) {
+                    damage = Some((event_start, problem));
+                    break;
+                }
+            }
+            Ok(Event::End(_)) => {
+                open.pop();
+            }
+            Ok(Event::Eof) => {
+                if let Some(name) = open.last() {
+                    damage = Some((xml.len(), format!("file ends inside <{}>", name)));
+                }
+                break;
+            }
+            Err(e) => {
+                damage = Some((event_start, e.to_string()));
+                break;
+            }
+            _ => {
+                buf.clear();
+                continue;
+            }
+        }
+        buf.clear();
+
+        if !inside_options(&open) {
+            safe = SafePoint {
+                offset: event_end,
+                open: open.clone(),
+            };
+        }
+        if open.is_empty() && safe.offset > 0 {
+            // The root element closed; anything but whitespace after it is damage
+            let rest = &xml[safe.offset..];
+            if !rest.trim().is_empty() {
+                damage = Some((
+                    safe.offset,
+                    "unexpected content after the end of the file".to_string(),
+                ));
+            }
+            break;
+        }
+    }
+
+    if safe.offset == 0 {
+        return Err(match damage {
+            Some((_, problem)) => format!("Nothing could be recovered: {}", problem),
+            None => "Nothing could be recovered: no complete element found".to_string(),
+        });
+    }
+
+    let mut repaired = xml[..safe.offset].to_string();
+    for (depth, name) in safe.open.iter().enumerate().rev() {
+        repaired.push('\n');
+        repaired.push_str(&" ".repeat(depth));
+        repaired.push_str(&format!("</{}>", name));
+    }
+    repaired.push('\n');
+
+    let bindings = ActionMaps::from_xml(&repaired)?;
+    let devices = controls::parse_actionmaps_options(&repaired)?;
+
+    let report = RecoveryReport {
+        intact: damage.is_none(),
+        damage_offset: damage.as_ref().map(|(offset, _)| *offset),
+        problem: damage.map(|(_, problem)| problem),
+        dropped_bytes: xml[safe.offset..].trim_end().len(),
+        options_blocks: devices.len(),
+        options: devices.iter().map(|d| d.options.len()).sum(),
+        action_maps: bindings.action_maps.len(),
+        rebinds: bindings.rebind_count(),
+    };
+    Ok(Recovery {
+        xml: if report.intact {
+            xml.to_string()
+        } else {
+            repaired
+        },
+        report,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTACT: &str = r#"<ActionMaps version="1" optionsVersion="2" rebindVersion="2" profileName="default">
+ <ActionProfiles version="1" optionsVersion="2" rebindVersion="2" profileName="default">
+  <options type="joystick" instance="1" Product="Stick">
+   <flight_move_pitch invert="1"/>
+  </options>
+  <options type="joystick" instance="2" Product="Throttle">
+   <flight_move_yaw invert="1"/>
+   <flight_move_roll invert="1"/>
+  </options>
+  <actionmap name="spaceship_general">
+   <action name="v_eject">
+    <rebind input="js1_button1"/>
+   </action>
+   <action name="v_exit">
+    <rebind input="js1_button2"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>
+"#;
+
+    #[test]
+    fn test_recover_truncated_file() {
+        let intact = recover(INTACT).unwrap();
+        assert!(intact.report.intact);
+        assert_eq!(intact.xml, INTACT);
+        assert_eq!(intact.report.dropped_bytes, 0);
+
+        // Cut inside the second options block: only the first one survives
+        let cut = INTACT.find("<flight_move_roll").unwrap();
+        let recovered = recover(&INTACT[..cut]).unwrap();
+        assert!(!recovered.report.intact);
+        assert_eq!(
+            recovered.report.problem.as_deref(),
+            Some("file ends inside <options>")
+        );
+        assert_eq!(recovered.report.options_blocks, 1);
+        assert_eq!(recovered.report.rebinds, 0);
+        assert!(ActionMaps::from_xml(&recovered.xml).is_ok());
+
+        // Crash padding part way through the second action: the first rebind is kept
+        let cut = INTACT.find("<rebind input=\"js1_button2\"").unwrap();
+        let damaged = format!("{}{}", &INTACT[..cut], "\0".repeat(64));
+        let recovered = recover(&damaged).unwrap();
+        assert_eq!(recovered.report.options_blocks, 2);
+        assert_eq!(recovered.report.options, 3);
+        assert_eq!(recovered.report.action_maps, 1);
+        assert_eq!(recovered.report.rebinds, 1);
+        assert!(recovered.report.dropped_bytes >= 64);
+        assert!(recovered
+            .xml
+            .ends_with("  </actionmap>\n </ActionProfiles>\n</ActionMaps>\n"));
+
+        assert!(recover("\0\0\0").is_err());
+    }
+}
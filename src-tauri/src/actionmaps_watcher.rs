@@ -0,0 +1,385 @@
+//! Watch the active actionmaps.xml for external changes
+//!
+//! The game rewrites actionmaps.xml on exit and other tools may touch it too. When that
+//! happens while the app has the file open we emit "actionmaps-changed" with a summary
+//! of what changed, so the UI can offer to reload or re-apply instead of silently working
+//! on stale data. Changes we didn't write ourselves are also recorded in the
+//! modification log, and if the user turned on auto-restore for their essentials, any
+//! essentials the change removed are put straight back.
+
+use crate::apply_rebase;
+use crate::backups::{self, BackupLocation};
+use crate::diff::{self, ActionmapsDiff};
+use crate::essentials::{self, Essentials, RestoreSummary};
+use crate::event_stream;
+use crate::modification_log::{self, ModificationEntry};
+use crate::settings;
+use crate::watcher::{self, FileWatcher};
+use crate::write_lock::{self, WriteLock};
+use log::{error, info};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+/// Short counts for a notification, the full diff is included for a details view
+#[derive(Debug, Serialize, Clone)]
+pub struct ChangeSummary {
+    pub options_changed: usize,
+    pub bindings_changed: usize,
+    /// Distinct "type instance" pairs whose options changed, e.g. "joystick 1"
+    pub devices_affected: Vec<String>,
+    /// The document couldn't be parsed, e.g. the game was mid-write or it's corrupt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_error: Option<String>,
+}
+
+/// Payload of the "actionmaps-changed" event
+#[derive(Debug, Serialize, Clone)]
+pub struct ActionmapsChangedEvent {
+    pub actionmaps_path: String,
+    pub summary: ChangeSummary,
+    pub diff: ActionmapsDiff,
+    /// The entry written to the modification log, if the change could be diffed
+    pub log_entry: Option<ModificationEntry>,
+}
+
+/// Payload of the "essentials-restored" event
+#[derive(Debug, Serialize, Clone)]
+pub struct EssentialsRestoredEvent {
+    pub actionmaps_path: String,
+    pub backup_path: String,
+    pub summary: RestoreSummary,
+}
+
+pub fn summarize(diff: &ActionmapsDiff) -> ChangeSummary {
+    let mut devices_affected: Vec<String> = diff
+        .option_changes
+        .iter()
+        .map(|c| format!("{} {}", c.device_type, c.instance))
+        .collect();
+    devices_affected.sort();
+    devices_affected.dedup();
+
+    ChangeSummary {
+        options_changed: diff.option_changes.len(),
+        bindings_changed: diff.binding_changes.len(),
+        devices_affected,
+        parse_error: None,
+    }
+}
+
+/// Diff an external change against the last version we saw; the diff is `None` when
+/// either document couldn't be parsed, which the summary reports instead
+fn summarize_change(previous: &str, xml: &str) -> (ChangeSummary, Option<ActionmapsDiff>) {
+    match diff::diff_actionmaps(previous, xml) {
+        Ok(diff) => (summarize(&diff), Some(diff)),
+        Err(e) => {
+            error!("Could not diff changed actionmaps.xml: {}", e);
+            let summary = ChangeSummary {
+                options_changed: 0,
+                bindings_changed: 0,
+                devices_affected: Vec::new(),
+                parse_error: Some(e),
+            };
+            (summary, None)
+        }
+    }
+}
+
+/// What an essentials restore wrote
+#[derive(Debug)]
+pub struct EssentialsRestored {
+    pub backup_path: String,
+    pub summary: RestoreSummary,
+    /// The actionmaps.xml content written
+    pub written: String,
+}
+
+/// Put any missing essentials back into actionmaps.xml.
+///
+/// The file is read only once the write lock is held, so nothing written since the
+/// change was detected is lost. It's backed up to the backup store and written through
+/// `apply_rebase`, like a normal apply.
+pub fn restore_essentials_to_file(
+    actionmaps_path: &str,
+    lock_dir: &Path,
+    essentials: &Essentials,
+    backup_location: &BackupLocation,
+    excluded: &[String],
+) -> Result<Option<EssentialsRestored>, String> {
+    let _write_lock = WriteLock::acquire(lock_dir, "gui", write_lock::DEFAULT_WAIT)?;
+
+    let xml = std::fs::read_to_string(actionmaps_path)
+        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+    if essentials::restore(&xml, essentials, excluded)?.is_none() {
+        return Ok(None);
+    }
+
+    let backup_path = backups::create_backup(backup_location, actionmaps_path)?;
+
+    // Re-run on whatever is on disk if the game writes again before we do
+    let mut summary = RestoreSummary::default();
+    let written = apply_rebase::write_edited(actionmaps_path, xml, |current| {
+        Ok(
+            essentials::restore(current, essentials, excluded)?.map(|(new_xml, restored)| {
+                summary = restored;
+                new_xml
+            }),
+        )
+    })?;
+
+    Ok(written.map(|write| EssentialsRestored {
+        backup_path,
+        summary,
+        written: write.written,
+    }))
+}
+
+/// Put back essentials an external change removed, if auto-restore is on. Changes that
+/// left the essentials alone don't trigger a restore. Returns the hash of the content we
+/// wrote.
+fn auto_restore_essentials(
+    actionmaps_path: &str,
+    data_dir: &Path,
+    config_dir: &Path,
+    app_handle: &AppHandle,
+    diff: &ActionmapsDiff,
+) -> Result<Option<u64>, String> {
+    let essentials = essentials::load_essentials(data_dir)?;
+    if !essentials.auto_restore || !essentials::removed_by(diff, &essentials) {
+        return Ok(None);
+    }
+    let settings = settings::load_settings(config_dir)?;
+    if settings.read_only {
+        return Ok(None);
+    }
+
+    let backup_location = crate::backup_location(app_handle)?;
+    let Some(restored) = restore_essentials_to_file(
+        actionmaps_path,
+        data_dir,
+        &essentials,
+        &backup_location,
+        &settings.excluded_options,
+    )?
+    else {
+        return Ok(None);
+    };
+
+    info!(
+        "Restored {} essential binding(s) and {} essential option(s) to {}",
+        restored.summary.bindings_restored, restored.summary.options_restored, actionmaps_path
+    );
+    let _ = app_handle.emit(
+        "essentials-restored",
+        EssentialsRestoredEvent {
+            actionmaps_path: actionmaps_path.to_string(),
+            backup_path: restored.backup_path,
+            summary: restored.summary,
+        },
+    );
+
+    Ok(Some(watcher::content_hash(restored.written.as_bytes())))
+}
+
+/// A running watcher. Dropping it stops watching.
+pub struct ActionmapsWatcher {
+    path: String,
+    _watcher: FileWatcher,
+}
+
+impl ActionmapsWatcher {
+    pub fn start(
+        actionmaps_path: String,
+        data_dir: PathBuf,
+        config_dir: PathBuf,
+        app_handle: AppHandle,
+    ) -> Result<Self, String> {
+        let mut previous = std::fs::read_to_string(&actionmaps_path)
+            .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+
+        let event_path = actionmaps_path.clone();
+        let watcher = FileWatcher::start(PathBuf::from(&actionmaps_path), move |xml| {
+            // Our own applies and the curve watchdog aren't external changes
+            if modification_log::is_own_write(xml) {
+                previous = xml.to_string();
+                return None;
+            }
+
+            let (summary, diff) = summarize_change(&previous, xml);
+            let log_entry = diff.as_ref().map(|diff| {
+                let entry = modification_log::entry_for(&event_path, &previous, xml, diff);
+                if let Err(e) = modification_log::append_entry(&data_dir, &entry) {
+                    error!("Could not record external change: {}", e);
+                }
+                entry
+            });
+            let diff = diff.unwrap_or_default();
+            previous = xml.to_string();
+
+            info!(
+                "actionmaps.xml changed externally: {} option(s), {} binding(s)",
+                summary.options_changed, summary.bindings_changed
+            );
+            let event = ActionmapsChangedEvent {
+                actionmaps_path: event_path.clone(),
+                summary,
+                diff,
+                log_entry,
+            };
+            event_stream::publish("actionmaps-changed", &event);
+            let _ = app_handle.emit("actionmaps-changed", &event);
+
+            match auto_restore_essentials(
+                &event_path,
+                &data_dir,
+                &config_dir,
+                &app_handle,
+                &event.diff,
+            ) {
+                Ok(Some(hash)) => {
+                    // Keep diffing against what's on disk now
+                    if let Ok(restored) = std::fs::read_to_string(&event_path) {
+                        previous = restored;
+                    }
+                    Some(hash)
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    error!("Could not restore essentials: {}", e);
+                    None
+                }
+            }
+        })?;
+
+        Ok(ActionmapsWatcher {
+            path: actionmaps_path,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::essentials::EssentialBinding;
+    use crate::sc_sim::{ScSim, SAMPLE_ACTIONMAPS};
+
+    fn landing_gear() -> Essentials {
+        Essentials {
+            auto_restore: true,
+            bindings: vec![EssentialBinding {
+                action_map: "spaceship_general".to_string(),
+                action: "v_toggle_landing_system".to_string(),
+                input: "js1_button3".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_summary_counts_changes_per_device() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        sim.exit_game();
+
+        let (summary, diff) = summarize_change(SAMPLE_ACTIONMAPS, &sim.read());
+        let diff = diff.unwrap();
+        assert_eq!(summary.options_changed, diff.option_changes.len());
+        assert!(
+            summary.options_changed >= 2,
+            "curve and exponent were dropped"
+        );
+        assert_eq!(summary.bindings_changed, 0);
+        assert_eq!(summary.devices_affected, vec!["joystick 1".to_string()]);
+        assert!(summary.parse_error.is_none());
+
+        sim.wipe();
+        let (summary, _) = summarize_change(SAMPLE_ACTIONMAPS, &sim.read());
+        assert_eq!(summary.bindings_changed, 2);
+        assert_eq!(
+            summary.devices_affected,
+            vec!["joystick 1".to_string(), "joystick 2".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_summary_reports_parse_error() {
+        let half_written = &SAMPLE_ACTIONMAPS[..SAMPLE_ACTIONMAPS.len() / 2];
+        let (summary, diff) = summarize_change(SAMPLE_ACTIONMAPS, half_written);
+
+        assert!(diff.is_none());
+        assert!(summary.parse_error.is_some());
+        assert_eq!(summary.options_changed, 0);
+        assert_eq!(summary.bindings_changed, 0);
+        assert!(summary.devices_affected.is_empty());
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert!(json["parse_error"].is_string());
+        let (summary, _) = summarize_change(SAMPLE_ACTIONMAPS, SAMPLE_ACTIONMAPS);
+        let json = serde_json::to_value(&summary).unwrap();
+        assert!(json.get("parse_error").is_none());
+    }
+
+    #[test]
+    fn test_restore_after_wipe_backs_up_to_the_store() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        let store = backups::resolve_location(None, &sim.dir().join("backups"));
+
+        sim.wipe();
+        let wiped = sim.read();
+        let restored =
+            restore_essentials_to_file(sim.path_str(), sim.dir(), &landing_gear(), &store, &[])
+                .unwrap()
+                .expect("landing gear should be restored");
+
+        assert_eq!(restored.summary.bindings_restored, 1);
+        assert_eq!(restored.written, sim.read());
+        assert!(essentials::check(&sim.read(), &landing_gear())
+            .unwrap()
+            .is_complete());
+        assert_eq!(
+            std::fs::read_to_string(&restored.backup_path).unwrap(),
+            wiped
+        );
+        assert!(backups::list_legacy_backups(Path::new(sim.path_str())).is_empty());
+
+        // Nothing missing any more: no write and no further backup
+        assert!(restore_essentials_to_file(
+            sim.path_str(),
+            sim.dir(),
+            &landing_gear(),
+            &store,
+            &[]
+        )
+        .unwrap()
+        .is_none());
+        assert_eq!(backups::list_backups(Path::new(&store.path)).len(), 1);
+    }
+
+    #[test]
+    fn test_restore_keeps_writes_made_after_detection() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        let store = backups::resolve_location(None, &sim.dir().join("backups"));
+
+        // The watcher saw the wipe, then the game wrote again before the lock was taken
+        sim.wipe();
+        let later = sim.read().replace(
+            "<modifiers />",
+            "<modifiers />\n  <actionmap name=\"spaceship_movement\">\n   <action name=\"v_ifcs_toggle_vector_decoupling\">\n    <rebind input=\"kb1_lalt+v\"/>\n   </action>\n  </actionmap>",
+        );
+        std::fs::write(sim.path_str(), &later).unwrap();
+
+        restore_essentials_to_file(sim.path_str(), sim.dir(), &landing_gear(), &store, &[])
+            .unwrap()
+            .expect("landing gear should be restored");
+        let written = sim.read();
+        assert!(written.contains("kb1_lalt+v"));
+        assert!(essentials::check(&written, &landing_gear())
+            .unwrap()
+            .is_complete());
+    }
+}
@@ -0,0 +1,156 @@
+//! Confirmation before applies that change a lot
+//!
+//! Applying the wrong profile over a heavily customized actionmaps.xml can quietly replace
+//! dozens of tuned options. Before anything is written, the apply is diffed against the
+//! current file; if it changes more options than the configured threshold, or removes any
+//! binding, it's held back and the full summary returned with a confirmation token.
+//! Applying again with that token goes ahead. The token is derived from the file and the
+//! result of the apply, so it only confirms the exact change the user was shown.
+
+use crate::diff::{self, ActionmapsDiff, BindingChange};
+use crate::watcher::content_hash;
+use serde::{Deserialize, Serialize};
+
+/// When an apply needs confirming
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConfirmationPolicy {
+    /// Applies changing more options than this need confirming
+    pub max_option_changes: usize,
+    /// Applies that would remove any binding need confirming
+    pub confirm_binding_removals: bool,
+}
+
+impl Default for ConfirmationPolicy {
+    fn default() -> Self {
+        ConfirmationPolicy {
+            max_option_changes: 25,
+            confirm_binding_removals: true,
+        }
+    }
+}
+
+/// What the caller of an apply agreed to
+#[derive(Debug, Clone, Default)]
+pub struct ApplyConsent {
+    /// Apply even though Star Citizen is running from the same installation
+    pub force: bool,
+    /// Token from a previous ConfirmationRequired, for this exact change
+    pub confirmation_token: Option<String>,
+    pub policy: ConfirmationPolicy,
+}
+
+/// An apply held back until the user confirms it
+#[derive(Debug, Serialize, Clone)]
+pub struct ConfirmationRequired {
+    /// Pass back to apply this change
+    pub token: String,
+    /// e.g. "changes 40 options (more than 25)"
+    pub reasons: Vec<String>,
+    pub summary: ActionmapsDiff,
+}
+
+fn removes_binding(change: &BindingChange) -> bool {
+    change
+        .before
+        .iter()
+        .any(|input| !change.after.contains(input))
+}
+
+/// Why `diff` needs confirming under `policy`; empty when it doesn't
+pub fn reasons(policy: &ConfirmationPolicy, diff: &ActionmapsDiff) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if diff.option_changes.len() > policy.max_option_changes {
+        reasons.push(format!(
+            "changes {} options (more than {})",
+            diff.option_changes.len(),
+            policy.max_option_changes
+        ));
+    }
+    let removals = diff
+        .binding_changes
+        .iter()
+        .filter(|c| removes_binding(c))
+        .count();
+    if policy.confirm_binding_removals && removals > 0 {
+        reasons.push(format!("removes bindings from {} action(s)", removals));
+    }
+    reasons
+}
+
+/// Token confirming the change from `before_xml` to `after_xml`
+pub fn token(before_xml: &str, after_xml: &str) -> String {
+    let mut content = Vec::with_capacity(before_xml.len() + after_xml.len() + 1);
+    content.extend_from_slice(before_xml.as_bytes());
+    content.push(0);
+    content.extend_from_slice(after_xml.as_bytes());
+    format!("{:016x}", content_hash(&content))
+}
+
+/// Hold back the change from `before_xml` to `after_xml` if the policy asks for a
+/// confirmation the caller hasn't given
+pub fn check(
+    consent: &ApplyConsent,
+    before_xml: &str,
+    after_xml: &str,
+) -> Result<Option<ConfirmationRequired>, String> {
+    let summary = diff::diff_actionmaps(before_xml, after_xml)?;
+    let reasons = reasons(&consent.policy, &summary);
+    if reasons.is_empty() {
+        return Ok(None);
+    }
+
+    let token = token(before_xml, after_xml);
+    if consent.confirmation_token.as_deref() == Some(token.as_str()) {
+        return Ok(None);
+    }
+    Ok(Some(ConfirmationRequired {
+        token,
+        reasons,
+        summary,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options_xml(count: usize) -> String {
+        let options: String = (0..count)
+            .map(|i| format!("   <option_{} invert=\"1\"/>\n", i))
+            .collect();
+        format!(
+            "<ActionMaps>\n <ActionProfiles profileName=\"default\">\n  <options type=\"joystick\" instance=\"1\" Product=\"Stick\">\n{}  </options>\n </ActionProfiles>\n</ActionMaps>",
+            options
+        )
+    }
+
+    #[test]
+    fn test_large_apply_needs_confirmation() {
+        let consent = ApplyConsent {
+            policy: ConfirmationPolicy {
+                max_option_changes: 3,
+                confirm_binding_removals: true,
+            },
+            ..Default::default()
+        };
+        let before = options_xml(0);
+
+        assert!(check(&consent, &before, &options_xml(3)).unwrap().is_none());
+
+        let after = options_xml(4);
+        let required = check(&consent, &before, &after).unwrap().unwrap();
+        assert_eq!(required.summary.option_changes.len(), 4);
+        assert_eq!(required.reasons.len(), 1);
+
+        // The token confirms this change only
+        let confirmed = ApplyConsent {
+            confirmation_token: Some(required.token.clone()),
+            ..consent.clone()
+        };
+        assert!(check(&confirmed, &before, &after).unwrap().is_none());
+        assert!(check(&confirmed, &before, &options_xml(5))
+            .unwrap()
+            .is_some());
+    }
+}
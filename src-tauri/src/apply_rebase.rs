@@ -0,0 +1,214 @@
+//! Write merged options to actionmaps.xml, re-basing if the game got there first
+//!
+//! Star Citizen sometimes rewrites actionmaps.xml between our read and our write. Writing
+//! our merge of the stale copy would silently throw away whatever the game just saved.
+//! Instead the file is re-read right before writing; if it changed, the pending options
+//! are merged into the fresh content and we try again. Options the game changed that we
+//! are also setting are reported as conflicts (our value still wins).
+
+use crate::controls::{self, ActionmapsDeviceOptions};
+use crate::diff;
+use crate::modification_log;
+use log::warn;
+use std::time::Duration;
+
+/// How many times to re-base before giving up
+pub const MAX_APPLY_ATTEMPTS: u32 = 3;
+
+/// Give the game a moment to finish writing before re-reading
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub struct RebasedWrite {
+    /// 1 when the file didn't change under us
+    pub attempts: u32,
+    /// Options the game changed mid-apply that we then overwrote, e.g. "joystick 1 flight_move_pitch"
+    pub conflicts: Vec<String>,
+    /// The content that was written
+    pub written: String,
+}
+
+/// Pending options that were also changed between `base` and `current`
+fn overlapping_changes(
+    base: &str,
+    current: &str,
+    pending: &[ActionmapsDeviceOptions],
+) -> Vec<String> {
+    let changes = match diff::diff_actionmaps(base, current) {
+        Ok(diff) => diff.option_changes,
+        // Most likely caught the game mid-write; the retry will compare against the finished file
+        Err(e) => {
+            warn!("Could not diff actionmaps.xml changed during apply: {}", e);
+            return Vec::new();
+        }
+    };
+
+    changes
+        .iter()
+        .filter(|change| {
+            pending.iter().any(|device| {
+                device.device_type == change.device_type
+                    && device.instance == change.instance
+                    && device.options.iter().any(|o| o.name == change.option)
+            })
+        })
+        .map(|change| {
+            format!(
+                "{} {} {}",
+                change.device_type, change.instance, change.option
+            )
+        })
+        .collect()
+}
+
+/// Apply `edit` to `base` (the content the caller read) and write the result to `path`.
+/// If the file changed since, `edit` is re-run on the fresh content and we try again.
+/// `edit` returning `None` means there's nothing to write; the file is left alone.
+/// `on_rebase` is told about each change found under us, as (old base, new content).
+/// Fails if the file keeps changing for MAX_APPLY_ATTEMPTS attempts.
+fn write_loop<E, R>(
+    path: &str,
+    base: String,
+    mut edit: E,
+    mut on_rebase: R,
+) -> Result<Option<(u32, String)>, String>
+where
+    E: FnMut(&str) -> Result<Option<String>, String>,
+    R: FnMut(&str, &str),
+{
+    let mut base = base;
+
+    for attempt in 1..=MAX_APPLY_ATTEMPTS {
+        let Some(new_xml) = edit(&base)? else {
+            return Ok(None);
+        };
+
+        let current = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+        if current == base {
+            modification_log::note_own_write(&new_xml);
+            std::fs::write(path, &new_xml)
+                .map_err(|e| format!("Failed to write actionmaps.xml: {}", e))?;
+            return Ok(Some((attempt, new_xml)));
+        }
+
+        warn!(
+            "actionmaps.xml changed while applying (attempt {} of {}), re-basing",
+            attempt, MAX_APPLY_ATTEMPTS
+        );
+        on_rebase(&base, &current);
+        base = current;
+        std::thread::sleep(RETRY_DELAY);
+    }
+
+    Err(format!(
+        "actionmaps.xml kept changing while applying ({} attempts). Close Star Citizen and try again.",
+        MAX_APPLY_ATTEMPTS
+    ))
+}
+
+/// Merge `pending` into the file at `path` and write it, starting from `base` (the content
+/// the caller read), leaving `excluded` options alone. Fails if the file keeps changing for
+/// MAX_APPLY_ATTEMPTS attempts.
+pub fn write_rebased(
+    path: &str,
+    base: String,
+    pending: Vec<ActionmapsDeviceOptions>,
+    excluded: &[String],
+) -> Result<RebasedWrite, String> {
+    let mut conflicts: Vec<String> = Vec::new();
+
+    let (attempts, written) = write_loop(
+        path,
+        base,
+        |xml| controls::merge_options_into_xml(xml, pending.clone(), excluded).map(Some),
+        |base, current| conflicts.extend(overlapping_changes(base, current, &pending)),
+    )?
+    .ok_or_else(|| "Nothing was written to actionmaps.xml".to_string())?;
+
+    conflicts.sort();
+    conflicts.dedup();
+    Ok(RebasedWrite {
+        attempts,
+        conflicts,
+        written,
+    })
+}
+
+/// Like `write_rebased`, for changes that aren't a plain options merge (e.g. restoring
+/// bindings). `edit` is re-run on the fresh content whenever the file changed under us;
+/// `Ok(None)` from it (or from here) means there was nothing left to write.
+pub fn write_edited<E>(path: &str, base: String, edit: E) -> Result<Option<RebasedWrite>, String>
+where
+    E: FnMut(&str) -> Result<Option<String>, String>,
+{
+    Ok(
+        write_loop(path, base, edit, |_, _| {})?.map(|(attempts, written)| RebasedWrite {
+            attempts,
+            conflicts: Vec::new(),
+            written,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::ActionmapsControlOption;
+    use crate::sc_sim::ScSim;
+
+    fn actionmaps(pitch_invert: &str) -> String {
+        format!(
+            r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="joystick" instance="1" Product="Stick">
+   <flight_move_pitch invert="{}"/>
+   <flight_move_yaw invert="1"/>
+  </options>
+ </ActionProfiles>
+</ActionMaps>"#,
+            pitch_invert
+        )
+    }
+
+    #[test]
+    fn test_rebases_when_the_game_wrote_first() {
+        // We read pitch inverted, then the game saved it not inverted
+        let base = actionmaps("1");
+        let sim = ScSim::new(&actionmaps("0"));
+
+        let pending = vec![ActionmapsDeviceOptions {
+            device_type: "joystick".to_string(),
+            instance: "1".to_string(),
+            product: String::new(),
+            options: vec![ActionmapsControlOption {
+                name: "flight_move_pitch".to_string(),
+                attributes: vec![("invert".to_string(), "1".to_string())],
+                curve_points: Vec::new(),
+                extra_children: Vec::new(),
+            }],
+            extra_attributes: Vec::new(),
+        }];
+        let result = write_rebased(sim.path_str(), base, pending, &[]).unwrap();
+        assert_eq!(result.attempts, 2);
+        assert_eq!(result.conflicts, vec!["joystick 1 flight_move_pitch"]);
+
+        // Our value wins and the option we didn't touch is kept
+        let written = controls::parse_actionmaps_options(&sim.read()).unwrap();
+        let invert = |name: &str| {
+            written[0]
+                .options
+                .iter()
+                .find(|o| o.name == name)
+                .and_then(|o| o.attributes.iter().find(|(k, _)| k == "invert"))
+                .map(|(_, v)| v.clone())
+        };
+        assert_eq!(invert("flight_move_pitch").as_deref(), Some("1"));
+        assert_eq!(invert("flight_move_yaw").as_deref(), Some("1"));
+
+        // Nothing changed under us: written on the first attempt without conflicts
+        let result = write_rebased(sim.path_str(), sim.read(), Vec::new(), &[]).unwrap();
+        assert_eq!(result.attempts, 1);
+        assert!(result.conflicts.is_empty());
+    }
+}
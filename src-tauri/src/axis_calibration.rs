@@ -0,0 +1,516 @@
+//! Axis calibration from live input
+//!
+//! The input monitor already polls every device, so calibration just watches axes
+//! through it for a while and turns what it saw into settings or a diagnosis. Positions
+//! are on the monitor's -1..1 scale, which for anything measured around center is the
+//! same scale SC uses for deadzone (a fraction of the half axis).
+
+use crate::axis_feel::{DEADZONE_MARGIN, MAX_DEADZONE};
+use crate::controls::ControlsFile;
+use crate::curve_convert::round;
+use crate::input_monitor::InputSnapshot;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long an axis is watched when the caller doesn't say
+pub const DEFAULT_IDLE_SECONDS: f64 = 3.0;
+
+/// Longest calibration run we'll do
+const MAX_SECONDS: f64 = 30.0;
+
+/// Time between samples; faster than the monitor polls, so no update is missed
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Watch one axis for `seconds`, returning a position for every sample
+pub fn sample_axis(
+    snapshot: &InputSnapshot,
+    device_uuid: &str,
+    axis_id: u32,
+    seconds: f64,
+) -> Result<Vec<f64>, String> {
+    let duration = Duration::from_secs_f64(seconds.clamp(0.1, MAX_SECONDS));
+    let started = Instant::now();
+    let mut values = Vec::new();
+    while started.elapsed() < duration {
+        // Nothing until the monitor's first poll has reached the device
+        if let Some(value) = snapshot.axis(device_uuid, axis_id) {
+            values.push(value as f64);
+        }
+        thread::sleep(SAMPLE_INTERVAL);
+    }
+    if values.is_empty() {
+        return Err(format!(
+            "Axis {} on device {} didn't report; is the device connected?",
+            axis_id, device_uuid
+        ));
+    }
+    Ok(values)
+}
+
+/// Raw statistics for a run of samples
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AxisStats {
+    pub samples: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub std_dev: f64,
+}
+
+impl AxisStats {
+    pub fn from_values(values: &[f64]) -> Self {
+        let count = values.len().max(1) as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+        AxisStats {
+            samples: values.len(),
+            mean,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            std_dev: variance.sqrt(),
+        }
+    }
+}
+
+/// What an idle axis did and the deadzone that hides it
+#[derive(Debug, Serialize, Clone)]
+pub struct DeadzoneCalibration {
+    pub stats: AxisStats,
+    /// Where the axis rests, relative to the reported center
+    pub center_offset: f64,
+    /// Furthest the axis wandered from where it rests
+    pub noise: f64,
+    pub recommended_deadzone: f64,
+    /// Every sample, for drawing the jitter
+    pub values: Vec<f64>,
+    pub notes: Vec<String>,
+}
+
+/// Recommend a deadzone from samples of an axis nobody was touching. It covers the rest
+/// offset plus the noise around it, with the same margin and cap as the feel presets.
+pub fn calibrate_deadzone(values: Vec<f64>) -> DeadzoneCalibration {
+    let stats = AxisStats::from_values(&values);
+    let center_offset = stats.mean;
+    let noise = (stats.max - center_offset).max(center_offset - stats.min);
+
+    let rest_error = center_offset.abs() + noise;
+    let mut notes = Vec::new();
+    let recommended_deadzone = if rest_error > 0.0 {
+        round((rest_error + DEADZONE_MARGIN).min(MAX_DEADZONE), 3)
+    } else {
+        notes.push("The axis didn't move at all; no deadzone is needed".to_string());
+        0.0
+    };
+    if rest_error + DEADZONE_MARGIN > MAX_DEADZONE {
+        notes.push(format!(
+            "The axis wanders {:.3} at rest, more than a {:.2} deadzone should hide; the stick may need cleaning or recalibration",
+            rest_error, MAX_DEADZONE
+        ));
+    }
+
+    DeadzoneCalibration {
+        center_offset: round(center_offset, 3),
+        noise: round(noise, 3),
+        recommended_deadzone,
+        stats,
+        values,
+        notes,
+    }
+}
+
+/// How long the drift report watches when the caller doesn't say
+pub const DEFAULT_DRIFT_SECONDS: f64 = 30.0;
+
+/// Longest run watching every axis; slow drift can take minutes to show
+const MAX_TRACE_SECONDS: f64 = 600.0;
+
+/// Time between samples for the drift report; drift is slow, so this keeps long runs small
+pub const DRIFT_SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Resting further from center than this is a persistent offset
+const OFFSET_THRESHOLD: f64 = 0.02;
+
+/// Moving further than this over the window, on a steady trend, is drift
+const DRIFT_THRESHOLD: f64 = 0.02;
+
+/// An axis that moved more than this was touched, so it can't be judged
+const TOUCHED_RANGE: f64 = 0.25;
+
+/// Resting further out than this is a throttle, slider or toe brake, not an off-center stick
+const NON_CENTERING_REST: f64 = 0.5;
+
+/// Samples of one axis over time
+#[derive(Debug, Clone)]
+pub struct AxisTrace {
+    pub device_uuid: String,
+    pub device_name: String,
+    pub axis_id: u32,
+    pub axis_name: String,
+    /// (seconds since the start, position)
+    pub samples: Vec<(f64, f64)>,
+}
+
+/// Watch every axis of every device for `seconds`
+pub fn sample_all(snapshot: &InputSnapshot, seconds: f64, interval: Duration) -> Vec<AxisTrace> {
+    let duration = Duration::from_secs_f64(seconds.clamp(1.0, MAX_TRACE_SECONDS));
+    let started = Instant::now();
+    let mut traces: BTreeMap<(String, u32), AxisTrace> = BTreeMap::new();
+    while started.elapsed() < duration {
+        let at = started.elapsed().as_secs_f64();
+        for device in snapshot.devices() {
+            for axis in device.axes {
+                traces
+                    .entry((device.device_uuid.clone(), axis.axis_id))
+                    .or_insert_with(|| AxisTrace {
+                        device_uuid: device.device_uuid.clone(),
+                        device_name: device.device_name.clone(),
+                        axis_id: axis.axis_id,
+                        axis_name: axis.name,
+                        samples: Vec::new(),
+                    })
+                    .samples
+                    .push((at, axis.value as f64));
+            }
+        }
+        thread::sleep(interval);
+    }
+    traces.into_values().collect()
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    /// Rests away from center
+    Offset,
+    /// Keeps moving in one direction
+    Drift,
+    /// Both
+    OffsetAndDrift,
+}
+
+/// An axis that would send input in game while nobody touches it
+#[derive(Debug, Serialize, Clone)]
+pub struct AxisDrift {
+    pub device_uuid: String,
+    pub device_name: String,
+    pub axis_id: u32,
+    pub axis_name: String,
+    pub kind: DriftKind,
+    /// Mean position over the window
+    pub offset: f64,
+    /// How far the trend moved over the window
+    pub drift: f64,
+    /// Deadzone that would hide the axis at rest
+    pub suggested_deadzone: f64,
+    pub steps: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DriftReport {
+    pub seconds: f64,
+    pub axes_checked: usize,
+    /// Axes that moved too much during the window to judge
+    pub axes_touched: Vec<String>,
+    pub findings: Vec<AxisDrift>,
+}
+
+/// Least squares slope of position over time, in units per second
+fn slope(samples: &[(f64, f64)]) -> f64 {
+    let count = samples.len() as f64;
+    let mean_t = samples.iter().map(|s| s.0).sum::<f64>() / count;
+    let mean_v = samples.iter().map(|s| s.1).sum::<f64>() / count;
+    let (covariance, variance) = samples.iter().fold((0.0, 0.0), |(c, v), (t, value)| {
+        (
+            c + (t - mean_t) * (value - mean_v),
+            v + (t - mean_t).powi(2),
+        )
+    });
+    if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    }
+}
+
+/// Find axes with a persistent offset or a slow drift in traces of a device at rest
+pub fn analyze_drift(traces: Vec<AxisTrace>, seconds: f64) -> DriftReport {
+    let mut report = DriftReport {
+        seconds,
+        axes_checked: 0,
+        axes_touched: Vec::new(),
+        findings: Vec::new(),
+    };
+
+    for trace in traces {
+        if trace.samples.len() < 2 {
+            continue;
+        }
+        report.axes_checked += 1;
+        let values: Vec<f64> = trace.samples.iter().map(|s| s.1).collect();
+        let stats = AxisStats::from_values(&values);
+        if stats.max - stats.min > TOUCHED_RANGE {
+            report
+                .axes_touched
+                .push(format!("{} {}", trace.device_name, trace.axis_name));
+            continue;
+        }
+
+        let window = trace.samples[trace.samples.len() - 1].0 - trace.samples[0].0;
+        let drift = slope(&trace.samples) * window;
+        let centering = stats.mean.abs() <= NON_CENTERING_REST;
+        let offset = centering && stats.mean.abs() > OFFSET_THRESHOLD;
+        let drifting = drift.abs() > DRIFT_THRESHOLD;
+        let kind = match (offset, drifting) {
+            (true, true) => DriftKind::OffsetAndDrift,
+            (true, false) => DriftKind::Offset,
+            (false, true) => DriftKind::Drift,
+            (false, false) => continue,
+        };
+
+        let suggested_deadzone = calibrate_deadzone(values).recommended_deadzone;
+        let mut steps = Vec::new();
+        if offset {
+            steps.push(format!(
+                "Set a deadzone of {:.3} on {}, or re-center the device in its calibration tool",
+                suggested_deadzone, trace.axis_name
+            ));
+        }
+        if drifting {
+            steps.push(
+                "The axis keeps moving at rest: recalibrate it in the vendor software or Windows game controller settings, and check the sensor and cable"
+                    .to_string(),
+            );
+            if !centering {
+                steps.push(format!(
+                    "{} rests at {:.2}; a creeping throttle or slider changes speed on its own in game",
+                    trace.axis_name, stats.mean
+                ));
+            }
+        }
+
+        report.findings.push(AxisDrift {
+            device_uuid: trace.device_uuid,
+            device_name: trace.device_name,
+            axis_id: trace.axis_id,
+            axis_name: trace.axis_name,
+            kind,
+            offset: round(stats.mean, 3),
+            drift: round(drift, 3),
+            suggested_deadzone,
+            steps,
+        });
+    }
+    report
+}
+
+/// How long the range pass records when the caller doesn't say
+pub const DEFAULT_RANGE_SECONDS: f64 = 15.0;
+
+/// Time between samples for the range pass; quick flicks to a stop must not be missed
+pub const RANGE_SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Reaching this far counts as full deflection
+const FULL_RANGE_REACH: f64 = 0.98;
+
+/// An axis that moved less than this wasn't part of the pass
+const MOVED_RANGE: f64 = 0.1;
+
+/// What an axis reached during the "move everything to its stops" pass
+#[derive(Debug, Serialize, Clone)]
+pub struct AxisRange {
+    pub device_uuid: String,
+    pub device_name: String,
+    pub axis_id: u32,
+    pub axis_name: String,
+    pub min: f64,
+    pub max: f64,
+    /// How far the shorter side gets, as a fraction of full deflection
+    pub reach: f64,
+    pub full_range: bool,
+    /// Saturation that makes full deflection reachable, when it isn't
+    pub suggested_saturation: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RangeReport {
+    pub axes: Vec<AxisRange>,
+    /// Axes that didn't move during the pass
+    pub axes_not_moved: Vec<String>,
+}
+
+/// Find the axes that didn't reach their stops in traces of a range pass
+pub fn analyze_range(traces: Vec<AxisTrace>) -> RangeReport {
+    let mut report = RangeReport {
+        axes: Vec::new(),
+        axes_not_moved: Vec::new(),
+    };
+    for trace in traces {
+        let values: Vec<f64> = trace.samples.iter().map(|s| s.1).collect();
+        let stats = AxisStats::from_values(&values);
+        if values.is_empty() || stats.max - stats.min < MOVED_RANGE {
+            report
+                .axes_not_moved
+                .push(format!("{} {}", trace.device_name, trace.axis_name));
+            continue;
+        }
+
+        // Both sides share one saturation, so the shorter side decides
+        let reach = stats.max.min(-stats.min).clamp(0.0, 1.0);
+        let full_range = reach >= FULL_RANGE_REACH;
+        report.axes.push(AxisRange {
+            device_uuid: trace.device_uuid,
+            device_name: trace.device_name,
+            axis_id: trace.axis_id,
+            axis_name: trace.axis_name,
+            min: round(stats.min, 3),
+            max: round(stats.max, 3),
+            reach: round(reach, 3),
+            full_range,
+            // Same floor as the feel presets; an axis reaching less is broken, not short
+            suggested_saturation: (!full_range).then(|| round(reach.max(0.5), 3)),
+        });
+    }
+    report
+}
+
+/// A saturation to write to one option of a profile
+#[derive(Debug, Deserialize, Clone)]
+pub struct SaturationUpdate {
+    pub device_type: String,
+    pub instance: String,
+    pub option: String,
+    pub saturation: f64,
+}
+
+/// Write range pass saturations into a profile, returning how many options changed
+pub fn apply_saturation(
+    controls: &mut ControlsFile,
+    updates: &[SaturationUpdate],
+) -> Result<usize, String> {
+    let mut changed = 0;
+    for update in updates {
+        if !(0.0..=1.0).contains(&update.saturation) {
+            return Err(format!(
+                "Saturation {} for {} must be between 0 and 1",
+                update.saturation, update.option
+            ));
+        }
+        let settings = controls
+            .device_mut(&update.device_type, &update.instance)?
+            .options
+            .entry(update.option.clone())
+            .or_default();
+        if settings.saturation != Some(update.saturation) {
+            settings.saturation = Some(update.saturation);
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadzone_covers_offset_and_noise() {
+        let calibration = calibrate_deadzone(vec![0.02, 0.03, 0.01, 0.02, 0.04, 0.0]);
+        assert_eq!(calibration.stats.samples, 6);
+        assert_eq!(calibration.center_offset, 0.02);
+        assert_eq!(calibration.noise, 0.02);
+        assert_eq!(calibration.recommended_deadzone, 0.05);
+        assert!(calibration.notes.is_empty());
+
+        let still = calibrate_deadzone(vec![0.0; 10]);
+        assert_eq!(still.recommended_deadzone, 0.0);
+        assert_eq!(still.stats.std_dev, 0.0);
+
+        let worn = calibrate_deadzone(vec![-0.3, 0.3]);
+        assert_eq!(worn.recommended_deadzone, MAX_DEADZONE);
+        assert_eq!(worn.notes.len(), 1);
+    }
+
+    #[test]
+    fn test_drift_report_flags_offset_and_trend() {
+        let trace = |axis_id: u32, value: &dyn Fn(f64) -> f64| AxisTrace {
+            device_uuid: "stick".to_string(),
+            device_name: "Stick".to_string(),
+            axis_id,
+            axis_name: format!("axis {}", axis_id),
+            samples: (0..=100)
+                .map(|i| {
+                    let t = i as f64 / 10.0;
+                    (t, value(t))
+                })
+                .collect(),
+        };
+        let jitter = |t: f64| {
+            if (t * 10.0) as u32 % 2 == 0 {
+                0.005
+            } else {
+                -0.005
+            }
+        };
+
+        let report = analyze_drift(
+            vec![
+                trace(1, &|t| jitter(t)),
+                trace(2, &|t| 0.05 + jitter(t)),
+                trace(3, &|t| t * 0.006),
+                trace(4, &|_| 0.8),
+                trace(5, &|t| (t / 10.0) * 2.0 - 1.0),
+            ],
+            10.0,
+        );
+        assert_eq!(report.axes_checked, 5);
+        assert_eq!(report.axes_touched, vec!["Stick axis 5".to_string()]);
+
+        let kinds: Vec<(u32, DriftKind)> = report
+            .findings
+            .iter()
+            .map(|finding| (finding.axis_id, finding.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![(2, DriftKind::Offset), (3, DriftKind::OffsetAndDrift)]
+        );
+        assert_eq!(report.findings[0].offset, 0.05);
+        assert!(report.findings[0].suggested_deadzone > 0.05);
+        assert_eq!(report.findings[1].drift, 0.06);
+    }
+
+    #[test]
+    fn test_range_pass_suggests_saturation() {
+        let trace = |axis_id: u32, values: &[f64]| AxisTrace {
+            device_uuid: "stick".to_string(),
+            device_name: "Stick".to_string(),
+            axis_id,
+            axis_name: format!("axis {}", axis_id),
+            samples: values.iter().map(|&v| (0.0, v)).collect(),
+        };
+        let report = analyze_range(vec![
+            trace(1, &[0.0, 1.0, -0.99]),
+            trace(2, &[0.0, 0.9, -0.85, 0.2]),
+            trace(3, &[0.01, 0.02]),
+        ]);
+        assert_eq!(report.axes_not_moved, vec!["Stick axis 3".to_string()]);
+        assert!(report.axes[0].full_range);
+        assert_eq!(report.axes[0].suggested_saturation, None);
+        assert!(!report.axes[1].full_range);
+        assert_eq!(report.axes[1].suggested_saturation, Some(0.85));
+
+        let mut controls = ControlsFile::new("Range".to_string());
+        let update = SaturationUpdate {
+            device_type: "joystick".to_string(),
+            instance: "1".to_string(),
+            option: "flight_move_pitch".to_string(),
+            saturation: 0.85,
+        };
+        assert_eq!(apply_saturation(&mut controls, &[update.clone()]), Ok(1));
+        assert_eq!(apply_saturation(&mut controls, &[update]), Ok(0));
+        let pitch = &controls.device_mut("joystick", "1").unwrap().options["flight_move_pitch"];
+        assert_eq!(pitch.saturation, Some(0.85));
+    }
+}
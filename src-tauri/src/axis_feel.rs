@@ -0,0 +1,190 @@
+//! Axis "feel" presets adapted to the measured device
+//!
+//! The same curve feels very different on a short-throw desk stick with a stiff spring
+//! and on a long-throw floor-mounted one. Rather than applying identical numbers
+//! everywhere, a feel preset describes the intent ("precision center, fast edges") and
+//! is turned into deadzone, saturation and curve points using the device's measured
+//! travel and center behavior.
+
+use crate::controls::{ControlOptionSettings, CurveData, CurveInterpolation, CurvePoint};
+use serde::{Deserialize, Serialize};
+
+/// Throw (center to stop, in degrees) the preset shapes are tuned for
+const REFERENCE_TRAVEL_DEGREES: f64 = 20.0;
+
+/// Extra deadzone on top of the measured center noise so the axis doesn't flicker at rest
+pub const DEADZONE_MARGIN: f64 = 0.01;
+
+/// Largest deadzone we'll suggest; anything more means the stick needs attention, not a curve
+pub const MAX_DEADZONE: f64 = 0.2;
+
+/// Number of curve points written between 0 and 1
+const CURVE_SAMPLES: usize = 8;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeelPreset {
+    Linear,
+    /// Softened center, normal edges
+    Smooth,
+    /// Very fine control near center, quick ramp toward full deflection
+    PrecisionCenterFastEdges,
+    /// More response than linear near center
+    Twitchy,
+}
+
+impl FeelPreset {
+    /// Curve strength on the reference stick, as an exponent (1.0 is linear)
+    fn base_exponent(self) -> f64 {
+        match self {
+            FeelPreset::Linear => 1.0,
+            FeelPreset::Smooth => 1.4,
+            FeelPreset::PrecisionCenterFastEdges => 2.2,
+            FeelPreset::Twitchy => 0.75,
+        }
+    }
+}
+
+/// What calibration measured for one axis, normalized to a half axis (0..1)
+#[derive(Debug, Deserialize, Clone)]
+pub struct AxisMeasurements {
+    /// Furthest deflection the stick actually reaches
+    pub max_deflection: f64,
+    /// How far the value wanders around center when the stick is released
+    #[serde(default)]
+    pub center_noise: f64,
+    /// Where the stick settles when released, relative to the reported center
+    #[serde(default)]
+    pub center_offset: f64,
+    /// Physical throw from center to stop, if known
+    pub travel_degrees: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AdaptedFeel {
+    pub settings: ControlOptionSettings,
+    /// The exponent the curve points were sampled from
+    pub effective_exponent: f64,
+    /// Why the values differ from the plain preset, for display
+    pub notes: Vec<String>,
+}
+
+/// Turn a feel preset into option settings for a specific device's axis
+pub fn adapt(preset: FeelPreset, measurements: &AxisMeasurements) -> AdaptedFeel {
+    let mut notes = Vec::new();
+
+    // A stick that rests off center or wobbles needs that much deadzone before any shaping
+    let rest_error = measurements.center_noise.abs() + measurements.center_offset.abs();
+    let deadzone = if rest_error > 0.0 {
+        (rest_error + DEADZONE_MARGIN).min(MAX_DEADZONE)
+    } else {
+        0.0
+    };
+    if deadzone > 0.0 {
+        notes.push(format!(
+            "Deadzone {:.3} covers the measured center wander",
+            deadzone
+        ));
+    }
+    if rest_error + DEADZONE_MARGIN > MAX_DEADZONE {
+        notes.push(
+            "Center wander is unusually large; the stick may need cleaning or recalibration"
+                .to_string(),
+        );
+    }
+
+    // Stop short of the range when the stick can't reach full deflection
+    let saturation = measurements.max_deflection.clamp(0.5, 1.0);
+    if saturation < 1.0 {
+        notes.push(format!(
+            "Saturation {:.3} so full deflection is reachable",
+            saturation
+        ));
+    }
+
+    // Longer throw already gives more physical resolution near center, so the same feel
+    // needs a weaker curve; a short throw needs a stronger one
+    let base = preset.base_exponent();
+    let effective_exponent = match measurements.travel_degrees {
+        Some(travel) if travel > 0.0 => {
+            let ratio = (travel / REFERENCE_TRAVEL_DEGREES).clamp(0.5, 2.0);
+            let exponent = 1.0 + (base - 1.0) / ratio;
+            if (exponent - base).abs() > 0.01 {
+                notes.push(format!(
+                    "Curve strength adjusted for {:.0} degree throw",
+                    travel
+                ));
+            }
+            exponent
+        }
+        _ => base,
+    };
+
+    let points = (1..CURVE_SAMPLES)
+        .map(|i| {
+            let x = i as f64 / CURVE_SAMPLES as f64;
+            CurvePoint {
+                input: x,
+                output: (x.powf(effective_exponent) * 1000.0).round() / 1000.0,
+            }
+        })
+        .collect();
+
+    let settings = ControlOptionSettings {
+        deadzone: (deadzone > 0.0).then_some((deadzone * 1000.0).round() / 1000.0),
+        saturation: (saturation < 1.0).then_some((saturation * 1000.0).round() / 1000.0),
+        curve_mode: Some("curve".to_string()),
+        curve: Some(CurveData {
+            points,
+            interpolation: CurveInterpolation::Linear,
+        }),
+        ..Default::default()
+    };
+
+    AdaptedFeel {
+        settings,
+        effective_exponent,
+        notes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurements(noise: f64, offset: f64, travel: Option<f64>) -> AxisMeasurements {
+        AxisMeasurements {
+            max_deflection: 0.9,
+            center_noise: noise,
+            center_offset: offset,
+            travel_degrees: travel,
+        }
+    }
+
+    #[test]
+    fn test_adapt_to_measured_axis() {
+        // Deadzone covers the wander plus the margin, saturation the reachable range
+        let linear = adapt(FeelPreset::Linear, &measurements(0.02, -0.01, None));
+        assert_eq!(linear.settings.deadzone, Some(0.04));
+        assert_eq!(linear.settings.saturation, Some(0.9));
+        assert_eq!(linear.effective_exponent, 1.0);
+        let points = &linear.settings.curve.as_ref().unwrap().points;
+        assert_eq!(points.len(), CURVE_SAMPLES - 1);
+        assert_eq!((points[3].input, points[3].output), (0.5, 0.5));
+
+        // A long throw softens the curve
+        let precise = adapt(
+            FeelPreset::PrecisionCenterFastEdges,
+            &measurements(0.0, 0.0, Some(40.0)),
+        );
+        assert_eq!(precise.settings.deadzone, None);
+        assert!((precise.effective_exponent - 1.6).abs() < 1e-9);
+        assert!(precise.settings.curve.unwrap().points[3].output < 0.5);
+        assert!(precise.notes.iter().any(|n| n.contains("40 degree")));
+
+        // Wander beyond what a deadzone should hide is capped and flagged
+        let worn = adapt(FeelPreset::Smooth, &measurements(0.3, 0.0, None));
+        assert_eq!(worn.settings.deadzone, Some(MAX_DEADZONE));
+        assert!(worn.notes.iter().any(|n| n.contains("unusually large")));
+    }
+}
@@ -0,0 +1,296 @@
+//! Backup store for actionmaps.xml
+//!
+//! Backups taken before we modify the game's files go into a single store directory.
+//! By default that's inside the app data directory, but users can point it anywhere
+//! (a second drive, a NAS share). External locations can disappear, so every use
+//! checks availability first and falls back to the local default rather than failing
+//! the operation that needed the backup.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// SC environment folders, used to tell LIVE and PTU backups apart in a shared store
+const SC_ENVIRONMENTS: [&str; 4] = ["LIVE", "PTU", "EPTU", "TECH-PREVIEW"];
+
+/// Marker every backup file name contains, used to find backups when migrating
+const BACKUP_MARKER: &str = ".backup.";
+
+/// Where backups are actually going right now
+#[derive(Debug, Serialize, Clone)]
+pub struct BackupLocation {
+    /// The directory in use
+    pub path: String,
+    /// The directory the user configured, if any
+    pub configured_path: Option<String>,
+    /// True when the configured directory is unreachable and we fell back to the default
+    pub using_fallback: bool,
+    /// Why the configured directory couldn't be used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unavailable_reason: Option<String>,
+}
+
+/// Check a directory exists (creating it and any missing parents) and that we can write to it
+pub fn check_available(dir: &Path) -> Result<(), String> {
+    if !dir.exists() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    }
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+
+    let probe = dir.join(".boxxy-write-test");
+    std::fs::write(&probe, b"ok")
+        .map_err(|e| format!("{} is not writable: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Pick the backup directory: the configured one when reachable, otherwise the default
+pub fn resolve_location(configured: Option<&str>, default_dir: &Path) -> BackupLocation {
+    let fallback = |reason: Option<String>| BackupLocation {
+        path: default_dir.to_string_lossy().to_string(),
+        configured_path: configured.map(str::to_string),
+        using_fallback: reason.is_some(),
+        unavailable_reason: reason,
+    };
+
+    let Some(configured) = configured.filter(|p| !p.trim().is_empty()) else {
+        return fallback(None);
+    };
+
+    match check_available(Path::new(configured)) {
+        Ok(()) => BackupLocation {
+            path: configured.to_string(),
+            configured_path: Some(configured.to_string()),
+            using_fallback: false,
+            unavailable_reason: None,
+        },
+        Err(reason) => {
+            log::warn!(
+                "Backup location {} unavailable, using {}: {}",
+                configured,
+                default_dir.display(),
+                reason
+            );
+            fallback(Some(reason))
+        }
+    }
+}
+
+/// The SC environment a file sits in, if any
+fn source_environment(source: &Path) -> Option<String> {
+    source
+        .ancestors()
+        .filter_map(|a| a.file_name())
+        .map(|n| n.to_string_lossy().to_uppercase())
+        .find(|n| SC_ENVIRONMENTS.contains(&n.as_str()))
+}
+
+/// Backup file name, prefixed with the SC environment when the source is inside one
+fn backup_file_name(source: &Path) -> String {
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "actionmaps.xml".to_string());
+
+    let environment = source_environment(source);
+
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    match environment {
+        Some(env) => format!("{}_{}{}{}", env, file_name, BACKUP_MARKER, timestamp),
+        None => format!("{}{}{}", file_name, BACKUP_MARKER, timestamp),
+    }
+}
+
+/// Copy a file into the backup store, returning the backup's path
+pub fn create_backup(location: &BackupLocation, source: &str) -> Result<String, String> {
+    let dir = Path::new(&location.path);
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+
+    let backup_path = dir.join(backup_file_name(Path::new(source)));
+    std::fs::copy(source, &backup_path).map_err(|e| format!("Failed to create backup: {}", e))?;
+    tracing::info!(source, backup = %backup_path.display(), "Created backup");
+
+    Ok(backup_path.to_string_lossy().to_string())
+}
+
+/// A backup in the store
+#[derive(Debug, Serialize, Clone)]
+pub struct BackupEntry {
+    pub path: String,
+    pub file_name: String,
+    /// SC environment the backed up file came from, when known
+    pub environment: Option<String>,
+    /// ISO timestamp of the backup
+    pub created_at: Option<String>,
+    pub size: u64,
+}
+
+fn backup_entry(path: &Path) -> Option<BackupEntry> {
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    if !path.is_file() || !file_name.contains(BACKUP_MARKER) {
+        return None;
+    }
+    let metadata = std::fs::metadata(path).ok()?;
+    let environment = file_name
+        .split_once('_')
+        .map(|(prefix, _)| prefix.to_string())
+        .filter(|prefix| SC_ENVIRONMENTS.contains(&prefix.as_str()));
+    Some(BackupEntry {
+        path: path.to_string_lossy().to_string(),
+        environment,
+        created_at: metadata
+            .modified()
+            .ok()
+            .map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339()),
+        size: metadata.len(),
+        file_name,
+    })
+}
+
+/// Sort backups newest first
+pub fn sort_newest_first(backups: &mut [BackupEntry]) {
+    // RFC 3339 timestamps in the same offset sort chronologically
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+}
+
+/// Backups in `dir`, newest first
+pub fn list_backups(dir: &Path) -> Vec<BackupEntry> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<BackupEntry> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| backup_entry(&entry.path()))
+        .collect();
+    sort_newest_first(&mut backups);
+    backups
+}
+
+/// Backups older versions (including their curve watchdog and essentials auto-restore)
+/// left next to actionmaps.xml as `actionmaps.xml.backup.*`, newest first
+pub fn list_legacy_backups(actionmaps_path: &Path) -> Vec<BackupEntry> {
+    let (Some(dir), Some(file_name)) = (actionmaps_path.parent(), actionmaps_path.file_name())
+    else {
+        return Vec::new();
+    };
+    let prefix = format!("{}{}", file_name.to_string_lossy(), BACKUP_MARKER);
+    let environment = source_environment(actionmaps_path);
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<BackupEntry> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|entry| backup_entry(&entry.path()))
+        .map(|backup| BackupEntry {
+            environment: backup.environment.clone().or_else(|| environment.clone()),
+            ..backup
+        })
+        .collect();
+    sort_newest_first(&mut backups);
+    backups
+}
+
+/// Move every backup from one store to another. Copies then deletes, since the two
+/// directories are often on different drives. Returns the number of backups moved.
+pub fn migrate_backups(from: &Path, to: &Path) -> Result<usize, String> {
+    if !from.is_dir() || from == to {
+        return Ok(0);
+    }
+    check_available(to)?;
+
+    let entries =
+        std::fs::read_dir(from).map_err(|e| format!("Failed to read backup directory: {}", e))?;
+
+    let mut moved = 0;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let is_backup = path.is_file()
+            && path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().contains(BACKUP_MARKER));
+        if !is_backup {
+            continue;
+        }
+
+        let target: PathBuf = to.join(entry.file_name());
+        std::fs::copy(&path, &target)
+            .map_err(|e| format!("Failed to copy {}: {}", path.display(), e))?;
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        moved += 1;
+    }
+
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::TempDir;
+
+    #[test]
+    fn test_backups_and_location() {
+        let temp = TempDir::new("backups");
+        let root = temp.path();
+        let default_dir = root.join("default");
+
+        // Nothing configured: the default, without a fallback warning
+        let location = resolve_location(None, &default_dir);
+        assert_eq!(location.path, default_dir.to_string_lossy());
+        assert!(!location.using_fallback);
+
+        // A configured directory is created when missing
+        let configured = root.join("nas").join("backups");
+        let location = resolve_location(configured.to_str(), &default_dir);
+        assert_eq!(location.path, configured.to_string_lossy());
+        assert!(configured.is_dir());
+
+        // One that can't be used falls back to the default and says why
+        let not_a_dir = root.join("file");
+        std::fs::write(&not_a_dir, "x").unwrap();
+        let fallback = resolve_location(not_a_dir.to_str(), &default_dir);
+        assert_eq!(fallback.path, default_dir.to_string_lossy());
+        assert!(fallback.using_fallback);
+        assert!(fallback.unavailable_reason.is_some());
+
+        // Backups of a LIVE actionmaps.xml are named and listed with their environment
+        let profile_dir = root.join("LIVE").join("user").join("client");
+        std::fs::create_dir_all(&profile_dir).unwrap();
+        let actionmaps = profile_dir.join("actionmaps.xml");
+        std::fs::write(&actionmaps, "<ActionMaps/>").unwrap();
+
+        let backup = create_backup(&location, &actionmaps.to_string_lossy()).unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "<ActionMaps/>");
+        let listed = list_backups(&configured);
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0]
+            .file_name
+            .starts_with("LIVE_actionmaps.xml.backup."));
+        assert_eq!(listed[0].environment.as_deref(), Some("LIVE"));
+
+        // Backups left next to actionmaps.xml are found too, other files aren't
+        std::fs::write(profile_dir.join("actionmaps.xml.backup.essentials"), "a").unwrap();
+        std::fs::write(profile_dir.join("actionmaps.xml.backup.curve_ab"), "b").unwrap();
+        std::fs::write(profile_dir.join("attributes.xml"), "c").unwrap();
+        let mut legacy: Vec<String> = list_legacy_backups(&actionmaps)
+            .into_iter()
+            .map(|b| {
+                assert_eq!(b.environment.as_deref(), Some("LIVE"));
+                b.file_name
+            })
+            .collect();
+        legacy.sort();
+        assert_eq!(
+            legacy,
+            vec![
+                "actionmaps.xml.backup.curve_ab",
+                "actionmaps.xml.backup.essentials"
+            ]
+        );
+    }
+}
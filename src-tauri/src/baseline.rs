@@ -0,0 +1,369 @@
+//! Squadron baseline: a shared profile applied underneath the user's own
+//!
+//! Squadrons and orgs often agree on a common setup (inversion, deadzones, curves for
+//! the hardware most members fly). The baseline is a .sccontrols file from a shared
+//! source, usually a network drive or a synced folder. Every apply composes it first and
+//! the applied profile on top, so members keep their personal tweaks. The source is
+//! re-read on each apply; when it has changed, the previous baseline is kept so the
+//! update can be shown as a diff.
+
+use crate::controls::{self, ControlOptionSettings, ControlsFile, DeviceInstanceSettings};
+use crate::diff::{self, OptionChange};
+use crate::watcher::FileWatcher;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+/// Directory inside the app data directory holding the baseline and its state
+pub const BASELINE_DIR_NAME: &str = "baseline";
+
+const STATE_FILE_NAME: &str = "baseline.json";
+const CURRENT_FILE_NAME: &str = "current.sccontrols";
+const PREVIOUS_FILE_NAME: &str = "previous.sccontrols";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BaselineState {
+    /// Path of the shared .sccontrols file
+    pub source: Option<String>,
+    /// Compose the baseline into applies
+    pub enabled: bool,
+    /// ISO timestamp of the last time the local copy changed
+    pub updated_at: Option<String>,
+}
+
+/// What the frontend shows about the baseline
+#[derive(Debug, Serialize, Clone)]
+pub struct BaselineStatus {
+    pub source: Option<String>,
+    pub enabled: bool,
+    pub profile_name: Option<String>,
+    pub updated_at: Option<String>,
+    /// Changes in the latest update, against the baseline it replaced
+    pub changes: Vec<OptionChange>,
+}
+
+fn baseline_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(BASELINE_DIR_NAME)
+}
+
+fn load_state(dir: &Path) -> Result<BaselineState, String> {
+    let path = dir.join(STATE_FILE_NAME);
+    if !path.exists() {
+        return Ok(BaselineState::default());
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read baseline state: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse baseline state: {}", e))
+}
+
+fn save_state(dir: &Path, state: &BaselineState) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create baseline directory: {}", e))?;
+    let json = serde_json::to_string_pretty(state)
+        .map_err(|e| format!("Failed to serialize baseline state: {}", e))?;
+    std::fs::write(dir.join(STATE_FILE_NAME), json)
+        .map_err(|e| format!("Failed to write baseline state: {}", e))
+}
+
+fn load_copy(dir: &Path, file_name: &str) -> Result<Option<ControlsFile>, String> {
+    let path = dir.join(file_name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read baseline: {}", e))?;
+    ControlsFile::from_json(&json).map(Some)
+}
+
+/// Read the shared file, checking it is a profile before it replaces anything
+fn read_source(source: &str) -> Result<String, String> {
+    let json = std::fs::read_to_string(source)
+        .map_err(|e| format!("Failed to read baseline {}: {}", source, e))?;
+    ControlsFile::from_json(&json)?;
+    Ok(json)
+}
+
+/// Use a new shared source. The previous baseline, if any, is kept for the diff.
+pub fn set_source(data_dir: &Path, source: &str) -> Result<BaselineStatus, String> {
+    let dir = baseline_dir(data_dir);
+    let json = read_source(source)?;
+
+    let mut state = load_state(&dir)?;
+    state.source = Some(source.to_string());
+    state.enabled = true;
+    store_update(&dir, &mut state, &json)?;
+    status(data_dir)
+}
+
+pub fn set_enabled(data_dir: &Path, enabled: bool) -> Result<(), String> {
+    let dir = baseline_dir(data_dir);
+    let mut state = load_state(&dir)?;
+    if enabled && state.source.is_none() {
+        return Err("No baseline has been imported yet".to_string());
+    }
+    state.enabled = enabled;
+    save_state(&dir, &state)
+}
+
+/// Forget the baseline entirely
+pub fn clear(data_dir: &Path) -> Result<(), String> {
+    let dir = baseline_dir(data_dir);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove baseline: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Rotate the local copy: current becomes previous, `json` becomes current
+fn store_update(dir: &Path, state: &mut BaselineState, json: &str) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create baseline directory: {}", e))?;
+    let current = dir.join(CURRENT_FILE_NAME);
+    if current.exists() {
+        std::fs::rename(&current, dir.join(PREVIOUS_FILE_NAME))
+            .map_err(|e| format!("Failed to keep previous baseline: {}", e))?;
+    }
+    std::fs::write(&current, json).map_err(|e| format!("Failed to write baseline: {}", e))?;
+    state.updated_at = Some(chrono::Local::now().to_rfc3339());
+    save_state(dir, state)
+}
+
+/// Pick up changes to the shared file. Returns the changes when it was updated; an
+/// unreachable source (e.g. the network drive is offline) keeps the local copy.
+pub fn refresh(data_dir: &Path) -> Result<Option<Vec<OptionChange>>, String> {
+    let dir = baseline_dir(data_dir);
+    let mut state = load_state(&dir)?;
+    let Some(source) = state.source.clone() else {
+        return Ok(None);
+    };
+
+    let json = match read_source(&source) {
+        Ok(json) => json,
+        Err(e) => {
+            info!("Using the local baseline copy: {}", e);
+            return Ok(None);
+        }
+    };
+    let current = std::fs::read_to_string(dir.join(CURRENT_FILE_NAME)).unwrap_or_default();
+    if json == current {
+        return Ok(None);
+    }
+
+    store_update(&dir, &mut state, &json)?;
+    let changes = latest_changes(&dir)?;
+    info!(
+        "Baseline {} updated, {} option change(s)",
+        source,
+        changes.len()
+    );
+    Ok(Some(changes))
+}
+
+/// Option changes between the previous and the current baseline
+fn latest_changes(dir: &Path) -> Result<Vec<OptionChange>, String> {
+    let (Some(previous), Some(current)) = (
+        load_copy(dir, PREVIOUS_FILE_NAME)?,
+        load_copy(dir, CURRENT_FILE_NAME)?,
+    ) else {
+        return Ok(Vec::new());
+    };
+    Ok(diff::diff_device_options(
+        controls::controls_to_actionmaps(&previous, true),
+        controls::controls_to_actionmaps(&current, true),
+    ))
+}
+
+pub fn status(data_dir: &Path) -> Result<BaselineStatus, String> {
+    let dir = baseline_dir(data_dir);
+    let state = load_state(&dir)?;
+    Ok(BaselineStatus {
+        profile_name: load_copy(&dir, CURRENT_FILE_NAME)?.map(|b| b.profile_name),
+        changes: latest_changes(&dir)?,
+        source: state.source,
+        enabled: state.enabled,
+        updated_at: state.updated_at,
+    })
+}
+
+/// Emitted as "baseline-updated" when the watcher picks up a change to the shared file
+#[derive(Debug, Serialize, Clone)]
+pub struct BaselineUpdatedEvent {
+    pub source: String,
+    pub changes: Vec<OptionChange>,
+}
+
+/// Watch the shared file so updates show up (and are composed into the next apply)
+/// without waiting for an apply. Returns None when there is no enabled baseline.
+pub fn watch(data_dir: &Path, app_handle: AppHandle) -> Result<Option<FileWatcher>, String> {
+    let state = load_state(&baseline_dir(data_dir))?;
+    let Some(source) = state.source.filter(|_| state.enabled) else {
+        return Ok(None);
+    };
+
+    let data_dir = data_dir.to_path_buf();
+    let event_source = source.clone();
+    let watcher = FileWatcher::start(PathBuf::from(&source), move |_| {
+        match refresh(&data_dir) {
+            Ok(Some(changes)) => {
+                let _ = app_handle.emit(
+                    "baseline-updated",
+                    BaselineUpdatedEvent {
+                        source: event_source.clone(),
+                        changes,
+                    },
+                );
+            }
+            Ok(None) => {}
+            Err(e) => error!("Could not update the baseline: {}", e),
+        }
+        None
+    })?;
+    info!("Watching baseline {}", source);
+    Ok(Some(watcher))
+}
+
+/// Overlay one option on another field by field. Curve settings go together, so a
+/// personal exponent isn't mixed with the baseline's curve points.
+fn overlay_option(
+    base: &ControlOptionSettings,
+    overlay: &ControlOptionSettings,
+) -> ControlOptionSettings {
+    let mut composed = overlay.clone();
+    composed.invert = overlay.invert.or(base.invert);
+    composed.deadzone = overlay.deadzone.or(base.deadzone);
+    composed.saturation = overlay.saturation.or(base.saturation);
+    composed.sensitivity = overlay.sensitivity.or(base.sensitivity);
+    composed.smoothing = overlay.smoothing.or(base.smoothing);
+    if overlay.curve_mode.is_none() && overlay.exponent.is_none() && overlay.curve.is_none() {
+        composed.curve_mode = base.curve_mode.clone();
+        composed.exponent = base.exponent;
+        composed.curve = base.curve.clone();
+    }
+    for (key, value) in &base.extra {
+        composed.extra.entry(key.clone()).or_insert(value.clone());
+    }
+    composed
+}
+
+fn overlay_device(base: &DeviceInstanceSettings, overlay: &mut DeviceInstanceSettings) {
+    if overlay.product.is_none() {
+        overlay.product = base.product.clone();
+    }
+    for (name, settings) in &base.options {
+        let composed = match overlay.options.get(name) {
+            Some(own) => overlay_option(settings, own),
+            None => settings.clone(),
+        };
+        overlay.options.insert(name.clone(), composed);
+    }
+}
+
+/// The baseline with `profile` on top. The result keeps the profile's name and metadata.
+pub fn compose(baseline: &ControlsFile, profile: &ControlsFile) -> ControlsFile {
+    let mut composed = profile.clone();
+    if let Some(base) = &baseline.devices.keyboard {
+        overlay_device(
+            base,
+            composed
+                .devices
+                .keyboard
+                .get_or_insert_with(Default::default),
+        );
+    }
+    if let Some(base) = &baseline.devices.mouse {
+        overlay_device(
+            base,
+            composed.devices.mouse.get_or_insert_with(Default::default),
+        );
+    }
+    if let Some(base) = &baseline.devices.gamepad {
+        overlay_device(
+            base,
+            composed
+                .devices
+                .gamepad
+                .get_or_insert_with(Default::default),
+        );
+    }
+    for (instance, base) in baseline.devices.joystick.iter().flatten() {
+        let joysticks = composed
+            .devices
+            .joystick
+            .get_or_insert_with(Default::default);
+        overlay_device(base, joysticks.entry(instance.clone()).or_default());
+    }
+    composed
+}
+
+/// The baseline applies compose under, when one is enabled, picking up any update to the
+/// shared file first
+pub fn active(data_dir: &Path) -> Result<Option<ControlsFile>, String> {
+    let dir = baseline_dir(data_dir);
+    if !load_state(&dir)?.enabled {
+        return Ok(None);
+    }
+    if let Err(e) = refresh(data_dir) {
+        error!("Could not update the baseline: {}", e);
+    }
+    load_copy(&dir, CURRENT_FILE_NAME)
+}
+
+/// What an apply of `profile` should write: the profile composed over the baseline when
+/// one is enabled
+pub fn compose_for_apply(data_dir: &Path, profile: &ControlsFile) -> Result<ControlsFile, String> {
+    match active(data_dir)? {
+        Some(baseline) => {
+            info!(
+                "Composing {} over baseline {}",
+                profile.profile_name, baseline.profile_name
+            );
+            Ok(compose(&baseline, profile))
+        }
+        None => Ok(profile.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(invert: Option<bool>, deadzone: Option<f64>) -> ControlOptionSettings {
+        ControlOptionSettings {
+            invert,
+            deadzone,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_personal_settings_win_over_baseline() {
+        let mut baseline = ControlsFile::new("Squadron".to_string());
+        let stick = baseline.device_mut("joystick", "1").unwrap();
+        stick.product = Some("Squadron stick".to_string());
+        stick.options.insert(
+            "flight_move_pitch".to_string(),
+            option(Some(true), Some(0.05)),
+        );
+        stick
+            .options
+            .insert("flight_move_yaw".to_string(), option(None, Some(0.1)));
+
+        let mut personal = ControlsFile::new("Mine".to_string());
+        personal
+            .device_mut("joystick", "1")
+            .unwrap()
+            .options
+            .insert("flight_move_pitch".to_string(), option(None, Some(0.02)));
+
+        let composed = compose(&baseline, &personal);
+        assert_eq!(composed.profile_name, "Mine");
+        let stick = composed.device("joystick", "1").unwrap();
+        assert_eq!(stick.product.as_deref(), Some("Squadron stick"));
+        let pitch = &stick.options["flight_move_pitch"];
+        assert_eq!(pitch.invert, Some(true));
+        assert_eq!(pitch.deadzone, Some(0.02));
+        assert_eq!(stick.options["flight_move_yaw"].deadzone, Some(0.1));
+    }
+}
@@ -0,0 +1,228 @@
+//! Bindings known to cause trouble
+//!
+//! Some keys never reach the action they're bound to, or do something else as well: the
+//! game keeps Escape and the console key for itself, Windows acts on Alt+F4 and Alt+Tab
+//! before the game sees them, and lock keys change state as they're pressed. Each rule
+//! below names such a key, the modifiers it needs and how bad a binding on it is, so
+//! the bindings pages can highlight them when a file is loaded or a binding edited.
+
+use crate::binding_string::{BindingInput, Modifier};
+use crate::keybindings::{ActionMaps, InputType};
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Works, but something else may react to the key too
+    Info,
+    /// Likely to misbehave, e.g. switching away from the game
+    Warning,
+    /// The action won't fire, or the key does something drastic (closing the game)
+    Error,
+}
+
+/// A modifier held on either side of the keyboard
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Held {
+    Ctrl,
+    Shift,
+    Alt,
+}
+
+impl Held {
+    fn of(modifier: Modifier) -> Held {
+        match modifier {
+            Modifier::LCtrl | Modifier::RCtrl => Held::Ctrl,
+            Modifier::LShift | Modifier::RShift => Held::Shift,
+            Modifier::LAlt | Modifier::RAlt => Held::Alt,
+        }
+    }
+}
+
+struct Rule {
+    /// SC keyboard key name
+    key: &'static str,
+    held: &'static [Held],
+    /// Also matches with other modifiers held, e.g. Alt+Shift+Tab for Alt+Tab
+    any_modifiers: bool,
+    severity: Severity,
+    reason: &'static str,
+}
+
+const RULES: &[Rule] = &[
+    Rule {
+        key: "escape",
+        held: &[],
+        any_modifiers: true,
+        severity: Severity::Error,
+        reason: "Escape opens the game menu and can't be rebound",
+    },
+    Rule {
+        key: "grave",
+        held: &[],
+        any_modifiers: true,
+        severity: Severity::Error,
+        reason: "The console key opens the game console",
+    },
+    Rule {
+        key: "f4",
+        held: &[Held::Alt],
+        any_modifiers: true,
+        severity: Severity::Error,
+        reason: "Alt+F4 closes the game",
+    },
+    Rule {
+        key: "delete",
+        held: &[Held::Ctrl, Held::Alt],
+        any_modifiers: true,
+        severity: Severity::Error,
+        reason: "Ctrl+Alt+Delete opens the Windows security screen",
+    },
+    Rule {
+        key: "tab",
+        held: &[Held::Alt],
+        any_modifiers: true,
+        severity: Severity::Warning,
+        reason: "Alt+Tab switches to another window",
+    },
+    Rule {
+        key: "enter",
+        held: &[Held::Alt],
+        any_modifiers: false,
+        severity: Severity::Warning,
+        reason: "Alt+Enter toggles fullscreen",
+    },
+    Rule {
+        key: "space",
+        held: &[Held::Alt],
+        any_modifiers: false,
+        severity: Severity::Warning,
+        reason: "Alt+Space opens the window menu, and PowerToys Run if installed",
+    },
+    Rule {
+        key: "print",
+        held: &[],
+        any_modifiers: true,
+        severity: Severity::Info,
+        reason: "Print Screen is also taken by Windows and capture tools (Steam, Game Bar)",
+    },
+    Rule {
+        key: "numlock",
+        held: &[],
+        any_modifiers: true,
+        severity: Severity::Info,
+        reason: "Num Lock also changes what the numpad keys send",
+    },
+    Rule {
+        key: "capslock",
+        held: &[],
+        any_modifiers: true,
+        severity: Severity::Info,
+        reason: "Caps Lock also toggles capitals in chat",
+    },
+    Rule {
+        key: "scrolllock",
+        held: &[],
+        any_modifiers: true,
+        severity: Severity::Info,
+        reason: "Scroll Lock also toggles its lock state",
+    },
+];
+
+impl Rule {
+    fn matches(&self, input: &BindingInput) -> bool {
+        if input.device != InputType::Keyboard || input.key != self.key {
+            return false;
+        }
+        let held: Vec<Held> = input.modifiers.iter().map(|m| Held::of(*m)).collect();
+        let has_all = self.held.iter().all(|h| held.contains(h));
+        has_all && (self.any_modifiers || held.iter().all(|h| self.held.contains(h)))
+    }
+}
+
+/// A binding one of the rules flags
+#[derive(Debug, Serialize, Clone)]
+pub struct BindingWarning {
+    pub severity: Severity,
+    pub action_map: String,
+    pub action: String,
+    pub input: String,
+    pub message: String,
+}
+
+/// What the rules have to say about one binding string
+pub fn check_input(input: &str, multi_tap: Option<u32>) -> Vec<(Severity, &'static str)> {
+    let Ok(input) = BindingInput::parse(input, multi_tap) else {
+        return Vec::new();
+    };
+    RULES
+        .iter()
+        .filter(|rule| rule.matches(&input))
+        .map(|rule| (rule.severity, rule.reason))
+        .collect()
+}
+
+/// Warnings for binding `input` to an action
+pub fn check_rebind(
+    action_map: &str,
+    action: &str,
+    input: &str,
+    multi_tap: Option<u32>,
+) -> Vec<BindingWarning> {
+    check_input(input, multi_tap)
+        .into_iter()
+        .map(|(severity, reason)| BindingWarning {
+            severity,
+            action_map: action_map.to_string(),
+            action: action.to_string(),
+            input: input.to_string(),
+            message: reason.to_string(),
+        })
+        .collect()
+}
+
+/// Warnings for every rebind in `bindings`, most severe first
+pub fn check_bindings(bindings: &ActionMaps) -> Vec<BindingWarning> {
+    let mut warnings: Vec<BindingWarning> = bindings
+        .action_maps
+        .iter()
+        .flat_map(|map| {
+            map.actions.iter().flat_map(move |action| {
+                action.rebinds.iter().flat_map(move |rebind| {
+                    check_rebind(&map.name, &action.name, &rebind.input, rebind.multi_tap)
+                })
+            })
+        })
+        .collect();
+    warnings.sort_by(|a, b| b.severity.cmp(&a.severity));
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn severities(input: &str) -> Vec<Severity> {
+        check_input(input, None)
+            .into_iter()
+            .map(|(severity, _)| severity)
+            .collect()
+    }
+
+    #[test]
+    fn test_rules_match_modifiers() {
+        assert_eq!(severities("kb1_lalt+f4"), vec![Severity::Error]);
+        assert_eq!(severities("kb1_f4+ralt"), vec![Severity::Error]);
+        assert!(severities("kb1_f4").is_empty());
+        assert_eq!(severities("kb1_lshift+lalt+tab"), vec![Severity::Warning]);
+        assert_eq!(severities("kb1_ralt+enter"), vec![Severity::Warning]);
+        // Alt+Enter only toggles fullscreen on its own
+        assert!(severities("kb1_lctrl+lalt+enter").is_empty());
+        assert_eq!(severities("kb1_rctrl+lalt+delete"), vec![Severity::Error]);
+        assert!(severities("kb1_lctrl+delete").is_empty());
+        assert_eq!(severities("kb1_grave"), vec![Severity::Error]);
+        assert_eq!(severities("kb1_print"), vec![Severity::Info]);
+        assert!(severities("js1_button4").is_empty());
+        assert!(severities("kb1_ ").is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+//! Structured form of SC binding strings
+//!
+//! SC writes a binding as one string: device prefix and instance, any held modifiers,
+//! then the key or button, e.g. `kb1_lshift+lctrl+x` or `js1_lalt+button3`. Older files
+//! and other tools also put the modifiers in front (`LALT+js1_button3`) or after the key
+//! (`kb_u+lshift`). Double taps aren't part of the string; they come from the rebind's
+//! `multiTap` attribute. Parsing everything into one shape lets conflict checks tell
+//! `lalt+f` from `f` without caring how either was spelled.
+
+use crate::keybindings::InputType;
+use serde::{Deserialize, Serialize};
+
+/// A keyboard modifier, in the order SC writes them
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Modifier {
+    LShift,
+    LCtrl,
+    LAlt,
+    RShift,
+    RCtrl,
+    RAlt,
+}
+
+impl Modifier {
+    pub fn from_sc_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "lshift" => Some(Modifier::LShift),
+            "lctrl" => Some(Modifier::LCtrl),
+            "lalt" => Some(Modifier::LAlt),
+            "rshift" => Some(Modifier::RShift),
+            "rctrl" => Some(Modifier::RCtrl),
+            "ralt" => Some(Modifier::RAlt),
+            _ => None,
+        }
+    }
+
+    pub fn sc_name(self) -> &'static str {
+        match self {
+            Modifier::LShift => "lshift",
+            Modifier::LCtrl => "lctrl",
+            Modifier::LAlt => "lalt",
+            Modifier::RShift => "rshift",
+            Modifier::RCtrl => "rctrl",
+            Modifier::RAlt => "ralt",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Modifier::LShift => "Left Shift",
+            Modifier::LCtrl => "Left Ctrl",
+            Modifier::LAlt => "Left Alt",
+            Modifier::RShift => "Right Shift",
+            Modifier::RCtrl => "Right Ctrl",
+            Modifier::RAlt => "Right Alt",
+        }
+    }
+}
+
+/// A parsed binding string
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BindingInput {
+    pub device: InputType,
+    /// Device instance, e.g. 2 for `js2_button1`. SC's `kb_`/`mo_` mean instance 1.
+    pub instance: u32,
+    /// Held modifiers, sorted and without duplicates
+    pub modifiers: Vec<Modifier>,
+    /// Key, button or axis name; empty for a cleared binding (`js1_ `)
+    pub key: String,
+    /// Taps needed to trigger, from the rebind's `multiTap` attribute (1 for a plain press)
+    pub taps: u32,
+}
+
+/// Split "js2" into its device and instance
+fn parse_device(prefix: &str) -> Option<(InputType, u32)> {
+    let prefix = prefix.trim().to_lowercase();
+    let digits_at = prefix
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(prefix.len());
+    let device = match &prefix[..digits_at] {
+        "kb" => InputType::Keyboard,
+        "mo" | "mouse" => InputType::Mouse,
+        "js" => InputType::Joystick,
+        "gp" => InputType::Gamepad,
+        _ => return None,
+    };
+    let instance = match &prefix[digits_at..] {
+        "" => 1,
+        digits => digits.parse().ok()?,
+    };
+    Some((device, instance))
+}
+
+fn device_prefix(device: &InputType) -> &'static str {
+    match device {
+        InputType::Keyboard => "kb",
+        InputType::Mouse => "mo",
+        InputType::Joystick => "js",
+        InputType::Gamepad => "gp",
+        InputType::Unknown => "",
+    }
+}
+
+impl BindingInput {
+    /// Parse an SC binding string. `multi_tap` is the rebind's `multiTap` attribute.
+    pub fn parse(input: &str, multi_tap: Option<u32>) -> Result<Self, String> {
+        let mut device = None;
+        let mut modifiers = Vec::new();
+        let mut keys: Vec<String> = Vec::new();
+
+        for part in input.split('+') {
+            let mut part = part.trim();
+            if let Some((prefix, rest)) = part.split_once('_') {
+                if let Some(parsed) = parse_device(prefix) {
+                    if device.replace(parsed).is_some() {
+                        return Err(format!("More than one device in binding '{}'", input));
+                    }
+                    part = rest.trim();
+                }
+            }
+            if part.is_empty() {
+                continue;
+            }
+            match Modifier::from_sc_name(part) {
+                Some(modifier) => modifiers.push(modifier),
+                None => keys.push(part.to_lowercase()),
+            }
+        }
+
+        let (device, instance) =
+            device.ok_or_else(|| format!("No device prefix in binding '{}'", input))?;
+
+        // A modifier on its own is the key itself, e.g. `kb1_lalt`
+        if keys.is_empty() && modifiers.len() == 1 {
+            keys.push(modifiers.remove(0).sc_name().to_string());
+        }
+        if keys.len() > 1 {
+            return Err(format!(
+                "Binding '{}' has more than one key ({})",
+                input,
+                keys.join(", ")
+            ));
+        }
+
+        modifiers.sort();
+        modifiers.dedup();
+        Ok(BindingInput {
+            device,
+            instance,
+            modifiers,
+            key: keys.pop().unwrap_or_default(),
+            taps: multi_tap.unwrap_or(1).max(1),
+        })
+    }
+
+    pub fn is_cleared(&self) -> bool {
+        self.key.is_empty()
+    }
+
+    /// The binding string in SC's own spelling, e.g. `kb1_lshift+lctrl+x`
+    pub fn to_sc_string(&self) -> String {
+        let mut parts: Vec<&str> = self.modifiers.iter().map(|m| m.sc_name()).collect();
+        parts.push(&self.key);
+        format!(
+            "{}{}_{}",
+            device_prefix(&self.device),
+            self.instance,
+            parts.join("+")
+        )
+    }
+
+    /// Same device and key, regardless of modifiers or taps
+    pub fn same_trigger(&self, other: &BindingInput) -> bool {
+        !self.is_cleared()
+            && self.device == other.device
+            && self.instance == other.instance
+            && self.key == other.key
+    }
+
+    /// Would both bindings fire on the same input? `lalt+f` and `f` don't, a double tap
+    /// and a single press of the same key don't either.
+    pub fn conflicts_with(&self, other: &BindingInput) -> bool {
+        self.same_trigger(other) && self.modifiers == other.modifiers && self.taps == other.taps
+    }
+
+    /// Human readable form, e.g. "Double tap Left Alt + F"
+    pub fn label(&self) -> String {
+        let mut parts: Vec<String> = self
+            .modifiers
+            .iter()
+            .map(|m| m.label().to_string())
+            .collect();
+        parts.push(if self.is_cleared() {
+            "Unbound".to_string()
+        } else {
+            self.key.replace('_', " ").to_uppercase()
+        });
+        let combo = parts.join(" + ");
+        match self.taps {
+            1 => combo,
+            2 => format!("Double tap {}", combo),
+            taps => format!("{}x tap {}", taps, combo),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modifier_spellings_parse_the_same() {
+        let canonical = BindingInput::parse("kb1_lshift+lctrl+x", None).unwrap();
+        assert_eq!(canonical.modifiers, vec![Modifier::LShift, Modifier::LCtrl]);
+        assert_eq!(canonical.key, "x");
+
+        for spelling in [
+            "LCTRL+LSHIFT+kb1_x",
+            "kb_x+lshift+lctrl",
+            "kb1_lctrl+lshift+x",
+        ] {
+            assert_eq!(
+                BindingInput::parse(spelling, None).unwrap(),
+                canonical,
+                "parsing {}",
+                spelling
+            );
+        }
+        assert_eq!(canonical.to_sc_string(), "kb1_lshift+lctrl+x");
+
+        let js = BindingInput::parse("LALT+js2_button3", None).unwrap();
+        assert_eq!(js.device, InputType::Joystick);
+        assert_eq!(js.instance, 2);
+        assert_eq!(js.to_sc_string(), "js2_lalt+button3");
+    }
+
+    #[test]
+    fn test_special_forms() {
+        let alone = BindingInput::parse("kb1_lalt", None).unwrap();
+        assert!(alone.modifiers.is_empty());
+        assert_eq!(alone.key, "lalt");
+
+        let cleared = BindingInput::parse("js1_ ", None).unwrap();
+        assert!(cleared.is_cleared());
+
+        let double = BindingInput::parse("kb1_f", Some(2)).unwrap();
+        assert_eq!(double.taps, 2);
+        assert_eq!(double.label(), "Double tap F");
+
+        assert!(BindingInput::parse("f", None).is_err());
+        assert!(BindingInput::parse("kb1_f+g", None).is_err());
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let f = BindingInput::parse("kb1_f", None).unwrap();
+        let alt_f = BindingInput::parse("kb1_lalt+f", None).unwrap();
+        let alt_f_old = BindingInput::parse("LALT+kb1_f", None).unwrap();
+        let double_f = BindingInput::parse("kb1_f", Some(2)).unwrap();
+
+        assert!(!f.conflicts_with(&alt_f));
+        assert!(f.same_trigger(&alt_f));
+        assert!(alt_f.conflicts_with(&alt_f_old));
+        assert!(!f.conflicts_with(&double_f));
+        assert!(!BindingInput::parse("js1_ ", None)
+            .unwrap()
+            .conflicts_with(&BindingInput::parse("js1_ ", None).unwrap()));
+    }
+}
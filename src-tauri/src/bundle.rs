@@ -0,0 +1,402 @@
+//! Shareable profile bundles (.boxxy)
+//!
+//! A .sccontrols file on its own doesn't say which sticks it was made for, who made it or
+//! how it's meant to be flown. A bundle is a zip holding the profile together with a
+//! manifest (author, notes, the devices it needs with their identities and nicknames) and
+//! optional preview images, so a complete setup can be passed around as one file.
+//!
+//! Layout: `manifest.json`, `profile.sccontrols` and `previews/<image>`.
+
+use crate::controls::ControlsFile;
+use crate::device_identity::DeviceIdentity;
+use crate::device_nicknames::DeviceNicknames;
+use crate::product_names;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, Write};
+use zip::write::SimpleFileOptions;
+
+pub const BUNDLE_EXTENSION: &str = "boxxy";
+
+/// Bumped when the layout changes in a way older versions can't read
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const PROFILE_ENTRY: &str = "profile.sccontrols";
+const PREVIEWS_DIR: &str = "previews/";
+
+const PREVIEW_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "webp", "gif"];
+const MAX_PREVIEWS: usize = 8;
+/// Per image, and the limit for the profile and manifest too, so a crafted bundle can't
+/// unpack into gigabytes
+const MAX_ENTRY_BYTES: u64 = 8 * 1024 * 1024;
+
+/// A device the profile was made for
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BundleDevice {
+    pub device_type: String,
+    pub instance: String,
+    /// SC Product string
+    pub product: Option<String>,
+    /// "vid:pid", when the product string carries it
+    pub uuid: Option<String>,
+    /// "vid:pid#n" of the author's device, telling identical sticks apart
+    pub identity: Option<String>,
+    pub nickname: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub profile_name: String,
+    pub author: String,
+    #[serde(default)]
+    pub contact: Option<String>,
+    #[serde(default)]
+    pub notes: String,
+    /// ISO timestamp
+    pub created_at: String,
+    #[serde(default)]
+    pub devices: Vec<BundleDevice>,
+    /// File names of the images under previews/
+    #[serde(default)]
+    pub previews: Vec<String>,
+}
+
+/// What the author fills in when exporting
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct BundleMetadata {
+    pub author: String,
+    #[serde(default)]
+    pub contact: Option<String>,
+    #[serde(default)]
+    pub notes: String,
+    /// Paths of images to include
+    #[serde(default)]
+    pub previews: Vec<String>,
+}
+
+/// A preview image: file name and contents
+pub struct Preview {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// An unpacked bundle
+pub struct BundleContents {
+    pub manifest: BundleManifest,
+    pub controls: ControlsFile,
+    pub previews: Vec<Preview>,
+}
+
+/// Only a plain file name with an image extension is accepted for a preview
+fn is_preview_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(['/', '\\', ':'])
+        && !name.starts_with('.')
+        && name.rsplit_once('.').is_some_and(|(_, ext)| {
+            PREVIEW_EXTENSIONS
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// The devices a profile uses, each matched to one of the author's connected devices
+/// (identical devices are matched one-to-one, in instance order)
+pub fn required_devices(
+    controls: &ControlsFile,
+    identities: &[DeviceIdentity],
+    nicknames: &DeviceNicknames,
+) -> Vec<BundleDevice> {
+    let mut entries = Vec::new();
+    if let Some(keyboard) = &controls.devices.keyboard {
+        entries.push(("keyboard", "1".to_string(), keyboard.product.clone()));
+    }
+    if let Some(mouse) = &controls.devices.mouse {
+        entries.push(("mouse", "1".to_string(), mouse.product.clone()));
+    }
+    if let Some(gamepad) = &controls.devices.gamepad {
+        entries.push(("gamepad", "1".to_string(), gamepad.product.clone()));
+    }
+    let mut joysticks: Vec<_> = controls.devices.joystick.iter().flatten().collect();
+    joysticks.sort_by_key(|(instance, _)| instance.parse::<u32>().unwrap_or(u32::MAX));
+    for (instance, joystick) in joysticks {
+        entries.push(("joystick", instance.clone(), joystick.product.clone()));
+    }
+
+    let mut used = vec![false; identities.len()];
+    entries
+        .into_iter()
+        .map(|(device_type, instance, product)| {
+            let uuid = product
+                .as_deref()
+                .and_then(product_names::uuid_from_product);
+            let matched = product.as_deref().and_then(|product| {
+                let index = identities.iter().enumerate().position(|(i, id)| {
+                    !used[i]
+                        && match &uuid {
+                            Some(uuid) => id.uuid.eq_ignore_ascii_case(uuid),
+                            None => id
+                                .product
+                                .as_deref()
+                                .is_some_and(|name| product_names::same_name(name, product)),
+                        }
+                })?;
+                used[index] = true;
+                Some(&identities[index])
+            });
+            let identity = matched.map(|id| id.identity.clone());
+            let nickname = identity
+                .as_deref()
+                .and_then(|id| nicknames.get(id))
+                .or_else(|| uuid.as_deref().and_then(|uuid| nicknames.get(uuid)))
+                .map(str::to_string);
+
+            BundleDevice {
+                device_type: device_type.to_string(),
+                instance,
+                product,
+                uuid,
+                identity,
+                nickname,
+            }
+        })
+        .collect()
+}
+
+/// Bundle devices with no counterpart among the connected ones
+pub fn missing_devices(
+    required: &[BundleDevice],
+    identities: &[DeviceIdentity],
+) -> Vec<BundleDevice> {
+    let mut used = vec![false; identities.len()];
+    required
+        .iter()
+        .filter(|device| device.device_type == "joystick")
+        .filter(|device| {
+            let found = identities.iter().enumerate().position(|(i, id)| {
+                !used[i]
+                    && match (&device.uuid, &device.product) {
+                        (Some(uuid), _) => id.uuid.eq_ignore_ascii_case(uuid),
+                        (None, Some(product)) => id
+                            .product
+                            .as_deref()
+                            .is_some_and(|name| product_names::same_name(name, product)),
+                        (None, None) => true,
+                    }
+            });
+            match found {
+                Some(i) => {
+                    used[i] = true;
+                    false
+                }
+                None => true,
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Build the manifest for a profile
+pub fn manifest(
+    controls: &ControlsFile,
+    metadata: &BundleMetadata,
+    devices: Vec<BundleDevice>,
+    previews: &[Preview],
+) -> Result<BundleManifest, String> {
+    let author = metadata.author.trim();
+    if author.is_empty() {
+        return Err("Author name cannot be empty".to_string());
+    }
+    Ok(BundleManifest {
+        format_version: FORMAT_VERSION,
+        profile_name: controls.profile_name.clone(),
+        author: author.to_string(),
+        contact: metadata
+            .contact
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(str::to_string),
+        notes: metadata.notes.trim().to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        devices,
+        previews: previews.iter().map(|p| p.name.clone()).collect(),
+    })
+}
+
+/// Write a bundle
+pub fn write_bundle<W: Write + Seek>(
+    writer: W,
+    manifest: &BundleManifest,
+    controls: &ControlsFile,
+    previews: &[Preview],
+) -> Result<(), String> {
+    if previews.len() > MAX_PREVIEWS {
+        return Err(format!("A bundle holds at most {} previews", MAX_PREVIEWS));
+    }
+    for preview in previews {
+        if !is_preview_name(&preview.name) {
+            return Err(format!("{} is not a supported image", preview.name));
+        }
+        if preview.data.len() as u64 > MAX_ENTRY_BYTES {
+            return Err(format!(
+                "{} is larger than {} MB",
+                preview.name,
+                MAX_ENTRY_BYTES / 1024 / 1024
+            ));
+        }
+    }
+
+    let manifest_json = serde_json::to_string_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize bundle manifest: {}", e))?;
+    let mut zip = zip::ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut add = |name: &str, data: &[u8]| -> Result<(), String> {
+        zip.start_file(name, options)
+            .and_then(|_| zip.write_all(data).map_err(Into::into))
+            .map_err(|e| format!("Failed to write {} to bundle: {}", name, e))
+    };
+    add(MANIFEST_ENTRY, manifest_json.as_bytes())?;
+    add(PROFILE_ENTRY, controls.to_json()?.as_bytes())?;
+    for preview in previews {
+        add(&format!("{}{}", PREVIEWS_DIR, preview.name), &preview.data)?;
+    }
+
+    zip.finish()
+        .map_err(|e| format!("Failed to finish bundle: {}", e))?;
+    Ok(())
+}
+
+fn read_entry<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, String> {
+    let entry = archive
+        .by_name(name)
+        .map_err(|_| format!("Bundle has no {}", name))?;
+    if entry.size() > MAX_ENTRY_BYTES {
+        return Err(format!("{} in the bundle is too large", name));
+    }
+    let mut data = Vec::new();
+    entry
+        .take(MAX_ENTRY_BYTES)
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read {} from bundle: {}", name, e))?;
+    Ok(data)
+}
+
+/// Read a bundle. Previews the manifest doesn't list, or with unexpected names, are ignored.
+pub fn read_bundle<R: Read + Seek>(reader: R) -> Result<BundleContents, String> {
+    let mut archive =
+        zip::ZipArchive::new(reader).map_err(|e| format!("Not a valid bundle: {}", e))?;
+
+    let manifest: BundleManifest =
+        serde_json::from_slice(&read_entry(&mut archive, MANIFEST_ENTRY)?)
+            .map_err(|e| format!("Failed to parse bundle manifest: {}", e))?;
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "This bundle was made by a newer version (format {}); please update to open it",
+            manifest.format_version
+        ));
+    }
+
+    let profile = String::from_utf8(read_entry(&mut archive, PROFILE_ENTRY)?)
+        .map_err(|_| "The bundled profile is not valid text".to_string())?;
+    let controls = ControlsFile::from_json(&profile)?;
+
+    let mut previews = Vec::new();
+    for name in manifest.previews.iter().take(MAX_PREVIEWS) {
+        if !is_preview_name(name) {
+            continue;
+        }
+        match read_entry(&mut archive, &format!("{}{}", PREVIEWS_DIR, name)) {
+            Ok(data) => previews.push(Preview {
+                name: name.clone(),
+                data,
+            }),
+            Err(e) => log::warn!("Skipping bundle preview: {}", e),
+        }
+    }
+
+    Ok(BundleContents {
+        manifest,
+        controls,
+        previews,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::DeviceInstanceSettings;
+    use crate::device_identity::IdentitySource;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    const GLADIATOR: &str = " VKBsim Gladiator EVO R    {0200231D-0000-0000-0000-504944564944}";
+
+    fn identity(index: usize) -> DeviceIdentity {
+        DeviceIdentity {
+            uuid: "231d:0200".to_string(),
+            product: Some("VKBsim Gladiator EVO R".to_string()),
+            index,
+            identity: format!("231d:0200#{}", index),
+            source: IdentitySource::Serial,
+            has_twin: true,
+            path: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let mut controls = ControlsFile::new("Twin sticks".to_string());
+        let stick = || DeviceInstanceSettings {
+            product: Some(GLADIATOR.to_string()),
+            role: None,
+            options: HashMap::new(),
+            extra: Default::default(),
+        };
+        controls.devices.joystick = Some(HashMap::from([
+            ("1".to_string(), stick()),
+            ("2".to_string(), stick()),
+        ]));
+
+        let mut nicknames = DeviceNicknames::default();
+        nicknames.set("231d:0200#2", "Left stick").unwrap();
+        let devices = required_devices(&controls, &[identity(1), identity(2)], &nicknames);
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[1].identity.as_deref(), Some("231d:0200#2"));
+        assert_eq!(devices[1].nickname.as_deref(), Some("Left stick"));
+
+        let previews = vec![Preview {
+            name: "cockpit.png".to_string(),
+            data: vec![0x89, b'P', b'N', b'G'],
+        }];
+        let metadata = BundleMetadata {
+            author: " Boxxy ".to_string(),
+            notes: "Space brake on the trigger".to_string(),
+            ..Default::default()
+        };
+        let manifest = manifest(&controls, &metadata, devices, &previews).unwrap();
+        assert_eq!(manifest.author, "Boxxy");
+
+        let mut buffer = Cursor::new(Vec::new());
+        write_bundle(&mut buffer, &manifest, &controls, &previews).unwrap();
+        buffer.set_position(0);
+        let contents = read_bundle(buffer).unwrap();
+
+        assert_eq!(contents.manifest.profile_name, "Twin sticks");
+        assert_eq!(contents.manifest.devices, manifest.devices);
+        assert_eq!(contents.previews.len(), 1);
+        assert_eq!(contents.previews[0].data, previews[0].data);
+
+        // Only one of the two sticks is plugged in on the other end
+        let missing = missing_devices(&contents.manifest.devices, &[identity(1)]);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].instance, "2");
+
+        assert!(!is_preview_name("../evil.png"));
+        assert!(!is_preview_name("notes.txt"));
+    }
+}
@@ -0,0 +1,463 @@
+//! Printable keybind cheat sheets
+//!
+//! Renders the loaded actionmaps.xml as a cheat sheet grouped by device and then by
+//! action map. Labels come from AllBinds.xml, resolved through the game's global.ini
+//! when the user points us at one. Joysticks are headed with their device nicknames.
+//! HTML and Markdown are generated here; PDF is printed from the HTML by a headless
+//! Edge/Chrome.
+
+use crate::device_nicknames::DeviceNicknames;
+use crate::keybindings::{format_display_name, ActionMaps, AllBinds, InputType, Rebind};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheatSheetFormat {
+    Html,
+    Markdown,
+    Pdf,
+}
+
+/// One bound action on a device
+#[derive(Debug, Clone)]
+struct CheatSheetEntry {
+    action: String,
+    binding: String,
+}
+
+/// Device section -> action map label -> entries
+type Sections = BTreeMap<DeviceKey, BTreeMap<String, Vec<CheatSheetEntry>>>;
+
+/// Sort key for device sections so keyboard comes first, then mouse, joysticks, gamepad
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum DeviceKey {
+    Keyboard,
+    Mouse,
+    Joystick(u32),
+    Gamepad,
+}
+
+/// Parse a global.ini localization file ("key=value" per line) into a lookup table
+pub fn parse_localization(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim_start_matches('\u{feff}').split_once('=')?;
+            // Some keys carry a ",P" suffix for platform variants
+            let key = key.trim().split(',').next().unwrap_or("").to_string();
+            Some((key, value.trim().to_string()))
+        })
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+/// Resolve a "@ui_..." label through the localization table, falling back to a formatted name
+pub fn resolve_label(
+    label: Option<&str>,
+    name: &str,
+    localization: &HashMap<String, String>,
+) -> String {
+    match label {
+        Some(label) if label.starts_with('@') => localization
+            .get(&label[1..])
+            .cloned()
+            .unwrap_or_else(|| format_display_name(name)),
+        Some(label) if !label.is_empty() => label.to_string(),
+        _ => format_display_name(name),
+    }
+}
+
+fn device_key(rebind: &Rebind) -> Option<DeviceKey> {
+    match rebind.get_input_type() {
+        InputType::Keyboard => Some(DeviceKey::Keyboard),
+        InputType::Mouse => Some(DeviceKey::Mouse),
+        InputType::Gamepad => Some(DeviceKey::Gamepad),
+        InputType::Joystick => {
+            let input = rebind.input.trim();
+            let device = input
+                .split('+')
+                .find(|part| part.trim().starts_with("js"))
+                .unwrap_or(input);
+            let instance = device
+                .trim()
+                .trim_start_matches("js")
+                .split('_')
+                .next()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(1);
+            Some(DeviceKey::Joystick(instance))
+        }
+        InputType::Unknown => None,
+    }
+}
+
+/// Strip the "Keyboard - " style device prefix, since the section heading already says it
+fn binding_label(rebind: &Rebind) -> String {
+    let display = rebind.get_display_name();
+    let mut label = match display.split_once(" - ") {
+        Some((modifiers_and_device, binding)) => match modifiers_and_device.rsplit_once(" + ") {
+            Some((modifiers, _)) => format!("{} + {}", modifiers, binding),
+            None => binding.to_string(),
+        },
+        None => display,
+    };
+
+    if let Some(taps) = rebind.multi_tap.filter(|&taps| taps > 1) {
+        label.push_str(&format!(" (x{})", taps));
+    }
+    if !rebind.activation_mode.is_empty() {
+        label.push_str(&format!(
+            " [{}]",
+            format_display_name(&rebind.activation_mode)
+        ));
+    }
+    label
+}
+
+fn collect_sections(
+    bindings: &ActionMaps,
+    all_binds: Option<&AllBinds>,
+    localization: &HashMap<String, String>,
+) -> Sections {
+    let mut sections: Sections = BTreeMap::new();
+
+    for action_map in &bindings.action_maps {
+        let all_binds_map = all_binds.and_then(|ab| {
+            ab.action_maps
+                .iter()
+                .find(|map| map.name == action_map.name)
+        });
+        let map_label = resolve_label(
+            all_binds_map.map(|map| map.ui_label.as_str()),
+            &action_map.name,
+            localization,
+        );
+
+        for action in &action_map.actions {
+            let action_label = resolve_label(
+                all_binds_map
+                    .and_then(|map| map.actions.iter().find(|a| a.name == action.name))
+                    .map(|a| a.ui_label.as_str()),
+                &action.name,
+                localization,
+            );
+
+            for rebind in &action.rebinds {
+                let Some(device) = device_key(rebind) else {
+                    continue;
+                };
+                sections
+                    .entry(device)
+                    .or_default()
+                    .entry(map_label.clone())
+                    .or_default()
+                    .push(CheatSheetEntry {
+                        action: action_label.clone(),
+                        binding: binding_label(rebind),
+                    });
+            }
+        }
+    }
+
+    sections
+}
+
+fn device_heading(
+    device: &DeviceKey,
+    bindings: &ActionMaps,
+    nicknames: &DeviceNicknames,
+) -> String {
+    match device {
+        DeviceKey::Keyboard => "Keyboard".to_string(),
+        DeviceKey::Mouse => "Mouse".to_string(),
+        DeviceKey::Gamepad => "Gamepad".to_string(),
+        DeviceKey::Joystick(instance) => {
+            match (*instance as usize)
+                .checked_sub(1)
+                .and_then(|index| bindings.devices.joysticks.get(index))
+            {
+                Some(product) => match nicknames.get(product) {
+                    Some(nickname) => format!("Joystick {}: {} ({})", instance, nickname, product),
+                    None => format!("Joystick {} ({})", instance, product),
+                },
+                None => format!("Joystick {}", instance),
+            }
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// Render a cheat sheet as a standalone, print-friendly HTML page
+pub fn render_html(
+    bindings: &ActionMaps,
+    all_binds: Option<&AllBinds>,
+    localization: &HashMap<String, String>,
+    nicknames: &DeviceNicknames,
+) -> String {
+    let title = escape_html(&bindings.profile_name);
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{} - Cheat Sheet</title>\n", title));
+    html.push_str(
+        "<style>\n\
+         body { font-family: Segoe UI, Arial, sans-serif; font-size: 10pt; margin: 1.5em; }\n\
+         h1 { font-size: 16pt; }\n\
+         h2 { font-size: 13pt; border-bottom: 2px solid #333; page-break-before: auto; }\n\
+         h3 { font-size: 11pt; margin-bottom: 0.2em; }\n\
+         table { border-collapse: collapse; width: 100%; margin-bottom: 0.8em; }\n\
+         td { border-bottom: 1px solid #ddd; padding: 2px 6px; }\n\
+         td.binding { font-family: Consolas, monospace; width: 40%; }\n\
+         .device { break-inside: avoid-page; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    html.push_str(&format!("<h1>{}</h1>\n", title));
+
+    for (device, maps) in collect_sections(bindings, all_binds, localization) {
+        html.push_str(&format!(
+            "<section class=\"device\">\n<h2>{}</h2>\n",
+            escape_html(&device_heading(&device, bindings, nicknames))
+        ));
+        for (map_label, entries) in maps {
+            html.push_str(&format!("<h3>{}</h3>\n<table>\n", escape_html(&map_label)));
+            for entry in entries {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td class=\"binding\">{}</td></tr>\n",
+                    escape_html(&entry.action),
+                    escape_html(&entry.binding)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Render a cheat sheet as Markdown tables
+pub fn render_markdown(
+    bindings: &ActionMaps,
+    all_binds: Option<&AllBinds>,
+    localization: &HashMap<String, String>,
+    nicknames: &DeviceNicknames,
+) -> String {
+    let mut md = format!("# {}\n", bindings.profile_name);
+
+    for (device, maps) in collect_sections(bindings, all_binds, localization) {
+        md.push_str(&format!(
+            "\n## {}\n",
+            device_heading(&device, bindings, nicknames)
+        ));
+        for (map_label, entries) in maps {
+            md.push_str(&format!(
+                "\n### {}\n\n| Action | Binding |\n| --- | --- |\n",
+                map_label
+            ));
+            for entry in entries {
+                md.push_str(&format!(
+                    "| {} | {} |\n",
+                    escape_markdown(&entry.action),
+                    escape_markdown(&entry.binding)
+                ));
+            }
+        }
+    }
+
+    md
+}
+
+/// Browsers that can print HTML to PDF (or screenshot a page) headlessly, in order of
+/// preference
+#[cfg(windows)]
+const HEADLESS_BROWSERS: &[&str] = &[
+    r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe",
+    r"C:\Program Files\Microsoft\Edge\Application\msedge.exe",
+    r"C:\Program Files\Google\Chrome\Application\chrome.exe",
+];
+
+#[cfg(not(windows))]
+const HEADLESS_BROWSERS: &[&str] = &["chromium", "chromium-browser", "google-chrome"];
+
+/// Run the first headless browser found with `args`, and check it wrote `output_path`
+pub fn run_headless_browser(args: &[String], output_path: &Path, what: &str) -> Result<(), String> {
+    HEADLESS_BROWSERS
+        .iter()
+        .find_map(|browser| {
+            std::process::Command::new(browser)
+                .arg("--headless")
+                .arg("--disable-gpu")
+                .args(args)
+                .output()
+                .ok()
+        })
+        .ok_or_else(|| {
+            format!(
+                "No Edge or Chrome installation found to render the {}",
+                what
+            )
+        })
+        .and_then(|output| {
+            if output.status.success() && output_path.exists() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{} rendering failed: {}",
+                    what,
+                    String::from_utf8_lossy(&output.stderr)
+                ))
+            }
+        })
+}
+
+/// Print an HTML cheat sheet to PDF using a headless Chromium-based browser
+pub fn render_pdf(html: &str, pdf_path: &Path) -> Result<(), String> {
+    let html_path = pdf_path.with_extension("cheatsheet.html");
+    std::fs::write(&html_path, html)
+        .map_err(|e| format!("Failed to write temporary HTML: {}", e))?;
+
+    let file_url = format!("file:///{}", html_path.to_string_lossy().replace('\\', "/"));
+    let result = run_headless_browser(
+        &[
+            "--no-pdf-header-footer".to_string(),
+            format!("--print-to-pdf={}", pdf_path.to_string_lossy()),
+            file_url,
+        ],
+        pdf_path,
+        "PDF",
+    );
+
+    let _ = std::fs::remove_file(&html_path);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACTIONMAPS: &str = r#"<ActionMaps>
+ <ActionProfiles version="1" profileName="default">
+  <options type="keyboard" instance="1" Product="Keyboard  {6F1D2B61-D5A0-11CF-BFC7-444553540000}"/>
+  <options type="joystick" instance="1" Product=" VKB-Sim Gladiator NXT L    {0200231D-0000-0000-0000-504944564944}"/>
+  <actionmap name="spaceship_weapons">
+   <action name="v_attack1_group1">
+    <rebind input="js1_button1"/>
+   </action>
+   <action name="v_weapon_cycle_missile_fwd">
+    <rebind input="kb1_lalt+g" activationMode="double_tap"/>
+   </action>
+  </actionmap>
+  <actionmap name="spaceship_general">
+   <action name="v_toggle_landing_system">
+    <rebind input="js1_button3"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>"#;
+
+    const ALL_BINDS: &str = r#"<profile version="1">
+ <actionmap name="spaceship_weapons" version="2" UILabel="@ui_CGWeapons">
+  <action name="v_attack1_group1" UILabel="@ui_v_attack1_group1"/>
+  <action name="v_weapon_cycle_missile_fwd" UILabel="@ui_v_cycle_missile"/>
+ </actionmap>
+ <actionmap name="spaceship_general" version="2" UILabel="@ui_CGGeneral">
+  <action name="v_toggle_landing_system" UILabel="@ui_v_landing"/>
+ </actionmap>
+</profile>"#;
+
+    const GLOBAL_INI: &str = "\u{feff}ui_CGWeapons=Weapons | Missiles\n\
+                              ui_v_attack1_group1=Fire <Guns> & \"Forget\"\n\
+                              ui_v_cycle_missile,P=Cycle missiles | fwd\n\
+                              ui_CGGeneral=General\n";
+
+    fn fixture() -> (
+        ActionMaps,
+        AllBinds,
+        HashMap<String, String>,
+        DeviceNicknames,
+    ) {
+        let mut bindings = ActionMaps::from_xml(ACTIONMAPS).unwrap();
+        bindings.profile_name = "Tom & Jerry's <Dual>".to_string();
+        let all_binds = AllBinds::from_xml(ALL_BINDS).unwrap();
+        let mut nicknames = DeviceNicknames::default();
+        nicknames.set("231d:0200", "Left <Stick> & Co").unwrap();
+        (
+            bindings,
+            all_binds,
+            parse_localization(GLOBAL_INI),
+            nicknames,
+        )
+    }
+
+    #[test]
+    fn test_render_html_escapes_names() {
+        let (bindings, all_binds, localization, nicknames) = fixture();
+        let html = render_html(&bindings, Some(&all_binds), &localization, &nicknames);
+
+        assert!(html.contains("<title>Tom &amp; Jerry's &lt;Dual&gt; - Cheat Sheet</title>"));
+        assert!(html.contains("<h1>Tom &amp; Jerry's &lt;Dual&gt;</h1>"));
+        assert!(html.contains("<h2>Joystick 1: Left &lt;Stick&gt; &amp; Co ("));
+        assert!(html.contains(
+            "<tr><td>Fire &lt;Guns&gt; &amp; &quot;Forget&quot;</td><td class=\"binding\">"
+        ));
+        assert!(!html.contains("<Guns>"));
+        assert!(!html.contains("<Stick>"));
+
+        // Keyboard comes before joysticks, and the device prefix isn't repeated per binding
+        let keyboard = html.find("<h2>Keyboard</h2>").unwrap();
+        let joystick = html.find("<h2>Joystick 1").unwrap();
+        assert!(keyboard < joystick);
+        assert!(html.contains("<td>Cycle missiles | fwd</td>"));
+        assert!(html.contains("[Double Tap]"));
+        assert!(!html.contains("Keyboard - "));
+        assert!(html.contains("<h3>General</h3>"));
+        assert!(html.ends_with("</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn test_render_markdown_tables() {
+        let (bindings, all_binds, localization, nicknames) = fixture();
+        let md = render_markdown(&bindings, Some(&all_binds), &localization, &nicknames);
+
+        assert!(md.starts_with("# Tom & Jerry's <Dual>\n"));
+        assert!(md.contains("\n## Keyboard\n"));
+        assert!(md.contains("\n## Joystick 1: Left <Stick> & Co ("));
+        assert!(md.contains("\n### Weapons | Missiles\n\n| Action | Binding |\n| --- | --- |\n"));
+        assert!(md.contains("| Fire <Guns> & \"Forget\" | "));
+        // A pipe inside a cell would otherwise split the row
+        assert!(md.contains("| Cycle missiles \\| fwd | "));
+        assert_eq!(md.matches("| --- | --- |").count(), 3);
+
+        // Without AllBinds.xml the internal names are formatted instead
+        let md = render_markdown(&bindings, None, &localization, &DeviceNicknames::default());
+        assert!(md.contains("\n## Joystick 1 ("));
+        assert!(!md.contains("Fire <Guns>"));
+    }
+
+    #[test]
+    fn test_parse_localization() {
+        let localization = parse_localization(GLOBAL_INI);
+        assert_eq!(localization["ui_CGWeapons"], "Weapons | Missiles");
+        assert_eq!(localization["ui_v_cycle_missile"], "Cycle missiles | fwd");
+        assert_eq!(
+            resolve_label(Some("@ui_missing"), "v_eject", &localization),
+            format_display_name("v_eject")
+        );
+        assert_eq!(
+            resolve_label(Some("Plain"), "v_eject", &localization),
+            "Plain"
+        );
+    }
+}
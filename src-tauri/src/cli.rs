@@ -0,0 +1,392 @@
+//! Headless command line mode
+//!
+//! `boxxy-binder <command> ...` applies, backs up, diffs and exports profiles without
+//! opening the window, so profile application can be scripted from a pre-launch batch
+//! file. Without a recognized command the normal GUI starts.
+//!
+//! The CLI shares the GUI's settings, backup store and write lock, so it honors
+//! read-only mode and never writes at the same time as a running GUI.
+
+use crate::controls::{self, ControlsFile, OptionContext};
+use crate::{apply_guard, backups, diff, settings, variables, write_lock};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Must match "identifier" in tauri.conf.json, it names the app's config and data directories
+const APP_IDENTIFIER: &str = "com.boxxy.boxxy-binder";
+
+/// Default RSI Launcher install location, used when --install isn't given
+const DEFAULT_INSTALL_DIR: &str = r"C:\Program Files\Roberts Space Industries\StarCitizen";
+
+const DEFAULT_ENV: &str = "LIVE";
+
+const USAGE: &str = "Usage: boxxy-binder <command> [options]
+
+Commands:
+  apply   --profile <file.sccontrols> [--context <context>]... [--force] [--confirm <token>]
+          [--name <snapshot name>]
+          Snapshot actionmaps.xml and apply the profile's options to it.
+          Refuses while Star Citizen is running unless --force is given, and
+          shows large changes for confirming with the --confirm token first
+  backup  Copy actionmaps.xml into the backup store
+  diff    --profile <file.sccontrols>   Show what applying the profile would change
+  diff    --from <a.xml> --to <b.xml>   Compare two actionmaps.xml files
+  export  --out <file.sccontrols> [--name <profile name>]
+          Save the options currently in actionmaps.xml as a profile
+  help    Show this message
+
+Locating actionmaps.xml (apply, backup, diff --profile, export):
+  --env <LIVE|PTU|EPTU|TECH-PREVIEW>   Default: LIVE
+  --install <StarCitizen folder>       Default: the RSI Launcher location
+  --actionmaps <file>                  Use this file directly
+
+Exit codes: 0 success, 1 failure, 2 bad usage";
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+const COMMANDS: [&str; 5] = ["apply", "backup", "diff", "export", "help"];
+
+/// Options that take no value
+const FLAGS: [&str; 1] = ["force"];
+
+/// Parsed `--name value` options and `--flag`s; names may repeat
+struct Args {
+    values: HashMap<String, Vec<String>>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, String> {
+        let mut values: HashMap<String, Vec<String>> = HashMap::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("Unexpected argument: {}", arg))?;
+            if FLAGS.contains(&name) {
+                values.entry(name.to_string()).or_default();
+                continue;
+            }
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("Missing value for --{}", name))?;
+            values
+                .entry(name.to_string())
+                .or_default()
+                .push(value.clone());
+        }
+        Ok(Args { values })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .get(name)
+            .and_then(|v| v.last())
+            .map(String::as_str)
+    }
+
+    fn require(&self, name: &str) -> Result<&str, String> {
+        self.get(name)
+            .ok_or_else(|| format!("Missing required option --{}", name))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    fn all(&self, name: &str) -> &[String] {
+        self.values.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// Home directory from the environment, for the non-Windows directory layouts
+fn home_dir() -> Result<PathBuf, String> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| "HOME is not set".to_string())
+}
+
+/// The same config and data directories Tauri resolves for the GUI
+fn app_dirs() -> Result<(PathBuf, PathBuf), String> {
+    let (config_base, data_base) = if cfg!(windows) {
+        let roaming = std::env::var_os("APPDATA")
+            .map(PathBuf::from)
+            .ok_or_else(|| "APPDATA is not set".to_string())?;
+        (roaming.clone(), roaming)
+    } else if cfg!(target_os = "macos") {
+        let support = home_dir()?.join("Library").join("Application Support");
+        (support.clone(), support)
+    } else {
+        let config = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => home_dir()?.join(".config"),
+        };
+        let data = match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => home_dir()?.join(".local").join("share"),
+        };
+        (config, data)
+    };
+    Ok((
+        config_base.join(APP_IDENTIFIER),
+        data_base.join(APP_IDENTIFIER),
+    ))
+}
+
+fn resolve_actionmaps(args: &Args) -> Result<String, String> {
+    if let Some(path) = args.get("actionmaps") {
+        return Ok(path.to_string());
+    }
+
+    let install = args.get("install").unwrap_or(DEFAULT_INSTALL_DIR);
+    let env = args.get("env").unwrap_or(DEFAULT_ENV).to_uppercase();
+    let path = crate::actionmaps_path_in(&Path::new(install).join(&env));
+    if !path.exists() {
+        return Err(format!(
+            "No actionmaps.xml for {} at {} (use --install or --actionmaps)",
+            env,
+            path.display()
+        ));
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Load a .sccontrols profile, filling in ${VARIABLES} from the connected devices
+fn load_profile(path: &str, config_dir: &Path) -> Result<ControlsFile, String> {
+    let json =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    let json = if variables::find_variables(&json).is_empty() {
+        json
+    } else {
+        let settings = settings::load_settings(config_dir)?;
+        let devices = crate::directinput::detect_joysticks().unwrap_or_default();
+        let values = variables::resolve_device_variables(&settings.device_variables, &devices);
+        variables::substitute(&json, &values)?
+    };
+
+    ControlsFile::from_json(&json)
+}
+
+/// Refuse in read-only mode, then take the shared write lock
+fn begin_write(config_dir: &Path, data_dir: &Path) -> Result<write_lock::WriteLock, String> {
+    if settings::load_settings(config_dir)?.read_only {
+        return Err(
+            "Read-only mode is enabled. Turn it off in Boxxy Binder's settings to make changes."
+                .to_string(),
+        );
+    }
+    write_lock::WriteLock::acquire(data_dir, "cli", write_lock::DEFAULT_WAIT)
+}
+
+fn backup_location(config_dir: &Path, data_dir: &Path) -> Result<backups::BackupLocation, String> {
+    let settings = settings::load_settings(config_dir)?;
+    Ok(backups::resolve_location(
+        settings.backup_dir.as_deref(),
+        &data_dir.join("backups"),
+    ))
+}
+
+fn parse_contexts(args: &Args) -> Result<Option<Vec<OptionContext>>, String> {
+    let names = args.all("context");
+    if names.is_empty() {
+        return Ok(None);
+    }
+    names
+        .iter()
+        .map(|name| {
+            serde_json::from_value(serde_json::Value::String(name.replace('-', "_")))
+                .map_err(|_| format!("Unknown context: {}", name))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+fn print_diff(diff: &diff::ActionmapsDiff) {
+    if diff.is_empty() {
+        println!("No differences");
+        return;
+    }
+
+    for change in &diff.option_changes {
+        let marker = match (&change.before, &change.after) {
+            (None, Some(_)) => '+',
+            (Some(_), None) => '-',
+            _ => '~',
+        };
+        println!(
+            "{} {} {} {}",
+            marker, change.device_type, change.instance, change.option
+        );
+    }
+    for change in &diff.binding_changes {
+        println!(
+            "~ {} {}: [{}] -> [{}]",
+            change.action_map,
+            change.action,
+            change.before.join(", "),
+            change.after.join(", ")
+        );
+    }
+    println!(
+        "{} option change(s), {} binding change(s)",
+        diff.option_changes.len(),
+        diff.binding_changes.len()
+    );
+}
+
+fn apply_command(args: &Args, config_dir: &Path, data_dir: &Path) -> Result<(), String> {
+    let profile = load_profile(args.require("profile")?, config_dir)?;
+    let contexts = parse_contexts(args)?;
+    let actionmaps_path = resolve_actionmaps(args)?;
+
+    let settings = settings::load_settings(config_dir)?;
+    let _write_lock = begin_write(config_dir, data_dir)?;
+    let result = crate::apply_controls_file(
+        &actionmaps_path,
+        &profile,
+        contexts.as_deref(),
+        args.get("name").map(str::to_string),
+        data_dir,
+        &apply_guard::ApplyConsent {
+            force: args.flag("force"),
+            confirmation_token: args.get("confirm").map(str::to_string),
+            policy: settings.apply_confirmation,
+        },
+        &settings.excluded_options,
+    )?;
+    if let Some(confirmation) = &result.confirmation {
+        print_diff(&confirmation.summary);
+        return Err(format!(
+            "{} Run again with --confirm {} to apply them.",
+            result.message, confirmation.token
+        ));
+    }
+    if !result.success {
+        return Err(format!("{} (use --force to apply anyway)", result.message));
+    }
+
+    println!("Applied {} to {}", profile.profile_name, actionmaps_path);
+    if let Some(snapshot) = result.snapshot {
+        println!("Snapshot: {}", snapshot.id);
+    }
+    for conflict in &result.conflicts {
+        println!(
+            "Changed by the game during apply, overwritten: {}",
+            conflict
+        );
+    }
+    Ok(())
+}
+
+fn backup_command(args: &Args, config_dir: &Path, data_dir: &Path) -> Result<(), String> {
+    let actionmaps_path = resolve_actionmaps(args)?;
+    let location = backup_location(config_dir, data_dir)?;
+    if let Some(reason) = &location.unavailable_reason {
+        eprintln!(
+            "Backup location unavailable ({}), using {}",
+            reason, location.path
+        );
+    }
+    println!("{}", backups::create_backup(&location, &actionmaps_path)?);
+    Ok(())
+}
+
+fn diff_command(args: &Args, config_dir: &Path, data_dir: &Path) -> Result<(), String> {
+    let (before, after) = match (args.get("from"), args.get("to")) {
+        (Some(from), Some(to)) => (
+            std::fs::read_to_string(from).map_err(|e| format!("Failed to read {}: {}", from, e))?,
+            std::fs::read_to_string(to).map_err(|e| format!("Failed to read {}: {}", to, e))?,
+        ),
+        _ => {
+            // Preview an apply: merge what apply would write into a copy of the current file
+            let profile = load_profile(args.require("profile")?, config_dir)?;
+            let actionmaps_path = resolve_actionmaps(args)?;
+            let current = std::fs::read_to_string(&actionmaps_path)
+                .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+            let contexts = parse_contexts(args)?;
+            let new_devices = crate::pending_options(&profile, contexts.as_deref(), data_dir)?;
+            let excluded = settings::load_settings(config_dir)?.excluded_options;
+            let merged = controls::merge_options_into_xml(&current, new_devices, &excluded)?;
+            (current, merged)
+        }
+    };
+
+    print_diff(&diff::diff_actionmaps(&before, &after)?);
+    Ok(())
+}
+
+fn export_command(args: &Args, config_dir: &Path, data_dir: &Path) -> Result<(), String> {
+    let out = args.require("out")?;
+    let actionmaps_path = resolve_actionmaps(args)?;
+    let xml = std::fs::read_to_string(&actionmaps_path)
+        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+
+    let name = args.get("name").map(str::to_string).unwrap_or_else(|| {
+        Path::new(out)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Exported".to_string())
+    });
+    let controls_file = controls::controls_file_from_actionmaps_options(
+        name,
+        controls::parse_actionmaps_options(&xml)?,
+        &settings::load_settings(config_dir)?.excluded_options,
+    );
+
+    let _write_lock = begin_write(config_dir, data_dir)?;
+    std::fs::write(out, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write {}: {}", out, e))?;
+    println!("Exported {} to {}", actionmaps_path, out);
+    Ok(())
+}
+
+/// On Windows release builds the exe has no console of its own; borrow the one we were
+/// started from so output shows up in the terminal or batch file
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    // Fails harmlessly when there is no parent console or we already have one
+    let _ = unsafe { AttachConsole(ATTACH_PARENT_PROCESS) };
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+/// Run a CLI command if the arguments name one. Returns the exit code, or None to start the GUI.
+pub fn run(args: &[String]) -> Option<i32> {
+    let command = args.first()?.as_str();
+    if !COMMANDS.contains(&command) {
+        return None;
+    }
+    attach_console();
+
+    if command == "help" {
+        println!("{}", USAGE);
+        return Some(0);
+    }
+
+    let args = match Args::parse(&args[1..]) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return Some(EXIT_USAGE);
+        }
+    };
+
+    let result = app_dirs().and_then(|(config_dir, data_dir)| match command {
+        "apply" => apply_command(&args, &config_dir, &data_dir),
+        "backup" => backup_command(&args, &config_dir, &data_dir),
+        "diff" => diff_command(&args, &config_dir, &data_dir),
+        "export" => export_command(&args, &config_dir, &data_dir),
+        _ => unreachable!("command checked against COMMANDS"),
+    });
+
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            Some(EXIT_FAILURE)
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Loading a profile into a running game from the console
+//!
+//! A profile exported to the mappings folder can be loaded mid-session with
+//! `pp_rebindkeys <name>`, no restart needed. Alongside the mapping file we can write a
+//! small console script into the installation folder, so the command doesn't have to be
+//! remembered: `exec boxxy_<name>.cfg` runs it. The script also carries the instructions
+//! as comments, for whoever opens it later.
+
+/// Prefix of the console scripts we write, keeping them apart from the player's own .cfg files
+const SCRIPT_PREFIX: &str = "boxxy_";
+
+/// Mapping names end up in a file name and a console command, so only letters, digits,
+/// `_` and `-` are kept
+pub fn mapping_name(name: &str) -> Result<String, String> {
+    let name: String = name
+        .trim()
+        .trim_end_matches(".xml")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        return Err("Mapping name cannot be empty".to_string());
+    }
+    Ok(name)
+}
+
+/// The console command that loads a mapping file
+pub fn rebind_command(mapping_name: &str) -> String {
+    format!("pp_rebindkeys {}", mapping_name)
+}
+
+/// File name of the console script for a mapping, placed in the installation folder
+pub fn script_file_name(mapping_name: &str) -> String {
+    format!("{}{}.cfg", SCRIPT_PREFIX, mapping_name)
+}
+
+/// Steps for loading the mapping in a running game
+pub fn instructions(mapping_name: &str, with_script: bool) -> Vec<String> {
+    let mut steps = vec![
+        "In game, open the console with the ~ key (left of 1)".to_string(),
+        format!("Type: {}", rebind_command(mapping_name)),
+    ];
+    if with_script {
+        steps.push(format!(
+            "Or run the script instead: exec {}",
+            script_file_name(mapping_name)
+        ));
+    }
+    steps.push(
+        "The bindings take effect immediately; apply the profile to actionmaps.xml as \
+         usual to keep them after a restart"
+            .to_string(),
+    );
+    steps
+}
+
+/// Contents of the console script: the rebind command, with the instructions as comments
+pub fn script(mapping_name: &str) -> String {
+    let mut script = format!("; Boxxy Binder: load the \"{}\" mapping\n", mapping_name);
+    for step in instructions(mapping_name, true) {
+        script.push_str(&format!("; {}\n", step));
+    }
+    script.push_str(&rebind_command(mapping_name));
+    script.push('\n');
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_script() {
+        let name = mapping_name(" Dual sticks (PTU).xml").unwrap();
+        assert_eq!(name, "Dual_sticks__PTU_");
+        assert_eq!(script_file_name(&name), "boxxy_Dual_sticks__PTU_.cfg");
+        assert!(mapping_name(" ").is_err());
+
+        let script = script(&name);
+        // The only line the console executes is the command itself
+        let commands: Vec<&str> = script.lines().filter(|l| !l.starts_with(';')).collect();
+        assert_eq!(commands, vec!["pp_rebindkeys Dual_sticks__PTU_"]);
+    }
+}
@@ -0,0 +1,229 @@
+//! The game's preset control profiles ("Advanced HOTAS", "Gamepad"...)
+//!
+//! CIG ships a handful of complete layouts in Data.p4k under Data/Libs/Config/Mappings, in
+//! the same ActionMaps format as an exported mapping: a CustomisationUIHeader naming the
+//! layout and the devices it's for, device options, and the rebinds. Data.p4k itself is
+//! compressed and partly encrypted, so the presets are read from an extracted copy of that
+//! folder (unp4k and similar tools keep the Data/... layout). A preset can then seed a new
+//! library profile instead of starting from the raw defaults.
+
+use crate::controls::{self, ControlsFile};
+use crate::keybindings::ActionMaps;
+use quick_xml::events::{BytesStart, Event};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Where the presets sit inside Data.p4k, and so inside an extracted copy of it
+pub const PRESETS_SUBDIR: [&str; 4] = ["Data", "Libs", "Config", "Mappings"];
+
+/// A preset layout as shown in the picker
+#[derive(Debug, Serialize, Clone)]
+pub struct ControlPreset {
+    pub file_name: String,
+    pub path: String,
+    /// Display name, e.g. "Advanced HOTAS"
+    pub name: String,
+    /// CustomisationUIHeader label, usually a localization key
+    pub label: String,
+    pub description: String,
+    /// Device types the layout is for, e.g. ["keyboard", "joystick"]
+    pub devices: Vec<String>,
+    pub rebind_count: usize,
+}
+
+/// A preset turned into the starting point of a new profile
+pub struct PresetImport {
+    pub bindings: ActionMaps,
+    pub controls: ControlsFile,
+}
+
+/// The presets folder of an installation, or of wherever Data.p4k was extracted to.
+/// Accepts the Mappings folder itself too.
+pub fn presets_dir(base: &Path) -> PathBuf {
+    let nested = PRESETS_SUBDIR
+        .iter()
+        .fold(base.to_path_buf(), |p, c| p.join(c));
+    if nested.is_dir() {
+        nested
+    } else {
+        base.to_path_buf()
+    }
+}
+
+#[derive(Debug, Default)]
+struct PresetHeader {
+    profile_name: String,
+    label: String,
+    description: String,
+    devices: Vec<String>,
+}
+
+fn attr(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == key)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+/// Read the ActionMaps root and its CustomisationUIHeader; None if the file isn't a layout
+fn read_header(xml: &str) -> Option<PresetHeader> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut header = PresetHeader::default();
+    let mut is_layout = false;
+    let mut in_devices = false;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name().as_ref() {
+                b"ActionMaps" => {
+                    is_layout = true;
+                    header.profile_name = attr(e, b"profileName").unwrap_or_default();
+                }
+                b"CustomisationUIHeader" => {
+                    header.label = attr(e, b"label").unwrap_or_default();
+                    header.description = attr(e, b"description").unwrap_or_default();
+                }
+                b"devices" => in_devices = true,
+                name if in_devices => {
+                    let device = String::from_utf8_lossy(name).to_string();
+                    if !header.devices.contains(&device) {
+                        header.devices.push(device);
+                    }
+                }
+                // Everything we need comes before the first actionmap
+                b"actionmap" => break,
+                _ => {}
+            },
+            Ok(Event::End(ref e)) if e.name().as_ref() == b"devices" => in_devices = false,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    is_layout.then_some(header)
+}
+
+/// "Advanced_HOTAS" or "@ui_Advanced_HOTAS" -> "Advanced HOTAS"
+fn display_name(header: &PresetHeader, file_name: &str) -> String {
+    let raw = [header.profile_name.as_str(), header.label.as_str()]
+        .into_iter()
+        .find(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| file_name.trim_end_matches(".xml"));
+    let raw = raw.trim_start_matches('@');
+    let raw = raw.strip_prefix("ui_").unwrap_or(raw);
+    raw.replace('_', " ").trim().to_string()
+}
+
+fn summarize(path: &Path) -> Result<Option<ControlPreset>, String> {
+    let xml = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let Some(header) = read_header(&xml) else {
+        return Ok(None);
+    };
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    Ok(Some(ControlPreset {
+        name: display_name(&header, &file_name),
+        path: path.to_string_lossy().to_string(),
+        rebind_count: ActionMaps::from_xml(&xml)?.rebind_count(),
+        file_name,
+        label: header.label,
+        description: header.description,
+        devices: header.devices,
+    }))
+}
+
+/// List the preset layouts in a folder, by name. Files that aren't layouts are skipped,
+/// ones that fail to read are logged and skipped.
+pub fn list_presets(dir: &Path) -> Result<Vec<ControlPreset>, String> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read presets folder: {}", e))?;
+
+    let mut presets: Vec<ControlPreset> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"))
+        })
+        .filter_map(|path| match summarize(&path) {
+            Ok(preset) => preset,
+            Err(e) => {
+                log::warn!("Skipping preset: {}", e);
+                None
+            }
+        })
+        .collect();
+
+    presets.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    Ok(presets)
+}
+
+/// Read a preset's bindings and options, naming the result `profile_name` and leaving out
+/// `excluded` options
+pub fn import_preset(
+    path: &Path,
+    profile_name: &str,
+    excluded: &[String],
+) -> Result<PresetImport, String> {
+    let xml = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if read_header(&xml).is_none() {
+        return Err(format!("{} is not a control profile", path.display()));
+    }
+
+    let mut bindings = ActionMaps::from_xml(&xml)?;
+    bindings.profile_name = profile_name.to_string();
+    let controls = controls::controls_file_from_actionmaps_options(
+        profile_name.to_string(),
+        controls::parse_actionmaps_options(&xml)?,
+        excluded,
+    );
+    Ok(PresetImport { bindings, controls })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_header() {
+        let xml = r#"<ActionMaps version="1" optionsVersion="2" rebindVersion="2" profileName="Advanced_HOTAS">
+ <CustomisationUIHeader label="@ui_Advanced_HOTAS" description="@ui_Advanced_HOTAS_Desc" image="">
+  <devices>
+   <keyboard instance="1"/>
+   <joystick instance="1"/>
+   <joystick instance="2"/>
+  </devices>
+ </CustomisationUIHeader>
+ <actionmap name="spaceship_movement">
+  <action name="v_pitch">
+   <rebind input="js1_y"/>
+  </action>
+ </actionmap>
+</ActionMaps>"#;
+        let header = read_header(xml).unwrap();
+        assert_eq!(header.devices, vec!["keyboard", "joystick"]);
+        assert_eq!(header.description, "@ui_Advanced_HOTAS_Desc");
+        assert_eq!(
+            display_name(&header, "advanced_hotas.xml"),
+            "Advanced HOTAS"
+        );
+
+        let unnamed = PresetHeader::default();
+        assert_eq!(
+            display_name(&unnamed, "layout_gamepad.xml"),
+            "layout gamepad"
+        );
+
+        assert!(read_header("<profile version=\"1\"/>").is_none());
+    }
+}
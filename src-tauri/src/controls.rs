@@ -1,22 +1,284 @@
 //! Controls file handling for SC Joy Mapper
 //!
-//! This module handles saving/loading control settings (inversion only)
-//! to/from our custom .sccontrols JSON format.
-//!
-//! NOTE: Sensitivity curve and exponent settings are DISABLED because they do not
-//! persist properly in Star Citizen. Only inversion settings are functional.
+//! This module handles saving/loading control settings (inversion, deadzone, saturation,
+//! sensitivity curves and exponents) to/from our custom .sccontrols JSON format, and
+//! converting them to the options sections of actionmaps.xml.
 //!
 //! Star Citizen does NOT import curve settings from XML files - they must be applied
-//! directly to actionmaps.xml. However, even when applied directly, they don't persist
-//! across game restarts. This module provides:
-//! 1. Custom file format for saving/loading control configurations (inversion only)
-//! 2. Functions to apply settings to actionmaps.xml
-
+//! directly to actionmaps.xml, and the game drops them again when it rewrites the file
+//! on exit. So curves are only written when the caller asks for them (`include_curves`),
+//! and the curve watchdog (see `curve_watchdog`) re-applies them after each rewrite.
+//! Inversion, deadzone and saturation persist normally.
+//!
+//! Fields we don't recognize (added by newer versions of the app or by other tools)
+//! are captured in `extra` maps and written back out when the profile is re-saved.
+
+use crate::curve_interpolation;
+use crate::device_roles::DeviceRole;
+use crate::options_editor;
+use crate::vjoy_feeder::FeederConfig;
+use log::info;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Version of the controls file format
-pub const CONTROLS_FILE_VERSION: &str = "1.0";
+pub const CONTROLS_FILE_VERSION: &str = "1.9";
+
+/// Sensitivity range for gamepad options (UISensitivityMin/Max on the gamepad optiontree)
+pub const GAMEPAD_SENSITIVITY_RANGE: (f64, f64) = (0.01, 2.0);
+
+/// Sensitivity range for mouse options
+pub const MOUSE_SENSITIVITY_RANGE: (f64, f64) = (0.01, 10.0);
+
+/// Exponent range the game's options menu accepts for axis response
+pub const EXPONENT_RANGE: (f64, f64) = (0.5, 3.0);
+
+/// Range of deadzone, saturation and mouse smoothing, as fractions
+pub const UNIT_RANGE: (f64, f64) = (0.0, 1.0);
+
+/// A fraction clamped to `UNIT_RANGE`; values that aren't a number are dropped
+pub fn unit_value(value: Option<f64>) -> Option<f64> {
+    let (min, max) = UNIT_RANGE;
+    value.filter(|v| v.is_finite()).map(|v| v.clamp(min, max))
+}
+
+/// Version assumed for files written before the version field existed
+const LEGACY_CONTROLS_FILE_VERSION: &str = "0.0";
+
+/// A single upgrade step for the controls file schema.
+/// Migrations operate on the raw JSON so they can handle shapes the current structs can't parse.
+struct SchemaMigration {
+    /// Version this step upgrades from
+    from: &'static str,
+    /// Version this step produces
+    to: &'static str,
+    migrate: fn(&mut serde_json::Value) -> Result<(), String>,
+}
+
+/// Registry of schema migrations, oldest first.
+/// When the format changes: bump CONTROLS_FILE_VERSION and add a step from the previous version.
+const SCHEMA_MIGRATIONS: &[SchemaMigration] = &[
+    SchemaMigration {
+        from: LEGACY_CONTROLS_FILE_VERSION,
+        to: "1.0",
+        migrate: migrate_legacy_to_1_0,
+    },
+    SchemaMigration {
+        from: "1.0",
+        to: "1.1",
+        migrate: migrate_1_0_to_1_1,
+    },
+    SchemaMigration {
+        from: "1.1",
+        to: "1.2",
+        migrate: migrate_1_1_to_1_2,
+    },
+    SchemaMigration {
+        from: "1.2",
+        to: "1.3",
+        migrate: migrate_1_2_to_1_3,
+    },
+    SchemaMigration {
+        from: "1.3",
+        to: "1.4",
+        migrate: migrate_1_3_to_1_4,
+    },
+    SchemaMigration {
+        from: "1.4",
+        to: "1.5",
+        migrate: migrate_1_4_to_1_5,
+    },
+    SchemaMigration {
+        from: "1.5",
+        to: "1.6",
+        migrate: migrate_1_5_to_1_6,
+    },
+    SchemaMigration {
+        from: "1.6",
+        to: "1.7",
+        migrate: migrate_1_6_to_1_7,
+    },
+    SchemaMigration {
+        from: "1.7",
+        to: "1.8",
+        migrate: migrate_1_7_to_1_8,
+    },
+    SchemaMigration {
+        from: "1.8",
+        to: "1.9",
+        migrate: migrate_1_8_to_1_9,
+    },
+];
+
+/// Most changelog entries a profile keeps; older ones are dropped first
+const MAX_CHANGELOG_ENTRIES: usize = 50;
+
+/// Longest changelog message kept, in characters
+const MAX_CHANGELOG_MESSAGE_CHARS: usize = 500;
+
+/// Parse a "major.minor" version string into a comparable tuple
+fn parse_schema_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.trim().split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = match parts.next() {
+        Some(minor) => minor.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((major, minor))
+}
+
+/// Upgrade a raw controls file to the current schema version in place
+pub fn migrate_to_current(value: &mut serde_json::Value) -> Result<(), String> {
+    let original_version = value
+        .get("version")
+        .and_then(|v| v.as_str())
+        .unwrap_or(LEGACY_CONTROLS_FILE_VERSION)
+        .to_string();
+
+    let current = parse_schema_version(CONTROLS_FILE_VERSION)
+        .ok_or("Invalid CONTROLS_FILE_VERSION constant")?;
+    let mut version = parse_schema_version(&original_version)
+        .ok_or_else(|| format!("Unrecognized controls file version '{}'", original_version))?;
+
+    // A newer major version may have changed the meaning of existing fields, so refuse it.
+    // A newer minor version only adds fields, which we keep as unknown fields.
+    if version.0 > current.0 {
+        return Err(format!(
+            "This profile was saved in controls format {} which is newer than this version of Boxxy Binder supports ({}). Please update Boxxy Binder to open it.",
+            original_version, CONTROLS_FILE_VERSION
+        ));
+    }
+
+    if version > current {
+        info!(
+            "Controls file format {} is newer than {}; unknown fields will be preserved",
+            original_version, CONTROLS_FILE_VERSION
+        );
+        return Ok(());
+    }
+
+    while version < current {
+        let step = SCHEMA_MIGRATIONS
+            .iter()
+            .find(|m| parse_schema_version(m.from) == Some(version))
+            .ok_or_else(|| {
+                format!(
+                    "No migration available for controls format {}.{}",
+                    version.0, version.1
+                )
+            })?;
+
+        (step.migrate)(value)?;
+        value["version"] = serde_json::Value::String(step.to.to_string());
+        version = parse_schema_version(step.to).ok_or("Invalid migration target version")?;
+
+        info!(
+            "Migrated controls file from format {} to {}",
+            step.from, step.to
+        );
+    }
+
+    Ok(())
+}
+
+/// 1.1 only adds the optional changelog, which older files simply don't have
+fn migrate_1_0_to_1_1(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// 1.2 only adds the optional device role on joystick instances
+fn migrate_1_1_to_1_2(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// 1.3 only adds the optional gamepad attributes on options
+fn migrate_1_2_to_1_3(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// 1.4 only adds the optional mouse device and mouse smoothing on options
+fn migrate_1_3_to_1_4(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// 1.5 only adds the optional vJoy feeder section
+fn migrate_1_4_to_1_5(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// 1.6 only adds optional chords to the vJoy feeder
+fn migrate_1_5_to_1_6(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// 1.7 only adds optional macros to the vJoy feeder
+fn migrate_1_6_to_1_7(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// 1.8 only adds the optional game attributes section
+fn migrate_1_7_to_1_8(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// 1.9 only adds the optional curve interpolation, linear when missing
+fn migrate_1_8_to_1_9(_value: &mut serde_json::Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// Files from before the version field used the camelCase keys from the original design
+fn migrate_legacy_to_1_0(value: &mut serde_json::Value) -> Result<(), String> {
+    let root = value
+        .as_object_mut()
+        .ok_or("Controls file root is not an object")?;
+
+    for (old_key, new_key) in [
+        ("profileName", "profile_name"),
+        ("lastModified", "last_modified"),
+    ] {
+        if let Some(v) = root.remove(old_key) {
+            root.entry(new_key).or_insert(v);
+        }
+    }
+
+    root.entry("profile_name")
+        .or_insert_with(|| serde_json::Value::String("Untitled".to_string()));
+
+    let devices = root
+        .entry("devices")
+        .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+
+    // Collect every options map: keyboard/mouse/gamepad hold one directly, joystick holds one per instance
+    let mut option_maps = Vec::new();
+    if let Some(devices) = devices.as_object_mut() {
+        for (device_type, device) in devices.iter_mut() {
+            if device_type == "joystick" {
+                if let Some(instances) = device.as_object_mut() {
+                    option_maps.extend(instances.values_mut().filter_map(|i| i.get_mut("options")));
+                }
+            } else if let Some(options) = device.get_mut("options") {
+                option_maps.push(options);
+            }
+        }
+    }
+
+    for options in option_maps {
+        if let Some(options) = options.as_object_mut() {
+            for setting in options.values_mut() {
+                if let Some(setting) = setting.as_object_mut() {
+                    if let Some(mode) = setting.remove("curveMode") {
+                        setting.entry("curve_mode").or_insert(mode);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
 
 /// A point on a response curve
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -27,20 +289,60 @@ pub struct CurvePoint {
     pub output: f64,
 }
 
+/// How the editor joins curve points. SC itself only draws straight segments, so
+/// anything smoother is resampled into dense points when written to actionmaps.xml.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveInterpolation {
+    #[default]
+    Linear,
+    /// Monotone cubic spline through the points
+    Spline,
+    /// Bezier curve using the points as control points
+    Bezier,
+}
+
+impl CurveInterpolation {
+    pub fn is_linear(&self) -> bool {
+        *self == CurveInterpolation::Linear
+    }
+}
+
 /// Curve data for an option
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CurveData {
     #[serde(default)]
     pub points: Vec<CurvePoint>,
+    #[serde(default, skip_serializing_if = "CurveInterpolation::is_linear")]
+    pub interpolation: CurveInterpolation,
 }
 
+/// Unknown fields captured during deserialization so they survive a load/save round trip
+pub type ExtraFields = serde_json::Map<String, serde_json::Value>;
+
 /// Settings for a single control option
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ControlOptionSettings {
     /// Whether the axis is inverted
     #[serde(skip_serializing_if = "Option::is_none")]
     pub invert: Option<bool>,
 
+    /// Deadzone around the axis center (0.0 - 1.0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadzone: Option<f64>,
+
+    /// Saturation point, input past this is treated as full deflection (0.0 - 1.0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation: Option<f64>,
+
+    /// Sensitivity multiplier, for gamepad (e.g. thumbstick aim sensitivity) and mouse options
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<f64>,
+
+    /// Mouse-only input smoothing (0.0 - 1.0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoothing: Option<f64>,
+
     /// The curve mode: "exponent" or "curve"
     #[serde(skip_serializing_if = "Option::is_none")]
     pub curve_mode: Option<String>,
@@ -52,18 +354,35 @@ pub struct ControlOptionSettings {
     /// Custom curve points (used when curve_mode is "curve")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub curve: Option<CurveData>,
+
+    /// Other attributes SC keeps on a gamepad option (e.g. toggles only the gamepad
+    /// optiontree has), carried from actionmaps.xml and written back unchanged
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub gamepad_attributes: BTreeMap<String, String>,
+
+    /// Fields we don't know about, preserved as-is
+    #[serde(flatten, default, skip_serializing_if = "ExtraFields::is_empty")]
+    pub extra: ExtraFields,
 }
 
 /// Settings for a specific device instance
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct DeviceInstanceSettings {
     /// The Product string for this device (for identification)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub product: Option<String>,
 
+    /// What the device is for (stick, throttle, pedals...), for joystick instances
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<DeviceRole>,
+
     /// Control options for this device instance
     /// Key is the option name (e.g., "flight_move_pitch")
     pub options: HashMap<String, ControlOptionSettings>,
+
+    /// Fields we don't know about, preserved as-is
+    #[serde(flatten, default, skip_serializing_if = "ExtraFields::is_empty")]
+    pub extra: ExtraFields,
 }
 
 /// Settings for all joystick instances (for future use)
@@ -81,11 +400,29 @@ pub struct DeviceSettings {
     #[serde(default)]
     pub keyboard: Option<DeviceInstanceSettings>,
 
+    #[serde(default)]
+    pub mouse: Option<DeviceInstanceSettings>,
+
     #[serde(default)]
     pub gamepad: Option<DeviceInstanceSettings>,
 
     #[serde(default)]
     pub joystick: Option<HashMap<String, DeviceInstanceSettings>>,
+
+    /// Device types we don't know about, preserved as-is
+    #[serde(flatten, default, skip_serializing_if = "ExtraFields::is_empty")]
+    pub extra: ExtraFields,
+}
+
+/// One save of a profile, as recorded in its changelog
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChangelogEntry {
+    /// ISO timestamp of the save
+    pub timestamp: String,
+    /// Options added, removed or changed by the save
+    pub changed_options: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 /// The main controls file structure
@@ -103,6 +440,22 @@ pub struct ControlsFile {
 
     /// Device-specific settings
     pub devices: DeviceSettings,
+
+    /// History of saves, oldest first, so a shared profile carries its own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changelog: Vec<ChangelogEntry>,
+
+    /// What the vJoy feeder does with this profile (shift layers, chords, macros)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feeder: Option<FeederConfig>,
+
+    /// Settings from the game's attributes.xml to apply with the profile, by attribute name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub game_attributes: BTreeMap<String, String>,
+
+    /// Fields we don't know about, preserved as-is
+    #[serde(flatten, default, skip_serializing_if = "ExtraFields::is_empty")]
+    pub extra: ExtraFields,
 }
 
 impl ControlsFile {
@@ -113,12 +466,23 @@ impl ControlsFile {
             profile_name,
             last_modified: Some(chrono::Utc::now().to_rfc3339()),
             devices: DeviceSettings::default(),
+            changelog: Vec::new(),
+            feeder: None,
+            game_attributes: BTreeMap::new(),
+            extra: ExtraFields::new(),
         }
     }
 
-    /// Parse controls file from JSON string
+    /// Parse controls file from JSON string, upgrading older schema versions on the way.
+    /// Files from a newer major version are rejected rather than silently misread; newer
+    /// minor versions load, with any fields we don't understand kept in `extra`.
     pub fn from_json(json: &str) -> Result<Self, String> {
-        serde_json::from_str(json).map_err(|e| format!("Failed to parse controls file: {}", e))
+        let mut value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse controls file: {}", e))?;
+
+        migrate_to_current(&mut value)?;
+
+        serde_json::from_value(value).map_err(|e| format!("Failed to parse controls file: {}", e))
     }
 
     /// Serialize controls file to JSON string
@@ -127,11 +491,229 @@ impl ControlsFile {
             .map_err(|e| format!("Failed to serialize controls file: {}", e))
     }
 
-    /// Update the last_modified timestamp to now (for future use)
-    #[allow(dead_code)]
+    /// Copy unknown fields from a previously saved version of this profile.
+    ///
+    /// Profiles are rebuilt from frontend input on save, which only carries the fields this
+    /// version understands. Carrying over `extra` from the file on disk keeps data written by
+    /// newer versions or other tools instead of silently dropping it.
+    pub fn preserve_unknown_fields(&mut self, previous: &ControlsFile) {
+        for (key, value) in &previous.extra {
+            self.extra.entry(key.clone()).or_insert(value.clone());
+        }
+        for (key, value) in &previous.devices.extra {
+            self.devices
+                .extra
+                .entry(key.clone())
+                .or_insert(value.clone());
+        }
+
+        // Never downgrade the version of a file written by a newer minor version
+        if parse_schema_version(&previous.version) > parse_schema_version(&self.version) {
+            self.version = previous.version.clone();
+        }
+
+        fn carry_device(current: &mut DeviceInstanceSettings, previous: &DeviceInstanceSettings) {
+            for (key, value) in &previous.extra {
+                current.extra.entry(key.clone()).or_insert(value.clone());
+            }
+            for (name, settings) in current.options.iter_mut() {
+                if let Some(prev) = previous.options.get(name) {
+                    for (key, value) in &prev.extra {
+                        settings.extra.entry(key.clone()).or_insert(value.clone());
+                    }
+                    if settings.gamepad_attributes.is_empty() {
+                        settings.gamepad_attributes = prev.gamepad_attributes.clone();
+                    }
+                }
+            }
+        }
+
+        if let (Some(current), Some(prev)) =
+            (&mut self.devices.keyboard, &previous.devices.keyboard)
+        {
+            carry_device(current, prev);
+        }
+        if let (Some(current), Some(prev)) = (&mut self.devices.mouse, &previous.devices.mouse) {
+            carry_device(current, prev);
+        }
+        if let (Some(current), Some(prev)) = (&mut self.devices.gamepad, &previous.devices.gamepad)
+        {
+            carry_device(current, prev);
+        }
+        if let (Some(current), Some(prev)) =
+            (&mut self.devices.joystick, &previous.devices.joystick)
+        {
+            for (instance, settings) in current.iter_mut() {
+                if let Some(prev_settings) = prev.get(instance) {
+                    carry_device(settings, prev_settings);
+                }
+            }
+        }
+    }
+
+    /// Keep the roles `previous` gave joystick instances that this file leaves unset
+    pub fn carry_device_roles(&mut self, previous: &ControlsFile) {
+        for (instance, device) in previous.devices.joystick.iter().flatten() {
+            let Some(role) = device.role else {
+                continue;
+            };
+            let current = self
+                .devices
+                .joystick
+                .get_or_insert_with(HashMap::new)
+                .entry(instance.clone())
+                .or_default();
+            current.role.get_or_insert(role);
+            if current.product.is_none() {
+                current.product = device.product.clone();
+            }
+        }
+    }
+
+    /// Update the last_modified timestamp to now
     pub fn touch(&mut self) {
         self.last_modified = Some(chrono::Utc::now().to_rfc3339());
     }
+
+    /// Add a changelog entry for a save, trimming the message and the history to size
+    pub fn record_change(&mut self, changed_options: usize, message: Option<&str>) {
+        let message = message
+            .map(|m| {
+                m.trim()
+                    .chars()
+                    .take(MAX_CHANGELOG_MESSAGE_CHARS)
+                    .collect::<String>()
+            })
+            .filter(|m| !m.is_empty());
+        self.changelog.push(ChangelogEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            changed_options,
+            message,
+        });
+        let excess = self.changelog.len().saturating_sub(MAX_CHANGELOG_ENTRIES);
+        self.changelog.drain(..excess);
+    }
+
+    /// Get a device's settings, adding an empty entry if the profile doesn't cover it yet
+    pub fn device_mut(
+        &mut self,
+        device_type: &str,
+        instance: &str,
+    ) -> Result<&mut DeviceInstanceSettings, String> {
+        match device_type {
+            "keyboard" => Ok(self.devices.keyboard.get_or_insert_with(Default::default)),
+            "mouse" => Ok(self.devices.mouse.get_or_insert_with(Default::default)),
+            "gamepad" => Ok(self.devices.gamepad.get_or_insert_with(Default::default)),
+            "joystick" => Ok(self
+                .devices
+                .joystick
+                .get_or_insert_with(HashMap::new)
+                .entry(instance.to_string())
+                .or_default()),
+            _ => Err(format!("Unknown device type: {}", device_type)),
+        }
+    }
+
+    /// Get a device's settings, if the profile has any
+    pub fn device(&self, device_type: &str, instance: &str) -> Option<&DeviceInstanceSettings> {
+        match device_type {
+            "keyboard" => self.devices.keyboard.as_ref(),
+            "mouse" => self.devices.mouse.as_ref(),
+            "gamepad" => self.devices.gamepad.as_ref(),
+            "joystick" => self.devices.joystick.as_ref()?.get(instance),
+            _ => None,
+        }
+    }
+
+    /// Copy every option (inversion, curves, deadzones...) from one device to another, except
+    /// those named in `exclude`. Options the target already has are overwritten, the rest of
+    /// the target is kept, and its Product string stays its own. Returns how many were copied.
+    pub fn copy_device_options(
+        &mut self,
+        from: (&str, &str),
+        to: (&str, &str),
+        exclude: &[String],
+    ) -> Result<usize, String> {
+        if from == to {
+            return Err("Source and target are the same device".to_string());
+        }
+        let options: Vec<(String, ControlOptionSettings)> = self
+            .device(from.0, from.1)
+            .ok_or_else(|| format!("The profile has no settings for {} {}", from.0, from.1))?
+            .options
+            .iter()
+            .filter(|(name, _)| !exclude.contains(name))
+            .map(|(name, settings)| (name.clone(), settings.clone()))
+            .collect();
+
+        let count = options.len();
+        self.device_mut(to.0, to.1)?.options.extend(options);
+        Ok(count)
+    }
+
+    /// Set or toggle inversion on many options of one device at once. `axes` lists the
+    /// option names with their default invert state, which is what a toggle starts from
+    /// when the profile doesn't set the option yet. Options the profile already inverts
+    /// explicitly are included too. Returns how many options changed.
+    pub fn invert_device_options(
+        &mut self,
+        device_type: &str,
+        instance: &str,
+        axes: &[(String, bool)],
+        axis_filter: &[String],
+        mode: InvertMode,
+    ) -> Result<usize, String> {
+        let device = self.device_mut(device_type, instance)?;
+
+        let mut targets: Vec<(String, bool)> = axes.to_vec();
+        for (name, settings) in &device.options {
+            if settings.invert.is_some() && !targets.iter().any(|(n, _)| n == name) {
+                targets.push((name.clone(), false));
+            }
+        }
+
+        let mut changed = 0;
+        for (name, default) in targets {
+            if !matches_axis_filter(&name, axis_filter) {
+                continue;
+            }
+            let current = device
+                .options
+                .get(&name)
+                .and_then(|settings| settings.invert)
+                .unwrap_or(default);
+            let inverted = match mode {
+                InvertMode::Toggle => !current,
+                InvertMode::Set(value) => value,
+            };
+            // Leave options alone that already end up the way they should
+            if inverted != current {
+                device.options.entry(name).or_default().invert = Some(inverted);
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// How a bulk invert changes each option
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvertMode {
+    Toggle,
+    Set(bool),
+}
+
+/// Whether an option is about one of the given axes, e.g. ["pitch", "roll", "yaw"]. Axis
+/// names are matched against whole words of the option name, so "roll" matches
+/// flight_move_roll but not a hypothetical flight_scroll. An empty filter matches all.
+pub fn matches_axis_filter(name: &str, axis_filter: &[String]) -> bool {
+    axis_filter.is_empty()
+        || name.split('_').any(|word| {
+            axis_filter
+                .iter()
+                .any(|axis| axis.eq_ignore_ascii_case(word))
+        })
 }
 
 /// Input from the frontend for saving controls
@@ -147,11 +729,18 @@ pub struct DeviceSettingsInput {
     #[serde(default)]
     pub keyboard: Option<HashMap<String, ControlOptionInput>>,
 
+    #[serde(default)]
+    pub mouse: Option<HashMap<String, ControlOptionInput>>,
+
     #[serde(default)]
     pub gamepad: Option<HashMap<String, ControlOptionInput>>,
 
     #[serde(default)]
     pub joystick: Option<HashMap<String, HashMap<String, ControlOptionInput>>>,
+
+    /// Role of each joystick instance
+    #[serde(default)]
+    pub roles: Option<HashMap<String, DeviceRole>>,
 }
 
 /// Control option input from frontend
@@ -160,6 +749,18 @@ pub struct ControlOptionInput {
     #[serde(default)]
     pub invert: Option<bool>,
 
+    #[serde(default)]
+    pub deadzone: Option<f64>,
+
+    #[serde(default)]
+    pub saturation: Option<f64>,
+
+    #[serde(default)]
+    pub sensitivity: Option<f64>,
+
+    #[serde(default)]
+    pub smoothing: Option<f64>,
+
     #[serde(default, rename = "curveMode")]
     pub curve_mode: Option<String>,
 
@@ -168,6 +769,9 @@ pub struct ControlOptionInput {
 
     #[serde(default)]
     pub curve: Option<CurveInput>,
+
+    #[serde(default, rename = "gamepadAttributes")]
+    pub gamepad_attributes: BTreeMap<String, String>,
 }
 
 /// Curve input from frontend
@@ -175,6 +779,8 @@ pub struct ControlOptionInput {
 pub struct CurveInput {
     #[serde(default)]
     pub points: Vec<CurvePointInput>,
+    #[serde(default)]
+    pub interpolation: CurveInterpolation,
 }
 
 /// Curve point input from frontend
@@ -197,6 +803,19 @@ impl From<SaveControlsInput> for ControlsFile {
                 file.devices.keyboard = Some(DeviceInstanceSettings {
                     product: None,
                     options,
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Convert mouse settings
+        if let Some(mouse_opts) = input.devices.mouse {
+            let options = convert_options_map(mouse_opts);
+            if !options.is_empty() {
+                file.devices.mouse = Some(DeviceInstanceSettings {
+                    product: None,
+                    options,
+                    ..Default::default()
                 });
             }
         }
@@ -208,6 +827,7 @@ impl From<SaveControlsInput> for ControlsFile {
                 file.devices.gamepad = Some(DeviceInstanceSettings {
                     product: None,
                     options,
+                    ..Default::default()
                 });
             }
         }
@@ -223,6 +843,7 @@ impl From<SaveControlsInput> for ControlsFile {
                         DeviceInstanceSettings {
                             product: None,
                             options,
+                            ..Default::default()
                         },
                     );
                 }
@@ -232,6 +853,12 @@ impl From<SaveControlsInput> for ControlsFile {
             }
         }
 
+        for (instance, role) in input.devices.roles.into_iter().flatten() {
+            if let Ok(device) = file.device_mut("joystick", &instance) {
+                device.role = Some(role);
+            }
+        }
+
         file
     }
 }
@@ -245,6 +872,10 @@ fn convert_options_map(
     for (name, opt) in opts {
         let settings = ControlOptionSettings {
             invert: opt.invert,
+            deadzone: unit_value(opt.deadzone),
+            saturation: unit_value(opt.saturation),
+            sensitivity: opt.sensitivity,
+            smoothing: opt.smoothing,
             curve_mode: opt.curve_mode,
             exponent: opt.exponent,
             curve: opt.curve.map(|c| CurveData {
@@ -256,14 +887,22 @@ fn convert_options_map(
                         output: p.output,
                     })
                     .collect(),
+                interpolation: c.interpolation,
             }),
+            gamepad_attributes: opt.gamepad_attributes,
+            ..Default::default()
         };
 
         // Only add if there's at least one non-None field
         if settings.invert.is_some()
+            || settings.deadzone.is_some()
+            || settings.saturation.is_some()
+            || settings.sensitivity.is_some()
+            || settings.smoothing.is_some()
             || settings.curve_mode.is_some()
             || settings.exponent.is_some()
             || settings.curve.is_some()
+            || !settings.gamepad_attributes.is_empty()
         {
             result.insert(name, settings);
         }
@@ -279,6 +918,7 @@ pub struct LoadControlsOutput {
     pub profile_name: String,
     pub last_modified: Option<String>,
     pub devices: DeviceSettingsOutput,
+    pub changelog: Vec<ChangelogEntry>,
 }
 
 #[derive(Debug, Serialize)]
@@ -286,11 +926,18 @@ pub struct DeviceSettingsOutput {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub keyboard: Option<HashMap<String, ControlOptionOutput>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mouse: Option<HashMap<String, ControlOptionOutput>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gamepad: Option<HashMap<String, ControlOptionOutput>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub joystick: Option<HashMap<String, HashMap<String, ControlOptionOutput>>>,
+
+    /// Role of each joystick instance that has one
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub roles: HashMap<String, DeviceRole>,
 }
 
 #[derive(Debug, Serialize)]
@@ -298,6 +945,18 @@ pub struct ControlOptionOutput {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub invert: Option<bool>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadzone: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoothing: Option<f64>,
+
     #[serde(skip_serializing_if = "Option::is_none", rename = "curveMode")]
     pub curve_mode: Option<String>,
 
@@ -306,11 +965,18 @@ pub struct ControlOptionOutput {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub curve: Option<CurveOutputData>,
+
+    #[serde(
+        skip_serializing_if = "BTreeMap::is_empty",
+        rename = "gamepadAttributes"
+    )]
+    pub gamepad_attributes: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct CurveOutputData {
     pub points: Vec<CurvePointOutput>,
+    pub interpolation: CurveInterpolation,
 }
 
 #[derive(Debug, Serialize)]
@@ -323,12 +989,20 @@ pub struct CurvePointOutput {
 
 impl From<ControlsFile> for LoadControlsOutput {
     fn from(file: ControlsFile) -> Self {
+        let roles = file
+            .devices
+            .joystick
+            .iter()
+            .flatten()
+            .filter_map(|(instance, device)| Some((instance.clone(), device.role?)))
+            .collect();
         LoadControlsOutput {
             version: file.version,
             profile_name: file.profile_name,
             last_modified: file.last_modified,
             devices: DeviceSettingsOutput {
                 keyboard: file.devices.keyboard.map(convert_device_to_output),
+                mouse: file.devices.mouse.map(convert_device_to_output),
                 gamepad: file.devices.gamepad.map(convert_device_to_output),
                 joystick: file.devices.joystick.map(|instances| {
                     instances
@@ -336,7 +1010,9 @@ impl From<ControlsFile> for LoadControlsOutput {
                         .map(|(k, v)| (k, convert_device_to_output(v)))
                         .collect()
                 }),
+                roles,
             },
+            changelog: file.changelog,
         }
     }
 }
@@ -352,6 +1028,10 @@ fn convert_device_to_output(
                 name,
                 ControlOptionOutput {
                     invert: settings.invert,
+                    deadzone: settings.deadzone,
+                    saturation: settings.saturation,
+                    sensitivity: settings.sensitivity,
+                    smoothing: settings.smoothing,
                     curve_mode: settings.curve_mode,
                     exponent: settings.exponent,
                     curve: settings.curve.map(|c| CurveOutputData {
@@ -363,7 +1043,9 @@ fn convert_device_to_output(
                                 output: p.output,
                             })
                             .collect(),
+                        interpolation: c.interpolation,
                     }),
+                    gamepad_attributes: settings.gamepad_attributes,
                 },
             )
         })
@@ -378,170 +1060,234 @@ fn convert_device_to_output(
 #[derive(Debug, Serialize)]
 pub struct ApplyControlsResult {
     pub success: bool,
-    pub backup_path: Option<String>,
+    /// Snapshot of actionmaps.xml taken just before writing it
+    pub snapshot: Option<crate::snapshots::SnapshotMeta>,
     pub message: String,
+    /// How many times the write was attempted (more than 1 if the game rewrote the file meanwhile)
+    pub attempts: u32,
+    /// Options the game changed mid-apply that were then overwritten, e.g. "joystick 1 flight_move_pitch"
+    pub conflicts: Vec<String>,
+    /// Star Citizen running from the target installation. Without `force` nothing was
+    /// written (`success` is false).
+    pub game_running: Option<crate::game_process::GameProcess>,
+    /// Set when the apply was held back as too large; nothing was written
+    pub confirmation: Option<crate::apply_guard::ConfirmationRequired>,
+    /// Option names the installed game version doesn't know, found before writing
+    pub warnings: Vec<crate::identifier_check::ValidationWarning>,
+}
+
+/// An attribute's value with XML entities decoded (`&amp;` -> `&`), so the values we hold
+/// are always plain text and are escaped again by generate_options_xml
+fn attribute_value(attr: &quick_xml::events::attributes::Attribute) -> String {
+    attr.unescape_value()
+        .map(|v| v.into_owned())
+        .unwrap_or_else(|_| String::from_utf8_lossy(&attr.value).into_owned())
 }
 
+/// Read buffer for streaming actionmaps.xml from disk. Memory use stays around this plus
+/// the largest single element, however big the file is.
+const ACTIONMAPS_READ_BUFFER: usize = 64 * 1024;
+
 /// Parse the actionmaps.xml file and extract current control options
 pub fn parse_actionmaps_options(xml: &str) -> Result<Vec<ActionmapsDeviceOptions>, String> {
-    use quick_xml::events::Event;
-    use quick_xml::Reader;
+    let mut devices = Vec::new();
+    for_each_actionmaps_device(xml.as_bytes(), |device| devices.push(device))?;
+    Ok(devices)
+}
 
-    let mut reader = Reader::from_str(xml);
-    let mut buf = Vec::new();
+/// `parse_actionmaps_options` streamed from disk, without reading the whole file first
+pub fn parse_actionmaps_options_file(
+    path: &std::path::Path,
+) -> Result<Vec<ActionmapsDeviceOptions>, String> {
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
     let mut devices = Vec::new();
+    for_each_actionmaps_device(
+        std::io::BufReader::with_capacity(ACTIONMAPS_READ_BUFFER, file),
+        |device| devices.push(device),
+    )?;
+    Ok(devices)
+}
+
+/// Streaming variant of `parse_actionmaps_options`: each device's options are handed to
+/// `on_device` as soon as its `<options>` element closes. Returns the number of devices.
+///
+/// Anything we don't model is kept so it can be written back untouched: extra attributes
+/// on `<options>` and child elements of an option other than `<nonlinearity_curve>`
+/// (re-emitted event by event, which reproduces the raw XML).
+pub fn for_each_actionmaps_device<R: std::io::BufRead>(
+    source: R,
+    mut on_device: impl FnMut(ActionmapsDeviceOptions),
+) -> Result<usize, String> {
+    use quick_xml::events::{BytesStart, Event};
+    use quick_xml::{Reader, Writer};
+
+    fn device_from(e: &BytesStart) -> ActionmapsDeviceOptions {
+        let mut device = ActionmapsDeviceOptions {
+            device_type: String::new(),
+            instance: String::new(),
+            product: String::new(),
+            options: Vec::new(),
+            extra_attributes: Vec::new(),
+        };
+        for attr in e.attributes().flatten() {
+            match attr.key.as_ref() {
+                b"type" => device.device_type = attribute_value(&attr),
+                b"instance" => device.instance = attribute_value(&attr),
+                b"Product" => device.product = attribute_value(&attr),
+                key => device.extra_attributes.push((
+                    String::from_utf8_lossy(key).into_owned(),
+                    attribute_value(&attr),
+                )),
+            }
+        }
+        device
+    }
+
+    fn option_from(e: &BytesStart) -> ActionmapsControlOption {
+        ActionmapsControlOption {
+            name: String::from_utf8_lossy(e.name().as_ref()).into_owned(),
+            attributes: e
+                .attributes()
+                .flatten()
+                .map(|attr| {
+                    (
+                        String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+                        attribute_value(&attr),
+                    )
+                })
+                .collect(),
+            curve_points: Vec::new(),
+            extra_children: Vec::new(),
+        }
+    }
+
+    fn raw_xml(writer: Writer<Vec<u8>>) -> String {
+        String::from_utf8_lossy(&writer.into_inner()).into_owned()
+    }
+
+    let mut reader = Reader::from_reader(source);
+    let mut buf = Vec::new();
+    let mut device_count = 0;
     let mut current_device: Option<ActionmapsDeviceOptions> = None;
     let mut current_option: Option<ActionmapsControlOption> = None;
     let mut in_curve = false;
+    // Copy and nesting depth of an unknown child element being kept verbatim
+    let mut unknown_child: Option<(Writer<Vec<u8>>, usize)> = None;
 
     loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                match e.name().as_ref() {
-                    b"options" => {
-                        let mut device_type = String::new();
-                        let mut instance = String::new();
-                        let mut product = String::new();
-
-                        for attr in e.attributes().flatten() {
-                            match attr.key.as_ref() {
-                                b"type" => {
-                                    device_type = String::from_utf8_lossy(&attr.value).into_owned();
-                                }
-                                b"instance" => {
-                                    instance = String::from_utf8_lossy(&attr.value).into_owned();
-                                }
-                                b"Product" => {
-                                    product = String::from_utf8_lossy(&attr.value).into_owned();
-                                }
-                                _ => {}
-                            }
-                        }
-
-                        current_device = Some(ActionmapsDeviceOptions {
-                            device_type,
-                            instance,
-                            product,
-                            options: Vec::new(),
-                        });
+        let event = reader.read_event_into(&mut buf);
+
+        if let Some((writer, depth)) = unknown_child.as_mut() {
+            match event {
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(format!("XML parse error: {}", e)),
+                Ok(event) => {
+                    match event {
+                        Event::Start(_) => *depth += 1,
+                        Event::End(_) => *depth -= 1,
+                        _ => {}
                     }
-                    b"nonlinearity_curve" => {
-                        in_curve = true;
-                    }
-                    _ if current_device.is_some() && !in_curve => {
-                        // This is a control option element
-                        let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
-                        let mut attributes = Vec::new();
-
-                        for attr in e.attributes().flatten() {
-                            let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
-                            let value = String::from_utf8_lossy(&attr.value).into_owned();
-                            attributes.push((key, value));
+                    writer
+                        .write_event(event)
+                        .map_err(|e| format!("XML parse error: {}", e))?;
+                    if *depth == 0 {
+                        if let (Some((writer, _)), Some(opt)) =
+                            (unknown_child.take(), current_option.as_mut())
+                        {
+                            opt.extra_children.push(raw_xml(writer));
                         }
-
-                        current_option = Some(ActionmapsControlOption {
-                            name,
-                            attributes,
-                            curve_points: Vec::new(),
-                        });
                     }
-                    _ => {}
                 }
             }
-            Ok(Event::Empty(ref e)) => {
-                match e.name().as_ref() {
-                    b"options" => {
-                        // Self-closing options tag
-                        let mut device_type = String::new();
-                        let mut instance = String::new();
-                        let mut product = String::new();
+            buf.clear();
+            continue;
+        }
+
+        match event {
+            Ok(Event::Start(ref e)) => match e.name().as_ref() {
+                b"options" => {
+                    current_device = Some(device_from(e));
+                }
+                b"nonlinearity_curve" if current_option.is_some() => {
+                    in_curve = true;
+                }
+                _ if current_option.is_some() && !in_curve => {
+                    // A child of the option we don't understand
+                    let mut writer = Writer::new(Vec::new());
+                    writer
+                        .write_event(Event::Start(e.borrow()))
+                        .map_err(|e| format!("XML parse error: {}", e))?;
+                    unknown_child = Some((writer, 1));
+                }
+                _ if current_device.is_some() && !in_curve => {
+                    // This is a control option element
+                    current_option = Some(option_from(e));
+                }
+                _ => {}
+            },
+            Ok(Event::Empty(ref e)) => match e.name().as_ref() {
+                b"options" => {
+                    // Self-closing options tag
+                    device_count += 1;
+                    on_device(device_from(e));
+                }
+                b"point" if in_curve => {
+                    if let Some(ref mut opt) = current_option {
+                        let mut in_val = String::new();
+                        let mut out_val = String::new();
 
                         for attr in e.attributes().flatten() {
                             match attr.key.as_ref() {
-                                b"type" => {
-                                    device_type = String::from_utf8_lossy(&attr.value).into_owned();
-                                }
-                                b"instance" => {
-                                    instance = String::from_utf8_lossy(&attr.value).into_owned();
-                                }
-                                b"Product" => {
-                                    product = String::from_utf8_lossy(&attr.value).into_owned();
-                                }
+                                b"in" => in_val = attribute_value(&attr),
+                                b"out" => out_val = attribute_value(&attr),
                                 _ => {}
                             }
                         }
 
-                        devices.push(ActionmapsDeviceOptions {
-                            device_type,
-                            instance,
-                            product,
-                            options: Vec::new(),
-                        });
-                    }
-                    b"point" if in_curve => {
-                        if let Some(ref mut opt) = current_option {
-                            let mut in_val = String::new();
-                            let mut out_val = String::new();
-
-                            for attr in e.attributes().flatten() {
-                                match attr.key.as_ref() {
-                                    b"in" => {
-                                        in_val = String::from_utf8_lossy(&attr.value).into_owned();
-                                    }
-                                    b"out" => {
-                                        out_val = String::from_utf8_lossy(&attr.value).into_owned();
-                                    }
-                                    _ => {}
-                                }
-                            }
-
-                            opt.curve_points
-                                .push(ActionmapsCurvePoint { in_val, out_val });
-                        }
+                        opt.curve_points
+                            .push(ActionmapsCurvePoint { in_val, out_val });
                     }
-                    _ if current_device.is_some() && !in_curve => {
-                        // Self-closing control option
-                        let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
-                        let mut attributes = Vec::new();
-
-                        for attr in e.attributes().flatten() {
-                            let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
-                            let value = String::from_utf8_lossy(&attr.value).into_owned();
-                            attributes.push((key, value));
-                        }
-
-                        if let Some(ref mut device) = current_device {
-                            device.options.push(ActionmapsControlOption {
-                                name,
-                                attributes,
-                                curve_points: Vec::new(),
-                            });
-                        }
+                }
+                _ if current_option.is_some() && !in_curve => {
+                    // A self-closing child of the option we don't understand
+                    let mut writer = Writer::new(Vec::new());
+                    writer
+                        .write_event(Event::Empty(e.borrow()))
+                        .map_err(|e| format!("XML parse error: {}", e))?;
+                    if let Some(ref mut opt) = current_option {
+                        opt.extra_children.push(raw_xml(writer));
                     }
-                    _ => {}
                 }
-            }
-            Ok(Event::End(ref e)) => {
-                match e.name().as_ref() {
-                    b"options" => {
-                        if let Some(device) = current_device.take() {
-                            devices.push(device);
-                        }
+                _ if current_device.is_some() && !in_curve => {
+                    // Self-closing control option
+                    let option = option_from(e);
+                    if let Some(ref mut device) = current_device {
+                        device.options.push(option);
                     }
-                    b"nonlinearity_curve" => {
-                        in_curve = false;
+                }
+                _ => {}
+            },
+            Ok(Event::End(ref e)) => match e.name().as_ref() {
+                b"options" => {
+                    if let Some(device) = current_device.take() {
+                        device_count += 1;
+                        on_device(device);
                     }
-                    _ if current_option.is_some() && !in_curve => {
-                        // End of a control option with children
-                        if let Some(opt) = current_option.take() {
-                            if let Some(ref mut device) = current_device {
-                                device.options.push(opt);
-                            }
+                }
+                b"nonlinearity_curve" if in_curve => {
+                    in_curve = false;
+                }
+                _ if current_option.is_some() && !in_curve => {
+                    // End of a control option with children
+                    if let Some(opt) = current_option.take() {
+                        if let Some(ref mut device) = current_device {
+                            device.options.push(opt);
                         }
                     }
-                    _ => {}
                 }
-            }
+                _ => {}
+            },
             Ok(Event::Eof) => break,
             Err(e) => return Err(format!("XML parse error: {}", e)),
             _ => {}
@@ -549,115 +1295,259 @@ pub fn parse_actionmaps_options(xml: &str) -> Result<Vec<ActionmapsDeviceOptions
         buf.clear();
     }
 
-    Ok(devices)
+    Ok(device_count)
 }
 
-/// Device options from actionmaps.xml
-#[derive(Debug, Clone, Serialize)]
-pub struct ActionmapsDeviceOptions {
-    pub device_type: String,
-    pub instance: String,
-    pub product: String,
-    pub options: Vec<ActionmapsControlOption>,
-}
+/// Convert `<options>` parsed from an actionmaps.xml (or an exported layout file) into
+/// our controls file model, leaving out the `excluded` option names
+pub fn controls_file_from_actionmaps_options(
+    profile_name: String,
+    mut device_options: Vec<ActionmapsDeviceOptions>,
+    excluded: &[String],
+) -> ControlsFile {
+    let mut controls_file = ControlsFile::new(profile_name);
+    remove_excluded(&mut device_options, excluded);
+
+    for device in device_options {
+        let options: HashMap<String, ControlOptionSettings> = device
+            .options
+            .iter()
+            .map(|opt| {
+                let mut invert = None;
+                let mut deadzone = None;
+                let mut saturation = None;
+                let mut sensitivity = None;
+                let mut smoothing = None;
+                let mut exponent = None;
+                let mut gamepad_attributes = BTreeMap::new();
+
+                for (key, value) in &opt.attributes {
+                    match key.as_str() {
+                        "invert" => invert = Some(value == "1"),
+                        "deadzone" => deadzone = unit_value(value.parse().ok()),
+                        "saturation" => saturation = unit_value(value.parse().ok()),
+                        "sensitivity" => sensitivity = value.parse().ok(),
+                        "smoothing" if device.device_type == "mouse" => {
+                            smoothing = value.parse().ok()
+                        }
+                        "exponent" => exponent = value.parse().ok(),
+                        _ if device.device_type == "gamepad" => {
+                            gamepad_attributes.insert(key.clone(), value.clone());
+                        }
+                        _ => {}
+                    }
+                }
 
-/// A control option from actionmaps.xml
+                let curve = if opt.curve_points.is_empty() {
+                    None
+                } else {
+                    Some(CurveData {
+                        points: opt
+                            .curve_points
+                            .iter()
+                            .map(|p| CurvePoint {
+                                input: p.in_val.parse().unwrap_or(0.0),
+                                output: p.out_val.parse().unwrap_or(0.0),
+                            })
+                            .collect(),
+                        interpolation: CurveInterpolation::Linear,
+                    })
+                };
+
+                let curve_mode = if curve.is_some() {
+                    Some("curve".to_string())
+                } else if exponent.is_some() {
+                    Some("exponent".to_string())
+                } else {
+                    None
+                };
+
+                (
+                    opt.name.clone(),
+                    ControlOptionSettings {
+                        invert,
+                        deadzone,
+                        saturation,
+                        sensitivity,
+                        smoothing,
+                        curve_mode,
+                        exponent,
+                        curve,
+                        gamepad_attributes,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+
+        if !options.is_empty() {
+            let instance_settings = DeviceInstanceSettings {
+                product: Some(device.product.clone()),
+                options,
+                ..Default::default()
+            };
+
+            match device.device_type.as_str() {
+                "keyboard" => controls_file.devices.keyboard = Some(instance_settings),
+                "mouse" => controls_file.devices.mouse = Some(instance_settings),
+                "gamepad" => controls_file.devices.gamepad = Some(instance_settings),
+                "joystick" => {
+                    let joysticks = controls_file.devices.joystick.get_or_insert(HashMap::new());
+                    joysticks.insert(device.instance.clone(), instance_settings);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    controls_file
+}
+
+/// Device options from actionmaps.xml
 #[derive(Debug, Clone, Serialize)]
+pub struct ActionmapsDeviceOptions {
+    pub device_type: String,
+    pub instance: String,
+    pub product: String,
+    pub options: Vec<ActionmapsControlOption>,
+    /// Attributes on `<options>` other than type/instance/Product, kept as-is
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_attributes: Vec<(String, String)>,
+}
+
+/// A control option from actionmaps.xml
+#[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct ActionmapsControlOption {
     pub name: String,
     pub attributes: Vec<(String, String)>,
     pub curve_points: Vec<ActionmapsCurvePoint>,
+    /// Child elements other than `<nonlinearity_curve>`, as raw XML written back verbatim
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_children: Vec<String>,
 }
 
 /// A curve point from actionmaps.xml
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct ActionmapsCurvePoint {
     pub in_val: String,
     pub out_val: String,
 }
 
-/// Generate XML string for an options element with control settings
+/// Generate XML string for an options element with control settings.
+/// Attribute values are escaped, so a Product like `Saitek "X52" & Pro` stays valid XML.
+/// Unknown attributes and child elements carried from the parsed file are written back as-is.
 pub fn generate_options_xml(device: &ActionmapsDeviceOptions) -> String {
-    let mut xml = String::new();
+    use quick_xml::escape::escape;
+
+    let mut xml = format!(
+        "  <options type=\"{}\" instance=\"{}\"",
+        escape(&device.device_type),
+        escape(&device.instance)
+    );
+    if !device.product.is_empty() {
+        xml.push_str(&format!(" Product=\"{}\"", escape(&device.product)));
+    }
+    for (key, value) in &device.extra_attributes {
+        xml.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+    }
 
     if device.options.is_empty() {
         // Self-closing tag
-        xml.push_str(&format!(
-            "  <options type=\"{}\" instance=\"{}\"",
-            device.device_type, device.instance
-        ));
-        if !device.product.is_empty() {
-            xml.push_str(&format!(" Product=\"{}\"", device.product));
-        }
         xml.push_str("/>\n");
-    } else {
-        // Opening tag
-        xml.push_str(&format!(
-            "  <options type=\"{}\" instance=\"{}\"",
-            device.device_type, device.instance
-        ));
-        if !device.product.is_empty() {
-            xml.push_str(&format!(" Product=\"{}\"", device.product));
-        }
-        xml.push_str(">\n");
+        return xml;
+    }
+    xml.push_str(">\n");
 
-        // Control options
-        for opt in &device.options {
-            xml.push_str(&format!("   <{}", opt.name));
+    // Control options
+    for opt in &device.options {
+        xml.push_str(&generate_option_xml(opt, "   "));
+    }
 
-            // Attributes
-            for (key, value) in &opt.attributes {
-                xml.push_str(&format!(" {}=\"{}\"", key, value));
-            }
+    xml.push_str("  </options>\n");
+    xml
+}
 
-            if opt.curve_points.is_empty() {
-                xml.push_str("/>\n");
-            } else {
-                xml.push_str(">\n");
-                xml.push_str("    <nonlinearity_curve>\n");
-                for point in &opt.curve_points {
-                    xml.push_str(&format!(
-                        "     <point in=\"{}\" out=\"{}\"/>\n",
-                        point.in_val, point.out_val
-                    ));
-                }
-                xml.push_str("    </nonlinearity_curve>\n");
-                xml.push_str(&format!("   </{}>\n", opt.name));
-            }
-        }
+/// Generate the XML of a single option element, starting at `indent` and ending with a
+/// newline; its curve and child elements are indented one space further
+pub fn generate_option_xml(opt: &ActionmapsControlOption, indent: &str) -> String {
+    use quick_xml::escape::escape;
+
+    let mut xml = format!("{}<{}", indent, opt.name);
+    for (key, value) in &opt.attributes {
+        xml.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+    }
 
-        xml.push_str("  </options>\n");
+    if opt.curve_points.is_empty() && opt.extra_children.is_empty() {
+        xml.push_str("/>\n");
+        return xml;
     }
 
+    xml.push_str(">\n");
+    if !opt.curve_points.is_empty() {
+        xml.push_str(&format!("{} <nonlinearity_curve>\n", indent));
+        for point in &opt.curve_points {
+            xml.push_str(&format!(
+                "{}  <point in=\"{}\" out=\"{}\"/>\n",
+                indent,
+                escape(&point.in_val),
+                escape(&point.out_val)
+            ));
+        }
+        xml.push_str(&format!("{} </nonlinearity_curve>\n", indent));
+    }
+    for child in &opt.extra_children {
+        xml.push_str(&format!("{} {}\n", indent, child));
+    }
+    xml.push_str(&format!("{}</{}>\n", indent, opt.name));
     xml
 }
 
-/// Convert our ControlsFile format to ActionmapsDeviceOptions for writing
-pub fn controls_to_actionmaps(controls: &ControlsFile) -> Vec<ActionmapsDeviceOptions> {
+/// Convert our ControlsFile format to ActionmapsDeviceOptions for writing.
+/// Curve/exponent settings are only included when `include_curves` is set (see module notes).
+pub fn controls_to_actionmaps(
+    controls: &ControlsFile,
+    include_curves: bool,
+) -> Vec<ActionmapsDeviceOptions> {
     let mut result = Vec::new();
 
     // Convert keyboard
     if let Some(ref keyboard) = controls.devices.keyboard {
-        let options = convert_options_to_actionmaps(&keyboard.options);
+        let options = convert_options_to_actionmaps(&keyboard.options, "keyboard", include_curves);
         if !options.is_empty() {
             result.push(ActionmapsDeviceOptions {
                 device_type: "keyboard".to_string(),
                 instance: "1".to_string(),
                 product: keyboard.product.clone().unwrap_or_default(),
                 options,
+                extra_attributes: Vec::new(),
+            });
+        }
+    }
+
+    // Convert mouse
+    if let Some(ref mouse) = controls.devices.mouse {
+        let options = convert_options_to_actionmaps(&mouse.options, "mouse", include_curves);
+        if !options.is_empty() {
+            result.push(ActionmapsDeviceOptions {
+                device_type: "mouse".to_string(),
+                instance: "1".to_string(),
+                product: mouse.product.clone().unwrap_or_default(),
+                options,
+                extra_attributes: Vec::new(),
             });
         }
     }
 
     // Convert gamepad
     if let Some(ref gamepad) = controls.devices.gamepad {
-        let options = convert_options_to_actionmaps(&gamepad.options);
+        let options = convert_options_to_actionmaps(&gamepad.options, "gamepad", include_curves);
         if !options.is_empty() {
             result.push(ActionmapsDeviceOptions {
                 device_type: "gamepad".to_string(),
                 instance: "1".to_string(),
                 product: gamepad.product.clone().unwrap_or_default(),
                 options,
+                extra_attributes: Vec::new(),
             });
         }
     }
@@ -665,13 +1555,15 @@ pub fn controls_to_actionmaps(controls: &ControlsFile) -> Vec<ActionmapsDeviceOp
     // Convert joysticks
     if let Some(ref joysticks) = controls.devices.joystick {
         for (instance, settings) in joysticks {
-            let options = convert_options_to_actionmaps(&settings.options);
+            let options =
+                convert_options_to_actionmaps(&settings.options, "joystick", include_curves);
             if !options.is_empty() {
                 result.push(ActionmapsDeviceOptions {
                     device_type: "joystick".to_string(),
                     instance: instance.clone(),
                     product: settings.product.clone().unwrap_or_default(),
                     options,
+                    extra_attributes: Vec::new(),
                 });
             }
         }
@@ -680,17 +1572,26 @@ pub fn controls_to_actionmaps(controls: &ControlsFile) -> Vec<ActionmapsDeviceOp
     result
 }
 
+/// Gamepads use the same option names as joysticks but SC gives them their own semantics:
+/// thumbstick options take a `sensitivity` multiplier (e.g. aim sensitivity), and a few
+/// joystick-only options (zoom, mining throttle) don't exist on the gamepad optiontree.
+/// Mouse options take a sensitivity too, on their own range, and a `smoothing` fraction.
 fn convert_options_to_actionmaps(
     options: &HashMap<String, ControlOptionSettings>,
+    device_type: &str,
+    include_curves: bool,
 ) -> Vec<ActionmapsControlOption> {
+    let is_gamepad = device_type == "gamepad";
+    let is_mouse = device_type == "mouse";
+
     options
         .iter()
+        .filter(|(name, _)| !(is_gamepad && is_joystick_only_option(name)))
         .map(|(name, settings)| {
             let mut attributes = Vec::new();
-            let curve_points = Vec::new();
+            let mut curve_points = Vec::new();
 
-            // Only add invert attribute - curve/exponent are disabled
-            // because they don't persist in Star Citizen
+            // Invert/deadzone/saturation always persist in Star Citizen
             if let Some(invert) = settings.invert {
                 attributes.push((
                     "invert".to_string(),
@@ -698,19 +1599,424 @@ fn convert_options_to_actionmaps(
                 ));
             }
 
-            // NOTE: Curve and exponent settings are intentionally skipped
-            // They don't persist properly in Star Citizen, even when written to actionmaps.xml
+            if let Some(deadzone) = unit_value(settings.deadzone) {
+                attributes.push(("deadzone".to_string(), format!("{}", deadzone)));
+            }
+
+            if let Some(saturation) = unit_value(settings.saturation) {
+                attributes.push(("saturation".to_string(), format!("{}", saturation)));
+            }
+
+            if is_mouse {
+                if let Some(sensitivity) = settings.sensitivity.filter(|s| s.is_finite()) {
+                    let (min, max) = MOUSE_SENSITIVITY_RANGE;
+                    attributes.push((
+                        "sensitivity".to_string(),
+                        format!("{}", sensitivity.clamp(min, max)),
+                    ));
+                }
+                if let Some(smoothing) = unit_value(settings.smoothing) {
+                    attributes.push(("smoothing".to_string(), format!("{}", smoothing)));
+                }
+            }
+
+            // Otherwise sensitivity is only exposed on the gamepad optiontree
+            if is_gamepad {
+                if let Some(sensitivity) = settings.sensitivity {
+                    let (min, max) = GAMEPAD_SENSITIVITY_RANGE;
+                    attributes.push((
+                        "sensitivity".to_string(),
+                        format!("{}", sensitivity.clamp(min, max)),
+                    ));
+                }
+
+                // Gamepad-only attributes we don't model go back as they came
+                for (key, value) in &settings.gamepad_attributes {
+                    if GAMEPAD_RESERVED_ATTRIBUTES.contains(&key.as_str()) {
+                        continue;
+                    }
+                    attributes.push((key.clone(), value.clone()));
+                }
+            }
+
+            // NOTE: Curve and exponent settings are skipped for normal applies.
+            // They don't persist properly in Star Citizen, even when written to actionmaps.xml,
+            // so they are only written by the curve watchdog right after the game rewrites the file.
+            if include_curves {
+                if let Some(exponent) = settings.exponent.filter(|e| e.is_finite()) {
+                    let (min, max) = EXPONENT_RANGE;
+                    attributes.push((
+                        "exponent".to_string(),
+                        format!("{}", exponent.clamp(min, max)),
+                    ));
+                }
+
+                if settings.curve_mode.as_deref() != Some("exponent") {
+                    if let Some(ref curve) = settings.curve {
+                        curve_points =
+                            curve_interpolation::resample(&curve.points, curve.interpolation)
+                                .iter()
+                                .map(|p| ActionmapsCurvePoint {
+                                    in_val: format!("{}", p.input),
+                                    out_val: format!("{}", p.output),
+                                })
+                                .collect();
+                    }
+                }
+            }
 
             ActionmapsControlOption {
                 name: name.clone(),
                 attributes,
                 curve_points,
+                extra_children: Vec::new(),
             }
         })
         .filter(|opt| !opt.attributes.is_empty() || !opt.curve_points.is_empty())
         .collect()
 }
 
+/// One setting of an option: as stored in the profile and as it ends up in actionmaps.xml
+#[derive(Debug, Serialize, Clone)]
+pub struct FieldValue {
+    pub field: String,
+    pub raw: String,
+    /// What gets written, None when the setting is dropped
+    pub effective: Option<String>,
+    /// Why the effective value differs from the raw one
+    pub note: Option<String>,
+}
+
+/// Raw and effective values of every setting of one option
+#[derive(Debug, Serialize, Clone)]
+pub struct OptionValues {
+    pub device_type: String,
+    pub instance: String,
+    pub option: String,
+    pub fields: Vec<FieldValue>,
+    /// Some setting is written differently from how it is stored
+    pub differs: bool,
+}
+
+fn range_note(label: &str, (min, max): (f64, f64)) -> String {
+    format!("Clamped to {} {}-{}", label, min, max)
+}
+
+/// Compare an option's stored settings with what convert_options_to_actionmaps writes for it
+fn option_values(
+    device_type: &str,
+    instance: &str,
+    name: &str,
+    settings: &ControlOptionSettings,
+    include_curves: bool,
+    excluded: bool,
+) -> OptionValues {
+    let single = HashMap::from([(name.to_string(), settings.clone())]);
+    let written = convert_options_to_actionmaps(&single, device_type, include_curves)
+        .into_iter()
+        .next()
+        .filter(|_| !excluded);
+    let attribute = |key: &str| {
+        written
+            .as_ref()?
+            .attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    };
+
+    // Reasons that drop the whole option
+    let dropped = if excluded {
+        Some("Excluded from writes in settings")
+    } else if device_type == "gamepad" && is_joystick_only_option(name) {
+        Some("Not available on gamepads")
+    } else {
+        None
+    };
+    let curves_skipped = "Curves and exponents are only written by the curve watchdog";
+
+    let mut fields = Vec::new();
+    let mut push = |field: &str, raw: String, effective: Option<String>, note: Option<&str>| {
+        let note = if raw.as_str() == effective.as_deref().unwrap_or_default() {
+            None
+        } else {
+            dropped.or(note).map(str::to_string)
+        };
+        fields.push(FieldValue {
+            field: field.to_string(),
+            raw,
+            effective,
+            note,
+        });
+    };
+
+    if let Some(invert) = settings.invert {
+        push(
+            "invert",
+            if invert { "1" } else { "0" }.to_string(),
+            attribute("invert"),
+            None,
+        );
+    }
+    for (field, value) in [
+        ("deadzone", settings.deadzone),
+        ("saturation", settings.saturation),
+    ] {
+        if let Some(value) = value {
+            let note = if value.is_finite() {
+                range_note("the range", UNIT_RANGE)
+            } else {
+                "Not a number".to_string()
+            };
+            push(
+                field,
+                format!("{}", value),
+                attribute(field),
+                Some(note.as_str()),
+            );
+        }
+    }
+    if let Some(sensitivity) = settings.sensitivity {
+        let note = match device_type {
+            "gamepad" => range_note("the gamepad range", GAMEPAD_SENSITIVITY_RANGE),
+            "mouse" => range_note("the mouse range", MOUSE_SENSITIVITY_RANGE),
+            _ => "Only gamepad and mouse options have a sensitivity".to_string(),
+        };
+        push(
+            "sensitivity",
+            format!("{}", sensitivity),
+            attribute("sensitivity"),
+            Some(note.as_str()),
+        );
+    }
+    if let Some(smoothing) = settings.smoothing {
+        let note = if device_type != "mouse" {
+            "Only mouse options have smoothing".to_string()
+        } else if !smoothing.is_finite() {
+            "Not a number".to_string()
+        } else {
+            range_note("the range", UNIT_RANGE)
+        };
+        push(
+            "smoothing",
+            format!("{}", smoothing),
+            attribute("smoothing"),
+            Some(note.as_str()),
+        );
+    }
+    if let Some(exponent) = settings.exponent {
+        let note = if !include_curves {
+            curves_skipped.to_string()
+        } else if !exponent.is_finite() {
+            "Not a number".to_string()
+        } else {
+            range_note("the game's range", EXPONENT_RANGE)
+        };
+        push(
+            "exponent",
+            format!("{}", exponent),
+            attribute("exponent"),
+            Some(note.as_str()),
+        );
+    }
+    if let Some(curve) = settings.curve.as_ref().filter(|c| !c.points.is_empty()) {
+        let points = |count: usize| format!("{} points", count);
+        let note = if !include_curves {
+            curves_skipped
+        } else {
+            "Ignored in exponent mode"
+        };
+        push(
+            "curve",
+            points(curve.points.len()),
+            written
+                .as_ref()
+                .map(|option| option.curve_points.len())
+                .filter(|count| *count > 0)
+                .map(points),
+            Some(note),
+        );
+    }
+
+    OptionValues {
+        device_type: device_type.to_string(),
+        instance: instance.to_string(),
+        option: name.to_string(),
+        differs: fields.iter().any(|f| f.note.is_some()),
+        fields,
+    }
+}
+
+/// Raw and effective values of every option in a profile, in device and option name order.
+/// `include_curves` as for controls_to_actionmaps: false for a normal apply. `excluded`
+/// options are shown as never written.
+pub fn effective_option_values(
+    controls: &ControlsFile,
+    include_curves: bool,
+    excluded: &[String],
+) -> Vec<OptionValues> {
+    let mut devices: Vec<(&str, &str, &DeviceInstanceSettings)> = Vec::new();
+    if let Some(keyboard) = &controls.devices.keyboard {
+        devices.push(("keyboard", "1", keyboard));
+    }
+    if let Some(mouse) = &controls.devices.mouse {
+        devices.push(("mouse", "1", mouse));
+    }
+    if let Some(gamepad) = &controls.devices.gamepad {
+        devices.push(("gamepad", "1", gamepad));
+    }
+    if let Some(joysticks) = &controls.devices.joystick {
+        let mut instances: Vec<_> = joysticks.iter().collect();
+        instances.sort_by(|a, b| a.0.cmp(b.0));
+        devices.extend(
+            instances
+                .into_iter()
+                .map(|(instance, settings)| ("joystick", instance.as_str(), settings)),
+        );
+    }
+
+    let mut values = Vec::new();
+    for (device_type, instance, device) in devices {
+        let mut names: Vec<&String> = device.options.keys().collect();
+        names.sort();
+        for name in names {
+            values.push(option_values(
+                device_type,
+                instance,
+                name,
+                &device.options[name],
+                include_curves,
+                excluded.contains(name),
+            ));
+        }
+    }
+    values
+}
+
+/// Attributes we write from typed fields, never from `gamepad_attributes`
+const GAMEPAD_RESERVED_ATTRIBUTES: [&str; 5] = [
+    "invert",
+    "deadzone",
+    "saturation",
+    "sensitivity",
+    "exponent",
+];
+
+/// Options on the joystick optiontree that SC doesn't offer for gamepads
+fn is_joystick_only_option(name: &str) -> bool {
+    matches!(
+        name,
+        "flight_zoom" | "flight_zoom_abs" | "mgv_zoom" | "mgv_zoom_abs" | "mining_throttle"
+    )
+}
+
+/// Gameplay context an option belongs to, so vehicle- or on-foot-focused players
+/// can work with just the options they care about
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionContext {
+    Flight,
+    Turret,
+    GroundVehicle,
+    /// On-foot (FPS) view and movement
+    OnFoot,
+    /// Zero-g movement while on foot
+    Eva,
+    Other,
+}
+
+/// Classify an option by its SC name
+pub fn option_context(name: &str) -> OptionContext {
+    // EVA roll shares the fps_ prefix, so check EVA first
+    if name.starts_with("eva") || name == "fps_view_roll" {
+        OptionContext::Eva
+    } else if name.starts_with("fps") {
+        OptionContext::OnFoot
+    } else if name.starts_with("turret") {
+        OptionContext::Turret
+    } else if name.starts_with("mgv_") || name == "manned_ground_vehicle" {
+        OptionContext::GroundVehicle
+    } else if name.starts_with("flight") {
+        OptionContext::Flight
+    } else {
+        OptionContext::Other
+    }
+}
+
+/// Keep only options in the given contexts, dropping devices left with nothing to write
+pub fn retain_contexts(devices: &mut Vec<ActionmapsDeviceOptions>, contexts: &[OptionContext]) {
+    for device in devices.iter_mut() {
+        device
+            .options
+            .retain(|opt| contexts.contains(&option_context(&opt.name)));
+    }
+    devices.retain(|device| !device.options.is_empty());
+}
+
+/// Drop excluded options, and devices that had nothing else. Returns how many options were
+/// dropped.
+fn remove_excluded(devices: &mut Vec<ActionmapsDeviceOptions>, excluded: &[String]) -> usize {
+    let mut removed = 0;
+    devices.retain_mut(|device| {
+        let before = device.options.len();
+        device.options.retain(|opt| !excluded.contains(&opt.name));
+        removed += before - device.options.len();
+        before == device.options.len() || !device.options.is_empty()
+    });
+    removed
+}
+
+/// Option attributes we write from profile settings; any others belong to the game or
+/// another tool and are left alone
+const MANAGED_OPTION_ATTRIBUTES: [&str; 6] = [
+    "invert",
+    "deadzone",
+    "saturation",
+    "sensitivity",
+    "smoothing",
+    "exponent",
+];
+
+/// Replace an option's settings with ours while keeping what we don't manage: unknown
+/// attributes stay (after ours) and unknown child elements are carried over
+pub fn merge_option(
+    existing: &ActionmapsControlOption,
+    new: &ActionmapsControlOption,
+) -> ActionmapsControlOption {
+    let mut merged = new.clone();
+    merged.attributes.extend(
+        existing
+            .attributes
+            .iter()
+            .filter(|(key, _)| !MANAGED_OPTION_ATTRIBUTES.contains(&key.as_str()))
+            .filter(|(key, _)| !new.attributes.iter().any(|(k, _)| k == key))
+            .cloned(),
+    );
+    for child in &existing.extra_children {
+        if !merged.extra_children.contains(child) {
+            merged.extra_children.push(child.clone());
+        }
+    }
+    merged
+}
+
+/// Merge new device options into an actionmaps.xml document and return the updated XML.
+/// Only the option elements whose settings change are rewritten; everything else in the
+/// file stays byte for byte as it was (see options_editor). `excluded` option names (the
+/// user's exclusion list from settings) keep whatever the file already has.
+pub fn merge_options_into_xml(
+    xml: &str,
+    mut new_devices: Vec<ActionmapsDeviceOptions>,
+    excluded: &[String],
+) -> Result<String, String> {
+    let removed = remove_excluded(&mut new_devices, excluded);
+    if removed > 0 {
+        info!("Left {} excluded option(s) untouched", removed);
+    }
+
+    options_editor::edit_options(xml, &new_devices)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -726,7 +2032,7 @@ mod tests {
                 invert: Some(true),
                 curve_mode: Some("exponent".to_string()),
                 exponent: Some(1.5),
-                curve: None,
+                ..Default::default()
             },
         );
 
@@ -737,6 +2043,7 @@ mod tests {
                 DeviceInstanceSettings {
                     product: Some("VKB Gladiator NXT".to_string()),
                     options,
+                    ..Default::default()
                 },
             );
             instances
@@ -749,4 +2056,640 @@ mod tests {
         assert_eq!(parsed.profile_name, "Test Profile");
         assert_eq!(parsed.version, CONTROLS_FILE_VERSION);
     }
+
+    #[test]
+    fn test_legacy_controls_file_migration() {
+        let json = r#"{
+            "profileName": "Old Profile",
+            "lastModified": "2024-12-04T12:00:00Z",
+            "devices": {
+                "joystick": {
+                    "1": {
+                        "options": {
+                            "flight_move_pitch": { "invert": true, "curveMode": "exponent", "exponent": 1.5 }
+                        }
+                    }
+                }
+            }
+        }"#;
+
+        let parsed = ControlsFile::from_json(json).unwrap();
+        assert_eq!(parsed.version, CONTROLS_FILE_VERSION);
+        assert_eq!(parsed.profile_name, "Old Profile");
+
+        let joysticks = parsed.devices.joystick.unwrap();
+        let pitch = &joysticks["1"].options["flight_move_pitch"];
+        assert_eq!(pitch.curve_mode.as_deref(), Some("exponent"));
+    }
+
+    #[test]
+    fn test_changelog_recorded_and_capped() {
+        let mut file = ControlsFile::new("Shared".to_string());
+        file.record_change(3, Some("  Softer pitch curve \n"));
+        file.record_change(0, Some("   "));
+        assert_eq!(
+            file.changelog[0].message.as_deref(),
+            Some("Softer pitch curve")
+        );
+        assert_eq!(file.changelog[1].message, None);
+
+        let parsed = ControlsFile::from_json(&file.to_json().unwrap()).unwrap();
+        assert_eq!(parsed.changelog, file.changelog);
+
+        for i in 0..MAX_CHANGELOG_ENTRIES {
+            file.record_change(i, None);
+        }
+        assert_eq!(file.changelog.len(), MAX_CHANGELOG_ENTRIES);
+        assert_eq!(file.changelog[0].changed_options, 0);
+        assert_eq!(
+            file.changelog.last().unwrap().changed_options,
+            MAX_CHANGELOG_ENTRIES - 1
+        );
+    }
+
+    #[test]
+    fn test_newer_controls_file_rejected() {
+        let json = r#"{ "version": "99.0", "profile_name": "Future", "devices": {} }"#;
+        let err = ControlsFile::from_json(json).unwrap_err();
+        assert!(err.contains("newer"));
+    }
+
+    #[test]
+    fn test_option_context() {
+        assert_eq!(option_context("fps_view_pitch"), OptionContext::OnFoot);
+        assert_eq!(option_context("fps_move_lateral"), OptionContext::OnFoot);
+        assert_eq!(option_context("fps_view_roll"), OptionContext::Eva);
+        assert_eq!(
+            option_context("eva_move_strafe_vertical"),
+            OptionContext::Eva
+        );
+        assert_eq!(option_context("turret_aim_yaw"), OptionContext::Turret);
+        assert_eq!(
+            option_context("mgv_move_forward"),
+            OptionContext::GroundVehicle
+        );
+        assert_eq!(option_context("flight_move_pitch"), OptionContext::Flight);
+        assert_eq!(
+            option_context("weapon_convergence_distance_rel"),
+            OptionContext::Other
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_preserved() {
+        let json = r#"{
+            "version": "1.9",
+            "profile_name": "Future Minor",
+            "author": "someone",
+            "devices": {
+                "headtracker": { "options": {} },
+                "joystick": {
+                    "1": {
+                        "nickname": "Left stick",
+                        "options": {
+                            "flight_move_pitch": { "invert": true, "response_time": 0.2 }
+                        }
+                    }
+                }
+            }
+        }"#;
+
+        let loaded = ControlsFile::from_json(json).unwrap();
+        assert_eq!(loaded.extra["author"], "someone");
+        assert!(loaded.devices.extra.contains_key("headtracker"));
+
+        // Simulate a save: the profile is rebuilt without the unknown fields
+        let mut saved = ControlsFile::new("Future Minor".to_string());
+        let mut options = HashMap::new();
+        options.insert(
+            "flight_move_pitch".to_string(),
+            ControlOptionSettings {
+                invert: Some(false),
+                ..Default::default()
+            },
+        );
+        let mut joysticks = HashMap::new();
+        joysticks.insert(
+            "1".to_string(),
+            DeviceInstanceSettings {
+                options,
+                ..Default::default()
+            },
+        );
+        saved.devices.joystick = Some(joysticks);
+        saved.preserve_unknown_fields(&loaded);
+
+        let reparsed = ControlsFile::from_json(&saved.to_json().unwrap()).unwrap();
+        assert_eq!(reparsed.version, "1.9");
+        assert_eq!(reparsed.extra["author"], "someone");
+        assert!(reparsed.devices.extra.contains_key("mouse"));
+
+        let stick = &reparsed.devices.joystick.unwrap()["1"];
+        assert_eq!(stick.extra["nickname"], "Left stick");
+        let pitch = &stick.options["flight_move_pitch"];
+        assert_eq!(pitch.invert, Some(false));
+        assert_eq!(pitch.extra["response_time"], 0.2);
+    }
+
+    fn round_trip(device: ActionmapsDeviceOptions) -> ActionmapsDeviceOptions {
+        let xml = format!(
+            "<ActionMaps>\n <ActionProfiles profileName=\"default\">\n{}</ActionProfiles>\n</ActionMaps>\n",
+            generate_options_xml(&device)
+        );
+        let mut parsed = parse_actionmaps_options(&xml).unwrap();
+        assert_eq!(parsed.len(), 1);
+        parsed.remove(0)
+    }
+
+    #[test]
+    fn test_options_xml_escapes_product() {
+        for product in [
+            r#"Saitek "X52" Pro"#,
+            "Thrustmaster T.16000M & TWCS",
+            "<Virtual> Joystick's Device",
+        ] {
+            let device = ActionmapsDeviceOptions {
+                device_type: "joystick".to_string(),
+                instance: "1".to_string(),
+                product: product.to_string(),
+                options: vec![ActionmapsControlOption {
+                    name: "flight_move_pitch".to_string(),
+                    attributes: vec![("invert".to_string(), "1".to_string())],
+                    curve_points: vec![ActionmapsCurvePoint {
+                        in_val: "0.5".to_string(),
+                        out_val: "0.25".to_string(),
+                    }],
+                    extra_children: Vec::new(),
+                }],
+                extra_attributes: Vec::new(),
+            };
+            let parsed = round_trip(device.clone());
+            assert_eq!(parsed.product, product);
+            assert_eq!(parsed.options, device.options);
+        }
+    }
+
+    #[test]
+    fn test_options_xml_escapes_self_closing_device() {
+        let device = ActionmapsDeviceOptions {
+            device_type: "keyboard".to_string(),
+            instance: "1".to_string(),
+            product: "Keyboard & Mouse \"Combo\"".to_string(),
+            options: Vec::new(),
+            extra_attributes: Vec::new(),
+        };
+        let parsed = round_trip(device.clone());
+        assert_eq!(parsed.product, device.product);
+        assert!(parsed.options.is_empty());
+    }
+
+    #[test]
+    fn test_copy_device_options_with_exclusions() {
+        let mut controls = ControlsFile::new("Copy".to_string());
+        let old_stick = controls.device_mut("joystick", "1").unwrap();
+        for name in ["flight_move_pitch", "flight_move_yaw"] {
+            old_stick.options.insert(
+                name.to_string(),
+                ControlOptionSettings {
+                    invert: Some(true),
+                    deadzone: Some(0.05),
+                    ..Default::default()
+                },
+            );
+        }
+        let new_stick = controls.device_mut("joystick", "2").unwrap();
+        new_stick.product = Some("New stick".to_string());
+        new_stick.options.insert(
+            "flight_move_roll".to_string(),
+            ControlOptionSettings::default(),
+        );
+
+        let copied = controls
+            .copy_device_options(
+                ("joystick", "1"),
+                ("joystick", "2"),
+                &["flight_move_yaw".to_string()],
+            )
+            .unwrap();
+        assert_eq!(copied, 1);
+
+        let new_stick = controls.device("joystick", "2").unwrap();
+        assert_eq!(new_stick.product.as_deref(), Some("New stick"));
+        assert_eq!(new_stick.options["flight_move_pitch"].invert, Some(true));
+        assert!(new_stick.options.contains_key("flight_move_roll"));
+        assert!(!new_stick.options.contains_key("flight_move_yaw"));
+
+        assert!(controls
+            .copy_device_options(("joystick", "3"), ("joystick", "2"), &[])
+            .is_err());
+    }
+
+    #[test]
+    fn test_invert_device_options() {
+        let mut controls = ControlsFile::new("Invert".to_string());
+        controls
+            .device_mut("joystick", "1")
+            .unwrap()
+            .options
+            .insert(
+                "flight_move_pitch".to_string(),
+                ControlOptionSettings {
+                    invert: Some(true),
+                    ..Default::default()
+                },
+            );
+        let axes: Vec<(String, bool)> = [
+            ("flight_move_pitch", false),
+            ("flight_move_yaw", false),
+            ("flight_move_roll", false),
+            ("flight_throttle_abs", true),
+        ]
+        .into_iter()
+        .map(|(name, default)| (name.to_string(), default))
+        .collect();
+        let flight_axes: Vec<String> = ["pitch", "roll", "yaw"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let changed = controls
+            .invert_device_options("joystick", "1", &axes, &flight_axes, InvertMode::Toggle)
+            .unwrap();
+        assert_eq!(changed, 3);
+        let stick = controls.device("joystick", "1").unwrap();
+        assert_eq!(stick.options["flight_move_pitch"].invert, Some(false));
+        assert_eq!(stick.options["flight_move_yaw"].invert, Some(true));
+        assert!(!stick.options.contains_key("flight_throttle_abs"));
+
+        // The throttle is inverted by default, so setting everything inverted leaves it out
+        let changed = controls
+            .invert_device_options("joystick", "1", &axes, &[], InvertMode::Set(true))
+            .unwrap();
+        assert_eq!(changed, 1);
+        assert_eq!(
+            controls.device("joystick", "1").unwrap().options["flight_move_pitch"].invert,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_remove_excluded_options() {
+        let option = |name: &str| ActionmapsControlOption {
+            name: name.to_string(),
+            attributes: vec![("sensitivity".to_string(), "0.5".to_string())],
+            curve_points: Vec::new(),
+            extra_children: Vec::new(),
+        };
+        let device =
+            |device_type: &str, options: Vec<ActionmapsControlOption>| ActionmapsDeviceOptions {
+                device_type: device_type.to_string(),
+                instance: "1".to_string(),
+                product: String::new(),
+                options,
+                extra_attributes: Vec::new(),
+            };
+        let mut devices = vec![
+            device("keyboard", vec![option("fps_view_pitch")]),
+            device(
+                "gamepad",
+                vec![option("fps_view_pitch"), option("flight_view_yaw")],
+            ),
+            device("joystick", Vec::new()),
+        ];
+
+        let removed = remove_excluded(&mut devices, &["fps_view_pitch".to_string()]);
+        assert_eq!(removed, 2);
+        // The keyboard had nothing else; the empty joystick wasn't emptied by us
+        let types: Vec<&str> = devices.iter().map(|d| d.device_type.as_str()).collect();
+        assert_eq!(types, vec!["gamepad", "joystick"]);
+        assert_eq!(devices[0].options, vec![option("flight_view_yaw")]);
+    }
+
+    #[test]
+    fn test_gamepad_attributes_round_trip() {
+        let option = ActionmapsControlOption {
+            name: "flight_move_pitch".to_string(),
+            attributes: vec![
+                ("invert".to_string(), "1".to_string()),
+                ("sensitivity".to_string(), "0.8".to_string()),
+                ("vibration".to_string(), "0".to_string()),
+            ],
+            curve_points: Vec::new(),
+            extra_children: Vec::new(),
+        };
+        let device = |device_type: &str| ActionmapsDeviceOptions {
+            device_type: device_type.to_string(),
+            instance: "1".to_string(),
+            product: String::new(),
+            options: vec![option.clone()],
+            extra_attributes: Vec::new(),
+        };
+
+        let file = controls_file_from_actionmaps_options(
+            "Pad".to_string(),
+            vec![device("gamepad"), device("joystick")],
+            &[],
+        );
+        let pad = &file.devices.gamepad.as_ref().unwrap().options["flight_move_pitch"];
+        assert_eq!(pad.gamepad_attributes["vibration"], "0");
+        assert_eq!(pad.gamepad_attributes.len(), 1);
+        let stick = &file.device("joystick", "1").unwrap().options["flight_move_pitch"];
+        assert!(stick.gamepad_attributes.is_empty());
+
+        let written = controls_to_actionmaps(&file, false);
+        let pad = written.iter().find(|d| d.device_type == "gamepad").unwrap();
+        assert_eq!(pad.options[0].attributes, option.attributes);
+    }
+
+    #[test]
+    fn test_mouse_options_round_trip() {
+        let option = ActionmapsControlOption {
+            name: "fps_view_pitch".to_string(),
+            attributes: vec![
+                ("invert".to_string(), "1".to_string()),
+                ("sensitivity".to_string(), "1.5".to_string()),
+                ("smoothing".to_string(), "0.3".to_string()),
+            ],
+            curve_points: Vec::new(),
+            extra_children: Vec::new(),
+        };
+        let mouse = ActionmapsDeviceOptions {
+            device_type: "mouse".to_string(),
+            instance: "1".to_string(),
+            product: String::new(),
+            options: vec![option.clone()],
+            extra_attributes: Vec::new(),
+        };
+
+        let mut file = controls_file_from_actionmaps_options("Mouse".to_string(), vec![mouse], &[]);
+        let pitch = &file.device("mouse", "1").unwrap().options["fps_view_pitch"];
+        assert_eq!(pitch.invert, Some(true));
+        assert_eq!(pitch.sensitivity, Some(1.5));
+        assert_eq!(pitch.smoothing, Some(0.3));
+
+        let written = controls_to_actionmaps(&file, false);
+        assert_eq!(written[0].device_type, "mouse");
+        assert_eq!(written[0].options[0].attributes, option.attributes);
+
+        // Smoothing is clamped like the other fractions
+        file.device_mut("mouse", "1")
+            .unwrap()
+            .options
+            .get_mut("fps_view_pitch")
+            .unwrap()
+            .smoothing = Some(4.0);
+        let written = controls_to_actionmaps(&file, false);
+        assert!(written[0].options[0]
+            .attributes
+            .contains(&("smoothing".to_string(), "1".to_string())));
+    }
+
+    #[test]
+    fn test_deadzone_and_saturation_kept_in_range() {
+        let input: SaveControlsInput = serde_json::from_value(serde_json::json!({
+            "profile_name": "Ranges",
+            "devices": {
+                "joystick": {
+                    "1": {
+                        "flight_move_pitch": { "deadzone": 1.5, "saturation": -0.2 },
+                        "flight_move_yaw": { "deadzone": 0.05, "saturation": 0.9 }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let file = ControlsFile::from(input);
+        let options = &file.device("joystick", "1").unwrap().options;
+        assert_eq!(options["flight_move_pitch"].deadzone, Some(1.0));
+        assert_eq!(options["flight_move_pitch"].saturation, Some(0.0));
+        assert_eq!(options["flight_move_yaw"].deadzone, Some(0.05));
+        assert_eq!(options["flight_move_yaw"].saturation, Some(0.9));
+
+        // Values read from actionmaps.xml are held to the same range
+        let imported = controls_file_from_actionmaps_options(
+            "Imported".to_string(),
+            vec![ActionmapsDeviceOptions {
+                device_type: "joystick".to_string(),
+                instance: "1".to_string(),
+                product: String::new(),
+                options: vec![ActionmapsControlOption {
+                    name: "flight_move_roll".to_string(),
+                    attributes: vec![
+                        ("deadzone".to_string(), "NaN".to_string()),
+                        ("saturation".to_string(), "2".to_string()),
+                    ],
+                    curve_points: Vec::new(),
+                    extra_children: Vec::new(),
+                }],
+                extra_attributes: Vec::new(),
+            }],
+            &[],
+        );
+        let roll = &imported.device("joystick", "1").unwrap().options["flight_move_roll"];
+        assert_eq!(roll.deadzone, None);
+        assert_eq!(roll.saturation, Some(1.0));
+
+        let written = controls_to_actionmaps(&imported, false);
+        assert_eq!(
+            written[0].options[0].attributes,
+            vec![("saturation".to_string(), "1".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_effective_option_values() {
+        let mut file = ControlsFile::new("Test".to_string());
+        file.device_mut("gamepad", "1").unwrap().options.insert(
+            "fps_view_pitch".to_string(),
+            ControlOptionSettings {
+                invert: Some(true),
+                deadzone: Some(1.5),
+                sensitivity: Some(5.0),
+                exponent: Some(2.0),
+                ..Default::default()
+            },
+        );
+        file.device_mut("gamepad", "1").unwrap().options.insert(
+            "mining_throttle".to_string(),
+            ControlOptionSettings {
+                invert: Some(true),
+                ..Default::default()
+            },
+        );
+
+        let field = |values: &[OptionValues], option: usize, name: &str| {
+            values[option]
+                .fields
+                .iter()
+                .find(|f| f.field == name)
+                .unwrap()
+                .clone()
+        };
+
+        let values = effective_option_values(&file, false, &[]);
+        assert_eq!(values[0].option, "fps_view_pitch");
+        assert!(values[0].differs);
+        assert_eq!(field(&values, 0, "invert").note, None);
+        assert_eq!(
+            field(&values, 0, "deadzone").effective.as_deref(),
+            Some("1")
+        );
+        assert_eq!(
+            field(&values, 0, "sensitivity").effective.as_deref(),
+            Some("2")
+        );
+        assert_eq!(field(&values, 0, "exponent").effective, None);
+        assert!(field(&values, 0, "exponent")
+            .note
+            .unwrap()
+            .contains("watchdog"));
+        assert_eq!(
+            field(&values, 1, "invert").note.as_deref(),
+            Some("Not available on gamepads")
+        );
+
+        // Written with curves, the exponent goes through
+        let values = effective_option_values(&file, true, &[]);
+        assert_eq!(
+            field(&values, 0, "exponent").effective.as_deref(),
+            Some("2")
+        );
+
+        // Excluded options are never written
+        let values = effective_option_values(&file, false, &["fps_view_pitch".to_string()]);
+        assert_eq!(field(&values, 0, "sensitivity").effective, None);
+        assert_eq!(
+            field(&values, 0, "sensitivity").note.as_deref(),
+            Some("Excluded from writes in settings")
+        );
+    }
+
+    #[test]
+    fn test_merge_keeps_unknown_attributes_and_elements() {
+        let xml = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="joystick" instance="1" Product="Stick" hidGuid="{ABC}">
+   <flight_move_pitch invert="1" future_setting="7">
+    <nonlinearity_curve>
+     <point in="0.5" out="0.2"/>
+    </nonlinearity_curve>
+    <tool_data source="other"><note>keep me</note></tool_data>
+   </flight_move_pitch>
+   <flight_move_yaw invert="0"/>
+  </options>
+  <modifiers />
+ </ActionProfiles>
+</ActionMaps>
+"#;
+        let new_devices = vec![ActionmapsDeviceOptions {
+            device_type: "joystick".to_string(),
+            instance: "1".to_string(),
+            product: "Stick".to_string(),
+            options: vec![ActionmapsControlOption {
+                name: "flight_move_pitch".to_string(),
+                attributes: vec![("invert".to_string(), "0".to_string())],
+                curve_points: Vec::new(),
+                extra_children: Vec::new(),
+            }],
+            extra_attributes: Vec::new(),
+        }];
+
+        let merged = merge_options_into_xml(xml, new_devices, &[]).unwrap();
+        let devices = parse_actionmaps_options(&merged).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(
+            devices[0].extra_attributes,
+            vec![("hidGuid".to_string(), "{ABC}".to_string())]
+        );
+
+        let pitch = &devices[0].options[0];
+        assert_eq!(
+            pitch.attributes,
+            vec![
+                ("invert".to_string(), "0".to_string()),
+                ("future_setting".to_string(), "7".to_string()),
+            ]
+        );
+        assert_eq!(
+            pitch.extra_children,
+            vec![r#"<tool_data source="other"><note>keep me</note></tool_data>"#.to_string()]
+        );
+        assert_eq!(devices[0].options[1].name, "flight_move_yaw");
+    }
+
+    /// An actionmaps.xml with `joysticks` devices of `options` options each, every option
+    /// with a curve, plus a few hundred rebinds
+    fn large_actionmaps(joysticks: usize, options: usize) -> String {
+        let mut xml =
+            String::from("<ActionMaps>\n <ActionProfiles version=\"1\" profileName=\"default\">\n");
+        for instance in 1..=joysticks {
+            xml.push_str(&format!(
+                "  <options type=\"joystick\" instance=\"{}\" Product=\"Stick {}\">\n",
+                instance, instance
+            ));
+            for i in 0..options {
+                xml.push_str(&format!(
+                    "   <option_{} invert=\"1\" deadzone=\"0.05\">\n    <nonlinearity_curve>\n     <point in=\"0.5\" out=\"0.25\"/>\n    </nonlinearity_curve>\n    <tool_data note=\"{}\"/>\n   </option_{}>\n",
+                    i, i, i
+                ));
+            }
+            xml.push_str("  </options>\n");
+        }
+        xml.push_str("  <actionmap name=\"spaceship_general\">\n");
+        for i in 0..500 {
+            xml.push_str(&format!(
+                "   <action name=\"v_action_{}\">\n    <rebind input=\"js1_button{}\"/>\n   </action>\n",
+                i, i
+            ));
+        }
+        xml.push_str("  </actionmap>\n </ActionProfiles>\n</ActionMaps>\n");
+        xml
+    }
+
+    #[test]
+    fn test_streamed_file_parse_matches() {
+        let xml = large_actionmaps(2, 20);
+        let sim = crate::sc_sim::ScSim::new(&xml);
+        let streamed = parse_actionmaps_options_file(std::path::Path::new(sim.path_str())).unwrap();
+        let in_memory = parse_actionmaps_options(&xml).unwrap();
+        assert_eq!(streamed.len(), 2);
+        assert_eq!(
+            serde_json::to_string(&streamed).unwrap(),
+            serde_json::to_string(&in_memory).unwrap()
+        );
+        assert_eq!(
+            streamed[0].options[3].extra_children,
+            vec![r#"<tool_data note="3"/>"#.to_string()]
+        );
+    }
+
+    /// Run with `cargo test --release bench_parse_large_actionmaps -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_parse_large_actionmaps() {
+        let xml = large_actionmaps(8, 2000);
+        let sim = crate::sc_sim::ScSim::new(&xml);
+        let path = std::path::Path::new(sim.path_str());
+        let runs = 10;
+
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            let xml = std::fs::read_to_string(path).unwrap();
+            assert_eq!(parse_actionmaps_options(&xml).unwrap().len(), 8);
+        }
+        let in_memory = start.elapsed() / runs;
+
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            assert_eq!(parse_actionmaps_options_file(path).unwrap().len(), 8);
+        }
+        let streamed = start.elapsed() / runs;
+
+        println!(
+            "{} KiB: read + parse {:?}, streamed {:?}",
+            xml.len() / 1024,
+            in_memory,
+            streamed
+        );
+    }
 }
@@ -0,0 +1,155 @@
+//! Critical actions left without a binding
+//!
+//! A fresh actionmaps.xml, a patch that renames an actionmap or a profile built for a
+//! new stick can leave the actions you only need once in a while (ejecting, powering
+//! up, spooling the quantum drive) bound to nothing at all, which tends to be found out
+//! at the worst moment. This checks a short list of such actions against the merged
+//! bindings (defaults plus the user's rebinds) and reports the ones no device can
+//! trigger.
+
+use crate::keybindings::{MergedBinding, MergedBindings};
+use serde::Serialize;
+
+/// Actions nobody should launch without, with why they matter
+pub const CRITICAL_ACTIONS: &[(&str, &str)] = &[
+    (
+        "v_flightready",
+        "Flight ready: power up the ship and its systems",
+    ),
+    ("v_power_toggle", "Toggle ship power"),
+    ("v_eject", "Eject from a ship that's going down"),
+    ("v_emergency_exit", "Leave the seat in a hurry"),
+    ("v_toggle_landing_system", "Landing gear"),
+    ("v_toggle_quantum_mode", "Enter quantum travel mode"),
+    ("v_toggle_qdrive_engagement", "Engage the quantum drive"),
+    (
+        "v_engineering_assignment_weapons_increase",
+        "Power triangle: more power to weapons",
+    ),
+    (
+        "v_engineering_assignment_engine_increase",
+        "Power triangle: more power to engines",
+    ),
+    (
+        "v_engineering_assignment_shields_increase",
+        "Power triangle: more power to shields",
+    ),
+    ("v_engineering_assignment_reset", "Power triangle: reset"),
+    (
+        "v_weapon_countermeasure_decoy_launch",
+        "Launch decoys at incoming missiles",
+    ),
+    ("v_atc_request", "Request landing from ATC"),
+];
+
+/// A critical action that has no binding on any device
+#[derive(Debug, Serialize, Clone)]
+pub struct UnboundAction {
+    pub action_map: String,
+    pub action: String,
+    pub ui_label: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct CriticalActionsReport {
+    /// Critical actions found in the defaultProfile (one per actionmap they appear in)
+    pub checked: usize,
+    pub unbound: Vec<UnboundAction>,
+    /// Critical actions the defaultProfile no longer has, likely renamed by a patch
+    pub not_in_default_profile: Vec<String>,
+}
+
+/// A binding counts unless it's a cleared one, e.g. "js1_ "
+fn is_bound(binding: &MergedBinding) -> bool {
+    binding
+        .input
+        .trim()
+        .split_once('_')
+        .is_some_and(|(_, input)| !input.is_empty())
+}
+
+/// Find the critical actions with no binding on any device
+pub fn check(merged: &MergedBindings) -> CriticalActionsReport {
+    let mut report = CriticalActionsReport::default();
+
+    for (name, reason) in CRITICAL_ACTIONS {
+        let mut found = false;
+        for action_map in &merged.action_maps {
+            for action in action_map.actions.iter().filter(|a| a.name == *name) {
+                found = true;
+                report.checked += 1;
+                if !action.bindings.iter().any(is_bound) {
+                    report.unbound.push(UnboundAction {
+                        action_map: action_map.name.clone(),
+                        action: action.name.clone(),
+                        ui_label: action.ui_label.clone(),
+                        reason: reason.to_string(),
+                    });
+                }
+            }
+        }
+        if !found {
+            report.not_in_default_profile.push(name.to_string());
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybindings::{ActionMaps, AllBinds};
+
+    #[test]
+    fn test_unbound_critical_actions() {
+        let all_binds = AllBinds::from_xml(
+            r#"<profile version="1">
+ <actionmap name="spaceship_general" version="2" UILabel="@ui_CGSpaceFlightCockpit" UICategory="@ui_CCSpaceFlight">
+  <action name="v_flightready" activationMode="press" keyboard="ralt+r" gamepad=" " joystick=" "/>
+  <action name="v_eject" activationMode="press" keyboard="ralt+y" gamepad=" " joystick=" "/>
+  <action name="v_toggle_landing_system" activationMode="tap" keyboard="n" gamepad=" " joystick=" "/>
+ </actionmap>
+ <actionmap name="vehicle_general" version="27" UILabel="@ui_CGVehicleGeneral" UICategory="@ui_CCVehicle">
+  <action name="v_flightready" activationMode="press" keyboard=" " gamepad=" " joystick=" "/>
+ </actionmap>
+</profile>"#,
+        )
+        .unwrap();
+
+        // The eject key was cleared, landing gear moved to the stick
+        let user = ActionMaps::from_xml(
+            r#"<ActionMaps>
+ <actionmap name="spaceship_general">
+  <action name="v_eject">
+   <rebind input="kb1_ "/>
+  </action>
+  <action name="v_toggle_landing_system">
+   <rebind input="kb1_ "/>
+   <rebind input="js1_button3"/>
+  </action>
+ </actionmap>
+</ActionMaps>"#,
+        )
+        .unwrap();
+
+        let report = check(&all_binds.merge_with_user_bindings(Some(&user)));
+        assert_eq!(report.checked, 4);
+        let unbound: Vec<(&str, &str)> = report
+            .unbound
+            .iter()
+            .map(|u| (u.action_map.as_str(), u.action.as_str()))
+            .collect();
+        assert_eq!(
+            unbound,
+            vec![
+                ("vehicle_general", "v_flightready"),
+                ("spaceship_general", "v_eject")
+            ]
+        );
+        assert!(report
+            .not_in_default_profile
+            .contains(&"v_power_toggle".to_string()));
+    }
+}
@@ -0,0 +1,236 @@
+//! A/B comparison of two curves on one axis
+//!
+//! Telling two curves apart needs flying with both, ideally back to back. An A/B test
+//! keeps two curve configurations for one axis of a profile, writes whichever is active
+//! to the profile and actionmaps.xml, swaps between them on request, and finally records
+//! which one the user chose. Finished tests stay in the store as a record.
+
+use crate::controls::{self, ControlOptionSettings, ControlsFile};
+use crate::curve_presets::{self, CurvePreset};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the A/B test store inside the app data directory
+pub const AB_TESTS_FILE_NAME: &str = "curve_ab_tests.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Variant {
+    A,
+    B,
+}
+
+impl Variant {
+    fn other(self) -> Self {
+        match self {
+            Variant::A => Variant::B,
+            Variant::B => Variant::A,
+        }
+    }
+}
+
+/// The axis under test: one option of one device in a .sccontrols profile
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TestedAxis {
+    pub profile_path: String,
+    pub device_type: String,
+    pub instance: String,
+    pub option: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CurveAbTest {
+    #[serde(flatten)]
+    pub axis: TestedAxis,
+    pub a: CurvePreset,
+    pub b: CurvePreset,
+    pub active: Variant,
+    /// How many times the user swapped, a rough measure of how thorough the comparison was
+    pub swaps: u32,
+    /// ISO timestamp
+    pub started_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chosen: Option<Variant>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_at: Option<String>,
+}
+
+impl CurveAbTest {
+    pub fn is_open(&self) -> bool {
+        self.chosen.is_none()
+    }
+
+    pub fn active_curve(&self) -> &CurvePreset {
+        match self.active {
+            Variant::A => &self.a,
+            Variant::B => &self.b,
+        }
+    }
+
+    /// Set the active curve on the profile's option, keeping its other settings. Returns
+    /// the option's full settings, which is what has to go to actionmaps.xml.
+    pub fn write_to_profile(
+        &self,
+        profile: &mut ControlsFile,
+    ) -> Result<ControlOptionSettings, String> {
+        let settings = profile
+            .device_mut(&self.axis.device_type, &self.axis.instance)?
+            .options
+            .entry(self.axis.option.clone())
+            .or_default();
+        curve_presets::apply_to_option(self.active_curve(), settings);
+        Ok(settings.clone())
+    }
+
+    /// The axis alone, as device options to merge into actionmaps.xml
+    pub fn actionmaps_options(
+        &self,
+        settings: ControlOptionSettings,
+    ) -> Result<Vec<controls::ActionmapsDeviceOptions>, String> {
+        let mut single = ControlsFile::new(String::new());
+        single
+            .device_mut(&self.axis.device_type, &self.axis.instance)?
+            .options
+            .insert(self.axis.option.clone(), settings);
+        Ok(controls::controls_to_actionmaps(&single, true))
+    }
+}
+
+fn store_path(dir: &Path) -> PathBuf {
+    dir.join(AB_TESTS_FILE_NAME)
+}
+
+pub fn load_tests(dir: &Path) -> Result<Vec<CurveAbTest>, String> {
+    let path = store_path(dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read curve A/B tests: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse curve A/B tests: {}", e))
+}
+
+pub fn save_tests(dir: &Path, tests: &[CurveAbTest]) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    let json = serde_json::to_string_pretty(tests)
+        .map_err(|e| format!("Failed to serialize curve A/B tests: {}", e))?;
+    std::fs::write(store_path(dir), json)
+        .map_err(|e| format!("Failed to write curve A/B tests: {}", e))
+}
+
+/// Start comparing `a` and `b` on an axis, with A active. Both curves are validated the
+/// way saved presets are. An open test on the same axis is replaced.
+pub fn start(
+    tests: &mut Vec<CurveAbTest>,
+    axis: TestedAxis,
+    a: CurvePreset,
+    b: CurvePreset,
+) -> Result<CurveAbTest, String> {
+    let a = curve_presets::validate(a).map_err(|e| format!("Curve A: {}", e))?;
+    let b = curve_presets::validate(b).map_err(|e| format!("Curve B: {}", e))?;
+    tests.retain(|t| !(t.is_open() && t.axis == axis));
+    let test = CurveAbTest {
+        axis,
+        a,
+        b,
+        active: Variant::A,
+        swaps: 0,
+        started_at: chrono::Local::now().to_rfc3339(),
+        chosen: None,
+        decided_at: None,
+    };
+    tests.push(test.clone());
+    Ok(test)
+}
+
+/// The open test on an axis
+pub fn open_test<'a>(
+    tests: &'a mut [CurveAbTest],
+    axis: &TestedAxis,
+) -> Result<&'a mut CurveAbTest, String> {
+    tests
+        .iter_mut()
+        .find(|t| t.is_open() && &t.axis == axis)
+        .ok_or_else(|| {
+            format!(
+                "No curve comparison running on {} {} {}",
+                axis.device_type, axis.instance, axis.option
+            )
+        })
+}
+
+/// Switch to the other curve
+pub fn swap(test: &mut CurveAbTest) {
+    test.active = test.active.other();
+    test.swaps += 1;
+}
+
+/// Record the user's choice and make it the active curve
+pub fn choose(test: &mut CurveAbTest, variant: Variant) {
+    test.active = variant;
+    test.chosen = Some(variant);
+    test.decided_at = Some(chrono::Local::now().to_rfc3339());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exponent(name: &str, value: f64) -> CurvePreset {
+        CurvePreset {
+            name: name.to_string(),
+            description: None,
+            curve_mode: "exponent".to_string(),
+            exponent: Some(value),
+            points: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_swap_and_choose() {
+        let axis = TestedAxis {
+            profile_path: "p.sccontrols".to_string(),
+            device_type: "joystick".to_string(),
+            instance: "1".to_string(),
+            option: "flight_move_pitch".to_string(),
+        };
+        let mut tests = Vec::new();
+        start(
+            &mut tests,
+            axis.clone(),
+            exponent("Soft", 2.0),
+            exponent("Linear", 1.0),
+        )
+        .unwrap();
+
+        // Curves are checked like saved presets
+        let mut steep = exponent("Steep", 1.0);
+        steep.curve_mode = "curve".to_string();
+        steep.points = vec![controls::CurvePoint {
+            input: 0.5,
+            output: 1.5,
+        }];
+        assert!(start(&mut tests, axis.clone(), exponent("Linear", 1.0), steep).is_err());
+
+        let mut profile = ControlsFile::new("Test".to_string());
+        profile.device_mut("joystick", "1").unwrap().options.insert(
+            "flight_move_pitch".to_string(),
+            ControlOptionSettings {
+                invert: Some(true),
+                ..Default::default()
+            },
+        );
+
+        let test = open_test(&mut tests, &axis).unwrap();
+        swap(test);
+        let settings = test.write_to_profile(&mut profile).unwrap();
+        assert_eq!(settings.exponent, Some(1.0));
+        // The rest of the option is untouched
+        assert_eq!(settings.invert, Some(true));
+
+        choose(test, Variant::A);
+        assert_eq!(test.active_curve().name, "Soft");
+        assert_eq!(test.swaps, 1);
+        assert!(open_test(&mut tests, &axis).is_err());
+        assert_eq!(tests.len(), 1);
+    }
+}
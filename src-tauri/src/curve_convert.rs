@@ -0,0 +1,125 @@
+//! Convert between exponent and curve modes
+//!
+//! Switching an option from exponent to curve mode (or back) shouldn't throw away the
+//! feel the user tuned. An exponent becomes curve points sampled from x^e; a curve
+//! becomes the exponent whose x^e is closest to it in the least squares sense, measured
+//! over the whole 0..1 range rather than only at the points, with the remaining error
+//! reported so the UI can say when a curve has no good exponent equivalent (an S-curve,
+//! say).
+
+use crate::controls::{CurvePoint, EXPONENT_RANGE};
+use crate::curve_eval::AxisCurve;
+use serde::Serialize;
+
+/// Curve points an exponent becomes, not counting SC's implicit (0,0) and (1,1)
+pub const DEFAULT_CURVE_POINTS: usize = 9;
+
+/// Most points we'll generate; SC's curve editor doesn't go much past this either
+const MAX_CURVE_POINTS: usize = 32;
+
+/// Where the curve and the candidate x^e are compared
+const FIT_SAMPLES: usize = 200;
+
+/// Golden-section iterations; the bracket shrinks by ~0.618 each, so 60 is plenty
+const FIT_ITERATIONS: usize = 60;
+
+/// An exponent fitted to a curve
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct ExponentFit {
+    pub exponent: f64,
+    /// Root mean square difference between the curve and x^exponent over 0..1
+    pub rms_error: f64,
+}
+
+/// Round to `decimals` places, so written values don't carry float noise
+pub fn round(value: f64, decimals: i32) -> f64 {
+    let scale = 10f64.powi(decimals);
+    (value * scale).round() / scale
+}
+
+/// `count` evenly spaced points on x^exponent, between the implicit ends
+pub fn exponent_to_points(exponent: f64, count: usize) -> Vec<CurvePoint> {
+    let count = count.clamp(1, MAX_CURVE_POINTS);
+    (1..=count)
+        .map(|i| {
+            let x = i as f64 / (count + 1) as f64;
+            CurvePoint {
+                input: round(x, 4),
+                output: round(x.powf(exponent), 4),
+            }
+        })
+        .collect()
+}
+
+fn rms_error(curve: &AxisCurve, exponent: f64) -> f64 {
+    let sum: f64 = (0..=FIT_SAMPLES)
+        .map(|i| {
+            let x = i as f64 / FIT_SAMPLES as f64;
+            (curve.shape(x) - x.powf(exponent)).powi(2)
+        })
+        .sum();
+    (sum / (FIT_SAMPLES + 1) as f64).sqrt()
+}
+
+/// The exponent closest to a curve
+pub fn fit_exponent(points: &[CurvePoint]) -> ExponentFit {
+    let curve = AxisCurve {
+        points: points.to_vec(),
+        ..Default::default()
+    };
+
+    // The error is unimodal in the exponent for the monotonic curves SC allows, so a
+    // golden-section search finds the minimum. Only exponents the game accepts are
+    // considered, and searching ln(e) treats 0.5 and 2 alike.
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let error_at = |ln_e: f64| rms_error(&curve, ln_e.exp());
+    let (min, max) = EXPONENT_RANGE;
+    let (mut low, mut high) = (min.ln(), max.ln());
+    for _ in 0..FIT_ITERATIONS {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if error_at(a) < error_at(b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+
+    let exponent = round(((low + high) / 2.0).exp(), 4);
+    ExponentFit {
+        exponent,
+        rms_error: rms_error(&curve, exponent),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponent_round_trips_through_points() {
+        let points = exponent_to_points(2.0, 3);
+        assert_eq!(points.len(), 3);
+        assert_eq!((points[1].input, points[1].output), (0.5, 0.25));
+
+        let fit = fit_exponent(&exponent_to_points(2.0, DEFAULT_CURVE_POINTS));
+        assert!((fit.exponent - 2.0).abs() < 0.05, "{:?}", fit);
+        assert!(fit.rms_error < 0.01, "{:?}", fit);
+
+        // A straight line is exponent 1
+        assert_eq!(fit_exponent(&[]).exponent, 1.0);
+
+        // An S-curve has no good exponent
+        let s_curve = [
+            CurvePoint {
+                input: 0.3,
+                output: 0.1,
+            },
+            CurvePoint {
+                input: 0.7,
+                output: 0.9,
+            },
+        ];
+        assert!(fit_exponent(&s_curve).rms_error > 0.05);
+    }
+}
@@ -0,0 +1,140 @@
+//! Evaluate an axis response the way SC does
+//!
+//! The curve editor graph and the live "dot on the curve" preview both need the output
+//! SC will produce for an input. Computing it in one place keeps the two consistent, and
+//! consistent with what gets written to actionmaps.xml. SC describes the positive half of
+//! the axis (0..1) and mirrors it; an input goes through inversion, the deadzone and
+//! saturation (rescaling what's left to the full range) and then the exponent or curve.
+
+use crate::controls::{CurveInterpolation, CurvePoint};
+use crate::curve_interpolation;
+use serde::{Deserialize, Serialize};
+
+/// Samples returned when the caller doesn't ask for a number
+pub const DEFAULT_SAMPLES: usize = 101;
+
+/// Most samples we'll compute for one request
+pub const MAX_SAMPLES: usize = 2001;
+
+/// An axis response: curve points or an exponent, with inversion, deadzone and saturation
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AxisCurve {
+    /// Curve points over 0..1; when empty the exponent is used
+    #[serde(default)]
+    pub points: Vec<CurvePoint>,
+    /// How the points are joined; smooth curves are evaluated as their resampled points
+    #[serde(default)]
+    pub interpolation: CurveInterpolation,
+    pub exponent: Option<f64>,
+    #[serde(default)]
+    pub invert: bool,
+    pub deadzone: Option<f64>,
+    pub saturation: Option<f64>,
+}
+
+/// One input and the output SC produces for it
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct CurveSample {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl AxisCurve {
+    /// The points SC will get, sorted, with its implicit (0,0) and (1,1) ends
+    fn anchored_points(&self) -> Vec<(f64, f64)> {
+        curve_interpolation::anchored(&curve_interpolation::resample(
+            &self.points,
+            self.interpolation,
+        ))
+    }
+
+    /// The exponent or curve alone, for x in 0..1
+    pub fn shape(&self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        if self.points.is_empty() {
+            return x.powf(self.exponent.unwrap_or(1.0));
+        }
+        let points = self.anchored_points();
+        let upper = points
+            .iter()
+            .position(|p| p.0 >= x)
+            .unwrap_or(points.len() - 1)
+            .max(1);
+        let (x0, y0) = points[upper - 1];
+        let (x1, y1) = points[upper];
+        if x1 <= x0 {
+            return y1;
+        }
+        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+    }
+
+    /// SC's output for an axis position in -1..1
+    pub fn evaluate(&self, position: f64) -> f64 {
+        let position = position.clamp(-1.0, 1.0);
+        let sign = position.signum() * if self.invert { -1.0 } else { 1.0 };
+        let deadzone = self.deadzone.unwrap_or(0.0).clamp(0.0, 1.0);
+        let saturation = self.saturation.unwrap_or(1.0).clamp(0.0, 1.0);
+
+        let magnitude = position.abs();
+        if magnitude <= deadzone || saturation <= deadzone {
+            return 0.0;
+        }
+        let scaled = ((magnitude - deadzone) / (saturation - deadzone)).min(1.0);
+        sign * self.shape(scaled)
+    }
+
+    /// `count` evenly spaced samples over 0..1, the half of the axis the editor draws
+    pub fn sample(&self, count: usize) -> Vec<CurveSample> {
+        let count = count.clamp(2, MAX_SAMPLES);
+        (0..count)
+            .map(|i| {
+                let input = i as f64 / (count - 1) as f64;
+                CurveSample {
+                    input,
+                    output: self.evaluate(input),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_applies_deadzone_saturation_and_curve() {
+        let exponent = AxisCurve {
+            exponent: Some(2.0),
+            deadzone: Some(0.1),
+            saturation: Some(0.9),
+            ..Default::default()
+        };
+        assert_eq!(exponent.evaluate(0.05), 0.0);
+        assert!((exponent.evaluate(0.5) - 0.25).abs() < 1e-9);
+        assert!((exponent.evaluate(-0.5) + 0.25).abs() < 1e-9);
+        assert_eq!(exponent.evaluate(0.95), 1.0);
+
+        let curve = AxisCurve {
+            points: vec![CurvePoint {
+                input: 0.5,
+                output: 0.2,
+            }],
+            invert: true,
+            ..Default::default()
+        };
+        assert!((curve.evaluate(0.25) + 0.1).abs() < 1e-9);
+        assert!((curve.evaluate(0.75) + 0.6).abs() < 1e-9);
+        assert_eq!(curve.evaluate(1.0), -1.0);
+
+        let samples = AxisCurve::default().sample(5);
+        assert_eq!(samples.len(), 5);
+        assert_eq!(
+            samples[2],
+            CurveSample {
+                input: 0.5,
+                output: 0.5
+            }
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Export curves for use in other remapping tools
+//!
+//! Star Citizen curves only describe one half of the axis (0..1, mirrored by the game).
+//! Joystick Gremlin response curves span the full -1..1 range, so the points are mirrored
+//! on export. The CSV export is the raw 0..1 points for anything else.
+
+use crate::controls::CurvePoint;
+use serde::Deserialize;
+
+/// Number of points used when exporting an exponent as a curve
+const EXPONENT_SAMPLES: usize = 10;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CurveExportFormat {
+    /// Joystick Gremlin `<response-curve>` snippet
+    Gremlin,
+    Csv,
+}
+
+/// The axis settings to export
+#[derive(Debug, Deserialize)]
+pub struct CurveExportInput {
+    #[serde(default)]
+    pub points: Vec<CurvePoint>,
+    /// Used when there are no points (exponent curve mode)
+    pub exponent: Option<f64>,
+    #[serde(default)]
+    pub invert: bool,
+    pub deadzone: Option<f64>,
+    pub saturation: Option<f64>,
+}
+
+/// The curve as 0..1 points, sampling the exponent when there are no explicit points
+fn curve_points(input: &CurveExportInput) -> Vec<(f64, f64)> {
+    let mut points: Vec<(f64, f64)> = if !input.points.is_empty() {
+        input.points.iter().map(|p| (p.input, p.output)).collect()
+    } else {
+        let exponent = input.exponent.unwrap_or(1.0);
+        (0..=EXPONENT_SAMPLES)
+            .map(|i| {
+                let x = i as f64 / EXPONENT_SAMPLES as f64;
+                (x, x.powf(exponent))
+            })
+            .collect()
+    };
+
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // SC curves implicitly start at (0,0) and end at (1,1)
+    if points.first().is_none_or(|p| p.0 > 0.0) {
+        points.insert(0, (0.0, 0.0));
+    }
+    if points.last().is_none_or(|p| p.0 < 1.0) {
+        points.push((1.0, 1.0));
+    }
+    points
+}
+
+fn fmt(value: f64) -> String {
+    let rounded = (value * 10000.0).round() / 10000.0;
+    // Avoid "-0"
+    format!("{}", if rounded == 0.0 { 0.0 } else { rounded })
+}
+
+/// Write the curve as a Joystick Gremlin response curve, mirrored over -1..1
+pub fn to_gremlin_response_curve(input: &CurveExportInput) -> String {
+    let sign = if input.invert { -1.0 } else { 1.0 };
+    let points = curve_points(input);
+
+    // Negative half mirrored from the positive one, without duplicating the center point
+    let mut control_points: Vec<(f64, f64)> = points
+        .iter()
+        .rev()
+        .filter(|(x, _)| *x > 0.0)
+        .map(|(x, y)| (-x, -y * sign))
+        .collect();
+    control_points.extend(points.iter().map(|(x, y)| (*x, y * sign)));
+
+    let deadzone = input.deadzone.unwrap_or(0.0);
+    let saturation = input.saturation.unwrap_or(1.0);
+
+    let mut xml = String::from("<response-curve>\n");
+    xml.push_str(&format!(
+        "    <deadzone low=\"{}\" center-low=\"{}\" center-high=\"{}\" high=\"{}\"/>\n",
+        fmt(-saturation),
+        fmt(-deadzone),
+        fmt(deadzone),
+        fmt(saturation)
+    ));
+    xml.push_str("    <mapping type=\"cubic-spline\">\n");
+    for (x, y) in control_points {
+        xml.push_str(&format!(
+            "        <control-point x=\"{}\" y=\"{}\"/>\n",
+            fmt(x),
+            fmt(y)
+        ));
+    }
+    xml.push_str("    </mapping>\n</response-curve>\n");
+    xml
+}
+
+/// Write the curve as "in,out" rows over 0..1
+pub fn to_csv(input: &CurveExportInput) -> String {
+    let sign = if input.invert { -1.0 } else { 1.0 };
+    let mut csv = String::from("in,out\n");
+    for (x, y) in curve_points(input) {
+        csv.push_str(&format!("{},{}\n", fmt(x), fmt(y * sign)));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gremlin_curve_mirrors_and_csv_adds_ends() {
+        let exponent = CurveExportInput {
+            points: Vec::new(),
+            exponent: Some(2.0),
+            invert: true,
+            deadzone: Some(0.05),
+            saturation: None,
+        };
+        let xml = to_gremlin_response_curve(&exponent);
+        assert!(
+            xml.contains(r#"<deadzone low="-1" center-low="-0.05" center-high="0.05" high="1"/>"#)
+        );
+        let control_points: Vec<&str> = xml
+            .lines()
+            .filter(|l| l.contains("<control-point"))
+            .map(str::trim)
+            .collect();
+        // Both halves of the sampled exponent, sharing one center point, and inverted
+        assert_eq!(control_points.len(), 2 * EXPONENT_SAMPLES + 1);
+        assert_eq!(control_points[0], r#"<control-point x="-1" y="1"/>"#);
+        assert_eq!(
+            control_points[EXPONENT_SAMPLES],
+            r#"<control-point x="0" y="0"/>"#
+        );
+        assert_eq!(
+            control_points[EXPONENT_SAMPLES + 5],
+            r#"<control-point x="0.5" y="-0.25"/>"#
+        );
+
+        let points = CurveExportInput {
+            points: vec![CurvePoint {
+                input: 0.5,
+                output: 0.3,
+            }],
+            exponent: None,
+            invert: false,
+            deadzone: None,
+            saturation: None,
+        };
+        assert_eq!(to_csv(&points), "in,out\n0,0\n0.5,0.3\n1,1\n");
+    }
+}
@@ -0,0 +1,171 @@
+//! Smooth interpolation between curve points
+//!
+//! SC joins the points of a nonlinearity_curve with straight segments. The editor can
+//! also join them with a monotone cubic spline (which passes through every point but
+//! never overshoots, so the curve can't bend backwards) or treat them as Bezier control
+//! points (which only pulls the curve towards them). Either way the smooth curve is
+//! resampled into a dense list of points, which is what actually gets written.
+
+use crate::controls::{CurveInterpolation, CurvePoint};
+use crate::curve_convert::round;
+
+/// Points a smooth curve is resampled into, not counting SC's implicit (0,0) and (1,1)
+pub const RESAMPLED_POINTS: usize = 24;
+
+/// The points sorted, with SC's implicit (0,0) and (1,1) ends and repeated inputs dropped
+pub fn anchored(points: &[CurvePoint]) -> Vec<(f64, f64)> {
+    let mut anchored: Vec<(f64, f64)> = points.iter().map(|p| (p.input, p.output)).collect();
+    anchored.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if anchored.first().is_none_or(|p| p.0 > 0.0) {
+        anchored.insert(0, (0.0, 0.0));
+    }
+    if anchored.last().is_none_or(|p| p.0 < 1.0) {
+        anchored.push((1.0, 1.0));
+    }
+    anchored.dedup_by(|b, a| b.0 <= a.0);
+    anchored
+}
+
+/// Tangents for a monotone cubic through `points` (Fritsch-Carlson)
+fn monotone_tangents(points: &[(f64, f64)]) -> Vec<f64> {
+    let slopes: Vec<f64> = points
+        .windows(2)
+        .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+        .collect();
+
+    let mut tangents = Vec::with_capacity(points.len());
+    tangents.push(slopes[0]);
+    for pair in slopes.windows(2) {
+        tangents.push(if pair[0] * pair[1] <= 0.0 {
+            0.0
+        } else {
+            (pair[0] + pair[1]) / 2.0
+        });
+    }
+    tangents.push(slopes[slopes.len() - 1]);
+
+    // Shrink tangents that would make a segment overshoot its end points
+    for (k, &slope) in slopes.iter().enumerate() {
+        if slope == 0.0 {
+            tangents[k] = 0.0;
+            tangents[k + 1] = 0.0;
+            continue;
+        }
+        let (a, b) = (tangents[k] / slope, tangents[k + 1] / slope);
+        let length = a * a + b * b;
+        if length > 9.0 {
+            let tau = 3.0 / length.sqrt();
+            tangents[k] = tau * a * slope;
+            tangents[k + 1] = tau * b * slope;
+        }
+    }
+    tangents
+}
+
+/// The spline's output at `x`
+fn spline_at(points: &[(f64, f64)], tangents: &[f64], x: f64) -> f64 {
+    let upper = points
+        .iter()
+        .position(|p| p.0 >= x)
+        .unwrap_or(points.len() - 1)
+        .max(1);
+    let ((x0, y0), (x1, y1)) = (points[upper - 1], points[upper]);
+    let h = x1 - x0;
+    let t = (x - x0) / h;
+    let (t2, t3) = (t * t, t * t * t);
+    (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+        + (t3 - 2.0 * t2 + t) * h * tangents[upper - 1]
+        + (-2.0 * t3 + 3.0 * t2) * y1
+        + (t3 - t2) * h * tangents[upper]
+}
+
+/// The Bezier curve at parameter `t`, by de Casteljau's construction
+fn bezier_at(points: &[(f64, f64)], t: f64) -> (f64, f64) {
+    let mut level = points.to_vec();
+    while level.len() > 1 {
+        level = level
+            .windows(2)
+            .map(|w| {
+                (
+                    w[0].0 + (w[1].0 - w[0].0) * t,
+                    w[0].1 + (w[1].1 - w[0].1) * t,
+                )
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// The points SC should get for a curve: the points themselves for linear
+/// interpolation, otherwise the smooth curve resampled
+pub fn resample(points: &[CurvePoint], interpolation: CurveInterpolation) -> Vec<CurvePoint> {
+    let anchored = anchored(points);
+    if interpolation.is_linear() || anchored.len() < 3 {
+        return points.to_vec();
+    }
+
+    let tangents = monotone_tangents(&anchored);
+    let mut resampled: Vec<CurvePoint> = (1..=RESAMPLED_POINTS)
+        .map(|i| {
+            let t = i as f64 / (RESAMPLED_POINTS + 1) as f64;
+            let (x, y) = match interpolation {
+                CurveInterpolation::Bezier => bezier_at(&anchored, t),
+                _ => (t, spline_at(&anchored, &tangents, t)),
+            };
+            CurvePoint {
+                input: round(x, 4),
+                output: round(y.clamp(0.0, 1.0), 4),
+            }
+        })
+        .collect();
+    resampled.dedup_by(|b, a| b.input <= a.input);
+    resampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smooth_curves_resample_monotonically() {
+        let points = vec![
+            CurvePoint {
+                input: 0.5,
+                output: 0.2,
+            },
+            CurvePoint {
+                input: 0.8,
+                output: 0.3,
+            },
+        ];
+
+        let linear = resample(&points, CurveInterpolation::Linear);
+        assert_eq!(linear.len(), 2);
+
+        // The spline passes through the points
+        let knots = anchored(&points);
+        let tangents = monotone_tangents(&knots);
+        assert!((spline_at(&knots, &tangents, 0.5) - 0.2).abs() < 1e-9);
+        assert!((spline_at(&knots, &tangents, 0.8) - 0.3).abs() < 1e-9);
+
+        // A single control point gives a quadratic Bezier
+        let control = [CurvePoint {
+            input: 0.5,
+            output: 0.0,
+        }];
+        let quadratic = bezier_at(&anchored(&control), 0.5);
+        assert!((quadratic.0 - 0.5).abs() < 1e-9 && (quadratic.1 - 0.25).abs() < 1e-9);
+
+        for interpolation in [CurveInterpolation::Spline, CurveInterpolation::Bezier] {
+            let smooth = resample(&points, interpolation);
+            assert_eq!(smooth.len(), RESAMPLED_POINTS, "{:?}", interpolation);
+            for pair in smooth.windows(2) {
+                assert!(pair[0].input < pair[1].input, "{:?}", interpolation);
+                assert!(pair[0].output <= pair[1].output, "{:?}", interpolation);
+            }
+        }
+
+        // Nothing to smooth without points
+        assert!(resample(&[], CurveInterpolation::Spline).is_empty());
+    }
+}
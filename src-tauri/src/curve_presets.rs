@@ -0,0 +1,252 @@
+//! Library of named response curves
+//!
+//! Presets ("gentle", "precision aim", ...) are kept in their own JSON file in the app
+//! data directory, separate from any profile, so the same curve can be applied to any
+//! axis of any profile by name.
+
+use crate::controls::{
+    ControlOptionSettings, CurveData, CurveInterpolation, CurvePoint, EXPONENT_RANGE,
+};
+use crate::curve_validation;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the preset store inside the app data directory
+pub const PRESETS_FILE_NAME: &str = "curve_presets.json";
+
+/// A reusable response curve
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CurvePreset {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// "exponent" or "curve", same as ControlOptionSettings::curve_mode
+    pub curve_mode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exponent: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub points: Vec<CurvePoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct PresetStore {
+    #[serde(default)]
+    presets: Vec<CurvePreset>,
+}
+
+fn point(input: f64, output: f64) -> CurvePoint {
+    CurvePoint { input, output }
+}
+
+/// Presets the store starts out with
+fn builtin_presets() -> Vec<CurvePreset> {
+    vec![
+        CurvePreset {
+            name: "Gentle".to_string(),
+            description: Some("Slightly softened center, full response at the edges".to_string()),
+            curve_mode: "exponent".to_string(),
+            exponent: Some(1.5),
+            points: Vec::new(),
+        },
+        CurvePreset {
+            name: "Precision aim".to_string(),
+            description: Some("Very fine control near center for small corrections".to_string()),
+            curve_mode: "curve".to_string(),
+            exponent: None,
+            points: vec![
+                point(0.2, 0.05),
+                point(0.4, 0.15),
+                point(0.6, 0.3),
+                point(0.8, 0.55),
+            ],
+        },
+        CurvePreset {
+            name: "Racing".to_string(),
+            description: Some("Responsive center with a linear run to full deflection".to_string()),
+            curve_mode: "curve".to_string(),
+            exponent: None,
+            points: vec![point(0.25, 0.3), point(0.5, 0.55), point(0.75, 0.78)],
+        },
+    ]
+}
+
+fn presets_path(dir: &Path) -> PathBuf {
+    dir.join(PRESETS_FILE_NAME)
+}
+
+/// Load the presets, starting with the built-in ones if the store doesn't exist yet
+pub fn load_presets(dir: &Path) -> Result<Vec<CurvePreset>, String> {
+    let path = presets_path(dir);
+    if !path.exists() {
+        return Ok(builtin_presets());
+    }
+
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read curve presets: {}", e))?;
+    let store: PresetStore =
+        serde_json::from_str(&json).map_err(|e| format!("Failed to parse curve presets: {}", e))?;
+    Ok(store.presets)
+}
+
+fn save_presets(dir: &Path, presets: Vec<CurvePreset>) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+    let json = serde_json::to_string_pretty(&PresetStore { presets })
+        .map_err(|e| format!("Failed to serialize curve presets: {}", e))?;
+    std::fs::write(presets_path(dir), json)
+        .map_err(|e| format!("Failed to write curve presets: {}", e))
+}
+
+/// Preset names are matched case-insensitively so "Gentle" and "gentle" can't coexist
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+pub fn find_preset(dir: &Path, name: &str) -> Result<CurvePreset, String> {
+    load_presets(dir)?
+        .into_iter()
+        .find(|p| same_name(&p.name, name))
+        .ok_or_else(|| format!("No curve preset named '{}'", name))
+}
+
+/// Check a preset's mode, normalize its points and clamp its exponent to what SC accepts
+pub fn validate(mut preset: CurvePreset) -> Result<CurvePreset, String> {
+    match preset.curve_mode.as_str() {
+        "exponent" if preset.exponent.is_none() => {
+            return Err("Exponent presets need an exponent value".to_string())
+        }
+        "curve" if preset.points.is_empty() => {
+            return Err("Curve presets need at least one point".to_string())
+        }
+        "exponent" | "curve" => {}
+        other => return Err(format!("Unknown curve mode: {}", other)),
+    }
+    preset.points = curve_validation::normalize_points(&preset.points).map_err(|issues| {
+        let messages: Vec<String> = issues.into_iter().map(|(_, _, message)| message).collect();
+        format!("Invalid curve points: {}", messages.join("; "))
+    })?;
+    if let Some(exponent) = preset.exponent {
+        let (min, max) = EXPONENT_RANGE;
+        preset.exponent = Some(exponent.clamp(min, max));
+    }
+    Ok(preset)
+}
+
+/// Add a preset, or replace the one with the same name
+pub fn save_preset(dir: &Path, preset: CurvePreset) -> Result<Vec<CurvePreset>, String> {
+    if preset.name.trim().is_empty() {
+        return Err("Curve preset name cannot be empty".to_string());
+    }
+    let preset = validate(preset)?;
+
+    let mut presets = load_presets(dir)?;
+    match presets
+        .iter_mut()
+        .find(|p| same_name(&p.name, &preset.name))
+    {
+        Some(existing) => *existing = preset,
+        None => presets.push(preset),
+    }
+    save_presets(dir, presets.clone())?;
+    Ok(presets)
+}
+
+pub fn delete_preset(dir: &Path, name: &str) -> Result<Vec<CurvePreset>, String> {
+    let mut presets = load_presets(dir)?;
+    let before = presets.len();
+    presets.retain(|p| !same_name(&p.name, name));
+    if presets.len() == before {
+        return Err(format!("No curve preset named '{}'", name));
+    }
+    save_presets(dir, presets.clone())?;
+    Ok(presets)
+}
+
+/// Set an option's curve from a preset, leaving its other settings (invert, deadzone, ...) alone
+pub fn apply_to_option(preset: &CurvePreset, settings: &mut ControlOptionSettings) {
+    settings.curve_mode = Some(preset.curve_mode.clone());
+    if preset.curve_mode == "exponent" {
+        settings.exponent = preset.exponent;
+        settings.curve = None;
+    } else {
+        settings.exponent = None;
+        settings.curve = Some(CurveData {
+            points: preset.points.clone(),
+            interpolation: CurveInterpolation::Linear,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::TempDir;
+
+    #[test]
+    fn test_presets_saved_validated_and_applied() {
+        let temp = TempDir::new("curve-presets");
+        let dir = temp.path();
+
+        // A new store starts with the built-in presets
+        assert_eq!(load_presets(dir).unwrap().len(), builtin_presets().len());
+
+        let custom = CurvePreset {
+            name: "Custom".to_string(),
+            description: None,
+            curve_mode: "curve".to_string(),
+            exponent: None,
+            points: vec![point(0.7, 0.5), point(0.3, 0.1)],
+        };
+        let presets = save_preset(dir, custom.clone()).unwrap();
+        assert_eq!(presets.len(), builtin_presets().len() + 1);
+
+        // Points are stored sorted and found regardless of case
+        let saved = find_preset(dir, " custom ").unwrap();
+        assert_eq!(saved.points[0].input, 0.3);
+
+        // Points outside 0..1 and unknown modes are rejected
+        let out_of_range = CurvePreset {
+            points: vec![point(0.5, 1.5)],
+            ..custom.clone()
+        };
+        assert!(save_preset(dir, out_of_range).is_err());
+        let unknown_mode = CurvePreset {
+            curve_mode: "spline".to_string(),
+            ..custom.clone()
+        };
+        assert!(save_preset(dir, unknown_mode).is_err());
+
+        // Exponents are clamped to what the game accepts
+        let steep = CurvePreset {
+            curve_mode: "exponent".to_string(),
+            exponent: Some(10.0),
+            points: Vec::new(),
+            ..custom
+        };
+        assert_eq!(
+            validate(steep.clone()).unwrap().exponent,
+            Some(EXPONENT_RANGE.1)
+        );
+
+        // Applying replaces the curve but keeps the option's other settings
+        let mut settings = ControlOptionSettings {
+            invert: Some(true),
+            curve_mode: Some("curve".to_string()),
+            curve: Some(CurveData {
+                points: saved.points.clone(),
+                interpolation: CurveInterpolation::Linear,
+            }),
+            ..Default::default()
+        };
+        apply_to_option(&steep, &mut settings);
+        assert_eq!(settings.invert, Some(true));
+        assert_eq!(settings.curve_mode.as_deref(), Some("exponent"));
+        assert!(settings.curve.is_none());
+
+        assert_eq!(
+            delete_preset(dir, "CUSTOM").unwrap().len(),
+            builtin_presets().len()
+        );
+        assert!(delete_preset(dir, "Custom").is_err());
+    }
+}
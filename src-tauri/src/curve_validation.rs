@@ -0,0 +1,199 @@
+//! Validation and normalization of response curves
+//!
+//! Curve points come from the editor, imported files and presets. Before they are
+//! written anywhere they are checked: every point inside [0,1], and strictly increasing
+//! on the input axis. Problems that have one obvious fix (points out of order, exact
+//! duplicates) are fixed; anything else is reported per point so the editor can
+//! highlight it, instead of writing a curve the game would misread.
+
+use crate::controls::{ControlsFile, CurvePoint, DeviceInstanceSettings};
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveIssueKind {
+    /// NaN or infinite value
+    NotFinite,
+    /// Value outside [0,1]
+    OutOfRange,
+    /// Two points share an input value but have different outputs
+    DuplicateInput,
+}
+
+/// One problem with one curve
+#[derive(Debug, Serialize, Clone)]
+pub struct CurveIssue {
+    pub device_type: String,
+    pub instance: String,
+    pub option: String,
+    /// Index of the offending point in the curve as given
+    pub point_index: usize,
+    pub kind: CurveIssueKind,
+    pub message: String,
+}
+
+/// A point-level problem, before we know which option it belongs to
+type PointIssue = (usize, CurveIssueKind, String);
+
+/// Check a curve and return it sorted and deduplicated
+pub fn normalize_points(points: &[CurvePoint]) -> Result<Vec<CurvePoint>, Vec<PointIssue>> {
+    let mut issues = Vec::new();
+
+    for (index, point) in points.iter().enumerate() {
+        for (label, value) in [("in", point.input), ("out", point.output)] {
+            if !value.is_finite() {
+                issues.push((
+                    index,
+                    CurveIssueKind::NotFinite,
+                    format!("Point {}: '{}' is not a number", index + 1, label),
+                ));
+            } else if !(0.0..=1.0).contains(&value) {
+                issues.push((
+                    index,
+                    CurveIssueKind::OutOfRange,
+                    format!(
+                        "Point {}: '{}' is {} but must be between 0 and 1",
+                        index + 1,
+                        label,
+                        value
+                    ),
+                ));
+            }
+        }
+    }
+    if !issues.is_empty() {
+        return Err(issues);
+    }
+
+    let mut indexed: Vec<(usize, &CurvePoint)> = points.iter().enumerate().collect();
+    indexed.sort_by(|a, b| a.1.input.total_cmp(&b.1.input));
+
+    let mut normalized: Vec<CurvePoint> = Vec::with_capacity(points.len());
+    for (index, point) in indexed {
+        match normalized.last() {
+            Some(last) if last.input == point.input => {
+                if last.output != point.output {
+                    issues.push((
+                        index,
+                        CurveIssueKind::DuplicateInput,
+                        format!(
+                            "Point {}: another point already has input {} with a different output",
+                            index + 1,
+                            point.input
+                        ),
+                    ));
+                }
+            }
+            _ => normalized.push(point.clone()),
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(normalized)
+    } else {
+        Err(issues)
+    }
+}
+
+fn normalize_device(
+    device_type: &str,
+    instance: &str,
+    device: &mut DeviceInstanceSettings,
+    issues: &mut Vec<CurveIssue>,
+) {
+    for (option, settings) in device.options.iter_mut() {
+        let Some(curve) = settings.curve.as_mut() else {
+            continue;
+        };
+        match normalize_points(&curve.points) {
+            Ok(points) => curve.points = points,
+            Err(point_issues) => issues.extend(point_issues.into_iter().map(
+                |(point_index, kind, message)| CurveIssue {
+                    device_type: device_type.to_string(),
+                    instance: instance.to_string(),
+                    option: option.clone(),
+                    point_index,
+                    kind,
+                    message,
+                },
+            )),
+        }
+    }
+}
+
+/// Normalize every curve in a profile, or return all the problems found
+pub fn normalize_controls(controls: &mut ControlsFile) -> Result<(), Vec<CurveIssue>> {
+    let mut issues = Vec::new();
+
+    if let Some(keyboard) = controls.devices.keyboard.as_mut() {
+        normalize_device("keyboard", "1", keyboard, &mut issues);
+    }
+    if let Some(mouse) = controls.devices.mouse.as_mut() {
+        normalize_device("mouse", "1", mouse, &mut issues);
+    }
+    if let Some(gamepad) = controls.devices.gamepad.as_mut() {
+        normalize_device("gamepad", "1", gamepad, &mut issues);
+    }
+    if let Some(joysticks) = controls.devices.joystick.as_mut() {
+        for (instance, joystick) in joysticks.iter_mut() {
+            normalize_device("joystick", instance, joystick, &mut issues);
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        issues.sort_by(|a, b| {
+            (&a.device_type, &a.instance, &a.option, a.point_index).cmp(&(
+                &b.device_type,
+                &b.instance,
+                &b.option,
+                b.point_index,
+            ))
+        });
+        Err(issues)
+    }
+}
+
+/// Collapse issues into one message for commands that return plain errors
+pub fn describe_issues(issues: &[CurveIssue]) -> String {
+    let details: Vec<String> = issues
+        .iter()
+        .map(|i| {
+            format!(
+                "{} {} {}: {}",
+                i.device_type, i.instance, i.option, i.message
+            )
+        })
+        .collect();
+    format!("Invalid curve data:\n{}", details.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(input: f64, output: f64) -> CurvePoint {
+        CurvePoint { input, output }
+    }
+
+    #[test]
+    fn test_points_sorted_and_deduplicated() {
+        let points = normalize_points(&[p(0.5, 0.3), p(0.2, 0.1), p(0.5, 0.3)]).unwrap();
+        let inputs: Vec<f64> = points.iter().map(|p| p.input).collect();
+        assert_eq!(inputs, vec![0.2, 0.5]);
+    }
+
+    #[test]
+    fn test_invalid_points_reported() {
+        let issues = normalize_points(&[p(0.2, 1.5), p(f64::NAN, 0.1)]).unwrap_err();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].0, 0);
+        assert_eq!(issues[0].1, CurveIssueKind::OutOfRange);
+        assert_eq!(issues[1].1, CurveIssueKind::NotFinite);
+
+        let issues = normalize_points(&[p(0.4, 0.2), p(0.4, 0.3)]).unwrap_err();
+        assert_eq!(issues[0].0, 1);
+        assert_eq!(issues[0].1, CurveIssueKind::DuplicateInput);
+    }
+}
@@ -0,0 +1,327 @@
+//! Curve re-apply watchdog
+//!
+//! Star Citizen drops custom curve/exponent settings when it rewrites actionmaps.xml,
+//! so a normal apply can't make them stick. The watchdog watches the file, and each
+//! time the game rewrites it, re-applies the curves from the selected .sccontrols profile.
+
+use crate::apply_rebase;
+use crate::backups::{self, BackupLocation};
+use crate::controls::{self, ActionmapsControlOption, ActionmapsDeviceOptions, ControlsFile};
+use crate::curve_validation;
+use crate::watcher::{self, FileWatcher};
+use crate::write_lock::{self, WriteLock};
+use log::{error, info};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+/// Watchdog configuration, also returned to the frontend as its status
+#[derive(Debug, Serialize, Clone)]
+pub struct WatchdogConfig {
+    pub actionmaps_path: String,
+    /// The .sccontrols profile whose curves get re-applied
+    pub profile_path: String,
+    /// Skip the write when the file already has the profile's curves
+    pub only_when_curves_differ: bool,
+}
+
+/// Payload of the "curves-reapplied" event
+#[derive(Debug, Serialize, Clone)]
+pub struct CurvesReappliedEvent {
+    pub actionmaps_path: String,
+    pub backup_path: String,
+    pub options_applied: usize,
+}
+
+/// Does this option carry any curve/exponent data?
+fn has_curve_data(option: &ActionmapsControlOption) -> bool {
+    !option.curve_points.is_empty() || option.attributes.iter().any(|(k, _)| k == "exponent")
+}
+
+fn exponent_of(option: &ActionmapsControlOption) -> Option<f64> {
+    option
+        .attributes
+        .iter()
+        .find(|(k, _)| k == "exponent")
+        .and_then(|(_, v)| v.parse().ok())
+}
+
+fn points_of(option: &ActionmapsControlOption) -> Vec<(Option<f64>, Option<f64>)> {
+    option
+        .curve_points
+        .iter()
+        .map(|p| (p.in_val.parse().ok(), p.out_val.parse().ok()))
+        .collect()
+}
+
+/// Compare curve data numerically, so "1.50" and "1.5" count as the same
+fn curves_match(wanted: &ActionmapsControlOption, existing: &ActionmapsControlOption) -> bool {
+    exponent_of(wanted) == exponent_of(existing) && points_of(wanted) == points_of(existing)
+}
+
+/// The profile's curve options that need writing to an actionmaps.xml document, or
+/// `None` if there is nothing to do.
+fn pending_curves(
+    xml: &str,
+    controls_file: &ControlsFile,
+    only_when_curves_differ: bool,
+) -> Result<Option<Vec<ActionmapsDeviceOptions>>, String> {
+    // Never write curves the game would misread
+    let mut controls_file = controls_file.clone();
+    curve_validation::normalize_controls(&mut controls_file)
+        .map_err(|issues| curve_validation::describe_issues(&issues))?;
+
+    // Only options that actually have curves; everything else is left as the game wrote it
+    let wanted: Vec<ActionmapsDeviceOptions> =
+        controls::controls_to_actionmaps(&controls_file, true)
+            .into_iter()
+            .map(|mut device| {
+                device.options.retain(has_curve_data);
+                device
+            })
+            .filter(|device| !device.options.is_empty())
+            .collect();
+
+    if wanted.is_empty() {
+        return Ok(None);
+    }
+
+    if only_when_curves_differ {
+        let existing = controls::parse_actionmaps_options(xml)?;
+        let all_match = wanted.iter().all(|device| {
+            let existing_device = existing
+                .iter()
+                .find(|d| d.device_type == device.device_type && d.instance == device.instance);
+            device.options.iter().all(|opt| {
+                existing_device
+                    .and_then(|d| d.options.iter().find(|o| o.name == opt.name))
+                    .is_some_and(|existing_opt| curves_match(opt, existing_opt))
+            })
+        });
+
+        if all_match {
+            info!("Curves already match the profile, nothing to re-apply");
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(wanted))
+}
+
+/// Re-apply the profile's curve settings to an actionmaps.xml document, except on
+/// `excluded` options.
+///
+/// Returns the updated XML, or `None` if there is nothing to do.
+pub fn reapply_curves(
+    xml: &str,
+    controls_file: &ControlsFile,
+    only_when_curves_differ: bool,
+    excluded: &[String],
+) -> Result<Option<String>, String> {
+    pending_curves(xml, controls_file, only_when_curves_differ)?
+        .map(|wanted| controls::merge_options_into_xml(xml, wanted, excluded))
+        .transpose()
+}
+
+/// What one re-apply wrote
+#[derive(Debug)]
+pub struct Reapplied {
+    pub backup_path: String,
+    pub options_applied: usize,
+    /// The actionmaps.xml content written
+    pub written: String,
+}
+
+/// Re-apply the profile's curves to the watched actionmaps.xml.
+///
+/// The file is read only once the write lock is held, so nothing written between the
+/// change being detected and the lock being taken is lost. It's backed up to the backup
+/// store and written through `apply_rebase`, like a normal apply.
+pub fn reapply_to_file(
+    config: &WatchdogConfig,
+    lock_dir: &Path,
+    backup_location: &BackupLocation,
+    excluded: &[String],
+) -> Result<Option<Reapplied>, String> {
+    // Reload the profile every time so edits made since starting the watchdog are picked up
+    let json = std::fs::read_to_string(&config.profile_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let controls_file = ControlsFile::from_json(&json)?;
+
+    let _write_lock = WriteLock::acquire(lock_dir, "gui", write_lock::DEFAULT_WAIT)?;
+
+    let xml = std::fs::read_to_string(&config.actionmaps_path)
+        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+    let Some(pending) = pending_curves(&xml, &controls_file, config.only_when_curves_differ)?
+    else {
+        return Ok(None);
+    };
+    let options_applied = pending.iter().map(|d| d.options.len()).sum();
+
+    let backup_path = backups::create_backup(backup_location, &config.actionmaps_path)?;
+    let result = apply_rebase::write_rebased(&config.actionmaps_path, xml, pending, excluded)?;
+
+    Ok(Some(Reapplied {
+        backup_path,
+        options_applied,
+        written: result.written,
+    }))
+}
+
+/// Handles one detected rewrite of actionmaps.xml. Returns the hash of the content we wrote.
+fn on_actionmaps_changed(
+    config: &WatchdogConfig,
+    lock_dir: &Path,
+    app_handle: &AppHandle,
+) -> Result<Option<u64>, String> {
+    let backup_location = crate::backup_location(app_handle)?;
+    let excluded = crate::excluded_options(app_handle)?;
+    let Some(reapplied) = reapply_to_file(config, lock_dir, &backup_location, &excluded)? else {
+        return Ok(None);
+    };
+
+    info!(
+        "Re-applied {} curve setting(s) from {} to {}",
+        reapplied.options_applied, config.profile_path, config.actionmaps_path
+    );
+
+    let _ = app_handle.emit(
+        "curves-reapplied",
+        CurvesReappliedEvent {
+            actionmaps_path: config.actionmaps_path.clone(),
+            backup_path: reapplied.backup_path,
+            options_applied: reapplied.options_applied,
+        },
+    );
+
+    Ok(Some(watcher::content_hash(reapplied.written.as_bytes())))
+}
+
+/// A running watchdog. Dropping it stops the watcher.
+pub struct CurveWatchdog {
+    config: WatchdogConfig,
+    _watcher: FileWatcher,
+}
+
+impl CurveWatchdog {
+    pub fn start(
+        config: WatchdogConfig,
+        lock_dir: PathBuf,
+        app_handle: AppHandle,
+    ) -> Result<Self, String> {
+        // Fail early on a bad profile rather than on the first game exit
+        let json = std::fs::read_to_string(&config.profile_path)
+            .map_err(|e| format!("Failed to read controls file: {}", e))?;
+        ControlsFile::from_json(&json)?;
+
+        let callback_config = config.clone();
+        let watcher = FileWatcher::start(PathBuf::from(&config.actionmaps_path), move |_xml| {
+            // The file is re-read under the write lock, the content seen here may be stale
+            match on_actionmaps_changed(&callback_config, &lock_dir, &app_handle) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    error!("Curve watchdog failed to re-apply curves: {}", e);
+                    None
+                }
+            }
+        })?;
+
+        Ok(CurveWatchdog {
+            config,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::{ControlOptionSettings, CurveData, CurveInterpolation, CurvePoint};
+    use crate::sc_sim::{ScSim, SAMPLE_ACTIONMAPS};
+
+    /// A profile with a pitch curve, saved next to the sim's actionmaps.xml
+    fn watchdog_config(sim: &ScSim) -> WatchdogConfig {
+        let mut profile = ControlsFile::new("Curves".to_string());
+        profile.device_mut("joystick", "1").unwrap().options.insert(
+            "flight_move_pitch".to_string(),
+            ControlOptionSettings {
+                curve_mode: Some("curve".to_string()),
+                curve: Some(CurveData {
+                    points: vec![CurvePoint {
+                        input: 0.5,
+                        output: 0.25,
+                    }],
+                    interpolation: CurveInterpolation::Linear,
+                }),
+                ..Default::default()
+            },
+        );
+        let profile_path = sim.dir().join("curves.sccontrols");
+        std::fs::write(&profile_path, profile.to_json().unwrap()).unwrap();
+
+        WatchdogConfig {
+            actionmaps_path: sim.path_str().to_string(),
+            profile_path: profile_path.to_string_lossy().to_string(),
+            only_when_curves_differ: true,
+        }
+    }
+
+    #[test]
+    fn test_reapply_after_exit_backs_up_to_the_store() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        let config = watchdog_config(&sim);
+        let store = backups::resolve_location(None, &sim.dir().join("backups"));
+
+        sim.exit_game();
+        let exited = sim.read();
+        let reapplied = reapply_to_file(&config, sim.dir(), &store, &[])
+            .unwrap()
+            .expect("curves should be re-applied");
+
+        assert_eq!(reapplied.options_applied, 1);
+        assert_eq!(reapplied.written, sim.read());
+        assert!(reapplied
+            .written
+            .contains("<point in=\"0.5\" out=\"0.25\"/>"));
+
+        // The backup is the file as the game left it, in the store rather than next to it
+        assert_eq!(
+            std::fs::read_to_string(&reapplied.backup_path).unwrap(),
+            exited
+        );
+        assert_eq!(backups::list_backups(Path::new(&store.path)).len(), 1);
+        assert!(backups::list_legacy_backups(Path::new(sim.path_str())).is_empty());
+
+        // Nothing to do (and no backup) once they're back
+        assert!(reapply_to_file(&config, sim.dir(), &store, &[])
+            .unwrap()
+            .is_none());
+        assert_eq!(backups::list_backups(Path::new(&store.path)).len(), 1);
+    }
+
+    #[test]
+    fn test_reapply_keeps_writes_made_after_detection() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        let config = watchdog_config(&sim);
+        let store = backups::resolve_location(None, &sim.dir().join("backups"));
+
+        // The watcher saw the exit, then the file changed again before the lock was taken
+        sim.exit_game();
+        let later = sim.read().replace(
+            "<flight_throttle_abs invert=\"0\"/>",
+            "<flight_throttle_abs invert=\"1\"/>",
+        );
+        std::fs::write(sim.path_str(), &later).unwrap();
+
+        reapply_to_file(&config, sim.dir(), &store, &[])
+            .unwrap()
+            .expect("curves should be re-applied");
+        let written = sim.read();
+        assert!(written.contains("<point in=\"0.5\" out=\"0.25\"/>"));
+        assert!(written.contains("<flight_throttle_abs invert=\"1\"/>"));
+    }
+}
@@ -0,0 +1,181 @@
+//! On-disk cache of the parsed defaultProfile
+//!
+//! The defaultProfile (AllBinds.xml) runs to tens of thousands of lines and used to be
+//! parsed into actions and the option catalog on every start. The parsed model is kept
+//! next to a hash of the XML it came from, so it's reused until a different
+//! defaultProfile ships with the app.
+
+use crate::keybindings::AllBinds;
+use crate::option_catalog::{self, OptionCatalog};
+use crate::watcher::content_hash;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the cache file inside the app cache directory
+const CACHE_FILE_NAME: &str = "default_profile_cache.json";
+
+/// Bumped whenever the parsed model changes shape, so older caches are parsed again
+const CACHE_FORMAT: u32 = 1;
+
+/// Build information SC writes into each installation folder
+const BUILD_MANIFEST_FILE_NAME: &str = "build_manifest.id";
+
+/// What a cached model was parsed from
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CacheKey {
+    pub format: u32,
+    pub source_hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    key: CacheKey,
+    all_binds: AllBinds,
+    option_catalog: OptionCatalog,
+}
+
+/// The build id in an installation's build_manifest.id
+pub fn read_build_id(installation: &Path) -> Option<String> {
+    let json = std::fs::read_to_string(installation.join(BUILD_MANIFEST_FILE_NAME)).ok()?;
+    let manifest: serde_json::Value = serde_json::from_str(&json).ok()?;
+    let data = manifest.get("Data").unwrap_or(&manifest);
+    ["BuildId", "RequestedP4ChangeNum", "Version"]
+        .iter()
+        .find_map(|key| match data.get(key)? {
+            serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+}
+
+fn cache_path(dir: &Path) -> PathBuf {
+    dir.join(CACHE_FILE_NAME)
+}
+
+fn read_cache(dir: &Path, key: &CacheKey) -> Option<(AllBinds, OptionCatalog)> {
+    let json = std::fs::read(cache_path(dir)).ok()?;
+    match serde_json::from_slice::<CacheFile>(&json) {
+        Ok(cache) if cache.key == *key => Some((cache.all_binds, cache.option_catalog)),
+        Ok(_) => None,
+        Err(e) => {
+            warn!("Ignoring unreadable defaultProfile cache: {}", e);
+            None
+        }
+    }
+}
+
+fn write_cache(
+    dir: &Path,
+    key: CacheKey,
+    all_binds: &AllBinds,
+    option_catalog: &OptionCatalog,
+) -> Result<(), String> {
+    #[derive(Serialize)]
+    struct CacheFileRef<'a> {
+        key: CacheKey,
+        all_binds: &'a AllBinds,
+        option_catalog: &'a OptionCatalog,
+    }
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create cache directory: {}", e))?;
+    let json = serde_json::to_vec(&CacheFileRef {
+        key,
+        all_binds,
+        option_catalog,
+    })
+    .map_err(|e| format!("Failed to serialize defaultProfile cache: {}", e))?;
+    std::fs::write(cache_path(dir), json)
+        .map_err(|e| format!("Failed to write defaultProfile cache: {}", e))
+}
+
+/// The parsed defaultProfile: from the cache in `dir` when it matches, otherwise parsed
+/// from `xml` and cached. A cache that can't be written only costs the next start.
+pub fn load_or_parse(dir: &Path, xml: &str) -> Result<(AllBinds, OptionCatalog), String> {
+    let key = CacheKey {
+        format: CACHE_FORMAT,
+        source_hash: format!("{:016x}", content_hash(xml.as_bytes())),
+    };
+    let (all_binds, option_catalog, cached) = match read_cache(dir, &key) {
+        Some((all_binds, option_catalog)) => (all_binds, option_catalog, true),
+        None => {
+            let all_binds = AllBinds::from_xml(xml)?;
+            let option_catalog = option_catalog::parse_option_catalog(xml)?;
+            if let Err(e) = write_cache(dir, key.clone(), &all_binds, &option_catalog) {
+                warn!("{}", e);
+            }
+            (all_binds, option_catalog, false)
+        }
+    };
+    tracing::info!(
+        source_hash = %key.source_hash,
+        cached,
+        action_maps = all_binds.action_maps.len(),
+        options = option_catalog.options.len(),
+        "Loaded defaultProfile"
+    );
+    Ok((all_binds, option_catalog))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::TempDir;
+
+    #[test]
+    fn test_cache_follows_source() {
+        let temp = TempDir::new("default-profile-cache");
+        let dir = temp.path();
+        let xml = r#"<profile version="1">
+ <optiontree type="joystick" instances="8" name="root">
+  <optiongroup name="flight_move_pitch"/>
+ </optiontree>
+ <actionmap name="spaceship_general" version="2" UILabel="@ui_CGSpaceFlightCockpit">
+  <action name="v_eject" activationMode="press" keyboard="ralt+y"/>
+ </actionmap>
+</profile>"#;
+
+        let (parsed, catalog) = load_or_parse(dir, xml).unwrap();
+        assert_eq!(parsed.action_maps[0].actions[0].name, "v_eject");
+        assert_eq!(catalog.options.len(), 1);
+
+        // Same XML: served from the cache
+        let key = CacheKey {
+            format: CACHE_FORMAT,
+            source_hash: format!("{:016x}", content_hash(xml.as_bytes())),
+        };
+        assert!(read_cache(dir, &key).is_some());
+
+        // A different defaultProfile or an older cache format is parsed again
+        let changed = xml.replace("v_eject", "v_eject_now");
+        let (parsed, _) = load_or_parse(dir, &changed).unwrap();
+        assert_eq!(parsed.action_maps[0].actions[0].name, "v_eject_now");
+        assert!(read_cache(dir, &key).is_none());
+        let old_format = CacheKey {
+            format: CACHE_FORMAT - 1,
+            source_hash: format!("{:016x}", content_hash(changed.as_bytes())),
+        };
+        assert!(read_cache(dir, &old_format).is_none());
+    }
+
+    #[test]
+    fn test_read_build_id() {
+        let temp = TempDir::new("build-manifest");
+        let install = temp.path().join("LIVE");
+        std::fs::create_dir_all(&install).unwrap();
+        assert_eq!(read_build_id(&install), None);
+
+        std::fs::write(
+            install.join(BUILD_MANIFEST_FILE_NAME),
+            r#"{"Data": {"Branch": "sc-alpha-4.0", "BuildId": "9428532"}}"#,
+        )
+        .unwrap();
+        assert_eq!(read_build_id(&install).as_deref(), Some("9428532"));
+
+        std::fs::write(
+            install.join(BUILD_MANIFEST_FILE_NAME),
+            r#"{"RequestedP4ChangeNum": 9430001}"#,
+        )
+        .unwrap();
+        assert_eq!(read_build_id(&install).as_deref(), Some("9430001"));
+    }
+}
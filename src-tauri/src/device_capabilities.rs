@@ -0,0 +1,381 @@
+//! Full capability enumeration for connected game controllers
+//!
+//! Reads each device's HID report descriptor to report what DirectInput would:
+//! the product GUID, axis count and types, button count and POV (hat) count.
+//! Axis indices follow the same ordering as `get_directinput_to_hid_axis_mapping`,
+//! so they line up with the jsN_axisM strings used elsewhere.
+
+use crate::directinput;
+use crate::hid_reader;
+use crate::product_names;
+use hidreport::{Field, Report, ReportDescriptor};
+use hut::Usage;
+use log::warn;
+use rusty_xinput::XInputHandle;
+use serde::Serialize;
+
+/// HID usage ID of the hat switch on the Generic Desktop page
+const HID_HAT_SWITCH_USAGE: u16 = 0x39;
+
+/// SC names of the Generic Desktop X..Rz axes (usages 0x30..0x35)
+const SC_FIXED_AXES: [&str; 6] = ["x", "y", "z", "rotx", "roty", "rotz"];
+
+/// DirectInput has two slider slots; anything beyond them SC never sees
+const SC_SLIDERS: [&str; 2] = ["slider1", "slider2"];
+
+/// SC names of the XInput axes, in the order `enumerate_devices` lists them
+const SC_XINPUT_AXES: [&str; 6] = [
+    "thumblx",
+    "thumbly",
+    "thumbrx",
+    "thumbry",
+    "triggerl_btn",
+    "triggerr_btn",
+];
+
+/// SC names of the XInput buttons
+const SC_XINPUT_BUTTONS: [&str; 10] = [
+    "a",
+    "b",
+    "x",
+    "y",
+    "shoulderl",
+    "shoulderr",
+    "back",
+    "start",
+    "thumbl",
+    "thumbr",
+];
+
+/// A single axis as DirectInput would expose it
+#[derive(Debug, Serialize, Clone)]
+pub struct AxisCapability {
+    /// 1-based DirectInput axis index
+    pub index: u32,
+    pub hid_usage_page: u16,
+    pub hid_usage_id: u16,
+    /// Axis type from the HID usage tables, e.g. "X", "Rz", "Slider", "Throttle"
+    pub name: String,
+    pub logical_min: i32,
+    pub logical_max: i32,
+}
+
+/// Counts and axis types read from a report descriptor
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DescriptorCapabilities {
+    pub axes: Vec<AxisCapability>,
+    pub button_count: usize,
+    pub pov_count: usize,
+}
+
+/// Everything we know about one connected controller
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceCapabilities {
+    /// 1-based instance in enumeration order
+    pub instance: usize,
+    /// DirectInput product GUID, as used in SC Product strings
+    pub guid: Option<String>,
+    /// Our "vid:pid" device id
+    pub uuid: String,
+    pub product_name: String,
+    pub manufacturer: Option<String>,
+    /// "joystick" or "gamepad"
+    pub device_type: String,
+    pub axis_count: usize,
+    pub axes: Vec<AxisCapability>,
+    pub button_count: usize,
+    pub pov_count: usize,
+    pub path: Option<String>,
+}
+
+/// An axis and the input name SC binds it as
+#[derive(Debug, Serialize, Clone)]
+pub struct ProbedAxis {
+    #[serde(flatten)]
+    pub axis: AxisCapability,
+    /// e.g. "rotz" or "slider1"; None when the axis has no DirectInput slot left
+    pub sc_name: Option<String>,
+}
+
+/// The exact inputs one device exposes, by their SC names
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceProbe {
+    pub instance: usize,
+    pub device_type: String,
+    pub product_name: String,
+    pub axes: Vec<ProbedAxis>,
+    /// e.g. ["button1", "button2", ...] or ["a", "b", ...] for a gamepad
+    pub buttons: Vec<String>,
+    /// e.g. ["hat1"], or ["dpad"] for a gamepad
+    pub hats: Vec<String>,
+}
+
+/// Name axes the way DirectInput slots them: X..Rz by usage, everything else (sliders,
+/// dials, throttles, rudders) into the two slider slots in descriptor order
+fn sc_axis_names(axes: &[AxisCapability]) -> Vec<Option<String>> {
+    let mut taken: Vec<&str> = Vec::new();
+    let mut sliders = SC_SLIDERS.iter();
+
+    axes.iter()
+        .map(|axis| {
+            let fixed = (axis.hid_usage_page == 0x01)
+                .then(|| axis.hid_usage_id.checked_sub(0x30))
+                .flatten()
+                .and_then(|i| SC_FIXED_AXES.get(i as usize));
+            let name = match fixed {
+                Some(name) if !taken.contains(name) => Some(*name),
+                Some(_) => None,
+                None => sliders.next().copied(),
+            }?;
+            taken.push(name);
+            Some(name.to_string())
+        })
+        .collect()
+}
+
+/// What a connected device exposes, named as SC would bind it
+pub fn probe(device: DeviceCapabilities) -> DeviceProbe {
+    let xinput = device.uuid.starts_with("xinput_");
+
+    let sc_names = if xinput {
+        SC_XINPUT_AXES.iter().map(|n| Some(n.to_string())).collect()
+    } else {
+        sc_axis_names(&device.axes)
+    };
+    let axes = device
+        .axes
+        .into_iter()
+        .zip(sc_names)
+        .map(|(axis, sc_name)| ProbedAxis { axis, sc_name })
+        .collect();
+
+    let (buttons, hats) = if xinput {
+        (
+            SC_XINPUT_BUTTONS.iter().map(|b| b.to_string()).collect(),
+            vec!["dpad".to_string()],
+        )
+    } else {
+        (
+            (1..=device.button_count)
+                .map(|n| format!("button{}", n))
+                .collect(),
+            (1..=device.pov_count)
+                .map(|n| format!("hat{}", n))
+                .collect(),
+        )
+    };
+
+    DeviceProbe {
+        instance: device.instance,
+        device_type: device.device_type,
+        product_name: device.product_name,
+        axes,
+        buttons,
+        hats,
+    }
+}
+
+/// Read axis types, button and POV counts from a HID report descriptor
+pub fn capabilities_from_descriptor(descriptor: &[u8]) -> Result<DescriptorCapabilities, String> {
+    let rdesc = ReportDescriptor::try_from(descriptor)
+        .map_err(|e| format!("Failed to parse report descriptor: {:?}", e))?;
+
+    let mut caps = DescriptorCapabilities::default();
+    let mut directinput_index: u32 = 1;
+
+    for report in rdesc.input_reports() {
+        for field in report.fields() {
+            let Field::Variable(var) = field else {
+                continue;
+            };
+
+            let usage_page = u16::from(var.usage.usage_page);
+            let usage_id = u16::from(var.usage.usage_id);
+
+            // Buttons (Usage Page 0x09) are one bit each
+            if usage_page == 0x09 {
+                caps.button_count += 1;
+                continue;
+            }
+
+            // Every other variable field takes a DirectInput index, matching the axis mapping
+            let index = directinput_index;
+            directinput_index += 1;
+
+            if usage_page == 0x01 && usage_id == HID_HAT_SWITCH_USAGE {
+                caps.pov_count += 1;
+                continue;
+            }
+
+            let usage_val = ((usage_page as u32) << 16) | usage_id as u32;
+            let name = Usage::try_from(usage_val)
+                .ok()
+                .map(|u| u.name().to_string())
+                .unwrap_or_else(|| format!("Usage {:04x}:{:04x}", usage_page, usage_id));
+
+            caps.axes.push(AxisCapability {
+                index,
+                hid_usage_page: usage_page,
+                hid_usage_id: usage_id,
+                name,
+                logical_min: i32::from(var.logical_minimum),
+                logical_max: i32::from(var.logical_maximum),
+            });
+        }
+    }
+
+    Ok(caps)
+}
+
+/// Enumerate connected controllers (HID + XInput) with full capability data
+pub fn enumerate_devices() -> Result<Vec<DeviceCapabilities>, String> {
+    let mut devices = Vec::new();
+
+    for (idx, device) in hid_reader::list_hid_game_controllers()?
+        .into_iter()
+        .enumerate()
+    {
+        let caps = match hid_reader::get_hid_descriptor_bytes(&device.path)
+            .and_then(|descriptor| capabilities_from_descriptor(&descriptor))
+        {
+            Ok(caps) => caps,
+            Err(e) => {
+                warn!(
+                    "[Capabilities] Could not read descriptor for {}: {}",
+                    device.path, e
+                );
+                DescriptorCapabilities::default()
+            }
+        };
+
+        let product_name = device
+            .product
+            .clone()
+            .unwrap_or_else(|| "Unknown Device".to_string());
+
+        let device_type = if directinput::is_gamepad(&product_name) {
+            "gamepad"
+        } else {
+            "joystick"
+        };
+
+        devices.push(DeviceCapabilities {
+            instance: idx + 1,
+            guid: Some(product_names::product_guid(
+                device.vendor_id,
+                device.product_id,
+            )),
+            uuid: format!("{:04x}:{:04x}", device.vendor_id, device.product_id),
+            product_name,
+            manufacturer: device.manufacturer.clone(),
+            device_type: device_type.to_string(),
+            axis_count: caps.axes.len(),
+            axes: caps.axes,
+            button_count: caps.button_count,
+            pov_count: caps.pov_count,
+            path: Some(device.path),
+        });
+    }
+
+    // XInput controllers have a fixed layout
+    if let Ok(xinput) = XInputHandle::load_default() {
+        for controller_id in 0..4 {
+            if xinput.get_state(controller_id).is_err() {
+                continue;
+            }
+
+            let axes = [
+                "Left Stick X",
+                "Left Stick Y",
+                "Right Stick X",
+                "Right Stick Y",
+                "Left Trigger",
+                "Right Trigger",
+            ]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| AxisCapability {
+                index: i as u32 + 1,
+                hid_usage_page: 0,
+                hid_usage_id: 0,
+                name: name.to_string(),
+                logical_min: if i < 4 { -32768 } else { 0 },
+                logical_max: if i < 4 { 32767 } else { 255 },
+            })
+            .collect::<Vec<_>>();
+
+            devices.push(DeviceCapabilities {
+                instance: controller_id as usize + 1,
+                guid: None,
+                uuid: format!("xinput_{}", controller_id),
+                product_name: format!("Xbox Controller (XInput {})", controller_id),
+                manufacturer: None,
+                device_type: "gamepad".to_string(),
+                axis_count: axes.len(),
+                axes,
+                button_count: 10,
+                pov_count: 1,
+                path: None,
+            });
+        }
+    }
+
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axis(index: u32, page: u16, usage: u16, name: &str) -> AxisCapability {
+        AxisCapability {
+            index,
+            hid_usage_page: page,
+            hid_usage_id: usage,
+            name: name.to_string(),
+            logical_min: 0,
+            logical_max: 65535,
+        }
+    }
+
+    #[test]
+    fn test_probe_names_axes_like_directinput() {
+        let axes = vec![
+            axis(1, 0x01, 0x30, "X"),
+            axis(2, 0x01, 0x31, "Y"),
+            axis(3, 0x01, 0x35, "Rz"),
+            axis(4, 0x02, 0xBB, "Throttle"),
+            axis(5, 0x01, 0x36, "Slider"),
+            axis(6, 0x01, 0x37, "Dial"),
+        ];
+        let device = DeviceCapabilities {
+            instance: 2,
+            guid: None,
+            uuid: "231d:0200".to_string(),
+            product_name: "VKBsim Gladiator EVO R".to_string(),
+            manufacturer: None,
+            device_type: "joystick".to_string(),
+            axis_count: axes.len(),
+            axes,
+            button_count: 3,
+            pov_count: 1,
+            path: None,
+        };
+
+        let probe = probe(device);
+        let names: Vec<Option<&str>> = probe.axes.iter().map(|a| a.sc_name.as_deref()).collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("x"),
+                Some("y"),
+                Some("rotz"),
+                Some("slider1"),
+                Some("slider2"),
+                None
+            ]
+        );
+        assert_eq!(probe.buttons, vec!["button1", "button2", "button3"]);
+        assert_eq!(probe.hats, vec!["hat1"]);
+    }
+}
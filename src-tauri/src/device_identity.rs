@@ -0,0 +1,305 @@
+//! Telling identical devices apart
+//!
+//! Two of the same stick (a left and right Gladiator in the same mode, twin throttles,
+//! a pair of rudder pedals) report the same product name and VID/PID, so nothing in
+//! the device list says which is which. Each device gets a key from the best source
+//! available: its serial number, else the USB port it is plugged into, else just its
+//! position in the enumeration. Keys are remembered per VID/PID so a device keeps its
+//! number ("231d:0200#2") across sessions; when only the enumeration order is left,
+//! the report warns that the numbering can change.
+
+use crate::hid_reader::HidDeviceListItem;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Name of the identity store inside the app data directory
+pub const IDENTITIES_FILE_NAME: &str = "device_identities.json";
+
+/// What a device's key is based on, from most to least reliable
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentitySource {
+    Serial,
+    /// The USB port (Windows device instance path); changes if the device is moved
+    PortPath,
+    /// Position in the enumeration; can change after a reboot or replug
+    EnumerationOrder,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceIdentity {
+    /// "vid:pid"
+    pub uuid: String,
+    pub product: Option<String>,
+    /// 1-based number among devices with the same uuid, stable across sessions
+    pub index: usize,
+    /// "vid:pid#index", usable wherever a device identity is expected (e.g. nicknames)
+    pub identity: String,
+    pub source: IdentitySource,
+    /// More than one connected device shares this uuid
+    pub has_twin: bool,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct IdentityReport {
+    pub devices: Vec<DeviceIdentity>,
+    pub warnings: Vec<String>,
+}
+
+/// A controller as the HID stack lists it, with the identity telling it apart from
+/// identical units
+#[derive(Debug, Serialize, Clone)]
+pub struct RawHidController {
+    #[serde(flatten)]
+    pub device: HidDeviceListItem,
+    /// "vid:pid#index"; None if the device wasn't numbered
+    pub identity: Option<String>,
+    pub identity_source: Option<IdentitySource>,
+}
+
+/// Pair each listed device with its identity from `report`
+pub fn with_identities(
+    devices: Vec<HidDeviceListItem>,
+    report: &IdentityReport,
+) -> Vec<RawHidController> {
+    devices
+        .into_iter()
+        .map(|device| {
+            let identity = report.devices.iter().find(|i| i.path == device.path);
+            RawHidController {
+                identity: identity.map(|i| i.identity.clone()),
+                identity_source: identity.map(|i| i.source),
+                device,
+            }
+        })
+        .collect()
+}
+
+/// Keys seen per uuid; a key's position is its index
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct IdentityStore(BTreeMap<String, Vec<String>>);
+
+/// The instance part of a Windows HID path, which Windows derives from the port:
+/// `\\?\hid#vid_231d&pid_0200#8&1a2b3c4d&0&0000#{...}` -> `8&1a2b3c4d&0&0000`.
+/// Paths from other platforms (e.g. /dev/hidraw3) follow the enumeration order instead.
+fn port_instance(path: &str) -> Option<String> {
+    let mut parts = path.split('#');
+    parts.next()?;
+    parts.next()?;
+    let instance = parts.next()?;
+    (!instance.is_empty()).then(|| instance.to_lowercase())
+}
+
+fn uuid_of(device: &HidDeviceListItem) -> String {
+    format!("{:04x}:{:04x}", device.vendor_id, device.product_id)
+}
+
+/// Best available key for each device of one uuid group, in enumeration order
+fn group_keys(group: &[&HidDeviceListItem]) -> Vec<(String, IdentitySource)> {
+    let unique = |keys: &[Option<String>]| {
+        let mut seen: Vec<&String> = Vec::new();
+        keys.iter().all(|key| match key {
+            Some(key) if !seen.contains(&key) => {
+                seen.push(key);
+                true
+            }
+            _ => false,
+        })
+    };
+
+    let serials: Vec<Option<String>> = group
+        .iter()
+        .map(|d| {
+            d.serial_number
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        })
+        .collect();
+    if unique(&serials) {
+        return serials
+            .into_iter()
+            .map(|s| {
+                (
+                    format!("serial:{}", s.unwrap_or_default()),
+                    IdentitySource::Serial,
+                )
+            })
+            .collect();
+    }
+
+    let ports: Vec<Option<String>> = group.iter().map(|d| port_instance(&d.path)).collect();
+    if unique(&ports) {
+        return ports
+            .into_iter()
+            .map(|p| {
+                (
+                    format!("port:{}", p.unwrap_or_default()),
+                    IdentitySource::PortPath,
+                )
+            })
+            .collect();
+    }
+
+    (1..=group.len())
+        .map(|n| (format!("order:{}", n), IdentitySource::EnumerationOrder))
+        .collect()
+}
+
+impl IdentityStore {
+    /// Number the connected devices, remembering new keys
+    pub fn assign(&mut self, devices: &[HidDeviceListItem]) -> IdentityReport {
+        let mut groups: BTreeMap<String, Vec<&HidDeviceListItem>> = BTreeMap::new();
+        for device in devices {
+            groups.entry(uuid_of(device)).or_default().push(device);
+        }
+
+        let mut identities: HashMap<String, DeviceIdentity> = HashMap::new();
+        let mut report = IdentityReport::default();
+        for (uuid, group) in groups {
+            let keys = group_keys(&group);
+            let known = self.0.entry(uuid.clone()).or_default();
+            let has_twin = group.len() > 1;
+
+            for (device, (key, source)) in group.iter().zip(keys) {
+                let index = match known.iter().position(|k| *k == key) {
+                    Some(position) => position + 1,
+                    None => {
+                        known.push(key);
+                        known.len()
+                    }
+                };
+                identities.insert(
+                    device.path.clone(),
+                    DeviceIdentity {
+                        identity: format!("{}#{}", uuid, index),
+                        uuid: uuid.clone(),
+                        product: device.product.clone(),
+                        index,
+                        source,
+                        has_twin,
+                        path: device.path.clone(),
+                    },
+                );
+            }
+
+            let name = group[0].product.as_deref().unwrap_or("Unknown device");
+            match identities[&group[0].path].source {
+                IdentitySource::EnumerationOrder if has_twin => report.warnings.push(format!(
+                    "{} devices report as {} ({}) without a serial number or port path; \
+                     they are told apart by the order Windows lists them, which can change \
+                     after a reboot or replug",
+                    group.len(),
+                    name,
+                    uuid
+                )),
+                IdentitySource::PortPath if has_twin => report.warnings.push(format!(
+                    "{} devices report as {} ({}) without serial numbers; they are told apart \
+                     by USB port, so keep each on the same port",
+                    group.len(),
+                    name,
+                    uuid
+                )),
+                _ => {}
+            }
+        }
+
+        // Back in enumeration order
+        report.devices = devices
+            .iter()
+            .filter_map(|device| identities.remove(&device.path))
+            .collect();
+        report
+    }
+}
+
+fn store_path(dir: &Path) -> PathBuf {
+    dir.join(IDENTITIES_FILE_NAME)
+}
+
+pub fn load_store(dir: &Path) -> Result<IdentityStore, String> {
+    let path = store_path(dir);
+    if !path.exists() {
+        return Ok(IdentityStore::default());
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read device identities: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse device identities: {}", e))
+}
+
+pub fn save_store(dir: &Path, store: &IdentityStore) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    let json = serde_json::to_string_pretty(store)
+        .map_err(|e| format!("Failed to serialize device identities: {}", e))?;
+    std::fs::write(store_path(dir), json)
+        .map_err(|e| format!("Failed to write device identities: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stick(serial: Option<&str>, path: &str) -> HidDeviceListItem {
+        HidDeviceListItem {
+            vendor_id: 0x231d,
+            product_id: 0x0200,
+            serial_number: serial.map(str::to_string),
+            manufacturer: None,
+            product: Some("VKBsim Gladiator NXT".to_string()),
+            path: path.to_string(),
+            interface_number: 0,
+            usage_page: 0x01,
+            usage: 0x04,
+        }
+    }
+
+    #[test]
+    fn test_identical_devices_keep_their_numbers() {
+        let left = stick(None, r"\\?\hid#vid_231d&pid_0200#8&aaaa&0&0000#{4d1e55b2}");
+        let right = stick(None, r"\\?\hid#vid_231d&pid_0200#8&bbbb&0&0000#{4d1e55b2}");
+
+        let mut store = IdentityStore::default();
+        let first = store.assign(&[left.clone(), right.clone()]);
+        assert_eq!(first.devices[0].identity, "231d:0200#1");
+        assert_eq!(first.devices[1].source, IdentitySource::PortPath);
+        assert_eq!(first.warnings.len(), 1);
+
+        // Enumerated the other way round next session
+        let second = store.assign(&[right, left]);
+        assert_eq!(second.devices[0].identity, "231d:0200#2");
+        assert_eq!(second.devices[1].identity, "231d:0200#1");
+    }
+
+    #[test]
+    fn test_identity_sources() {
+        let mut store = IdentityStore::default();
+        let serials = store.assign(&[
+            stick(Some("A1"), "/dev/hidraw1"),
+            stick(Some("B2"), "/dev/hidraw2"),
+        ]);
+        assert!(serials
+            .devices
+            .iter()
+            .all(|d| d.source == IdentitySource::Serial));
+        assert!(serials.warnings.is_empty());
+
+        // Same serial on both (some firmwares do this) and no port information
+        let mut store = IdentityStore::default();
+        let order = store.assign(&[
+            stick(Some("0"), "/dev/hidraw1"),
+            stick(Some("0"), "/dev/hidraw2"),
+        ]);
+        assert_eq!(order.devices[1].source, IdentitySource::EnumerationOrder);
+        assert!(order.warnings[0].contains("order"));
+
+        // A single device doesn't need a warning
+        let mut store = IdentityStore::default();
+        let single = store.assign(&[stick(None, "/dev/hidraw1")]);
+        assert!(!single.devices[0].has_twin);
+        assert!(single.warnings.is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+//! Hotplug detection for game controllers
+//!
+//! Periodically enumerates connected devices and emits a "devices-changed" event when
+//! something is plugged in or removed, so the frontend can refresh instance assignments.
+//! The event also lists devices referenced by the active profile that are no longer connected.
+
+use crate::directinput::{self, DeviceInfo};
+use crate::product_names;
+use log::info;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// How often connected devices are enumerated
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Payload of the "devices-changed" event
+#[derive(Debug, Serialize, Clone)]
+pub struct DevicesChangedEvent {
+    pub added: Vec<DeviceInfo>,
+    pub removed: Vec<DeviceInfo>,
+    /// Everything currently connected
+    pub devices: Vec<DeviceInfo>,
+    /// Product strings from the active profile with no matching connected device
+    pub missing_profile_devices: Vec<String>,
+}
+
+/// Does this connected device satisfy a profile Product string?
+fn matches_product(device: &DeviceInfo, product: &str) -> bool {
+    match product_names::uuid_from_product(product) {
+        Some(uuid) => device.uuid.eq_ignore_ascii_case(&uuid),
+        None => product_names::same_name(&device.name, product),
+    }
+}
+
+/// Profile devices that have no connected counterpart. Identical devices are matched one-to-one,
+/// so a profile with two identical sticks reports one missing when only one is plugged in.
+pub fn find_missing_devices(expected_products: &[String], devices: &[DeviceInfo]) -> Vec<String> {
+    let mut used = vec![false; devices.len()];
+    expected_products
+        .iter()
+        .filter(|product| {
+            let found = devices
+                .iter()
+                .enumerate()
+                .position(|(i, d)| !used[i] && matches_product(d, product));
+            match found {
+                Some(i) => {
+                    used[i] = true;
+                    false
+                }
+                None => true,
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Devices in `current` that aren't in `previous`, counting duplicates of the same uuid
+fn devices_not_in(current: &[DeviceInfo], previous: &[DeviceInfo]) -> Vec<DeviceInfo> {
+    let mut remaining: HashMap<&str, usize> = HashMap::new();
+    for device in previous {
+        *remaining.entry(device.uuid.as_str()).or_insert(0) += 1;
+    }
+
+    current
+        .iter()
+        .filter(|device| match remaining.get_mut(device.uuid.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// A running device monitor. Dropping it stops the polling thread.
+pub struct DeviceMonitor {
+    stop: Arc<AtomicBool>,
+}
+
+impl DeviceMonitor {
+    /// Start polling for device changes. `expected_products` are the Product strings
+    /// of the devices the active profile uses.
+    pub fn start(app_handle: AppHandle, expected_products: Vec<String>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        thread::spawn(move || {
+            let mut previous = directinput::list_connected_devices().unwrap_or_default();
+            let mut previous_missing = find_missing_devices(&expected_products, &previous);
+
+            // Report the initial state so a missing device is flagged right away
+            if !previous_missing.is_empty() {
+                let _ = app_handle.emit(
+                    "devices-changed",
+                    DevicesChangedEvent {
+                        added: Vec::new(),
+                        removed: Vec::new(),
+                        devices: previous.clone(),
+                        missing_profile_devices: previous_missing.clone(),
+                    },
+                );
+            }
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(POLL_INTERVAL);
+                if thread_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let current = match directinput::list_connected_devices() {
+                    Ok(devices) => devices,
+                    Err(_) => continue,
+                };
+
+                let added = devices_not_in(&current, &previous);
+                let removed = devices_not_in(&previous, &current);
+                let missing = find_missing_devices(&expected_products, &current);
+
+                if added.is_empty() && removed.is_empty() && missing == previous_missing {
+                    continue;
+                }
+
+                for device in &added {
+                    tracing::info!(device = %device.name, uuid = %device.uuid, "Device connected");
+                }
+                for device in &removed {
+                    tracing::info!(device = %device.name, uuid = %device.uuid, "Device disconnected");
+                }
+
+                let _ = app_handle.emit(
+                    "devices-changed",
+                    DevicesChangedEvent {
+                        added,
+                        removed,
+                        devices: current.clone(),
+                        missing_profile_devices: missing.clone(),
+                    },
+                );
+
+                previous = current;
+                previous_missing = missing;
+            }
+
+            info!("Device monitor stopped");
+        });
+
+        DeviceMonitor { stop }
+    }
+}
+
+impl Drop for DeviceMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
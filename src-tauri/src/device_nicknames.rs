@@ -0,0 +1,127 @@
+//! User-chosen nicknames for devices on this machine
+//!
+//! "VKB-Sim Gladiator NXT R" and "VKB-Sim Gladiator NXT L" are easy to mix up in a list,
+//! and a vJoy device is just "vJoy Device". Users can name their devices ("Right stick",
+//! "Pedals"); the names are kept per machine, keyed by device identity rather than by
+//! instance number, so they follow the device when Windows renumbers it.
+
+use crate::product_names;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the nickname store inside the app data directory
+pub const NICKNAMES_FILE_NAME: &str = "device_nicknames.json";
+
+/// Device identity -> nickname
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(transparent)]
+pub struct DeviceNicknames(BTreeMap<String, String>);
+
+/// An enumerated device with its nickname, if it has one
+#[derive(Debug, Serialize, Clone)]
+pub struct NamedDevice<T> {
+    #[serde(flatten)]
+    pub device: T,
+    pub nickname: Option<String>,
+}
+
+/// Canonical key for a device identity. Accepts our "vid:pid" uuid, a DirectInput product
+/// GUID or a whole SC Product string, so the same device gets the same key from any source.
+/// Other identities (e.g. "xinput_0" or a serial) are used as given.
+pub fn identity_key(identity: &str) -> Option<String> {
+    let identity = identity.trim();
+    if identity.is_empty() {
+        return None;
+    }
+    if identity.contains('{') {
+        return product_names::uuid_from_product(identity);
+    }
+    Some(identity.to_lowercase())
+}
+
+impl DeviceNicknames {
+    pub fn get(&self, identity: &str) -> Option<&str> {
+        self.0.get(&identity_key(identity)?).map(String::as_str)
+    }
+
+    /// Set a nickname, or remove it when `nickname` is empty
+    pub fn set(&mut self, identity: &str, nickname: &str) -> Result<(), String> {
+        let key = identity_key(identity)
+            .ok_or_else(|| format!("'{}' doesn't identify a device", identity))?;
+        let nickname = nickname.trim();
+        if nickname.is_empty() {
+            self.0.remove(&key);
+        } else {
+            self.0.insert(key, nickname.to_string());
+        }
+        Ok(())
+    }
+
+    /// Attach nicknames to enumeration results, looked up by each device's identity
+    pub fn name_devices<T>(
+        &self,
+        devices: Vec<T>,
+        identity: impl Fn(&T) -> Option<&str>,
+    ) -> Vec<NamedDevice<T>> {
+        devices
+            .into_iter()
+            .map(|device| NamedDevice {
+                nickname: identity(&device)
+                    .and_then(|id| self.get(id))
+                    .map(str::to_string),
+                device,
+            })
+            .collect()
+    }
+}
+
+fn nicknames_path(dir: &Path) -> PathBuf {
+    dir.join(NICKNAMES_FILE_NAME)
+}
+
+/// Load the nickname store, empty if none has been saved yet
+pub fn load_nicknames(dir: &Path) -> Result<DeviceNicknames, String> {
+    let path = nicknames_path(dir);
+    if !path.exists() {
+        return Ok(DeviceNicknames::default());
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read device nicknames: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse device nicknames: {}", e))
+}
+
+pub fn save_nicknames(dir: &Path, nicknames: &DeviceNicknames) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    let json = serde_json::to_string_pretty(nicknames)
+        .map_err(|e| format!("Failed to serialize device nicknames: {}", e))?;
+    std::fs::write(nicknames_path(dir), json)
+        .map_err(|e| format!("Failed to write device nicknames: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nickname_found_from_any_identity() {
+        let mut nicknames = DeviceNicknames::default();
+        nicknames.set("231D:0200", "Right stick").unwrap();
+
+        assert_eq!(nicknames.get("231d:0200"), Some("Right stick"));
+        assert_eq!(
+            nicknames.get(" VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}"),
+            Some("Right stick")
+        );
+        assert_eq!(
+            nicknames.get("Keyboard  {6F1D2B61-D5A0-11CF-BFC7-444553540000}"),
+            None
+        );
+
+        nicknames
+            .set("{0200231D-0000-0000-0000-504944564944}", " ")
+            .unwrap();
+        assert_eq!(nicknames.get("231d:0200"), None);
+        assert!(nicknames.set("", "Nothing").is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Matching an imported profile's devices to the ones connected here
+//!
+//! A profile made on someone else's machine numbers its joysticks the way their Windows
+//! enumerated them, often with sticks this machine doesn't have. Each profile device is
+//! scored against each connected joystick, on the product (same VID/PID, same name, or
+//! words in common) and on whether the local device has enough buttons, axes and hats for
+//! what the profile binds on it. The best pairs are proposed as an instance mapping for
+//! the user to confirm, then applied to every jsN_ binding through instance_swap.
+
+use crate::device_capabilities::DeviceCapabilities;
+use crate::instance_swap::InstanceMapping;
+use crate::keybindings::ActionMaps;
+use crate::product_names;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Pairs scoring below this aren't proposed
+const MIN_SCORE: i32 = 25;
+
+/// What a profile binds on one joystick instance
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct ProfileDevice {
+    pub instance: String,
+    /// SC Product string, when the profile names the device
+    pub product: Option<String>,
+    pub rebind_count: usize,
+    /// Highest button number bound
+    pub max_button: u32,
+    /// Distinct axes bound, e.g. ["x", "rotz"]
+    pub axes: Vec<String>,
+    /// Highest hat number bound
+    pub max_hat: u32,
+}
+
+/// One profile device and the connected device proposed for it
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceMatch {
+    pub profile_device: ProfileDevice,
+    /// Instance of the proposed local device, None when nothing fits
+    pub to_instance: Option<String>,
+    pub local_product: Option<String>,
+    pub score: i32,
+    /// Why the pair scored as it did, or why it had to move
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RemapProposal {
+    pub matches: Vec<DeviceMatch>,
+    /// Instances that change number, ready for instance_swap
+    pub mapping: InstanceMapping,
+}
+
+/// `lalt+button12` -> `button12`
+fn bound_input(input: &str) -> &str {
+    input.rsplit('+').next().unwrap_or(input)
+}
+
+/// Joystick instances are numbered from 1
+fn instance_number(instance: &str) -> Option<u32> {
+    instance.parse().ok().filter(|n| *n > 0)
+}
+
+/// The joystick instances a profile uses and what it binds on each
+pub fn profile_devices(bindings: &ActionMaps) -> Vec<ProfileDevice> {
+    let mut devices: BTreeMap<u32, ProfileDevice> = BTreeMap::new();
+    let mut axes: BTreeMap<u32, BTreeSet<String>> = BTreeMap::new();
+
+    for options in &bindings.devices.device_options {
+        let Some(number) = instance_number(&options.instance) else {
+            continue;
+        };
+        if options.device_type == "joystick" {
+            let device = devices.entry(number).or_default();
+            if !options.product.trim().is_empty() {
+                device.product = Some(options.product.clone());
+            }
+        }
+    }
+
+    for rebind in bindings
+        .action_maps
+        .iter()
+        .flat_map(|m| m.actions.iter())
+        .flat_map(|a| a.rebinds.iter())
+    {
+        let Some((number, input)) = rebind
+            .input
+            .trim()
+            .strip_prefix("js")
+            .and_then(|rest| rest.split_once('_'))
+        else {
+            continue;
+        };
+        let Some(number) = instance_number(number) else {
+            continue;
+        };
+        let input = bound_input(input.trim());
+        if input.is_empty() {
+            continue;
+        }
+
+        let device = devices.entry(number).or_default();
+        device.rebind_count += 1;
+        if let Some(button) = input.strip_prefix("button") {
+            device.max_button = device.max_button.max(button.parse().unwrap_or(0));
+        } else if let Some(hat) = input.strip_prefix("hat") {
+            let hat = hat.split('_').next().unwrap_or("");
+            device.max_hat = device.max_hat.max(hat.parse().unwrap_or(1));
+        } else {
+            axes.entry(number).or_default().insert(input.to_string());
+        }
+    }
+
+    devices
+        .into_iter()
+        .map(|(number, mut device)| {
+            device.instance = number.to_string();
+            if device.product.is_none() {
+                // The device list is in instance order (index 0 is js1)
+                device.product = bindings
+                    .devices
+                    .joysticks
+                    .get(number as usize - 1)
+                    .filter(|p| !p.trim().is_empty())
+                    .cloned();
+            }
+            device.axes = axes
+                .remove(&number)
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+            device
+        })
+        .collect()
+}
+
+/// Share of the profile name's words found in the local name
+fn word_overlap(profile: &str, local: &str) -> f64 {
+    let profile = product_names::normalize(profile);
+    let local = product_names::normalize(local);
+    let words: Vec<&str> = profile.split(' ').filter(|w| !w.is_empty()).collect();
+    if words.is_empty() {
+        return 0.0;
+    }
+    let local_words: Vec<&str> = local.split(' ').collect();
+    words.iter().filter(|w| local_words.contains(w)).count() as f64 / words.len() as f64
+}
+
+/// Score a profile device against a connected one
+fn score(device: &ProfileDevice, local: &DeviceCapabilities) -> (i32, Vec<String>) {
+    let mut score = 0;
+    let mut notes = Vec::new();
+
+    if let Some(product) = &device.product {
+        let uuid = product_names::uuid_from_product(product);
+        if uuid.is_some_and(|uuid| uuid.eq_ignore_ascii_case(&local.uuid)) {
+            score += 60;
+            notes.push("Same product (VID/PID)".to_string());
+        } else if product_names::names_match(product, &local.product_name) {
+            score += 50;
+            notes.push("Same product name".to_string());
+        } else {
+            let overlap = word_overlap(product, &local.product_name);
+            if overlap > 0.0 {
+                score += (overlap * 30.0).round() as i32;
+                notes.push(format!("Similar name ({:.0}% of words)", overlap * 100.0));
+            }
+        }
+    }
+
+    let mut fit = |needed: usize, available: usize, what: &str| {
+        if needed <= available {
+            score += 10;
+        } else {
+            score -= 15;
+            notes.push(format!("Needs {} {}, has {}", needed, what, available));
+        }
+    };
+    fit(device.max_button as usize, local.button_count, "buttons");
+    fit(device.axes.len(), local.axis_count, "axes");
+    fit(device.max_hat as usize, local.pov_count, "hats");
+
+    // All else equal, keep the number it had
+    if device.instance == local.instance.to_string() {
+        score += 1;
+    }
+    (score, notes)
+}
+
+/// Propose which connected joystick each profile device should become
+pub fn propose(devices: &[ProfileDevice], local: &[DeviceCapabilities]) -> RemapProposal {
+    let local: Vec<&DeviceCapabilities> = local
+        .iter()
+        .filter(|d| d.device_type == "joystick")
+        .collect();
+
+    let mut pairs: Vec<(i32, usize, usize, Vec<String>)> = Vec::new();
+    for (d, device) in devices.iter().enumerate() {
+        for (l, local_device) in local.iter().enumerate() {
+            let (score, notes) = score(device, local_device);
+            if score >= MIN_SCORE {
+                pairs.push((score, d, l, notes));
+            }
+        }
+    }
+    // Best first; ties go to the earlier profile device, then the earlier local one
+    pairs.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut matches: Vec<DeviceMatch> = devices
+        .iter()
+        .map(|device| DeviceMatch {
+            profile_device: device.clone(),
+            to_instance: None,
+            local_product: None,
+            score: 0,
+            notes: Vec::new(),
+        })
+        .collect();
+    let mut local_used = vec![false; local.len()];
+    for (score, d, l, notes) in pairs {
+        if matches[d].to_instance.is_some() || local_used[l] {
+            continue;
+        }
+        local_used[l] = true;
+        matches[d] = DeviceMatch {
+            profile_device: devices[d].clone(),
+            to_instance: Some(local[l].instance.to_string()),
+            local_product: Some(local[l].product_name.clone()),
+            score,
+            notes,
+        };
+    }
+
+    let mut mapping: InstanceMapping = matches
+        .iter()
+        .filter_map(|m| {
+            let to = m.to_instance.as_ref()?;
+            (*to != m.profile_device.instance)
+                .then(|| (m.profile_device.instance.clone(), to.clone()))
+        })
+        .collect();
+
+    // Unmatched devices keep their number unless a matched one is moving onto it; then
+    // they move to the first free number so the mapping stays a clean renumbering
+    let mut taken: BTreeSet<u32> = matches
+        .iter()
+        .map(|m| m.to_instance.as_ref().unwrap_or(&m.profile_device.instance))
+        .filter_map(|n| n.parse().ok())
+        .collect();
+    for m in matches.iter_mut().filter(|m| m.to_instance.is_none()) {
+        let instance = &m.profile_device.instance;
+        let displaced = matches_target(&mapping, instance);
+        if !displaced {
+            continue;
+        }
+        let free = (1..).find(|n| !taken.contains(n)).unwrap_or(1);
+        taken.insert(free);
+        mapping.insert(instance.clone(), free.to_string());
+        m.notes.push(format!(
+            "No matching device here; moved to js{} to make room",
+            free
+        ));
+    }
+
+    RemapProposal { matches, mapping }
+}
+
+/// Is some instance being moved onto `instance`?
+fn matches_target(mapping: &InstanceMapping, instance: &str) -> bool {
+    mapping.values().any(|to| to == instance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(instance: usize, uuid: &str, name: &str, buttons: usize) -> DeviceCapabilities {
+        DeviceCapabilities {
+            instance,
+            guid: None,
+            uuid: uuid.to_string(),
+            product_name: name.to_string(),
+            manufacturer: None,
+            device_type: "joystick".to_string(),
+            axis_count: 6,
+            axes: Vec::new(),
+            button_count: buttons,
+            pov_count: 1,
+            path: None,
+        }
+    }
+
+    #[test]
+    fn test_propose_remap() {
+        let bindings = ActionMaps::from_xml(
+            r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="joystick" instance="1" Product=" VKBsim Gladiator EVO R    {0200231D-0000-0000-0000-504944564944}"/>
+  <options type="joystick" instance="2" Product=" T-Rudder    {B679044F-0000-0000-0000-504944564944}"/>
+  <options type="joystick" instance="3" Product=" Thrustmaster TWCS Throttle    {B687044F-0000-0000-0000-504944564944}"/>
+  <actionmap name="spaceship_movement">
+   <action name="v_pitch">
+    <rebind input="js1_y"/>
+   </action>
+   <action name="v_yaw">
+    <rebind input="js2_rotz"/>
+   </action>
+   <action name="v_strafe_forward">
+    <rebind input="js3_z"/>
+   </action>
+  </actionmap>
+  <actionmap name="spaceship_general">
+   <action name="v_toggle_landing_system">
+    <rebind input="js3_lalt+button14"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>"#,
+        )
+        .unwrap();
+
+        let devices = profile_devices(&bindings);
+        assert_eq!(devices.len(), 3);
+        assert_eq!(devices[2].max_button, 14);
+        assert_eq!(devices[2].axes, vec!["z"]);
+
+        // Here the throttle enumerates first, the stick is a different model and there
+        // are no pedals
+        let connected = [
+            local(1, "044f:b687", "TWCS Throttle", 14),
+            local(2, "231d:0200", "VKBsim Gladiator NXT EVO R", 32),
+        ];
+        let proposal = propose(&devices, &connected);
+
+        assert_eq!(proposal.matches[0].to_instance.as_deref(), Some("2"));
+        assert_eq!(proposal.matches[2].to_instance.as_deref(), Some("1"));
+        assert!(proposal.matches[1].to_instance.is_none());
+
+        // js3 -> js1, js1 -> js2, and the pedals move out of js2's way
+        let expected: InstanceMapping = [("1", "2"), ("2", "3"), ("3", "1")]
+            .into_iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect();
+        assert_eq!(proposal.mapping, expected);
+        let existing: Vec<String> = devices.iter().map(|d| d.instance.clone()).collect();
+        assert!(crate::instance_swap::validate_mapping(&proposal.mapping, &existing).is_ok());
+    }
+}
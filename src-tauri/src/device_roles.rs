@@ -0,0 +1,131 @@
+//! What each joystick is for: stick, throttle, pedals or button box
+//!
+//! SC only knows joysticks 1..N, which says nothing about which one is the rudder. A
+//! profile can tag each joystick instance with a role, so the UI can show "Pedals (js3)"
+//! and an apply can fill in the options that role nearly always wants (a little deadzone
+//! on the yaw of pedals that don't re-center precisely) without the user setting them on
+//! every profile. Options the profile sets itself always win.
+
+use crate::controls::{ControlOptionSettings, ControlsFile};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceRole {
+    Stick,
+    Throttle,
+    Pedals,
+    ButtonBox,
+}
+
+impl DeviceRole {
+    pub fn label(self) -> &'static str {
+        match self {
+            DeviceRole::Stick => "Stick",
+            DeviceRole::Throttle => "Throttle",
+            DeviceRole::Pedals => "Pedals",
+            DeviceRole::ButtonBox => "Button box",
+        }
+    }
+}
+
+/// Words in a product name that give its role away, checked in order
+const ROLE_KEYWORDS: &[(&str, DeviceRole)] = &[
+    ("pedal", DeviceRole::Pedals),
+    ("rudder", DeviceRole::Pedals),
+    ("throttle", DeviceRole::Throttle),
+    ("twcs", DeviceRole::Throttle),
+    ("button box", DeviceRole::ButtonBox),
+    ("panel", DeviceRole::ButtonBox),
+    ("stick", DeviceRole::Stick),
+    ("joystick", DeviceRole::Stick),
+    ("gladiator", DeviceRole::Stick),
+];
+
+/// Guess a device's role from its product name
+pub fn guess_role(product_name: &str) -> Option<DeviceRole> {
+    let name = product_name.to_lowercase();
+    ROLE_KEYWORDS
+        .iter()
+        .find(|(keyword, _)| name.contains(keyword))
+        .map(|(_, role)| *role)
+}
+
+/// Options a role gets unless the profile sets them
+fn role_defaults(role: DeviceRole) -> Vec<(&'static str, ControlOptionSettings)> {
+    match role {
+        DeviceRole::Pedals => ["flight_move_yaw", "mgv_move_yaw"]
+            .into_iter()
+            .map(|name| {
+                (
+                    name,
+                    ControlOptionSettings {
+                        deadzone: Some(0.05),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect(),
+        DeviceRole::Stick | DeviceRole::Throttle | DeviceRole::ButtonBox => Vec::new(),
+    }
+}
+
+/// Fill in each joystick's role defaults where the profile doesn't set the option.
+/// Returns the number of options added.
+pub fn apply_role_defaults(controls: &mut ControlsFile) -> usize {
+    let mut added = 0;
+    for device in controls
+        .devices
+        .joystick
+        .iter_mut()
+        .flat_map(|j| j.values_mut())
+    {
+        let Some(role) = device.role else {
+            continue;
+        };
+        for (name, settings) in role_defaults(role) {
+            if !device.options.contains_key(name) {
+                device.options.insert(name.to_string(), settings);
+                added += 1;
+            }
+        }
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles() {
+        assert_eq!(guess_role("T-Rudder"), Some(DeviceRole::Pedals));
+        assert_eq!(
+            guess_role("Thrustmaster TWCS Throttle"),
+            Some(DeviceRole::Throttle)
+        );
+        assert_eq!(
+            guess_role("VKBsim Gladiator EVO R"),
+            Some(DeviceRole::Stick)
+        );
+        assert_eq!(guess_role("vJoy Device"), None);
+
+        let mut controls = ControlsFile::new("Roles".to_string());
+        let pedals = controls.device_mut("joystick", "3").unwrap();
+        pedals.role = Some(DeviceRole::Pedals);
+        pedals.options.insert(
+            "mgv_move_yaw".to_string(),
+            ControlOptionSettings {
+                invert: Some(true),
+                ..Default::default()
+            },
+        );
+        controls.device_mut("joystick", "1").unwrap().role = Some(DeviceRole::Stick);
+
+        assert_eq!(apply_role_defaults(&mut controls), 1);
+        let pedals = controls.device("joystick", "3").unwrap();
+        assert_eq!(pedals.options["flight_move_yaw"].deadzone, Some(0.05));
+        assert_eq!(pedals.options["mgv_move_yaw"].deadzone, None);
+        assert!(controls.device("joystick", "1").unwrap().options.is_empty());
+    }
+}
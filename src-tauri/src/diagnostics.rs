@@ -0,0 +1,119 @@
+//! Startup and lazy-init timing
+//!
+//! Heavy subsystems (device enumeration, file watchers, option catalog parsing) are only
+//! initialized on first use. Each startup stage and each first-use initialization is
+//! timed here so regressions in cold start time show up in the diagnostics command.
+
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Startup budget we try to stay under, from process start to the setup hook finishing
+pub const STARTUP_BUDGET_MS: f64 = 500.0;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct StageTiming {
+    pub name: String,
+    pub millis: f64,
+    /// True for subsystems initialized lazily on first use rather than at startup
+    pub lazy: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Diagnostics {
+    /// Time from process start until the app finished its setup hook
+    pub startup_ms: Option<f64>,
+    pub startup_budget_ms: f64,
+    pub stages: Vec<StageTiming>,
+    /// Size of the running executable, for tracking binary growth
+    pub binary_size_bytes: Option<u64>,
+}
+
+static PROCESS_START: OnceLock<Instant> = OnceLock::new();
+static STARTUP_DONE: OnceLock<Duration> = OnceLock::new();
+static STAGES: Mutex<Vec<StageTiming>> = Mutex::new(Vec::new());
+
+/// Call as early as possible in `run()`
+pub fn mark_process_start() {
+    PROCESS_START.get_or_init(Instant::now);
+}
+
+/// Call at the end of the setup hook
+pub fn mark_startup_done() {
+    if let Some(start) = PROCESS_START.get() {
+        let elapsed = *STARTUP_DONE.get_or_init(|| start.elapsed());
+        let millis = elapsed.as_secs_f64() * 1000.0;
+        if millis > STARTUP_BUDGET_MS {
+            log::warn!(
+                "Startup took {:.0}ms, over the {:.0}ms budget",
+                millis,
+                STARTUP_BUDGET_MS
+            );
+        } else {
+            log::info!("Startup took {:.0}ms", millis);
+        }
+    }
+}
+
+fn record(name: &str, elapsed: Duration, lazy: bool) {
+    STAGES.lock().unwrap().push(StageTiming {
+        name: name.to_string(),
+        millis: elapsed.as_secs_f64() * 1000.0,
+        lazy,
+    });
+}
+
+/// Time a startup stage
+pub fn stage<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(name, start.elapsed(), false);
+    result
+}
+
+/// Initialize a lazily created value on first use, timing the initialization.
+/// Failed initializations are not cached so the next use retries.
+pub fn lazy_init<'a, T>(
+    slot: &'a mut Option<T>,
+    name: &str,
+    init: impl FnOnce() -> Result<T, String>,
+) -> Result<&'a mut T, String> {
+    if slot.is_none() {
+        let start = Instant::now();
+        let value = init()?;
+        record(name, start.elapsed(), true);
+        *slot = Some(value);
+    }
+    Ok(slot.as_mut().unwrap())
+}
+
+/// Time the first call of a subsystem that has no state to cache (e.g. the first device
+/// enumeration, which loads the HID/XInput libraries). A failed call isn't recorded, so
+/// the first successful one is timed instead.
+pub fn first_use<T, E>(
+    flag: &OnceLock<()>,
+    name: &str,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    if flag.get().is_some() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    if result.is_ok() && flag.set(()).is_ok() {
+        record(name, start.elapsed(), true);
+    }
+    result
+}
+
+pub fn snapshot() -> Diagnostics {
+    Diagnostics {
+        startup_ms: STARTUP_DONE.get().map(|d| d.as_secs_f64() * 1000.0),
+        startup_budget_ms: STARTUP_BUDGET_MS,
+        stages: STAGES.lock().unwrap().clone(),
+        binary_size_bytes: std::env::current_exe()
+            .and_then(std::fs::metadata)
+            .map(|m| m.len())
+            .ok(),
+    }
+}
@@ -0,0 +1,395 @@
+//! Structured comparison of two actionmaps.xml documents
+//!
+//! Used wherever we need to show the user what changed between two versions of
+//! the game's file (snapshots, backups, the live file). `DiffModel` is the same result
+//! grouped into per-device and per-actionmap hunks, the one shape the preview pane
+//! renders whatever is being compared: profiles, actionmaps.xml files, snapshots or
+//! the result of an apply.
+
+use crate::controls::{self, ActionmapsControlOption, ActionmapsDeviceOptions};
+use crate::keybindings::ActionMaps;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A single control option that was added, removed or modified
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionChange {
+    pub device_type: String,
+    pub instance: String,
+    pub product: String,
+    pub option: String,
+    pub before: Option<ActionmapsControlOption>,
+    pub after: Option<ActionmapsControlOption>,
+}
+
+/// An action whose rebinds differ between the two documents
+#[derive(Debug, Clone, Serialize)]
+pub struct BindingChange {
+    pub action_map: String,
+    pub action: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// Everything that differs between two actionmaps.xml documents
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct ActionmapsDiff {
+    pub option_changes: Vec<OptionChange>,
+    pub binding_changes: Vec<BindingChange>,
+}
+
+impl ActionmapsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.option_changes.is_empty() && self.binding_changes.is_empty()
+    }
+}
+
+/// Compare two actionmaps.xml documents
+pub fn diff_actionmaps(before_xml: &str, after_xml: &str) -> Result<ActionmapsDiff, String> {
+    Ok(ActionmapsDiff {
+        option_changes: diff_options(before_xml, after_xml)?,
+        binding_changes: diff_bindings(before_xml, after_xml)?,
+    })
+}
+
+fn diff_options(before_xml: &str, after_xml: &str) -> Result<Vec<OptionChange>, String> {
+    Ok(diff_device_options(
+        controls::parse_actionmaps_options(before_xml)?,
+        controls::parse_actionmaps_options(after_xml)?,
+    ))
+}
+
+/// Compare two sets of device options, e.g. two profiles converted with
+/// `controls::controls_to_actionmaps`
+pub fn diff_device_options(
+    before: Vec<ActionmapsDeviceOptions>,
+    after: Vec<ActionmapsDeviceOptions>,
+) -> Vec<OptionChange> {
+    // Key: (device_type, instance, option name) -> (product, option)
+    type OptionMap = BTreeMap<(String, String, String), (String, ActionmapsControlOption)>;
+
+    fn collect(devices: Vec<ActionmapsDeviceOptions>) -> OptionMap {
+        let mut map = BTreeMap::new();
+        for device in devices {
+            for opt in device.options {
+                map.insert(
+                    (
+                        device.device_type.clone(),
+                        device.instance.clone(),
+                        opt.name.clone(),
+                    ),
+                    (device.product.clone(), opt),
+                );
+            }
+        }
+        map
+    }
+
+    let before = collect(before);
+    let after = collect(after);
+
+    let mut keys: Vec<_> = before.keys().chain(after.keys()).cloned().collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut changes = Vec::new();
+    for key in keys {
+        let old = before.get(&key);
+        let new = after.get(&key);
+
+        if let (Some((_, old_opt)), Some((_, new_opt))) = (old, new) {
+            if old_opt == new_opt {
+                continue;
+            }
+        }
+
+        let product = new
+            .or(old)
+            .map(|(product, _)| product.clone())
+            .unwrap_or_default();
+
+        changes.push(OptionChange {
+            device_type: key.0,
+            instance: key.1,
+            product,
+            option: key.2,
+            before: old.map(|(_, opt)| opt.clone()),
+            after: new.map(|(_, opt)| opt.clone()),
+        });
+    }
+
+    changes
+}
+
+fn diff_bindings(before_xml: &str, after_xml: &str) -> Result<Vec<BindingChange>, String> {
+    fn collect(xml: &str) -> Result<BTreeMap<(String, String), Vec<String>>, String> {
+        let action_maps = ActionMaps::from_xml(xml)?;
+        let mut map = BTreeMap::new();
+        for action_map in action_maps.action_maps {
+            for action in action_map.actions {
+                let mut inputs: Vec<String> = action.rebinds.into_iter().map(|r| r.input).collect();
+                inputs.sort();
+                map.insert((action_map.name.clone(), action.name), inputs);
+            }
+        }
+        Ok(map)
+    }
+
+    let before = collect(before_xml)?;
+    let after = collect(after_xml)?;
+
+    let mut keys: Vec<_> = before.keys().chain(after.keys()).cloned().collect();
+    keys.sort();
+    keys.dedup();
+
+    Ok(keys
+        .into_iter()
+        .filter_map(|key| {
+            let old = before.get(&key).cloned().unwrap_or_default();
+            let new = after.get(&key).cloned().unwrap_or_default();
+            if old == new {
+                return None;
+            }
+            Some(BindingChange {
+                action_map: key.0,
+                action: key.1,
+                before: old,
+                after: new,
+            })
+        })
+        .collect())
+}
+
+/// One side of a comparison, as the frontend names it
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffSource {
+    /// An actionmaps.xml, a backup of one or an exported layout
+    File {
+        path: String,
+    },
+    /// A .sccontrols profile (options only)
+    Profile {
+        path: String,
+    },
+    Snapshot {
+        id: String,
+    },
+    /// actionmaps.xml as it would be after applying a profile
+    Applied {
+        profile_path: String,
+        actionmaps_path: String,
+    },
+}
+
+/// A comparison side once loaded: a whole document, or just device options
+pub enum DiffInput {
+    Xml(String),
+    Options(Vec<ActionmapsDeviceOptions>),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HunkKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+fn hunk_kind<T>(before: &Option<T>, after: &Option<T>) -> HunkKind {
+    match (before, after) {
+        (None, _) => HunkKind::Added,
+        (_, None) => HunkKind::Removed,
+        _ => HunkKind::Modified,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionHunk {
+    pub option: String,
+    pub kind: HunkKind,
+    pub before: Option<ActionmapsControlOption>,
+    pub after: Option<ActionmapsControlOption>,
+}
+
+/// Option changes on one device
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceHunk {
+    pub device_type: String,
+    pub instance: String,
+    pub product: String,
+    pub options: Vec<OptionHunk>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionHunk {
+    pub action: String,
+    pub kind: HunkKind,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// Binding changes in one actionmap
+#[derive(Debug, Clone, Serialize)]
+pub struct BindingHunk {
+    pub action_map: String,
+    pub actions: Vec<ActionHunk>,
+}
+
+/// Any comparison, grouped for display
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct DiffModel {
+    pub devices: Vec<DeviceHunk>,
+    pub bindings: Vec<BindingHunk>,
+    /// False when a side has no bindings to compare (a profile), so `bindings` is
+    /// empty for that reason rather than because nothing changed
+    pub bindings_compared: bool,
+    pub option_change_count: usize,
+    pub binding_change_count: usize,
+}
+
+impl DiffModel {
+    pub fn from_diff(diff: ActionmapsDiff, bindings_compared: bool) -> Self {
+        let mut model = DiffModel {
+            option_change_count: diff.option_changes.len(),
+            binding_change_count: diff.binding_changes.len(),
+            bindings_compared,
+            ..Default::default()
+        };
+
+        // Changes arrive sorted by device then option, and by actionmap then action
+        for change in diff.option_changes {
+            let hunk = OptionHunk {
+                kind: hunk_kind(&change.before, &change.after),
+                option: change.option,
+                before: change.before,
+                after: change.after,
+            };
+            match model.devices.last_mut() {
+                Some(device)
+                    if device.device_type == change.device_type
+                        && device.instance == change.instance =>
+                {
+                    device.options.push(hunk)
+                }
+                _ => model.devices.push(DeviceHunk {
+                    device_type: change.device_type,
+                    instance: change.instance,
+                    product: change.product,
+                    options: vec![hunk],
+                }),
+            }
+        }
+
+        for change in diff.binding_changes {
+            let kind = match (change.before.is_empty(), change.after.is_empty()) {
+                (true, _) => HunkKind::Added,
+                (_, true) => HunkKind::Removed,
+                _ => HunkKind::Modified,
+            };
+            let hunk = ActionHunk {
+                action: change.action,
+                kind,
+                before: change.before,
+                after: change.after,
+            };
+            match model.bindings.last_mut() {
+                Some(map) if map.action_map == change.action_map => map.actions.push(hunk),
+                _ => model.bindings.push(BindingHunk {
+                    action_map: change.action_map,
+                    actions: vec![hunk],
+                }),
+            }
+        }
+
+        model
+    }
+}
+
+/// Compare two loaded sides. Bindings are only compared when both are whole documents.
+pub fn diff_inputs(before: DiffInput, after: DiffInput) -> Result<DiffModel, String> {
+    fn options(input: DiffInput) -> Result<Vec<ActionmapsDeviceOptions>, String> {
+        match input {
+            DiffInput::Xml(xml) => controls::parse_actionmaps_options(&xml),
+            DiffInput::Options(options) => Ok(options),
+        }
+    }
+
+    if let (DiffInput::Xml(before), DiffInput::Xml(after)) = (&before, &after) {
+        return Ok(DiffModel::from_diff(diff_actionmaps(before, after)?, true));
+    }
+    let diff = ActionmapsDiff {
+        option_changes: diff_device_options(options(before)?, options(after)?),
+        binding_changes: Vec::new(),
+    };
+    Ok(DiffModel::from_diff(diff, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_model_hunks() {
+        let before = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="joystick" instance="1" Product="Stick">
+   <flight_move_pitch invert="1"/>
+   <flight_move_yaw invert="1"/>
+  </options>
+  <actionmap name="spaceship_general">
+   <action name="v_eject">
+    <rebind input="js1_button5"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>"#;
+        let after = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="joystick" instance="1" Product="Stick">
+   <flight_move_pitch invert="0"/>
+   <flight_move_roll invert="1"/>
+  </options>
+  <actionmap name="spaceship_general">
+   <action name="v_flightready">
+    <rebind input="js1_button6"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>"#;
+
+        let model = diff_inputs(
+            DiffInput::Xml(before.to_string()),
+            DiffInput::Xml(after.to_string()),
+        )
+        .unwrap();
+        assert!(model.bindings_compared);
+        assert_eq!(model.devices.len(), 1);
+        let kinds: Vec<(&str, HunkKind)> = model.devices[0]
+            .options
+            .iter()
+            .map(|h| (h.option.as_str(), h.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("flight_move_pitch", HunkKind::Modified),
+                ("flight_move_roll", HunkKind::Added),
+                ("flight_move_yaw", HunkKind::Removed),
+            ]
+        );
+        assert_eq!(model.bindings.len(), 1);
+        assert_eq!(model.bindings[0].actions.len(), 2);
+
+        // Against options only, bindings aren't compared
+        let options = controls::parse_actionmaps_options(after).unwrap();
+        let model = diff_inputs(
+            DiffInput::Xml(before.to_string()),
+            DiffInput::Options(options),
+        )
+        .unwrap();
+        assert!(!model.bindings_compared);
+        assert!(model.bindings.is_empty());
+        assert_eq!(model.option_change_count, 3);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::hid_reader;
+use crate::keyboard_capture;
 use rusty_xinput::{XInputHandle, XInputState};
 use serde::Serialize;
 use std::collections::HashMap;
@@ -11,7 +12,7 @@ const AXIS_TRIGGER_THRESHOLD: f32 = 0.5;
 const AXIS_RESET_THRESHOLD: f32 = 0.3;
 const MOVEMENT_THRESHOLD: f32 = 0.3;
 
-fn is_gamepad(name: &str) -> bool {
+pub fn is_gamepad(name: &str) -> bool {
     let name_lower = name.to_lowercase();
 
     eprintln!("is_gamepad: Checking device: '{}'", name);
@@ -744,6 +745,58 @@ pub fn wait_for_input(
     Ok(None)
 }
 
+/// Capture the next input from any device, including the keyboard, for press-to-bind.
+/// Returns the input with its SC binding string (e.g. `js2_button14`, `kb1_lctrl+k`).
+pub fn capture_next_input(
+    session_id: String,
+    timeout_secs: u64,
+) -> Result<Option<DetectedInput>, String> {
+    let start = Instant::now();
+    let timeout = Duration::from_secs(timeout_secs);
+
+    eprintln!(
+        "capture_next_input: Listening on all devices for {} seconds",
+        timeout_secs
+    );
+
+    let mut detector = InputDetector::new(session_id.clone());
+    let mut keyboard = keyboard_capture::KeyboardPoller::new();
+
+    while start.elapsed() < timeout {
+        if let Some(press) = keyboard.poll() {
+            let binding = keyboard_capture::keyboard_binding(&press.modifiers, &press.key);
+            let is_modifier = keyboard_capture::is_modifier_key(&press.key);
+
+            return Ok(Some(DetectedInput {
+                display_name: format!(
+                    "Keyboard - {}",
+                    binding.trim_start_matches("kb1_").to_uppercase()
+                ),
+                input_string: binding,
+                device_type: "Keyboard".to_string(),
+                axis_value: None,
+                modifiers: press.modifiers.iter().map(|m| m.to_uppercase()).collect(),
+                is_modifier,
+                session_id,
+                device_uuid: None,
+                raw_button_code: None,
+                raw_code_index: None,
+                device_name: Some("Keyboard".to_string()),
+                hid_usage_id: None,
+                hid_axis_name: None,
+            }));
+        }
+
+        if let Some(input) = detector.poll().into_iter().next() {
+            return Ok(Some(input));
+        }
+
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    Ok(None)
+}
+
 /// Wait for joystick inputs and emit events in real-time
 pub fn wait_for_inputs_with_events(
     window: tauri::Window,
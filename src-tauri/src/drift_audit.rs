@@ -0,0 +1,262 @@
+//! Nightly dry-run audit of the pinned environments
+//!
+//! The environment dashboard only tells you about drift when you look at it. The audit
+//! runs the same comparison once a day at a chosen hour (or at the next start, if the app
+//! wasn't running then) and raises a desktop notification when an environment's
+//! actionmaps.xml no longer matches its pinned profile, so it gets fixed before the next
+//! session rather than noticed in the cockpit. Nothing is written to the game files.
+
+use crate::environments::{EnvironmentStatus, SyncStatus};
+use chrono::{Local, NaiveDateTime, Timelike};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_notification::NotificationExt;
+
+/// Name of the last audit report inside the app data directory
+pub const REPORT_FILE_NAME: &str = "drift_audit.json";
+
+/// How often the scheduler checks whether the audit is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When and where to audit, stored in the settings
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DriftAuditConfig {
+    /// The StarCitizen folder holding the environments
+    pub base_path: String,
+    /// Local hour of day (0-23) to run at
+    pub hour: u32,
+}
+
+/// An environment whose actionmaps.xml no longer matches its pinned profile
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DriftedEnvironment {
+    pub name: String,
+    pub pinned_profile: String,
+    pub differing_options: usize,
+}
+
+/// Outcome of one audit, also the payload of the "drift-audit" event
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AuditReport {
+    /// ISO timestamp
+    pub ran_at: String,
+    /// Environments with a pinned profile that were compared
+    pub checked: usize,
+    pub drifted: Vec<DriftedEnvironment>,
+    /// Environments that couldn't be compared, as "name: error"
+    pub errors: Vec<String>,
+}
+
+/// Summarize dashboard rows into an audit report
+pub fn report_from(statuses: &[EnvironmentStatus]) -> AuditReport {
+    let mut report = AuditReport {
+        ran_at: Local::now().to_rfc3339(),
+        ..Default::default()
+    };
+    for status in statuses {
+        let Some(profile) = &status.pinned_profile else {
+            continue;
+        };
+        match status.sync {
+            SyncStatus::InSync => report.checked += 1,
+            SyncStatus::OutOfSync => {
+                report.checked += 1;
+                report.drifted.push(DriftedEnvironment {
+                    name: status.name.clone(),
+                    pinned_profile: profile.clone(),
+                    differing_options: status.differing_options,
+                });
+            }
+            SyncStatus::Error => report.errors.push(format!(
+                "{}: {}",
+                status.name,
+                status.error.as_deref().unwrap_or("unknown error")
+            )),
+            SyncStatus::NoActionmaps | SyncStatus::NoProfilePinned => {}
+        }
+    }
+    report
+}
+
+/// Is the audit due? It is when it hasn't run since the last time `hour` came round, so a
+/// run missed while the app was closed happens at the next start.
+pub fn is_due(hour: u32, now: NaiveDateTime, last_run: Option<NaiveDateTime>) -> bool {
+    let Some(today_at_hour) = now.date().and_hms_opt(hour, 0, 0) else {
+        return false;
+    };
+    let scheduled = if now.hour() >= hour {
+        today_at_hour
+    } else {
+        today_at_hour - chrono::Duration::days(1)
+    };
+    !last_run.is_some_and(|ran_at| ran_at >= scheduled)
+}
+
+fn report_path(dir: &Path) -> PathBuf {
+    dir.join(REPORT_FILE_NAME)
+}
+
+/// The last audit's report, if one has run
+pub fn load_report(dir: &Path) -> Result<Option<AuditReport>, String> {
+    let path = report_path(dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read drift audit report: {}", e))?;
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse drift audit report: {}", e))
+}
+
+fn save_report(dir: &Path, report: &AuditReport) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    let json = serde_json::to_string_pretty(report)
+        .map_err(|e| format!("Failed to serialize drift audit report: {}", e))?;
+    std::fs::write(report_path(dir), json)
+        .map_err(|e| format!("Failed to write drift audit report: {}", e))
+}
+
+/// When the last audit ran, in local time
+fn last_run(dir: &Path) -> Option<NaiveDateTime> {
+    let report = load_report(dir).ok()??;
+    chrono::DateTime::parse_from_rfc3339(&report.ran_at)
+        .ok()
+        .map(|ran_at| ran_at.with_timezone(&Local).naive_local())
+}
+
+/// Run the comparison, store the report, emit "drift-audit" and notify if anything drifted
+pub fn run(
+    app_handle: &AppHandle,
+    data_dir: &Path,
+    dashboard: &dyn Fn() -> Result<Vec<EnvironmentStatus>, String>,
+) -> Result<AuditReport, String> {
+    let report = report_from(&dashboard()?);
+    save_report(data_dir, &report)?;
+    info!(
+        "Drift audit: {} environment(s) checked, {} drifted",
+        report.checked,
+        report.drifted.len()
+    );
+
+    if !report.drifted.is_empty() {
+        let body = report
+            .drifted
+            .iter()
+            .map(|env| format!("{}: {} option(s) differ", env.name, env.differing_options))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Err(e) = app_handle
+            .notification()
+            .builder()
+            .title("Controls have drifted from the pinned profile")
+            .body(body)
+            .show()
+        {
+            error!("Failed to show drift notification: {}", e);
+        }
+    }
+    let _ = app_handle.emit("drift-audit", report.clone());
+    Ok(report)
+}
+
+/// A running audit schedule. Dropping it stops the scheduler thread.
+pub struct DriftAudit {
+    stop: Arc<AtomicBool>,
+}
+
+impl DriftAudit {
+    /// Run the audit daily at `hour`. `dashboard` produces the environment statuses to audit.
+    pub fn start(
+        app_handle: AppHandle,
+        data_dir: PathBuf,
+        hour: u32,
+        dashboard: impl Fn() -> Result<Vec<EnvironmentStatus>, String> + Send + 'static,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                if is_due(hour, Local::now().naive_local(), last_run(&data_dir)) {
+                    if let Err(e) = run(&app_handle, &data_dir, &dashboard) {
+                        error!("Drift audit failed: {}", e);
+                        // Don't retry every minute; try again tomorrow
+                        let _ = save_report(
+                            &data_dir,
+                            &AuditReport {
+                                ran_at: Local::now().to_rfc3339(),
+                                errors: vec![e],
+                                ..Default::default()
+                            },
+                        );
+                    }
+                }
+                thread::sleep(CHECK_INTERVAL);
+            }
+            info!("Drift audit schedule stopped");
+        });
+
+        DriftAudit { stop }
+    }
+}
+
+impl Drop for DriftAudit {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(name: &str, sync: SyncStatus, differing_options: usize) -> EnvironmentStatus {
+        EnvironmentStatus {
+            name: name.to_string(),
+            installation_path: String::new(),
+            actionmaps_path: String::new(),
+            pinned_profile: (sync != SyncStatus::NoProfilePinned)
+                .then(|| format!("{}.sccontrols", name)),
+            sync,
+            differing_options,
+            error: None,
+            last_applied: None,
+            last_external_change: None,
+            game_running: None,
+        }
+    }
+
+    #[test]
+    fn test_audit_report_and_schedule() {
+        let report = report_from(&[
+            status("LIVE", SyncStatus::OutOfSync, 3),
+            status("PTU", SyncStatus::InSync, 0),
+            status("EPTU", SyncStatus::NoProfilePinned, 0),
+        ]);
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.drifted.len(), 1);
+        assert_eq!(report.drifted[0].name, "LIVE");
+        assert_eq!(report.drifted[0].differing_options, 3);
+
+        let at = |day: u32, hour: u32| {
+            chrono::NaiveDate::from_ymd_opt(2026, 3, day)
+                .unwrap()
+                .and_hms_opt(hour, 30, 0)
+                .unwrap()
+        };
+        assert!(is_due(20, at(14, 21), None));
+        assert!(is_due(20, at(14, 21), Some(at(13, 20))));
+        assert!(!is_due(20, at(14, 21), Some(at(14, 20))));
+        // Before the hour, yesterday's run counts
+        assert!(!is_due(20, at(14, 9), Some(at(13, 20))));
+        // Missed yesterday evening (the app was closed): run now
+        assert!(is_due(20, at(14, 9), Some(at(13, 10))));
+    }
+}
@@ -0,0 +1,219 @@
+//! Per-environment status: LIVE, PTU, EPTU and TECH-PREVIEW side by side
+//!
+//! Each SC environment has its own actionmaps.xml, and players testing on PTU tend to lose
+//! track of which profile went where. A profile can be pinned to each environment; the
+//! dashboard then shows, for every installed environment, the pinned profile, whether the
+//! file still matches it, when we last applied to it and what last changed it behind our
+//! back.
+
+use crate::controls::{self, ControlsFile};
+use crate::diff;
+use crate::game_process::{self, GameProcess};
+use crate::modification_log::{self, ModificationEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Environment folders inside the StarCitizen directory, in display order
+pub const ENVIRONMENTS: [&str; 4] = ["LIVE", "PTU", "EPTU", "TECH-PREVIEW"];
+
+/// Name of the apply history inside the app data directory
+pub const APPLY_HISTORY_FILE_NAME: &str = "apply_history.json";
+
+/// Installed environments under a StarCitizen directory, as (name, installation path)
+pub fn detect_environments(base: &Path) -> Vec<(String, PathBuf)> {
+    ENVIRONMENTS
+        .iter()
+        .map(|name| (name.to_string(), base.join(name)))
+        .filter(|(_, path)| path.join("data.p4k").is_file())
+        .collect()
+}
+
+/// The last time a profile was applied to an actionmaps.xml
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApplyRecord {
+    /// ISO timestamp
+    pub applied_at: String,
+    pub profile_name: String,
+}
+
+fn history_path(dir: &Path) -> PathBuf {
+    dir.join(APPLY_HISTORY_FILE_NAME)
+}
+
+/// Last apply per actionmaps.xml path
+fn load_history(dir: &Path) -> Result<HashMap<String, ApplyRecord>, String> {
+    let path = history_path(dir);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read apply history: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse apply history: {}", e))
+}
+
+pub fn record_apply(dir: &Path, actionmaps_path: &str, profile_name: &str) -> Result<(), String> {
+    let mut history = load_history(dir)?;
+    history.insert(
+        actionmaps_path.to_string(),
+        ApplyRecord {
+            applied_at: chrono::Local::now().to_rfc3339(),
+            profile_name: profile_name.to_string(),
+        },
+    );
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    let json = serde_json::to_string_pretty(&history)
+        .map_err(|e| format!("Failed to serialize apply history: {}", e))?;
+    std::fs::write(history_path(dir), json)
+        .map_err(|e| format!("Failed to write apply history: {}", e))
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    /// Applying the pinned profile would change nothing
+    InSync,
+    /// Some options differ from the pinned profile
+    OutOfSync,
+    NoProfilePinned,
+    /// The environment has no actionmaps.xml yet (the game hasn't been run)
+    NoActionmaps,
+    /// The pinned profile or actionmaps.xml couldn't be read
+    Error,
+}
+
+/// One row of the dashboard
+#[derive(Debug, Serialize, Clone)]
+pub struct EnvironmentStatus {
+    pub name: String,
+    pub installation_path: String,
+    pub actionmaps_path: String,
+    pub pinned_profile: Option<String>,
+    pub sync: SyncStatus,
+    /// Options an apply of the pinned profile would change
+    pub differing_options: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub last_applied: Option<ApplyRecord>,
+    /// Newest modification log entry for this environment's actionmaps.xml
+    pub last_external_change: Option<ModificationEntry>,
+    /// Star Citizen running from this environment, which blocks applying to it
+    pub game_running: Option<GameProcess>,
+}
+
+/// How many options applying `profile` would change
+fn differing_options(
+    xml: &str,
+    profile: &ControlsFile,
+    excluded: &[String],
+) -> Result<usize, String> {
+    let merged = controls::merge_options_into_xml(
+        xml,
+        controls::controls_to_actionmaps(profile, false),
+        excluded,
+    )?;
+    Ok(diff::diff_actionmaps(xml, &merged)?.option_changes.len())
+}
+
+/// Status of every installed environment. `load_profile` reads a pinned .sccontrols file
+/// (resolving any device variables); `excluded` options don't count as out of sync.
+pub fn dashboard(
+    base: &Path,
+    pinned_profiles: &HashMap<String, String>,
+    data_dir: &Path,
+    excluded: &[String],
+    load_profile: impl Fn(&str) -> Result<ControlsFile, String>,
+) -> Result<Vec<EnvironmentStatus>, String> {
+    let history = load_history(data_dir)?;
+    let log = modification_log::read_entries(data_dir, None)?;
+    let processes = game_process::running_game_processes();
+
+    let statuses = detect_environments(base)
+        .into_iter()
+        .map(|(name, installation)| {
+            let actionmaps_path = crate::actionmaps_path_in(&installation)
+                .to_string_lossy()
+                .to_string();
+            let pinned_profile = pinned_profiles.get(&name).cloned();
+
+            let mut status = EnvironmentStatus {
+                name,
+                installation_path: installation.to_string_lossy().to_string(),
+                actionmaps_path: actionmaps_path.clone(),
+                pinned_profile: pinned_profile.clone(),
+                sync: SyncStatus::NoProfilePinned,
+                differing_options: 0,
+                error: None,
+                last_applied: history.get(&actionmaps_path).cloned(),
+                last_external_change: log
+                    .iter()
+                    .find(|entry| entry.actionmaps_path == actionmaps_path)
+                    .cloned(),
+                game_running: None,
+            };
+            if !processes.is_empty() {
+                status.game_running = game_process::running_in(&processes, Some(&installation));
+            }
+
+            if !Path::new(&actionmaps_path).exists() {
+                status.sync = SyncStatus::NoActionmaps;
+            } else if let Some(profile_path) = &pinned_profile {
+                let result = std::fs::read_to_string(&actionmaps_path)
+                    .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))
+                    .and_then(|xml| {
+                        differing_options(&xml, &load_profile(profile_path)?, excluded)
+                    });
+                match result {
+                    Ok(0) => status.sync = SyncStatus::InSync,
+                    Ok(count) => {
+                        status.sync = SyncStatus::OutOfSync;
+                        status.differing_options = count;
+                    }
+                    Err(e) => {
+                        status.sync = SyncStatus::Error;
+                        status.error = Some(e);
+                    }
+                }
+            }
+            status
+        })
+        .collect();
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::ControlOptionSettings;
+
+    #[test]
+    fn test_differing_options() {
+        let xml = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="joystick" instance="1" Product="Stick">
+   <flight_move_pitch invert="1"/>
+  </options>
+ </ActionProfiles>
+</ActionMaps>
+"#;
+        let mut profile = ControlsFile::new("Pinned".to_string());
+        profile.device_mut("joystick", "1").unwrap().options.insert(
+            "flight_move_pitch".to_string(),
+            ControlOptionSettings {
+                invert: Some(true),
+                ..Default::default()
+            },
+        );
+        assert_eq!(differing_options(xml, &profile, &[]).unwrap(), 0);
+
+        profile.device_mut("joystick", "1").unwrap().options.insert(
+            "flight_move_yaw".to_string(),
+            ControlOptionSettings {
+                invert: Some(true),
+                ..Default::default()
+            },
+        );
+        assert_eq!(differing_options(xml, &profile, &[]).unwrap(), 1);
+    }
+}
@@ -0,0 +1,498 @@
+//! Essentials: a small set of bindings and options that must survive a wipe
+//!
+//! Game patches and "reset to defaults" regularly leave players with a fresh
+//! actionmaps.xml. Users mark the handful of bindings and options they can't fly
+//! without (landing gear, quantum drive, stick inversion...) as essentials, and these
+//! can be put back in one go, or automatically when the watcher sees them disappear,
+//! even when the rest of the profile is going to be rebuilt by hand.
+
+use crate::controls::{self, ActionmapsDeviceOptions, ControlOptionSettings, ControlsFile};
+use crate::diff::ActionmapsDiff;
+use crate::keybindings::ActionMaps;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the essentials store inside the app data directory
+pub const ESSENTIALS_FILE_NAME: &str = "essentials.json";
+
+/// A binding that must always be present, e.g. spaceship_general / v_toggle_landing_system / js1_button3
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EssentialBinding {
+    pub action_map: String,
+    pub action: String,
+    pub input: String,
+}
+
+/// An option that must always be set, e.g. joystick 1 flight_move_pitch invert
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EssentialOption {
+    pub device_type: String,
+    pub instance: String,
+    pub option: String,
+    pub settings: ControlOptionSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Essentials {
+    /// Restore missing essentials as soon as the actionmaps watcher notices them gone
+    pub auto_restore: bool,
+    pub bindings: Vec<EssentialBinding>,
+    pub options: Vec<EssentialOption>,
+}
+
+/// Which essentials a given actionmaps.xml is missing
+#[derive(Debug, Serialize, Clone)]
+pub struct EssentialsStatus {
+    pub missing_bindings: Vec<EssentialBinding>,
+    /// "type instance option", e.g. "joystick 1 flight_move_pitch"
+    pub missing_options: Vec<String>,
+    /// The file has no rebinds at all, i.e. it was reset or freshly created by the game
+    pub looks_wiped: bool,
+}
+
+impl EssentialsStatus {
+    pub fn is_complete(&self) -> bool {
+        self.missing_bindings.is_empty() && self.missing_options.is_empty()
+    }
+}
+
+fn essentials_path(dir: &Path) -> PathBuf {
+    dir.join(ESSENTIALS_FILE_NAME)
+}
+
+/// Load the essentials, empty if none have been marked yet
+pub fn load_essentials(dir: &Path) -> Result<Essentials, String> {
+    let path = essentials_path(dir);
+    if !path.exists() {
+        return Ok(Essentials::default());
+    }
+
+    let json =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read essentials: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse essentials: {}", e))
+}
+
+pub fn save_essentials(dir: &Path, essentials: &Essentials) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+    let json = serde_json::to_string_pretty(essentials)
+        .map_err(|e| format!("Failed to serialize essentials: {}", e))?;
+    std::fs::write(essentials_path(dir), json)
+        .map_err(|e| format!("Failed to write essentials: {}", e))
+}
+
+/// The essential options as they'd be written to actionmaps.xml
+fn wanted_options(essentials: &Essentials) -> Result<Vec<ActionmapsDeviceOptions>, String> {
+    let mut controls_file = ControlsFile::new("Essentials".to_string());
+    for option in &essentials.options {
+        controls_file
+            .device_mut(&option.device_type, &option.instance)?
+            .options
+            .insert(option.option.clone(), option.settings.clone());
+    }
+    Ok(controls::controls_to_actionmaps(&controls_file, false))
+}
+
+/// Attribute values match when equal as numbers ("0.10" == "0.1") or as text
+fn same_value(a: &str, b: &str) -> bool {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => (a - b).abs() < 1e-6,
+        _ => a == b,
+    }
+}
+
+pub fn check(xml: &str, essentials: &Essentials) -> Result<EssentialsStatus, String> {
+    let action_maps = ActionMaps::from_xml(xml)?;
+
+    let missing_bindings = essentials
+        .bindings
+        .iter()
+        .filter(|binding| {
+            !action_maps
+                .action_maps
+                .iter()
+                .filter(|m| m.name == binding.action_map)
+                .flat_map(|m| m.actions.iter())
+                .filter(|a| a.name == binding.action)
+                .any(|a| a.rebinds.iter().any(|r| r.input == binding.input))
+        })
+        .cloned()
+        .collect();
+
+    let existing = controls::parse_actionmaps_options(xml)?;
+    let mut missing_options = Vec::new();
+    for device in wanted_options(essentials)? {
+        let existing_device = existing
+            .iter()
+            .find(|d| d.device_type == device.device_type && d.instance == device.instance);
+        for option in &device.options {
+            let present = existing_device
+                .and_then(|d| d.options.iter().find(|o| o.name == option.name))
+                .is_some_and(|existing_option| {
+                    option.attributes.iter().all(|(key, value)| {
+                        existing_option
+                            .attributes
+                            .iter()
+                            .any(|(k, v)| k == key && same_value(v, value))
+                    })
+                });
+            if !present {
+                missing_options.push(format!(
+                    "{} {} {}",
+                    device.device_type, device.instance, option.name
+                ));
+            }
+        }
+    }
+
+    Ok(EssentialsStatus {
+        missing_bindings,
+        missing_options,
+        looks_wiped: action_maps.rebind_count() == 0,
+    })
+}
+
+/// Device prefix of an input, e.g. "js1_" for "js1_button3"
+fn device_prefix(input: &str) -> &str {
+    input.find('_').map(|i| &input[..=i]).unwrap_or(input)
+}
+
+/// Where an element sits: its start tag (or the whole element when self-closing) and
+/// the start of its end tag
+struct Span {
+    start: usize,
+    end: usize,
+    close_start: Option<usize>,
+}
+
+/// Where a binding's action map, action and same-device rebind sit in the file
+#[derive(Default)]
+struct BindingLayout {
+    /// Start of `</ActionProfiles>`
+    profiles_close: Option<usize>,
+    map: Option<Span>,
+    action: Option<Span>,
+    rebind: Option<Span>,
+}
+
+fn attribute(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == key)
+        .map(|a| {
+            a.unescape_value()
+                .map(|v| v.into_owned())
+                .unwrap_or_else(|_| String::from_utf8_lossy(&a.value).into_owned())
+        })
+}
+
+/// Scan the file once for the elements a binding goes into
+fn scan_binding(xml: &str, binding: &EssentialBinding) -> Result<BindingLayout, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut layout = BindingLayout::default();
+    let mut open: Vec<String> = Vec::new();
+    // How many elements were open around the matched map, action and rebind
+    let mut depths: [Option<usize>; 3] = [None; 3];
+    let prefix = device_prefix(&binding.input);
+
+    loop {
+        let start = reader.buffer_position() as usize;
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Failed to parse actionmaps.xml: {}", e))?;
+        let end = reader.buffer_position() as usize;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                let parent = open.last().map(String::as_str);
+                let span = Span {
+                    start,
+                    end,
+                    close_start: None,
+                };
+                let matched = match (parent, name.as_str()) {
+                    (Some("ActionProfiles"), "actionmap")
+                        if layout.map.is_none()
+                            && attribute(e, b"name").as_deref()
+                                == Some(binding.action_map.as_str()) =>
+                    {
+                        layout.map = Some(span);
+                        Some(0)
+                    }
+                    (Some("actionmap"), "action")
+                        if depths[0] == Some(open.len() - 1)
+                            && layout.action.is_none()
+                            && attribute(e, b"name").as_deref()
+                                == Some(binding.action.as_str()) =>
+                    {
+                        layout.action = Some(span);
+                        Some(1)
+                    }
+                    (Some("action"), "rebind")
+                        if depths[1] == Some(open.len() - 1)
+                            && layout.rebind.is_none()
+                            && attribute(e, b"input")
+                                .is_some_and(|input| input.trim().starts_with(prefix)) =>
+                    {
+                        layout.rebind = Some(span);
+                        Some(2)
+                    }
+                    _ => None,
+                };
+                if !is_empty {
+                    if let Some(level) = matched {
+                        depths[level] = Some(open.len());
+                    }
+                    open.push(name);
+                }
+            }
+            Event::End(_) => {
+                let name = open.pop().unwrap_or_default();
+                if name == "ActionProfiles" && layout.profiles_close.is_none() {
+                    layout.profiles_close = Some(start);
+                }
+                let spans = [&mut layout.map, &mut layout.action, &mut layout.rebind];
+                for (depth, span) in depths.iter_mut().zip(spans) {
+                    if *depth == Some(open.len()) {
+                        *depth = None;
+                        if let Some(span) = span {
+                            span.close_start = Some(start);
+                            span.end = end;
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(layout)
+}
+
+/// `pos` moved back over the spaces and tabs before it
+fn trimmed(xml: &str, pos: usize) -> usize {
+    xml[..pos].trim_end_matches([' ', '\t']).len()
+}
+
+/// Put one binding into an actionmaps.xml document. An existing rebind for the same
+/// device on that action is replaced, since an action has one input per device.
+fn insert_binding(xml: &str, binding: &EssentialBinding) -> Result<String, String> {
+    let rebind = format!("<rebind input=\"{}\"/>", escape(&binding.input));
+    let action = |rebind: &str| {
+        format!(
+            "<action name=\"{}\">\n    {}\n   </action>",
+            escape(&binding.action),
+            rebind
+        )
+    };
+    let action_map = || {
+        format!(
+            "<actionmap name=\"{}\">\n   {}\n  </actionmap>",
+            escape(&binding.action_map),
+            action(&rebind)
+        )
+    };
+
+    let layout = scan_binding(xml, binding)?;
+    let (from, to, text) = match (layout.map, layout.action, layout.rebind) {
+        (_, _, Some(existing)) => (existing.start, existing.end, rebind.clone()),
+        (_, Some(span), None) => match span.close_start {
+            Some(close) => (trimmed(xml, close), close, format!("    {}\n   ", rebind)),
+            None => (span.start, span.end, action(&rebind)),
+        },
+        (Some(span), None, None) => match span.close_start {
+            Some(close) => (
+                trimmed(xml, close),
+                close,
+                format!("   {}\n  ", action(&rebind)),
+            ),
+            None => (span.start, span.end, action_map()),
+        },
+        // No overrides in this action map yet
+        (None, None, None) => {
+            let close = layout
+                .profiles_close
+                .ok_or("Could not find ActionProfiles in actionmaps.xml")?;
+            (trimmed(xml, close), close, format!("  {}\n ", action_map()))
+        }
+    };
+    Ok(format!("{}{}{}", &xml[..from], text, &xml[to..]))
+}
+
+/// Did a change take away any essential, i.e. drop an essential rebind or change an
+/// essential option? Auto-restore only runs when one did.
+pub fn removed_by(diff: &ActionmapsDiff, essentials: &Essentials) -> bool {
+    let binding_removed = diff.binding_changes.iter().any(|change| {
+        essentials.bindings.iter().any(|binding| {
+            let has = |inputs: &[String]| inputs.iter().any(|i| i.trim() == binding.input);
+            change.action_map == binding.action_map
+                && change.action == binding.action
+                && has(&change.before)
+                && !has(&change.after)
+        })
+    });
+    let option_changed = diff.option_changes.iter().any(|change| {
+        essentials.options.iter().any(|option| {
+            change.device_type == option.device_type
+                && change.instance == option.instance
+                && change.option == option.option
+        })
+    });
+    binding_removed || option_changed
+}
+
+/// Counts of what a restore put back
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RestoreSummary {
+    pub bindings_restored: usize,
+    pub options_restored: usize,
+}
+
+/// Put any missing essentials back into an actionmaps.xml document.
+/// Returns None when nothing is missing.
+pub fn restore(
+    xml: &str,
+    essentials: &Essentials,
+    excluded: &[String],
+) -> Result<Option<(String, RestoreSummary)>, String> {
+    let status = check(xml, essentials)?;
+    if status.is_complete() {
+        return Ok(None);
+    }
+
+    let mut xml = xml.to_string();
+    for binding in &status.missing_bindings {
+        xml = insert_binding(&xml, binding)?;
+    }
+
+    let wanted: Vec<ActionmapsDeviceOptions> = wanted_options(essentials)?
+        .into_iter()
+        .map(|mut device| {
+            device.options.retain(|o| {
+                status.missing_options.contains(&format!(
+                    "{} {} {}",
+                    device.device_type, device.instance, o.name
+                ))
+            });
+            device
+        })
+        .filter(|device| !device.options.is_empty())
+        .collect();
+    if !wanted.is_empty() {
+        xml = controls::merge_options_into_xml(&xml, wanted, excluded)?;
+    }
+
+    Ok(Some((
+        xml,
+        RestoreSummary {
+            bindings_restored: status.missing_bindings.len(),
+            options_restored: status.missing_options.len(),
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIPED: &str = r#"<ActionMaps>
+ <ActionProfiles version="1" optionsVersion="2" rebindVersion="2" profileName="default">
+  <options type="joystick" instance="1" Product="Stick"/>
+  <modifiers />
+ </ActionProfiles>
+</ActionMaps>
+"#;
+
+    fn landing_gear(input: &str) -> EssentialBinding {
+        EssentialBinding {
+            action_map: "spaceship_general".to_string(),
+            action: "v_toggle_landing_system".to_string(),
+            input: input.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_restore_into_wiped_file() {
+        let essentials = Essentials {
+            auto_restore: false,
+            bindings: vec![landing_gear("js1_button3")],
+            options: vec![EssentialOption {
+                device_type: "joystick".to_string(),
+                instance: "1".to_string(),
+                option: "flight_move_pitch".to_string(),
+                settings: ControlOptionSettings {
+                    invert: Some(true),
+                    ..Default::default()
+                },
+            }],
+        };
+
+        let status = check(WIPED, &essentials).unwrap();
+        assert!(status.looks_wiped);
+        assert_eq!(status.missing_bindings.len(), 1);
+        assert_eq!(status.missing_options, vec!["joystick 1 flight_move_pitch"]);
+
+        let (restored, summary) = restore(WIPED, &essentials, &[]).unwrap().unwrap();
+        assert_eq!(summary.bindings_restored, 1);
+        assert_eq!(summary.options_restored, 1);
+        assert!(check(&restored, &essentials).unwrap().is_complete());
+        assert!(restore(&restored, &essentials, &[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_restore_replaces_same_device_rebind() {
+        let essentials = Essentials {
+            bindings: vec![landing_gear("js1_button3")],
+            ..Default::default()
+        };
+        let (first, _) = restore(WIPED, &essentials, &[]).unwrap().unwrap();
+
+        let moved = Essentials {
+            bindings: vec![landing_gear("js1_button7")],
+            ..Default::default()
+        };
+        let (second, _) = restore(&first, &moved, &[]).unwrap().unwrap();
+        assert!(second.contains("js1_button7"));
+        assert!(!second.contains("js1_button3"));
+    }
+
+    #[test]
+    fn test_restore_into_existing_elements_and_gate_on_removal() {
+        // The action map is there with another action, which mentions the name in text
+        let xml = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <actionmap name="spaceship_general">
+   <action name="v_toggle_landing_system_alt">
+    <rebind input="js1_button3"/>
+   </action>
+   <action name="v_toggle_landing_system"/>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>
+"#;
+        let essentials = Essentials {
+            auto_restore: true,
+            bindings: vec![landing_gear("js1_button5")],
+            ..Default::default()
+        };
+        let (restored, _) = restore(xml, &essentials, &[]).unwrap().unwrap();
+        assert!(check(&restored, &essentials).unwrap().is_complete());
+        // The other action keeps its rebind
+        assert!(restored.contains(r#"<rebind input="js1_button3"/>"#));
+        assert_eq!(restored.matches("<actionmap ").count(), 1);
+
+        // Only a change that drops the essential calls for a restore
+        let unrelated = restored.replace("js1_button3", "js1_button4");
+        let diff = crate::diff::diff_actionmaps(&restored, &unrelated).unwrap();
+        assert!(!removed_by(&diff, &essentials));
+        let diff = crate::diff::diff_actionmaps(&restored, xml).unwrap();
+        assert!(removed_by(&diff, &essentials));
+    }
+}
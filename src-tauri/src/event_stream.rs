@@ -0,0 +1,424 @@
+//! WebSocket stream of live input and app events
+//!
+//! Dashboards and practice/trainer tools want input as it happens rather than polling
+//! the local API. A client upgrades a token-checked request to `/api/events` into a
+//! WebSocket and then receives one JSON text message per event:
+//! `{"event": "input", "payload": {"type": "button", ...}}`. Events are published from
+//! wherever they happen (the input monitor, profile loads, the actionmaps watcher)
+//! through a process-wide list of subscribers, which costs nothing while nobody listens.
+//! Only the small part of RFC 6455 a server sending text needs is implemented; client
+//! messages other than close are ignored. Each client gets a bounded queue, and one that
+//! reads too slowly to keep up is dropped rather than buffered without limit.
+
+use crate::input_monitor::DeviceInputState;
+use crate::local_api::Request;
+use base64::Engine;
+use log::{info, warn};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Path clients upgrade to a WebSocket
+pub const EVENTS_PATH: &str = "/api/events";
+
+/// Appended to the client's key to prove the server speaks WebSocket (RFC 6455 1.3)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How often the stream pings, so dead clients are noticed
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Largest client frame we accept; clients have nothing to say beyond close and pings
+const MAX_CLIENT_FRAME: u64 = 64 * 1024;
+
+/// Events queued for a client before it's considered too slow and dropped
+const SUBSCRIBER_QUEUE: usize = 1024;
+
+/// How long a write to a client may block before the client is given up on
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+
+static SUBSCRIBERS: Mutex<Vec<(u64, SyncSender<String>)>> = Mutex::new(Vec::new());
+
+static NEXT_SUBSCRIBER: AtomicU64 = AtomicU64::new(0);
+
+/// A change in one input, payload of the "input" event
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputEvent {
+    Button {
+        device_uuid: String,
+        instance: usize,
+        button: u32,
+        pressed: bool,
+    },
+    Axis {
+        device_uuid: String,
+        instance: usize,
+        axis_id: u32,
+        name: String,
+        value: f32,
+    },
+    Hat {
+        device_uuid: String,
+        instance: usize,
+        hat_id: u32,
+        direction: String,
+    },
+}
+
+/// Is anyone listening? Lets publishers skip building payloads.
+pub fn has_subscribers() -> bool {
+    !SUBSCRIBERS.lock().unwrap().is_empty()
+}
+
+/// Send an event to every connected client, dropping clients whose queue is full
+pub fn publish(event: &str, payload: impl Serialize) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    if subscribers.is_empty() {
+        return;
+    }
+    let message = serde_json::json!({ "event": event, "payload": payload }).to_string();
+    subscribers.retain(|(_, subscriber)| subscriber.try_send(message.clone()).is_ok());
+}
+
+/// A client's place in the subscriber list, given up when it's dropped so a client
+/// that went away doesn't count as listening until the next publish
+struct Subscription {
+    id: u64,
+    events: Receiver<String>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        SUBSCRIBERS.lock().unwrap().retain(|(id, _)| *id != self.id);
+    }
+}
+
+fn subscribe() -> Subscription {
+    let (sender, events) = mpsc::sync_channel(SUBSCRIBER_QUEUE);
+    let id = NEXT_SUBSCRIBER.fetch_add(1, Ordering::Relaxed);
+    SUBSCRIBERS.lock().unwrap().push((id, sender));
+    Subscription { id, events }
+}
+
+/// What changed between two polls of the input monitor. A device that just appeared
+/// only reports its pressed buttons, as if everything else had been at rest.
+pub fn input_events(
+    previous: &[DeviceInputState],
+    current: &[DeviceInputState],
+) -> Vec<InputEvent> {
+    let mut events = Vec::new();
+    for device in current {
+        let before = previous
+            .iter()
+            .find(|d| d.device_uuid == device.device_uuid);
+        let uuid = || device.device_uuid.clone();
+
+        let was_pressed = |button: &u32| before.is_some_and(|b| b.pressed_buttons.contains(button));
+        for button in device.pressed_buttons.iter().filter(|b| !was_pressed(b)) {
+            events.push(InputEvent::Button {
+                device_uuid: uuid(),
+                instance: device.instance,
+                button: *button,
+                pressed: true,
+            });
+        }
+        if let Some(before) = before {
+            for button in before
+                .pressed_buttons
+                .iter()
+                .filter(|b| !device.pressed_buttons.contains(b))
+            {
+                events.push(InputEvent::Button {
+                    device_uuid: uuid(),
+                    instance: device.instance,
+                    button: *button,
+                    pressed: false,
+                });
+            }
+
+            for axis in &device.axes {
+                let moved = before
+                    .axes
+                    .iter()
+                    .find(|a| a.axis_id == axis.axis_id)
+                    .is_none_or(|a| a.value != axis.value);
+                if moved {
+                    events.push(InputEvent::Axis {
+                        device_uuid: uuid(),
+                        instance: device.instance,
+                        axis_id: axis.axis_id,
+                        name: axis.name.clone(),
+                        value: axis.value,
+                    });
+                }
+            }
+            for hat in &device.hats {
+                if !before.hats.contains(hat) {
+                    events.push(InputEvent::Hat {
+                        device_uuid: uuid(),
+                        instance: device.instance,
+                        hat_id: hat.hat_id,
+                        direction: hat.direction.clone(),
+                    });
+                }
+            }
+        }
+    }
+    events
+}
+
+/// Publish the input changes between two polls
+pub fn publish_input(previous: &[DeviceInputState], current: &[DeviceInputState]) {
+    if !has_subscribers() {
+        return;
+    }
+    for event in input_events(previous, current) {
+        publish("input", event);
+    }
+}
+
+/// Is the request asking to become a WebSocket?
+pub fn is_upgrade(request: &Request) -> bool {
+    request
+        .headers
+        .get("upgrade")
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+/// The Sec-WebSocket-Accept answer to a client's Sec-WebSocket-Key
+pub fn accept_key(client_key: &str) -> String {
+    let digest = Sha1::digest(format!("{}{}", client_key.trim(), WEBSOCKET_GUID).as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(digest)
+}
+
+/// An unmasked, unfragmented server frame
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Read one client frame, returning its opcode (the payload is dropped). Client frames
+/// must be masked (RFC 6455 5.1), so an unmasked one is an error.
+fn read_frame(stream: &mut impl Read) -> Result<u8, String> {
+    let mut read = |buffer: &mut [u8]| {
+        stream
+            .read_exact(buffer)
+            .map_err(|e| format!("Failed to read frame: {}", e))
+    };
+    let mut header = [0u8; 2];
+    read(&mut header)?;
+    let mut len = (header[1] & 0x7f) as u64;
+    if len == 126 {
+        let mut bytes = [0u8; 2];
+        read(&mut bytes)?;
+        len = u16::from_be_bytes(bytes) as u64;
+    } else if len == 127 {
+        let mut bytes = [0u8; 8];
+        read(&mut bytes)?;
+        len = u64::from_be_bytes(bytes);
+    }
+    if len > MAX_CLIENT_FRAME {
+        return Err(format!("Client frame of {} bytes is too large", len));
+    }
+    if header[1] & 0x80 == 0 {
+        return Err("Client frame is not masked".to_string());
+    }
+    let mut rest = vec![0u8; len as usize + 4];
+    read(&mut rest)?;
+    Ok(header[0] & 0x0f)
+}
+
+/// Finish the handshake and stream events to the client until it closes, the
+/// connection drops or `stop` is set
+pub fn serve(mut stream: TcpStream, request: &Request, stop: &AtomicBool) -> Result<(), String> {
+    let key = request
+        .headers
+        .get("sec-websocket-key")
+        .ok_or("WebSocket upgrade without Sec-WebSocket-Key")?;
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    stream
+        .write_all(handshake.as_bytes())
+        .map_err(|e| format!("Failed to write handshake: {}", e))?;
+
+    stream
+        .set_write_timeout(Some(WRITE_TIMEOUT))
+        .map_err(|e| format!("Failed to configure connection: {}", e))?;
+
+    let subscription = subscribe();
+    info!("Event stream client connected");
+
+    // Clients only ever close (or ping, which we don't need to answer for a one-way
+    // stream); a reader thread notices so the writer can stop
+    let closed = Arc::new(AtomicBool::new(false));
+    let mut reader = stream
+        .try_clone()
+        .map_err(|e| format!("Failed to clone connection: {}", e))?;
+    reader
+        .set_read_timeout(None)
+        .map_err(|e| format!("Failed to configure connection: {}", e))?;
+    let reader_closed = closed.clone();
+    thread::spawn(move || {
+        while !matches!(read_frame(&mut reader), Ok(OPCODE_CLOSE) | Err(_)) {}
+        reader_closed.store(true, Ordering::Relaxed);
+    });
+
+    let write = |stream: &mut TcpStream, opcode: u8, payload: &[u8]| {
+        stream
+            .write_all(&frame(opcode, payload))
+            .map_err(|e| format!("Failed to write event: {}", e))
+    };
+    let mut pinged = Instant::now();
+    let result = loop {
+        if closed.load(Ordering::Relaxed) || stop.load(Ordering::Relaxed) {
+            break write(&mut stream, OPCODE_CLOSE, &[]);
+        }
+        // Wake up now and then to notice a close or a stop even when nothing happens
+        let sent = match subscription.events.recv_timeout(Duration::from_millis(250)) {
+            Ok(message) => write(&mut stream, OPCODE_TEXT, message.as_bytes()),
+            Err(RecvTimeoutError::Timeout) => Ok(()),
+            // Dropped by publish for falling behind
+            Err(RecvTimeoutError::Disconnected) => {
+                warn!("Event stream client fell behind, disconnecting");
+                break write(&mut stream, OPCODE_CLOSE, &[]);
+            }
+        };
+        if let Err(e) = sent {
+            break Err(e);
+        }
+        if pinged.elapsed() >= PING_INTERVAL {
+            pinged = Instant::now();
+            if let Err(e) = write(&mut stream, OPCODE_PING, &[]) {
+                break Err(e);
+            }
+        }
+    };
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    info!("Event stream client disconnected");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_monitor::{AxisValue, HatValue};
+
+    #[test]
+    fn test_handshake_and_input_events() {
+        // Example from RFC 6455 section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(frame(OPCODE_TEXT, b"hi"), vec![0x81, 2, b'h', b'i']);
+        assert_eq!(&frame(OPCODE_TEXT, &[0; 300])[..4], &[0x81, 126, 1, 44]);
+        // Past 65535 bytes the length takes eight bytes
+        let long = frame(OPCODE_TEXT, &[0; 70000]);
+        assert_eq!(&long[..10], &[0x81, 127, 0, 0, 0, 0, 0, 1, 0x11, 0x70]);
+        assert_eq!(long.len(), 70010);
+        // Masked client close: header, mask key, no payload
+        assert_eq!(
+            read_frame(&mut &[0x88, 0x80, 1, 2, 3, 4][..]),
+            Ok(OPCODE_CLOSE)
+        );
+        // Clients must mask their frames
+        assert!(read_frame(&mut &[0x88, 0x00][..]).is_err());
+        // A client frame with an eight byte length is refused when it's too large
+        assert!(read_frame(&mut &[0x81, 0xff, 0, 0, 0, 0, 0, 1, 0x11, 0x70][..]).is_err());
+
+        let state = |buttons: Vec<u32>, x: f32, hat: &str| DeviceInputState {
+            device_uuid: "231d:0200".to_string(),
+            device_name: "VKB Gladiator NXT".to_string(),
+            device_type: "joystick".to_string(),
+            instance: 1,
+            axes: vec![AxisValue {
+                axis_id: 0,
+                name: "x".to_string(),
+                value: x,
+            }],
+            pressed_buttons: buttons,
+            hats: vec![HatValue {
+                hat_id: 1,
+                direction: hat.to_string(),
+            }],
+        };
+        let before = [state(vec![1, 3], 0.0, "centered")];
+        let after = [state(vec![3, 7], 0.5, "up")];
+        let button = |button, pressed| InputEvent::Button {
+            device_uuid: "231d:0200".to_string(),
+            instance: 1,
+            button,
+            pressed,
+        };
+        assert_eq!(
+            input_events(&before, &after),
+            vec![
+                button(7, true),
+                button(1, false),
+                InputEvent::Axis {
+                    device_uuid: "231d:0200".to_string(),
+                    instance: 1,
+                    axis_id: 0,
+                    name: "x".to_string(),
+                    value: 0.5,
+                },
+                InputEvent::Hat {
+                    device_uuid: "231d:0200".to_string(),
+                    instance: 1,
+                    hat_id: 1,
+                    direction: "up".to_string(),
+                },
+            ]
+        );
+        // A device that just appeared reports what's held
+        assert_eq!(
+            input_events(&[], &before),
+            vec![button(1, true), button(3, true)]
+        );
+    }
+
+    #[test]
+    fn test_subscription_ends_with_its_client() {
+        let subscribed = |id: u64| SUBSCRIBERS.lock().unwrap().iter().any(|(i, _)| *i == id);
+
+        let first = subscribe();
+        let second = subscribe();
+        assert!(has_subscribers());
+        publish("test", "hello");
+        assert!(first.events.try_recv().unwrap().contains("\"hello\""));
+
+        // A client that disconnects is removed without waiting for the next publish
+        let id = first.id;
+        drop(first);
+        assert!(!subscribed(id));
+        assert!(subscribed(second.id));
+        drop(second);
+    }
+}
@@ -0,0 +1,274 @@
+//! The game's attributes.xml
+//!
+//! Next to actionmaps.xml SC keeps the user's game settings in attributes.xml, a flat
+//! list of `<Attr name="..." value="..."/>` (field of view, head tracking, mouse
+//! inversion and the like). A profile can carry some of these so applying it also sets
+//! the game up the way its bindings expect. Only the attributes the profile names are
+//! changed; everything else is written back as it was read.
+
+use crate::backups::{self, BackupLocation};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const ATTRIBUTES_FILE_NAME: &str = "attributes.xml";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GameAttribute {
+    pub name: String,
+    pub value: String,
+}
+
+/// An attribute an apply changed or added
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AttributeChange {
+    pub name: String,
+    /// None when the attribute wasn't in the file yet
+    pub old_value: Option<String>,
+    pub new_value: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AttributesApplyResult {
+    pub changes: Vec<AttributeChange>,
+    /// Backup of the file as it was, if it existed and anything changed
+    pub backup_path: Option<String>,
+}
+
+/// A parsed attributes.xml
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributesFile {
+    /// Attributes of the root element (SC writes `Version`), in file order
+    root: Vec<(String, String)>,
+    pub attributes: Vec<GameAttribute>,
+}
+
+/// attributes.xml sits in the same profile folder as actionmaps.xml
+pub fn path_for(actionmaps_path: &Path) -> PathBuf {
+    actionmaps_path.with_file_name(ATTRIBUTES_FILE_NAME)
+}
+
+fn attr(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == key)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+impl Default for AttributesFile {
+    fn default() -> Self {
+        AttributesFile {
+            root: vec![("Version".to_string(), "1".to_string())],
+            attributes: Vec::new(),
+        }
+    }
+}
+
+impl AttributesFile {
+    pub fn parse(xml: &str) -> Result<Self, String> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let mut buf = Vec::new();
+        let mut file = None;
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name().as_ref() {
+                    b"Attributes" => {
+                        let root = e
+                            .attributes()
+                            .flatten()
+                            .filter_map(|a| {
+                                let value = a.unescape_value().ok()?.into_owned();
+                                Some((String::from_utf8_lossy(a.key.as_ref()).into_owned(), value))
+                            })
+                            .collect();
+                        file = Some(AttributesFile {
+                            root,
+                            attributes: Vec::new(),
+                        });
+                    }
+                    b"Attr" => {
+                        if let (Some(file), Some(name)) = (file.as_mut(), attr(e, b"name")) {
+                            file.attributes.push(GameAttribute {
+                                name,
+                                value: attr(e, b"value").unwrap_or_default(),
+                            });
+                        }
+                    }
+                    _ => {}
+                },
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(format!("Failed to parse attributes.xml: {}", e)),
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        file.ok_or_else(|| "attributes.xml has no Attributes element".to_string())
+    }
+
+    /// Set attributes, adding the ones the file doesn't have yet at the end
+    pub fn set(&mut self, values: &BTreeMap<String, String>) -> Vec<AttributeChange> {
+        let mut changes = Vec::new();
+        for (name, value) in values {
+            match self.attributes.iter_mut().find(|a| &a.name == name) {
+                Some(existing) if &existing.value == value => {}
+                Some(existing) => {
+                    changes.push(AttributeChange {
+                        name: name.clone(),
+                        old_value: Some(std::mem::replace(&mut existing.value, value.clone())),
+                        new_value: value.clone(),
+                    });
+                }
+                None => {
+                    self.attributes.push(GameAttribute {
+                        name: name.clone(),
+                        value: value.clone(),
+                    });
+                    changes.push(AttributeChange {
+                        name: name.clone(),
+                        old_value: None,
+                        new_value: value.clone(),
+                    });
+                }
+            }
+        }
+        changes
+    }
+
+    /// The file in SC's own layout
+    pub fn to_xml(&self) -> String {
+        let root: String = self
+            .root
+            .iter()
+            .map(|(key, value)| format!(" {}=\"{}\"", key, escape(value.as_str())))
+            .collect();
+        let mut xml = format!("<Attributes{}>\n", root);
+        for attribute in &self.attributes {
+            xml.push_str(&format!(
+                " <Attr name=\"{}\" value=\"{}\"/>\n",
+                escape(attribute.name.as_str()),
+                escape(attribute.value.as_str())
+            ));
+        }
+        xml.push_str("</Attributes>\n");
+        xml
+    }
+}
+
+/// Read attributes.xml; a missing file reads as empty (the game hasn't written one yet)
+pub fn load(path: &Path) -> Result<AttributesFile, String> {
+    if !path.exists() {
+        return Ok(AttributesFile::default());
+    }
+    let xml = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read attributes.xml: {}", e))?;
+    AttributesFile::parse(&xml)
+}
+
+/// Write next to the target and rename over it, so the game never sees half a file
+fn write_atomic(path: &Path, content: &str) -> Result<(), String> {
+    let temp = path.with_extension("xml.tmp");
+    std::fs::write(&temp, content)
+        .map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    std::fs::rename(&temp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+/// Set `values` in the attributes.xml at `path`, backing the file up first. Nothing is
+/// written when every value is already set.
+pub fn apply(
+    path: &Path,
+    values: &BTreeMap<String, String>,
+    backup_location: &BackupLocation,
+) -> Result<AttributesApplyResult, String> {
+    let mut file = load(path)?;
+    let changes = file.set(values);
+    if changes.is_empty() {
+        return Ok(AttributesApplyResult {
+            changes,
+            backup_path: None,
+        });
+    }
+
+    let backup_path = if path.exists() {
+        Some(backups::create_backup(
+            backup_location,
+            &path.to_string_lossy(),
+        )?)
+    } else {
+        None
+    };
+    write_atomic(path, &file.to_xml())?;
+    tracing::info!(
+        path = %path.display(),
+        changed = changes.len(),
+        "Applied game attributes"
+    );
+    Ok(AttributesApplyResult {
+        changes,
+        backup_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_keeps_other_attributes() {
+        let xml = r#"<Attributes Version="1">
+ <Attr name="FOV" value="90"/>
+ <Attr name="HeadtrackingSource" value="0"/>
+ <Attr name="Nickname" value="A &amp; B"/>
+</Attributes>
+"#;
+        let get = |file: &AttributesFile, name: &str| {
+            file.attributes
+                .iter()
+                .find(|a| a.name == name)
+                .map(|a| a.value.clone())
+        };
+        let mut file = AttributesFile::parse(xml).unwrap();
+        assert_eq!(get(&file, "FOV").as_deref(), Some("90"));
+        assert_eq!(file.to_xml(), xml);
+
+        let changes = file.set(&BTreeMap::from([
+            ("FOV".to_string(), "100".to_string()),
+            ("HeadtrackingSource".to_string(), "0".to_string()),
+            ("InvertMouse".to_string(), "1".to_string()),
+        ]));
+        assert_eq!(
+            changes,
+            vec![
+                AttributeChange {
+                    name: "FOV".to_string(),
+                    old_value: Some("90".to_string()),
+                    new_value: "100".to_string(),
+                },
+                AttributeChange {
+                    name: "InvertMouse".to_string(),
+                    old_value: None,
+                    new_value: "1".to_string(),
+                },
+            ]
+        );
+
+        let reparsed = AttributesFile::parse(&file.to_xml()).unwrap();
+        assert_eq!(reparsed, file);
+        assert_eq!(get(&reparsed, "Nickname").as_deref(), Some("A & B"));
+        assert_eq!(reparsed.attributes.last().unwrap().name, "InvertMouse");
+
+        assert!(AttributesFile::parse("<ActionMaps/>").is_err());
+        assert_eq!(
+            path_for(Path::new(
+                "/sc/LIVE/user/client/0/Profiles/default/actionmaps.xml"
+            )),
+            Path::new("/sc/LIVE/user/client/0/Profiles/default/attributes.xml")
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Is Star Citizen running?
+//!
+//! The game reads actionmaps.xml at startup and writes its own copy back when it exits,
+//! so an apply while it runs is ignored until a restart at best and overwritten at worst.
+//! Applies check for a StarCitizen.exe belonging to the target environment first. Each
+//! environment runs its own executable (LIVE\Bin64\StarCitizen.exe, PTU\Bin64\...), so a
+//! PTU session doesn't block applying to LIVE.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The game client's executable name
+#[cfg(windows)]
+const GAME_EXE: &str = "StarCitizen.exe";
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct GameProcess {
+    pub pid: u32,
+    /// Full path of the executable, None when Windows won't tell us (e.g. access denied)
+    pub exe_path: Option<String>,
+}
+
+/// Every running StarCitizen.exe
+#[cfg(windows)]
+pub fn running_game_processes() -> Vec<GameProcess> {
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    };
+    use windows::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    let mut processes = Vec::new();
+    let snapshot = match unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) } {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            log::warn!("Could not list processes: {}", e);
+            return processes;
+        }
+    };
+
+    let mut entry = PROCESSENTRY32W {
+        dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+        ..Default::default()
+    };
+    let mut more = unsafe { Process32FirstW(snapshot, &mut entry) }.is_ok();
+    while more {
+        let len = entry
+            .szExeFile
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(entry.szExeFile.len());
+        let name = String::from_utf16_lossy(&entry.szExeFile[..len]);
+        if name.eq_ignore_ascii_case(GAME_EXE) {
+            let pid = entry.th32ProcessID;
+            let exe_path = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }
+                .ok()
+                .and_then(|process| {
+                    let mut buffer = [0u16; 1024];
+                    let mut size = buffer.len() as u32;
+                    let result = unsafe {
+                        QueryFullProcessImageNameW(
+                            process,
+                            PROCESS_NAME_WIN32,
+                            PWSTR(buffer.as_mut_ptr()),
+                            &mut size,
+                        )
+                    };
+                    let _ = unsafe { CloseHandle(process) };
+                    result
+                        .ok()
+                        .map(|_| String::from_utf16_lossy(&buffer[..size as usize]))
+                });
+            processes.push(GameProcess { pid, exe_path });
+        }
+        more = unsafe { Process32NextW(snapshot, &mut entry) }.is_ok();
+    }
+    let _ = unsafe { CloseHandle(snapshot) };
+    processes
+}
+
+#[cfg(not(windows))]
+pub fn running_game_processes() -> Vec<GameProcess> {
+    Vec::new()
+}
+
+/// Where actionmaps.xml sits inside an installation folder, as normalized by `normalize`
+const ACTIONMAPS_SUFFIX: &str = "/user/client/0/profiles/default/actionmaps.xml";
+
+/// The installation folder an actionmaps.xml belongs to, when it sits in the usual place
+/// (`<installation>\user\client\0\Profiles\default\actionmaps.xml`)
+pub fn installation_of(actionmaps_path: &Path) -> Option<PathBuf> {
+    let path = actionmaps_path.to_string_lossy();
+    normalize(actionmaps_path)
+        .ends_with(ACTIONMAPS_SUFFIX)
+        .then(|| PathBuf::from(&path[..path.len() - ACTIONMAPS_SUFFIX.len()]))
+}
+
+/// Windows paths compare case-insensitively and with either separator. Keeps the byte
+/// length, so positions in the result match the original.
+fn normalize(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .to_ascii_lowercase()
+}
+
+/// The game process running from `installation`. A process whose path is unknown, or any
+/// process when the installation is unknown, counts: better a needless warning than a
+/// lost apply.
+pub fn running_in(processes: &[GameProcess], installation: Option<&Path>) -> Option<GameProcess> {
+    let installation = installation.map(|path| {
+        let mut prefix = normalize(path);
+        if !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        prefix
+    });
+    processes
+        .iter()
+        .find(|process| match (&installation, &process.exe_path) {
+            (Some(prefix), Some(exe)) => normalize(Path::new(exe)).starts_with(prefix),
+            _ => true,
+        })
+        .cloned()
+}
+
+/// The game process that would interfere with writing `actionmaps_path`, if any
+pub fn running_for(actionmaps_path: &str) -> Option<GameProcess> {
+    let processes = running_game_processes();
+    if processes.is_empty() {
+        return None;
+    }
+    running_in(
+        &processes,
+        installation_of(Path::new(actionmaps_path)).as_deref(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_process_matched_to_environment() {
+        let actionmaps = r"C:\Games\StarCitizen\PTU\user\client\0\Profiles\default\actionmaps.xml";
+        let installation = installation_of(Path::new(actionmaps)).unwrap();
+        assert_eq!(installation, PathBuf::from(r"C:\Games\StarCitizen\PTU"));
+        assert_eq!(installation_of(Path::new(r"C:\Temp\actionmaps.xml")), None);
+
+        let live = GameProcess {
+            pid: 1,
+            exe_path: Some(r"C:\Games\StarCitizen\LIVE\Bin64\StarCitizen.exe".to_string()),
+        };
+        let ptu = GameProcess {
+            pid: 2,
+            exe_path: Some(r"c:\games\starcitizen\ptu\Bin64\StarCitizen.exe".to_string()),
+        };
+        let unknown = GameProcess {
+            pid: 3,
+            exe_path: None,
+        };
+
+        assert_eq!(running_in(&[live.clone()], Some(&installation)), None);
+        assert_eq!(
+            running_in(&[live.clone(), ptu.clone()], Some(&installation)),
+            Some(ptu)
+        );
+        assert_eq!(
+            running_in(&[live.clone(), unknown.clone()], Some(&installation)),
+            Some(unknown)
+        );
+        // Somewhere we can't place: any running game counts
+        assert_eq!(running_in(&[live.clone()], None), Some(live));
+    }
+}
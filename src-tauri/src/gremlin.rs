@@ -0,0 +1,420 @@
+//! Joystick Gremlin profile import
+//!
+//! Gremlin profiles remap physical device inputs onto vJoy devices, optionally shaping
+//! axes with a response curve. Star Citizen is then bound to the vJoy inputs. When a
+//! user migrates away from Gremlin we can:
+//! - turn the response curves into .sccontrols curves on the SC option the vJoy axis drives
+//! - rewrite SC rebinds from the vJoy input to the physical input it came from
+//!
+//! Both need to know which SC joystick instance the vJoy and physical devices appear as,
+//! which the user supplies.
+
+use crate::controls::{
+    ControlOptionSettings, ControlsFile, CurveData, CurveInterpolation, CurvePoint,
+    LoadControlsOutput,
+};
+use crate::keybindings::{ActionMaps, AllBinds};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// SC joystick axis names in DirectInput/vJoy axis order (Gremlin axis ids are 1-based)
+const SC_AXES: [&str; 8] = ["x", "y", "z", "rotx", "roty", "rotz", "slider1", "slider2"];
+
+/// The option each action's curve lives on, from the actions' optionGroup in AllBinds.xml
+pub fn action_options(all_binds: &AllBinds) -> HashMap<String, String> {
+    all_binds
+        .action_maps
+        .iter()
+        .flat_map(|am| am.actions.iter())
+        .filter(|a| !a.option_group.is_empty())
+        .map(|a| (a.name.clone(), a.option_group.clone()))
+        .collect()
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GremlinInputType {
+    Axis,
+    Button,
+    Hat,
+}
+
+/// A physical input remapped onto a vJoy input by the Gremlin profile
+#[derive(Debug, Serialize, Clone)]
+pub struct GremlinRemap {
+    pub device_name: String,
+    pub device_guid: String,
+    pub mode: String,
+    pub input_type: GremlinInputType,
+    pub input_id: u32,
+    pub vjoy_id: u32,
+    pub vjoy_input_id: u32,
+    /// Response curve control points in Gremlin's -1..1 space
+    pub curve: Vec<(f64, f64)>,
+}
+
+/// Result of importing a Gremlin profile
+#[derive(Debug, Serialize)]
+pub struct GremlinImport {
+    pub remaps: Vec<GremlinRemap>,
+    /// Curves carried over as a .sccontrols profile
+    pub controls: LoadControlsOutput,
+    /// SC rebinds rewritten from vJoy inputs to physical inputs
+    pub rebinds_rewritten: usize,
+    /// Remaps we couldn't carry over, with the reason
+    pub skipped: Vec<String>,
+}
+
+fn attr(e: &quick_xml::events::BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name)
+        .map(|a| String::from_utf8_lossy(&a.value).into_owned())
+}
+
+/// Parse the remaps out of a Joystick Gremlin .xml profile
+pub fn parse_gremlin_profile(xml: &str) -> Result<Vec<GremlinRemap>, String> {
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut remaps = Vec::new();
+
+    let mut device_name = String::new();
+    let mut device_guid = String::new();
+    let mut mode = String::new();
+    let mut input: Option<(GremlinInputType, u32)> = None;
+    let mut curve: Vec<(f64, f64)> = Vec::new();
+    let mut in_response_curve = false;
+    let mut seen_profile = false;
+
+    loop {
+        let (e, is_start) = match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => (e.into_owned(), true),
+            Ok(Event::Empty(e)) => (e.into_owned(), false),
+            Ok(Event::End(e)) => {
+                match e.name().as_ref() {
+                    b"axis" | b"button" | b"hat" => {
+                        input = None;
+                        curve.clear();
+                    }
+                    b"response-curve" => in_response_curve = false,
+                    _ => {}
+                }
+                buf.clear();
+                continue;
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("XML parse error: {}", e)),
+            _ => {
+                buf.clear();
+                continue;
+            }
+        };
+
+        match e.name().as_ref() {
+            b"profile" => seen_profile = true,
+            b"device" => {
+                device_name = attr(&e, b"name").unwrap_or_default();
+                device_guid = attr(&e, b"device-guid").unwrap_or_default();
+            }
+            b"mode" => mode = attr(&e, b"name").unwrap_or_default(),
+            name @ (b"axis" | b"button" | b"hat") if is_start && input.is_none() => {
+                let input_type = match name {
+                    b"axis" => GremlinInputType::Axis,
+                    b"button" => GremlinInputType::Button,
+                    _ => GremlinInputType::Hat,
+                };
+                input = attr(&e, b"id")
+                    .and_then(|id| id.parse().ok())
+                    .map(|id| (input_type, id));
+            }
+            b"response-curve" if is_start => in_response_curve = true,
+            b"control-point" if in_response_curve => {
+                let x = attr(&e, b"x").and_then(|v| v.parse().ok());
+                let y = attr(&e, b"y").and_then(|v| v.parse().ok());
+                if let (Some(x), Some(y)) = (x, y) {
+                    curve.push((x, y));
+                }
+            }
+            b"remap" => {
+                let Some((input_type, input_id)) = input else {
+                    buf.clear();
+                    continue;
+                };
+                let target = match input_type {
+                    GremlinInputType::Axis => attr(&e, b"axis"),
+                    GremlinInputType::Button => attr(&e, b"button"),
+                    GremlinInputType::Hat => attr(&e, b"hat"),
+                };
+                let vjoy_id = attr(&e, b"vjoy").and_then(|v| v.parse().ok());
+                if let (Some(vjoy_id), Some(vjoy_input_id)) =
+                    (vjoy_id, target.and_then(|t| t.parse().ok()))
+                {
+                    remaps.push(GremlinRemap {
+                        device_name: device_name.clone(),
+                        device_guid: device_guid.clone(),
+                        mode: mode.clone(),
+                        input_type,
+                        input_id,
+                        vjoy_id,
+                        vjoy_input_id,
+                        curve: curve.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if !seen_profile {
+        return Err("Not a Joystick Gremlin profile (no <profile> element)".to_string());
+    }
+
+    Ok(remaps)
+}
+
+/// SC input name (without the "jsN_" prefix) for a Gremlin/vJoy input
+fn sc_input_name(input_type: GremlinInputType, id: u32) -> Option<String> {
+    match input_type {
+        GremlinInputType::Axis => SC_AXES
+            .get(id.checked_sub(1)? as usize)
+            .map(|a| a.to_string()),
+        GremlinInputType::Button => Some(format!("button{}", id)),
+        GremlinInputType::Hat => Some(format!("hat{}_", id)),
+    }
+}
+
+/// Convert a Gremlin -1..1 response curve into SC's 0..1 curve, using the positive half.
+/// Returns the curve points and whether the curve inverts the axis.
+fn convert_curve(points: &[(f64, f64)]) -> Option<(Vec<CurvePoint>, bool)> {
+    let mut positive: Vec<&(f64, f64)> = points.iter().filter(|(x, _)| *x >= 0.0).collect();
+    positive.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let &&(_, end_y) = positive.last()?;
+    let inverted = end_y < 0.0;
+
+    let curve: Vec<CurvePoint> = positive
+        .iter()
+        .map(|(x, y)| CurvePoint {
+            input: x.clamp(0.0, 1.0),
+            output: y.abs().clamp(0.0, 1.0),
+        })
+        .collect();
+
+    // A straight line from (0,0) to (1,1) isn't worth writing as a curve
+    let linear = curve.iter().all(|p| (p.input - p.output).abs() < 1e-6);
+    if curve.len() < 2 || (linear && !inverted) {
+        return None;
+    }
+    Some((curve, inverted))
+}
+
+/// Map Gremlin remaps onto the SC model.
+///
+/// `action_options` maps SC actions to the option their curve lives on (see
+/// [`action_options`]). `vjoy_instances` maps vJoy device ids to the SC joystick
+/// instance they appear as.
+/// `device_instances` maps Gremlin device GUIDs to the SC joystick instance the physical
+/// device will have once Gremlin is out of the picture. Rebinds are only rewritten for
+/// devices present in both.
+pub fn import_remaps(
+    remaps: Vec<GremlinRemap>,
+    bindings: Option<&mut ActionMaps>,
+    action_options: &HashMap<String, String>,
+    vjoy_instances: &HashMap<u32, u32>,
+    device_instances: &HashMap<String, u32>,
+    profile_name: String,
+) -> GremlinImport {
+    let mut controls = ControlsFile::new(profile_name);
+    let mut skipped = Vec::new();
+    let mut rewrites: Vec<(String, String)> = Vec::new();
+
+    for remap in &remaps {
+        let label = format!(
+            "{} {:?} {} -> vJoy {} {}",
+            remap.device_name, remap.input_type, remap.input_id, remap.vjoy_id, remap.vjoy_input_id
+        );
+        let (Some(vjoy_name), Some(physical_name)) = (
+            sc_input_name(remap.input_type, remap.vjoy_input_id),
+            sc_input_name(remap.input_type, remap.input_id),
+        ) else {
+            skipped.push(format!("{}: unsupported input id", label));
+            continue;
+        };
+        let Some(&vjoy_instance) = vjoy_instances.get(&remap.vjoy_id) else {
+            skipped.push(format!(
+                "{}: no SC instance for vJoy {}",
+                label, remap.vjoy_id
+            ));
+            continue;
+        };
+        let physical_instance = device_instances.get(&remap.device_guid).copied();
+
+        let vjoy_input = format!("js{}_{}", vjoy_instance, vjoy_name);
+        if let Some(instance) = physical_instance {
+            rewrites.push((
+                vjoy_input.clone(),
+                format!("js{}_{}", instance, physical_name),
+            ));
+        }
+
+        if remap.input_type != GremlinInputType::Axis {
+            continue;
+        }
+        let Some((points, inverted)) = convert_curve(&remap.curve) else {
+            continue;
+        };
+
+        // The curve belongs on whichever SC option the vJoy axis drives
+        let options: Vec<&str> = bindings
+            .as_deref()
+            .map(|b| {
+                b.action_maps
+                    .iter()
+                    .flat_map(|am| am.actions.iter())
+                    .filter(|a| a.rebinds.iter().any(|r| r.input.trim() == vjoy_input))
+                    .filter_map(|a| action_options.get(&a.name).map(String::as_str))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if options.is_empty() {
+            skipped.push(format!("{}: curve has no matching SC option", label));
+            continue;
+        }
+
+        let instance = physical_instance.unwrap_or(vjoy_instance).to_string();
+        let device = controls
+            .devices
+            .joystick
+            .get_or_insert_with(HashMap::new)
+            .entry(instance)
+            .or_default();
+        if device.product.is_none() && physical_instance.is_some() {
+            device.product = Some(remap.device_name.clone());
+        }
+        for option in options {
+            device.options.insert(
+                option.to_string(),
+                ControlOptionSettings {
+                    invert: inverted.then_some(true),
+                    curve_mode: Some("curve".to_string()),
+                    curve: Some(CurveData {
+                        points: points.clone(),
+                        interpolation: CurveInterpolation::Linear,
+                    }),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    let mut rebinds_rewritten = 0;
+    if let Some(bindings) = bindings {
+        for rebind in bindings
+            .action_maps
+            .iter_mut()
+            .flat_map(|am| am.actions.iter_mut())
+            .flat_map(|a| a.rebinds.iter_mut())
+        {
+            let input = rebind.input.trim().to_string();
+            // Hats rewrite by prefix ("js2_hat1_" matches "js2_hat1_up")
+            if let Some((from, to)) = rewrites.iter().find(|(from, _)| {
+                input == *from || (from.ends_with('_') && input.starts_with(from.as_str()))
+            }) {
+                rebind.input = format!("{}{}", to, &input[from.len()..]);
+                rebinds_rewritten += 1;
+            }
+        }
+    }
+
+    GremlinImport {
+        remaps,
+        controls: controls.into(),
+        rebinds_rewritten,
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curve_follows_the_actions_option_group() {
+        let gremlin = r#"<profile version="13">
+ <devices>
+  <device name="VKB Gladiator NXT" device-guid="{A1}">
+   <mode name="Default">
+    <axis id="2">
+     <action-set>
+      <response-curve>
+       <mapping type="cubic-spline">
+        <control-point x="-1.0" y="-1.0"/>
+        <control-point x="0.0" y="0.0"/>
+        <control-point x="0.5" y="0.25"/>
+        <control-point x="1.0" y="1.0"/>
+       </mapping>
+      </response-curve>
+      <remap axis="2" vjoy="1"/>
+     </action-set>
+    </axis>
+   </mode>
+  </device>
+ </devices>
+</profile>"#;
+        let all_binds = AllBinds::from_xml(
+            r#"<profile version="1">
+ <actionmap name="spaceship_movement" version="3">
+  <action name="v_pitch" UILabel="@ui_CIPitch" optionGroup="flight_move_pitch"/>
+  <action name="v_toggle_cruise_control"/>
+ </actionmap>
+</profile>"#,
+        )
+        .unwrap();
+        let mut bindings = ActionMaps::from_xml(
+            r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <actionmap name="spaceship_movement">
+   <action name="v_pitch">
+    <rebind input="js2_y"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>"#,
+        )
+        .unwrap();
+
+        let action_options = action_options(&all_binds);
+        assert_eq!(action_options.len(), 1);
+        assert_eq!(action_options["v_pitch"], "flight_move_pitch");
+
+        let remaps = parse_gremlin_profile(gremlin).unwrap();
+        assert_eq!(remaps.len(), 1);
+        let import = import_remaps(
+            remaps,
+            Some(&mut bindings),
+            &action_options,
+            &HashMap::from([(1, 2)]),
+            &HashMap::from([("{A1}".to_string(), 1)]),
+            "Gremlin".to_string(),
+        );
+
+        assert!(import.skipped.is_empty(), "{:?}", import.skipped);
+        assert_eq!(import.rebinds_rewritten, 1);
+        assert_eq!(bindings.action_maps[0].actions[0].rebinds[0].input, "js1_y");
+        let joystick = import.controls.devices.joystick.unwrap();
+        let pitch = &joystick["1"]["flight_move_pitch"];
+        let points: Vec<(f64, f64)> = pitch
+            .curve
+            .as_ref()
+            .unwrap()
+            .points
+            .iter()
+            .map(|p| (p.input, p.output))
+            .collect();
+        assert_eq!(points, vec![(0.0, 0.0), (0.5, 0.25), (1.0, 1.0)]);
+    }
+}
@@ -46,6 +46,9 @@ pub struct HidDeviceListItem {
     pub product: Option<String>,
     pub path: String,
     pub interface_number: i32,
+    /// HID usage page and usage of the top-level collection (0x01 and 0x04 for a joystick)
+    pub usage_page: u16,
+    pub usage: u16,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -78,12 +81,12 @@ pub fn list_hid_game_controllers() -> Result<Vec<HidDeviceListItem>, String> {
         .filter(|info| {
             // Filter for devices that are likely game controllers
             // HID Usage Page 0x01 = Generic Desktop Controls
-            // HID Usage 0x04 = Joystick, 0x05 = Gamepad
+            // HID Usage 0x04 = Joystick, 0x05 = Gamepad, 0x08 = Multi-axis Controller
             let usage_page = info.usage_page();
             let usage = info.usage();
 
-            // Check if it's a joystick (0x04) or gamepad (0x05)
-            usage_page == 0x01 && (usage == 0x04 || usage == 0x05)
+            // Pedals and some throttles report as multi-axis controllers
+            usage_page == 0x01 && (usage == 0x04 || usage == 0x05 || usage == 0x08)
         })
         .map(|info| HidDeviceListItem {
             vendor_id: info.vendor_id(),
@@ -93,6 +96,8 @@ pub fn list_hid_game_controllers() -> Result<Vec<HidDeviceListItem>, String> {
             product: info.product_string().map(|s| s.to_string()),
             path: info.path().to_string_lossy().to_string(),
             interface_number: info.interface_number(),
+            usage_page: info.usage_page(),
+            usage: info.usage(),
         })
         .collect();
 
@@ -0,0 +1,623 @@
+//! Built-in layouts for popular HOTAS hardware
+//!
+//! Starting a profile for a new stick from the raw defaults means first working out which
+//! button number is the pinky lever. Each template here names the physical controls of a
+//! known device (as its default firmware reports them) and binds the ones with an obvious
+//! job in SC: flight axes, fire groups, missiles, countermeasures, decoupling, gear and
+//! quantum. A new profile is made from a template by finding its devices among the
+//! connected joysticks, so the bindings land on the instances SC will actually use.
+
+use crate::controls::ControlsFile;
+use crate::device_roles::{self, DeviceRole};
+use crate::keybindings::{Action, ActionMap, ActionMaps, DeviceInfo, Rebind};
+use crate::product_names;
+use serde::Serialize;
+
+/// A physical control and what it does in a new profile
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct TemplateControl {
+    /// SC input without the device prefix, e.g. "button3", "rotz", "hat1_up"
+    pub input: &'static str,
+    /// What it is on the device, e.g. "Pinky lever"
+    pub name: &'static str,
+    /// Actionmap and action it's bound to, if any
+    pub binding: Option<(&'static str, &'static str)>,
+}
+
+/// One USB device of a template
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct TemplateDevice {
+    pub label: &'static str,
+    pub role: DeviceRole,
+    /// "vid:pid" of the default firmware, when fixed
+    pub uuid: Option<&'static str>,
+    /// Fragments of the normalized product name (see product_names), for devices whose
+    /// ids vary
+    pub names: &'static [&'static str],
+    pub controls: &'static [TemplateControl],
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct HotasTemplate {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub devices: &'static [TemplateDevice],
+}
+
+const fn bound(
+    input: &'static str,
+    name: &'static str,
+    action_map: &'static str,
+    action: &'static str,
+) -> TemplateControl {
+    TemplateControl {
+        input,
+        name,
+        binding: Some((action_map, action)),
+    }
+}
+
+const fn named(input: &'static str, name: &'static str) -> TemplateControl {
+    TemplateControl {
+        input,
+        name,
+        binding: None,
+    }
+}
+
+const MOVEMENT: &str = "spaceship_movement";
+const WEAPONS: &str = "spaceship_weapons";
+const MISSILES: &str = "spaceship_missiles";
+const DEFENSIVE: &str = "spaceship_defensive";
+const SEAT: &str = "seat_general";
+
+pub const TEMPLATES: &[HotasTemplate] = &[
+    HotasTemplate {
+        id: "vkb_gladiator_nxt",
+        name: "VKB Gladiator NXT EVO",
+        devices: &[TemplateDevice {
+            label: "Gladiator NXT EVO",
+            role: DeviceRole::Stick,
+            uuid: Some("231d:0200"),
+            names: &["gladiator"],
+            controls: &[
+                bound("x", "Stick left/right", MOVEMENT, "v_roll"),
+                bound("y", "Stick forward/back", MOVEMENT, "v_pitch"),
+                bound("rotz", "Twist", MOVEMENT, "v_yaw"),
+                bound(
+                    "button1",
+                    "Trigger, first stage",
+                    WEAPONS,
+                    "v_attack_group1",
+                ),
+                bound(
+                    "button2",
+                    "Trigger, second stage",
+                    WEAPONS,
+                    "v_attack_group2",
+                ),
+                bound(
+                    "button3",
+                    "Red button (A2)",
+                    MISSILES,
+                    "v_weapon_launch_missile",
+                ),
+                bound(
+                    "button4",
+                    "Pinky lever",
+                    MOVEMENT,
+                    "v_ifcs_vector_decoupling_toggle",
+                ),
+                bound(
+                    "button5",
+                    "Thumb button (D1)",
+                    DEFENSIVE,
+                    "v_weapon_countermeasure_decoy_launch",
+                ),
+                named("button6", "Index button (A1)"),
+                named("button7", "Index hat (A4) up"),
+                named("button8", "Index hat (A4) right"),
+                named("button9", "Index hat (A4) down"),
+                named("button10", "Index hat (A4) left"),
+                named("button11", "Index hat (A4) press"),
+            ],
+        }],
+    },
+    HotasTemplate {
+        id: "virpil_constellation_alpha",
+        name: "Virpil Constellation Alpha",
+        devices: &[TemplateDevice {
+            label: "Constellation Alpha",
+            role: DeviceRole::Stick,
+            // Virpil ids are set per device in VPC Configurator
+            uuid: None,
+            names: &["constellation alpha"],
+            controls: &[
+                bound("x", "Stick left/right", MOVEMENT, "v_roll"),
+                bound("y", "Stick forward/back", MOVEMENT, "v_pitch"),
+                bound("rotz", "Twist (WarBRD-D base)", MOVEMENT, "v_yaw"),
+                bound(
+                    "button1",
+                    "Trigger, first stage",
+                    WEAPONS,
+                    "v_attack_group1",
+                ),
+                bound(
+                    "button2",
+                    "Trigger, second stage",
+                    WEAPONS,
+                    "v_attack_group2",
+                ),
+                bound("button3", "Red button", MISSILES, "v_weapon_launch_missile"),
+                bound(
+                    "button4",
+                    "Pinky button",
+                    MOVEMENT,
+                    "v_ifcs_vector_decoupling_toggle",
+                ),
+                bound(
+                    "button5",
+                    "Flip trigger",
+                    DEFENSIVE,
+                    "v_weapon_countermeasure_decoy_launch",
+                ),
+                named("button6", "Brake lever"),
+                named("hat1_up", "Index hat up"),
+                named("hat1_right", "Index hat right"),
+                named("hat1_down", "Index hat down"),
+                named("hat1_left", "Index hat left"),
+            ],
+        }],
+    },
+    HotasTemplate {
+        id: "saitek_x52",
+        name: "Saitek/Logitech X52",
+        devices: &[TemplateDevice {
+            label: "X52 (stick and throttle)",
+            role: DeviceRole::Stick,
+            uuid: Some("06a3:0255"),
+            names: &["x52"],
+            controls: &[
+                bound("x", "Stick left/right", MOVEMENT, "v_roll"),
+                bound("y", "Stick forward/back", MOVEMENT, "v_pitch"),
+                bound("rotz", "Stick twist", MOVEMENT, "v_yaw"),
+                bound("z", "Throttle", MOVEMENT, "v_strafe_longitudinal"),
+                named("rotx", "Throttle rotary"),
+                named("slider1", "Throttle slider"),
+                bound(
+                    "button1",
+                    "Trigger, first stage",
+                    WEAPONS,
+                    "v_attack_group1",
+                ),
+                bound(
+                    "button15",
+                    "Trigger, second stage",
+                    WEAPONS,
+                    "v_attack_group2",
+                ),
+                bound(
+                    "button2",
+                    "Fire button",
+                    MISSILES,
+                    "v_weapon_launch_missile",
+                ),
+                bound(
+                    "button3",
+                    "Button A",
+                    DEFENSIVE,
+                    "v_weapon_countermeasure_decoy_launch",
+                ),
+                named("button4", "Button B"),
+                bound(
+                    "button5",
+                    "Button C",
+                    MOVEMENT,
+                    "v_ifcs_vector_decoupling_toggle",
+                ),
+                named("button6", "Pinky switch"),
+                bound(
+                    "button7",
+                    "Throttle button D",
+                    SEAT,
+                    "v_toggle_quantum_mode",
+                ),
+                named("button8", "Throttle button E"),
+                bound(
+                    "button9",
+                    "Toggle T1 up",
+                    MOVEMENT,
+                    "v_toggle_landing_system",
+                ),
+                named("button10", "Toggle T1 down"),
+                named("button11", "Toggle T3 up"),
+                named("button12", "Toggle T3 down"),
+                named("button13", "Toggle T5 up"),
+                named("button14", "Toggle T5 down"),
+                named("hat1_up", "Stick POV up"),
+                named("hat1_right", "Stick POV right"),
+                named("hat1_down", "Stick POV down"),
+                named("hat1_left", "Stick POV left"),
+            ],
+        }],
+    },
+    HotasTemplate {
+        id: "thrustmaster_t16000m",
+        name: "Thrustmaster T.16000M",
+        devices: &[TemplateDevice {
+            label: "T.16000M",
+            role: DeviceRole::Stick,
+            uuid: Some("044f:b10a"),
+            names: &["t 16000m", "t16000m"],
+            controls: &[
+                bound("x", "Stick left/right", MOVEMENT, "v_roll"),
+                bound("y", "Stick forward/back", MOVEMENT, "v_pitch"),
+                bound("rotz", "Twist", MOVEMENT, "v_yaw"),
+                bound(
+                    "slider1",
+                    "Throttle wheel",
+                    MOVEMENT,
+                    "v_strafe_longitudinal",
+                ),
+                bound("button1", "Trigger", WEAPONS, "v_attack_group1"),
+                bound(
+                    "button2",
+                    "Thumb button, bottom",
+                    MISSILES,
+                    "v_weapon_launch_missile",
+                ),
+                bound("button3", "Thumb button, left", WEAPONS, "v_attack_group2"),
+                bound(
+                    "button4",
+                    "Thumb button, right",
+                    DEFENSIVE,
+                    "v_weapon_countermeasure_decoy_launch",
+                ),
+                bound(
+                    "button5",
+                    "Base, left side, top row 1",
+                    MOVEMENT,
+                    "v_ifcs_vector_decoupling_toggle",
+                ),
+                bound(
+                    "button6",
+                    "Base, left side, top row 2",
+                    MOVEMENT,
+                    "v_toggle_landing_system",
+                ),
+                bound(
+                    "button7",
+                    "Base, left side, top row 3",
+                    SEAT,
+                    "v_toggle_quantum_mode",
+                ),
+                named("button8", "Base, left side, bottom row 3"),
+                named("button9", "Base, left side, bottom row 2"),
+                named("button10", "Base, left side, bottom row 1"),
+                named("hat1_up", "Hat up"),
+                named("hat1_right", "Hat right"),
+                named("hat1_down", "Hat down"),
+                named("hat1_left", "Hat left"),
+            ],
+        }],
+    },
+    HotasTemplate {
+        id: "thrustmaster_warthog",
+        name: "Thrustmaster HOTAS Warthog",
+        devices: &[
+            TemplateDevice {
+                label: "Warthog stick",
+                role: DeviceRole::Stick,
+                uuid: Some("044f:0402"),
+                names: &["joystick hotas warthog"],
+                controls: &[
+                    bound("x", "Stick left/right", MOVEMENT, "v_roll"),
+                    bound("y", "Stick forward/back", MOVEMENT, "v_pitch"),
+                    bound(
+                        "button1",
+                        "Trigger, first stage (TG1)",
+                        WEAPONS,
+                        "v_attack_group1",
+                    ),
+                    bound(
+                        "button2",
+                        "Weapon release (S2)",
+                        MISSILES,
+                        "v_weapon_launch_missile",
+                    ),
+                    named("button3", "Nosewheel steering (S3)"),
+                    bound(
+                        "button4",
+                        "Pinky lever (S4)",
+                        MOVEMENT,
+                        "v_ifcs_vector_decoupling_toggle",
+                    ),
+                    bound("button5", "Pickle (S1)", WEAPONS, "v_attack_group2"),
+                    named("button6", "Trigger, second stage (TG2)"),
+                    named("button7", "TMS up (H2)"),
+                    named("button8", "TMS right (H2)"),
+                    named("button9", "TMS down (H2)"),
+                    named("button10", "TMS left (H2)"),
+                    named("button11", "DMS up (H3)"),
+                    named("button12", "DMS right (H3)"),
+                    named("button13", "DMS down (H3)"),
+                    named("button14", "DMS left (H3)"),
+                    bound(
+                        "button15",
+                        "CMS up (H4)",
+                        DEFENSIVE,
+                        "v_weapon_countermeasure_decoy_launch",
+                    ),
+                    named("button16", "CMS right (H4)"),
+                    named("button17", "CMS down (H4)"),
+                    named("button18", "CMS left (H4)"),
+                    named("button19", "CMS press (H4)"),
+                    named("hat1_up", "Trim up (H1)"),
+                    named("hat1_right", "Trim right (H1)"),
+                    named("hat1_down", "Trim down (H1)"),
+                    named("hat1_left", "Trim left (H1)"),
+                ],
+            },
+            TemplateDevice {
+                label: "Warthog throttle",
+                role: DeviceRole::Throttle,
+                uuid: Some("044f:0404"),
+                names: &["throttle hotas warthog"],
+                controls: &[
+                    bound("z", "Right throttle", MOVEMENT, "v_strafe_longitudinal"),
+                    named("rotz", "Left throttle"),
+                    bound("x", "Slew control left/right", MOVEMENT, "v_strafe_lateral"),
+                    bound("y", "Slew control up/down", MOVEMENT, "v_strafe_vertical"),
+                    named("slider1", "Friction control"),
+                    named("button1", "Slew control press"),
+                    named("button2", "MIC switch press"),
+                    named("button3", "MIC switch up"),
+                    named("button4", "MIC switch forward"),
+                    named("button5", "MIC switch down"),
+                    named("button6", "MIC switch aft"),
+                    named("button7", "Speedbrake forward"),
+                    named("button8", "Speedbrake aft"),
+                    named("button9", "Boat switch forward"),
+                    named("button10", "Boat switch aft"),
+                    named("button11", "China hat forward"),
+                    named("button12", "China hat aft"),
+                    bound(
+                        "button13",
+                        "Pinky switch forward",
+                        MOVEMENT,
+                        "v_toggle_landing_system",
+                    ),
+                    named("button14", "Pinky switch aft"),
+                    bound(
+                        "button15",
+                        "Left throttle button",
+                        SEAT,
+                        "v_toggle_quantum_mode",
+                    ),
+                    named("button16", "Engine fuel flow left"),
+                    named("button17", "Engine fuel flow right"),
+                ],
+            },
+        ],
+    },
+];
+
+pub fn find(id: &str) -> Option<&'static HotasTemplate> {
+    TEMPLATES.iter().find(|t| t.id == id)
+}
+
+/// A connected joystick, as SC numbers it
+#[derive(Debug, Clone)]
+pub struct DetectedJoystick {
+    pub instance: usize,
+    /// "vid:pid"
+    pub uuid: String,
+    pub product_name: String,
+}
+
+impl TemplateDevice {
+    fn matches(&self, joystick: &DetectedJoystick) -> bool {
+        if self
+            .uuid
+            .is_some_and(|uuid| uuid.eq_ignore_ascii_case(&joystick.uuid))
+        {
+            return true;
+        }
+        let name = product_names::normalize(&joystick.product_name);
+        self.names.iter().any(|fragment| name.contains(fragment))
+    }
+}
+
+/// Where a template device went in the new profile
+#[derive(Debug, Serialize, Clone)]
+pub struct TemplateAssignment {
+    pub label: String,
+    /// SC joystick instance (js1 = 1)
+    pub instance: usize,
+    /// The connected device it was found as; None when it isn't connected and took the
+    /// next free instance
+    pub product_name: Option<String>,
+}
+
+pub struct TemplateProfile {
+    pub bindings: ActionMaps,
+    pub controls: ControlsFile,
+    pub assignments: Vec<TemplateAssignment>,
+}
+
+/// Build a new profile from `template` for the connected `joysticks`
+pub fn instantiate(
+    template: &HotasTemplate,
+    profile_name: &str,
+    joysticks: &[DetectedJoystick],
+) -> TemplateProfile {
+    // Product strings by instance, for the options SC matches devices with
+    let mut products: Vec<String> = Vec::new();
+    let mut set_product = |instance: usize, product: String| {
+        if products.len() < instance {
+            products.resize(instance, String::new());
+        }
+        products[instance - 1] = product;
+    };
+    for joystick in joysticks {
+        set_product(
+            joystick.instance,
+            product_names::sc_product_string(&joystick.product_name, Some(&joystick.uuid)),
+        );
+    }
+
+    let mut taken = Vec::new();
+    let mut next_free = joysticks.iter().map(|j| j.instance).max().unwrap_or(0) + 1;
+    let mut assignments = Vec::new();
+    for device in template.devices {
+        let found = joysticks
+            .iter()
+            .find(|j| !taken.contains(&j.instance) && device.matches(j));
+        let assignment = match found {
+            Some(joystick) => TemplateAssignment {
+                label: device.label.to_string(),
+                instance: joystick.instance,
+                product_name: Some(joystick.product_name.clone()),
+            },
+            None => {
+                set_product(
+                    next_free,
+                    product_names::sc_product_string(device.label, device.uuid),
+                );
+                next_free += 1;
+                TemplateAssignment {
+                    label: device.label.to_string(),
+                    instance: next_free - 1,
+                    product_name: None,
+                }
+            }
+        };
+        taken.push(assignment.instance);
+        assignments.push(assignment);
+    }
+
+    let mut action_maps: Vec<ActionMap> = Vec::new();
+    let mut controls = ControlsFile::new(profile_name.to_string());
+    for (device, assignment) in template.devices.iter().zip(&assignments) {
+        if let Ok(settings) = controls.device_mut("joystick", &assignment.instance.to_string()) {
+            settings.role = Some(device.role);
+        }
+        for control in device.controls {
+            let Some((map_name, action_name)) = control.binding else {
+                continue;
+            };
+            let rebind = Rebind {
+                input: format!("js{}_{}", assignment.instance, control.input),
+                multi_tap: None,
+                activation_mode: String::new(),
+            };
+            let map = match action_maps.iter().position(|m| m.name == map_name) {
+                Some(index) => &mut action_maps[index],
+                None => {
+                    action_maps.push(ActionMaps::new_empty_action_map(
+                        map_name.to_string(),
+                        Vec::new(),
+                    ));
+                    action_maps.last_mut().unwrap()
+                }
+            };
+            match map.actions.iter_mut().find(|a| a.name == action_name) {
+                Some(action) => action.rebinds.push(rebind),
+                None => map.actions.push(Action {
+                    name: action_name.to_string(),
+                    rebinds: vec![rebind],
+                }),
+            }
+        }
+    }
+    device_roles::apply_role_defaults(&mut controls);
+
+    TemplateProfile {
+        bindings: ActionMaps {
+            profile_name: profile_name.to_string(),
+            action_maps,
+            categories: Vec::new(),
+            devices: DeviceInfo {
+                keyboards: Vec::new(),
+                mice: Vec::new(),
+                joysticks: products,
+                device_options: Vec::new(),
+            },
+        },
+        controls,
+        assignments,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::is_bundled_action;
+
+    #[test]
+    fn test_template_follows_detected_instances() {
+        let warthog = find("thrustmaster_warthog").unwrap();
+        // Throttle enumerated first, stick not connected
+        let joysticks = vec![
+            DetectedJoystick {
+                instance: 1,
+                uuid: "044f:0404".to_string(),
+                product_name: "Throttle - HOTAS Warthog".to_string(),
+            },
+            DetectedJoystick {
+                instance: 2,
+                uuid: "1234:bead".to_string(),
+                product_name: "vJoy Device".to_string(),
+            },
+        ];
+        let profile = instantiate(warthog, "Warthog", &joysticks);
+
+        assert_eq!(profile.assignments[0].instance, 3);
+        assert_eq!(profile.assignments[0].product_name, None);
+        assert_eq!(profile.assignments[1].instance, 1);
+        assert_eq!(profile.bindings.devices.joysticks.len(), 3);
+        assert!(profile.bindings.devices.joysticks[2].contains("{0402044F-"));
+
+        let inputs: Vec<&str> = profile
+            .bindings
+            .action_maps
+            .iter()
+            .flat_map(|m| &m.actions)
+            .flat_map(|a| &a.rebinds)
+            .map(|r| r.input.as_str())
+            .collect();
+        assert!(inputs.contains(&"js3_button1"));
+        assert!(inputs.contains(&"js1_z"));
+        assert_eq!(
+            profile.controls.device("joystick", "1").unwrap().role,
+            Some(DeviceRole::Throttle)
+        );
+
+        // Every template binds its flight axes
+        for template in TEMPLATES {
+            let actions: Vec<&str> = template
+                .devices
+                .iter()
+                .flat_map(|d| d.controls)
+                .filter_map(|c| c.binding.map(|(_, action)| action))
+                .collect();
+            assert!(
+                ["v_pitch", "v_roll"].iter().all(|a| actions.contains(a)),
+                "{}",
+                template.id
+            );
+        }
+    }
+
+    #[test]
+    fn test_templates_bind_known_actions() {
+        let unknown: Vec<(&str, &str, &str)> = TEMPLATES
+            .iter()
+            .flat_map(|t| t.devices.iter().map(move |d| (t.id, d)))
+            .flat_map(|(id, d)| d.controls.iter().map(move |c| (id, c)))
+            .filter_map(|(id, c)| c.binding.map(|(map, action)| (id, map, action)))
+            .filter(|(_, map, action)| !is_bundled_action(map, action))
+            .collect();
+        assert!(unknown.is_empty(), "not in AllBinds.xml: {:?}", unknown);
+    }
+}
@@ -0,0 +1,308 @@
+//! Check a profile against the installed game version before it's written
+//!
+//! SC skips option names, actions and inputs it doesn't know without a word, so a typo or
+//! an identifier from another patch only shows up as a control that does nothing in game.
+//! This lists everything the installed defaultProfile doesn't have: unknown option and
+//! action names, binding strings that don't parse, and joystick, gamepad or mouse inputs
+//! SC doesn't name that way. Keyboard keys depend on the layout and aren't checked.
+
+use crate::binding_string::BindingInput;
+use crate::controls::{ControlsFile, DeviceInstanceSettings};
+use crate::keybindings::{Action, ActionMaps, AllBinds, InputType};
+use crate::sc_migration::{IdentifierKind, KnownIdentifiers};
+use serde::Serialize;
+use std::collections::HashSet;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    UnknownOption,
+    UnknownAction,
+    InvalidBinding,
+    UnknownInput,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ValidationWarning {
+    pub kind: WarningKind,
+    /// Device of an option (e.g. "joystick 1"), actionmap and action of a binding
+    pub context: String,
+    pub identifier: String,
+    pub message: String,
+}
+
+/// Gamepad inputs SC names, besides those the defaultProfile binds
+const GAMEPAD_INPUTS: &[&str] = &[
+    "a",
+    "b",
+    "x",
+    "y",
+    "back",
+    "start",
+    "shoulderl",
+    "shoulderr",
+    "triggerl_btn",
+    "triggerr_btn",
+    "triggerl_axis",
+    "triggerr_axis",
+    "thumbl",
+    "thumbr",
+    "thumblx",
+    "thumbly",
+    "thumbrx",
+    "thumbry",
+    "thumbl_up",
+    "thumbl_down",
+    "thumbl_left",
+    "thumbl_right",
+    "thumbr_up",
+    "thumbr_down",
+    "thumbr_left",
+    "thumbr_right",
+    "dpad_up",
+    "dpad_down",
+    "dpad_left",
+    "dpad_right",
+];
+
+const JOYSTICK_AXES: &[&str] = &["x", "y", "z", "rotx", "roty", "rotz"];
+const DIRECTIONS: &[&str] = &["up", "down", "left", "right"];
+
+/// Gamepad and mouse inputs the installed defaultProfile binds
+#[derive(Debug, Clone, Default)]
+pub struct DefaultInputs {
+    gamepad: HashSet<String>,
+    mouse: HashSet<String>,
+}
+
+impl DefaultInputs {
+    pub fn new(all_binds: &AllBinds) -> Self {
+        let mut inputs = DefaultInputs::default();
+        let keys = |binding: &str| -> Vec<String> {
+            binding
+                .split('+')
+                .map(|key| key.trim().to_lowercase())
+                .filter(|key| !key.is_empty())
+                .collect()
+        };
+        for action in all_binds.action_maps.iter().flat_map(|m| m.actions.iter()) {
+            inputs.gamepad.extend(keys(&action.default_gamepad));
+            inputs.mouse.extend(keys(&action.default_mouse));
+        }
+        inputs
+    }
+}
+
+/// "button12" -> Some("12") for prefix "button"
+fn numbered<'a>(key: &'a str, prefix: &str) -> Option<&'a str> {
+    key.strip_prefix(prefix)
+        .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_known_input(device: &InputType, key: &str, defaults: &DefaultInputs) -> bool {
+    match device {
+        InputType::Joystick => {
+            JOYSTICK_AXES.contains(&key)
+                || numbered(key, "button").is_some()
+                || numbered(key, "slider").is_some()
+                || key.split_once('_').is_some_and(|(hat, direction)| {
+                    numbered(hat, "hat").is_some() && DIRECTIONS.contains(&direction)
+                })
+        }
+        InputType::Gamepad => GAMEPAD_INPUTS.contains(&key) || defaults.gamepad.contains(key),
+        InputType::Mouse => {
+            numbered(key, "mouse").is_some()
+                || key
+                    .strip_prefix("mwheel_")
+                    .is_some_and(|d| DIRECTIONS.contains(&d))
+                || matches!(key, "maxis_x" | "maxis_y" | "maxis_z")
+                || defaults.mouse.contains(key)
+        }
+        InputType::Keyboard | InputType::Unknown => true,
+    }
+}
+
+/// The device and keys of a binding string. Gamepad chords like `gp1_shoulderl+y` hold
+/// a button rather than a keyboard modifier, so their keys are checked one by one.
+fn binding_keys(input: &str, multi_tap: Option<u32>) -> Result<(InputType, Vec<String>), String> {
+    match BindingInput::parse(input, multi_tap) {
+        Ok(binding) if binding.is_cleared() => Ok((binding.device, Vec::new())),
+        Ok(binding) => Ok((binding.device, vec![binding.key])),
+        Err(e) => match input.trim().split_once('_') {
+            Some((prefix, keys)) if prefix.to_lowercase().starts_with("gp") => Ok((
+                InputType::Gamepad,
+                keys.split('+').map(|k| k.trim().to_lowercase()).collect(),
+            )),
+            _ => Err(e),
+        },
+    }
+}
+
+/// The action and binding strings of one action in `action_map` the installed game
+/// doesn't know
+pub fn check_action(
+    action_map: &str,
+    action: &Action,
+    known: &KnownIdentifiers,
+    defaults: &DefaultInputs,
+) -> Vec<ValidationWarning> {
+    let mut warnings = Vec::new();
+    let context = format!("{} / {}", action_map, action.name);
+    if !known.knows(IdentifierKind::Action, &action.name) {
+        warnings.push(ValidationWarning {
+            kind: WarningKind::UnknownAction,
+            context: action_map.to_string(),
+            identifier: action.name.clone(),
+            message: format!(
+                "The installed game has no action '{}'; its bindings will be ignored",
+                action.name
+            ),
+        });
+    }
+    for rebind in &action.rebinds {
+        match binding_keys(&rebind.input, rebind.multi_tap) {
+            Err(e) => warnings.push(ValidationWarning {
+                kind: WarningKind::InvalidBinding,
+                context: context.clone(),
+                identifier: rebind.input.clone(),
+                message: e,
+            }),
+            Ok((device, keys)) => {
+                for key in keys
+                    .iter()
+                    .filter(|k| !is_known_input(&device, k, defaults))
+                {
+                    warnings.push(ValidationWarning {
+                        kind: WarningKind::UnknownInput,
+                        context: context.clone(),
+                        identifier: rebind.input.clone(),
+                        message: format!(
+                            "'{}' is not an input name the game uses for this device",
+                            key
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// Actions and binding strings of `bindings` the installed game doesn't know
+pub fn check_bindings(
+    bindings: &ActionMaps,
+    known: &KnownIdentifiers,
+    defaults: &DefaultInputs,
+) -> Vec<ValidationWarning> {
+    bindings
+        .action_maps
+        .iter()
+        .flat_map(|action_map| {
+            action_map
+                .actions
+                .iter()
+                .flat_map(|action| check_action(&action_map.name, action, known, defaults))
+        })
+        .collect()
+}
+
+/// Option names of `controls` the installed game doesn't know
+pub fn check_controls(controls: &ControlsFile, known: &KnownIdentifiers) -> Vec<ValidationWarning> {
+    let devices = &controls.devices;
+    let mut entries: Vec<(String, &DeviceInstanceSettings)> = [
+        ("keyboard", devices.keyboard.as_ref()),
+        ("mouse", devices.mouse.as_ref()),
+        ("gamepad", devices.gamepad.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(device_type, device)| Some((format!("{} 1", device_type), device?)))
+    .collect();
+    let mut joysticks: Vec<_> = devices.joystick.iter().flatten().collect();
+    joysticks.sort_by(|a, b| a.0.cmp(b.0));
+    entries.extend(
+        joysticks
+            .into_iter()
+            .map(|(instance, device)| (format!("joystick {}", instance), device)),
+    );
+
+    let mut warnings = Vec::new();
+    for (context, device) in entries {
+        let mut names: Vec<&String> = device.options.keys().collect();
+        names.sort();
+        for name in names
+            .into_iter()
+            .filter(|n| !known.knows(IdentifierKind::Option, n))
+        {
+            warnings.push(ValidationWarning {
+                kind: WarningKind::UnknownOption,
+                context: context.clone(),
+                identifier: name.clone(),
+                message: format!(
+                    "The installed game has no option '{}'; its settings will be ignored",
+                    name
+                ),
+            });
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::ControlOptionSettings;
+    use crate::option_catalog::parse_option_catalog;
+
+    #[test]
+    fn test_unknown_identifiers_warned() {
+        let default_profile = r#"<profile version="1">
+ <optiontree type="joystick" instances="8" name="root">
+  <optiongroup name="flight_move_pitch" UILabel="@ui_CIPitch"/>
+ </optiontree>
+ <actionmap name="spaceship_general" version="2" UILabel="@ui_CGSpaceFlightCockpit">
+  <action name="v_eject" activationMode="press" keyboard="ralt+y" mouse="mouse4" gamepad="shoulderl+y" joystick=" "/>
+ </actionmap>
+</profile>"#;
+        let all_binds = AllBinds::from_xml(default_profile).unwrap();
+        let catalog = parse_option_catalog(default_profile).unwrap();
+        let known = KnownIdentifiers::new(&all_binds, &catalog);
+        let defaults = DefaultInputs::new(&all_binds);
+
+        let bindings = ActionMaps::from_xml(
+            r#"<ActionMaps>
+ <actionmap name="spaceship_general">
+  <action name="v_eject">
+   <rebind input="js1_button12"/>
+   <rebind input="gp1_shoulderl+y"/>
+   <rebind input="mo1_mouse4"/>
+   <rebind input="kb1_ "/>
+  </action>
+  <action name="v_ejcet">
+   <rebind input="js2_hat1_sideways"/>
+  </action>
+ </actionmap>
+</ActionMaps>"#,
+        )
+        .unwrap();
+        let kinds: Vec<WarningKind> = check_bindings(&bindings, &known, &defaults)
+            .iter()
+            .map(|w| w.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![WarningKind::UnknownAction, WarningKind::UnknownInput]
+        );
+
+        let mut controls = ControlsFile::new("Typo".to_string());
+        let stick = controls.device_mut("joystick", "1").unwrap();
+        for name in ["flight_move_pitch", "flight_move_pitchh"] {
+            stick
+                .options
+                .insert(name.to_string(), ControlOptionSettings::default());
+        }
+        let warnings = check_controls(&controls, &known);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].identifier, "flight_move_pitchh");
+        assert_eq!(warnings[0].context, "joystick 1");
+    }
+}
@@ -0,0 +1,375 @@
+//! Live input streaming
+//!
+//! Polls every connected controller and streams axis positions, pressed buttons and
+//! hat directions to the frontend as "input-state" events. Used by the input tester,
+//! the live curve preview and the event stream of the local API.
+
+use crate::event_stream;
+use crate::hid_reader;
+use log::info;
+use rusty_xinput::XInputHandle;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Default time between events (~60 updates per second)
+pub const DEFAULT_INTERVAL_MS: u64 = 16;
+
+/// HID usage ID of the hat switch on the Generic Desktop page
+const HID_HAT_SWITCH_USAGE: u32 = 0x39;
+
+/// XInput button masks, in the same order as the gpN_buttonM numbering used for detection
+const XINPUT_BUTTONS: [(u16, u32); 10] = [
+    (0x1000, 1),  // A
+    (0x2000, 2),  // B
+    (0x4000, 3),  // X
+    (0x8000, 4),  // Y
+    (0x0100, 5),  // LB
+    (0x0200, 6),  // RB
+    (0x0010, 7),  // Back
+    (0x0020, 8),  // Start
+    (0x0040, 9),  // LS
+    (0x0080, 10), // RS
+];
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AxisValue {
+    /// DirectInput axis index when known, otherwise the HID usage ID
+    pub axis_id: u32,
+    pub name: String,
+    /// Normalized position from -1.0 to 1.0
+    pub value: f32,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct HatValue {
+    pub hat_id: u32,
+    /// "centered", "up", "up_right", "right", ... (matches SC's hat naming)
+    pub direction: String,
+}
+
+/// Current state of one device
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DeviceInputState {
+    pub device_uuid: String,
+    pub device_name: String,
+    pub device_type: String,
+    pub instance: usize,
+    pub axes: Vec<AxisValue>,
+    pub pressed_buttons: Vec<u32>,
+    pub hats: Vec<HatValue>,
+}
+
+/// Payload of the "input-state" event
+#[derive(Debug, Serialize, Clone)]
+pub struct InputStateEvent {
+    pub devices: Vec<DeviceInputState>,
+}
+
+/// Convert an 8-way hat value (0 = up, clockwise) to a direction name
+fn hat_direction(value: i32) -> &'static str {
+    match value {
+        0 => "up",
+        1 => "up_right",
+        2 => "right",
+        3 => "down_right",
+        4 => "down",
+        5 => "down_left",
+        6 => "left",
+        7 => "up_left",
+        _ => "centered",
+    }
+}
+
+/// Scale a raw value within its logical range to -1.0..1.0
+fn normalize(value: u16, (min, max): (i32, i32)) -> f32 {
+    if max <= min {
+        return 0.0;
+    }
+    let clamped = (value as i32).clamp(min, max);
+    ((clamped - min) as f32 / (max - min) as f32) * 2.0 - 1.0
+}
+
+/// An opened HID device with everything needed to decode its reports
+struct HidSource {
+    device: hid_reader::HidDeviceListItem,
+    opened: hid_reader::OpenedHidDevice,
+    descriptor: Vec<u8>,
+    instance: usize,
+    hid_to_axis: HashMap<u32, u32>,
+    last_report: Option<hid_reader::HidFullReport>,
+}
+
+impl HidSource {
+    /// Drain queued reports, keeping the latest
+    fn update(&mut self) {
+        for _ in 0..10 {
+            let bytes = match self.opened.read(0) {
+                Ok(bytes) if !bytes.is_empty() => bytes,
+                _ => break,
+            };
+            if let Ok(report) = hid_reader::parse_hid_full_report(&bytes, &self.descriptor) {
+                self.last_report = Some(report);
+            }
+        }
+    }
+
+    fn state(&self) -> Option<DeviceInputState> {
+        let report = self.last_report.as_ref()?;
+
+        let mut axes = Vec::new();
+        let mut hats = Vec::new();
+
+        let mut usages: Vec<_> = report.axis_values.keys().copied().collect();
+        usages.sort();
+
+        for usage in usages {
+            let raw = report.axis_values[&usage];
+            let range = report
+                .axis_ranges
+                .get(&usage)
+                .copied()
+                .unwrap_or((0, 65535));
+
+            if usage == HID_HAT_SWITCH_USAGE {
+                hats.push(HatValue {
+                    hat_id: hats.len() as u32 + 1,
+                    direction: hat_direction(raw as i32 - range.0).to_string(),
+                });
+                continue;
+            }
+
+            axes.push(AxisValue {
+                axis_id: self.hid_to_axis.get(&usage).copied().unwrap_or(usage),
+                name: report
+                    .axis_names
+                    .get(&usage)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Axis {}", usage)),
+                value: normalize(raw, range),
+            });
+        }
+
+        let mut pressed_buttons = report.pressed_buttons.clone();
+        pressed_buttons.sort();
+
+        Some(DeviceInputState {
+            device_uuid: format!(
+                "{:04x}:{:04x}",
+                self.device.vendor_id, self.device.product_id
+            ),
+            device_name: self
+                .device
+                .product
+                .clone()
+                .unwrap_or_else(|| "Unknown Device".to_string()),
+            device_type: "Joystick".to_string(),
+            instance: self.instance,
+            axes,
+            pressed_buttons,
+            hats,
+        })
+    }
+}
+
+fn open_hid_sources() -> Vec<HidSource> {
+    let devices = hid_reader::list_hid_game_controllers().unwrap_or_default();
+
+    devices
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, device)| {
+            let opened = hid_reader::OpenedHidDevice::open(&device.path).ok()?;
+            let descriptor = hid_reader::get_hid_descriptor_bytes(&device.path).ok()?;
+            let hid_to_axis = hid_reader::get_directinput_to_hid_axis_mapping(&device.path)
+                .map(|di_to_hid| {
+                    di_to_hid
+                        .into_iter()
+                        .map(|(axis_idx, usage)| (usage, axis_idx))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Some(HidSource {
+                device,
+                opened,
+                descriptor,
+                instance: idx + 1,
+                hid_to_axis,
+                last_report: None,
+            })
+        })
+        .collect()
+}
+
+fn xinput_states(xinput: &XInputHandle) -> Vec<DeviceInputState> {
+    (0..4)
+        .filter_map(|controller_id| {
+            let state = xinput.get_state(controller_id).ok()?;
+            let pad = state.raw.Gamepad;
+
+            let axes = [
+                (1, pad.sThumbLX as f32 / 32768.0, "Left Stick X"),
+                (2, pad.sThumbLY as f32 / 32768.0, "Left Stick Y"),
+                (3, pad.sThumbRX as f32 / 32768.0, "Right Stick X"),
+                (4, pad.sThumbRY as f32 / 32768.0, "Right Stick Y"),
+                (
+                    5,
+                    pad.bLeftTrigger as f32 / 255.0 * 2.0 - 1.0,
+                    "Left Trigger",
+                ),
+                (
+                    6,
+                    pad.bRightTrigger as f32 / 255.0 * 2.0 - 1.0,
+                    "Right Trigger",
+                ),
+            ]
+            .into_iter()
+            .map(|(axis_id, value, name)| AxisValue {
+                axis_id,
+                name: name.to_string(),
+                value,
+            })
+            .collect();
+
+            let pressed_buttons = XINPUT_BUTTONS
+                .iter()
+                .filter(|(mask, _)| pad.wButtons & mask != 0)
+                .map(|(_, button)| *button)
+                .collect();
+
+            let up = pad.wButtons & 0x0001 != 0;
+            let down = pad.wButtons & 0x0002 != 0;
+            let left = pad.wButtons & 0x0004 != 0;
+            let right = pad.wButtons & 0x0008 != 0;
+            let direction = match (up, down, left, right) {
+                (true, _, false, true) => "up_right",
+                (true, _, true, false) => "up_left",
+                (_, true, false, true) => "down_right",
+                (_, true, true, false) => "down_left",
+                (true, _, _, _) => "up",
+                (_, true, _, _) => "down",
+                (_, _, true, _) => "left",
+                (_, _, _, true) => "right",
+                _ => "centered",
+            };
+
+            Some(DeviceInputState {
+                device_uuid: format!("xinput_{}", controller_id),
+                device_name: format!("Xbox Controller (XInput {})", controller_id),
+                device_type: "Gamepad".to_string(),
+                instance: controller_id as usize + 1,
+                axes,
+                pressed_buttons,
+                hats: vec![HatValue {
+                    hat_id: 1,
+                    direction: direction.to_string(),
+                }],
+            })
+        })
+        .collect()
+}
+
+/// A running input monitor. Dropping it stops streaming.
+pub struct InputMonitor {
+    stop: Arc<AtomicBool>,
+    /// State of every device as last polled
+    latest: Arc<Mutex<Vec<DeviceInputState>>>,
+}
+
+/// The monitor's latest state, readable without holding on to the monitor itself (and
+/// whatever lock it lives behind) while sampling for a few seconds
+#[derive(Clone)]
+pub struct InputSnapshot(Arc<Mutex<Vec<DeviceInputState>>>);
+
+impl InputSnapshot {
+    /// Every device as last polled
+    pub fn devices(&self) -> Vec<DeviceInputState> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Current position of one axis, None until the device has been polled
+    pub fn axis(&self, device_uuid: &str, axis_id: u32) -> Option<f32> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|device| device.device_uuid == device_uuid)?
+            .axes
+            .iter()
+            .find(|axis| axis.axis_id == axis_id)
+            .map(|axis| axis.value)
+    }
+}
+
+impl InputMonitor {
+    /// Start streaming input state. Events are only sent when something changed.
+    pub fn start(app_handle: AppHandle, interval: Duration) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let latest = Arc::new(Mutex::new(Vec::new()));
+        let thread_latest = latest.clone();
+
+        thread::spawn(move || {
+            let xinput = XInputHandle::load_default().ok();
+            let mut sources = open_hid_sources();
+            let mut last_sent: Option<Vec<DeviceInputState>> = None;
+
+            info!(
+                "InputMonitor: Streaming {} HID devices and 4 XInput slots",
+                sources.len()
+            );
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                let mut devices: Vec<DeviceInputState> = sources
+                    .iter_mut()
+                    .filter_map(|source| {
+                        source.update();
+                        source.state()
+                    })
+                    .collect();
+
+                if let Some(ref xinput) = xinput {
+                    devices.extend(xinput_states(xinput));
+                }
+
+                if last_sent.as_ref() != Some(&devices) {
+                    *thread_latest.lock().unwrap() = devices.clone();
+                    event_stream::publish_input(last_sent.as_deref().unwrap_or_default(), &devices);
+                    let _ = app_handle.emit(
+                        "input-state",
+                        InputStateEvent {
+                            devices: devices.clone(),
+                        },
+                    );
+                    last_sent = Some(devices);
+                }
+
+                thread::sleep(interval);
+            }
+
+            info!("InputMonitor: Stopped");
+        });
+
+        InputMonitor { stop, latest }
+    }
+
+    /// State of every device as last polled
+    pub fn latest(&self) -> Vec<DeviceInputState> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    pub fn snapshot(&self) -> InputSnapshot {
+        InputSnapshot(self.latest.clone())
+    }
+}
+
+impl Drop for InputMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
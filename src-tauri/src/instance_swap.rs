@@ -0,0 +1,254 @@
+//! Renumber joystick instances across a profile and actionmaps.xml
+//!
+//! Windows sometimes enumerates devices in a different order after an update or a USB
+//! shuffle, and SC then calls the stick js2 and the throttle js1. Fixing that by hand
+//! means touching `<options instance>`, every `jsN_` rebind and the profile's joystick
+//! entries consistently. A mapping like {"1": "2", "2": "1"} (or any other permutation)
+//! is applied to all of them in one pass, so swapped numbers can't collide halfway.
+
+use crate::controls::ControlsFile;
+use crate::keybindings::ActionMaps;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Old instance number -> new instance number, both as strings like "1"
+pub type InstanceMapping = BTreeMap<String, String>;
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RemapCounts {
+    /// `<options type="joystick">` blocks renumbered
+    pub options_blocks: usize,
+    pub rebinds: usize,
+    /// Joystick entries renumbered in the .sccontrols profile
+    pub profile_devices: usize,
+}
+
+/// Check the mapping is a proper renumbering: numbers only, no two instances sent to the
+/// same number, and no instance moved onto one that stays where it is.
+pub fn validate_mapping(mapping: &InstanceMapping, existing: &[String]) -> Result<(), String> {
+    let mut targets = HashSet::new();
+    for (from, to) in mapping {
+        for value in [from, to] {
+            if value.parse::<u32>().map_or(true, |n| n == 0) {
+                return Err(format!("'{}' is not a joystick instance number", value));
+            }
+        }
+        if !targets.insert(to) {
+            return Err(format!("More than one instance would become js{}", to));
+        }
+    }
+
+    for instance in existing {
+        if !mapping.contains_key(instance) && targets.contains(instance) {
+            return Err(format!(
+                "js{} would end up twice; include it in the mapping to move it out of the way",
+                instance
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `js2_button1` -> `js1_button1` under {"2": "1"}, including inputs with modifiers in front
+fn remap_input(input: &str, mapping: &InstanceMapping) -> Option<String> {
+    let mut changed = false;
+    let parts: Vec<String> = input
+        .split('+')
+        .map(|part| {
+            let remapped = part.strip_prefix("js").and_then(|rest| {
+                let (number, tail) = rest.split_once('_')?;
+                let to = mapping.get(number)?;
+                Some(format!("js{}_{}", to, tail))
+            });
+            match remapped {
+                Some(remapped) => {
+                    changed = true;
+                    remapped
+                }
+                None => part.to_string(),
+            }
+        })
+        .collect();
+    changed.then(|| parts.join("+"))
+}
+
+/// Replace the value of `attribute` in a single tag, if it's present
+fn replace_attribute(
+    tag: &str,
+    attribute: &str,
+    f: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let needle = format!(" {}=\"", attribute);
+    let start = tag.find(&needle)? + needle.len();
+    let end = start + tag[start..].find('"')?;
+    let value = f(&tag[start..end])?;
+    Some(format!("{}{}{}", &tag[..start], value, &tag[end..]))
+}
+
+/// Renumber joystick instances in an actionmaps.xml document
+pub fn remap_actionmaps_xml(
+    xml: &str,
+    mapping: &InstanceMapping,
+) -> Result<(String, RemapCounts), String> {
+    let mut counts = RemapCounts::default();
+    let mut result = String::with_capacity(xml.len());
+    let mut rest = xml;
+
+    // Tag by tag, so each value is renumbered exactly once
+    while let Some(start) = rest.find('<') {
+        let end = start
+            + rest[start..]
+                .find('>')
+                .ok_or("Unterminated tag in actionmaps.xml")?
+            + 1;
+        result.push_str(&rest[..start]);
+        let tag = &rest[start..end];
+
+        let remapped = if tag.starts_with("<options ") && tag.contains(" type=\"joystick\"") {
+            replace_attribute(tag, "instance", |instance| mapping.get(instance).cloned())
+                .inspect(|_| counts.options_blocks += 1)
+        } else {
+            replace_attribute(tag, "input", |input| remap_input(input, mapping))
+                .inspect(|_| counts.rebinds += 1)
+        };
+        result.push_str(remapped.as_deref().unwrap_or(tag));
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+
+    Ok((result, counts))
+}
+
+/// Renumber the joystick entries of a .sccontrols profile
+pub fn remap_controls(controls: &mut ControlsFile, mapping: &InstanceMapping) -> usize {
+    let Some(joysticks) = controls.devices.joystick.take() else {
+        return 0;
+    };
+
+    let mut moved = 0;
+    let remapped: HashMap<_, _> = joysticks
+        .into_iter()
+        .map(|(instance, settings)| match mapping.get(&instance) {
+            Some(to) => {
+                moved += 1;
+                (to.clone(), settings)
+            }
+            None => (instance, settings),
+        })
+        .collect();
+    controls.devices.joystick = Some(remapped);
+    moved
+}
+
+/// Renumber rebinds and joystick options, and reorder the joystick device list of loaded
+/// keybindings
+pub fn remap_bindings(bindings: &mut ActionMaps, mapping: &InstanceMapping) -> usize {
+    let mut count = 0;
+    for rebind in bindings
+        .action_maps
+        .iter_mut()
+        .flat_map(|m| m.actions.iter_mut())
+        .flat_map(|a| a.rebinds.iter_mut())
+    {
+        if let Some(input) = remap_input(&rebind.input, mapping) {
+            rebind.input = input;
+            count += 1;
+        }
+    }
+
+    // The device list is in instance order (index 0 is js1)
+    let joysticks = &bindings.devices.joysticks;
+    let mut reordered = joysticks.clone();
+    for (from, to) in mapping {
+        let index = |instance: &str| instance.parse::<usize>().ok()?.checked_sub(1);
+        let (Some(from), Some(to)) = (index(from), index(to)) else {
+            continue;
+        };
+        if let (Some(product), Some(slot)) = (joysticks.get(from), reordered.get_mut(to)) {
+            *slot = product.clone();
+        }
+    }
+    bindings.devices.joysticks = reordered;
+
+    for options in bindings
+        .devices
+        .device_options
+        .iter_mut()
+        .filter(|o| o.device_type == "joystick")
+    {
+        if let Some(to) = mapping.get(&options.instance) {
+            options.instance = to.clone();
+        }
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swap(a: &str, b: &str) -> InstanceMapping {
+        [
+            (a.to_string(), b.to_string()),
+            (b.to_string(), a.to_string()),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn test_swap_actionmaps() {
+        let xml = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="keyboard" instance="1" Product="Keyboard"/>
+  <options type="joystick" instance="1" Product="Stick">
+   <flight_move_pitch invert="1"/>
+  </options>
+  <options type="joystick" instance="2" Product="Throttle"/>
+  <actionmap name="spaceship_general">
+   <action name="v_toggle_landing_system">
+    <rebind input="js1_button3"/>
+   </action>
+   <action name="v_toggle_quantum_mode">
+    <rebind input="js2_lalt+button7"/>
+    <rebind input="kb1_b"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>
+"#;
+        let (swapped, counts) = remap_actionmaps_xml(xml, &swap("1", "2")).unwrap();
+        assert_eq!(counts.options_blocks, 2);
+        assert_eq!(counts.rebinds, 2);
+        assert!(swapped.contains(r#"<options type="joystick" instance="2" Product="Stick">"#));
+        assert!(swapped.contains(r#"<options type="joystick" instance="1" Product="Throttle"/>"#));
+        assert!(swapped.contains(r#"<options type="keyboard" instance="1""#));
+        assert!(swapped.contains(r#"<rebind input="js2_button3"/>"#));
+        assert!(swapped.contains(r#"<rebind input="js1_lalt+button7"/>"#));
+
+        // Swapping back is a no-op overall
+        let (back, _) = remap_actionmaps_xml(&swapped, &swap("1", "2")).unwrap();
+        assert_eq!(back, xml);
+    }
+
+    #[test]
+    fn test_mapping_validation() {
+        let existing = vec!["1".to_string(), "2".to_string(), "3".to_string()];
+        assert!(validate_mapping(&swap("1", "2"), &existing).is_ok());
+
+        // js1 -> js3 while js3 stays put
+        let onto_existing: InstanceMapping =
+            [("1".to_string(), "3".to_string())].into_iter().collect();
+        assert!(validate_mapping(&onto_existing, &existing).is_err());
+
+        let duplicate: InstanceMapping = [
+            ("1".to_string(), "2".to_string()),
+            ("3".to_string(), "2".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        assert!(validate_mapping(&duplicate, &existing).is_err());
+        assert!(validate_mapping(&swap("0", "1"), &existing).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Guided inversion detection
+//!
+//! Whether pitch or the throttle needs inverting depends on how the device reports its
+//! axes, which users usually find out in the cockpit. Instead the UI asks for one
+//! movement ("push the stick forward"), the axis is watched while it's made, and the
+//! direction it moved is compared with what SC expects for that movement without
+//! inversion. The result is a proposed invert flag, written to the profile only once
+//! the user confirms it.
+
+use crate::controls::ControlsFile;
+use serde::{Deserialize, Serialize};
+
+/// How long the axis is watched when the caller doesn't say
+pub const DEFAULT_CHECK_SECONDS: f64 = 4.0;
+
+/// Less movement than this doesn't tell us anything
+const MIN_MOVEMENT: f64 = 0.3;
+
+/// Share of the samples, from the start, taken as the resting position
+const REST_SHARE: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InversionCheck {
+    Pitch,
+    Roll,
+    Yaw,
+    Throttle,
+}
+
+impl InversionCheck {
+    /// What the user is asked to do
+    pub fn prompt(self) -> &'static str {
+        match self {
+            InversionCheck::Pitch => "Push the stick fully forward and hold it",
+            InversionCheck::Roll => "Push the stick fully right and hold it",
+            InversionCheck::Yaw => "Twist the stick or press the pedal fully right and hold it",
+            InversionCheck::Throttle => "Pull the throttle fully back and hold it",
+        }
+    }
+
+    /// The end of the axis SC expects the movement to go towards without inversion:
+    /// forward is nose down (the low end), right is the high end, and the absolute
+    /// throttle reads fully back as the high end
+    fn expected_direction(self) -> f64 {
+        match self {
+            InversionCheck::Pitch => -1.0,
+            InversionCheck::Roll | InversionCheck::Yaw | InversionCheck::Throttle => 1.0,
+        }
+    }
+
+    /// Options whose invert flag the movement decides
+    pub fn options(self) -> &'static [&'static str] {
+        match self {
+            InversionCheck::Pitch => &["flight_move_pitch"],
+            InversionCheck::Roll => &["flight_move_roll"],
+            InversionCheck::Yaw => &["flight_move_yaw"],
+            InversionCheck::Throttle => &["flight_throttle_abs"],
+        }
+    }
+}
+
+/// The invert flag a movement calls for
+#[derive(Debug, Serialize, Clone)]
+pub struct InversionProposal {
+    pub check: InversionCheck,
+    pub options: Vec<String>,
+    /// How far the axis moved from rest, signed
+    pub movement: f64,
+    pub invert: bool,
+    pub message: String,
+}
+
+/// How far the axis moved from where it started, None if it barely moved
+fn movement(values: &[f64]) -> Option<f64> {
+    let rest_count = (values.len() / REST_SHARE).max(1);
+    let rest = values.iter().take(rest_count).sum::<f64>() / rest_count as f64;
+    let movement = values
+        .iter()
+        .map(|value| value - rest)
+        .max_by(|a, b| a.abs().total_cmp(&b.abs()))?;
+    (movement.abs() >= MIN_MOVEMENT).then_some(movement)
+}
+
+/// Propose an invert flag from samples taken while the user made the movement
+pub fn propose(check: InversionCheck, values: &[f64]) -> Result<InversionProposal, String> {
+    let movement = movement(values).ok_or_else(|| {
+        format!(
+            "The axis barely moved; {} and try again",
+            check.prompt().to_lowercase()
+        )
+    })?;
+    let invert = movement.signum() != check.expected_direction();
+    let message = if invert {
+        "The axis moves the opposite way to what SC expects, so it should be inverted"
+    } else {
+        "The axis already moves the way SC expects, so it shouldn't be inverted"
+    };
+    Ok(InversionProposal {
+        check,
+        options: check.options().iter().map(|o| o.to_string()).collect(),
+        movement: (movement * 1000.0).round() / 1000.0,
+        invert,
+        message: message.to_string(),
+    })
+}
+
+/// Write a confirmed invert flag to a device of a profile, returning how many options changed
+pub fn apply(
+    controls: &mut ControlsFile,
+    device_type: &str,
+    instance: &str,
+    check: InversionCheck,
+    invert: bool,
+) -> Result<usize, String> {
+    let device = controls.device_mut(device_type, instance)?;
+    let mut changed = 0;
+    for option in check.options() {
+        let settings = device.options.entry(option.to_string()).or_default();
+        if settings.invert != Some(invert) {
+            settings.invert = Some(invert);
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proposes_invert_from_movement() {
+        // Pushed forward, the axis went to the low end: what SC expects
+        let forward = [0.0, 0.01, -0.4, -0.9, -1.0, -1.0, -0.98];
+        let proposal = propose(InversionCheck::Pitch, &forward).unwrap();
+        assert!(!proposal.invert);
+        assert_eq!(proposal.movement, -1.0);
+
+        // Pulled back from full forward, the throttle went down instead of up
+        let throttle = [0.9, 0.9, 0.5, 0.0, -0.8, -1.0];
+        let proposal = propose(InversionCheck::Throttle, &throttle).unwrap();
+        assert!(proposal.invert);
+        assert_eq!(proposal.options, vec!["flight_throttle_abs".to_string()]);
+
+        assert!(propose(InversionCheck::Roll, &[0.0, 0.05, 0.1]).is_err());
+        assert!(propose(InversionCheck::Roll, &[]).is_err());
+
+        let mut controls = ControlsFile::new("Invert".to_string());
+        assert_eq!(
+            apply(
+                &mut controls,
+                "joystick",
+                "1",
+                InversionCheck::Throttle,
+                true
+            ),
+            Ok(1)
+        );
+        assert_eq!(
+            apply(
+                &mut controls,
+                "joystick",
+                "1",
+                InversionCheck::Throttle,
+                true
+            ),
+            Ok(0)
+        );
+        let throttle = &controls.device("joystick", "1").unwrap().options["flight_throttle_abs"];
+        assert_eq!(throttle.invert, Some(true));
+    }
+}
@@ -12,13 +12,16 @@ pub struct ActionMaps {
 }
 
 /// Represents the AllBinds.xml master file with all available actions
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AllBinds {
     pub action_maps: Vec<AllBindsActionMap>,
+    /// Actions declared in an `<actiongroup>` (the fire groups), which no actionmap lists
+    #[serde(default)]
+    pub grouped_actions: Vec<String>,
 }
 
 /// Action map from AllBinds.xml with UI metadata
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AllBindsActionMap {
     pub name: String,
     pub version: String,
@@ -28,7 +31,7 @@ pub struct AllBindsActionMap {
 }
 
 /// Action from AllBinds.xml with default bindings and UI metadata
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AllBindsAction {
     pub name: String,
     pub ui_label: String,
@@ -40,6 +43,8 @@ pub struct AllBindsAction {
     pub default_mouse: String,
     pub default_gamepad: String,
     pub default_joystick: String,
+    /// Option whose invert and curve apply to this action (optionGroup), empty when none
+    pub option_group: String,
 }
 
 /// A point on a nonlinearity curve
@@ -393,8 +398,10 @@ impl ActionMaps {
                             // Get profile name
                             for attr in e.attributes().flatten() {
                                 if attr.key.as_ref() == b"profileName" {
-                                    profile_name =
-                                        String::from_utf8(attr.value.to_vec()).unwrap_or_default();
+                                    profile_name = attr
+                                        .unescape_value()
+                                        .map(|v| v.into_owned())
+                                        .unwrap_or_default();
                                 }
                             }
                         }
@@ -699,7 +706,7 @@ impl ActionMaps {
         // Root ActionMaps element
         xml.push_str(&format!(
             "<ActionMaps version=\"1\" optionsVersion=\"2\" rebindVersion=\"2\" profileName=\"{}\">\n",
-            self.profile_name
+            quick_xml::escape::escape(&self.profile_name)
         ));
 
         // Build a map of actionmap names to their categories and order
@@ -747,7 +754,7 @@ impl ActionMaps {
 
         // Write CustomisationUIHeader
         xml.push_str(" <CustomisationUIHeader label=\"");
-        xml.push_str(&self.profile_name);
+        xml.push_str(&quick_xml::escape::escape(&self.profile_name));
         xml.push_str("\" description=\"\" image=\"\">\n");
 
         // Check if we have keyboard or mouse customizations
@@ -1022,6 +1029,76 @@ impl ActionMaps {
         }
     }
 
+    /// Extract only keyboard and mouse rebinds into a new, hardware-independent profile.
+    /// Cleared keyboard/mouse binds ("kb1_ ") are kept since they override SC defaults.
+    pub fn keyboard_mouse_only(&self, profile_name: String) -> ActionMaps {
+        let action_maps = self
+            .action_maps
+            .iter()
+            .filter_map(|action_map| {
+                let actions: Vec<Action> = action_map
+                    .actions
+                    .iter()
+                    .filter_map(|action| {
+                        let rebinds: Vec<Rebind> = action
+                            .rebinds
+                            .iter()
+                            .filter(|rebind| {
+                                matches!(
+                                    rebind.get_device_type(),
+                                    InputType::Keyboard | InputType::Mouse
+                                )
+                            })
+                            .cloned()
+                            .collect();
+
+                        if rebinds.is_empty() {
+                            None
+                        } else {
+                            Some(Action {
+                                name: action.name.clone(),
+                                rebinds,
+                            })
+                        }
+                    })
+                    .collect();
+
+                if actions.is_empty() {
+                    None
+                } else {
+                    Some(Self::new_empty_action_map(action_map.name.clone(), actions))
+                }
+            })
+            .collect();
+
+        ActionMaps {
+            profile_name,
+            action_maps,
+            categories: self.categories.clone(),
+            devices: DeviceInfo {
+                keyboards: self.devices.keyboards.clone(),
+                mice: self.devices.mice.clone(),
+                joysticks: Vec::new(),
+                device_options: self
+                    .devices
+                    .device_options
+                    .iter()
+                    .filter(|opt| opt.device_type == "keyboard")
+                    .cloned()
+                    .collect(),
+            },
+        }
+    }
+
+    /// Count the rebinds across all action maps
+    pub fn rebind_count(&self) -> usize {
+        self.action_maps
+            .iter()
+            .flat_map(|action_map| action_map.actions.iter())
+            .map(|action| action.rebinds.len())
+            .sum()
+    }
+
     /// Create a new empty action map with the given name and actions
     pub fn new_empty_action_map(name: String, actions: Vec<Action>) -> ActionMap {
         ActionMap { name, actions }
@@ -1162,12 +1239,22 @@ impl AllBinds {
         let mut buf = vec![];
 
         let mut current_action_map: Option<AllBindsActionMap> = None;
+        let mut grouped_actions = Vec::new();
+        let mut in_action_group = false;
 
         loop {
             match reader.read_event_into(&mut buf) {
                 Ok(quick_xml::events::Event::Start(ref e))
                 | Ok(quick_xml::events::Event::Empty(ref e)) => {
                     match e.name().as_ref() {
+                        b"actiongroup" => in_action_group = true,
+                        b"action" if in_action_group => {
+                            if let Ok(Some(attr)) = e.try_get_attribute("name") {
+                                grouped_actions.push(
+                                    String::from_utf8(attr.value.to_vec()).unwrap_or_default(),
+                                );
+                            }
+                        }
                         b"actionmap" => {
                             let mut name = String::new();
                             let mut version = String::new();
@@ -1216,6 +1303,7 @@ impl AllBinds {
                                 let mut mouse = String::new();
                                 let mut gamepad = String::new();
                                 let mut joystick = String::new();
+                                let mut option_group = String::new();
 
                                 for attr in e.attributes().flatten() {
                                     match attr.key.as_ref() {
@@ -1260,6 +1348,10 @@ impl AllBinds {
                                             joystick = String::from_utf8(attr.value.to_vec())
                                                 .unwrap_or_default()
                                         }
+                                        b"optionGroup" => {
+                                            option_group = String::from_utf8(attr.value.to_vec())
+                                                .unwrap_or_default()
+                                        }
                                         _ => {}
                                     }
                                 }
@@ -1281,19 +1373,22 @@ impl AllBinds {
                                     default_mouse: mouse,
                                     default_gamepad: gamepad,
                                     default_joystick: joystick,
+                                    option_group,
                                 });
                             }
                         }
                         _ => {}
                     }
                 }
-                Ok(quick_xml::events::Event::End(ref e)) => {
-                    if e.name().as_ref() == b"actionmap" {
+                Ok(quick_xml::events::Event::End(ref e)) => match e.name().as_ref() {
+                    b"actionmap" => {
                         if let Some(action_map) = current_action_map.take() {
                             action_maps.push(action_map);
                         }
                     }
-                }
+                    b"actiongroup" => in_action_group = false,
+                    _ => {}
+                },
                 Ok(quick_xml::events::Event::Eof) => break,
                 Err(e) => {
                     return Err(format!("XML parsing error: {}", e));
@@ -1303,7 +1398,20 @@ impl AllBinds {
             buf.clear();
         }
 
-        Ok(AllBinds { action_maps })
+        Ok(AllBinds {
+            action_maps,
+            grouped_actions,
+        })
+    }
+
+    /// Whether `action` can be bound in `action_map`; grouped actions are accepted in any
+    pub fn has_action(&self, action_map: &str, action: &str) -> bool {
+        self.grouped_actions.iter().any(|a| a == action)
+            || self
+                .action_maps
+                .iter()
+                .filter(|m| m.name == action_map)
+                .any(|m| m.actions.iter().any(|a| a.name == action))
     }
 }
 
@@ -0,0 +1,422 @@
+//! Keyboard capture for press-to-bind
+//!
+//! Polls the keyboard state and reports the next key press in Star Citizen's key naming
+//! (e.g. `k`, `np_5`, `lbracket`), along with any modifiers held at the time.
+//!
+//! SC names keys by their position on a US keyboard, not by what they type: the key right
+//! of P is `lbracket` whether it prints `[`, `ü` or a dead `^`. Windows virtual key codes
+//! for letters, digits and punctuation follow the active layout instead (AZERTY's A key
+//! sends VK_A from where US has Q), so those are resolved through their scan code. AltGr
+//! arrives as a right Alt with a fake left Ctrl, which is dropped so AltGr binds as `ralt`.
+
+use crate::binding_string::{BindingInput, Modifier};
+use crate::keybindings::InputType;
+
+/// Windows virtual key codes of the modifier keys, with their SC names
+const MODIFIER_KEYS: [(u16, &str); 6] = [
+    (0xA4, "lalt"),
+    (0xA5, "ralt"),
+    (0xA2, "lctrl"),
+    (0xA3, "rctrl"),
+    (0xA0, "lshift"),
+    (0xA1, "rshift"),
+];
+
+/// VK_LCONTROL and VK_RMENU, the pair Windows sends for AltGr
+const VK_LCTRL: u16 = 0xA2;
+const VK_RALT: u16 = 0xA5;
+
+/// Scan codes (set 1, extended ones prefixed with 0xE0) of the keys whose virtual key
+/// depends on the layout, named after the US key in that position
+const SCAN_CODE_NAMES: [(u16, &str); 48] = [
+    (0x02, "1"),
+    (0x03, "2"),
+    (0x04, "3"),
+    (0x05, "4"),
+    (0x06, "5"),
+    (0x07, "6"),
+    (0x08, "7"),
+    (0x09, "8"),
+    (0x0A, "9"),
+    (0x0B, "0"),
+    (0x0C, "minus"),
+    (0x0D, "equals"),
+    (0x10, "q"),
+    (0x11, "w"),
+    (0x12, "e"),
+    (0x13, "r"),
+    (0x14, "t"),
+    (0x15, "y"),
+    (0x16, "u"),
+    (0x17, "i"),
+    (0x18, "o"),
+    (0x19, "p"),
+    (0x1A, "lbracket"),
+    (0x1B, "rbracket"),
+    (0x1E, "a"),
+    (0x1F, "s"),
+    (0x20, "d"),
+    (0x21, "f"),
+    (0x22, "g"),
+    (0x23, "h"),
+    (0x24, "j"),
+    (0x25, "k"),
+    (0x26, "l"),
+    (0x27, "semicolon"),
+    (0x28, "apostrophe"),
+    (0x29, "grave"),
+    (0x2B, "backslash"),
+    (0x2C, "z"),
+    (0x2D, "x"),
+    (0x2E, "c"),
+    (0x2F, "v"),
+    (0x30, "b"),
+    (0x31, "n"),
+    (0x32, "m"),
+    (0x33, "comma"),
+    (0x34, "period"),
+    (0x35, "slash"),
+    // The extra key left of Z on ISO keyboards (`<>` on German, `\|` on UK)
+    (0x56, "oem_102"),
+];
+
+/// Virtual keys that move around with the layout: digits, letters and the OEM punctuation
+/// keys (VK_OEM_1 to VK_OEM_8, VK_OEM_102)
+fn is_layout_dependent(vk: u16) -> bool {
+    matches!(vk, 0x30..=0x39 | 0x41..=0x5A | 0xBA..=0xC0 | 0xDB..=0xDF | 0xE2)
+}
+
+/// SC name of the key at a scan code
+pub fn scan_code_name(scan_code: u16) -> Option<&'static str> {
+    SCAN_CODE_NAMES
+        .iter()
+        .find(|(code, _)| *code == scan_code)
+        .map(|(_, name)| *name)
+}
+
+/// Map a Windows virtual key code to its Star Citizen key name, as if on a US layout
+pub fn sc_key_name(vk: u16) -> Option<String> {
+    let name = match vk {
+        0x41..=0x5A => return Some(((vk as u8) as char).to_ascii_lowercase().to_string()),
+        0x30..=0x39 => return Some(((vk as u8) as char).to_string()),
+        0x60..=0x69 => return Some(format!("np_{}", vk - 0x60)),
+        0x70..=0x7B => return Some(format!("f{}", vk - 0x70 + 1)),
+        0x08 => "backspace",
+        0x09 => "tab",
+        0x0D => "enter",
+        0x13 => "pause",
+        0x14 => "capslock",
+        0x1B => "escape",
+        0x20 => "space",
+        0x21 => "pgup",
+        0x22 => "pgdn",
+        0x23 => "end",
+        0x24 => "home",
+        0x25 => "left",
+        0x26 => "up",
+        0x27 => "right",
+        0x28 => "down",
+        0x2C => "print",
+        0x2D => "insert",
+        0x2E => "delete",
+        0x6A => "np_multiply",
+        0x6B => "np_add",
+        0x6D => "np_subtract",
+        0x6E => "np_period",
+        0x6F => "np_divide",
+        0x90 => "numlock",
+        0x91 => "scrolllock",
+        0xBA => "semicolon",
+        0xBB => "equals",
+        0xBC => "comma",
+        0xBD => "minus",
+        0xBE => "period",
+        0xBF => "slash",
+        0xC0 => "grave",
+        0xDB => "lbracket",
+        0xDC => "backslash",
+        0xDD => "rbracket",
+        0xDE => "apostrophe",
+        0xE2 => "oem_102",
+        _ => {
+            return MODIFIER_KEYS
+                .iter()
+                .find(|(code, _)| *code == vk)
+                .map(|(_, name)| name.to_string())
+        }
+    };
+    Some(name.to_string())
+}
+
+/// Windows virtual key code of an SC key name on a US layout, the reverse of sc_key_name
+pub fn virtual_key(name: &str) -> Option<u16> {
+    (0x01..=0xFE).find(|&vk| sc_key_name(vk).as_deref() == Some(name))
+}
+
+/// SC name of a virtual key given the scan code the active layout maps it to. Keys that
+/// don't move with the layout (F-keys, numpad, arrows...) are named from the virtual key,
+/// so they're unaffected by scan code quirks like Pause sharing NumLock's code.
+pub fn key_name(vk: u16, scan_code: Option<u16>) -> Option<String> {
+    if !is_layout_dependent(vk) {
+        return sc_key_name(vk);
+    }
+    match scan_code {
+        Some(scan_code) => scan_code_name(scan_code).map(str::to_string),
+        // No layout to ask: assume US
+        None => sc_key_name(vk),
+    }
+}
+
+/// The scan code the foreground window's keyboard layout gives a virtual key
+#[cfg(windows)]
+fn scan_code(vk: u16) -> Option<u16> {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        GetKeyboardLayout, MapVirtualKeyExW, MAPVK_VK_TO_VSC_EX,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    let scan_code = unsafe {
+        let thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+        MapVirtualKeyExW(vk as u32, MAPVK_VK_TO_VSC_EX, GetKeyboardLayout(thread))
+    };
+    (scan_code != 0).then_some(scan_code as u16)
+}
+
+#[cfg(not(windows))]
+fn scan_code(_vk: u16) -> Option<u16> {
+    None
+}
+
+/// SC name of a virtual key on the current layout
+fn current_key_name(vk: u16) -> Option<String> {
+    key_name(vk, scan_code(vk))
+}
+
+/// Is this SC key name one of the modifier keys?
+pub fn is_modifier_key(name: &str) -> bool {
+    MODIFIER_KEYS.iter().any(|(_, modifier)| *modifier == name)
+}
+
+/// Build an SC keyboard binding string, e.g. `kb1_lctrl+k`
+pub fn keyboard_binding(modifiers: &[String], key: &str) -> String {
+    let mut modifiers: Vec<Modifier> = modifiers
+        .iter()
+        .filter_map(|m| Modifier::from_sc_name(m))
+        .filter(|m| m.sc_name() != key)
+        .collect();
+    modifiers.sort();
+    modifiers.dedup();
+
+    BindingInput {
+        device: InputType::Keyboard,
+        instance: 1,
+        modifiers,
+        key: key.to_string(),
+        taps: 1,
+    }
+    .to_sc_string()
+}
+
+/// A key press detected by the poller
+#[derive(Debug, Clone)]
+pub struct KeyPress {
+    /// SC key name of the main key (a modifier when it was pressed and released alone)
+    pub key: String,
+    /// SC names of modifiers held with it
+    pub modifiers: Vec<String>,
+}
+
+/// Tracks keyboard state between polls so only new presses are reported
+pub struct KeyboardPoller {
+    prev_down: Vec<u16>,
+    /// Modifier pressed on its own; bound if released before any other key
+    pending_modifier: Option<u16>,
+    /// Left Ctrl went down in the previous poll (AltGr's two halves can straddle polls)
+    lctrl_just_pressed: bool,
+    /// The left Ctrl currently down is AltGr's fake one
+    altgr_ctrl: bool,
+}
+
+impl KeyboardPoller {
+    pub fn new() -> Self {
+        KeyboardPoller {
+            prev_down: keys_down(),
+            pending_modifier: None,
+            lctrl_just_pressed: false,
+            altgr_ctrl: false,
+        }
+    }
+
+    /// Check for a newly completed key press
+    pub fn poll(&mut self) -> Option<KeyPress> {
+        self.update(keys_down(), current_key_name)
+    }
+
+    /// Process the keys now down; `name` gives a virtual key's SC name
+    fn update(&mut self, down: Vec<u16>, name: impl Fn(u16) -> Option<String>) -> Option<KeyPress> {
+        let is_modifier = |vk: &u16| MODIFIER_KEYS.iter().any(|(code, _)| code == vk);
+        let newly_down = |vk: u16| down.contains(&vk) && !self.prev_down.contains(&vk);
+
+        let lctrl_new = newly_down(VK_LCTRL);
+        if !down.contains(&VK_LCTRL) {
+            self.altgr_ctrl = false;
+        }
+        if newly_down(VK_RALT) && (lctrl_new || self.lctrl_just_pressed) {
+            self.altgr_ctrl = true;
+            if self.pending_modifier == Some(VK_LCTRL) {
+                self.pending_modifier = None;
+            }
+        }
+        self.lctrl_just_pressed = lctrl_new;
+
+        let altgr_ctrl = self.altgr_ctrl;
+        let counts = |vk: &u16| !(altgr_ctrl && *vk == VK_LCTRL);
+
+        let held_modifiers: Vec<String> = MODIFIER_KEYS
+            .iter()
+            .filter(|(code, _)| down.contains(code) && counts(code))
+            .map(|(_, name)| name.to_string())
+            .collect();
+
+        let newly_pressed: Vec<u16> = down
+            .iter()
+            .filter(|vk| !self.prev_down.contains(vk) && counts(vk))
+            .copied()
+            .collect();
+
+        let mut result = None;
+
+        if let Some(&vk) = newly_pressed.iter().find(|vk| !is_modifier(vk)) {
+            // A regular key completes the combo with whatever modifiers are held
+            self.pending_modifier = None;
+            if let Some(key) = name(vk) {
+                result = Some(KeyPress {
+                    key,
+                    modifiers: held_modifiers,
+                });
+            }
+        } else if let Some(&vk) = newly_pressed.iter().find(|vk| is_modifier(vk)) {
+            if self.pending_modifier.is_none() {
+                self.pending_modifier = Some(vk);
+            }
+        } else if let Some(vk) = self.pending_modifier {
+            // Modifier released without another key: bind the modifier itself
+            if !down.contains(&vk) {
+                self.pending_modifier = None;
+                let key = name(vk).unwrap_or_default();
+                let modifiers = held_modifiers.into_iter().filter(|m| *m != key).collect();
+                result = Some(KeyPress { key, modifiers });
+            }
+        }
+
+        self.prev_down = down;
+        result
+    }
+}
+
+impl Default for KeyboardPoller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Every virtual key currently held down
+#[cfg(windows)]
+fn keys_down() -> Vec<u16> {
+    use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
+
+    // Skip the generic shift/ctrl/alt codes (0x10-0x12) and mouse buttons; we use the left/right variants.
+    // The range runs to VK_OEM_102, the extra ISO key.
+    (0x08u16..=0xE2)
+        .filter(|vk| !(0x10..=0x12).contains(vk))
+        .filter(|vk| sc_key_name(*vk).is_some() || is_layout_dependent(*vk))
+        .filter(|vk| unsafe { GetAsyncKeyState(*vk as i32) as u16 & 0x8000 != 0 })
+        .collect()
+}
+
+// Stub for non-Windows platforms
+#[cfg(not(windows))]
+fn keys_down() -> Vec<u16> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_international_layouts() {
+        // (layout, virtual key, scan code the layout gives it, expected SC key)
+        let matrix: [(&str, u16, u16, &str); 16] = [
+            ("US", 0x41, 0x1E, "a"),
+            ("US", 0xDB, 0x1A, "lbracket"),
+            ("German", 0x5A, 0x15, "y"),
+            ("German", 0x59, 0x2C, "z"),
+            ("German", 0xBA, 0x1A, "lbracket"),  // ü
+            ("German", 0xDC, 0x29, "grave"),     // dead ^
+            ("German", 0xDD, 0x0D, "equals"),    // dead ´
+            ("German", 0xBF, 0x2B, "backslash"), // #
+            ("German", 0xE2, 0x56, "oem_102"),   // <>
+            ("French", 0x41, 0x10, "q"),
+            ("French", 0x51, 0x1E, "a"),
+            ("French", 0xDD, 0x1A, "lbracket"), // dead ^
+            ("French", 0xDF, 0x35, "slash"),    // !
+            ("French", 0xDE, 0x29, "grave"),    // ²
+            ("UK", 0xDF, 0x29, "grave"),        // `
+            ("UK", 0xDE, 0x2B, "backslash"),    // #
+        ];
+        for (layout, vk, scan_code, expected) in matrix {
+            assert_eq!(
+                key_name(vk, Some(scan_code)).as_deref(),
+                Some(expected),
+                "{} layout, VK {:#04x}",
+                layout,
+                vk
+            );
+        }
+
+        // Keys that don't move with the layout ignore the scan code (Pause shares NumLock's)
+        assert_eq!(key_name(0x13, Some(0x45)).as_deref(), Some("pause"));
+        assert_eq!(key_name(0x64, Some(0x4B)).as_deref(), Some("np_4"));
+        // Without a layout, fall back to US naming
+        assert_eq!(key_name(0xBA, None).as_deref(), Some("semicolon"));
+    }
+
+    #[test]
+    fn test_altgr_drops_fake_ctrl() {
+        let mut poller = KeyboardPoller::new();
+
+        // AltGr+Q: the fake left Ctrl and right Alt arrive together
+        assert!(poller
+            .update(vec![VK_LCTRL, VK_RALT], sc_key_name)
+            .is_none());
+        let press = poller
+            .update(vec![0x51, VK_LCTRL, VK_RALT], sc_key_name)
+            .unwrap();
+        assert_eq!(keyboard_binding(&press.modifiers, &press.key), "kb1_ralt+q");
+
+        // AltGr alone, its halves split over two polls, binds ralt
+        assert!(poller.update(vec![], sc_key_name).is_none());
+        assert!(poller.update(vec![VK_LCTRL], sc_key_name).is_none());
+        assert!(poller
+            .update(vec![VK_LCTRL, VK_RALT], sc_key_name)
+            .is_none());
+        let press = poller.update(vec![], sc_key_name).unwrap();
+        assert_eq!(press.key, "ralt");
+        assert!(press.modifiers.is_empty());
+
+        // A real left Ctrl held before right Alt is kept
+        assert!(poller.update(vec![VK_LCTRL], sc_key_name).is_none());
+        assert!(poller.update(vec![VK_LCTRL], sc_key_name).is_none());
+        assert!(poller
+            .update(vec![VK_LCTRL, VK_RALT], sc_key_name)
+            .is_none());
+        let press = poller
+            .update(vec![0x4B, VK_LCTRL, VK_RALT], sc_key_name)
+            .unwrap();
+        assert_eq!(
+            keyboard_binding(&press.modifiers, &press.key),
+            "kb1_lctrl+ralt+k"
+        );
+    }
+}
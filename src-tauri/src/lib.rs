@@ -1,13 +1,89 @@
-use log::{error, info};
+use log::{error, info, warn};
 use std::sync::Mutex;
 use tauri::Manager;
+use tauri_plugin_autostart::ManagerExt;
 use tauri_plugin_opener::OpenerExt;
 
+mod action_categories;
+mod actionmaps_recovery;
+mod actionmaps_watcher;
+mod apply_guard;
+mod apply_rebase;
+mod axis_calibration;
+mod axis_feel;
+mod backups;
+mod baseline;
+mod binding_rules;
+mod binding_string;
+mod bundle;
+mod cheat_sheet;
+mod cli;
+mod console_script;
+mod control_presets;
 mod controls;
+mod critical_actions;
+mod curve_ab;
+mod curve_convert;
+mod curve_eval;
+mod curve_export;
+mod curve_interpolation;
+mod curve_presets;
+mod curve_validation;
+mod curve_watchdog;
+mod default_profile_cache;
+mod device_capabilities;
+mod device_identity;
+mod device_monitor;
+mod device_nicknames;
+mod device_remap;
+mod device_roles;
+mod diagnostics;
+mod diff;
 mod directinput;
+mod drift_audit;
+mod environments;
+mod essentials;
+mod event_stream;
+mod game_attributes;
+mod game_process;
+mod gremlin;
 mod hid_reader;
+mod hotas_templates;
+mod identifier_check;
+mod input_monitor;
+mod instance_swap;
+mod inversion_check;
 mod keybindings;
-
+mod keyboard_capture;
+mod local_api;
+mod logs;
+mod modification_log;
+mod option_catalog;
+mod options_editor;
+mod product_names;
+mod profile_layers;
+mod profile_library;
+mod reference_cards;
+mod resolutions;
+mod restore;
+mod role_templates;
+mod sc_migration;
+#[cfg(test)]
+mod sc_sim;
+mod search;
+mod settings;
+mod snapshots;
+mod spreadsheet;
+mod variables;
+mod vertical_flip;
+mod vjoy;
+mod vjoy_feeder;
+mod vjoy_macros;
+mod voiceattack;
+mod watcher;
+mod write_lock;
+
+use binding_string::BindingInput;
 use keybindings::{Action, ActionMap, ActionMaps, AllBinds, MergedBindings, OrganizedKeybindings};
 
 // Resources subfolder name - change this to customize the bundled resources folder
@@ -45,11 +121,65 @@ struct CharacterFile {
     modified: u64, // Unix timestamp in seconds
 }
 
+// Struct for a layout_*_exported.xml file in an installation's mappings folder
+#[derive(serde::Serialize, Clone)]
+struct ExportedMappingFile {
+    name: String,
+    path: String,
+    modified: u64, // Unix timestamp in seconds
+}
+
+// Keybindings and control options read from an exported mapping file
+#[derive(serde::Serialize)]
+struct ExportedMappingImport {
+    bindings: OrganizedKeybindings,
+    controls: controls::LoadControlsOutput,
+}
+
+// A library profile started from one of the game's preset layouts, with the preset's
+// keybindings (now the current bindings)
+#[derive(serde::Serialize)]
+struct PresetProfileImport {
+    profile: profile_library::ProfileSummary,
+    bindings: OrganizedKeybindings,
+}
+
+// A library profile started from a built-in HOTAS template, with where each of the
+// template's devices was found
+#[derive(serde::Serialize)]
+struct TemplateProfileImport {
+    profile: profile_library::ProfileSummary,
+    bindings: OrganizedKeybindings,
+    assignments: Vec<hotas_templates::TemplateAssignment>,
+}
+
+// A library profile made by merging a role template over a base profile
+#[derive(serde::Serialize)]
+struct RoleProfileImport {
+    profile: profile_library::ProfileSummary,
+    bindings: OrganizedKeybindings,
+    skipped: Vec<role_templates::SkippedRoleBinding>,
+    displaced: Vec<String>,
+    priorities: Vec<role_templates::RolePriority>,
+}
+
 // Global state to hold the current keybindings
 struct AppState {
     current_bindings: Option<ActionMaps>,
     all_binds: Option<AllBinds>,
     current_file_name: Option<String>,
+    curve_watchdog: Option<curve_watchdog::CurveWatchdog>,
+    device_monitor: Option<device_monitor::DeviceMonitor>,
+    input_monitor: Option<input_monitor::InputMonitor>,
+    actionmaps_watcher: Option<actionmaps_watcher::ActionmapsWatcher>,
+    baseline_watcher: Option<watcher::FileWatcher>,
+    drift_audit: Option<drift_audit::DriftAudit>,
+    /// Parsed from AllBinds.xml on first use
+    option_catalog: Option<option_catalog::OptionCatalog>,
+    /// global.ini last used for search labels, by path
+    localization: Option<(String, std::collections::HashMap<String, String>)>,
+    vjoy_feeder: Option<vjoy_feeder::VJoyFeeder>,
+    local_api: Option<local_api::LocalApi>,
 }
 
 impl AppState {
@@ -58,6 +188,16 @@ impl AppState {
             current_bindings: None,
             all_binds: None,
             current_file_name: None,
+            curve_watchdog: None,
+            device_monitor: None,
+            input_monitor: None,
+            actionmaps_watcher: None,
+            baseline_watcher: None,
+            drift_audit: None,
+            option_catalog: None,
+            localization: None,
+            vjoy_feeder: None,
+            local_api: None,
         }
     }
 }
@@ -68,14 +208,551 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// Set once the HID/XInput stack has been initialized by the first device enumeration
+static DEVICE_ENUMERATION_INIT: std::sync::OnceLock<()> = std::sync::OnceLock::new();
+
+#[tauri::command]
+fn detect_joysticks(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<device_nicknames::NamedDevice<directinput::JoystickInfo>>, String> {
+    let devices = diagnostics::first_use(&DEVICE_ENUMERATION_INIT, "device enumeration", || {
+        directinput::detect_joysticks()
+    })?;
+    Ok(load_device_nicknames(&app_handle)?.name_devices(devices, |d| d.uuid.as_deref()))
+}
+
+/// Enumerate connected controllers with GUID, axis types, button and POV counts
+#[tauri::command]
+fn get_device_capabilities(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<device_nicknames::NamedDevice<device_capabilities::DeviceCapabilities>>, String> {
+    let devices = diagnostics::first_use(&DEVICE_ENUMERATION_INIT, "device enumeration", || {
+        device_capabilities::enumerate_devices()
+    })?;
+    Ok(load_device_nicknames(&app_handle)?.name_devices(devices, |d| Some(d.uuid.as_str())))
+}
+
+/// The axes, buttons and hats one connected device actually exposes, by SC input name,
+/// so only settings for inputs that exist get offered
+#[tauri::command]
+fn probe_device(
+    device_type: String,
+    instance: usize,
+) -> Result<device_capabilities::DeviceProbe, String> {
+    let devices = diagnostics::first_use(&DEVICE_ENUMERATION_INIT, "device enumeration", || {
+        device_capabilities::enumerate_devices()
+    })?;
+    devices
+        .into_iter()
+        .find(|d| d.device_type == device_type && d.instance == instance)
+        .map(device_capabilities::probe)
+        .ok_or_else(|| format!("No {} connected as instance {}", device_type, instance))
+}
+
+/// Detect vJoy and its configured virtual devices, flagging vJoy devices the loaded profile
+/// expects but that aren't configured
+#[tauri::command]
+fn get_vjoy_status(state: tauri::State<Mutex<AppState>>) -> Result<vjoy::VJoyStatus, String> {
+    let app_state = state.lock().unwrap();
+    let profile_products = app_state
+        .current_bindings
+        .as_ref()
+        .map(|bindings| bindings.devices.joysticks.clone())
+        .unwrap_or_default();
+    drop(app_state);
+
+    Ok(vjoy::query_status(&profile_products))
+}
+
+/// The vJoy feeder section of a profile, if it has one
+#[tauri::command]
+fn get_feeder_config(file_path: String) -> Result<Option<vjoy_feeder::FeederConfig>, String> {
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    Ok(controls::ControlsFile::from_json(&json)?.feeder)
+}
+
+/// Set or clear the vJoy feeder section of a profile. A running feeder keeps its
+/// configuration until restarted.
+#[tauri::command]
+fn set_feeder_config(
+    file_path: String,
+    config: Option<vjoy_feeder::FeederConfig>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    if let Some(config) = &config {
+        vjoy_feeder::validate(config)?;
+    }
+
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    controls_file.feeder = config;
+    controls_file.touch();
+
+    std::fs::write(&file_path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    info!("vJoy feeder configuration updated in {}", file_path);
+    Ok(())
+}
+
+/// The game settings in the attributes.xml next to `actionmaps_path`, in file order
+#[tauri::command]
+fn read_game_attributes(
+    actionmaps_path: String,
+) -> Result<Vec<game_attributes::GameAttribute>, String> {
+    let path = game_attributes::path_for(std::path::Path::new(&actionmaps_path));
+    Ok(game_attributes::load(&path)?.attributes)
+}
+
+/// Set the attributes.xml settings a profile carries; an empty map removes the section
+#[tauri::command]
+fn set_profile_game_attributes(
+    file_path: String,
+    attributes: std::collections::BTreeMap<String, String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    controls_file.game_attributes = attributes;
+    controls_file.touch();
+
+    std::fs::write(&file_path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    info!("Game attributes updated in {}", file_path);
+    Ok(())
+}
+
+/// Write the profile's game attributes into the attributes.xml next to `actionmaps_path`,
+/// backing it up first. Like actionmaps.xml, the game rewrites the file on exit, so this
+/// refuses while it runs from the same installation unless `force` is set.
+#[tauri::command]
+fn apply_profile_game_attributes(
+    file_path: String,
+    actionmaps_path: String,
+    force: bool,
+    app_handle: tauri::AppHandle,
+) -> Result<game_attributes::AttributesApplyResult, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    if let Some(process) = game_process::running_for(&actionmaps_path) {
+        if !force {
+            return Err(format!(
+                "Star Citizen is running from this installation (pid {}) and will overwrite \
+                 attributes.xml when it exits. Close the game first, or apply anyway.",
+                process.pid
+            ));
+        }
+    }
+
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let controls_file = controls::ControlsFile::from_json(&json)?;
+
+    let result = game_attributes::apply(
+        &game_attributes::path_for(std::path::Path::new(&actionmaps_path)),
+        &controls_file.game_attributes,
+        &backup_location(&app_handle)?,
+    )?;
+    info!(
+        "Applied {} game attribute change(s) from {}",
+        result.changes.len(),
+        file_path
+    );
+    Ok(result)
+}
+
+/// Start feeding vJoy from the physical device named in the profile's feeder section,
+/// replacing a feeder already running
+#[tauri::command]
+fn start_vjoy_feeder(
+    file_path: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<vjoy_feeder::FeederStatus, String> {
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let config = controls::ControlsFile::from_json(&json)?
+        .feeder
+        .ok_or_else(|| "The profile has no vJoy feeder configuration".to_string())?;
+
+    let device_path = device_identities(&app_handle)?
+        .devices
+        .into_iter()
+        .find(|device| device.identity == config.device)
+        .map(|device| device.path)
+        .ok_or_else(|| format!("Device {} is not connected", config.device))?;
+
+    let mut app_state = state.lock().unwrap();
+    // Release the vJoy device before the new feeder acquires it
+    app_state.vjoy_feeder = None;
+    let feeder = vjoy_feeder::VJoyFeeder::start(device_path, config)?;
+    let status = feeder.status();
+    app_state.vjoy_feeder = Some(feeder);
+    Ok(status)
+}
+
+#[tauri::command]
+fn stop_vjoy_feeder(state: tauri::State<Mutex<AppState>>) {
+    if state.lock().unwrap().vjoy_feeder.take().is_some() {
+        info!("vJoy feeder stopped");
+    }
+}
+
+#[tauri::command]
+fn get_vjoy_feeder_status(state: tauri::State<Mutex<AppState>>) -> vjoy_feeder::FeederStatus {
+    state
+        .lock()
+        .unwrap()
+        .vjoy_feeder
+        .as_ref()
+        .map(|feeder| feeder.status())
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+fn get_connected_devices(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<device_nicknames::NamedDevice<directinput::DeviceInfo>>, String> {
+    let devices = diagnostics::first_use(&DEVICE_ENUMERATION_INIT, "device enumeration", || {
+        directinput::list_connected_devices()
+    })?;
+    Ok(load_device_nicknames(&app_handle)?.name_devices(devices, |d| Some(d.uuid.as_str())))
+}
+
+/// Directory holding the per-machine device nicknames
+fn device_nicknames_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+fn load_device_nicknames(
+    app_handle: &tauri::AppHandle,
+) -> Result<device_nicknames::DeviceNicknames, String> {
+    device_nicknames::load_nicknames(&device_nicknames_dir(app_handle)?)
+}
+
+#[tauri::command]
+fn get_device_nicknames(
+    app_handle: tauri::AppHandle,
+) -> Result<device_nicknames::DeviceNicknames, String> {
+    load_device_nicknames(&app_handle)
+}
+
+/// Give a device a nickname, or clear it with an empty one. `identity` is the device's
+/// "vid:pid" uuid, product GUID or SC Product string, or a "vid:pid#n" identity from
+/// get_device_identities to name one of several identical devices.
+#[tauri::command]
+fn set_device_nickname(
+    identity: String,
+    nickname: String,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let dir = device_nicknames_dir(&app_handle)?;
+    let mut nicknames = device_nicknames::load_nicknames(&dir)?;
+    nicknames.set(&identity, &nickname)?;
+    device_nicknames::save_nicknames(&dir, &nicknames)?;
+    info!("Device {} nicknamed '{}'", identity, nickname.trim());
+    Ok(())
+}
+
+/// Directory holding the remembered identities of identical devices
+fn device_identities_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Number the connected HID game controllers so that devices sharing a product and
+/// VID/PID can be told apart, keeping each one's "vid:pid#n" identity across sessions.
+/// The report warns when the numbering relies on something that can change.
+#[tauri::command]
+fn get_device_identities(
+    app_handle: tauri::AppHandle,
+) -> Result<device_identity::IdentityReport, String> {
+    device_identities(&app_handle)
+}
+
+fn device_identities(
+    app_handle: &tauri::AppHandle,
+) -> Result<device_identity::IdentityReport, String> {
+    assign_device_identities(app_handle, &hid_reader::list_hid_game_controllers()?)
+}
+
+/// Every HID game controller with its VID/PID, serial number, usage page, interface and
+/// path straight from the HID stack, plus the identity that stays with the physical unit
+#[tauri::command]
+fn enumerate_hid_controllers(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<device_identity::RawHidController>, String> {
+    let devices = hid_reader::list_hid_game_controllers()?;
+    let report = assign_device_identities(&app_handle, &devices)?;
+    Ok(device_identity::with_identities(devices, &report))
+}
+
+fn assign_device_identities(
+    app_handle: &tauri::AppHandle,
+    devices: &[hid_reader::HidDeviceListItem],
+) -> Result<device_identity::IdentityReport, String> {
+    let dir = device_identities_dir(app_handle)?;
+    let mut store = device_identity::load_store(&dir)?;
+    let report = store.assign(devices);
+    device_identity::save_store(&dir, &store)?;
+    for warning in &report.warnings {
+        warn!("{}", warning);
+    }
+    Ok(report)
+}
+
+/// Start watching for devices being plugged in or removed ("devices-changed" events).
+/// Devices referenced by the loaded bindings are reported when missing, unless
+/// `expected_products` is given. Calling this again restarts the monitor.
+#[tauri::command]
+fn start_device_monitor(
+    expected_products: Option<Vec<String>>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let mut app_state = state.lock().unwrap();
+
+    let expected_products = expected_products.unwrap_or_else(|| {
+        app_state
+            .current_bindings
+            .as_ref()
+            .map(|bindings| bindings.devices.joysticks.clone())
+            .unwrap_or_default()
+    });
+
+    info!(
+        "Starting device monitor ({} profile devices)",
+        expected_products.len()
+    );
+
+    // Replacing the monitor drops (and stops) any previous one
+    app_state.device_monitor = None;
+    app_state.device_monitor = Some(device_monitor::DeviceMonitor::start(
+        app_handle,
+        expected_products,
+    ));
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_device_monitor(state: tauri::State<Mutex<AppState>>) -> Result<(), String> {
+    let mut app_state = state.lock().unwrap();
+    app_state.device_monitor = None;
+    Ok(())
+}
+
+/// Start streaming live axis/button/hat state as "input-state" events
+#[tauri::command]
+fn start_input_monitor(
+    interval_millis: Option<u64>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let interval = interval_millis.unwrap_or(input_monitor::DEFAULT_INTERVAL_MS);
+    let mut app_state = state.lock().unwrap();
+
+    // Only one stream at a time; dropping the old monitor stops it
+    app_state.input_monitor = None;
+    app_state.input_monitor = Some(input_monitor::InputMonitor::start(
+        app_handle,
+        std::time::Duration::from_millis(interval),
+    ));
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_input_monitor(state: tauri::State<Mutex<AppState>>) -> Result<(), String> {
+    let mut app_state = state.lock().unwrap();
+    app_state.input_monitor = None;
+    Ok(())
+}
+
+/// The running input monitor's state, starting the monitor if it isn't running
+fn input_snapshot(app_handle: &tauri::AppHandle) -> input_monitor::InputSnapshot {
+    let state = app_handle.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    app_state
+        .input_monitor
+        .get_or_insert_with(|| {
+            input_monitor::InputMonitor::start(
+                app_handle.clone(),
+                std::time::Duration::from_millis(input_monitor::DEFAULT_INTERVAL_MS),
+            )
+        })
+        .snapshot()
+}
+
+/// Watch an axis nobody is touching and recommend a deadzone for it
+#[tauri::command]
+async fn calibrate_deadzone(
+    device_uuid: String,
+    axis_id: u32,
+    seconds: Option<f64>,
+    app_handle: tauri::AppHandle,
+) -> Result<axis_calibration::DeadzoneCalibration, String> {
+    let snapshot = input_snapshot(&app_handle);
+    tokio::task::spawn_blocking(move || {
+        let values = axis_calibration::sample_axis(
+            &snapshot,
+            &device_uuid,
+            axis_id,
+            seconds.unwrap_or(axis_calibration::DEFAULT_IDLE_SECONDS),
+        )?;
+        Ok(axis_calibration::calibrate_deadzone(values))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Watch every axis for a while and report the ones that drift or rest off center
+#[tauri::command]
+async fn stick_drift_report(
+    seconds: Option<f64>,
+    app_handle: tauri::AppHandle,
+) -> Result<axis_calibration::DriftReport, String> {
+    let snapshot = input_snapshot(&app_handle);
+    let seconds = seconds.unwrap_or(axis_calibration::DEFAULT_DRIFT_SECONDS);
+    tokio::task::spawn_blocking(move || {
+        let traces = axis_calibration::sample_all(
+            &snapshot,
+            seconds,
+            axis_calibration::DRIFT_SAMPLE_INTERVAL,
+        );
+        if traces.is_empty() {
+            return Err("No axes reported; are any devices connected?".to_string());
+        }
+        let report = axis_calibration::analyze_drift(traces, seconds);
+        info!(
+            "Drift report: {} of {} axes flagged",
+            report.findings.len(),
+            report.axes_checked
+        );
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Record every axis while the user moves each one to its stops, and report the ones
+/// that don't reach full deflection
+#[tauri::command]
+async fn calibrate_axis_ranges(
+    seconds: Option<f64>,
+    app_handle: tauri::AppHandle,
+) -> Result<axis_calibration::RangeReport, String> {
+    let snapshot = input_snapshot(&app_handle);
+    let seconds = seconds.unwrap_or(axis_calibration::DEFAULT_RANGE_SECONDS);
+    tokio::task::spawn_blocking(move || {
+        let traces = axis_calibration::sample_all(
+            &snapshot,
+            seconds,
+            axis_calibration::RANGE_SAMPLE_INTERVAL,
+        );
+        if traces.is_empty() {
+            return Err("No axes reported; are any devices connected?".to_string());
+        }
+        Ok(axis_calibration::analyze_range(traces))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Write the saturations chosen from a range calibration into a .sccontrols profile
+#[tauri::command]
+fn apply_range_saturation(
+    profile_path: String,
+    updates: Vec<axis_calibration::SaturationUpdate>,
+    app_handle: tauri::AppHandle,
+) -> Result<usize, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let json = std::fs::read_to_string(&profile_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    let changed = axis_calibration::apply_saturation(&mut controls_file, &updates)?;
+    if changed > 0 {
+        controls_file.touch();
+        std::fs::write(&profile_path, controls_file.to_json()?)
+            .map_err(|e| format!("Failed to write controls file: {}", e))?;
+    }
+
+    info!(
+        "Applied {} saturation(s) from range calibration to {}",
+        changed, profile_path
+    );
+    Ok(changed)
+}
+
+/// What to ask the user to do before checking an axis' inversion
+#[tauri::command]
+fn get_inversion_prompt(check: inversion_check::InversionCheck) -> String {
+    check.prompt().to_string()
+}
+
+/// Watch an axis while the user makes the prompted movement and propose its invert flag
 #[tauri::command]
-fn detect_joysticks() -> Result<Vec<directinput::JoystickInfo>, String> {
-    directinput::detect_joysticks()
+async fn check_inversion(
+    check: inversion_check::InversionCheck,
+    device_uuid: String,
+    axis_id: u32,
+    seconds: Option<f64>,
+    app_handle: tauri::AppHandle,
+) -> Result<inversion_check::InversionProposal, String> {
+    let snapshot = input_snapshot(&app_handle);
+    tokio::task::spawn_blocking(move || {
+        let values = axis_calibration::sample_axis(
+            &snapshot,
+            &device_uuid,
+            axis_id,
+            seconds.unwrap_or(inversion_check::DEFAULT_CHECK_SECONDS),
+        )?;
+        inversion_check::propose(check, &values)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
 }
 
+/// Write a confirmed invert flag from an inversion check into a .sccontrols profile
 #[tauri::command]
-fn get_connected_devices() -> Result<Vec<directinput::DeviceInfo>, String> {
-    directinput::list_connected_devices()
+fn apply_inversion(
+    profile_path: String,
+    device_type: String,
+    instance: String,
+    check: inversion_check::InversionCheck,
+    invert: bool,
+    app_handle: tauri::AppHandle,
+) -> Result<usize, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let json = std::fs::read_to_string(&profile_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    let changed =
+        inversion_check::apply(&mut controls_file, &device_type, &instance, check, invert)?;
+    if changed > 0 {
+        controls_file.touch();
+        std::fs::write(&profile_path, controls_file.to_json()?)
+            .map_err(|e| format!("Failed to write controls file: {}", e))?;
+    }
+
+    info!(
+        "Set invert={} for {:?} on {} {} in {}",
+        invert, check, device_type, instance, profile_path
+    );
+    Ok(changed)
 }
 
 #[tauri::command]
@@ -87,6 +764,17 @@ fn detect_axis_movement(
     directinput::detect_axis_movement_for_device(&device_uuid, timeout)
 }
 
+/// Press-to-bind: wait for the next button, axis, hat or key combo on any device
+#[tauri::command]
+async fn capture_input_binding(
+    session_id: String,
+    timeout_secs: u64,
+) -> Result<Option<directinput::DetectedInput>, String> {
+    tokio::task::spawn_blocking(move || directinput::capture_next_input(session_id, timeout_secs))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
 #[tauri::command]
 async fn wait_for_input_binding(
     session_id: String,
@@ -118,6 +806,18 @@ async fn wait_for_inputs_with_events(
     .map_err(|e| format!("Task join error: {}", e))?
 }
 
+/// Tell event stream clients that different bindings were loaded, and how
+fn publish_profile_changed(app_state: &AppState, reason: &str) {
+    event_stream::publish(
+        "profile-changed",
+        serde_json::json!({
+            "reason": reason,
+            "file_name": app_state.current_file_name,
+            "profile_name": app_state.current_bindings.as_ref().map(|b| &b.profile_name),
+        }),
+    );
+}
+
 #[tauri::command]
 fn load_keybindings(
     file_path: String,
@@ -129,6 +829,14 @@ fn load_keybindings(
 
     // Parse the XML
     let action_maps = ActionMaps::from_xml(&xml_content)?;
+    let flagged = binding_rules::check_bindings(&action_maps);
+    if !flagged.is_empty() {
+        warn!(
+            "{} binding(s) in {} on problematic keys",
+            flagged.len(),
+            file_path
+        );
+    }
 
     // Extract filename from path
     let file_name = std::path::Path::new(&file_path)
@@ -141,11 +849,25 @@ fn load_keybindings(
     let mut app_state = state.lock().unwrap();
     app_state.current_bindings = Some(action_maps.clone());
     app_state.current_file_name = Some(file_name);
+    publish_profile_changed(&app_state, "loaded");
 
     // Organize the data for the UI
     Ok(action_maps.organize())
 }
 
+/// Loaded bindings on keys known to cause trouble (reserved by the game, Windows
+/// shortcuts), most severe first
+#[tauri::command]
+fn check_binding_rules(state: tauri::State<Mutex<AppState>>) -> Vec<binding_rules::BindingWarning> {
+    let app_state = state.lock().unwrap();
+    app_state
+        .current_bindings
+        .as_ref()
+        .map(binding_rules::check_bindings)
+        .unwrap_or_default()
+}
+
+/// Bind an action, returning warnings when the input is on a problematic key
 #[tauri::command]
 fn update_binding(
     action_map_name: String,
@@ -154,6 +876,39 @@ fn update_binding(
     multi_tap: Option<u32>,
     activation_mode: Option<String>,
     state: tauri::State<Mutex<AppState>>,
+) -> Result<Vec<binding_rules::BindingWarning>, String> {
+    let warnings =
+        binding_rules::check_rebind(&action_map_name, &action_name, &new_input, multi_tap);
+    let changed = serde_json::json!({
+        "action_map": action_map_name,
+        "action": action_name,
+        "input": new_input,
+    });
+    store_binding(
+        action_map_name,
+        action_name,
+        new_input,
+        multi_tap,
+        activation_mode,
+        &state,
+    )?;
+    event_stream::publish("binding-changed", changed);
+    for warning in &warnings {
+        warn!(
+            "{}/{} bound to {}: {}",
+            warning.action_map, warning.action, warning.input, warning.message
+        );
+    }
+    Ok(warnings)
+}
+
+fn store_binding(
+    action_map_name: String,
+    action_name: String,
+    new_input: String,
+    multi_tap: Option<u32>,
+    activation_mode: Option<String>,
+    state: &Mutex<AppState>,
 ) -> Result<(), String> {
     eprintln!("update_binding called with:");
     eprintln!("  action_map_name: '{}'", action_map_name);
@@ -467,6 +1222,110 @@ fn swap_device_prefixes(
     }
 }
 
+/// Renumber joystick instances in actionmaps.xml and, if given, a .sccontrols profile, in one
+/// step. `mapping` maps old to new instance numbers, e.g. {"1": "2", "2": "1"} to swap js1 and
+/// js2 after Windows reordered the devices. Loaded bindings are renumbered to match.
+#[tauri::command]
+fn remap_joystick_instances(
+    actionmaps_path: String,
+    profile_path: Option<String>,
+    mapping: instance_swap::InstanceMapping,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<instance_swap::RemapCounts, String> {
+    let _write_lock = begin_write(&app_handle)?;
+
+    let xml = std::fs::read_to_string(&actionmaps_path)
+        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+    let mut profile = match &profile_path {
+        Some(path) => Some(controls::ControlsFile::from_json(
+            &std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read controls file: {}", e))?,
+        )?),
+        None => None,
+    };
+
+    let mut existing: Vec<String> = controls::parse_actionmaps_options(&xml)?
+        .into_iter()
+        .filter(|d| d.device_type == "joystick")
+        .map(|d| d.instance)
+        .collect();
+    if let Some(joysticks) = profile.as_ref().and_then(|p| p.devices.joystick.as_ref()) {
+        existing.extend(joysticks.keys().cloned());
+    }
+    instance_swap::validate_mapping(&mapping, &existing)?;
+
+    let (new_xml, mut counts) = instance_swap::remap_actionmaps_xml(&xml, &mapping)?;
+
+    // Write the profile first: if that fails, actionmaps.xml is still untouched
+    if let (Some(path), Some(profile)) = (&profile_path, profile.as_mut()) {
+        counts.profile_devices = instance_swap::remap_controls(profile, &mapping);
+        profile.touch();
+        std::fs::write(path, profile.to_json()?)
+            .map_err(|e| format!("Failed to write controls file: {}", e))?;
+    }
+
+    let backup_path = backups::create_backup(&backup_location(&app_handle)?, &actionmaps_path)?;
+    info!("Created backup at: {}", backup_path);
+    modification_log::note_own_write(&new_xml);
+    std::fs::write(&actionmaps_path, new_xml)
+        .map_err(|e| format!("Failed to write actionmaps.xml: {}", e))?;
+
+    if let Some(ref mut bindings) = state.lock().unwrap().current_bindings {
+        instance_swap::remap_bindings(bindings, &mapping);
+    }
+
+    info!(
+        "Renumbered joystick instances {:?}: {} options block(s), {} rebind(s), {} profile device(s)",
+        mapping, counts.options_blocks, counts.rebinds, counts.profile_devices
+    );
+    Ok(counts)
+}
+
+/// Propose which connected joystick each device of the loaded bindings should become,
+/// for profiles made on another machine. Nothing is changed until the mapping is applied.
+#[tauri::command]
+fn propose_device_remap(
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<device_remap::RemapProposal, String> {
+    let devices = {
+        let app_state = state.lock().unwrap();
+        let bindings = app_state
+            .current_bindings
+            .as_ref()
+            .ok_or("No bindings loaded")?;
+        device_remap::profile_devices(bindings)
+    };
+    let connected = diagnostics::first_use(&DEVICE_ENUMERATION_INIT, "device enumeration", || {
+        device_capabilities::enumerate_devices()
+    })?;
+    Ok(device_remap::propose(&devices, &connected))
+}
+
+/// Apply a (possibly user-adjusted) device remap to the loaded bindings: every jsN_
+/// binding and joystick options block is renumbered. Returns the number of rebinds changed.
+#[tauri::command]
+fn apply_device_remap(
+    mapping: instance_swap::InstanceMapping,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<usize, String> {
+    let mut app_state = state.lock().unwrap();
+    let bindings = app_state
+        .current_bindings
+        .as_mut()
+        .ok_or("No bindings loaded")?;
+
+    let existing: Vec<String> = device_remap::profile_devices(bindings)
+        .into_iter()
+        .map(|d| d.instance)
+        .collect();
+    instance_swap::validate_mapping(&mapping, &existing)?;
+
+    let count = instance_swap::remap_bindings(bindings, &mapping);
+    info!("Remapped devices {:?}: {} rebind(s)", mapping, count);
+    Ok(count)
+}
+
 #[tauri::command]
 fn get_current_bindings(
     state: tauri::State<Mutex<AppState>>,
@@ -517,33 +1376,12 @@ fn export_keybindings(
                 if device.device_type == "Joystick" {
                     // Build Product string in Star Citizen format
                     // Format: " DeviceName    {GUID}"
-                    let product_string = if let Some(ref uuid) = device.uuid {
-                        // Convert uuid format "vendor_id:product_id" (e.g., "231d:0200")
-                        // to SC GUID format: {PPPPVVVV-0000-0000-0000-504944564944}
-                        // Example: vendor=0x231D, product=0x0200 -> {0200231D-...}
-                        info!("Device UUID: {}", uuid);
-                        let parts: Vec<&str> = uuid.split(':').collect();
-                        info!("Split parts: {:?}", parts);
-                        if parts.len() == 2 {
-                            // Pad each part to 4 hex digits and uppercase
-                            let vendor_hex = format!("{:0>4}", parts[0].to_uppercase());
-                            let product_hex = format!("{:0>4}", parts[1].to_uppercase());
-                            info!("vendor_hex: {}, product_hex: {}", vendor_hex, product_hex);
-
-                            // Use product_name if available, otherwise fall back to name
-                            let device_display_name =
-                                device.product_name.as_ref().unwrap_or(&device.name);
-
-                            format!(
-                                " {}    {{{}{}-0000-0000-0000-504944564944}}",
-                                device_display_name, product_hex, vendor_hex
-                            )
-                        } else {
-                            format!(" {}", device.name)
-                        }
-                    } else {
-                        format!(" {}", device.name)
-                    };
+                    // Use product_name if available, otherwise fall back to name
+                    let device_display_name = device.product_name.as_ref().unwrap_or(&device.name);
+                    let product_string = product_names::sc_product_string(
+                        device_display_name,
+                        device.uuid.as_deref(),
+                    );
 
                     bindings.devices.joysticks.push(product_string);
                     info!(
@@ -596,6 +1434,335 @@ fn export_keybindings(
     }
 }
 
+/// Export only the keyboard/mouse binds of the loaded profile, for sharing with players
+/// who don't have the same HOTAS hardware. Returns the number of binds exported.
+#[tauri::command]
+fn export_keyboard_only_profile(
+    file_path: String,
+    profile_name: String,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<usize, String> {
+    let app_state = state.lock().unwrap();
+
+    let bindings = app_state
+        .current_bindings
+        .as_ref()
+        .ok_or("No keybindings loaded to export")?;
+
+    let keyboard_profile =
+        bindings.keyboard_mouse_only(profile_library::sanitize_profile_name(&profile_name)?);
+    let count = keyboard_profile.rebind_count();
+
+    let xml_content = keyboard_profile.to_xml_with_categories(app_state.all_binds.as_ref());
+    std::fs::write(&file_path, xml_content)
+        .map_err(|e| format!("Failed to write keybindings file: {}", e))?;
+
+    info!(
+        "Exported keyboard-only profile with {} binds to {}",
+        count, file_path
+    );
+    Ok(count)
+}
+
+/// Export the loaded bindings to a CSV file, one row per binding
+#[tauri::command]
+fn export_bindings_csv(
+    file_path: String,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let app_state = state.lock().unwrap();
+    let bindings = app_state
+        .current_bindings
+        .as_ref()
+        .ok_or("No keybindings loaded to export")?;
+    std::fs::write(&file_path, spreadsheet::bindings_to_csv(bindings))
+        .map_err(|e| format!("Failed to write CSV: {}", e))?;
+    info!("Exported bindings to {}", file_path);
+    Ok(())
+}
+
+/// Import bindings edited in a spreadsheet. Every action in the file gets the bindings of
+/// its rows; nothing changes if any row names an unknown action or input. With
+/// `profile_path`, conflicts are resolved and recorded in that profile's transcript.
+#[tauri::command]
+fn import_bindings_csv(
+    file_path: String,
+    profile_path: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<spreadsheet::CsvImportReport, String> {
+    // Recording resolutions writes the profile's transcript
+    let write_lock = match &profile_path {
+        Some(_) => Some(begin_write(&app_handle)?),
+        None => None,
+    };
+    let csv = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    let mut app_state = state.lock().unwrap();
+    let (known, defaults) = installed_identifiers(&app_handle, &mut app_state)?;
+
+    let mut bindings = app_state.current_bindings.clone().unwrap_or(ActionMaps {
+        profile_name: "User Customizations".to_string(),
+        action_maps: Vec::new(),
+        categories: Vec::new(),
+        devices: keybindings::DeviceInfo {
+            keyboards: Vec::new(),
+            mice: Vec::new(),
+            joysticks: Vec::new(),
+            device_options: Vec::new(),
+        },
+    });
+    let before = bindings.clone();
+    let report = spreadsheet::import_bindings_csv(&csv, &mut bindings, &known, &defaults)?;
+    if report.problems.is_empty() {
+        if let (Some(profile_path), Some(write_lock)) = (&profile_path, &write_lock) {
+            resolve_apply_conflicts(profile_path, "import", &before, &mut bindings, write_lock)?;
+        }
+        app_state.current_bindings = Some(bindings);
+        publish_profile_changed(&app_state, "imported");
+        info!(
+            "Imported bindings for {} action(s) from {}",
+            report.imported, file_path
+        );
+    }
+    Ok(report)
+}
+
+/// Export a .sccontrols profile's device options to a CSV file, one row per option
+#[tauri::command]
+fn export_options_csv(profile_path: String, file_path: String) -> Result<(), String> {
+    let json = std::fs::read_to_string(&profile_path)
+        .map_err(|e| format!("Failed to read {}: {}", profile_path, e))?;
+    let controls_file = controls::ControlsFile::from_json(&json)?;
+    std::fs::write(&file_path, spreadsheet::options_to_csv(&controls_file))
+        .map_err(|e| format!("Failed to write CSV: {}", e))?;
+    info!("Exported options of {} to {}", profile_path, file_path);
+    Ok(())
+}
+
+/// Import device options edited in a spreadsheet into a .sccontrols profile. Nothing is
+/// saved if any row names an unknown option or has a value out of range.
+#[tauri::command]
+fn import_options_csv(
+    file_path: String,
+    profile_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<spreadsheet::CsvImportReport, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let csv = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    let json = std::fs::read_to_string(&profile_path)
+        .map_err(|e| format!("Failed to read {}: {}", profile_path, e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    let known = {
+        let state = app_handle.state::<Mutex<AppState>>();
+        let mut app_state = state.lock().unwrap();
+        installed_identifiers(&app_handle, &mut app_state)?.0
+    };
+    let report = spreadsheet::import_options_csv(&csv, &mut controls_file, &known)?;
+    if report.problems.is_empty() {
+        controls_file.record_change(report.imported, Some("Imported from CSV"));
+        controls_file.touch();
+        std::fs::write(&profile_path, controls_file.to_json()?)
+            .map_err(|e| format!("Failed to write controls file: {}", e))?;
+        info!(
+            "Imported {} option(s) from {} into {}",
+            report.imported, file_path, profile_path
+        );
+    }
+    Ok(report)
+}
+
+/// Export a printable cheat sheet of the loaded profile, grouped by device and action map.
+/// `localization_path` optionally points at the game's global.ini to resolve "@ui_" labels.
+#[tauri::command]
+fn export_cheat_sheet(
+    file_path: String,
+    format: cheat_sheet::CheatSheetFormat,
+    localization_path: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let nicknames = load_device_nicknames(&app_handle)?;
+    let localization = match localization_path {
+        Some(path) => cheat_sheet::parse_localization(
+            &std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read localization file: {}", e))?,
+        ),
+        None => Default::default(),
+    };
+
+    let app_state = state.lock().unwrap();
+    let bindings = app_state
+        .current_bindings
+        .as_ref()
+        .ok_or("No keybindings loaded to export")?;
+    let all_binds = app_state.all_binds.as_ref();
+
+    match format {
+        cheat_sheet::CheatSheetFormat::Html => {
+            let html = cheat_sheet::render_html(bindings, all_binds, &localization, &nicknames);
+            std::fs::write(&file_path, html)
+                .map_err(|e| format!("Failed to write cheat sheet: {}", e))?;
+        }
+        cheat_sheet::CheatSheetFormat::Markdown => {
+            let md = cheat_sheet::render_markdown(bindings, all_binds, &localization, &nicknames);
+            std::fs::write(&file_path, md)
+                .map_err(|e| format!("Failed to write cheat sheet: {}", e))?;
+        }
+        cheat_sheet::CheatSheetFormat::Pdf => {
+            let html = cheat_sheet::render_html(bindings, all_binds, &localization, &nicknames);
+            drop(app_state);
+            cheat_sheet::render_pdf(&html, std::path::Path::new(&file_path))?;
+        }
+    }
+
+    info!("Exported cheat sheet to {}", file_path);
+    Ok(())
+}
+
+/// Render a reference card for each device page of a joystick template, with the loaded
+/// profile's actions on its controls, into `output_dir` as SVG or PNG.
+/// `localization_path` optionally points at the game's global.ini to resolve "@ui_" labels.
+#[tauri::command]
+fn export_reference_cards(
+    template_path: String,
+    output_dir: String,
+    format: reference_cards::CardFormat,
+    localization_path: Option<String>,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<Vec<reference_cards::ReferenceCard>, String> {
+    let localization = match localization_path {
+        Some(path) => cheat_sheet::parse_localization(
+            &std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read localization file: {}", e))?,
+        ),
+        None => Default::default(),
+    };
+
+    let labels = {
+        let app_state = state.lock().unwrap();
+        let bindings = app_state
+            .current_bindings
+            .as_ref()
+            .ok_or("No keybindings loaded to export")?;
+        reference_cards::binding_labels(bindings, app_state.all_binds.as_ref(), &localization)
+    };
+
+    let cards = reference_cards::export_cards(
+        std::path::Path::new(&template_path),
+        std::path::Path::new(&output_dir),
+        format,
+        &labels,
+    )?;
+    info!(
+        "Exported {} reference card(s) to {}",
+        cards.len(),
+        output_dir
+    );
+    Ok(cards)
+}
+
+/// Export a VoiceAttack profile to `file_path` with a voice command for every keyboard-bound
+/// action, so the profile can be regenerated after a rebind. `localization_path` optionally
+/// points at the game's global.ini to turn "@ui_" labels into spoken phrases.
+#[tauri::command]
+fn export_voiceattack_profile(
+    file_path: String,
+    profile_name: String,
+    localization_path: Option<String>,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<voiceattack::VoiceAttackExport, String> {
+    let localization = match localization_path {
+        Some(path) => cheat_sheet::parse_localization(
+            &std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read localization file: {}", e))?,
+        ),
+        None => Default::default(),
+    };
+
+    let export = {
+        let app_state = state.lock().unwrap();
+        let all_binds = app_state
+            .all_binds
+            .as_ref()
+            .ok_or("AllBinds.xml not loaded. Please restart the application.")?;
+        let merged = all_binds.merge_with_user_bindings(app_state.current_bindings.as_ref());
+        voiceattack::export_profile(&profile_name, &merged, &localization)
+    };
+
+    std::fs::write(&file_path, &export.xml)
+        .map_err(|e| format!("Failed to write VoiceAttack profile: {}", e))?;
+    info!(
+        "Exported {} VoiceAttack command(s) to {} ({} skipped)",
+        export.commands,
+        file_path,
+        export.skipped.len()
+    );
+    Ok(export)
+}
+
+// An axis response sampled for the curve editor, with the live axis position on it
+#[derive(serde::Serialize)]
+struct CurveEvaluation {
+    samples: Vec<curve_eval::CurveSample>,
+    live: Option<curve_eval::CurveSample>,
+}
+
+/// Sample an axis response over 0..1 for the curve editor graph. Given `device_uuid` and
+/// `axis_id`, the axis' current position from the running input monitor is evaluated too.
+#[tauri::command]
+fn evaluate_curve(
+    curve: curve_eval::AxisCurve,
+    samples: Option<usize>,
+    device_uuid: Option<String>,
+    axis_id: Option<u32>,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<CurveEvaluation, String> {
+    let position = match (device_uuid, axis_id) {
+        (Some(device_uuid), Some(axis_id)) => state
+            .lock()
+            .unwrap()
+            .input_monitor
+            .as_ref()
+            .and_then(|monitor| {
+                monitor
+                    .latest()
+                    .into_iter()
+                    .find(|device| device.device_uuid == device_uuid)?
+                    .axes
+                    .into_iter()
+                    .find(|axis| axis.axis_id == axis_id)
+            })
+            .map(|axis| axis.value as f64),
+        _ => None,
+    };
+
+    Ok(CurveEvaluation {
+        samples: curve.sample(samples.unwrap_or(curve_eval::DEFAULT_SAMPLES)),
+        live: position.map(|input| curve_eval::CurveSample {
+            input,
+            output: curve.evaluate(input),
+        }),
+    })
+}
+
+/// Curve points approximating an exponent, for switching an option to curve mode
+#[tauri::command]
+fn exponent_to_curve(exponent: f64, points: Option<usize>) -> Vec<controls::CurvePoint> {
+    curve_convert::exponent_to_points(
+        exponent,
+        points.unwrap_or(curve_convert::DEFAULT_CURVE_POINTS),
+    )
+}
+
+/// The exponent closest to a curve, for switching an option to exponent mode
+#[tauri::command]
+fn curve_to_exponent(points: Vec<controls::CurvePoint>) -> curve_convert::ExponentFit {
+    curve_convert::fit_exponent(&points)
+}
+
 // Template management commands
 #[tauri::command]
 fn save_template(file_path: String, template_json: String) -> Result<(), String> {
@@ -643,12 +1810,14 @@ fn load_all_binds(
     let xml_content = std::fs::read_to_string(&all_binds_path)
         .map_err(|e| format!("Failed to read AllBinds.xml at {:?}: {}", all_binds_path, e))?;
 
-    // Parse the XML
-    let all_binds = AllBinds::from_xml(&xml_content)?;
+    // Parse the XML, or reuse the model parsed from this same file
+    let (all_binds, catalog) =
+        default_profile_cache::load_or_parse(&app_cache_dir(&app_handle)?, &xml_content)?;
 
     // Store in state
     let mut app_state = state.lock().unwrap();
     app_state.all_binds = Some(all_binds);
+    app_state.option_catalog = Some(catalog);
 
     Ok(())
 }
@@ -697,6 +1866,193 @@ fn get_merged_bindings(state: tauri::State<Mutex<AppState>>) -> Result<MergedBin
     }
 }
 
+/// Rank the merged actions against `query` across action names, labels (localized
+/// through the global.ini at `localization_path` if given), bound inputs and device names
+#[tauri::command]
+fn search_actions(
+    query: String,
+    limit: Option<usize>,
+    localization_path: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<Vec<search::SearchResult>, String> {
+    let nicknames = load_device_nicknames(&app_handle)?;
+    let mut app_state = state.lock().unwrap();
+
+    // global.ini is large; parse it once per path rather than per keystroke
+    match &localization_path {
+        Some(path) if app_state.localization.as_ref().map(|(p, _)| p) != Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read localization file: {}", e))?;
+            app_state.localization =
+                Some((path.clone(), cheat_sheet::parse_localization(&content)));
+        }
+        Some(_) => {}
+        None => app_state.localization = None,
+    }
+
+    let all_binds = app_state
+        .all_binds
+        .as_ref()
+        .ok_or("AllBinds.xml not loaded. Please restart the application.")?;
+    let merged = all_binds.merge_with_user_bindings(app_state.current_bindings.as_ref());
+    let empty = std::collections::HashMap::new();
+    let context = search::SearchContext {
+        localization: app_state
+            .localization
+            .as_ref()
+            .map(|(_, table)| table)
+            .unwrap_or(&empty),
+        joysticks: app_state
+            .current_bindings
+            .as_ref()
+            .map(|b| b.devices.joysticks.as_slice())
+            .unwrap_or_default(),
+        nicknames: &nicknames,
+    };
+    Ok(search::search(
+        &merged,
+        &query,
+        limit.unwrap_or(search::DEFAULT_LIMIT),
+        &context,
+    ))
+}
+
+/// Actions grouped the way the in-game keybinding screen shows them: UICategory tabs,
+/// UILabel headings, then actions
+#[tauri::command]
+fn get_action_categories(
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<action_categories::ActionCategories, String> {
+    let app_state = state.lock().unwrap();
+    let all_binds = app_state
+        .all_binds
+        .as_ref()
+        .ok_or("AllBinds.xml not loaded. Please restart the application.")?;
+    Ok(action_categories::build(all_binds))
+}
+
+/// Critical actions (eject, flight ready, quantum, power triangle...) that the loaded
+/// actionmaps leave without a binding on any device
+#[tauri::command]
+fn get_critical_unbound_actions(
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<critical_actions::CriticalActionsReport, String> {
+    let app_state = state.lock().unwrap();
+    let all_binds = app_state
+        .all_binds
+        .as_ref()
+        .ok_or("AllBinds.xml not loaded. Please restart the application.")?;
+    let merged = all_binds.merge_with_user_bindings(app_state.current_bindings.as_ref());
+    Ok(critical_actions::check(&merged))
+}
+
+/// Action and option names the installed defaultProfile has, and the gamepad and mouse
+/// inputs it binds
+fn installed_identifiers(
+    app_handle: &tauri::AppHandle,
+    app_state: &mut AppState,
+) -> Result<
+    (
+        sc_migration::KnownIdentifiers,
+        identifier_check::DefaultInputs,
+    ),
+    String,
+> {
+    let catalog = diagnostics::lazy_init(&mut app_state.option_catalog, "option catalog", || {
+        option_catalog::parse_option_catalog(&get_all_binds_xml(app_handle.clone())?)
+    })?
+    .clone();
+    let all_binds = app_state
+        .all_binds
+        .as_ref()
+        .ok_or("AllBinds.xml not loaded. Please restart the application.")?;
+    Ok((
+        sc_migration::KnownIdentifiers::new(all_binds, &catalog),
+        identifier_check::DefaultInputs::new(all_binds),
+    ))
+}
+
+/// Identifiers the installed game version doesn't know, in the loaded bindings and, if
+/// given, a .sccontrols profile. Nothing is changed.
+#[tauri::command]
+fn check_installed_identifiers(
+    profile_path: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<Vec<identifier_check::ValidationWarning>, String> {
+    let profile = match &profile_path {
+        Some(path) => Some(controls::ControlsFile::from_json(
+            &std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read controls file: {}", e))?,
+        )?),
+        None => None,
+    };
+
+    let mut app_state = state.lock().unwrap();
+    let (known, inputs) = installed_identifiers(&app_handle, &mut app_state)?;
+    let mut warnings = Vec::new();
+    if let Some(bindings) = &app_state.current_bindings {
+        warnings.extend(identifier_check::check_bindings(bindings, &known, &inputs));
+    }
+    if let Some(profile) = &profile {
+        warnings.extend(identifier_check::check_controls(profile, &known));
+    }
+    Ok(warnings)
+}
+
+/// Bring the loaded bindings and, if given, a .sccontrols profile up to date with the
+/// installed defaultProfile: identifiers a patch renamed are renamed, removed ones dropped
+/// and any others it doesn't have reported. With `dry_run` only the report is returned.
+#[tauri::command]
+fn migrate_to_installed_version(
+    profile_path: Option<String>,
+    dry_run: bool,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<sc_migration::MigrationReport, String> {
+    let _write_lock = begin_write(&app_handle)?;
+
+    let mut profile = match &profile_path {
+        Some(path) => Some(controls::ControlsFile::from_json(
+            &std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read controls file: {}", e))?,
+        )?),
+        None => None,
+    };
+
+    let mut app_state = state.lock().unwrap();
+    let (known, _) = installed_identifiers(&app_handle, &mut app_state)?;
+
+    let mut report = sc_migration::MigrationReport::default();
+    let mut bindings = app_state.current_bindings.clone();
+    if let Some(bindings) = bindings.as_mut() {
+        sc_migration::migrate_bindings(bindings, &known, &mut report);
+    }
+    if let Some(profile) = profile.as_mut() {
+        sc_migration::migrate_controls(profile, &known, &mut report);
+    }
+
+    info!(
+        "Migration to the installed defaultProfile: {} renamed, {} dropped, {} need attention{}",
+        report.renamed.len(),
+        report.dropped.len(),
+        report.needs_attention.len(),
+        if dry_run { " (dry run)" } else { "" }
+    );
+    if dry_run || !report.changed() {
+        return Ok(report);
+    }
+
+    if let (Some(path), Some(profile)) = (&profile_path, profile.as_mut()) {
+        profile.touch();
+        std::fs::write(path, profile.to_json()?)
+            .map_err(|e| format!("Failed to write controls file: {}", e))?;
+    }
+    app_state.current_bindings = bindings;
+    Ok(report)
+}
+
 #[tauri::command]
 fn get_user_customizations(
     state: tauri::State<Mutex<AppState>>,
@@ -743,6 +2099,7 @@ fn restore_user_customizations(
 #[tauri::command]
 fn find_conflicting_bindings(
     input: String,
+    multi_tap: Option<u32>,
     exclude_action_map: String,
     exclude_action: String,
     state: tauri::State<Mutex<AppState>>,
@@ -750,6 +2107,9 @@ fn find_conflicting_bindings(
     let app_state = state.lock().unwrap();
     let mut conflicts = Vec::new();
 
+    // Compare parsed bindings so `kb1_lalt+f` matches `LALT+kb1_f` but not `kb1_f`
+    let wanted = BindingInput::parse(&input, multi_tap).ok();
+
     // Check in current bindings
     if let Some(ref bindings) = app_state.current_bindings {
         for action_map in &bindings.action_maps {
@@ -761,7 +2121,15 @@ fn find_conflicting_bindings(
 
                 // Check if this action has the same input bound
                 for rebind in &action.rebinds {
-                    if rebind.input == input {
+                    let same_input = match (
+                        &wanted,
+                        BindingInput::parse(&rebind.input, rebind.multi_tap),
+                    ) {
+                        (Some(wanted), Ok(existing)) => wanted.conflicts_with(&existing),
+                        // Not something we can parse, fall back to comparing the strings
+                        _ => rebind.input == input,
+                    };
+                    if same_input {
                         conflicts.push(ConflictingBinding {
                             action_map_name: action_map.name.clone(),
                             action_map_label: action_map.name.clone(), // Will be enhanced with UI label
@@ -799,24 +2167,123 @@ fn find_conflicting_bindings(
     Ok(conflicts)
 }
 
+/// Parse an SC binding string into device, modifiers, key and tap count for the bind editor
 #[tauri::command]
-fn clear_specific_binding(
-    action_map_name: String,
-    action_name: String,
-    input_to_clear: String,
-    state: tauri::State<Mutex<AppState>>,
-) -> Result<(), String> {
-    eprintln!("clear_specific_binding called with:");
-    eprintln!("  action_map_name: '{}'", action_map_name);
-    eprintln!("  action_name: '{}'", action_name);
-    eprintln!("  input_to_clear: '{}'", input_to_clear);
+fn parse_binding_input(input: String, multi_tap: Option<u32>) -> Result<BindingInput, String> {
+    BindingInput::parse(&input, multi_tap)
+}
 
-    let mut app_state = state.lock().unwrap();
+/// The SC binding string for a structured binding, e.g. `kb1_lalt+f`
+#[tauri::command]
+fn format_binding_input(binding: BindingInput) -> String {
+    binding.to_sc_string()
+}
 
-    // Determine the input type of the binding to clear
-    let clear_rebind = keybindings::Rebind {
-        input: input_to_clear.clone(),
-        multi_tap: None,
+/// Human readable form of a structured binding, e.g. "Double tap Left Alt + F"
+#[tauri::command]
+fn binding_input_label(binding: BindingInput) -> String {
+    binding.label()
+}
+
+/// Record how the user resolved a binding conflict in the profile's resolution transcript
+#[tauri::command]
+fn record_conflict_resolution(
+    profile_path: String,
+    decision: resolutions::ResolutionDecision,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let path = resolutions::transcript_path(&profile_path);
+    let mut transcript = resolutions::load_transcript(&path)?;
+    transcript.record(decision);
+    resolutions::save_transcript(&path, &transcript)
+}
+
+/// Shared by merges, imports and template applies: conflicts the profile's transcript has
+/// a decision for are resolved that way again, then the resolutions the apply made are
+/// recorded so the next apply repeats them. Returns the recorded decisions it re-applied.
+/// Saving the transcript writes next to the profile, so the caller must hold the write lock.
+fn resolve_apply_conflicts(
+    profile_path: &str,
+    source: &str,
+    before: &ActionMaps,
+    after: &mut ActionMaps,
+    _write_lock: &write_lock::WriteLock,
+) -> Result<Vec<resolutions::ResolutionDecision>, String> {
+    let path = resolutions::transcript_path(profile_path);
+    let mut transcript = resolutions::load_transcript(&path)?;
+    let reapplied = transcript.reapply(after);
+
+    let decisions = resolutions::resolved_by_apply(source, before, after);
+    if !decisions.is_empty() {
+        info!(
+            "Recording {} conflict resolution(s) from {} for {}",
+            decisions.len(),
+            source,
+            profile_path
+        );
+        for decision in decisions {
+            transcript.record(decision);
+        }
+        resolutions::save_transcript(&path, &transcript)?;
+    }
+    Ok(reapplied)
+}
+
+#[tauri::command]
+fn get_conflict_resolutions(
+    profile_path: String,
+) -> Result<resolutions::ResolutionTranscript, String> {
+    resolutions::load_transcript(&resolutions::transcript_path(&profile_path))
+}
+
+/// Resolve any conflicts in the loaded bindings that the user has already decided on for
+/// this profile. Returns the decisions that were applied.
+#[tauri::command]
+fn reapply_conflict_resolutions(
+    profile_path: String,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<Vec<resolutions::ResolutionDecision>, String> {
+    let transcript = resolutions::load_transcript(&resolutions::transcript_path(&profile_path))?;
+
+    let mut app_state = state.lock().unwrap();
+    let bindings = app_state
+        .current_bindings
+        .as_mut()
+        .ok_or("No keybindings loaded")?;
+
+    let applied = transcript.reapply(bindings);
+    info!(
+        "Re-applied {} recorded conflict resolutions for {}",
+        applied.len(),
+        profile_path
+    );
+    Ok(applied)
+}
+
+#[tauri::command]
+fn clear_specific_binding(
+    action_map_name: String,
+    action_name: String,
+    input_to_clear: String,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    eprintln!("clear_specific_binding called with:");
+    eprintln!("  action_map_name: '{}'", action_map_name);
+    eprintln!("  action_name: '{}'", action_name);
+    eprintln!("  input_to_clear: '{}'", input_to_clear);
+    let changed = serde_json::json!({
+        "action_map": action_map_name,
+        "action": action_name,
+        "cleared": input_to_clear,
+    });
+
+    let mut app_state = state.lock().unwrap();
+
+    // Determine the input type of the binding to clear
+    let clear_rebind = keybindings::Rebind {
+        input: input_to_clear.clone(),
+        multi_tap: None,
         activation_mode: String::new(),
     };
     let input_type = clear_rebind.get_input_type();
@@ -913,6 +2380,7 @@ fn clear_specific_binding(
                 }
             }
         }
+        event_stream::publish("binding-changed", changed);
         return Ok(());
     }
 
@@ -976,6 +2444,7 @@ fn clear_specific_binding(
         });
 
         eprintln!("Successfully cleared binding with explicit unbind entry");
+        event_stream::publish("binding-changed", changed);
         Ok(())
     } else {
         Err("Failed to initialize bindings".to_string())
@@ -987,6 +2456,7 @@ fn clear_custom_bindings(state: tauri::State<Mutex<AppState>>) -> Result<(), Str
     let mut app_state = state.lock().unwrap();
     app_state.current_bindings = None;
     app_state.current_file_name = None;
+    publish_profile_changed(&app_state, "cleared");
     Ok(())
 }
 
@@ -1006,6 +2476,8 @@ struct ControlOptionInput {
 struct CurveInput {
     #[serde(default)]
     points: Vec<CurvePoint>,
+    #[serde(default)]
+    interpolation: controls::CurveInterpolation,
 }
 
 #[derive(serde::Deserialize)]
@@ -1114,7 +2586,15 @@ fn update_control_options(
 
                 // Handle curves with nested points
                 if let Some(curve) = opt.curve {
-                    for point in curve.points {
+                    let points: Vec<controls::CurvePoint> = curve
+                        .points
+                        .into_iter()
+                        .map(|p| controls::CurvePoint {
+                            input: p.input,
+                            output: p.output,
+                        })
+                        .collect();
+                    for point in curve_interpolation::resample(&points, curve.interpolation) {
                         curve_points.push(keybindings::CurvePointData {
                             in_val: format!("{}", point.input),
                             out_val: format!("{}", point.output),
@@ -1188,32 +2668,14 @@ fn scan_sc_installations(base_path: String) -> Result<Vec<ScInstallation>, Strin
         return Err("Path is not a directory".to_string());
     }
 
-    let mut installations = Vec::new();
-
-    // Common Star Citizen installation folder names
-    let sc_folders = ["LIVE", "PTU", "EPTU", "TECH-PREVIEW"];
-
-    // Scan for each potential installation
-    for folder_name in &sc_folders {
-        let folder_path = base.join(folder_name);
-
-        // Check if this folder exists
-        if !folder_path.exists() || !folder_path.is_dir() {
-            continue;
-        }
-
-        // Check for data.p4k in the Data folder
-        let data_p4k_path = folder_path.join("data.p4k");
-
-        if data_p4k_path.exists() && data_p4k_path.is_file() {
-            installations.push(ScInstallation {
-                name: folder_name.to_string(),
-                path: folder_path.to_string_lossy().to_string(),
-            });
-        }
-    }
-
-    Ok(installations)
+    // An installation is an environment folder with a data.p4k in it
+    Ok(environments::detect_environments(base)
+        .into_iter()
+        .map(|(name, path)| ScInstallation {
+            name,
+            path: path.to_string_lossy().to_string(),
+        })
+        .collect())
 }
 
 #[tauri::command]
@@ -1231,11 +2693,94 @@ fn get_current_file_name(state: tauri::State<Mutex<AppState>>) -> Result<String,
 fn save_bindings_to_install(
     installation_path: String,
     state: tauri::State<Mutex<AppState>>,
+    app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let mut app_state = state.lock().unwrap();
+
+    // Save under the file name the profile was loaded from
+    let file_name = app_state
+        .current_file_name
+        .as_ref()
+        .ok_or_else(|| "No filename stored".to_string())?
+        .clone();
+
+    write_bindings_to_mappings(&installation_path, &file_name, &mut app_state, &app_handle)?;
+    Ok(())
+}
+
+// Result of exporting a profile to the game's mappings folder
+#[derive(serde::Serialize)]
+struct MappingExport {
+    path: String,
+    /// Console command that loads the profile in-game
+    console_command: String,
+    /// Console script running the command, when one was written
+    script_path: Option<String>,
+    /// Steps for loading the profile in a running game
+    instructions: Vec<String>,
+}
+
+/// Export the loaded profile into the installation's mappings folder under a chosen name, so
+/// it can be loaded in-game with `pp_rebindkeys` without touching actionmaps.xml. With
+/// `console_script`, a `boxxy_<name>.cfg` running the command is written to the installation
+/// folder as well.
+#[tauri::command]
+fn export_to_mappings(
+    installation_path: String,
+    mapping_name: String,
+    console_script: Option<bool>,
+    state: tauri::State<Mutex<AppState>>,
+    app_handle: tauri::AppHandle,
+) -> Result<MappingExport, String> {
+    // Exporting stays available in read-only mode, so only the cross-instance lock is taken
+    let _write_lock = write_lock::WriteLock::acquire(
+        &write_lock_dir(&app_handle)?,
+        "gui",
+        write_lock::DEFAULT_WAIT,
+    )?;
+    let mapping_name = console_script::mapping_name(&mapping_name)?;
+    let with_script = console_script.unwrap_or(false);
+
+    let mut app_state = state.lock().unwrap();
+    let target_file = write_bindings_to_mappings(
+        &installation_path,
+        &format!("{}.xml", mapping_name),
+        &mut app_state,
+        &app_handle,
+    )?;
+
+    info!("Exported profile to {}", target_file.display());
+
+    let script_path = if with_script {
+        let path = std::path::Path::new(&installation_path)
+            .join(console_script::script_file_name(&mapping_name));
+        std::fs::write(&path, console_script::script(&mapping_name))
+            .map_err(|e| format!("Failed to write console script: {}", e))?;
+        Some(path.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    Ok(MappingExport {
+        path: target_file.to_string_lossy().to_string(),
+        console_command: console_script::rebind_command(&mapping_name),
+        script_path,
+        instructions: console_script::instructions(&mapping_name, with_script),
+    })
+}
+
+/// Write the loaded bindings into `INSTALL\user\client\0\controls\mappings\<file_name>`
+fn write_bindings_to_mappings(
+    installation_path: &str,
+    file_name: &str,
+    app_state: &mut AppState,
+    app_handle: &tauri::AppHandle,
+) -> Result<std::path::PathBuf, String> {
     use std::path::Path;
 
     // First, verify the installation path still exists
-    let install_path = Path::new(&installation_path);
+    let install_path = Path::new(installation_path);
     if !install_path.exists() {
         return Err(format!(
             "Installation folder no longer exists: {}",
@@ -1249,15 +2794,6 @@ fn save_bindings_to_install(
         ));
     }
 
-    let mut app_state = state.lock().unwrap();
-
-    // Get the filename first (before mutable borrow)
-    let file_name = app_state
-        .current_file_name
-        .as_ref()
-        .ok_or_else(|| "No filename stored".to_string())?
-        .clone();
-
     // Get AllBinds reference (before mutable borrow)
     let all_binds_option = app_state.all_binds.as_ref().map(|ab| ab.clone());
 
@@ -1270,8 +2806,9 @@ fn save_bindings_to_install(
     // Always regenerate device Product strings from detected devices on export
     // This ensures GUIDs are always correct and up-to-date
     bindings.devices.joysticks.clear();
+    let detected_devices = directinput::detect_joysticks().unwrap_or_default();
     {
-        if let Ok(detected_devices) = directinput::detect_joysticks() {
+        if !detected_devices.is_empty() {
             info!(
                 "Populating device Product strings from {} detected devices",
                 detected_devices.len()
@@ -1279,32 +2816,11 @@ fn save_bindings_to_install(
 
             for (idx, device) in detected_devices.iter().enumerate() {
                 if device.device_type == "Joystick" {
-                    let product_string = if let Some(ref uuid) = device.uuid {
-                        // Convert uuid format "vendor_id:product_id" to SC GUID format
-                        let parts: Vec<&str> = uuid.split(':').collect();
-                        if parts.len() == 2 {
-                            // Pad each part to 4 hex digits and uppercase
-                            let vendor_hex = format!("{:0>4}", parts[0].to_uppercase());
-                            let product_hex = format!("{:0>4}", parts[1].to_uppercase());
-
-                            // Use product_name if available, otherwise fall back to name
-                            let device_display_name =
-                                device.product_name.as_ref().unwrap_or(&device.name);
-
-                            format!(
-                                " {}    {{{}{}-0000-0000-0000-504944564944}}",
-                                device_display_name, product_hex, vendor_hex
-                            )
-                        } else {
-                            let device_display_name =
-                                device.product_name.as_ref().unwrap_or(&device.name);
-                            format!(" {}", device_display_name)
-                        }
-                    } else {
-                        let device_display_name =
-                            device.product_name.as_ref().unwrap_or(&device.name);
-                        format!(" {}", device_display_name)
-                    };
+                    let device_display_name = device.product_name.as_ref().unwrap_or(&device.name);
+                    let product_string = product_names::sc_product_string(
+                        device_display_name,
+                        device.uuid.as_deref(),
+                    );
 
                     bindings.devices.joysticks.push(product_string);
                     info!(
@@ -1319,7 +2835,7 @@ fn save_bindings_to_install(
     }
 
     // Build the target path: INSTALL\user\client\0\controls\mappings
-    let target_dir = Path::new(&installation_path)
+    let target_dir = Path::new(installation_path)
         .join("user")
         .join("client")
         .join("0")
@@ -1331,16 +2847,19 @@ fn save_bindings_to_install(
         .map_err(|e| format!("Failed to create directory structure: {}", e))?;
 
     // Full path to the target file
-    let target_file = target_dir.join(&file_name);
+    let target_file = target_dir.join(file_name);
 
-    // Serialize to XML with category information
-    let xml_content = bindings.to_xml_with_categories(all_binds_option.as_ref());
+    // Serialize to XML with category information, resolving any ${VARIABLES} in shared profiles
+    let xml_content = variables::substitute(
+        &bindings.to_xml_with_categories(all_binds_option.as_ref()),
+        &profile_variable_values(app_handle, &detected_devices)?,
+    )?;
 
     // Write to the target location
     std::fs::write(&target_file, xml_content)
         .map_err(|e| format!("Failed to write keybindings file: {}", e))?;
 
-    Ok(())
+    Ok(target_file)
 }
 
 #[tauri::command]
@@ -1364,19 +2883,37 @@ fn log_info(message: String) -> Result<(), String> {
     Ok(())
 }
 
-#[tauri::command]
-fn get_log_file_path(app_handle: tauri::AppHandle) -> Result<String, String> {
-    let log_dir = app_handle
+fn app_log_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
         .path()
         .app_log_dir()
-        .map_err(|e| format!("Failed to get log directory: {}", e))?;
+        .map_err(|e| format!("Failed to get log directory: {}", e))
+}
+
+#[tauri::command]
+fn get_log_file_path(app_handle: tauri::AppHandle) -> Result<String, String> {
+    let log_dir = app_log_dir(&app_handle)?;
 
-    // Use date-based log file name
-    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-    let log_file = log_dir.join(format!("boxxy-binder-{}.log", today));
+    // The file being written to now; the appender names it by date
+    let log_file = logs::log_files(&log_dir)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| log_dir.join(format!("{}.log", logs::LOG_FILE_PREFIX)));
     Ok(log_file.to_string_lossy().to_string())
 }
 
+/// The most recent log lines, for pasting into a bug report
+#[tauri::command]
+fn get_recent_logs(
+    max_lines: Option<usize>,
+    app_handle: tauri::AppHandle,
+) -> Result<logs::RecentLogs, String> {
+    Ok(logs::recent_lines(
+        &app_log_dir(&app_handle)?,
+        max_lines.unwrap_or(logs::DEFAULT_RECENT_LINES),
+    ))
+}
+
 #[tauri::command]
 fn get_resource_dir(app_handle: tauri::AppHandle) -> Result<String, String> {
     let resource_dir = if cfg!(debug_assertions) {
@@ -1413,78 +2950,19 @@ async fn open_url(app_handle: tauri::AppHandle, url: String) -> Result<(), Strin
 }
 
 fn setup_logging(app_handle: &tauri::AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-    use std::fs::OpenOptions;
-    use std::io::Write;
-
     // Get log directory
     let log_dir = app_handle.path().app_log_dir()?;
     std::fs::create_dir_all(&log_dir)?;
 
-    // Use date-based log file name
-    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-    let log_file = log_dir.join(format!("boxxy-binder-{}.log", today));
-
-    // Clean up old log files (keep only the last 3 days)
-    cleanup_old_logs(&log_dir, 3);
-
-    // Set up file logging with env_logger
-    let target = Box::new(
-        OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&log_file)?,
-    );
-
-    env_logger::Builder::from_default_env()
-        .target(env_logger::Target::Pipe(target))
-        .format(|buf, record| {
-            writeln!(
-                buf,
-                "[{}] {} - {}",
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-                record.level(),
-                record.args()
-            )
-        })
-        .init();
+    logs::init(&log_dir)?;
 
     info!("=== SC Joy Mapper Started ===");
     info!("Version: {}", env!("CARGO_PKG_VERSION"));
-    info!("Log file: {:?}", log_file);
+    info!("Log directory: {:?}", log_dir);
 
     Ok(())
 }
 
-/// Clean up old log files, keeping only the specified number of most recent files
-fn cleanup_old_logs(log_dir: &std::path::Path, keep_count: usize) {
-    use std::fs;
-
-    let entries = match fs::read_dir(log_dir) {
-        Ok(entries) => entries,
-        Err(_) => return,
-    };
-
-    // Collect all log files matching our pattern
-    let mut log_files: Vec<_> = entries
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
-            name_str.starts_with("boxxy-binder-") && name_str.ends_with(".log")
-        })
-        .collect();
-
-    // Sort by filename (which contains the date, so alphabetical order = chronological order)
-    log_files.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
-
-    // Remove files beyond the keep count
-    for file in log_files.iter().skip(keep_count) {
-        if let Err(e) = fs::remove_file(file.path()) {
-            eprintln!("Failed to remove old log file {:?}: {}", file.path(), e);
-        }
-    }
-}
-
 // Struct for unbind profile generation result
 #[derive(serde::Serialize)]
 struct UnbindProfileResult {
@@ -1502,7 +2980,9 @@ fn generate_unbind_profile(
     devices: keybindings::DeviceSelection,
     base_path: String,
     state: tauri::State<Mutex<AppState>>,
+    app_handle: tauri::AppHandle,
 ) -> Result<UnbindProfileResult, String> {
+    let _write_lock = begin_write(&app_handle)?;
     use std::fs;
 
     info!(
@@ -1583,7 +3063,8 @@ fn generate_unbind_profile(
 }
 
 #[tauri::command]
-fn remove_unbind_profile() -> Result<RemoveUnbindResult, String> {
+fn remove_unbind_profile(app_handle: tauri::AppHandle) -> Result<RemoveUnbindResult, String> {
+    let _write_lock = begin_write(&app_handle)?;
     use std::fs;
 
     info!("Removing unbind profile files");
@@ -1638,7 +3119,9 @@ fn generate_restore_defaults_profile(
     devices: keybindings::DeviceSelection,
     base_path: String,
     state: tauri::State<Mutex<AppState>>,
+    app_handle: tauri::AppHandle,
 ) -> Result<UnbindProfileResult, String> {
+    let _write_lock = begin_write(&app_handle)?;
     use std::fs;
 
     info!(
@@ -1725,7 +3208,10 @@ fn generate_restore_defaults_profile(
 }
 
 #[tauri::command]
-fn remove_restore_defaults_profile() -> Result<RemoveUnbindResult, String> {
+fn remove_restore_defaults_profile(
+    app_handle: tauri::AppHandle,
+) -> Result<RemoveUnbindResult, String> {
+    let _write_lock = begin_write(&app_handle)?;
     use std::fs;
 
     info!("Removing restore defaults profile files");
@@ -1843,7 +3329,9 @@ fn deploy_character_to_installation(
     character_name: String,
     library_path: String,
     installation_path: String,
+    app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
     use std::fs;
     use std::path::Path;
 
@@ -1879,7 +3367,9 @@ fn import_character_to_library(
     character_name: String,
     installation_path: String,
     library_path: String,
+    app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
     use std::fs;
     use std::path::Path;
 
@@ -1963,71 +3453,13 @@ fn find_matching_hid_device(
     hid_devices
         .iter()
         .find(|dev| {
-            let product = dev.product.as_deref().unwrap_or("").to_lowercase();
-            let manufacturer = dev.manufacturer.as_deref().unwrap_or("").to_lowercase();
-            let combined = format!("{} {}", manufacturer, product).trim().to_string();
-            let search_name = device_name.to_lowercase();
-
-            // Clean search name: remove (...) at the end which might be added by Gilrs/OS
-            // e.g. "VKB Gladiator NXT (Left)" -> "vkb gladiator nxt"
-            let clean_search_name = if let Some(idx) = search_name.find('(') {
-                search_name[..idx].trim().to_string()
-            } else {
-                search_name.clone()
-            };
-
-            // 1. Product contains search name OR Search name contains product
-            if !product.is_empty()
-                && (product.contains(&search_name) || search_name.contains(&product))
-            {
-                return true;
-            }
+            let product = dev.product.as_deref().unwrap_or("");
+            let manufacturer = dev.manufacturer.as_deref().unwrap_or("");
+            let combined = format!("{} {}", manufacturer, product);
 
-            // 2. Combined (Manuf + Prod) contains search name OR Search name contains Combined
-            if !combined.is_empty()
-                && (combined.contains(&search_name) || search_name.contains(&combined))
-            {
-                return true;
-            }
-
-            // 3. Try with cleaned search name (removed parentheses)
-            if !clean_search_name.is_empty() {
-                if !product.is_empty()
-                    && (product.contains(&clean_search_name)
-                        || clean_search_name.contains(&product))
-                {
-                    return true;
-                }
-                if !combined.is_empty()
-                    && (combined.contains(&clean_search_name)
-                        || clean_search_name.contains(&combined))
-                {
-                    return true;
-                }
-            }
-
-            // 4. Token based matching (fuzzy)
-            // Split cleaned search name into tokens and check if they exist in the product/combined name
-            let search_tokens: Vec<&str> = clean_search_name.split_whitespace().collect();
-            if search_tokens.len() >= 2 {
-                let matches = search_tokens
-                    .iter()
-                    .filter(|&t| {
-                        // Skip very short words
-                        if t.len() < 2 {
-                            return false;
-                        }
-                        combined.contains(t)
-                    })
-                    .count();
-
-                // If most tokens match, assume it's the same device
-                if matches >= search_tokens.len() - 1 {
-                    return true;
-                }
-            }
-
-            false
+            // HID splits the name into manufacturer and product, so try the product alone and both
+            product_names::names_match(device_name, product)
+                || product_names::names_match(device_name, &combined)
         })
         .cloned()
 }
@@ -2121,7 +3553,9 @@ fn get_directinput_to_hid_mapping(
 fn delete_character_from_library(
     character_name: String,
     library_path: String,
+    app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
     use std::fs;
     use std::path::Path;
 
@@ -2139,7 +3573,9 @@ fn delete_character_from_library(
 fn delete_character_from_installation(
     character_name: String,
     installation_path: String,
+    app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
     use std::fs;
     use std::path::Path;
 
@@ -2163,13 +3599,17 @@ fn delete_character_from_installation(
 
 // ===== Controls File Commands =====
 
-/// Save control settings to a .sccontrols file
+/// Save control settings to a .sccontrols file. With a changelog message, the save is also
+/// recorded in the file's changelog along with how many options it changed.
 #[tauri::command]
 fn save_controls_file(
     file_path: String,
     profile_name: String,
     settings: serde_json::Value,
+    changelog_message: Option<String>,
+    app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
     info!("Saving controls file to: {}", file_path);
 
     // Parse the settings from frontend format
@@ -2177,339 +3617,2801 @@ fn save_controls_file(
         serde_json::from_value(settings).map_err(|e| format!("Failed to parse settings: {}", e))?;
 
     let input = controls::SaveControlsInput {
-        profile_name,
+        profile_name: profile_library::sanitize_profile_name(&profile_name)?,
         devices,
     };
 
-    // Convert to our file format
-    let controls_file: controls::ControlsFile = input.into();
+    // Convert to our file format
+    let mut controls_file: controls::ControlsFile = input.into();
+
+    curve_validation::normalize_controls(&mut controls_file)
+        .map_err(|issues| curve_validation::describe_issues(&issues))?;
+
+    // Keep any fields the frontend doesn't know about, and the history, from the existing file
+    let mut existing = None;
+    if let Ok(existing_json) = std::fs::read_to_string(&file_path) {
+        match controls::ControlsFile::from_json(&existing_json) {
+            Ok(file) => {
+                controls_file.preserve_unknown_fields(&file);
+                controls_file.carry_device_roles(&file);
+                controls_file.changelog = file.changelog.clone();
+                controls_file.feeder = file.feeder.clone();
+                controls_file.game_attributes = file.game_attributes.clone();
+                existing = Some(file);
+            }
+            Err(e) => info!("Not preserving fields from existing controls file: {}", e),
+        }
+    }
+
+    if let Some(message) = changelog_message {
+        let changed_options = diff::diff_device_options(
+            existing
+                .as_ref()
+                .map(|file| controls::controls_to_actionmaps(file, true))
+                .unwrap_or_default(),
+            controls::controls_to_actionmaps(&controls_file, true),
+        )
+        .len();
+        controls_file.record_change(changed_options, Some(&message));
+    }
+
+    // Serialize to JSON
+    let json = controls_file.to_json()?;
+
+    // Write to file
+    std::fs::write(&file_path, json)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    info!("Controls file saved successfully");
+    Ok(())
+}
+
+/// Copy all options of one device instance to another within a .sccontrols profile, e.g.
+/// when replacing a stick with a new model. Options named in `exclude_options` are skipped.
+#[tauri::command]
+fn copy_device_settings(
+    file_path: String,
+    from_device_type: String,
+    from_instance: String,
+    to_device_type: String,
+    to_instance: String,
+    exclude_options: Option<Vec<String>>,
+    app_handle: tauri::AppHandle,
+) -> Result<usize, String> {
+    let _write_lock = begin_write(&app_handle)?;
+
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    let copied = controls_file.copy_device_options(
+        (&from_device_type, &from_instance),
+        (&to_device_type, &to_instance),
+        &exclude_options.unwrap_or_default(),
+    )?;
+    controls_file.touch();
+
+    std::fs::write(&file_path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    info!(
+        "Copied {} option(s) from {} {} to {} {} in {}",
+        copied, from_device_type, from_instance, to_device_type, to_instance, file_path
+    );
+    Ok(copied)
+}
+
+/// Set or toggle inversion on every invertible axis of one device in a .sccontrols
+/// profile. `axis_filter` limits it to options about the given axes, e.g.
+/// ["pitch", "roll", "yaw"]. Returns how many options changed.
+#[tauri::command]
+fn invert_device_axes(
+    file_path: String,
+    device_type: String,
+    instance: String,
+    mode: controls::InvertMode,
+    axis_filter: Option<Vec<String>>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<usize, String> {
+    let _write_lock = begin_write(&app_handle)?;
+
+    let axes = {
+        let mut app_state = state.lock().unwrap();
+        let catalog =
+            diagnostics::lazy_init(&mut app_state.option_catalog, "option catalog", || {
+                option_catalog::parse_option_catalog(&get_all_binds_xml(app_handle.clone())?)
+            })?;
+        catalog.invertible_options(&device_type)
+    };
+
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    let changed = controls_file.invert_device_options(
+        &device_type,
+        &instance,
+        &axes,
+        &axis_filter.unwrap_or_default(),
+        mode,
+    )?;
+    if changed == 0 {
+        return Ok(0);
+    }
+    controls_file.touch();
+
+    std::fs::write(&file_path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    info!(
+        "{:?} inversion on {} option(s) of {} {} in {}",
+        mode, changed, device_type, instance, file_path
+    );
+    Ok(changed)
+}
+
+/// Set or clear the role (stick, throttle, pedals, button box) of a joystick instance
+#[tauri::command]
+fn set_device_role(
+    file_path: String,
+    instance: String,
+    role: Option<device_roles::DeviceRole>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    controls_file.device_mut("joystick", &instance)?.role = role;
+    controls_file.touch();
+
+    std::fs::write(&file_path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    info!(
+        "Joystick {} role set to {:?} in {}",
+        instance, role, file_path
+    );
+    Ok(())
+}
+
+/// A connected joystick and the role its name suggests
+#[derive(serde::Serialize)]
+struct SuggestedRole {
+    instance: usize,
+    product_name: String,
+    role: Option<device_roles::DeviceRole>,
+    label: String,
+}
+
+/// Suggest a role for each connected joystick from its product name
+#[tauri::command]
+fn suggest_device_roles() -> Result<Vec<SuggestedRole>, String> {
+    let devices = diagnostics::first_use(&DEVICE_ENUMERATION_INIT, "device enumeration", || {
+        device_capabilities::enumerate_devices()
+    })?;
+    Ok(devices
+        .into_iter()
+        .filter(|d| d.device_type == "joystick")
+        .map(|d| {
+            let role = device_roles::guess_role(&d.product_name);
+            let label = match role {
+                Some(role) => format!("{} (js{})", role.label(), d.instance),
+                None => format!("Joystick {}", d.instance),
+            };
+            SuggestedRole {
+                instance: d.instance,
+                product_name: d.product_name,
+                role,
+                label,
+            }
+        })
+        .collect())
+}
+
+/// Flip every vertical axis of one device (flight, turrets, ground vehicles, on foot)
+/// in a single write, leaving cvar-driven and essential options alone
+#[tauri::command]
+fn flip_device_vertical_axes(
+    file_path: String,
+    device_type: String,
+    instance: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<vertical_flip::FlipReport, String> {
+    let _write_lock = begin_write(&app_handle)?;
+
+    let catalog = {
+        let mut app_state = state.lock().unwrap();
+        diagnostics::lazy_init(&mut app_state.option_catalog, "option catalog", || {
+            option_catalog::parse_option_catalog(&get_all_binds_xml(app_handle.clone())?)
+        })?
+        .clone()
+    };
+    let essentials: Vec<String> = essentials::load_essentials(&essentials_dir(&app_handle)?)?
+        .options
+        .into_iter()
+        .filter(|o| o.device_type == device_type && o.instance == instance)
+        .map(|o| o.option)
+        .collect();
+
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    let report = vertical_flip::flip_vertical_axes(
+        &mut controls_file,
+        &catalog,
+        &device_type,
+        &instance,
+        &essentials,
+    )?;
+    if report.flipped.is_empty() {
+        return Ok(report);
+    }
+    controls_file.touch();
+
+    std::fs::write(&file_path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    info!(
+        "Flipped {} vertical option(s) of {} {} in {} ({} locked)",
+        report.flipped.len(),
+        device_type,
+        instance,
+        file_path,
+        report.locked.len()
+    );
+    Ok(report)
+}
+
+/// Load control settings from a .sccontrols file
+#[tauri::command]
+fn load_controls_file(file_path: String) -> Result<controls::LoadControlsOutput, String> {
+    info!("Loading controls file from: {}", file_path);
+
+    // Read the file
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+
+    // Parse the JSON
+    let controls_file = controls::ControlsFile::from_json(&json)?;
+
+    info!(
+        "Loaded controls file: {} (version {})",
+        controls_file.profile_name, controls_file.version
+    );
+
+    // Convert to output format for frontend
+    Ok(controls_file.into())
+}
+
+/// Every option of a .sccontrols file with its stored value next to what an apply writes
+/// (clamped, dropped for the device, excluded...), so differences can be explained.
+/// `include_curves` shows what the curve watchdog writes instead of a normal apply.
+#[tauri::command]
+fn get_effective_option_values(
+    file_path: String,
+    include_curves: Option<bool>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<controls::OptionValues>, String> {
+    let json = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let controls_file = controls::ControlsFile::from_json(&json)?;
+    Ok(controls::effective_option_values(
+        &controls_file,
+        include_curves.unwrap_or(false),
+        &excluded_options(&app_handle)?,
+    ))
+}
+
+/// Read control options from actionmaps.xml for importing
+#[tauri::command]
+fn import_controls_from_actionmaps(
+    actionmaps_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<controls::LoadControlsOutput, String> {
+    info!(
+        "Importing controls from actionmaps.xml: {}",
+        actionmaps_path
+    );
+
+    // Parse the options elements, streamed from the file
+    let device_options =
+        controls::parse_actionmaps_options_file(std::path::Path::new(&actionmaps_path))?;
+
+    tracing::info!(
+        path = %actionmaps_path,
+        devices = device_options.len(),
+        options = device_options.iter().map(|d| d.options.len()).sum::<usize>(),
+        "Parsed options from actionmaps.xml"
+    );
+
+    // Convert to our internal format
+    let controls_file = controls::controls_file_from_actionmaps_options(
+        "Imported from Star Citizen".to_string(),
+        device_options,
+        &excluded_options(&app_handle)?,
+    );
+
+    Ok(controls_file.into())
+}
+
+/// What could be salvaged from a damaged actionmaps.xml
+#[derive(serde::Serialize)]
+struct RecoveredActionmaps {
+    report: actionmaps_recovery::RecoveryReport,
+    bindings: OrganizedKeybindings,
+    controls: controls::LoadControlsOutput,
+    /// Backup of the damaged file, when the repaired one was written over it
+    backup_path: Option<String>,
+}
+
+/// Best-effort load of an actionmaps.xml the normal parsers refuse (truncated or garbled
+/// after a crash): everything intact up to the damage is loaded like load_keybindings.
+/// With `write_repaired` the damaged file is backed up and replaced by the repaired one.
+#[tauri::command]
+fn recover_actionmaps(
+    actionmaps_path: String,
+    write_repaired: bool,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<RecoveredActionmaps, String> {
+    let _write_lock = begin_write(&app_handle)?;
+
+    // A crash can leave bytes that aren't valid UTF-8 too
+    let bytes = std::fs::read(&actionmaps_path)
+        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+    let recovery = actionmaps_recovery::recover(&String::from_utf8_lossy(&bytes))?;
+    let report = recovery.report.clone();
+    if let Some(problem) = &report.problem {
+        warn!(
+            "Recovering damaged actionmaps.xml {}: {} at byte {:?}, kept {} options block(s) and {} rebind(s)",
+            actionmaps_path, problem, report.damage_offset, report.options_blocks, report.rebinds
+        );
+    }
+
+    let mut backup_path = None;
+    if write_repaired && !report.intact {
+        let backup = backups::create_backup(&backup_location(&app_handle)?, &actionmaps_path)?;
+        info!("Created backup of damaged actionmaps.xml at: {}", backup);
+        modification_log::note_own_write(&recovery.xml);
+        std::fs::write(&actionmaps_path, &recovery.xml)
+            .map_err(|e| format!("Failed to write actionmaps.xml: {}", e))?;
+        backup_path = Some(backup);
+    }
+
+    let action_maps = ActionMaps::from_xml(&recovery.xml)?;
+    let controls_file = controls::controls_file_from_actionmaps_options(
+        "Recovered from Star Citizen".to_string(),
+        controls::parse_actionmaps_options(&recovery.xml)?,
+        &excluded_options(&app_handle)?,
+    );
+
+    let mut app_state = state.lock().unwrap();
+    app_state.current_bindings = Some(action_maps.clone());
+    app_state.current_file_name = std::path::Path::new(&actionmaps_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .map(str::to_string);
+    publish_profile_changed(&app_state, "recovered");
+
+    Ok(RecoveredActionmaps {
+        report,
+        bindings: action_maps.organize(),
+        controls: controls_file.into(),
+        backup_path,
+    })
+}
+
+/// Import a Joystick Gremlin profile: response curves become .sccontrols curves, and when
+/// `rewrite_rebinds` is set the loaded SC rebinds are moved from the vJoy inputs to the
+/// physical inputs they were remapped from.
+#[tauri::command]
+fn import_gremlin_profile(
+    file_path: String,
+    vjoy_instances: std::collections::HashMap<u32, u32>,
+    device_instances: std::collections::HashMap<String, u32>,
+    rewrite_rebinds: bool,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<gremlin::GremlinImport, String> {
+    let xml = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read Gremlin profile: {}", e))?;
+    let remaps = gremlin::parse_gremlin_profile(&xml)?;
+
+    let profile_name = std::path::Path::new(&file_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Imported from Joystick Gremlin".to_string());
+
+    let mut app_state = state.lock().unwrap();
+    let action_options = gremlin::action_options(
+        app_state
+            .all_binds
+            .as_ref()
+            .ok_or("AllBinds.xml not loaded. Please restart the application.")?,
+    );
+
+    // Work on a copy unless the user asked for the rebinds to be rewritten
+    let mut scratch = app_state.current_bindings.clone();
+    let bindings = if rewrite_rebinds {
+        app_state.current_bindings.as_mut()
+    } else {
+        scratch.as_mut()
+    };
+
+    let result = gremlin::import_remaps(
+        remaps,
+        bindings,
+        &action_options,
+        &vjoy_instances,
+        &device_instances,
+        profile_name,
+    );
+
+    info!(
+        "Imported {} Gremlin remaps from {} ({} rebinds rewritten, {} skipped)",
+        result.remaps.len(),
+        file_path,
+        result.rebinds_rewritten,
+        result.skipped.len()
+    );
+    Ok(result)
+}
+
+/// List the layout_*_exported.xml files the game wrote to user/client/0/controls/mappings
+#[tauri::command]
+fn list_exported_mappings(installation_path: String) -> Result<Vec<ExportedMappingFile>, String> {
+    use std::time::UNIX_EPOCH;
+
+    let mappings_dir = std::path::Path::new(&installation_path)
+        .join("user")
+        .join("client")
+        .join("0")
+        .join("controls")
+        .join("mappings");
+
+    if !mappings_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let entries = std::fs::read_dir(&mappings_dir)
+        .map_err(|e| format!("Failed to read mappings directory: {}", e))?;
+
+    let mut files: Vec<ExportedMappingFile> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_name()?.to_str()?.to_string();
+            let lower = name.to_lowercase();
+            if !(lower.starts_with("layout_") && lower.ends_with("_exported.xml")) {
+                return None;
+            }
+            let modified = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            Some(ExportedMappingFile {
+                name,
+                path: path.to_string_lossy().to_string(),
+                modified,
+            })
+        })
+        .collect();
+
+    // Newest first, that's usually the one the user just exported in-game
+    files.sort_by(|a, b| b.modified.cmp(&a.modified));
+
+    Ok(files)
+}
+
+/// Load a mapping file exported in-game: its keybindings become the current bindings and
+/// its control options are converted so they can be inspected and saved as .sccontrols
+#[tauri::command]
+fn import_exported_mapping(
+    file_path: String,
+    state: tauri::State<Mutex<AppState>>,
+    app_handle: tauri::AppHandle,
+) -> Result<ExportedMappingImport, String> {
+    let xml = std::fs::read_to_string(&file_path)
+        .map_err(|e| format!("Failed to read mapping file: {}", e))?;
+
+    let action_maps = ActionMaps::from_xml(&xml)?;
+    let device_options = controls::parse_actionmaps_options(&xml)?;
+
+    let file_name = std::path::Path::new(&file_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("layout_exported.xml")
+        .to_string();
+
+    let profile_name = if action_maps.profile_name.is_empty() {
+        file_name.trim_end_matches(".xml").to_string()
+    } else {
+        action_maps.profile_name.clone()
+    };
+    let controls_file = controls::controls_file_from_actionmaps_options(
+        profile_name,
+        device_options,
+        &excluded_options(&app_handle)?,
+    );
+
+    info!(
+        "Imported exported mapping {} ({} rebinds)",
+        file_path,
+        action_maps.rebind_count()
+    );
+
+    let mut app_state = state.lock().unwrap();
+    app_state.current_bindings = Some(action_maps.clone());
+    app_state.current_file_name = Some(file_name);
+    publish_profile_changed(&app_state, "imported");
+
+    Ok(ExportedMappingImport {
+        bindings: action_maps.organize(),
+        controls: controls_file.into(),
+    })
+}
+
+/// One device's options from a paged actionmaps.xml parse
+#[derive(Clone, serde::Serialize)]
+struct ActionmapsOptionsChunk {
+    request_id: String,
+    index: usize,
+    device: controls::ActionmapsDeviceOptions,
+}
+
+/// Sent once a paged parse has finished (or failed)
+#[derive(Clone, serde::Serialize)]
+struct ActionmapsOptionsDone {
+    request_id: String,
+    device_count: usize,
+    error: Option<String>,
+}
+
+/// Paged variant of the actionmaps.xml options parse for very large files. Each device's
+/// options are emitted as an "actionmaps-options-chunk" event as soon as they're parsed,
+/// followed by "actionmaps-options-done", so the UI can populate progressively.
+#[tauri::command]
+async fn parse_actionmaps_options_paged(
+    app_handle: tauri::AppHandle,
+    actionmaps_path: String,
+    request_id: String,
+) -> Result<usize, String> {
+    use tauri::Emitter;
+
+    tokio::task::spawn_blocking(move || {
+        let result = std::fs::File::open(&actionmaps_path)
+            .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))
+            .and_then(|file| {
+                let mut index = 0;
+                controls::for_each_actionmaps_device(std::io::BufReader::new(file), |device| {
+                    let _ = app_handle.emit(
+                        "actionmaps-options-chunk",
+                        ActionmapsOptionsChunk {
+                            request_id: request_id.clone(),
+                            index,
+                            device,
+                        },
+                    );
+                    index += 1;
+                })
+            });
+
+        let _ = app_handle.emit(
+            "actionmaps-options-done",
+            ActionmapsOptionsDone {
+                request_id: request_id.clone(),
+                device_count: *result.as_ref().unwrap_or(&0),
+                error: result.as_ref().err().cloned(),
+            },
+        );
+        result
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Apply control settings to actionmaps.xml.
+/// When `contexts` is given, only options in those contexts (e.g. turret, ground vehicle) are written.
+/// Nothing is written while Star Citizen runs from the same installation unless `force` is set,
+/// nor for a change large enough to need confirming, until it's applied again with its token.
+/// The file is snapshotted first under `snapshot_name` (or a name made from the profile).
+#[tauri::command]
+fn apply_controls_to_actionmaps(
+    actionmaps_path: String,
+    settings: serde_json::Value,
+    profile_name: String,
+    contexts: Option<Vec<controls::OptionContext>>,
+    force: Option<bool>,
+    confirmation_token: Option<String>,
+    snapshot_name: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<controls::ApplyControlsResult, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    info!("Applying controls to actionmaps.xml: {}", actionmaps_path);
+
+    // Resolve ${VARIABLES} (e.g. joystick instance keys) before parsing
+    let settings =
+        serde_json::to_string(&settings).map_err(|e| format!("Failed to parse settings: {}", e))?;
+    let settings = if variables::find_variables(&settings).is_empty() {
+        settings
+    } else {
+        let devices = directinput::detect_joysticks().unwrap_or_default();
+        variables::substitute(&settings, &profile_variable_values(&app_handle, &devices)?)?
+    };
+
+    // Parse the settings
+    let devices: controls::DeviceSettingsInput =
+        serde_json::from_str(&settings).map_err(|e| format!("Failed to parse settings: {}", e))?;
+
+    let input = controls::SaveControlsInput {
+        profile_name: profile_name.clone(),
+        devices,
+    };
+
+    let controls_file: controls::ControlsFile = input.into();
+
+    // Names the installed game doesn't know would be written and then silently ignored
+    let warnings = {
+        let state = app_handle.state::<Mutex<AppState>>();
+        let mut app_state = state.lock().unwrap();
+        match installed_identifiers(&app_handle, &mut app_state) {
+            Ok((known, _)) => identifier_check::check_controls(&controls_file, &known),
+            Err(e) => {
+                warn!("Not checking option names: {}", e);
+                Vec::new()
+            }
+        }
+    };
+    for warning in &warnings {
+        warn!(
+            "{} {}: {}",
+            warning.context, warning.identifier, warning.message
+        );
+    }
+
+    let settings = settings::load_settings(&app_config_dir(&app_handle)?)?;
+    let mut result = apply_controls_file(
+        &actionmaps_path,
+        &controls_file,
+        contexts.as_deref(),
+        snapshot_name,
+        &environments_dir(&app_handle)?,
+        &apply_guard::ApplyConsent {
+            force: force.unwrap_or(false),
+            confirmation_token,
+            policy: settings.apply_confirmation,
+        },
+        &settings.excluded_options,
+    )?;
+    result.warnings = warnings;
+    Ok(result)
+}
+
+/// The options an apply of `controls_file` writes: the profile over the squadron baseline
+/// and under the enabled overlays, with its device role defaults filled in, limited to
+/// `contexts` if given. Previews (like the CLI's diff) use this too, so they show exactly
+/// what an apply will write.
+fn pending_options(
+    controls_file: &controls::ControlsFile,
+    contexts: Option<&[controls::OptionContext]>,
+    data_dir: &std::path::Path,
+) -> Result<Vec<controls::ActionmapsDeviceOptions>, String> {
+    let mut controls_file = profile_layers::compose_for_apply(data_dir, controls_file)?;
+    let role_defaults = device_roles::apply_role_defaults(&mut controls_file);
+    if role_defaults > 0 {
+        info!(
+            "Added {} option(s) from device role defaults",
+            role_defaults
+        );
+    }
+
+    // Convert our settings to actionmaps format
+    let mut new_devices = controls::controls_to_actionmaps(&controls_file, false);
+    if let Some(contexts) = contexts {
+        controls::retain_contexts(&mut new_devices, contexts);
+    }
+    Ok(new_devices)
+}
+
+/// Snapshot actionmaps.xml (as `snapshot_name`, if given) and merge a profile's options
+/// into it (over the squadron baseline, if one is enabled), recording the apply in the history under `data_dir`.
+/// Refuses while the game runs from the same installation unless `consent.force` is set,
+/// and holds back changes the confirmation policy flags until they're confirmed.
+/// `excluded` options are left as the file has them.
+/// Shared by the apply command and the CLI; the caller holds the write lock.
+fn apply_controls_file(
+    actionmaps_path: &str,
+    controls_file: &controls::ControlsFile,
+    contexts: Option<&[controls::OptionContext]>,
+    snapshot_name: Option<String>,
+    data_dir: &std::path::Path,
+    consent: &apply_guard::ApplyConsent,
+    excluded: &[String],
+) -> Result<controls::ApplyControlsResult, String> {
+    // The game writes its own copy of actionmaps.xml on exit, over ours
+    let game_running = game_process::running_for(actionmaps_path);
+    if let Some(process) = &game_running {
+        if !consent.force {
+            tracing::info!(pid = process.pid, "Not applying: Star Citizen is running");
+            return Ok(controls::ApplyControlsResult {
+                success: false,
+                snapshot: None,
+                message: "Star Citizen is running from this installation and will overwrite \
+                          actionmaps.xml when it exits. Close the game first, or apply anyway."
+                    .to_string(),
+                attempts: 0,
+                conflicts: Vec::new(),
+                game_running,
+                confirmation: None,
+                warnings: Vec::new(),
+            });
+        }
+    }
+
+    // Read the existing actionmaps.xml
+    let xml = std::fs::read_to_string(actionmaps_path)
+        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+
+    let new_devices = pending_options(controls_file, contexts, data_dir)?;
+
+    let preview = controls::merge_options_into_xml(&xml, new_devices.clone(), excluded)?;
+    if let Some(confirmation) = apply_guard::check(consent, &xml, &preview)? {
+        tracing::info!(
+            reasons = %confirmation.reasons.join(", "),
+            "Not applying without confirmation"
+        );
+        return Ok(controls::ApplyControlsResult {
+            success: false,
+            snapshot: None,
+            message: format!(
+                "This apply {}. Review the changes and confirm to apply them.",
+                confirmation.reasons.join(" and ")
+            ),
+            attempts: 0,
+            conflicts: Vec::new(),
+            game_running,
+            confirmation: Some(confirmation),
+            warnings: Vec::new(),
+        });
+    }
+
+    let snapshot = snapshots::take_before_apply(
+        &snapshots::store_dir(data_dir),
+        actionmaps_path,
+        snapshot_name,
+        &controls_file.profile_name,
+    )?;
+    tracing::info!(id = %snapshot.id, name = ?snapshot.name, "Took snapshot before apply");
+    // Merge and write, re-basing onto the game's version if it rewrites the file meanwhile
+    let devices = new_devices.len();
+    let write = apply_rebase::write_rebased(actionmaps_path, xml, new_devices, excluded)?;
+
+    tracing::info!(
+        path = actionmaps_path,
+        profile = %controls_file.profile_name,
+        devices,
+        attempts = write.attempts,
+        conflicts = write.conflicts.len(),
+        "Applied controls to actionmaps.xml"
+    );
+    if let Err(e) =
+        environments::record_apply(data_dir, actionmaps_path, &controls_file.profile_name)
+    {
+        tracing::error!(error = %e, "Could not record apply");
+    }
+
+    let mut message = if game_running.is_some() {
+        "Controls applied while Star Citizen is running; the game may overwrite them when \
+         it exits. Restart it for the changes to take effect."
+            .to_string()
+    } else {
+        "Controls applied successfully. Please restart Star Citizen for changes to take effect."
+            .to_string()
+    };
+    if !write.conflicts.is_empty() {
+        message.push_str(&format!(
+            " The game changed {} while applying; your settings were applied over it.",
+            write.conflicts.join(", ")
+        ));
+    }
+
+    Ok(controls::ApplyControlsResult {
+        success: true,
+        snapshot: Some(snapshot),
+        message,
+        attempts: write.attempts,
+        conflicts: write.conflicts,
+        game_running,
+        confirmation: None,
+        warnings: Vec::new(),
+    })
+}
+
+/// Where actionmaps.xml lives inside an installation folder (e.g. D:\Games\StarCitizen\LIVE)
+fn actionmaps_path_in(installation: &std::path::Path) -> std::path::PathBuf {
+    installation
+        .join("user")
+        .join("client")
+        .join("0")
+        .join("Profiles")
+        .join("default")
+        .join("actionmaps.xml")
+}
+
+/// Find the default actionmaps.xml path for a given SC installation
+#[tauri::command]
+fn find_actionmaps_path(base_path: String) -> Result<Option<String>, String> {
+    use std::path::Path;
+
+    let base = Path::new(&base_path);
+
+    // First, check if the base_path itself is an installation folder
+    // (e.g., D:\Games\StarCitizen\LIVE)
+    let direct_actionmaps = actionmaps_path_in(base);
+
+    if direct_actionmaps.exists() {
+        return Ok(Some(direct_actionmaps.to_string_lossy().to_string()));
+    }
+
+    // Otherwise, check if it's a parent folder containing LIVE/PTU/etc.
+    for folder in &environments::ENVIRONMENTS {
+        let actionmaps_path = actionmaps_path_in(&base.join(folder));
+
+        if actionmaps_path.exists() {
+            return Ok(Some(actionmaps_path.to_string_lossy().to_string()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Get the catalog of control options (per device type) from AllBinds.xml
+#[tauri::command]
+fn get_option_catalog(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<option_catalog::OptionCatalog, String> {
+    let mut app_state = state.lock().unwrap();
+    let catalog = diagnostics::lazy_init(&mut app_state.option_catalog, "option catalog", || {
+        option_catalog::parse_option_catalog(&get_all_binds_xml(app_handle)?)
+    })?;
+    Ok(catalog.clone())
+}
+
+/// Check the curves in editor settings, returning every problem found (empty when valid)
+#[tauri::command]
+fn validate_curves(
+    settings: serde_json::Value,
+) -> Result<Vec<curve_validation::CurveIssue>, String> {
+    let devices: controls::DeviceSettingsInput =
+        serde_json::from_value(settings).map_err(|e| format!("Failed to parse settings: {}", e))?;
+    let mut controls_file: controls::ControlsFile = controls::SaveControlsInput {
+        profile_name: String::new(),
+        devices,
+    }
+    .into();
+
+    Ok(curve_validation::normalize_controls(&mut controls_file)
+        .err()
+        .unwrap_or_default())
+}
+
+// ===== End Controls File Commands =====
+
+// ===== Settings Commands =====
+
+/// Resolve the directory where our settings and other per-machine stores live
+fn app_config_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to get config directory: {}", e))
+}
+
+/// Resolve the directory for data that can be rebuilt, like the parsed defaultProfile
+fn app_cache_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_cache_dir()
+        .map_err(|e| format!("Failed to get cache directory: {}", e))
+}
+
+// Struct for returning autostart status
+#[derive(serde::Serialize)]
+struct AutostartStatus {
+    enabled: bool,
+    start_minimized: bool,
+}
+
+/// Get whether Boxxy Binder is registered to start at login
+#[tauri::command]
+fn get_autostart_status(app_handle: tauri::AppHandle) -> Result<AutostartStatus, String> {
+    let settings = settings::load_settings(&app_config_dir(&app_handle)?)?;
+
+    // The registry entry is the source of truth, the user may have removed it
+    // through Task Manager's startup tab
+    let enabled = app_handle
+        .autolaunch()
+        .is_enabled()
+        .map_err(|e| format!("Failed to query autostart: {}", e))?;
+
+    Ok(AutostartStatus {
+        enabled,
+        start_minimized: settings.start_minimized,
+    })
+}
+
+/// Enable or disable starting Boxxy Binder at login
+#[tauri::command]
+fn set_autostart(
+    enabled: bool,
+    start_minimized: bool,
+    app_handle: tauri::AppHandle,
+) -> Result<AutostartStatus, String> {
+    let config_dir = app_config_dir(&app_handle)?;
+    let mut settings = settings::load_settings(&config_dir)?;
+
+    let autolaunch = app_handle.autolaunch();
+    if enabled {
+        autolaunch
+            .enable()
+            .map_err(|e| format!("Failed to enable autostart: {}", e))?;
+    } else if autolaunch.is_enabled().unwrap_or(false) {
+        autolaunch
+            .disable()
+            .map_err(|e| format!("Failed to disable autostart: {}", e))?;
+    }
+
+    settings.autostart = enabled;
+    settings.start_minimized = start_minimized;
+    settings::save_settings(&config_dir, &settings)?;
+
+    info!(
+        "Autostart {} (start minimized: {})",
+        if enabled { "enabled" } else { "disabled" },
+        start_minimized
+    );
+
+    Ok(AutostartStatus {
+        enabled,
+        start_minimized,
+    })
+}
+
+/// Fail write operations (apply, restore, delete, ...) while read-only mode is on
+fn ensure_writable(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    if settings::load_settings(&app_config_dir(app_handle)?)?.read_only {
+        return Err(
+            "Read-only mode is enabled. Turn it off in settings to make changes.".to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Directory holding the cross-instance write lock, shared with the CLI
+fn write_lock_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Start a write operation: refuse in read-only mode, then take the single-writer lock so
+/// another instance (or the CLI) can't write actionmaps.xml or profiles at the same time.
+/// Hold the returned guard until the write is done.
+fn begin_write(app_handle: &tauri::AppHandle) -> Result<write_lock::WriteLock, String> {
+    ensure_writable(app_handle)?;
+    write_lock::WriteLock::acquire(
+        &write_lock_dir(app_handle)?,
+        "gui",
+        write_lock::DEFAULT_WAIT,
+    )
+}
+
+#[tauri::command]
+fn get_read_only(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(settings::load_settings(&app_config_dir(&app_handle)?)?.read_only)
+}
+
+/// Turn read-only mode on or off. Browsing, diffing and exporting keep working while it's on.
+#[tauri::command]
+fn set_read_only(
+    enabled: bool,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let config_dir = app_config_dir(&app_handle)?;
+    let mut settings = settings::load_settings(&config_dir)?;
+    settings.read_only = enabled;
+    settings::save_settings(&config_dir, &settings)?;
+
+    // The watchdog writes actionmaps.xml in the background, so it can't keep running
+    if enabled && state.lock().unwrap().curve_watchdog.take().is_some() {
+        info!("Curve watchdog stopped for read-only mode");
+    }
+
+    info!(
+        "Read-only mode {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+#[tauri::command]
+fn get_excluded_options(app_handle: tauri::AppHandle) -> Result<Vec<String>, String> {
+    excluded_options(&app_handle)
+}
+
+/// Option names the user never wants written or imported (e.g. mouse sensitivity they
+/// tune in-game), for every merge and import to pass on
+fn excluded_options(app_handle: &tauri::AppHandle) -> Result<Vec<String>, String> {
+    Ok(settings::load_settings(&app_config_dir(app_handle)?)?.excluded_options)
+}
+
+#[tauri::command]
+fn get_apply_confirmation_policy(
+    app_handle: tauri::AppHandle,
+) -> Result<apply_guard::ConfirmationPolicy, String> {
+    Ok(settings::load_settings(&app_config_dir(&app_handle)?)?.apply_confirmation)
+}
+
+/// Set when an apply is large enough to need an explicit confirmation
+#[tauri::command]
+fn set_apply_confirmation_policy(
+    policy: apply_guard::ConfirmationPolicy,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let config_dir = app_config_dir(&app_handle)?;
+    let mut settings = settings::load_settings(&config_dir)?;
+    settings.apply_confirmation = policy;
+    settings::save_settings(&config_dir, &settings)?;
+
+    info!(
+        "Apply confirmation policy: {:?}",
+        settings.apply_confirmation
+    );
+    Ok(())
+}
+
+/// Set the option names that applies, merges and imports must never touch
+#[tauri::command]
+fn set_excluded_options(names: Vec<String>, app_handle: tauri::AppHandle) -> Result<(), String> {
+    let mut names: Vec<String> = names
+        .iter()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let config_dir = app_config_dir(&app_handle)?;
+    let mut settings = settings::load_settings(&config_dir)?;
+    settings.excluded_options = names;
+    settings::save_settings(&config_dir, &settings)?;
+
+    info!("Excluded options: {:?}", settings.excluded_options);
+    Ok(())
+}
+
+/// Current values of the device variables, given the connected devices
+fn profile_variable_values(
+    app_handle: &tauri::AppHandle,
+    devices: &[directinput::JoystickInfo],
+) -> Result<std::collections::HashMap<String, String>, String> {
+    let settings = settings::load_settings(&app_config_dir(app_handle)?)?;
+    Ok(variables::resolve_device_variables(
+        &settings.device_variables,
+        devices,
+    ))
+}
+
+/// Get the device variable registry (variable name -> device uuid or product name)
+#[tauri::command]
+fn get_device_variables(
+    app_handle: tauri::AppHandle,
+) -> Result<std::collections::HashMap<String, String>, String> {
+    Ok(settings::load_settings(&app_config_dir(&app_handle)?)?.device_variables)
+}
+
+/// Bind a profile variable to a device, or remove it when `device` is None
+#[tauri::command]
+fn set_device_variable(
+    name: String,
+    device: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<std::collections::HashMap<String, String>, String> {
+    let name = name
+        .trim()
+        .trim_start_matches("${")
+        .trim_end_matches('}')
+        .trim()
+        .to_string();
+    if name.is_empty() {
+        return Err("Variable name cannot be empty".to_string());
+    }
+
+    let config_dir = app_config_dir(&app_handle)?;
+    let mut settings = settings::load_settings(&config_dir)?;
+
+    match device {
+        Some(device) => settings.device_variables.insert(name, device),
+        None => settings.device_variables.remove(&name),
+    };
+    settings::save_settings(&config_dir, &settings)?;
+
+    Ok(settings.device_variables)
+}
+
+/// Resolve the device variables against the currently connected devices
+#[tauri::command]
+fn resolve_device_variables(
+    app_handle: tauri::AppHandle,
+) -> Result<std::collections::HashMap<String, String>, String> {
+    let devices = directinput::detect_joysticks()?;
+    profile_variable_values(&app_handle, &devices)
+}
+
+/// Local backup store used when no location is configured or it's unreachable
+fn default_backup_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("backups"))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+fn backup_location(app_handle: &tauri::AppHandle) -> Result<backups::BackupLocation, String> {
+    let settings = settings::load_settings(&app_config_dir(app_handle)?)?;
+    Ok(backups::resolve_location(
+        settings.backup_dir.as_deref(),
+        &default_backup_dir(app_handle)?,
+    ))
+}
+
+/// Get the backup directory in use, and whether the configured one is unreachable
+#[tauri::command]
+fn get_backup_location(app_handle: tauri::AppHandle) -> Result<backups::BackupLocation, String> {
+    backup_location(&app_handle)
+}
+
+/// Point the backup store at a new directory (None for the default). The new directory
+/// must be reachable; existing backups are moved over when `migrate` is set.
+#[tauri::command]
+fn set_backup_location(
+    app_handle: tauri::AppHandle,
+    path: Option<String>,
+    migrate: bool,
+) -> Result<backups::BackupLocation, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let config_dir = app_config_dir(&app_handle)?;
+    let default_dir = default_backup_dir(&app_handle)?;
+    let path = path.filter(|p| !p.trim().is_empty());
+
+    let new_dir = path
+        .as_deref()
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| default_dir.clone());
+    backups::check_available(&new_dir)?;
+
+    let old_location = backup_location(&app_handle)?;
+    if migrate {
+        let moved = backups::migrate_backups(std::path::Path::new(&old_location.path), &new_dir)?;
+        info!(
+            "Moved {} backups from {} to {}",
+            moved,
+            old_location.path,
+            new_dir.display()
+        );
+    }
+
+    let mut app_settings = settings::load_settings(&config_dir)?;
+    app_settings.backup_dir = path;
+    settings::save_settings(&config_dir, &app_settings)?;
+
+    Ok(backups::resolve_location(
+        app_settings.backup_dir.as_deref(),
+        &default_dir,
+    ))
+}
+
+/// Startup time, per-stage timings and first-use initialization costs
+#[tauri::command]
+fn get_startup_diagnostics() -> diagnostics::Diagnostics {
+    diagnostics::snapshot()
+}
+
+// ===== End Settings Commands =====
+
+// ===== Snapshot Commands =====
+
+fn snapshots_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| snapshots::store_dir(&dir))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Take a snapshot of actionmaps.xml, optionally with a session note
+#[tauri::command]
+fn take_snapshot(
+    actionmaps_path: String,
+    kind: snapshots::SnapshotKind,
+    note: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<snapshots::SnapshotMeta, String> {
+    let meta =
+        snapshots::take_snapshot(&snapshots_dir(&app_handle)?, &actionmaps_path, kind, note)?;
+    info!(
+        "Took {:?} snapshot {} of {}",
+        kind, meta.id, actionmaps_path
+    );
+    Ok(meta)
+}
+
+/// List snapshot history, newest first
+#[tauri::command]
+fn list_snapshots(app_handle: tauri::AppHandle) -> Result<Vec<snapshots::SnapshotMeta>, String> {
+    snapshots::list_snapshots(&snapshots_dir(&app_handle)?)
+}
+
+/// Attach or replace the session note on a snapshot
+#[tauri::command]
+fn set_snapshot_note(
+    snapshot_id: String,
+    note: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<snapshots::SnapshotMeta, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    snapshots::set_note(&snapshots_dir(&app_handle)?, &snapshot_id, note)
+}
+
+/// What a snapshot restore did
+#[derive(serde::Serialize)]
+struct RestoredSnapshot {
+    restored: snapshots::SnapshotMeta,
+    /// Snapshot of the file that was replaced, to undo the restore with
+    replaced: Option<snapshots::SnapshotMeta>,
+}
+
+/// Write a snapshot back over actionmaps.xml (by default the file it was taken from)
+#[tauri::command]
+fn restore_snapshot(
+    snapshot_id: String,
+    actionmaps_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<RestoredSnapshot, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let (restored, replaced) = snapshots::restore(
+        &snapshots_dir(&app_handle)?,
+        &snapshot_id,
+        actionmaps_path.as_deref(),
+    )?;
+    tracing::info!(
+        id = %restored.id,
+        name = ?restored.name,
+        target = actionmaps_path.as_deref().unwrap_or(&restored.source_path),
+        "Restored snapshot"
+    );
+    Ok(RestoredSnapshot { restored, replaced })
+}
+
+/// Delete a snapshot
+#[tauri::command]
+fn delete_snapshot(snapshot_id: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    snapshots::delete(&snapshots_dir(&app_handle)?, &snapshot_id)?;
+    info!("Deleted snapshot {}", snapshot_id);
+    Ok(())
+}
+
+/// Load one side of a comparison
+fn load_diff_source(
+    source: diff::DiffSource,
+    app_handle: &tauri::AppHandle,
+) -> Result<diff::DiffInput, String> {
+    let read = |path: &str| {
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+    };
+    let load_profile = |path: &str| controls::ControlsFile::from_json(&read(path)?);
+
+    Ok(match source {
+        diff::DiffSource::File { path } => diff::DiffInput::Xml(read(&path)?),
+        diff::DiffSource::Profile { path } => diff::DiffInput::Options(
+            controls::controls_to_actionmaps(&load_profile(&path)?, true),
+        ),
+        diff::DiffSource::Snapshot { id } => {
+            diff::DiffInput::Xml(snapshots::load_xml(&snapshots_dir(app_handle)?, &id)?)
+        }
+        diff::DiffSource::Applied {
+            profile_path,
+            actionmaps_path,
+        } => {
+            let pending = pending_options(
+                &load_profile(&profile_path)?,
+                None,
+                &environments_dir(app_handle)?,
+            )?;
+            diff::DiffInput::Xml(controls::merge_options_into_xml(
+                &read(&actionmaps_path)?,
+                pending,
+                &excluded_options(app_handle)?,
+            )?)
+        }
+    })
+}
+
+/// Compare any two of: actionmaps.xml files (or backups), profiles, snapshots, and
+/// actionmaps.xml as a profile would leave it. Every comparison comes back in the same
+/// per-device / per-actionmap hunk model for the preview pane.
+#[tauri::command]
+fn diff_sources(
+    before: diff::DiffSource,
+    after: diff::DiffSource,
+    app_handle: tauri::AppHandle,
+) -> Result<diff::DiffModel, String> {
+    diff::diff_inputs(
+        load_diff_source(before, &app_handle)?,
+        load_diff_source(after, &app_handle)?,
+    )
+}
+
+/// Compare two snapshots (e.g. pre-game vs post-game)
+#[tauri::command]
+fn diff_snapshots(
+    from_id: String,
+    to_id: String,
+    app_handle: tauri::AppHandle,
+) -> Result<snapshots::SnapshotDiff, String> {
+    snapshots::diff_snapshots(&snapshots_dir(&app_handle)?, &from_id, &to_id)
+}
+
+/// The document a restore would write: a backup (or any actionmaps.xml) or a snapshot
+fn load_restore_source(
+    source: diff::DiffSource,
+    app_handle: &tauri::AppHandle,
+) -> Result<String, String> {
+    match source {
+        diff::DiffSource::File { path } => {
+            std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))
+        }
+        diff::DiffSource::Snapshot { id } => snapshots::load_xml(&snapshots_dir(app_handle)?, &id),
+        _ => Err("Only backups and snapshots can be restored".to_string()),
+    }
+}
+
+/// Backups in the store in use, newest first. With `actionmaps_path`, the backups older
+/// versions left next to that file are included.
+#[tauri::command]
+fn list_backups(
+    actionmaps_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<backups::BackupEntry>, String> {
+    let mut entries =
+        backups::list_backups(std::path::Path::new(&backup_location(&app_handle)?.path));
+    if let Some(actionmaps_path) = actionmaps_path {
+        // The store may be the folder actionmaps.xml is in
+        let legacy: Vec<backups::BackupEntry> =
+            backups::list_legacy_backups(std::path::Path::new(&actionmaps_path))
+                .into_iter()
+                .filter(|legacy| !entries.iter().any(|e| e.path == legacy.path))
+                .collect();
+        entries.extend(legacy);
+        backups::sort_newest_first(&mut entries);
+    }
+    Ok(entries)
+}
+
+/// Show what restoring a backup or snapshot over actionmaps.xml would change
+#[tauri::command]
+fn preview_restore(
+    source: diff::DiffSource,
+    actionmaps_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<restore::RestorePreview, String> {
+    restore::preview(&actionmaps_path, &load_restore_source(source, &app_handle)?)
+}
+
+/// Restore a backup or snapshot over actionmaps.xml, with the token from its preview.
+/// The replaced file is backed up first.
+#[tauri::command]
+fn restore_actionmaps(
+    source: diff::DiffSource,
+    actionmaps_path: String,
+    token: String,
+    app_handle: tauri::AppHandle,
+) -> Result<restore::RestoreResult, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let backup_xml = load_restore_source(source, &app_handle)?;
+    let result = restore::restore(
+        &actionmaps_path,
+        &backup_xml,
+        &token,
+        &backup_location(&app_handle)?,
+    )?;
+    tracing::info!(
+        path = %actionmaps_path,
+        options = result.changed_options,
+        bindings = result.changed_bindings,
+        backup = %result.backup_path,
+        "Restored actionmaps.xml"
+    );
+    Ok(result)
+}
+
+// ===== End Snapshot Commands =====
+
+// ===== Curve Watchdog Commands =====
+
+/// Set once the first file watcher has been created
+static FILE_WATCHER_INIT: std::sync::OnceLock<()> = std::sync::OnceLock::new();
+
+/// Start watching actionmaps.xml and re-apply the profile's curves whenever the game rewrites it
+#[tauri::command]
+fn start_curve_watchdog(
+    actionmaps_path: String,
+    profile_path: String,
+    only_when_curves_differ: bool,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<curve_watchdog::WatchdogConfig, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let config = curve_watchdog::WatchdogConfig {
+        actionmaps_path,
+        profile_path,
+        only_when_curves_differ,
+    };
+
+    let mut app_state = state.lock().unwrap();
+
+    // Replacing the watchdog drops (and stops) any previous one
+    app_state.curve_watchdog = None;
+    app_state.curve_watchdog = Some(diagnostics::first_use(
+        &FILE_WATCHER_INIT,
+        "file watcher",
+        || {
+            curve_watchdog::CurveWatchdog::start(
+                config.clone(),
+                write_lock_dir(&app_handle)?,
+                app_handle,
+            )
+        },
+    )?);
+
+    info!(
+        "Curve watchdog started for {} using profile {}",
+        config.actionmaps_path, config.profile_path
+    );
+    Ok(config)
+}
+
+/// Stop the curve watchdog if it is running
+#[tauri::command]
+fn stop_curve_watchdog(state: tauri::State<Mutex<AppState>>) -> Result<(), String> {
+    let mut app_state = state.lock().unwrap();
+    if app_state.curve_watchdog.take().is_some() {
+        info!("Curve watchdog stopped");
+    }
+    Ok(())
+}
+
+/// Get the running watchdog's configuration, or None if it isn't running
+#[tauri::command]
+fn get_curve_watchdog_status(
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<Option<curve_watchdog::WatchdogConfig>, String> {
+    let app_state = state.lock().unwrap();
+    Ok(app_state
+        .curve_watchdog
+        .as_ref()
+        .map(|watchdog| watchdog.config().clone()))
+}
+
+// ===== End Curve Watchdog Commands =====
+
+// ===== Curve Export Commands =====
+
+/// Export an axis's curve for use in another tool, as a Joystick Gremlin response curve or a CSV
+#[tauri::command]
+fn export_curve(
+    curve: curve_export::CurveExportInput,
+    format: curve_export::CurveExportFormat,
+    file_path: String,
+) -> Result<(), String> {
+    let contents = match format {
+        curve_export::CurveExportFormat::Gremlin => curve_export::to_gremlin_response_curve(&curve),
+        curve_export::CurveExportFormat::Csv => curve_export::to_csv(&curve),
+    };
+
+    std::fs::write(&file_path, contents).map_err(|e| format!("Failed to write curve: {}", e))?;
+    info!("Exported curve to {}", file_path);
+    Ok(())
+}
+
+// ===== End Curve Export Commands =====
+
+// ===== Curve Preset Commands =====
+
+fn curve_presets_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+#[tauri::command]
+fn list_curve_presets(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<curve_presets::CurvePreset>, String> {
+    curve_presets::load_presets(&curve_presets_dir(&app_handle)?)
+}
+
+/// Create a preset, or overwrite the existing one with the same name
+#[tauri::command]
+fn save_curve_preset(
+    preset: curve_presets::CurvePreset,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<curve_presets::CurvePreset>, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    curve_presets::save_preset(&curve_presets_dir(&app_handle)?, preset)
+}
+
+#[tauri::command]
+fn delete_curve_preset(
+    name: String,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<curve_presets::CurvePreset>, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    curve_presets::delete_preset(&curve_presets_dir(&app_handle)?, &name)
+}
+
+/// Apply a preset by name to one axis of a .sccontrols profile
+#[tauri::command]
+fn apply_curve_preset(
+    preset_name: String,
+    profile_path: String,
+    device_type: String,
+    instance: String,
+    option_name: String,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let preset = curve_presets::find_preset(&curve_presets_dir(&app_handle)?, &preset_name)?;
+
+    let json = std::fs::read_to_string(&profile_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+
+    let device = controls_file.device_mut(&device_type, &instance)?;
+    curve_presets::apply_to_option(
+        &preset,
+        device.options.entry(option_name.clone()).or_default(),
+    );
+    controls_file.touch();
+
+    std::fs::write(&profile_path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    info!(
+        "Applied curve preset '{}' to {} {} {} in {}",
+        preset.name, device_type, instance, option_name, profile_path
+    );
+    Ok(())
+}
+
+/// Generate settings for an axis from a feel preset, adapted to the stick's calibration measurements
+#[tauri::command]
+fn generate_axis_feel(
+    preset: axis_feel::FeelPreset,
+    measurements: axis_feel::AxisMeasurements,
+) -> Result<axis_feel::AdaptedFeel, String> {
+    Ok(axis_feel::adapt(preset, &measurements))
+}
+
+// ===== End Curve Preset Commands =====
+
+// ===== Curve A/B Commands =====
+
+fn curve_ab_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Write a test's active curve to its profile and, when given, straight into
+/// actionmaps.xml so the game picks it up on its next start. The caller holds the write lock.
+fn write_active_curve(
+    test: &curve_ab::CurveAbTest,
+    actionmaps_path: Option<&str>,
+    app_handle: &tauri::AppHandle,
+) -> Result<(), String> {
+    let json = std::fs::read_to_string(&test.axis.profile_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let mut controls_file = controls::ControlsFile::from_json(&json)?;
+    let settings = test.write_to_profile(&mut controls_file)?;
+    controls_file.touch();
+    std::fs::write(&test.axis.profile_path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+
+    if let Some(actionmaps_path) = actionmaps_path {
+        let xml = std::fs::read_to_string(actionmaps_path)
+            .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+        let new_xml = controls::merge_options_into_xml(
+            &xml,
+            test.actionmaps_options(settings)?,
+            &excluded_options(app_handle)?,
+        )?;
+
+        backups::create_backup(&backup_location(app_handle)?, actionmaps_path)?;
+        modification_log::note_own_write(&new_xml);
+        std::fs::write(actionmaps_path, &new_xml)
+            .map_err(|e| format!("Failed to write actionmaps.xml: {}", e))?;
+    }
+
+    info!(
+        "Curve '{}' active on {} {} {}",
+        test.active_curve().name,
+        test.axis.device_type,
+        test.axis.instance,
+        test.axis.option
+    );
+    Ok(())
+}
+
+/// Start comparing two curves on one axis of a profile. Curve A becomes active.
+#[tauri::command]
+fn start_curve_ab_test(
+    axis: curve_ab::TestedAxis,
+    actionmaps_path: Option<String>,
+    curve_a: curve_presets::CurvePreset,
+    curve_b: curve_presets::CurvePreset,
+    app_handle: tauri::AppHandle,
+) -> Result<curve_ab::CurveAbTest, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let dir = curve_ab_dir(&app_handle)?;
+    let mut tests = curve_ab::load_tests(&dir)?;
+
+    let test = curve_ab::start(&mut tests, axis, curve_a, curve_b)?;
+    write_active_curve(&test, actionmaps_path.as_deref(), &app_handle)?;
+    curve_ab::save_tests(&dir, &tests)?;
+    Ok(test)
+}
+
+/// Switch an axis to the other curve of its running comparison
+#[tauri::command]
+fn swap_curve_ab_test(
+    axis: curve_ab::TestedAxis,
+    actionmaps_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<curve_ab::CurveAbTest, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let dir = curve_ab_dir(&app_handle)?;
+    let mut tests = curve_ab::load_tests(&dir)?;
+
+    let test = curve_ab::open_test(&mut tests, &axis)?;
+    curve_ab::swap(test);
+    write_active_curve(test, actionmaps_path.as_deref(), &app_handle)?;
+    let test = test.clone();
+    curve_ab::save_tests(&dir, &tests)?;
+    Ok(test)
+}
+
+/// End a comparison with the user's pick, which stays on the axis
+#[tauri::command]
+fn choose_curve_ab_variant(
+    axis: curve_ab::TestedAxis,
+    actionmaps_path: Option<String>,
+    variant: curve_ab::Variant,
+    app_handle: tauri::AppHandle,
+) -> Result<curve_ab::CurveAbTest, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let dir = curve_ab_dir(&app_handle)?;
+    let mut tests = curve_ab::load_tests(&dir)?;
+
+    let test = curve_ab::open_test(&mut tests, &axis)?;
+    curve_ab::choose(test, variant);
+    write_active_curve(test, actionmaps_path.as_deref(), &app_handle)?;
+    let test = test.clone();
+    curve_ab::save_tests(&dir, &tests)?;
+    Ok(test)
+}
+
+/// All comparisons, running and finished
+#[tauri::command]
+fn list_curve_ab_tests(app_handle: tauri::AppHandle) -> Result<Vec<curve_ab::CurveAbTest>, String> {
+    curve_ab::load_tests(&curve_ab_dir(&app_handle)?)
+}
+
+// ===== End Curve A/B Commands =====
+
+// ===== Profile Library Commands =====
+
+fn profile_library_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("profiles"))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+#[tauri::command]
+fn get_profile_library_dir(app_handle: tauri::AppHandle) -> Result<String, String> {
+    Ok(profile_library_dir(&app_handle)?
+        .to_string_lossy()
+        .to_string())
+}
+
+/// List the library's profiles with their metadata and device summary
+#[tauri::command]
+fn list_library_profiles(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<profile_library::ProfileSummary>, String> {
+    profile_library::list_profiles(&profile_library_dir(&app_handle)?)
+}
+
+/// What a profile name will be saved as (control characters removed) and the file name it
+/// maps to, so the frontend can confirm any change with the user before saving
+#[tauri::command]
+fn check_profile_name(profile_name: String) -> Result<profile_library::ProfileNameCheck, String> {
+    profile_library::check_profile_name(&profile_name)
+}
+
+#[tauri::command]
+fn create_library_profile(
+    profile_name: String,
+    app_handle: tauri::AppHandle,
+) -> Result<profile_library::ProfileSummary, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    profile_library::create_profile(&profile_library_dir(&app_handle)?, &profile_name)
+}
+
+/// List the game's preset control profiles ("Advanced HOTAS"...) found in an extracted
+/// copy of Data.p4k; `directory` may be the extraction root or the Mappings folder itself
+#[tauri::command]
+fn list_control_presets(directory: String) -> Result<Vec<control_presets::ControlPreset>, String> {
+    control_presets::list_presets(&control_presets::presets_dir(std::path::Path::new(
+        &directory,
+    )))
+}
+
+/// Start a new library profile from a preset layout: its options become the profile and
+/// its keybindings are loaded as the current bindings
+#[tauri::command]
+fn create_profile_from_preset(
+    preset_path: String,
+    profile_name: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<PresetProfileImport, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let profile_name = profile_library::sanitize_profile_name(&profile_name)?;
+    let preset_path = std::path::Path::new(&preset_path);
+    let import = control_presets::import_preset(
+        preset_path,
+        &profile_name,
+        &excluded_options(&app_handle)?,
+    )?;
+
+    let profile =
+        profile_library::add_profile(&profile_library_dir(&app_handle)?, import.controls)?;
+    info!(
+        "Created profile {} from preset {} ({} rebinds)",
+        profile.file_name,
+        preset_path.display(),
+        import.bindings.rebind_count()
+    );
+
+    let bindings = import.bindings.organize();
+    let mut app_state = state.lock().unwrap();
+    app_state.current_file_name = preset_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string());
+    app_state.current_bindings = Some(import.bindings);
+    publish_profile_changed(&app_state, "created");
+
+    Ok(PresetProfileImport { profile, bindings })
+}
+
+/// Built-in layouts for known HOTAS hardware, with the name of each physical control
+#[tauri::command]
+fn list_hotas_templates() -> Vec<hotas_templates::HotasTemplate> {
+    hotas_templates::TEMPLATES.to_vec()
+}
+
+/// Start a new library profile from a HOTAS template, binding each of its devices on the
+/// joystick instance it's connected as; its keybindings are loaded as the current bindings
+#[tauri::command]
+fn create_profile_from_template(
+    template_id: String,
+    profile_name: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<TemplateProfileImport, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let profile_name = profile_library::sanitize_profile_name(&profile_name)?;
+    let template = hotas_templates::find(&template_id)
+        .ok_or_else(|| format!("Unknown HOTAS template: {}", template_id))?;
+
+    let devices = diagnostics::first_use(&DEVICE_ENUMERATION_INIT, "device enumeration", || {
+        device_capabilities::enumerate_devices()
+    })?;
+    let joysticks: Vec<hotas_templates::DetectedJoystick> = devices
+        .into_iter()
+        .filter(|d| d.device_type == "joystick")
+        .map(|d| hotas_templates::DetectedJoystick {
+            instance: d.instance,
+            uuid: d.uuid,
+            product_name: d.product_name,
+        })
+        .collect();
+    let import = hotas_templates::instantiate(template, &profile_name, &joysticks);
+
+    let profile =
+        profile_library::add_profile(&profile_library_dir(&app_handle)?, import.controls)?;
+    for assignment in &import.assignments {
+        if assignment.product_name.is_none() {
+            warn!(
+                "{} is not connected; bound as js{}",
+                assignment.label, assignment.instance
+            );
+        }
+    }
+    info!(
+        "Created profile {} from template {} ({} rebinds)",
+        profile.file_name,
+        template.id,
+        import.bindings.rebind_count()
+    );
+
+    let bindings = import.bindings.organize();
+    let mut app_state = state.lock().unwrap();
+    app_state.current_file_name = None;
+    app_state.current_bindings = Some(import.bindings);
+    publish_profile_changed(&app_state, "created");
+
+    Ok(TemplateProfileImport {
+        profile,
+        bindings,
+        assignments: import.assignments,
+    })
+}
+
+/// Built-in gameplay role templates, with the actions each role prioritizes
+#[tauri::command]
+fn list_role_templates() -> Vec<role_templates::RoleTemplate> {
+    role_templates::ROLE_TEMPLATES.to_vec()
+}
+
+/// Start a new library profile by merging a role template over a base profile and the
+/// current bindings; the merged keybindings are loaded as the current bindings
+#[tauri::command]
+fn create_role_profile(
+    role_id: String,
+    base_profile_path: String,
+    profile_name: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<RoleProfileImport, String> {
+    let write_lock = begin_write(&app_handle)?;
+    let profile_name = profile_library::sanitize_profile_name(&profile_name)?;
+    let template = role_templates::find(&role_id)
+        .ok_or_else(|| format!("Unknown role template: {}", role_id))?;
+
+    let json = std::fs::read_to_string(&base_profile_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let base_controls = controls::ControlsFile::from_json(&json)?;
+
+    let mut app_state = state.lock().unwrap();
+    let base_bindings = app_state.current_bindings.clone().unwrap_or(ActionMaps {
+        profile_name: profile_name.clone(),
+        action_maps: Vec::new(),
+        categories: Vec::new(),
+        devices: keybindings::DeviceInfo {
+            keyboards: Vec::new(),
+            mice: Vec::new(),
+            joysticks: Vec::new(),
+            device_options: Vec::new(),
+        },
+    });
+    // Without AllBinds.xml loaded every action is taken on trust
+    let all_binds = app_state.all_binds.as_ref();
+    let known_action = |action_map: &str, action: &str| {
+        all_binds.is_none_or(|all_binds| all_binds.has_action(action_map, action))
+    };
+    let mut merged = role_templates::merge(
+        template,
+        &profile_name,
+        &base_controls,
+        &base_bindings,
+        known_action,
+    );
+
+    let profile =
+        profile_library::add_profile(&profile_library_dir(&app_handle)?, merged.controls)?;
+    for skipped in &merged.skipped {
+        warn!(
+            "Role {}: skipped {}: {}",
+            template.id, skipped.action, skipped.reason
+        );
+    }
+    info!(
+        "Created profile {} from role {} over {} ({} rebinds)",
+        profile.file_name,
+        template.id,
+        base_profile_path,
+        merged.bindings.rebind_count()
+    );
+
+    resolve_apply_conflicts(
+        &profile.path,
+        "template",
+        &base_bindings,
+        &mut merged.bindings,
+        &write_lock,
+    )?;
+
+    let bindings = merged.bindings.organize();
+    app_state.current_file_name = None;
+    app_state.current_bindings = Some(merged.bindings);
+    publish_profile_changed(&app_state, "created");
+
+    Ok(RoleProfileImport {
+        profile,
+        bindings,
+        skipped: merged.skipped,
+        displaced: merged.displaced,
+        priorities: merged.priorities,
+    })
+}
+
+#[tauri::command]
+fn duplicate_library_profile(
+    file_name: String,
+    new_name: String,
+    app_handle: tauri::AppHandle,
+) -> Result<profile_library::ProfileSummary, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    profile_library::duplicate_profile(&profile_library_dir(&app_handle)?, &file_name, &new_name)
+}
+
+#[tauri::command]
+fn rename_library_profile(
+    file_name: String,
+    new_name: String,
+    app_handle: tauri::AppHandle,
+) -> Result<profile_library::ProfileSummary, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    profile_library::rename_profile(&profile_library_dir(&app_handle)?, &file_name, &new_name)
+}
+
+#[tauri::command]
+fn delete_library_profile(file_name: String, app_handle: tauri::AppHandle) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    profile_library::delete_profile(&profile_library_dir(&app_handle)?, &file_name)?;
+    info!("Deleted profile {} from the library", file_name);
+    Ok(())
+}
+
+// ===== End Profile Library Commands =====
+
+// ===== Actionmaps Watcher Commands =====
+
+/// Watch actionmaps.xml for changes made by the game or other tools. Each change emits an
+/// "actionmaps-changed" event with a summary and the full diff against the previous content.
+#[tauri::command]
+fn start_actionmaps_watcher(
+    actionmaps_path: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let mut app_state = state.lock().unwrap();
+
+    // Replacing the watcher drops (and stops) any previous one
+    app_state.actionmaps_watcher = None;
+    app_state.actionmaps_watcher = Some(diagnostics::first_use(
+        &FILE_WATCHER_INIT,
+        "file watcher",
+        || {
+            actionmaps_watcher::ActionmapsWatcher::start(
+                actionmaps_path,
+                modification_log_dir(&app_handle)?,
+                app_config_dir(&app_handle)?,
+                app_handle,
+            )
+        },
+    )?);
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_actionmaps_watcher(state: tauri::State<Mutex<AppState>>) -> Result<(), String> {
+    let mut app_state = state.lock().unwrap();
+    if app_state.actionmaps_watcher.take().is_some() {
+        info!("Actionmaps watcher stopped");
+    }
+    Ok(())
+}
+
+/// The file being watched, or None if the watcher isn't running
+#[tauri::command]
+fn get_actionmaps_watcher_status(
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<Option<String>, String> {
+    let app_state = state.lock().unwrap();
+    Ok(app_state
+        .actionmaps_watcher
+        .as_ref()
+        .map(|watcher| watcher.path().to_string()))
+}
+
+fn modification_log_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+/// Changes to actionmaps.xml made by the game or other tools, newest first
+#[tauri::command]
+fn get_modification_log(
+    limit: Option<usize>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<modification_log::ModificationEntry>, String> {
+    modification_log::read_entries(&modification_log_dir(&app_handle)?, limit)
+}
+
+#[tauri::command]
+fn clear_modification_log(app_handle: tauri::AppHandle) -> Result<(), String> {
+    modification_log::clear(&modification_log_dir(&app_handle)?)
+}
+
+// ===== End Actionmaps Watcher Commands =====
+
+// ===== Essentials Commands =====
+
+fn essentials_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+#[tauri::command]
+fn get_essentials(app_handle: tauri::AppHandle) -> Result<essentials::Essentials, String> {
+    essentials::load_essentials(&essentials_dir(&app_handle)?)
+}
+
+/// Replace the essentials list. With `auto_restore` set, a running actionmaps watcher puts
+/// missing essentials back as soon as it sees them disappear.
+#[tauri::command]
+fn set_essentials(
+    essentials: essentials::Essentials,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    essentials::save_essentials(&essentials_dir(&app_handle)?, &essentials)
+}
+
+/// Which essentials are missing from actionmaps.xml, and whether it looks freshly wiped
+#[tauri::command]
+fn check_essentials(
+    actionmaps_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<essentials::EssentialsStatus, String> {
+    let xml = std::fs::read_to_string(&actionmaps_path)
+        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+    essentials::check(
+        &xml,
+        &essentials::load_essentials(&essentials_dir(&app_handle)?)?,
+    )
+}
+
+/// Put just the missing essentials back into actionmaps.xml, leaving everything else alone
+#[tauri::command]
+fn restore_essentials(
+    actionmaps_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<essentials::RestoreSummary, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let essentials = essentials::load_essentials(&essentials_dir(&app_handle)?)?;
+
+    let xml = std::fs::read_to_string(&actionmaps_path)
+        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+    let Some((new_xml, summary)) =
+        essentials::restore(&xml, &essentials, &excluded_options(&app_handle)?)?
+    else {
+        return Ok(essentials::RestoreSummary::default());
+    };
+
+    let backup_path = backups::create_backup(&backup_location(&app_handle)?, &actionmaps_path)?;
+    info!("Created backup at: {}", backup_path);
+
+    modification_log::note_own_write(&new_xml);
+    std::fs::write(&actionmaps_path, new_xml)
+        .map_err(|e| format!("Failed to write actionmaps.xml: {}", e))?;
+
+    info!(
+        "Restored {} essential binding(s) and {} essential option(s) to {}",
+        summary.bindings_restored, summary.options_restored, actionmaps_path
+    );
+    Ok(summary)
+}
+
+// ===== End Essentials Commands =====
 
-    // Serialize to JSON
-    let json = controls_file.to_json()?;
+// ===== Environment Dashboard Commands =====
 
-    // Write to file
-    std::fs::write(&file_path, json)
-        .map_err(|e| format!("Failed to write controls file: {}", e))?;
+fn environments_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
 
-    info!("Controls file saved successfully");
-    Ok(())
+/// Pin a .sccontrols profile to an environment (LIVE, PTU, ...), or unpin with None
+#[tauri::command]
+fn pin_environment_profile(
+    environment: String,
+    profile_path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let config_dir = app_config_dir(&app_handle)?;
+    let mut settings = settings::load_settings(&config_dir)?;
+    match profile_path {
+        Some(path) => settings.pinned_profiles.insert(environment, path),
+        None => settings.pinned_profiles.remove(&environment),
+    };
+    settings::save_settings(&config_dir, &settings)
 }
 
-/// Load control settings from a .sccontrols file
+/// For each environment installed under `base_path` (the StarCitizen folder): the pinned
+/// profile, whether actionmaps.xml matches it, the last apply and the last external change
 #[tauri::command]
-fn load_controls_file(file_path: String) -> Result<controls::LoadControlsOutput, String> {
-    info!("Loading controls file from: {}", file_path);
+fn get_environment_dashboard(
+    base_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<environments::EnvironmentStatus>, String> {
+    environment_dashboard(&app_handle, &base_path)
+}
 
-    // Read the file
-    let json = std::fs::read_to_string(&file_path)
-        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+/// The dashboard rows, shared with the drift audit
+fn environment_dashboard(
+    app_handle: &tauri::AppHandle,
+    base_path: &str,
+) -> Result<Vec<environments::EnvironmentStatus>, String> {
+    let settings = settings::load_settings(&app_config_dir(app_handle)?)?;
+    let devices = directinput::detect_joysticks().unwrap_or_default();
+    let data_dir = environments_dir(app_handle)?;
+
+    environments::dashboard(
+        std::path::Path::new(base_path),
+        &settings.pinned_profiles,
+        &data_dir,
+        &settings.excluded_options,
+        |profile_path| {
+            let json = std::fs::read_to_string(profile_path)
+                .map_err(|e| format!("Failed to read {}: {}", profile_path, e))?;
+            let json = if variables::find_variables(&json).is_empty() {
+                json
+            } else {
+                variables::substitute(&json, &profile_variable_values(app_handle, &devices)?)?
+            };
+            // Compared the way it would be applied, over the baseline and under overlays
+            profile_layers::compose_for_apply(&data_dir, &controls::ControlsFile::from_json(&json)?)
+        },
+    )
+}
 
-    // Parse the JSON
-    let controls_file = controls::ControlsFile::from_json(&json)?;
+// ===== End Environment Dashboard Commands =====
 
-    info!(
-        "Loaded controls file: {} (version {})",
-        controls_file.profile_name, controls_file.version
-    );
+// ===== Drift Audit Commands =====
 
-    // Convert to output format for frontend
-    Ok(controls_file.into())
+/// Directory holding the last drift audit report
+fn drift_audit_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
 }
 
-/// Read control options from actionmaps.xml for importing
-#[tauri::command]
-fn import_controls_from_actionmaps(
-    actionmaps_path: String,
-) -> Result<controls::LoadControlsOutput, String> {
-    info!(
-        "Importing controls from actionmaps.xml: {}",
-        actionmaps_path
-    );
+/// Start the daily audit for a configuration
+fn start_drift_audit(
+    app_handle: &tauri::AppHandle,
+    config: drift_audit::DriftAuditConfig,
+) -> Result<drift_audit::DriftAudit, String> {
+    let dashboard_handle = app_handle.clone();
+    Ok(drift_audit::DriftAudit::start(
+        app_handle.clone(),
+        drift_audit_dir(app_handle)?,
+        config.hour,
+        move || environment_dashboard(&dashboard_handle, &config.base_path),
+    ))
+}
 
-    // Read the actionmaps.xml file
-    let xml = std::fs::read_to_string(&actionmaps_path)
-        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+#[derive(serde::Serialize)]
+struct DriftAuditStatus {
+    config: Option<drift_audit::DriftAuditConfig>,
+    last_report: Option<drift_audit::AuditReport>,
+}
 
-    // Parse the options elements
-    let device_options = controls::parse_actionmaps_options(&xml)?;
+#[tauri::command]
+fn get_drift_audit(app_handle: tauri::AppHandle) -> Result<DriftAuditStatus, String> {
+    Ok(DriftAuditStatus {
+        config: settings::load_settings(&app_config_dir(&app_handle)?)?.drift_audit,
+        last_report: drift_audit::load_report(&drift_audit_dir(&app_handle)?)?,
+    })
+}
 
-    info!(
-        "Found {} device options in actionmaps.xml",
-        device_options.len()
-    );
+/// Turn the daily drift audit on (at `config.hour`, local time) or off with None
+#[tauri::command]
+fn set_drift_audit(
+    config: Option<drift_audit::DriftAuditConfig>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    if let Some(config) = &config {
+        if config.hour > 23 {
+            return Err(format!("Invalid hour: {}", config.hour));
+        }
+    }
 
-    // Convert to our internal format
-    let mut controls_file = controls::ControlsFile::new("Imported from Star Citizen".to_string());
+    let config_dir = app_config_dir(&app_handle)?;
+    let mut settings = settings::load_settings(&config_dir)?;
+    settings.drift_audit = config.clone();
+    settings::save_settings(&config_dir, &settings)?;
 
-    for device in device_options {
-        let options: std::collections::HashMap<String, controls::ControlOptionSettings> = device
-            .options
-            .iter()
-            .map(|opt| {
-                let mut invert = None;
-                let mut exponent = None;
-
-                for (key, value) in &opt.attributes {
-                    match key.as_str() {
-                        "invert" => invert = Some(value == "1"),
-                        "exponent" => exponent = value.parse().ok(),
-                        _ => {}
-                    }
-                }
+    let mut app_state = state.lock().unwrap();
+    app_state.drift_audit = None;
+    if let Some(config) = config {
+        info!(
+            "Drift audit scheduled daily at {}:00 for {}",
+            config.hour, config.base_path
+        );
+        app_state.drift_audit = Some(start_drift_audit(&app_handle, config)?);
+    }
+    Ok(())
+}
 
-                let curve = if opt.curve_points.is_empty() {
-                    None
-                } else {
-                    Some(controls::CurveData {
-                        points: opt
-                            .curve_points
-                            .iter()
-                            .map(|p| controls::CurvePoint {
-                                input: p.in_val.parse().unwrap_or(0.0),
-                                output: p.out_val.parse().unwrap_or(0.0),
-                            })
-                            .collect(),
-                    })
-                };
+/// Run the audit now, notifying if anything drifted
+#[tauri::command]
+async fn run_drift_audit(
+    base_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<drift_audit::AuditReport, String> {
+    tokio::task::spawn_blocking(move || {
+        drift_audit::run(&app_handle, &drift_audit_dir(&app_handle)?, &|| {
+            environment_dashboard(&app_handle, &base_path)
+        })
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
 
-                let curve_mode = if curve.is_some() {
-                    Some("curve".to_string())
-                } else if exponent.is_some() {
-                    Some("exponent".to_string())
-                } else {
-                    None
-                };
+// ===== End Drift Audit Commands =====
+
+// ===== Local API Commands =====
+
+/// Answer a local API request from the app state:
+/// - `/api/profile`: the loaded actionmaps' profile name, file and devices
+/// - `/api/bindings`: every action with its bindings, defaults included
+/// - `/api/actions/<name>`: one action's bindings in each action map it appears in
+/// - `/api/devices`: connected controllers and, while the input monitor runs, their state
+/// - `/api/events`: WebSocket stream of input and profile changes, see event_stream
+fn local_api_route(
+    app_handle: &tauri::AppHandle,
+    request: &local_api::Request,
+) -> Option<Result<serde_json::Value, String>> {
+    fn json(value: impl serde::Serialize) -> Result<serde_json::Value, String> {
+        serde_json::to_value(value).map_err(|e| format!("Failed to serialize response: {}", e))
+    }
 
-                (
-                    opt.name.clone(),
-                    controls::ControlOptionSettings {
-                        invert,
-                        curve_mode,
-                        exponent,
-                        curve,
-                    },
-                )
+    let state = app_handle.state::<Mutex<AppState>>();
+    let merged = || {
+        let app_state = state.lock().unwrap();
+        app_state
+            .all_binds
+            .as_ref()
+            .ok_or_else(|| "AllBinds.xml not loaded. Please restart the application.".to_string())
+            .map(|all_binds| {
+                all_binds.merge_with_user_bindings(app_state.current_bindings.as_ref())
             })
-            .collect();
+    };
 
-        if !options.is_empty() {
-            let instance_settings = controls::DeviceInstanceSettings {
-                product: Some(device.product.clone()),
-                options,
+    match request.path.trim_end_matches('/') {
+        "/api/profile" => {
+            let app_state = state.lock().unwrap();
+            Some(json(serde_json::json!({
+                "file_name": app_state.current_file_name,
+                "profile_name": app_state.current_bindings.as_ref().map(|b| &b.profile_name),
+                "devices": app_state.current_bindings.as_ref().map(|b| &b.devices),
+            })))
+        }
+        "/api/bindings" => Some(merged().and_then(json)),
+        // Accept the upgrade, making sure there is input to stream
+        event_stream::EVENTS_PATH => {
+            input_snapshot(app_handle);
+            Some(Ok(serde_json::Value::Null))
+        }
+        "/api/devices" => {
+            let connected =
+                diagnostics::first_use(&DEVICE_ENUMERATION_INIT, "device enumeration", || {
+                    directinput::detect_joysticks()
+                });
+            let input = state
+                .lock()
+                .unwrap()
+                .input_monitor
+                .as_ref()
+                .map(|monitor| monitor.latest());
+            Some(connected.and_then(|connected| {
+                json(serde_json::json!({ "connected": connected, "input": input }))
+            }))
+        }
+        path => {
+            let name = path.strip_prefix("/api/actions/")?;
+            let merged = match merged() {
+                Ok(merged) => merged,
+                Err(e) => return Some(Err(e)),
             };
-
-            match device.device_type.as_str() {
-                "keyboard" => controls_file.devices.keyboard = Some(instance_settings),
-                "gamepad" => controls_file.devices.gamepad = Some(instance_settings),
-                "joystick" => {
-                    let joysticks = controls_file
-                        .devices
-                        .joystick
-                        .get_or_insert(std::collections::HashMap::new());
-                    joysticks.insert(device.instance.clone(), instance_settings);
+            let mut found = Vec::new();
+            for map in merged.action_maps {
+                for action in map.actions.into_iter().filter(|action| action.name == name) {
+                    found.push(serde_json::json!({ "action_map": map.name, "action": action }));
                 }
-                _ => {}
             }
+            (!found.is_empty()).then(|| json(found))
         }
     }
+}
 
-    Ok(controls_file.into())
+fn start_local_api(
+    app_handle: &tauri::AppHandle,
+    config: local_api::LocalApiConfig,
+) -> Result<local_api::LocalApi, String> {
+    let router_handle = app_handle.clone();
+    local_api::LocalApi::start(config, move |request| {
+        local_api_route(&router_handle, request)
+    })
 }
 
-/// Apply control settings to actionmaps.xml
 #[tauri::command]
-fn apply_controls_to_actionmaps(
-    actionmaps_path: String,
-    settings: serde_json::Value,
-    profile_name: String,
-) -> Result<controls::ApplyControlsResult, String> {
-    info!("Applying controls to actionmaps.xml: {}", actionmaps_path);
-
-    // Parse the settings
-    let devices: controls::DeviceSettingsInput =
-        serde_json::from_value(settings).map_err(|e| format!("Failed to parse settings: {}", e))?;
+fn get_local_api(
+    app_handle: tauri::AppHandle,
+) -> Result<Option<local_api::LocalApiConfig>, String> {
+    Ok(settings::load_settings(&app_config_dir(&app_handle)?)?.local_api)
+}
 
-    let input = controls::SaveControlsInput {
-        profile_name: profile_name.clone(),
-        devices,
-    };
+/// Turn the local API on or off (None). An empty token, or no port, keeps the current one or
+/// picks a new one; the saved configuration is returned so the token can be shown.
+#[tauri::command]
+fn set_local_api(
+    config: Option<local_api::LocalApiConfig>,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<Option<local_api::LocalApiConfig>, String> {
+    let config_dir = app_config_dir(&app_handle)?;
+    let mut settings = settings::load_settings(&config_dir)?;
+    let config = config
+        .map(|mut config| {
+            if config.token.is_empty() {
+                config.token = match &settings.local_api {
+                    Some(current) => current.token.clone(),
+                    None => local_api::generate_token()?,
+                };
+            }
+            if config.port == 0 {
+                config.port = local_api::DEFAULT_PORT;
+            }
+            Ok::<_, String>(config)
+        })
+        .transpose()?;
 
-    let controls_file: controls::ControlsFile = input.into();
+    // Stop the old server first so a restart on the same port can bind it
+    let mut app_state = state.lock().unwrap();
+    app_state.local_api = None;
+    if let Some(config) = &config {
+        app_state.local_api = Some(start_local_api(&app_handle, config.clone())?);
+    }
+    drop(app_state);
 
-    // Read the existing actionmaps.xml
-    let xml = std::fs::read_to_string(&actionmaps_path)
-        .map_err(|e| format!("Failed to read actionmaps.xml: {}", e))?;
+    settings.local_api = config.clone();
+    settings::save_settings(&config_dir, &settings)?;
+    Ok(config)
+}
 
-    // Create a backup
-    let backup_path = format!(
-        "{}.backup.{}",
-        actionmaps_path,
-        chrono::Local::now().format("%Y%m%d_%H%M%S")
-    );
-    std::fs::copy(&actionmaps_path, &backup_path)
-        .map_err(|e| format!("Failed to create backup: {}", e))?;
+// ===== End Local API Commands =====
 
-    info!("Created backup at: {}", backup_path);
+// ===== Baseline Commands =====
 
-    // Parse existing options
-    let existing_devices = controls::parse_actionmaps_options(&xml)?;
+/// Directory holding the squadron baseline
+fn baseline_data_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
 
-    // Convert our settings to actionmaps format
-    let new_devices = controls::controls_to_actionmaps(&controls_file);
+/// (Re)start or stop watching the baseline's shared file to match its state
+fn restart_baseline_watcher(
+    app_handle: &tauri::AppHandle,
+    state: &tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let data_dir = baseline_data_dir(app_handle)?;
+    let mut app_state = state.lock().unwrap();
+    app_state.baseline_watcher = None;
+    app_state.baseline_watcher =
+        diagnostics::first_use(&FILE_WATCHER_INIT, "file watcher", || {
+            baseline::watch(&data_dir, app_handle.clone())
+        })?;
+    Ok(())
+}
 
-    // Merge new settings with existing ones
-    // For each device type/instance, replace with new settings if we have them
-    let mut merged_devices = existing_devices.clone();
+/// Import a squadron baseline from a shared .sccontrols file (network drive, synced
+/// folder...). It is composed under every apply until disabled or cleared.
+#[tauri::command]
+fn import_baseline(
+    source_path: String,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<baseline::BaselineStatus, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let status = baseline::set_source(&baseline_data_dir(&app_handle)?, &source_path)?;
+    restart_baseline_watcher(&app_handle, &state)?;
+    info!("Imported baseline from {}", source_path);
+    Ok(status)
+}
 
-    for new_device in new_devices {
-        // Find and replace existing device options, or add new one
-        if let Some(existing) = merged_devices
-            .iter_mut()
-            .find(|d| d.device_type == new_device.device_type && d.instance == new_device.instance)
-        {
-            // Merge options: update existing options, add new ones
-            for new_opt in &new_device.options {
-                if let Some(existing_opt) =
-                    existing.options.iter_mut().find(|o| o.name == new_opt.name)
-                {
-                    *existing_opt = new_opt.clone();
-                } else {
-                    existing.options.push(new_opt.clone());
-                }
-            }
-        } else {
-            merged_devices.push(new_device);
+/// The baseline's source and name, with the changes of its latest update. Starts watching
+/// the shared file if that isn't running yet.
+#[tauri::command]
+fn get_baseline_status(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<baseline::BaselineStatus, String> {
+    let status = baseline::status(&baseline_data_dir(&app_handle)?)?;
+    let watching = state.lock().unwrap().baseline_watcher.is_some();
+    if status.enabled && !watching {
+        if let Err(e) = restart_baseline_watcher(&app_handle, &state) {
+            error!("Could not watch the baseline: {}", e);
         }
     }
+    Ok(status)
+}
+
+/// Re-read the shared file now. Returns the changes if it was updated.
+#[tauri::command]
+fn check_baseline_update(
+    app_handle: tauri::AppHandle,
+) -> Result<Option<Vec<diff::OptionChange>>, String> {
+    baseline::refresh(&baseline_data_dir(&app_handle)?)
+}
+
+#[tauri::command]
+fn set_baseline_enabled(
+    enabled: bool,
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    baseline::set_enabled(&baseline_data_dir(&app_handle)?, enabled)?;
+    restart_baseline_watcher(&app_handle, &state)?;
+    info!("Baseline {}", if enabled { "enabled" } else { "disabled" });
+    Ok(())
+}
 
-    // Now we need to reconstruct the XML with updated options
-    // This is a bit complex because we need to preserve the overall structure
+#[tauri::command]
+fn clear_baseline(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<Mutex<AppState>>,
+) -> Result<(), String> {
+    let _write_lock = begin_write(&app_handle)?;
+    state.lock().unwrap().baseline_watcher = None;
+    baseline::clear(&baseline_data_dir(&app_handle)?)?;
+    info!("Baseline cleared");
+    Ok(())
+}
 
-    // Find the positions of <options> elements and </ActionProfiles>
-    // We'll replace the options section while preserving everything else
+// ===== End Baseline Commands =====
 
-    // Simple approach: find where options start and end, replace that section
-    let options_start = xml.find("<options");
-    let modifiers_pos = xml.find("<modifiers");
+// ===== Profile Layer Commands =====
 
-    if options_start.is_none() || modifiers_pos.is_none() {
-        return Err("Could not find options section in actionmaps.xml".to_string());
-    }
+fn profile_layers_data_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
 
-    let options_start = options_start.unwrap();
-    let modifiers_pos = modifiers_pos.unwrap();
+/// The overlay stack, bottom first
+#[tauri::command]
+fn get_profile_layers(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<profile_layers::OverlayStatus>, String> {
+    profile_layers::status(&profile_layers_data_dir(&app_handle)?)
+}
 
-    // Build the new options section
-    let mut new_options_section = String::new();
-    for device in &merged_devices {
-        new_options_section.push_str(&controls::generate_options_xml(device));
-    }
+/// Replace the overlay stack (add, remove, reorder, enable or disable overlays). Later
+/// overlays win over earlier ones.
+#[tauri::command]
+fn set_profile_layers(
+    overlays: Vec<profile_layers::OverlayEntry>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<profile_layers::OverlayStatus>, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let data_dir = profile_layers_data_dir(&app_handle)?;
+    profile_layers::set_overlays(&data_dir, overlays)?;
+    info!("Profile layers updated");
+    profile_layers::status(&data_dir)
+}
 
-    // Reconstruct the XML
-    let new_xml = format!(
-        "{}{}  {}",
-        &xml[..options_start],
-        new_options_section,
-        &xml[modifiers_pos..]
-    );
+/// What applying a profile would write once the baseline and overlays are resolved, and
+/// which layers set each option
+#[tauri::command]
+fn get_effective_profile(
+    profile_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<profile_layers::EffectiveProfile, String> {
+    let json = std::fs::read_to_string(&profile_path)
+        .map_err(|e| format!("Failed to read {}: {}", profile_path, e))?;
+    profile_layers::effective(
+        &profile_layers_data_dir(&app_handle)?,
+        &controls::ControlsFile::from_json(&json)?,
+    )
+}
 
-    // Write the updated XML
-    std::fs::write(&actionmaps_path, new_xml)
-        .map_err(|e| format!("Failed to write actionmaps.xml: {}", e))?;
+// ===== End Profile Layer Commands =====
 
-    info!("Successfully applied controls to actionmaps.xml");
+// ===== Bundle Commands =====
 
-    Ok(controls::ApplyControlsResult {
-        success: true,
-        backup_path: Some(backup_path),
-        message:
-            "Controls applied successfully. Please restart Star Citizen for changes to take effect."
-                .to_string(),
-    })
+/// Directory the preview images of imported bundles are unpacked into
+fn bundle_previews_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("bundle_previews"))
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
 }
 
-/// Find the default actionmaps.xml path for a given SC installation
+/// Connected devices' identities for matching bundle devices; without them a bundle
+/// still works, it just can't tell identical sticks apart
+fn identities_for_bundle(app_handle: &tauri::AppHandle) -> Vec<device_identity::DeviceIdentity> {
+    match device_identities(app_handle) {
+        Ok(report) => report.devices,
+        Err(e) => {
+            warn!("Bundle devices won't carry identities: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// A bundle added to the library
+#[derive(serde::Serialize)]
+struct BundleImport {
+    profile: profile_library::ProfileSummary,
+    manifest: bundle::BundleManifest,
+    /// Where the preview images were unpacked
+    preview_paths: Vec<String>,
+    /// Devices the bundle needs that aren't connected
+    missing_devices: Vec<bundle::BundleDevice>,
+}
+
+/// Pack a .sccontrols profile into a .boxxy bundle with its required devices, the
+/// author's details, notes and preview images
 #[tauri::command]
-fn find_actionmaps_path(base_path: String) -> Result<Option<String>, String> {
-    use std::path::Path;
+fn export_profile_bundle(
+    profile_path: String,
+    output_path: String,
+    metadata: bundle::BundleMetadata,
+    app_handle: tauri::AppHandle,
+) -> Result<bundle::BundleManifest, String> {
+    let json = std::fs::read_to_string(&profile_path)
+        .map_err(|e| format!("Failed to read controls file: {}", e))?;
+    let controls_file = controls::ControlsFile::from_json(&json)?;
 
-    let base = Path::new(&base_path);
+    let previews = metadata
+        .previews
+        .iter()
+        .map(|path| {
+            let path = std::path::Path::new(path);
+            Ok(bundle::Preview {
+                name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                data: std::fs::read(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
 
-    // First, check if the base_path itself is an installation folder
-    // (e.g., D:\Games\StarCitizen\LIVE)
-    let direct_actionmaps = base
-        .join("user")
-        .join("client")
-        .join("0")
-        .join("Profiles")
-        .join("default")
-        .join("actionmaps.xml");
+    let devices = bundle::required_devices(
+        &controls_file,
+        &identities_for_bundle(&app_handle),
+        &load_device_nicknames(&app_handle)?,
+    );
+    let manifest = bundle::manifest(&controls_file, &metadata, devices, &previews)?;
 
-    if direct_actionmaps.exists() {
-        return Ok(Some(direct_actionmaps.to_string_lossy().to_string()));
+    let mut output_path = std::path::PathBuf::from(output_path);
+    if output_path.extension().and_then(|e| e.to_str()) != Some(bundle::BUNDLE_EXTENSION) {
+        output_path.set_extension(bundle::BUNDLE_EXTENSION);
     }
+    let file = std::fs::File::create(&output_path)
+        .map_err(|e| format!("Failed to create {}: {}", output_path.display(), e))?;
+    bundle::write_bundle(file, &manifest, &controls_file, &previews)?;
 
-    // Otherwise, check if it's a parent folder containing LIVE/PTU/etc.
-    let sc_folders = ["LIVE", "PTU", "EPTU", "TECH-PREVIEW"];
-
-    for folder in &sc_folders {
-        let actionmaps_path = base
-            .join(folder)
-            .join("user")
-            .join("client")
-            .join("0")
-            .join("Profiles")
-            .join("default")
-            .join("actionmaps.xml");
+    info!(
+        "Exported bundle {} ({} devices, {} previews)",
+        output_path.display(),
+        manifest.devices.len(),
+        manifest.previews.len()
+    );
+    Ok(manifest)
+}
 
-        if actionmaps_path.exists() {
-            return Ok(Some(actionmaps_path.to_string_lossy().to_string()));
+/// Add a .boxxy bundle's profile to the library, unpack its previews and report which of
+/// its devices aren't connected
+#[tauri::command]
+fn import_profile_bundle(
+    bundle_path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<BundleImport, String> {
+    let _write_lock = begin_write(&app_handle)?;
+    let file = std::fs::File::open(&bundle_path)
+        .map_err(|e| format!("Failed to open {}: {}", bundle_path, e))?;
+    let contents = bundle::read_bundle(file)?;
+
+    let profile =
+        profile_library::add_profile(&profile_library_dir(&app_handle)?, contents.controls)?;
+
+    let mut preview_paths = Vec::new();
+    if !contents.previews.is_empty() {
+        let dir = bundle_previews_dir(&app_handle)?
+            .join(profile.file_name.trim_end_matches(".sccontrols"));
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create previews directory: {}", e))?;
+        for preview in &contents.previews {
+            let path = dir.join(&preview.name);
+            std::fs::write(&path, &preview.data)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            preview_paths.push(path.to_string_lossy().to_string());
         }
     }
 
-    Ok(None)
+    let missing_devices = bundle::missing_devices(
+        &contents.manifest.devices,
+        &identities_for_bundle(&app_handle),
+    );
+    info!(
+        "Imported bundle {} by {} as {}",
+        bundle_path, contents.manifest.author, profile.file_name
+    );
+
+    Ok(BundleImport {
+        profile,
+        manifest: contents.manifest,
+        preview_paths,
+        missing_devices,
+    })
 }
 
-// ===== End Controls File Commands =====
+// ===== End Bundle Commands =====
+
+/// Run a headless CLI command if the process arguments name one (see cli.rs).
+/// Returns the exit code, or None when the GUI should start.
+pub fn run_cli() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    cli::run(&args)
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    diagnostics::mark_process_start();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![settings::AUTOSTART_ARG]),
+        ))
         .manage(Mutex::new(AppState::new()))
         .invoke_handler(tauri::generate_handler![
             get_app_version,
             greet,
             detect_joysticks,
             get_connected_devices,
+            get_device_nicknames,
+            set_device_nickname,
+            get_device_identities,
+            enumerate_hid_controllers,
+            get_device_capabilities,
+            probe_device,
+            get_vjoy_status,
+            get_feeder_config,
+            read_game_attributes,
+            set_profile_game_attributes,
+            apply_profile_game_attributes,
+            set_feeder_config,
+            start_vjoy_feeder,
+            stop_vjoy_feeder,
+            get_vjoy_feeder_status,
+            start_device_monitor,
+            stop_device_monitor,
+            start_input_monitor,
+            stop_input_monitor,
+            calibrate_deadzone,
+            stick_drift_report,
+            calibrate_axis_ranges,
+            apply_range_saturation,
+            get_inversion_prompt,
+            check_inversion,
+            apply_inversion,
             detect_axis_movement,
             wait_for_input_binding,
+            capture_input_binding,
             wait_for_inputs_with_events,
             load_keybindings,
             update_binding,
+            check_binding_rules,
             reset_binding,
             swap_device_prefixes,
+            remap_joystick_instances,
+            propose_device_remap,
+            apply_device_remap,
             get_current_bindings,
             export_keybindings,
+            export_keyboard_only_profile,
+            export_cheat_sheet,
+            export_reference_cards,
+            export_voiceattack_profile,
+            evaluate_curve,
+            exponent_to_curve,
+            curve_to_exponent,
+            export_bindings_csv,
+            import_bindings_csv,
+            export_options_csv,
+            import_options_csv,
             save_template,
             load_template,
             load_all_binds,
             get_all_binds_xml,
             get_merged_bindings,
+            get_action_categories,
+            search_actions,
+            get_critical_unbound_actions,
+            check_installed_identifiers,
+            migrate_to_installed_version,
             get_user_customizations,
             restore_user_customizations,
             find_conflicting_bindings,
+            parse_binding_input,
+            format_binding_input,
+            binding_input_label,
+            record_conflict_resolution,
+            get_conflict_resolutions,
+            reapply_conflict_resolutions,
             clear_specific_binding,
             clear_custom_bindings,
             update_control_options,
             scan_sc_installations,
             get_current_file_name,
             save_bindings_to_install,
+            export_to_mappings,
             write_binary_file,
             log_error,
             log_info,
             get_log_file_path,
+            get_recent_logs,
             get_resource_dir,
             open_url,
             generate_unbind_profile,
@@ -2532,17 +6434,160 @@ pub fn run() {
             get_hid_device_path,
             // Controls file commands
             save_controls_file,
+            copy_device_settings,
+            invert_device_axes,
+            flip_device_vertical_axes,
+            set_device_role,
+            suggest_device_roles,
             load_controls_file,
+            get_effective_option_values,
             import_controls_from_actionmaps,
+            recover_actionmaps,
+            parse_actionmaps_options_paged,
+            list_exported_mappings,
+            import_exported_mapping,
+            import_gremlin_profile,
             apply_controls_to_actionmaps,
-            find_actionmaps_path
+            find_actionmaps_path,
+            get_option_catalog,
+            validate_curves,
+            // Settings commands
+            get_autostart_status,
+            set_autostart,
+            get_device_variables,
+            set_device_variable,
+            resolve_device_variables,
+            get_startup_diagnostics,
+            get_read_only,
+            set_read_only,
+            get_excluded_options,
+            set_excluded_options,
+            get_apply_confirmation_policy,
+            set_apply_confirmation_policy,
+            get_backup_location,
+            set_backup_location,
+            // Snapshot commands
+            take_snapshot,
+            list_snapshots,
+            set_snapshot_note,
+            restore_snapshot,
+            delete_snapshot,
+            list_backups,
+            preview_restore,
+            restore_actionmaps,
+            diff_snapshots,
+            diff_sources,
+            // Curve watchdog commands
+            start_curve_watchdog,
+            stop_curve_watchdog,
+            get_curve_watchdog_status,
+            // Curve export commands
+            export_curve,
+            // Curve preset commands
+            list_curve_presets,
+            save_curve_preset,
+            delete_curve_preset,
+            apply_curve_preset,
+            // Curve A/B commands
+            start_curve_ab_test,
+            swap_curve_ab_test,
+            choose_curve_ab_variant,
+            list_curve_ab_tests,
+            generate_axis_feel,
+            // Profile library commands
+            get_profile_library_dir,
+            check_profile_name,
+            list_library_profiles,
+            create_library_profile,
+            list_control_presets,
+            create_profile_from_preset,
+            list_hotas_templates,
+            create_profile_from_template,
+            list_role_templates,
+            create_role_profile,
+            duplicate_library_profile,
+            rename_library_profile,
+            delete_library_profile,
+            // Actionmaps watcher commands
+            start_actionmaps_watcher,
+            stop_actionmaps_watcher,
+            get_actionmaps_watcher_status,
+            get_modification_log,
+            clear_modification_log,
+            // Essentials commands
+            get_essentials,
+            set_essentials,
+            check_essentials,
+            restore_essentials,
+            // Environment dashboard commands
+            pin_environment_profile,
+            get_environment_dashboard,
+            get_drift_audit,
+            set_drift_audit,
+            run_drift_audit,
+            get_local_api,
+            set_local_api,
+            // Baseline commands
+            import_baseline,
+            get_baseline_status,
+            check_baseline_update,
+            set_baseline_enabled,
+            clear_baseline,
+            get_profile_layers,
+            set_profile_layers,
+            get_effective_profile,
+            // Bundle commands
+            export_profile_bundle,
+            import_profile_bundle
         ])
         .setup(|app| {
             // Set up logging
-            if let Err(e) = setup_logging(app.handle()) {
+            if let Err(e) = diagnostics::stage("logging", || setup_logging(app.handle())) {
                 eprintln!("Failed to set up logging: {}", e);
             }
 
+            // When launched by the autostart entry, honor the start minimized preference
+            if std::env::args().any(|arg| arg == settings::AUTOSTART_ARG) {
+                let start_minimized = diagnostics::stage("settings", || {
+                    app_config_dir(app.handle()).and_then(|dir| settings::load_settings(&dir))
+                })
+                .map(|s| s.start_minimized)
+                .unwrap_or(true);
+
+                if start_minimized {
+                    if let Some(window) = app.get_webview_window("main") {
+                        info!("Launched at login, starting minimized");
+                        let _ = window.minimize();
+                    }
+                }
+            }
+
+            match app_config_dir(app.handle()).and_then(|dir| settings::load_settings(&dir)) {
+                Ok(settings) => {
+                    if let Some(config) = settings.drift_audit {
+                        match start_drift_audit(app.handle(), config) {
+                            Ok(audit) => {
+                                app.state::<Mutex<AppState>>().lock().unwrap().drift_audit =
+                                    Some(audit)
+                            }
+                            Err(e) => error!("Failed to start drift audit: {}", e),
+                        }
+                    }
+                    if let Some(config) = settings.local_api {
+                        match start_local_api(app.handle(), config) {
+                            Ok(api) => {
+                                app.state::<Mutex<AppState>>().lock().unwrap().local_api = Some(api)
+                            }
+                            Err(e) => error!("Failed to start local API: {}", e),
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to load settings: {}", e),
+            }
+
+            // Everything heavier (device enumeration, watchers, the option catalog) is
+            // initialized on first use, see get_startup_diagnostics
+            diagnostics::mark_startup_done();
             Ok(())
         })
         .build(tauri::generate_context!())
@@ -0,0 +1,378 @@
+//! Local HTTP API for companion tools
+//!
+//! Stream Deck plugins and overlays want to know what's bound to what without parsing
+//! actionmaps.xml themselves. When turned on, a small HTTP server on 127.0.0.1 answers
+//! read-only GET requests with JSON. Every request must carry the token from the
+//! settings, either as `Authorization: Bearer <token>` or as a `token` query parameter,
+//! so other local software (or a web page poking at localhost) can't read the profile.
+//! The endpoints themselves are routed by the caller; this module only speaks HTTP.
+
+use crate::event_stream;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Port used when the settings don't name one
+pub const DEFAULT_PORT: u16 = 47_210;
+
+/// Largest request head we read; the API has no request bodies
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+
+/// How long a client gets to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the accept loop checks whether it should stop
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Connections served at once, event streams included; more are turned away
+const MAX_CONNECTIONS: usize = 16;
+
+/// Where to listen and the token clients must send, stored in the settings
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LocalApiConfig {
+    pub port: u16,
+    pub token: String,
+}
+
+/// A random token for a new configuration, from the OS's random number generator
+pub fn generate_token() -> Result<String, String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to generate a token: {}", e))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The parts of an HTTP request the routes need
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string, percent-decoded
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// Header names lowercased
+    pub headers: HashMap<String, String>,
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Parse the request line and headers
+pub fn parse_request(head: &str) -> Result<Request, String> {
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(format!("Malformed request line '{}'", request_line));
+    };
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            // Forms encode spaces in the query as '+'
+            let decode = |text: &str| percent_decode(&text.replace('+', " "));
+            (decode(key), decode(value))
+        })
+        .collect();
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+
+    Ok(Request {
+        method: method.to_uppercase(),
+        path: percent_decode(path),
+        query,
+        headers,
+    })
+}
+
+/// Compare without bailing out at the first difference, so response times don't leak
+/// how much of a guessed token was right
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Does the request carry the token?
+pub fn is_authorized(request: &Request, token: &str) -> bool {
+    if token.is_empty() {
+        return false;
+    }
+    let bearer = request
+        .headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer
+        .or(request.query.get("token").map(String::as_str))
+        .is_some_and(|given| tokens_match(given.trim(), token))
+}
+
+/// Answers a route with JSON, None when there is no such route, or an error message
+pub type Router = dyn Fn(&Request) -> Option<Result<serde_json::Value, String>> + Send + Sync;
+
+/// Status code and JSON body for a request
+pub fn respond(request: &Request, token: &str, router: &Router) -> (u16, String) {
+    let error = |status: u16, message: &str| (status, serde_json::json!({ "error": message }));
+    let (status, body) = if request.method != "GET" {
+        error(405, "Only GET is supported")
+    } else if !is_authorized(request, token) {
+        error(401, "Missing or wrong token")
+    } else {
+        match router(request) {
+            Some(Ok(body)) => (200, body),
+            Some(Err(e)) => error(500, &e),
+            None => error(404, &format!("No endpoint {}", request.path)),
+        }
+    };
+    (status, body.to_string())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Read up to the blank line ending the request head
+fn read_head(stream: &mut TcpStream) -> Result<String, String> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_BYTES {
+            return Err("Request too large".to_string());
+        }
+        let read = stream
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read request: {}", e))?;
+        if read == 0 {
+            return Err("Connection closed before the request ended".to_string());
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    String::from_utf8(head).map_err(|_| "Request isn't UTF-8".to_string())
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    token: &str,
+    router: &Router,
+    stop: &AtomicBool,
+) -> Result<(), String> {
+    stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(READ_TIMEOUT)))
+        .map_err(|e| format!("Failed to configure connection: {}", e))?;
+
+    let (status, body) = match read_head(&mut stream).and_then(|head| parse_request(&head)) {
+        // Browser-based tools send a preflight before the real request
+        Ok(request) if request.method == "OPTIONS" => (204, String::new()),
+        // The route only has to accept the upgrade; the stream itself is event_stream's
+        Ok(request) if event_stream::is_upgrade(&request) => {
+            match respond(&request, token, router) {
+                (200, _) => return event_stream::serve(stream, &request, stop),
+                refused => refused,
+            }
+        }
+        Ok(request) => respond(&request, token, router),
+        Err(e) => (400, serde_json::json!({ "error": e }).to_string()),
+    };
+
+    write_response(&mut stream, status, &body)
+}
+
+fn write_response(stream: &mut TcpStream, status: u16, body: &str) -> Result<(), String> {
+    let response = format!(
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Headers: Authorization\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    stream
+        .write_all(response.as_bytes())
+        .map_err(|e| format!("Failed to write response: {}", e))
+}
+
+/// Counts a connection as open until it's dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A running API server. Dropping it stops listening, frees the port and ends any
+/// event streams.
+pub struct LocalApi {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl LocalApi {
+    /// Listen on 127.0.0.1 at the configured port, answering requests through `router`
+    pub fn start(
+        config: LocalApiConfig,
+        router: impl Fn(&Request) -> Option<Result<serde_json::Value, String>> + Send + Sync + 'static,
+    ) -> Result<Self, String> {
+        if config.token.is_empty() {
+            return Err("The local API needs a token".to_string());
+        }
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", config.port, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure listener: {}", e))?;
+        info!("Local API listening on 127.0.0.1:{}", config.port);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let router: Arc<Router> = Arc::new(router);
+        let token: Arc<str> = config.token.into();
+        let open = Arc::new(AtomicUsize::new(0));
+
+        let thread = thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((mut stream, _)) => {
+                        if open.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+                            open.fetch_sub(1, Ordering::Relaxed);
+                            warn!(
+                                "Local API turned a connection away, {} already open",
+                                MAX_CONNECTIONS
+                            );
+                            let body = serde_json::json!({ "error": "Too many connections" });
+                            let _ = stream.set_nonblocking(false);
+                            let _ = write_response(&mut stream, 503, &body.to_string());
+                            continue;
+                        }
+                        let slot = ConnectionSlot(open.clone());
+                        let router = router.clone();
+                        let token = token.clone();
+                        let stop = thread_stop.clone();
+                        thread::spawn(move || {
+                            let _slot = slot;
+                            if let Err(e) =
+                                handle_connection(stream, &token, router.as_ref(), &stop)
+                            {
+                                warn!("Local API request failed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_INTERVAL)
+                    }
+                    Err(e) => {
+                        error!("Local API stopped accepting connections: {}", e);
+                        break;
+                    }
+                }
+            }
+            info!("Local API stopped");
+        });
+
+        Ok(LocalApi {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for LocalApi {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_need_the_token() {
+        let router = |request: &Request| match request.path.as_str() {
+            "/api/actions/v_toggle landing" => Some(Ok(serde_json::json!({ "bound": true }))),
+            "/api/broken" => Some(Err("AllBinds.xml not loaded".to_string())),
+            _ => None,
+        };
+        let request = |head: &str| parse_request(head).unwrap();
+
+        let get = request(
+            "GET /api/actions/v_toggle%20landing?x=1 HTTP/1.1\r\n\
+             Host: 127.0.0.1\r\nAuthorization: Bearer s3cret\r\n\r\n",
+        );
+        assert_eq!(get.path, "/api/actions/v_toggle landing");
+        assert_eq!(get.query.get("x").map(String::as_str), Some("1"));
+        assert_eq!(
+            respond(&get, "s3cret", &router),
+            (200, r#"{"bound":true}"#.to_string())
+        );
+        assert_eq!(respond(&get, "other", &router).0, 401);
+        assert_eq!(respond(&get, "", &router).0, 401);
+
+        let query_token = request("GET /api/broken?token=s3cret HTTP/1.1\r\n\r\n");
+        assert_eq!(
+            respond(&query_token, "s3cret", &router),
+            (500, r#"{"error":"AllBinds.xml not loaded"}"#.to_string())
+        );
+        let missing = request("GET /nothing?token=s3cret HTTP/1.1\r\n\r\n");
+        assert_eq!(respond(&missing, "s3cret", &router).0, 404);
+        let post = request("POST /api/broken?token=s3cret HTTP/1.1\r\n\r\n");
+        assert_eq!(respond(&post, "s3cret", &router).0, 405);
+
+        assert!(parse_request("garbage\r\n\r\n").is_err());
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, generate_token().unwrap());
+    }
+}
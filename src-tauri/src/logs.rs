@@ -0,0 +1,153 @@
+//! Log files and reading them back
+//!
+//! The backend logs through `tracing`, and records from the `log` macros used across the
+//! crate are forwarded into it, so both end up in the same daily rotating file in the app
+//! log directory. Key events (parse results, applies, backups, device changes) carry
+//! structured fields. Users reporting a bug can fetch the most recent lines from inside
+//! the app instead of digging the file out of AppData.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::time::ChronoLocal;
+use tracing_subscriber::EnvFilter;
+
+/// Log files are named `boxxy-binder.<date>.log`
+pub const LOG_FILE_PREFIX: &str = "boxxy-binder";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// How many daily log files are kept
+const KEEP_LOG_FILES: usize = 3;
+
+/// Lines returned when the caller doesn't ask for a number
+pub const DEFAULT_RECENT_LINES: usize = 500;
+
+/// Set up the global subscriber writing to a daily file in `log_dir`. `RUST_LOG`
+/// overrides the default level of info.
+pub fn init(log_dir: &Path) -> Result<(), String> {
+    remove_old_files(log_dir);
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(KEEP_LOG_FILES)
+        .build(log_dir)
+        .map_err(|e| format!("Failed to open log file: {}", e))?;
+
+    tracing_subscriber::fmt()
+        .with_writer(appender)
+        .with_ansi(false)
+        .with_timer(ChronoLocal::new("%Y-%m-%d %H:%M:%S".to_string()))
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .try_init()
+        .map_err(|e| format!("Failed to set up logging: {}", e))
+}
+
+/// Our log files in `log_dir`, newest first. Also matches the `boxxy-binder-<date>.log`
+/// files of older versions; the date in the name sorts them.
+pub fn log_files(log_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(log_dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .map(|n| n.to_string_lossy())
+                .is_some_and(|n| n.starts_with(LOG_FILE_PREFIX) && n.ends_with(".log"))
+        })
+        .collect();
+    let date = |path: &PathBuf| {
+        path.file_name().and_then(|n| {
+            n.to_string_lossy()
+                .get(LOG_FILE_PREFIX.len() + 1..)
+                .map(str::to_string)
+        })
+    };
+    files.sort_by_key(|path| std::cmp::Reverse(date(path)));
+    files
+}
+
+/// Remove all but the newest KEEP_LOG_FILES files. The appender prunes its own files as
+/// it rotates; this also catches those left by older versions.
+fn remove_old_files(log_dir: &Path) {
+    for file in log_files(log_dir).iter().skip(KEEP_LOG_FILES) {
+        if let Err(e) = std::fs::remove_file(file) {
+            eprintln!("Failed to remove old log file {:?}: {}", file, e);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RecentLogs {
+    /// The file being written to now
+    pub log_file: Option<String>,
+    /// Oldest first, at most the number asked for
+    pub lines: Vec<String>,
+    /// More lines exist than were returned
+    pub truncated: bool,
+}
+
+/// The last `max_lines` lines logged, reaching back into earlier files when the current
+/// one is shorter
+pub fn recent_lines(log_dir: &Path, max_lines: usize) -> RecentLogs {
+    let files = log_files(log_dir);
+    let mut lines: Vec<String> = Vec::new();
+    let mut truncated = false;
+
+    for file in &files {
+        let Ok(bytes) = std::fs::read(file) else {
+            continue;
+        };
+        let content = String::from_utf8_lossy(&bytes);
+        let mut file_lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let wanted = max_lines - lines.len();
+        if file_lines.len() > wanted {
+            file_lines.drain(..file_lines.len() - wanted);
+            truncated = true;
+        }
+        file_lines.append(&mut lines);
+        lines = file_lines;
+        if lines.len() == max_lines {
+            truncated |= file != files.last().unwrap();
+            break;
+        }
+    }
+
+    RecentLogs {
+        log_file: files.first().map(|f| f.to_string_lossy().to_string()),
+        lines,
+        truncated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::TempDir;
+
+    #[test]
+    fn test_recent_lines_span_files() {
+        let temp = TempDir::new("logs");
+        let dir = temp.path();
+        std::fs::write(dir.join("boxxy-binder-2026-10-14.log"), "a\nb\n").unwrap();
+        std::fs::write(dir.join("boxxy-binder.2026-10-15.log"), "c\nd\n").unwrap();
+        std::fs::write(dir.join("boxxy-binder.2026-10-16.log"), "e\nf\n").unwrap();
+        std::fs::write(dir.join("other.log"), "x\n").unwrap();
+
+        let recent = recent_lines(dir, 3);
+        assert_eq!(recent.lines, vec!["d", "e", "f"]);
+        assert!(recent.truncated);
+        assert!(recent
+            .log_file
+            .unwrap()
+            .ends_with("boxxy-binder.2026-10-16.log"));
+
+        let all = recent_lines(dir, 10);
+        assert_eq!(all.lines, vec!["a", "b", "c", "d", "e", "f"]);
+        assert!(!all.truncated);
+    }
+}
@@ -0,0 +1,353 @@
+//! History of changes made to actionmaps.xml by something other than us
+//!
+//! When the actionmaps watcher sees a change we didn't write, the diff is summarized
+//! ("game added 2 options blocks, removed 1 curve") and appended to a JSON lines log in
+//! the app data directory, so users can look back at exactly when and what the game or
+//! another tool altered. Who made the change is a guess from what changed.
+
+use crate::controls;
+use crate::diff::ActionmapsDiff;
+use crate::watcher::content_hash;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Name of the log file inside the app data directory
+pub const LOG_FILE_NAME: &str = "modification_log.jsonl";
+
+/// How many of our own recent writes to remember
+const OWN_WRITES_REMEMBERED: usize = 16;
+
+/// Hashes of content we wrote to actionmaps.xml, so the watcher can tell our writes apart
+static OWN_WRITES: Mutex<VecDeque<u64>> = Mutex::new(VecDeque::new());
+
+/// Record that we are writing `content` to actionmaps.xml. Call before the write so the
+/// watcher can't see the change first.
+pub fn note_own_write(content: &str) {
+    let mut writes = OWN_WRITES.lock().unwrap();
+    writes.push_back(content_hash(content.as_bytes()));
+    while writes.len() > OWN_WRITES_REMEMBERED {
+        writes.pop_front();
+    }
+}
+
+/// Whether `content` is something we wrote ourselves recently
+pub fn is_own_write(content: &str) -> bool {
+    let hash = content_hash(content.as_bytes());
+    OWN_WRITES.lock().unwrap().contains(&hash)
+}
+
+/// Best guess at who changed the file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Attribution {
+    /// Star Citizen rewriting the file (on exit or after a change in the options menu)
+    Game,
+    /// Another remapping tool or a manual edit
+    OtherTool,
+    Unknown,
+}
+
+/// Counts of what changed, by kind
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChangeCounts {
+    /// Whole `<options>` device blocks
+    pub options_blocks_added: usize,
+    pub options_blocks_removed: usize,
+    pub options_added: usize,
+    pub options_removed: usize,
+    pub options_modified: usize,
+    pub curves_added: usize,
+    pub curves_removed: usize,
+    pub bindings_changed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModificationEntry {
+    /// ISO timestamp of when the change was detected
+    pub detected_at: String,
+    pub actionmaps_path: String,
+    pub attribution: Attribution,
+    /// Why we think it was `attribution`
+    pub attribution_reason: String,
+    /// One line description, e.g. "game added 2 options blocks, removed 1 curve"
+    pub summary: String,
+    pub counts: ChangeCounts,
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// (type, instance) of every `<options>` block, or empty if the document doesn't parse
+fn options_blocks(xml: &str) -> BTreeSet<(String, String)> {
+    controls::parse_actionmaps_options(xml)
+        .map(|devices| {
+            devices
+                .into_iter()
+                .map(|d| (d.device_type, d.instance))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn count_changes(before_xml: &str, after_xml: &str, diff: &ActionmapsDiff) -> ChangeCounts {
+    let before_blocks = options_blocks(before_xml);
+    let after_blocks = options_blocks(after_xml);
+
+    let mut counts = ChangeCounts {
+        options_blocks_added: after_blocks.difference(&before_blocks).count(),
+        options_blocks_removed: before_blocks.difference(&after_blocks).count(),
+        bindings_changed: diff.binding_changes.len(),
+        ..Default::default()
+    };
+
+    for change in &diff.option_changes {
+        let had_curve = change
+            .before
+            .as_ref()
+            .is_some_and(|o| !o.curve_points.is_empty());
+        let has_curve = change
+            .after
+            .as_ref()
+            .is_some_and(|o| !o.curve_points.is_empty());
+
+        match (&change.before, &change.after) {
+            (None, Some(_)) => counts.options_added += 1,
+            (Some(_), None) => counts.options_removed += 1,
+            _ => counts.options_modified += 1,
+        }
+        match (had_curve, has_curve) {
+            (false, true) => counts.curves_added += 1,
+            (true, false) => counts.curves_removed += 1,
+            _ => {}
+        }
+    }
+
+    counts
+}
+
+/// Guess who made the change from its shape
+fn attribute(counts: &ChangeCounts) -> (Attribution, String) {
+    // The game doesn't keep curves and writes an options block for every connected device
+    if counts.curves_removed > 0 && counts.curves_added == 0 {
+        return (
+            Attribution::Game,
+            "Curves were dropped, which Star Citizen does when it rewrites the file".to_string(),
+        );
+    }
+    if counts.options_blocks_added > 0 || counts.options_blocks_removed > 0 {
+        return (
+            Attribution::Game,
+            "Device options blocks changed, which happens when the game sees different devices"
+                .to_string(),
+        );
+    }
+    if counts.curves_added > 0 {
+        return (
+            Attribution::OtherTool,
+            "Curves were added, which the game doesn't do on its own".to_string(),
+        );
+    }
+    if counts.bindings_changed > 0 || counts.options_modified > 0 {
+        return (
+            Attribution::Unknown,
+            "Settings changed; could be the in-game options menu or another tool".to_string(),
+        );
+    }
+    (Attribution::Unknown, "Only formatting changed".to_string())
+}
+
+fn describe(attribution: Attribution, counts: &ChangeCounts) -> String {
+    let who = match attribution {
+        Attribution::Game => "game",
+        Attribution::OtherTool => "another tool",
+        Attribution::Unknown => "something",
+    };
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut other = Vec::new();
+    if counts.options_blocks_added > 0 {
+        added.push(plural(counts.options_blocks_added, "options block"));
+    }
+    if counts.options_added > 0 {
+        added.push(plural(counts.options_added, "option"));
+    }
+    if counts.curves_added > 0 {
+        added.push(plural(counts.curves_added, "curve"));
+    }
+    if counts.options_blocks_removed > 0 {
+        removed.push(plural(counts.options_blocks_removed, "options block"));
+    }
+    if counts.options_removed > 0 {
+        removed.push(plural(counts.options_removed, "option"));
+    }
+    if counts.curves_removed > 0 {
+        removed.push(plural(counts.curves_removed, "curve"));
+    }
+    if counts.options_modified > 0 {
+        other.push(format!(
+            "changed {}",
+            plural(counts.options_modified, "option")
+        ));
+    }
+    if counts.bindings_changed > 0 {
+        other.push(format!(
+            "changed {}",
+            plural(counts.bindings_changed, "binding")
+        ));
+    }
+
+    let mut parts = Vec::new();
+    if !added.is_empty() {
+        parts.push(format!("added {}", added.join(" and ")));
+    }
+    if !removed.is_empty() {
+        parts.push(format!("removed {}", removed.join(" and ")));
+    }
+    parts.extend(other);
+
+    if parts.is_empty() {
+        format!("{} rewrote the file without changing settings", who)
+    } else {
+        format!("{} {}", who, parts.join(", "))
+    }
+}
+
+/// Build a log entry for an external change
+pub fn entry_for(
+    actionmaps_path: &str,
+    before_xml: &str,
+    after_xml: &str,
+    diff: &ActionmapsDiff,
+) -> ModificationEntry {
+    let counts = count_changes(before_xml, after_xml, diff);
+    let (attribution, attribution_reason) = attribute(&counts);
+    ModificationEntry {
+        detected_at: chrono::Local::now().to_rfc3339(),
+        actionmaps_path: actionmaps_path.to_string(),
+        attribution,
+        attribution_reason,
+        summary: describe(attribution, &counts),
+        counts,
+    }
+}
+
+fn log_path(dir: &Path) -> PathBuf {
+    dir.join(LOG_FILE_NAME)
+}
+
+pub fn append_entry(dir: &Path, entry: &ModificationEntry) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+    let line = serde_json::to_string(entry)
+        .map_err(|e| format!("Failed to serialize modification entry: {}", e))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path(dir))
+        .map_err(|e| format!("Failed to open modification log: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write modification log: {}", e))
+}
+
+/// Read the log, newest first. Unreadable lines are skipped.
+pub fn read_entries(dir: &Path, limit: Option<usize>) -> Result<Vec<ModificationEntry>, String> {
+    let path = log_path(dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read modification log: {}", e))?;
+    let entries = contents
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    Ok(entries)
+}
+
+pub fn clear(dir: &Path) -> Result<(), String> {
+    let path = log_path(dir);
+    if path.exists() {
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to clear modification log: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff;
+    use crate::sc_sim::TempDir;
+
+    const CURVED: &str = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="joystick" instance="1" Product="Stick">
+   <flight_move_pitch invert="1">
+    <nonlinearity_curve>
+     <point in="0.5" out="0.25"/>
+    </nonlinearity_curve>
+   </flight_move_pitch>
+  </options>
+ </ActionProfiles>
+</ActionMaps>"#;
+
+    const PLAIN: &str = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <options type="joystick" instance="1" Product="Stick">
+   <flight_move_pitch invert="1"/>
+  </options>
+ </ActionProfiles>
+</ActionMaps>"#;
+
+    fn entry(before: &str, after: &str) -> ModificationEntry {
+        let diff = diff::diff_actionmaps(before, after).unwrap();
+        entry_for("actionmaps.xml", before, after, &diff)
+    }
+
+    #[test]
+    fn test_changes_attributed_and_logged() {
+        // Dropped curves are what the game does when it rewrites the file
+        let dropped = entry(CURVED, PLAIN);
+        assert_eq!(dropped.attribution, Attribution::Game);
+        assert_eq!(dropped.counts.curves_removed, 1);
+        assert_eq!(dropped.summary, "game removed 1 curve, changed 1 option");
+
+        // The game never adds curves on its own
+        let added = entry(PLAIN, CURVED);
+        assert_eq!(added.attribution, Attribution::OtherTool);
+        assert_eq!(
+            added.summary,
+            "another tool added 1 curve, changed 1 option"
+        );
+
+        let temp = TempDir::new("modification-log");
+        let dir = temp.path();
+        append_entry(dir, &dropped).unwrap();
+        append_entry(dir, &added).unwrap();
+
+        // Newest first
+        let entries = read_entries(dir, Some(1)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].attribution, Attribution::OtherTool);
+        assert_eq!(read_entries(dir, None).unwrap().len(), 2);
+
+        clear(dir).unwrap();
+        assert!(read_entries(dir, None).unwrap().is_empty());
+
+        // Our own writes are recognized by content
+        note_own_write(PLAIN);
+        assert!(is_own_write(PLAIN));
+        assert!(!is_own_write("<ActionMaps/>"));
+    }
+}
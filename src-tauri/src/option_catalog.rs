@@ -0,0 +1,289 @@
+//! Catalog of control options from AllBinds.xml
+//!
+//! The `<optiontree>` elements in AllBinds.xml describe every option SC exposes per device
+//! type, which UI controls apply to it, and device-specific details like the gamepad
+//! sensitivity range or options whose inversion is driven by a cvar.
+
+use crate::controls::{self, CurvePoint, OptionContext};
+use serde::{Deserialize, Serialize};
+
+/// Summary of one device type's optiontree
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OptionTreeInfo {
+    pub device_type: String,
+    pub instances: u32,
+    pub sensitivity_min: f64,
+    pub sensitivity_max: f64,
+}
+
+/// A single option (optiongroup) from the catalog
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OptionCatalogEntry {
+    pub device_type: String,
+    pub name: String,
+    pub label: Option<String>,
+    /// Name of the parent optiongroup, None for top-level groups
+    pub parent: Option<String>,
+    /// Gameplay context (flight, turret, ground vehicle, on foot, EVA, ...)
+    pub context: OptionContext,
+    /// Visibility with inheritance resolved (UIShow* = -1 inherits from the parent)
+    pub show_invert: bool,
+    pub show_curve: bool,
+    pub show_sensitivity: bool,
+    /// Cvar that controls inversion instead of the option itself (e.g. cl_invertController)
+    pub invert_cvar: Option<String>,
+    pub default_invert: Option<bool>,
+    pub default_exponent: Option<f64>,
+    /// The game's default response curve, from the group's own `<nonlinearity_curve>` or
+    /// the nearest ancestor's (the gamepad tree ships curves for flight, strafe, FPS view)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_curve: Option<Vec<CurvePoint>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OptionCatalog {
+    pub trees: Vec<OptionTreeInfo>,
+    pub options: Vec<OptionCatalogEntry>,
+}
+
+/// Resolve a UIShow* attribute against the parent's visibility
+fn resolve_visibility(attr: Option<&str>, inherited: bool) -> bool {
+    match attr {
+        Some("0") => false,
+        Some("1") => true,
+        _ => inherited,
+    }
+}
+
+/// Visibility state carried down the optiongroup hierarchy
+#[derive(Clone)]
+struct Scope {
+    name: Option<String>,
+    show_invert: bool,
+    show_curve: bool,
+    show_sensitivity: bool,
+    /// Default curve children inherit
+    curve: Option<Vec<CurvePoint>>,
+    /// Index of this group's catalog entry
+    entry: Option<usize>,
+}
+
+/// Parse all optiontrees from AllBinds.xml content
+pub fn parse_option_catalog(xml: &str) -> Result<OptionCatalog, String> {
+    use quick_xml::events::Event;
+    use quick_xml::Reader;
+
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut catalog = OptionCatalog::default();
+    let mut device_type: Option<String> = None;
+    let mut stack: Vec<Scope> = Vec::new();
+    let mut in_curve = false;
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| format!("Error parsing XML: {:?}", e))?;
+
+        let (e, is_empty) = match event {
+            Event::Start(ref e) => (e.clone(), false),
+            Event::Empty(ref e) => (e.clone(), true),
+            Event::End(ref e) => {
+                match e.name().as_ref() {
+                    b"optiontree" => {
+                        device_type = None;
+                        stack.clear();
+                    }
+                    b"optiongroup" => {
+                        stack.pop();
+                    }
+                    b"nonlinearity_curve" => in_curve = false,
+                    _ => {}
+                }
+                buf.clear();
+                continue;
+            }
+            Event::Eof => break,
+            _ => {
+                buf.clear();
+                continue;
+            }
+        };
+
+        let attr = |key: &str| {
+            e.attributes()
+                .flatten()
+                .find(|a| a.key.as_ref() == key.as_bytes())
+                .map(|a| String::from_utf8_lossy(&a.value).into_owned())
+        };
+
+        match e.name().as_ref() {
+            b"optiontree" => {
+                let tree_type = attr("type").unwrap_or_default();
+                catalog.trees.push(OptionTreeInfo {
+                    device_type: tree_type.clone(),
+                    instances: attr("instances").and_then(|v| v.parse().ok()).unwrap_or(1),
+                    sensitivity_min: attr("UISensitivityMin")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0.01),
+                    sensitivity_max: attr("UISensitivityMax")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(2.0),
+                });
+
+                stack.clear();
+                stack.push(Scope {
+                    name: None,
+                    show_invert: resolve_visibility(attr("UIShowInvert").as_deref(), true),
+                    show_curve: resolve_visibility(attr("UIShowCurve").as_deref(), true),
+                    show_sensitivity: resolve_visibility(
+                        attr("UIShowSensitivity").as_deref(),
+                        false,
+                    ),
+                    curve: None,
+                    entry: None,
+                });
+                device_type = Some(tree_type);
+            }
+            b"optiongroup" => {
+                let (Some(tree_type), Some(parent)) = (&device_type, stack.last().cloned()) else {
+                    buf.clear();
+                    continue;
+                };
+
+                let name = attr("name").unwrap_or_default();
+                let scope = Scope {
+                    name: Some(name.clone()),
+                    show_invert: resolve_visibility(
+                        attr("UIShowInvert").as_deref(),
+                        parent.show_invert,
+                    ),
+                    show_curve: resolve_visibility(
+                        attr("UIShowCurve").as_deref(),
+                        parent.show_curve,
+                    ),
+                    show_sensitivity: resolve_visibility(
+                        attr("UIShowSensitivity").as_deref(),
+                        parent.show_sensitivity,
+                    ),
+                    curve: parent.curve.clone(),
+                    entry: Some(catalog.options.len()),
+                };
+
+                catalog.options.push(OptionCatalogEntry {
+                    device_type: tree_type.clone(),
+                    context: controls::option_context(&name),
+                    name,
+                    label: attr("UILabel"),
+                    parent: parent.name.clone(),
+                    show_invert: scope.show_invert,
+                    show_curve: scope.show_curve,
+                    show_sensitivity: scope.show_sensitivity,
+                    invert_cvar: attr("invert_cvar"),
+                    default_invert: attr("invert").map(|v| v == "1"),
+                    default_exponent: attr("exponent").and_then(|v| v.parse().ok()),
+                    default_curve: parent.curve,
+                });
+
+                if !is_empty {
+                    stack.push(scope);
+                }
+            }
+            // A group's own curve replaces the inherited one, for it and its children
+            b"nonlinearity_curve" if device_type.is_some() => {
+                if let Some(scope) = stack.last_mut() {
+                    scope.curve = Some(Vec::new());
+                    if let Some(entry) = scope.entry {
+                        catalog.options[entry].default_curve = Some(Vec::new());
+                    }
+                }
+                in_curve = !is_empty;
+            }
+            b"point" if in_curve => {
+                let point = CurvePoint {
+                    input: attr("in").and_then(|v| v.parse().ok()).unwrap_or(0.0),
+                    output: attr("out").and_then(|v| v.parse().ok()).unwrap_or(0.0),
+                };
+                if let Some(scope) = stack.last_mut() {
+                    if let Some(entry) = scope.entry {
+                        if let Some(curve) = &mut catalog.options[entry].default_curve {
+                            curve.push(point.clone());
+                        }
+                    }
+                    if let Some(curve) = &mut scope.curve {
+                        curve.push(point);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        buf.clear();
+    }
+
+    Ok(catalog)
+}
+
+impl OptionCatalog {
+    /// Options of a device type whose inversion is set on the option itself, with their
+    /// default invert state. Parent groups and cvar-driven inversions are left out.
+    pub fn invertible_options(&self, device_type: &str) -> Vec<(String, bool)> {
+        self.options
+            .iter()
+            .filter(|entry| entry.device_type == device_type)
+            .filter(|entry| entry.show_invert && entry.invert_cvar.is_none())
+            .filter(|entry| {
+                !self.options.iter().any(|child| {
+                    child.device_type == device_type && child.parent.as_ref() == Some(&entry.name)
+                })
+            })
+            .map(|entry| (entry.name.clone(), entry.default_invert.unwrap_or(false)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_curves_inherited() {
+        let catalog = parse_option_catalog(
+            r#"<profile>
+ <optiontree type="gamepad" name="root" UIShowInvert="1">
+  <optiongroup name="flight">
+   <nonlinearity_curve>
+    <point in="0.5" out="0.08"/>
+    <point in="0.9" out="0.58"/>
+   </nonlinearity_curve>
+   <optiongroup name="flight_move">
+    <optiongroup name="flight_move_pitch" invert="1"/>
+    <optiongroup name="flight_move_strafe_lateral">
+     <nonlinearity_curve>
+      <point in="0.4" out="0.0"/>
+     </nonlinearity_curve>
+    </optiongroup>
+   </optiongroup>
+  </optiongroup>
+  <optiongroup name="turret_aim_pitch"/>
+ </optiontree>
+</profile>"#,
+        )
+        .unwrap();
+
+        let curve = |name: &str| -> Option<Vec<(f64, f64)>> {
+            let entry = catalog.options.iter().find(|o| o.name == name).unwrap();
+            entry
+                .default_curve
+                .as_ref()
+                .map(|c| c.iter().map(|p| (p.input, p.output)).collect())
+        };
+        assert_eq!(curve("flight"), Some(vec![(0.5, 0.08), (0.9, 0.58)]));
+        assert_eq!(
+            curve("flight_move_pitch"),
+            Some(vec![(0.5, 0.08), (0.9, 0.58)])
+        );
+        assert_eq!(curve("flight_move_strafe_lateral"), Some(vec![(0.4, 0.0)]));
+        assert_eq!(curve("turret_aim_pitch"), None);
+    }
+}
@@ -0,0 +1,414 @@
+//! Edit the options in actionmaps.xml in place
+//!
+//! Regenerating every `<options>` block on each apply reformats the whole section, so a
+//! change to one curve showed up as hundreds of changed lines and anything the game
+//! wrote in its own way (attribute order, spacing, empty elements) was rewritten too.
+//! Here the file is scanned once for the byte ranges of each `<options>` block and of
+//! each option in it, and only the options whose settings actually change are replaced.
+//! New options go at the end of their block and new devices after the last block;
+//! every other byte of the file is left as it was.
+
+use crate::controls::{self, ActionmapsControlOption, ActionmapsDeviceOptions};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// Byte range of an option element, from `<` of its start tag to the end of its end tag
+struct OptionSpan {
+    name: String,
+    start: usize,
+    end: usize,
+}
+
+/// Byte ranges of an `<options>` block
+struct OptionsBlock {
+    device_type: String,
+    instance: String,
+    start: usize,
+    end: usize,
+    /// Start of `</options>`; None for a self-closing `<options/>`
+    close_start: Option<usize>,
+    options: Vec<OptionSpan>,
+}
+
+/// Where everything we may edit sits in the file
+struct Layout {
+    blocks: Vec<OptionsBlock>,
+    /// Where a block goes when the file has none yet: before the first element in
+    /// `<ActionProfiles>`, or before its end tag
+    first_block_at: Option<usize>,
+}
+
+fn element_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.name().as_ref()).into_owned()
+}
+
+fn device_key(e: &BytesStart) -> (String, String) {
+    let mut key = (String::new(), String::new());
+    for attr in e.attributes().flatten() {
+        let value = attr
+            .unescape_value()
+            .map(|v| v.into_owned())
+            .unwrap_or_else(|_| String::from_utf8_lossy(&attr.value).into_owned());
+        match attr.key.as_ref() {
+            b"type" => key.0 = value,
+            b"instance" => key.1 = value,
+            _ => {}
+        }
+    }
+    key
+}
+
+fn scan(xml: &str) -> Result<Layout, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut layout = Layout {
+        blocks: Vec::new(),
+        first_block_at: None,
+    };
+    let mut open: Vec<String> = Vec::new();
+    // The block being read and how many elements are open around it
+    let mut current: Option<(OptionsBlock, usize)> = None;
+
+    loop {
+        let start = reader.buffer_position() as usize;
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Failed to parse actionmaps.xml: {}", e))?;
+        let end = reader.buffer_position() as usize;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let name = element_name(e);
+                match &mut current {
+                    Some((block, depth)) => {
+                        if open.len() == *depth + 1 {
+                            block.options.push(OptionSpan {
+                                name: name.clone(),
+                                start,
+                                end,
+                            });
+                        }
+                    }
+                    None if name == "options" => {
+                        let (device_type, instance) = device_key(e);
+                        let block = OptionsBlock {
+                            device_type,
+                            instance,
+                            start,
+                            end,
+                            close_start: None,
+                            options: Vec::new(),
+                        };
+                        if is_empty {
+                            layout.blocks.push(block);
+                        } else {
+                            current = Some((block, open.len()));
+                        }
+                    }
+                    None => {
+                        if layout.first_block_at.is_none()
+                            && open.last().is_some_and(|parent| parent == "ActionProfiles")
+                        {
+                            layout.first_block_at = Some(start);
+                        }
+                    }
+                }
+                if !is_empty {
+                    open.push(name);
+                }
+            }
+            Event::End(_) => {
+                let name = open.pop().unwrap_or_default();
+                match current.take() {
+                    Some((mut block, depth)) if open.len() == depth => {
+                        block.close_start = Some(start);
+                        block.end = end;
+                        layout.blocks.push(block);
+                    }
+                    Some((mut block, depth)) => {
+                        if open.len() == depth + 1 {
+                            if let Some(option) = block.options.last_mut() {
+                                option.end = end;
+                            }
+                        }
+                        current = Some((block, depth));
+                    }
+                    None => {
+                        if name == "ActionProfiles" && layout.first_block_at.is_none() {
+                            layout.first_block_at = Some(start);
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(layout)
+}
+
+/// Indentation of the line `pos` is on, when only whitespace precedes it there
+fn indent_at(xml: &str, pos: usize) -> Option<&str> {
+    let before = &xml[..pos];
+    let from = before.rfind('\n').map_or(0, |i| i + 1);
+    before[from..]
+        .chars()
+        .all(char::is_whitespace)
+        .then(|| &before[from..])
+}
+
+/// Start of the line `pos` is on if the element there starts the line, otherwise `pos`
+fn line_start(xml: &str, pos: usize) -> usize {
+    indent_at(xml, pos).map_or(pos, |indent| pos - indent.len())
+}
+
+/// Where to insert whole lines after `pos`: past the line break if the rest of the line
+/// is blank, otherwise `pos` itself (and the insert starts with a line break)
+fn after_line(xml: &str, pos: usize) -> (usize, bool) {
+    let rest = &xml[pos..];
+    match rest.find('\n') {
+        Some(i) if rest[..i].trim().is_empty() => (pos + i + 1, false),
+        _ => (pos, true),
+    }
+}
+
+/// Equal settings, ignoring the order of attributes
+fn same_option(a: &ActionmapsControlOption, b: &ActionmapsControlOption) -> bool {
+    let sorted = |o: &ActionmapsControlOption| {
+        let mut attributes = o.attributes.clone();
+        attributes.sort();
+        attributes
+    };
+    sorted(a) == sorted(b)
+        && a.curve_points == b.curve_points
+        && a.extra_children == b.extra_children
+}
+
+/// Replace the element at `start..end` with `generated` (whole lines with their own
+/// indentation); when the element shares its line with others only the element goes
+fn replace_element(
+    xml: &str,
+    edits: &mut Vec<(usize, usize, String)>,
+    start: usize,
+    end: usize,
+    generated: &str,
+) {
+    let edit = match indent_at(xml, start) {
+        Some(indent) => (start - indent.len(), end, generated.trim_end().to_string()),
+        None => (start, end, generated.trim().to_string()),
+    };
+    edits.push(edit);
+}
+
+/// Merge `new_devices` into the options of `xml`, rewriting only what changes
+pub fn edit_options(xml: &str, new_devices: &[ActionmapsDeviceOptions]) -> Result<String, String> {
+    let layout = scan(xml)?;
+    let existing = controls::parse_actionmaps_options(xml)?;
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    let mut added_devices = String::new();
+
+    for new_device in new_devices {
+        let same_device = |device_type: &str, instance: &str| {
+            device_type == new_device.device_type && instance == new_device.instance
+        };
+        let block = layout
+            .blocks
+            .iter()
+            .find(|b| same_device(&b.device_type, &b.instance));
+        let parsed = existing
+            .iter()
+            .find(|d| same_device(&d.device_type, &d.instance));
+        let (block, parsed) = match (block, parsed) {
+            (Some(block), Some(parsed)) => (block, parsed),
+            _ => {
+                added_devices.push_str(&controls::generate_options_xml(new_device));
+                continue;
+            }
+        };
+
+        let Some(close_start) = block.close_start else {
+            if new_device.options.is_empty() {
+                continue;
+            }
+            // A self-closing <options/> gets its first options: write the whole block
+            let device = ActionmapsDeviceOptions {
+                options: new_device.options.clone(),
+                ..parsed.clone()
+            };
+            let generated = controls::generate_options_xml(&device);
+            replace_element(xml, &mut edits, block.start, block.end, &generated);
+            continue;
+        };
+
+        let option_indent = block
+            .options
+            .first()
+            .and_then(|o| indent_at(xml, o.start))
+            .unwrap_or("   ")
+            .to_string();
+        let mut appended = String::new();
+        for new_opt in &new_device.options {
+            let span = block.options.iter().find(|o| o.name == new_opt.name);
+            let current = parsed.options.iter().find(|o| o.name == new_opt.name);
+            match (span, current) {
+                (Some(span), Some(current)) => {
+                    let merged = controls::merge_option(current, new_opt);
+                    if same_option(&merged, current) {
+                        continue;
+                    }
+                    let indent = indent_at(xml, span.start).unwrap_or(&option_indent);
+                    let generated = controls::generate_option_xml(&merged, indent);
+                    replace_element(xml, &mut edits, span.start, span.end, &generated);
+                }
+                _ => appended.push_str(&controls::generate_option_xml(new_opt, &option_indent)),
+            }
+        }
+        if !appended.is_empty() {
+            let at = line_start(xml, close_start);
+            if indent_at(xml, close_start).is_none() {
+                appended.insert(0, '\n');
+            }
+            edits.push((at, at, appended));
+        }
+    }
+
+    if !added_devices.is_empty() {
+        let (at, newline_first) = match layout.blocks.iter().map(|b| b.end).max() {
+            Some(last_end) => after_line(xml, last_end),
+            None => {
+                let at = layout
+                    .first_block_at
+                    .ok_or_else(|| "Could not find ActionProfiles in actionmaps.xml".to_string())?;
+                (line_start(xml, at), false)
+            }
+        };
+        if newline_first {
+            added_devices.insert(0, '\n');
+        }
+        edits.push((at, at, added_devices));
+    }
+
+    // Apply back to front so earlier offsets stay valid
+    edits.sort_by_key(|(start, end, _)| (*start, *end));
+    let mut result = xml.to_string();
+    for (start, end, text) in edits.into_iter().rev() {
+        result.replace_range(start..end, &text);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACTIONMAPS: &str = r#"<ActionMaps version="1" optionsVersion="2" rebindVersion="2" profileName="default">
+ <ActionProfiles version="1" optionsVersion="2" rebindVersion="2" profileName="default">
+  <deviceoptions name="Stick">
+   <option input="x" deadzone="0.015"/>
+  </deviceoptions>
+  <options type="keyboard" instance="1" Product="Keyboard  {6F1D2B61-D5A0-11CF-BFC7-444553540000}"/>
+  <options type="joystick" instance="1" Product=" Stick    {0125044F-0000-0000-0000-504944564944}">
+   <flight_move_pitch invert='1'   deadzone="0.05" />
+   <flight_move_yaw exponent="1.5" invert="1">
+    <nonlinearity_curve>
+     <point in="0.5" out="0.25"/>
+    </nonlinearity_curve>
+   </flight_move_yaw>
+  </options>
+  <modifiers />
+  <actionmap name="spaceship_general">
+   <action name="v_eject">
+    <rebind input="js1_button1"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>
+"#;
+
+    fn option(name: &str, attributes: &[(&str, &str)]) -> ActionmapsControlOption {
+        ActionmapsControlOption {
+            name: name.to_string(),
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            curve_points: Vec::new(),
+            extra_children: Vec::new(),
+        }
+    }
+
+    fn device(
+        device_type: &str,
+        instance: &str,
+        options: Vec<ActionmapsControlOption>,
+    ) -> ActionmapsDeviceOptions {
+        ActionmapsDeviceOptions {
+            device_type: device_type.to_string(),
+            instance: instance.to_string(),
+            product: String::new(),
+            options,
+            extra_attributes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_only_changed_options_rewritten() {
+        // Settings the file already has: nothing changes, down to the quoting
+        let unchanged = vec![device(
+            "joystick",
+            "1",
+            vec![option(
+                "flight_move_pitch",
+                &[("deadzone", "0.05"), ("invert", "1")],
+            )],
+        )];
+        assert_eq!(edit_options(ACTIONMAPS, &unchanged).unwrap(), ACTIONMAPS);
+
+        // One changed option and one new one: only those lines differ
+        let changed = vec![device(
+            "joystick",
+            "1",
+            vec![
+                option("flight_move_pitch", &[("invert", "0")]),
+                option("flight_move_roll", &[("invert", "1")]),
+            ],
+        )];
+        let edited = edit_options(ACTIONMAPS, &changed).unwrap();
+        let expected = ACTIONMAPS
+            .replace(
+                "   <flight_move_pitch invert='1'   deadzone=\"0.05\" />\n",
+                "   <flight_move_pitch invert=\"0\"/>\n",
+            )
+            .replace(
+                "   </flight_move_yaw>\n",
+                "   </flight_move_yaw>\n   <flight_move_roll invert=\"1\"/>\n",
+            );
+        assert_eq!(edited, expected);
+
+        // A device with a self-closing block and a device the file doesn't have yet
+        let added = vec![
+            device(
+                "keyboard",
+                "1",
+                vec![option("fps_view_pitch", &[("invert", "1")])],
+            ),
+            device(
+                "gamepad",
+                "1",
+                vec![option("fps_view_yaw", &[("invert", "1")])],
+            ),
+        ];
+        let edited = edit_options(ACTIONMAPS, &added).unwrap();
+        assert!(edited.contains(
+            "  <options type=\"joystick\" instance=\"1\" Product=\" Stick    {0125044F-0000-0000-0000-504944564944}\">\n   <flight_move_pitch invert='1'   deadzone=\"0.05\" />\n"
+        ));
+        assert!(edited.contains(
+            "  </options>\n  <options type=\"gamepad\" instance=\"1\">\n   <fps_view_yaw invert=\"1\"/>\n  </options>\n  <modifiers />\n"
+        ));
+        let devices = controls::parse_actionmaps_options(&edited).unwrap();
+        assert_eq!(devices[0].options[0].name, "fps_view_pitch");
+        assert_eq!(devices.len(), 3);
+        assert!(edited.ends_with(&ACTIONMAPS[ACTIONMAPS.find("  <modifiers />").unwrap()..]));
+    }
+}
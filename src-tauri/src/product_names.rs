@@ -0,0 +1,232 @@
+//! Device product string normalization and matching
+//!
+//! The same stick shows up under slightly different names depending on who is asked:
+//! Star Citizen writes " VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}",
+//! DirectInput reports "VKB-Sim Gladiator NXT R " and HID splits it into a manufacturer
+//! ("VKB-Sim © Alex Oz 2021") and a product ("VKBsim Gladiator NXT R"). Everything that
+//! matches devices across those sources goes through here, so they all agree on what
+//! counts as the same device.
+
+/// Suffix of the DirectInput product GUID for HID devices
+const PIDVID_SUFFIX: &str = "504944564944";
+
+/// Vendor names that some sources put in front of the product name and others leave out.
+/// Longer forms come first so "vkb sim" is stripped whole rather than just "vkb".
+const VENDOR_PREFIXES: &[&str] = &[
+    "vkb sim",
+    "vkbsim",
+    "vkb",
+    "virpil controls",
+    "virpil",
+    "thrustmaster",
+    "logitech g",
+    "logitech",
+    "saitek",
+    "mad catz",
+    "winwing",
+    "ch products",
+    "microsoft",
+];
+
+/// Marks that some sources include in names and others don't
+const NOISE: &[&str] = &["©", "®", "™", "(r)", "(tm)"];
+
+/// DirectInput product GUID for a HID device: {PPPPVVVV-0000-0000-0000-504944564944}
+pub fn product_guid(vendor_id: u16, product_id: u16) -> String {
+    format!(
+        "{{{:04X}{:04X}-0000-0000-0000-{}}}",
+        product_id, vendor_id, PIDVID_SUFFIX
+    )
+}
+
+/// Build an SC Product string from a device name and our "vid:pid" uuid, e.g.
+/// " VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}"
+pub fn sc_product_string(name: &str, uuid: Option<&str>) -> String {
+    let ids = uuid.and_then(|uuid| {
+        let (vendor, product) = uuid.split_once(':')?;
+        Some((
+            u16::from_str_radix(vendor, 16).ok()?,
+            u16::from_str_radix(product, 16).ok()?,
+        ))
+    });
+    match ids {
+        Some((vendor_id, product_id)) => format!(
+            " {}    {}",
+            name.trim(),
+            product_guid(vendor_id, product_id)
+        ),
+        None => format!(" {}", name.trim()),
+    }
+}
+
+/// Extract "vid:pid" from an SC Product string like " VKB Gladiator    {0200231D-0000-0000-0000-504944564944}".
+/// SC packs the ids as {PPPPVVVV-...}, the reverse of our uuid format.
+pub fn uuid_from_product(product: &str) -> Option<String> {
+    let start = product.find('{')? + 1;
+    let guid = product[start..].split('}').next()?;
+    if !guid.to_uppercase().ends_with(PIDVID_SUFFIX) || guid.len() < 8 {
+        return None;
+    }
+    let ids = &guid[..8];
+    if !ids.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("{}:{}", &ids[4..8], &ids[0..4]).to_lowercase())
+}
+
+/// The name part of a product string, without any GUID suffix or surrounding whitespace
+pub fn display_name(product: &str) -> &str {
+    product.split('{').next().unwrap_or("").trim()
+}
+
+/// Canonical form of a device name: GUID suffix, trademark marks and punctuation dropped,
+/// lowercase, single spaces. "VKB-Sim Gladiator NXT R  {...}" -> "vkb sim gladiator nxt r"
+pub fn normalize(product: &str) -> String {
+    let mut name = display_name(product).to_lowercase();
+    for noise in NOISE {
+        name = name.replace(noise, " ");
+    }
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A normalized name without a leading vendor name
+fn without_vendor(normalized: &str) -> &str {
+    for vendor in VENDOR_PREFIXES {
+        if let Some(rest) = normalized.strip_prefix(vendor) {
+            if let Some(rest) = rest.strip_prefix(' ') {
+                return rest;
+            }
+        }
+    }
+    normalized
+}
+
+/// Drop a trailing "(...)" some APIs add, e.g. "VKB Gladiator NXT (Left)"
+fn without_annotation(name: &str) -> &str {
+    let trimmed = name.trim_end();
+    match trimmed.strip_suffix(')').and_then(|s| s.rfind('(')) {
+        Some(open) if open > 0 => &trimmed[..open],
+        _ => name,
+    }
+}
+
+/// Strict comparison: the same name once normalized, with or without the vendor in front
+pub fn same_name(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize(a), normalize(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    a == b || without_vendor(&a) == without_vendor(&b)
+}
+
+/// Loose comparison for names from different APIs: like `same_name`, but also ignoring
+/// trailing annotations, and accepting a name of two or more words whose words all appear
+/// in the other (e.g. a HID "manufacturer product" pair).
+pub fn names_match(a: &str, b: &str) -> bool {
+    if same_name(a, b) {
+        return true;
+    }
+
+    let a = normalize(without_annotation(display_name(a)));
+    let b = normalize(without_annotation(display_name(b)));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    if without_vendor(&a) == without_vendor(&b) {
+        return true;
+    }
+
+    let a_words: Vec<&str> = a.split(' ').collect();
+    let b_words: Vec<&str> = b.split(' ').collect();
+    let (shorter, longer) = if a_words.len() <= b_words.len() {
+        (a_words, b_words)
+    } else {
+        (b_words, a_words)
+    };
+    shorter.len() >= 2 && shorter.iter().all(|word| longer.contains(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_real_world_names() {
+        let samples = [
+            (
+                " VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}",
+                "vkb sim gladiator nxt r",
+            ),
+            ("VKB-Sim Gladiator NXT R ", "vkb sim gladiator nxt r"),
+            ("VKB-Sim © Alex Oz 2021", "vkb sim alex oz 2021"),
+            (
+                " Thrustmaster T.16000M    {B10A044F-0000-0000-0000-504944564944}",
+                "thrustmaster t 16000m",
+            ),
+            (
+                "Keyboard  {6F1D2B61-D5A0-11CF-BFC7-444553540000}",
+                "keyboard",
+            ),
+            ("LEFT VPC Stick MT-50CM2", "left vpc stick mt 50cm2"),
+            (
+                "Saitek Pro Flight X-56 Rhino Throttle",
+                "saitek pro flight x 56 rhino throttle",
+            ),
+        ];
+        for (raw, expected) in samples {
+            assert_eq!(normalize(raw), expected, "normalizing {:?}", raw);
+        }
+    }
+
+    #[test]
+    fn test_uuid_round_trip() {
+        let product = sc_product_string("VKB-Sim Gladiator NXT R ", Some("231d:0200"));
+        assert_eq!(
+            product,
+            " VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}"
+        );
+        assert_eq!(uuid_from_product(&product).as_deref(), Some("231d:0200"));
+        assert_eq!(
+            uuid_from_product("Keyboard  {6F1D2B61-D5A0-11CF-BFC7-444553540000}"),
+            None
+        );
+        assert_eq!(sc_product_string("Stick", None), " Stick");
+    }
+
+    #[test]
+    fn test_matching() {
+        // SC vs DirectInput
+        assert!(same_name(
+            " VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}",
+            "VKB-Sim Gladiator NXT R "
+        ));
+        // Vendor prefix present in one source only
+        assert!(same_name("Thrustmaster T.16000M", "T.16000M"));
+        assert!(same_name(
+            "VKBsim Gladiator NXT R",
+            "VKB-Sim Gladiator NXT R"
+        ));
+        // HID manufacturer + product against the DirectInput name
+        assert!(names_match(
+            "VKB-Sim Gladiator NXT R",
+            "VKB-Sim © Alex Oz 2021 VKBsim Gladiator NXT R"
+        ));
+        // OS annotation
+        assert!(names_match("VKB Gladiator NXT (Left)", "VKB Gladiator NXT"));
+
+        // Left and right hand versions are different devices
+        assert!(!names_match(
+            "VKB-Sim Gladiator NXT L",
+            "VKB-Sim Gladiator NXT R"
+        ));
+        assert!(!names_match(
+            "LEFT VPC Stick MT-50CM2",
+            "RIGHT VPC Stick MT-50CM2"
+        ));
+        assert!(!same_name("", ""));
+    }
+}
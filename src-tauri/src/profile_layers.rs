@@ -0,0 +1,270 @@
+//! Layered profiles: overlays stacked on top of the applied profile
+//!
+//! Some settings only suit part of the time, like a mining overlay with a softer throttle
+//! or a landing overlay with bigger deadzones. Instead of a full copy of the profile for
+//! each, an overlay is a small .sccontrols file holding just what it changes, and the
+//! enabled overlays are stacked on whatever profile is applied. The stack is resolved at
+//! apply time in a fixed order: squadron baseline, the profile, then each enabled overlay
+//! in list order. A later layer wins field by field (see `baseline::compose`).
+
+use crate::baseline;
+use crate::controls::{ControlsFile, DeviceInstanceSettings};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const LAYERS_FILE_NAME: &str = "profile_layers.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct OverlayEntry {
+    /// Path of the overlay's .sccontrols file
+    pub path: String,
+    pub enabled: bool,
+}
+
+/// The overlay stack, bottom first
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct LayerState {
+    overlays: Vec<OverlayEntry>,
+}
+
+/// What the frontend shows about an overlay
+#[derive(Debug, Serialize, Clone)]
+pub struct OverlayStatus {
+    pub path: String,
+    pub enabled: bool,
+    pub profile_name: Option<String>,
+    /// Why the overlay can't be read right now
+    pub error: Option<String>,
+}
+
+/// Layers that set an option in the effective profile
+#[derive(Debug, Serialize, Clone)]
+pub struct OptionSource {
+    /// e.g. "joystick 1"
+    pub device: String,
+    pub option: String,
+    /// Names of the layers setting it, bottom first; the last one wins where they overlap
+    pub layers: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EffectiveProfile {
+    /// What an apply would write
+    pub profile: ControlsFile,
+    /// Names of all layers, bottom first
+    pub layers: Vec<String>,
+    pub sources: Vec<OptionSource>,
+}
+
+fn load_state(data_dir: &Path) -> Result<LayerState, String> {
+    let path = data_dir.join(LAYERS_FILE_NAME);
+    if !path.exists() {
+        return Ok(LayerState::default());
+    }
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read profile layers: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse profile layers: {}", e))
+}
+
+fn load_overlay(path: &str) -> Result<ControlsFile, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read overlay {}: {}", path, e))?;
+    ControlsFile::from_json(&json).map_err(|e| format!("Overlay {}: {}", path, e))
+}
+
+pub fn status(data_dir: &Path) -> Result<Vec<OverlayStatus>, String> {
+    Ok(load_state(data_dir)?
+        .overlays
+        .into_iter()
+        .map(|entry| {
+            let overlay = load_overlay(&entry.path);
+            OverlayStatus {
+                profile_name: overlay.as_ref().ok().map(|o| o.profile_name.clone()),
+                error: overlay.err(),
+                path: entry.path,
+                enabled: entry.enabled,
+            }
+        })
+        .collect())
+}
+
+/// Replace the overlay stack. Enabled overlays must be readable profiles.
+pub fn set_overlays(data_dir: &Path, overlays: Vec<OverlayEntry>) -> Result<(), String> {
+    for entry in overlays.iter().filter(|e| e.enabled) {
+        load_overlay(&entry.path)?;
+    }
+    std::fs::create_dir_all(data_dir)
+        .map_err(|e| format!("Failed to create data directory: {}", e))?;
+    let json = serde_json::to_string_pretty(&LayerState { overlays })
+        .map_err(|e| format!("Failed to serialize profile layers: {}", e))?;
+    std::fs::write(data_dir.join(LAYERS_FILE_NAME), json)
+        .map_err(|e| format!("Failed to write profile layers: {}", e))
+}
+
+/// The enabled overlays, bottom first. An overlay that can't be read fails the whole
+/// stack rather than silently applying something else.
+fn enabled_overlays(data_dir: &Path) -> Result<Vec<ControlsFile>, String> {
+    load_state(data_dir)?
+        .overlays
+        .iter()
+        .filter(|entry| entry.enabled)
+        .map(|entry| load_overlay(&entry.path))
+        .collect()
+}
+
+/// `profile` with `overlays` stacked on it in order. The result keeps the profile's name
+/// and metadata.
+pub fn stack(profile: &ControlsFile, overlays: &[ControlsFile]) -> ControlsFile {
+    let mut layered = profile.clone();
+    for overlay in overlays {
+        layered.devices = baseline::compose(&layered, overlay).devices;
+    }
+    layered
+}
+
+/// What an apply of `profile` writes: baseline, profile, then the enabled overlays
+pub fn compose_for_apply(data_dir: &Path, profile: &ControlsFile) -> Result<ControlsFile, String> {
+    let composed = baseline::compose_for_apply(data_dir, profile)?;
+    let overlays = enabled_overlays(data_dir)?;
+    if overlays.is_empty() {
+        return Ok(composed);
+    }
+    info!(
+        "Stacking {} on {}",
+        overlays
+            .iter()
+            .map(|o| o.profile_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        profile.profile_name
+    );
+    Ok(stack(&composed, &overlays))
+}
+
+fn device_entries(file: &ControlsFile) -> Vec<(String, &DeviceInstanceSettings)> {
+    let devices = &file.devices;
+    let mut entries: Vec<(String, &DeviceInstanceSettings)> = [
+        ("keyboard", devices.keyboard.as_ref()),
+        ("mouse", devices.mouse.as_ref()),
+        ("gamepad", devices.gamepad.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(device_type, device)| Some((format!("{} 1", device_type), device?)))
+    .collect();
+    entries.extend(
+        devices
+            .joystick
+            .iter()
+            .flatten()
+            .map(|(instance, device)| (format!("joystick {}", instance), device)),
+    );
+    entries
+}
+
+/// Which layers set each option of a stack, bottom first
+fn option_sources(layers: &[&ControlsFile]) -> Vec<OptionSource> {
+    let mut sources: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+    for layer in layers {
+        for (device, settings) in device_entries(layer) {
+            for option in settings.options.keys() {
+                sources
+                    .entry((device.clone(), option.clone()))
+                    .or_default()
+                    .push(layer.profile_name.clone());
+            }
+        }
+    }
+    sources
+        .into_iter()
+        .map(|((device, option), layers)| OptionSource {
+            device,
+            option,
+            layers,
+        })
+        .collect()
+}
+
+/// The profile an apply of `profile` would write, with where each option comes from
+pub fn effective(data_dir: &Path, profile: &ControlsFile) -> Result<EffectiveProfile, String> {
+    let base = baseline::active(data_dir)?;
+    let overlays = enabled_overlays(data_dir)?;
+
+    let composed = match &base {
+        Some(base) => baseline::compose(base, profile),
+        None => profile.clone(),
+    };
+    let layers: Vec<&ControlsFile> = base
+        .iter()
+        .chain(std::iter::once(profile))
+        .chain(overlays.iter())
+        .collect();
+
+    Ok(EffectiveProfile {
+        layers: layers.iter().map(|l| l.profile_name.clone()).collect(),
+        sources: option_sources(&layers),
+        profile: stack(&composed, &overlays),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::ControlOptionSettings;
+
+    fn with_option(name: &str, option: &str, settings: ControlOptionSettings) -> ControlsFile {
+        let mut file = ControlsFile::new(name.to_string());
+        file.device_mut("joystick", "1")
+            .unwrap()
+            .options
+            .insert(option.to_string(), settings);
+        file
+    }
+
+    #[test]
+    fn test_later_overlays_win() {
+        let profile = with_option(
+            "Global",
+            "flight_move_pitch",
+            ControlOptionSettings {
+                invert: Some(true),
+                deadzone: Some(0.02),
+                ..Default::default()
+            },
+        );
+        let mining = with_option(
+            "Mining",
+            "flight_move_pitch",
+            ControlOptionSettings {
+                deadzone: Some(0.1),
+                ..Default::default()
+            },
+        );
+        let landing = with_option(
+            "Landing",
+            "flight_move_pitch",
+            ControlOptionSettings {
+                deadzone: Some(0.2),
+                ..Default::default()
+            },
+        );
+
+        let layered = stack(&profile, &[mining.clone(), landing.clone()]);
+        assert_eq!(layered.profile_name, "Global");
+        let pitch = &layered.device("joystick", "1").unwrap().options["flight_move_pitch"];
+        assert_eq!(pitch.invert, Some(true));
+        assert_eq!(pitch.deadzone, Some(0.2));
+
+        let reversed = stack(&profile, &[landing.clone(), mining.clone()]);
+        let pitch = &reversed.device("joystick", "1").unwrap().options["flight_move_pitch"];
+        assert_eq!(pitch.deadzone, Some(0.1));
+
+        let sources = option_sources(&[&profile, &mining, &landing]);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].device, "joystick 1");
+        assert_eq!(sources[0].layers, vec!["Global", "Mining", "Landing"]);
+    }
+}
@@ -0,0 +1,492 @@
+//! Managed library of .sccontrols profiles
+//!
+//! Profiles live as individual .sccontrols files in one directory. Listing migrates each
+//! file like a full load, then only reads its header (name, timestamps, which devices it
+//! covers), skipping the option details, so the library view stays quick with many large
+//! profiles.
+
+use crate::controls::{self, ControlsFile};
+use crate::resolutions;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const PROFILE_EXTENSION: &str = "sccontrols";
+
+/// Just enough of a device entry to summarize it
+#[derive(Deserialize)]
+struct DeviceHeader {
+    #[serde(default)]
+    product: Option<String>,
+    #[serde(default)]
+    options: HashMap<String, IgnoredAny>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DevicesHeader {
+    keyboard: Option<DeviceHeader>,
+    mouse: Option<DeviceHeader>,
+    gamepad: Option<DeviceHeader>,
+    joystick: Option<HashMap<String, DeviceHeader>>,
+}
+
+/// The parts of a controls file shown in the library, parsed without the option contents
+#[derive(Deserialize)]
+struct ProfileHeader {
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    profile_name: String,
+    #[serde(default)]
+    last_modified: Option<String>,
+    #[serde(default)]
+    devices: DevicesHeader,
+}
+
+/// One device covered by a profile
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceSummary {
+    pub device_type: String,
+    pub instance: String,
+    pub product: Option<String>,
+    pub option_count: usize,
+}
+
+/// A profile as shown in the library view
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileSummary {
+    pub file_name: String,
+    pub path: String,
+    pub profile_name: String,
+    pub version: String,
+    pub last_modified: Option<String>,
+    pub devices: Vec<DeviceSummary>,
+}
+
+/// Characters Windows doesn't allow in file names
+const INVALID_FILE_NAME_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names Windows reserves for devices, with or without an extension ("con.sccontrols")
+const RESERVED_FILE_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest file name stem we generate, well inside Windows' path limits
+const MAX_STEM_CHARS: usize = 100;
+
+fn is_reserved_file_name(stem: &str) -> bool {
+    let base = stem.split('.').next().unwrap_or(stem).trim_end();
+    RESERVED_FILE_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(base))
+}
+
+/// The file name stem before reserved names are dealt with; empty if nothing usable is left
+fn base_stem(profile_name: &str) -> String {
+    let stem: String = profile_name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            c if INVALID_FILE_NAME_CHARS.contains(&c) => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_STEM_CHARS)
+        .collect();
+    stem.trim_end_matches(['.', ' ']).to_string()
+}
+
+/// Turn a profile name into a safe file name stem
+pub fn file_stem_for(profile_name: &str) -> String {
+    let stem = base_stem(profile_name);
+    if stem.is_empty() {
+        "profile".to_string()
+    } else if is_reserved_file_name(&stem) {
+        format!("{}_", stem)
+    } else {
+        stem
+    }
+}
+
+/// A profile name as it will be stored, for the frontend to confirm before saving
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileNameCheck {
+    /// The name as typed
+    pub original: String,
+    /// The name that will be saved
+    pub name: String,
+    /// The library file name it maps to, before any " (2)" suffix for a taken name
+    pub file_name: String,
+    /// Whether the name or file name differ from what was typed
+    pub changed: bool,
+    /// What was changed and why
+    pub notes: Vec<String>,
+}
+
+/// Clean a profile name for storage. Profile names end up in XML attributes (profileName),
+/// which can't hold control characters, so those become spaces; surrounding whitespace is
+/// trimmed. Fails when nothing is left.
+pub fn sanitize_profile_name(profile_name: &str) -> Result<String, String> {
+    let name: String = profile_name
+        .chars()
+        .map(|c| {
+            if c.is_control() || c == '\u{FFFE}' || c == '\u{FFFF}' {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect();
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Profile name can't be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// Check a profile name before saving: the name that will be stored, its file name and
+/// what had to change
+pub fn check_profile_name(profile_name: &str) -> Result<ProfileNameCheck, String> {
+    let name = sanitize_profile_name(profile_name)?;
+    let stem = file_stem_for(&name);
+    let mut notes = Vec::new();
+
+    if name != profile_name.trim() {
+        notes
+            .push("Line breaks and other control characters were replaced with spaces".to_string());
+    }
+    if name.contains(INVALID_FILE_NAME_CHARS) {
+        notes.push(format!(
+            "{} can't be used in file names and become _ in the file name",
+            INVALID_FILE_NAME_CHARS
+                .iter()
+                .filter(|c| name.contains(**c))
+                .map(char::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        ));
+    }
+    if name.chars().count() > MAX_STEM_CHARS {
+        notes.push(format!(
+            "The file name is shortened to {} characters",
+            MAX_STEM_CHARS
+        ));
+    }
+    let base = base_stem(&name);
+    if !base.is_empty() && is_reserved_file_name(&base) {
+        notes.push(format!(
+            "\"{}\" is reserved by Windows, so the file name gets a _ added",
+            base
+        ));
+    } else if stem != name && notes.is_empty() {
+        notes.push("Trailing dots are dropped from the file name".to_string());
+    }
+
+    Ok(ProfileNameCheck {
+        changed: name != profile_name || stem != name,
+        original: profile_name.to_string(),
+        file_name: format!("{}.{}", stem, PROFILE_EXTENSION),
+        name,
+        notes,
+    })
+}
+
+/// Resolve a library file name, refusing anything that would escape the library directory
+fn library_file(dir: &Path, file_name: &str) -> Result<PathBuf, String> {
+    let path = Path::new(file_name);
+    if path.components().count() != 1
+        || path.extension().and_then(|e| e.to_str()) != Some(PROFILE_EXTENSION)
+    {
+        return Err(format!("Invalid profile file name: {}", file_name));
+    }
+    Ok(dir.join(path))
+}
+
+/// Pick a file name for `profile_name` that isn't taken yet ("name.sccontrols", "name (2).sccontrols", ...)
+fn unused_file_name(dir: &Path, profile_name: &str) -> String {
+    let stem = file_stem_for(profile_name);
+    let mut candidate = format!("{}.{}", stem, PROFILE_EXTENSION);
+    let mut n = 2;
+    while dir.join(&candidate).exists() {
+        candidate = format!("{} ({}).{}", stem, n, PROFILE_EXTENSION);
+        n += 1;
+    }
+    candidate
+}
+
+fn summarize(path: &Path) -> Result<ProfileSummary, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut value: serde_json::Value = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    // Same migration as a full load, so old files show their names and files this
+    // version can't open aren't listed
+    controls::migrate_to_current(&mut value).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let header: ProfileHeader = serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    let mut devices = Vec::new();
+    let mut push = |device_type: &str, instance: String, device: DeviceHeader| {
+        devices.push(DeviceSummary {
+            device_type: device_type.to_string(),
+            instance,
+            product: device.product,
+            option_count: device.options.len(),
+        });
+    };
+    if let Some(keyboard) = header.devices.keyboard {
+        push("keyboard", "1".to_string(), keyboard);
+    }
+    if let Some(mouse) = header.devices.mouse {
+        push("mouse", "1".to_string(), mouse);
+    }
+    if let Some(gamepad) = header.devices.gamepad {
+        push("gamepad", "1".to_string(), gamepad);
+    }
+    let mut joysticks: Vec<_> = header
+        .devices
+        .joystick
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    joysticks.sort_by(|a, b| a.0.cmp(&b.0));
+    for (instance, joystick) in joysticks {
+        push("joystick", instance, joystick);
+    }
+
+    Ok(ProfileSummary {
+        file_name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        profile_name: header.profile_name,
+        version: header.version,
+        last_modified: header.last_modified,
+        devices,
+    })
+}
+
+/// List the profiles in the library, most recently modified first. Files that fail to
+/// parse are skipped (and logged) rather than hiding the rest of the library.
+pub fn list_profiles(dir: &Path) -> Result<Vec<ProfileSummary>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read profile library: {}", e))?;
+
+    let mut profiles: Vec<ProfileSummary> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == PROFILE_EXTENSION))
+        .filter_map(|path| match summarize(&path) {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                log::warn!("Skipping profile: {}", e);
+                None
+            }
+        })
+        .collect();
+
+    profiles.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
+    Ok(profiles)
+}
+
+fn write_profile(path: &Path, controls_file: &ControlsFile) -> Result<(), String> {
+    std::fs::write(path, controls_file.to_json()?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn read_profile(path: &Path) -> Result<ControlsFile, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    ControlsFile::from_json(&json)
+}
+
+/// Create a new, empty profile
+pub fn create_profile(dir: &Path, profile_name: &str) -> Result<ProfileSummary, String> {
+    let profile_name = &sanitize_profile_name(profile_name)?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create profile library: {}", e))?;
+
+    let path = dir.join(unused_file_name(dir, profile_name));
+    write_profile(&path, &ControlsFile::new(profile_name.to_string()))?;
+    summarize(&path)
+}
+
+/// Add a profile built elsewhere (e.g. from a preset) to the library under its own name
+pub fn add_profile(dir: &Path, mut controls_file: ControlsFile) -> Result<ProfileSummary, String> {
+    controls_file.profile_name = sanitize_profile_name(&controls_file.profile_name)?;
+    controls_file.touch();
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create profile library: {}", e))?;
+
+    let path = dir.join(unused_file_name(dir, &controls_file.profile_name));
+    write_profile(&path, &controls_file)?;
+    summarize(&path)
+}
+
+/// Copy a profile under a new name
+pub fn duplicate_profile(
+    dir: &Path,
+    file_name: &str,
+    new_name: &str,
+) -> Result<ProfileSummary, String> {
+    let new_name = &sanitize_profile_name(new_name)?;
+    let mut controls_file = read_profile(&library_file(dir, file_name)?)?;
+    controls_file.profile_name = new_name.to_string();
+    controls_file.touch();
+
+    let path = dir.join(unused_file_name(dir, new_name));
+    write_profile(&path, &controls_file)?;
+    summarize(&path)
+}
+
+/// The profile's conflict resolution transcript, kept next to it
+fn transcript_of(path: &Path) -> PathBuf {
+    resolutions::transcript_path(&path.to_string_lossy())
+}
+
+/// Rename a profile, updating both its display name and its file name
+pub fn rename_profile(
+    dir: &Path,
+    file_name: &str,
+    new_name: &str,
+) -> Result<ProfileSummary, String> {
+    let new_name = &sanitize_profile_name(new_name)?;
+    let old_path = library_file(dir, file_name)?;
+    let mut controls_file = read_profile(&old_path)?;
+    controls_file.profile_name = new_name.to_string();
+    controls_file.touch();
+
+    // Keep the file name if it already matches, otherwise move to a free one
+    let wanted = format!("{}.{}", file_stem_for(new_name), PROFILE_EXTENSION);
+    let new_path = if wanted == file_name {
+        old_path.clone()
+    } else {
+        dir.join(unused_file_name(dir, new_name))
+    };
+
+    write_profile(&new_path, &controls_file)?;
+    if new_path != old_path {
+        std::fs::remove_file(&old_path)
+            .map_err(|e| format!("Failed to remove {}: {}", old_path.display(), e))?;
+
+        // The recorded resolutions move with the profile
+        let old_transcript = transcript_of(&old_path);
+        if old_transcript.exists() {
+            let new_transcript = transcript_of(&new_path);
+            std::fs::rename(&old_transcript, &new_transcript)
+                .map_err(|e| format!("Failed to move {}: {}", old_transcript.display(), e))?;
+        }
+    }
+    summarize(&new_path)
+}
+
+/// Delete a profile along with its conflict resolution transcript
+pub fn delete_profile(dir: &Path, file_name: &str) -> Result<(), String> {
+    let path = library_file(dir, file_name)?;
+    std::fs::remove_file(&path)
+        .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+
+    let transcript = transcript_of(&path);
+    if transcript.exists() {
+        std::fs::remove_file(&transcript)
+            .map_err(|e| format!("Failed to delete {}: {}", transcript.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::TempDir;
+
+    #[test]
+    fn test_check_profile_name() {
+        let check = check_profile_name(" Gladiator L/R: \"dual\"\n").unwrap();
+        assert_eq!(check.name, "Gladiator L/R: \"dual\"");
+        assert_eq!(check.file_name, "Gladiator L_R_ _dual_.sccontrols");
+        assert!(check.changed);
+        assert_eq!(check.notes.len(), 1);
+
+        let check = check_profile_name("con").unwrap();
+        assert_eq!(check.file_name, "con_.sccontrols");
+        assert_eq!(check.notes.len(), 1);
+
+        let check = check_profile_name("Tab\tSeparated").unwrap();
+        assert_eq!(check.name, "Tab Separated");
+        assert_eq!(check.notes.len(), 1);
+
+        let check = check_profile_name("PTU daily").unwrap();
+        assert!(!check.changed);
+        assert!(check.notes.is_empty());
+
+        assert!(check_profile_name(" \r\n ").is_err());
+    }
+
+    #[test]
+    fn test_transcript_follows_rename_and_delete() {
+        let temp = TempDir::new("profile-library");
+        let dir = temp.path();
+        let profile = create_profile(dir, "Dogfight").unwrap();
+        let transcript = transcript_of(Path::new(&profile.path));
+        std::fs::write(&transcript, "{}").unwrap();
+
+        let renamed = rename_profile(dir, &profile.file_name, "Racing").unwrap();
+        assert_eq!(renamed.file_name, "Racing.sccontrols");
+        assert!(!transcript.exists());
+        let moved = transcript_of(Path::new(&renamed.path));
+        assert_eq!(std::fs::read_to_string(&moved).unwrap(), "{}");
+
+        // Renaming to the same file name leaves it in place
+        rename_profile(dir, &renamed.file_name, "Racing").unwrap();
+        assert!(moved.exists());
+
+        delete_profile(dir, &renamed.file_name).unwrap();
+        assert!(!moved.exists());
+        assert!(list_profiles(dir).unwrap().is_empty());
+
+        // Profiles without a transcript are deleted all the same
+        let plain = create_profile(dir, "Plain").unwrap();
+        delete_profile(dir, &plain.file_name).unwrap();
+    }
+
+    #[test]
+    fn test_list_migrates_old_files_and_skips_newer_majors() {
+        let temp = TempDir::new("profile-library");
+        let dir = temp.path();
+        std::fs::write(
+            dir.join("old.sccontrols"),
+            r#"{
+                "profileName": "Old Profile",
+                "lastModified": "2024-12-04T12:00:00Z",
+                "devices": {
+                    "joystick": {
+                        "1": { "options": { "flight_move_pitch": { "invert": true } } }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("future.sccontrols"),
+            r#"{ "version": "99.0", "profile_name": "From the future", "devices": {} }"#,
+        )
+        .unwrap();
+
+        let profiles = list_profiles(dir).unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].profile_name, "Old Profile");
+        assert_eq!(profiles[0].version, controls::CONTROLS_FILE_VERSION);
+        assert_eq!(
+            profiles[0].last_modified.as_deref(),
+            Some("2024-12-04T12:00:00Z")
+        );
+        assert_eq!(profiles[0].devices.len(), 1);
+        assert_eq!(profiles[0].devices[0].option_count, 1);
+    }
+}
@@ -0,0 +1,497 @@
+//! Reference card images per device
+//!
+//! The joystick viewer can export what's on screen as a PNG, one page at a time and only
+//! after arranging it by hand. Here a card is made for every page of a joystick template
+//! in one go: the template's diagram with each control's bound actions written into a box
+//! at its label position, as laid out in the template editor. Labels come from
+//! AllBinds.xml, resolved through global.ini when given, like the cheat sheet. Cards are
+//! SVG; PNG is rendered from the SVG by a headless Edge/Chrome, as for PDF cheat sheets.
+
+use crate::cheat_sheet;
+use crate::keybindings::{ActionMaps, AllBinds};
+use base64::Engine;
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+const BOX_WIDTH: f64 = 220.0;
+const LINE_HEIGHT: f64 = 16.0;
+const PADDING: f64 = 20.0;
+/// Longest action label written on a card, in characters
+const MAX_LABEL_CHARS: usize = 34;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CardFormat {
+    Svg,
+    Png,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+struct CardButton {
+    name: String,
+    #[serde(rename = "buttonPos")]
+    button_pos: Point,
+    #[serde(rename = "labelPos")]
+    label_pos: Option<Point>,
+    /// "main" for simple buttons, "up"/"down"/... for hats, each an SC input
+    inputs: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+struct TemplatePage {
+    id: String,
+    name: String,
+    #[serde(rename = "joystickNumber")]
+    joystick_number: Option<u32>,
+    device_prefix: Option<String>,
+    image_path: Option<String>,
+    image_data_url: Option<String>,
+    mirror_from_page_id: Option<String>,
+    buttons: Vec<CardButton>,
+}
+
+/// Template files from before pages, with a fixed left and right stick
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+struct LegacySide {
+    #[serde(rename = "joystickNumber")]
+    joystick_number: Option<u32>,
+    buttons: Vec<CardButton>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct TemplateFile {
+    name: String,
+    image_width: f64,
+    image_height: f64,
+    pages: Vec<TemplatePage>,
+    left_stick: Option<LegacySide>,
+    right_stick: Option<LegacySide>,
+    image_data_url: Option<String>,
+    /// Index of the legacy side whose image is mirrored
+    image_flipped: Option<usize>,
+}
+
+/// One card written to disk
+#[derive(Debug, Serialize, Clone)]
+pub struct ReferenceCard {
+    pub device: String,
+    /// SC joystick instance the card shows
+    pub instance: u32,
+    pub path: String,
+    pub bound_controls: usize,
+}
+
+/// A page ready to draw
+struct Card<'a> {
+    name: &'a str,
+    instance: u32,
+    buttons: &'a [CardButton],
+    image: Option<String>,
+    flipped: bool,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|v| !v.is_empty())
+}
+
+/// A page's diagram as a data URL, from the template or the image file next to it
+fn page_image(page: &TemplatePage, template_dir: &Path) -> Option<String> {
+    if let Some(url) = non_empty(&page.image_data_url) {
+        return Some(url.to_string());
+    }
+    let file = template_dir.join(non_empty(&page.image_path)?);
+    let mime = match file.extension()?.to_str()?.to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    };
+    let bytes = std::fs::read(&file).ok()?;
+    Some(format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+fn page_instance(page: &TemplatePage) -> u32 {
+    page.device_prefix
+        .as_deref()
+        .and_then(|prefix| prefix.trim().strip_prefix("js")?.parse().ok())
+        .or(page.joystick_number)
+        .unwrap_or(1)
+}
+
+fn cards<'a>(template: &'a TemplateFile, template_dir: &Path) -> Vec<Card<'a>> {
+    if template.pages.is_empty() {
+        let sides = [
+            ("Left stick", &template.left_stick),
+            ("Right stick", &template.right_stick),
+        ];
+        return sides
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, (name, side))| {
+                let side = side.as_ref()?;
+                Some(Card {
+                    name,
+                    instance: side.joystick_number.unwrap_or(index as u32 + 1),
+                    buttons: &side.buttons,
+                    image: non_empty(&template.image_data_url).map(str::to_string),
+                    flipped: template.image_flipped == Some(index),
+                })
+            })
+            .collect();
+    }
+
+    template
+        .pages
+        .iter()
+        .map(|page| {
+            // A mirrored page shows another page's diagram flipped
+            let source = non_empty(&page.mirror_from_page_id)
+                .and_then(|id| template.pages.iter().find(|p| p.id == id));
+            Card {
+                name: &page.name,
+                instance: page_instance(page),
+                buttons: &page.buttons,
+                image: page_image(source.unwrap_or(page), template_dir),
+                flipped: source.is_some(),
+            }
+        })
+        .collect()
+}
+
+/// Action labels by SC input ("js1_button3"), from the loaded bindings
+pub fn binding_labels(
+    bindings: &ActionMaps,
+    all_binds: Option<&AllBinds>,
+    localization: &HashMap<String, String>,
+) -> HashMap<String, Vec<String>> {
+    let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+    for action_map in &bindings.action_maps {
+        let all_binds_map = all_binds.and_then(|ab| {
+            ab.action_maps
+                .iter()
+                .find(|map| map.name == action_map.name)
+        });
+        for action in &action_map.actions {
+            let label = cheat_sheet::resolve_label(
+                all_binds_map
+                    .and_then(|map| map.actions.iter().find(|a| a.name == action.name))
+                    .map(|a| a.ui_label.as_str()),
+                &action.name,
+                localization,
+            );
+            for rebind in &action.rebinds {
+                let input = rebind.input.trim().to_lowercase();
+                if !input.starts_with("js") || input.ends_with('_') {
+                    continue;
+                }
+                let label = match rebind.multi_tap.filter(|&taps| taps > 1) {
+                    Some(taps) => format!("{} (x{})", label, taps),
+                    None => label.clone(),
+                };
+                labels.entry(input).or_default().push(label);
+            }
+        }
+    }
+    labels
+}
+
+/// Full SC input for a template input on joystick `instance`; templates store either
+/// "button3" or "js1_button3"
+fn full_input(input: &str, instance: u32) -> String {
+    let input = input.trim().to_lowercase();
+    if input.starts_with("js") {
+        input
+    } else {
+        format!("js{}_{}", instance, input)
+    }
+}
+
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= MAX_LABEL_CHARS {
+        return text.to_string();
+    }
+    let short: String = text.chars().take(MAX_LABEL_CHARS - 1).collect();
+    format!("{}…", short.trim_end())
+}
+
+/// Lines of a control's box: what each of its inputs does
+fn box_lines(
+    button: &CardButton,
+    instance: u32,
+    labels: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    const ORDER: [&str; 6] = ["main", "up", "right", "down", "left", "push"];
+    let mut slots: Vec<(&String, &String)> = button.inputs.iter().collect();
+    slots.sort_by_key(|(slot, _)| ORDER.iter().position(|o| o == slot).unwrap_or(ORDER.len()));
+
+    let mut lines = Vec::new();
+    for (slot, input) in slots {
+        if input.trim().is_empty() {
+            continue;
+        }
+        let bound = labels.get(&full_input(input, instance));
+        for label in bound.into_iter().flatten() {
+            lines.push(match slot.as_str() {
+                "main" => shorten(label),
+                slot => shorten(&format!(
+                    "{}{}: {}",
+                    slot[..1].to_uppercase(),
+                    &slot[1..],
+                    label
+                )),
+            });
+        }
+    }
+    lines
+}
+
+struct RenderedCard {
+    svg: String,
+    width: f64,
+    height: f64,
+    /// Controls with at least one action on them
+    bound: usize,
+}
+
+/// Draw one card as SVG
+fn render_card(
+    card: &Card,
+    image_width: f64,
+    image_height: f64,
+    labels: &HashMap<String, Vec<String>>,
+) -> RenderedCard {
+    let boxes: Vec<(&CardButton, Point, Vec<String>)> = card
+        .buttons
+        .iter()
+        .filter_map(|button| {
+            Some((
+                button,
+                button.label_pos?,
+                box_lines(button, card.instance, labels),
+            ))
+        })
+        .collect();
+    let bound = boxes
+        .iter()
+        .filter(|(_, _, lines)| !lines.is_empty())
+        .count();
+    let box_height = |lines: &[String]| (lines.len().max(1) as f64 + 1.0) * LINE_HEIGHT + 8.0;
+
+    // Labels often sit outside the diagram; fit everything
+    let (mut min_x, mut min_y) = (0.0f64, -2.0 * LINE_HEIGHT);
+    let (mut max_x, mut max_y) = (image_width, image_height);
+    for (_, at, lines) in &boxes {
+        let half_height = box_height(lines) / 2.0;
+        min_x = min_x.min(at.x - BOX_WIDTH / 2.0);
+        max_x = max_x.max(at.x + BOX_WIDTH / 2.0);
+        min_y = min_y.min(at.y - half_height);
+        max_y = max_y.max(at.y + half_height);
+    }
+    let (x0, y0) = (min_x - PADDING, min_y - PADDING);
+    let (width, height) = (max_x - min_x + 2.0 * PADDING, max_y - min_y + 2.0 * PADDING);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{x0} {y0} {width} {height}\" width=\"{width}\" height=\"{height}\" font-family=\"Segoe UI, sans-serif\" font-size=\"12\">\n<rect x=\"{x0}\" y=\"{y0}\" width=\"{width}\" height=\"{height}\" fill=\"#09090b\"/>\n"
+    );
+    svg.push_str(&format!(
+        "<text x=\"0\" y=\"{}\" fill=\"#fafafa\" font-size=\"18\" font-weight=\"bold\">{} (js{})</text>\n",
+        -LINE_HEIGHT,
+        escape(card.name),
+        card.instance
+    ));
+    if let Some(image) = &card.image {
+        let transform = if card.flipped {
+            format!(" transform=\"translate({}, 0) scale(-1, 1)\"", image_width)
+        } else {
+            String::new()
+        };
+        svg.push_str(&format!(
+            "<image href=\"{}\" x=\"0\" y=\"0\" width=\"{}\" height=\"{}\"{}/>\n",
+            escape(image),
+            image_width,
+            image_height,
+            transform
+        ));
+    }
+
+    for (button, at, lines) in &boxes {
+        let height = box_height(lines);
+        let (left, top) = (at.x - BOX_WIDTH / 2.0, at.y - height / 2.0);
+        let color = if lines.is_empty() {
+            "#52525b"
+        } else {
+            "#a78bfa"
+        };
+        svg.push_str(&format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"1.5\"/>\n<circle cx=\"{}\" cy=\"{}\" r=\"5\" fill=\"{}\"/>\n",
+            button.button_pos.x, button.button_pos.y, at.x, at.y, color,
+            button.button_pos.x, button.button_pos.y, color
+        ));
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"6\" fill=\"#18181b\" stroke=\"{}\"/>\n",
+            left, top, BOX_WIDTH, height, color
+        ));
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" fill=\"#a1a1aa\" font-weight=\"bold\">{}</text>\n",
+            left + 8.0,
+            top + LINE_HEIGHT,
+            escape(&shorten(&button.name))
+        ));
+        if lines.is_empty() {
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" fill=\"#52525b\">Unbound</text>\n",
+                left + 8.0,
+                top + 2.0 * LINE_HEIGHT
+            ));
+        }
+        for (i, line) in lines.iter().enumerate() {
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" fill=\"#fafafa\">{}</text>\n",
+                left + 8.0,
+                top + (i as f64 + 2.0) * LINE_HEIGHT,
+                escape(line)
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    RenderedCard {
+        svg,
+        width,
+        height,
+        bound,
+    }
+}
+
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == ' ' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    stem.trim().to_string()
+}
+
+/// Write a card for every device page of the joystick template at `template_path` into
+/// `output_dir`
+pub fn export_cards(
+    template_path: &Path,
+    output_dir: &Path,
+    format: CardFormat,
+    labels: &HashMap<String, Vec<String>>,
+) -> Result<Vec<ReferenceCard>, String> {
+    let json = std::fs::read_to_string(template_path)
+        .map_err(|e| format!("Failed to read template: {}", e))?;
+    let template: TemplateFile =
+        serde_json::from_str(&json).map_err(|e| format!("Failed to parse template: {}", e))?;
+    let template_dir = template_path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir.display(), e))?;
+
+    let mut written = Vec::new();
+    for card in cards(&template, template_dir) {
+        let rendered = render_card(&card, template.image_width, template.image_height, labels);
+        let stem = file_stem(&format!("{} - {}", template.name, card.name));
+        let svg_path = output_dir.join(format!("{}.svg", stem));
+        std::fs::write(&svg_path, &rendered.svg)
+            .map_err(|e| format!("Failed to write {}: {}", svg_path.display(), e))?;
+
+        let path = match format {
+            CardFormat::Svg => svg_path,
+            CardFormat::Png => {
+                let png_path = output_dir.join(format!("{}.png", stem));
+                let file_url = format!("file:///{}", svg_path.to_string_lossy().replace('\\', "/"));
+                let result = cheat_sheet::run_headless_browser(
+                    &[
+                        "--hide-scrollbars".to_string(),
+                        format!(
+                            "--window-size={},{}",
+                            rendered.width.ceil(),
+                            rendered.height.ceil()
+                        ),
+                        format!("--screenshot={}", png_path.to_string_lossy()),
+                        file_url,
+                    ],
+                    &png_path,
+                    "PNG",
+                );
+                let _ = std::fs::remove_file(&svg_path);
+                result?;
+                png_path
+            }
+        };
+        written.push(ReferenceCard {
+            device: card.name.to_string(),
+            instance: card.instance,
+            path: path.to_string_lossy().to_string(),
+            bound_controls: rendered.bound,
+        });
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_shows_bound_actions() {
+        let template: TemplateFile = serde_json::from_str(
+            r#"{"name": "Test", "imageWidth": 400, "imageHeight": 300, "pages": [
+                {"id": "a", "name": "Right Stick", "device_prefix": "js2", "buttons": [
+                    {"name": "Trigger", "buttonPos": {"x": 100, "y": 100},
+                     "labelPos": {"x": -150, "y": 50}, "inputs": {"main": "button1"}},
+                    {"name": "Hat", "buttonPos": {"x": 120, "y": 80},
+                     "labelPos": {"x": 500, "y": 80}, "inputs": {"up": "hat1_up", "down": "hat1_down"}}
+                ]},
+                {"id": "b", "name": "Left <Stick>", "joystickNumber": 1, "mirror_from_page_id": "a"}
+            ]}"#,
+        )
+        .unwrap();
+        let labels = HashMap::from([
+            ("js2_button1".to_string(), vec!["Fire & Forget".to_string()]),
+            (
+                "js2_hat1_down".to_string(),
+                vec!["Landing gear".to_string()],
+            ),
+            ("js1_button1".to_string(), vec!["Wrong stick".to_string()]),
+        ]);
+
+        let cards = cards(&template, Path::new("."));
+        assert_eq!(cards[0].instance, 2);
+        assert!(cards[1].flipped);
+        let card = render_card(&cards[0], 400.0, 300.0, &labels);
+        assert_eq!(card.bound, 2);
+        assert!(card.svg.contains("Fire &amp; Forget"));
+        assert!(card.svg.contains("Down: Landing gear"));
+        assert!(!card.svg.contains("Wrong stick"));
+        // The label left of the diagram is inside the card
+        assert!(card.svg.contains("viewBox=\"-280 "));
+        assert_eq!(card.width, 400.0 + 110.0 + 260.0 + 40.0);
+
+        let card = render_card(&cards[1], 400.0, 300.0, &labels);
+        assert_eq!(card.bound, 0);
+        assert!(card.svg.contains("Left &lt;Stick&gt; (js1)"));
+    }
+}
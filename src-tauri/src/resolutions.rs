@@ -0,0 +1,269 @@
+//! Conflict resolution transcripts
+//!
+//! When a merge, import or template application finds an input bound to two actions,
+//! the user decides which one keeps it. Every decision is recorded in a transcript
+//! stored next to the profile (`<profile>.resolutions.json`) so the same conflicts can
+//! be resolved automatically the next time they come up.
+
+use crate::keybindings::ActionMaps;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// What the user chose to do about a conflict
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolutionChoice {
+    /// The incoming action gets the input, it's removed from the existing action
+    UseIncoming,
+    /// The existing action keeps the input, it's removed from the incoming action
+    KeepExisting,
+    /// Leave the input on both actions
+    KeepBoth,
+}
+
+/// A single recorded decision
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResolutionDecision {
+    /// Where the conflict came from, e.g. "merge", "import" or "template"
+    pub source: String,
+    pub input: String,
+    pub action_map: String,
+    pub action: String,
+    pub existing_action_map: String,
+    pub existing_action: String,
+    pub choice: ResolutionChoice,
+    #[serde(default)]
+    pub decided_at: String,
+}
+
+impl ResolutionDecision {
+    /// Two decisions are about the same conflict regardless of source or when they were made
+    fn same_conflict(&self, other: &ResolutionDecision) -> bool {
+        self.input == other.input
+            && self.action_map == other.action_map
+            && self.action == other.action
+            && self.existing_action_map == other.existing_action_map
+            && self.existing_action == other.existing_action
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ResolutionTranscript {
+    pub decisions: Vec<ResolutionDecision>,
+}
+
+/// Transcript file stored alongside the profile
+pub fn transcript_path(profile_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.resolutions.json", profile_path))
+}
+
+pub fn load_transcript(path: &Path) -> Result<ResolutionTranscript, String> {
+    if !path.exists() {
+        return Ok(ResolutionTranscript::default());
+    }
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read resolution transcript: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse resolution transcript: {}", e))
+}
+
+pub fn save_transcript(path: &Path, transcript: &ResolutionTranscript) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(transcript)
+        .map_err(|e| format!("Failed to serialize resolution transcript: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write resolution transcript: {}", e))
+}
+
+impl ResolutionTranscript {
+    /// Record a decision, replacing any earlier decision for the same conflict
+    pub fn record(&mut self, mut decision: ResolutionDecision) {
+        if decision.decided_at.is_empty() {
+            decision.decided_at = chrono::Local::now().to_rfc3339();
+        }
+        self.decisions.retain(|d| !d.same_conflict(&decision));
+        self.decisions.push(decision);
+    }
+
+    /// Re-apply recorded decisions to any conflicts still present in the bindings.
+    /// Returns the decisions that changed something.
+    pub fn reapply(&self, bindings: &mut ActionMaps) -> Vec<ResolutionDecision> {
+        let mut applied = Vec::new();
+
+        for decision in &self.decisions {
+            if decision.choice == ResolutionChoice::KeepBoth {
+                continue;
+            }
+            if !has_input(
+                bindings,
+                &decision.action_map,
+                &decision.action,
+                &decision.input,
+            ) || !has_input(
+                bindings,
+                &decision.existing_action_map,
+                &decision.existing_action,
+                &decision.input,
+            ) {
+                continue;
+            }
+
+            let (loser_map, loser_action) = match decision.choice {
+                ResolutionChoice::UseIncoming => {
+                    (&decision.existing_action_map, &decision.existing_action)
+                }
+                _ => (&decision.action_map, &decision.action),
+            };
+            remove_input(bindings, loser_map, loser_action, &decision.input);
+            applied.push(decision.clone());
+        }
+
+        applied
+    }
+}
+
+/// Every (action map, action, input) in the bindings
+fn bound_inputs(bindings: &ActionMaps) -> Vec<(&str, &str, &str)> {
+    bindings
+        .action_maps
+        .iter()
+        .flat_map(|am| {
+            am.actions.iter().flat_map(move |a| {
+                a.rebinds
+                    .iter()
+                    .map(move |r| (am.name.as_str(), a.name.as_str(), r.input.trim()))
+            })
+        })
+        .filter(|(_, _, input)| !input.is_empty())
+        .collect()
+}
+
+/// The conflicts a merge, import or template apply ran into going from `before` to
+/// `after`, and how it resolved them: an input it added to an action that another action
+/// already had was either taken from that action (the incoming binding won) or left on
+/// both
+pub fn resolved_by_apply(
+    source: &str,
+    before: &ActionMaps,
+    after: &ActionMaps,
+) -> Vec<ResolutionDecision> {
+    let before = bound_inputs(before);
+    let after = bound_inputs(after);
+
+    let mut decisions = Vec::new();
+    for &(action_map, action, input) in after.iter().filter(|b| !before.contains(b)) {
+        for &(existing_map, existing_action, _) in before
+            .iter()
+            .filter(|b| b.2 == input && (b.0, b.1) != (action_map, action))
+        {
+            let choice = if after.contains(&(existing_map, existing_action, input)) {
+                ResolutionChoice::KeepBoth
+            } else {
+                ResolutionChoice::UseIncoming
+            };
+            decisions.push(ResolutionDecision {
+                source: source.to_string(),
+                input: input.to_string(),
+                action_map: action_map.to_string(),
+                action: action.to_string(),
+                existing_action_map: existing_map.to_string(),
+                existing_action: existing_action.to_string(),
+                choice,
+                decided_at: String::new(),
+            });
+        }
+    }
+    decisions
+}
+
+fn has_input(bindings: &ActionMaps, action_map: &str, action: &str, input: &str) -> bool {
+    bindings
+        .action_maps
+        .iter()
+        .filter(|am| am.name == action_map)
+        .flat_map(|am| am.actions.iter())
+        .filter(|a| a.name == action)
+        .any(|a| a.rebinds.iter().any(|r| r.input == input))
+}
+
+fn remove_input(bindings: &mut ActionMaps, action_map: &str, action: &str, input: &str) {
+    for am in bindings
+        .action_maps
+        .iter_mut()
+        .filter(|am| am.name == action_map)
+    {
+        for a in am.actions.iter_mut().filter(|a| a.name == action) {
+            a.rebinds.retain(|r| r.input != input);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEFORE: &str = r#"<ActionMaps>
+ <ActionProfiles profileName="default">
+  <actionmap name="spaceship_general">
+   <action name="v_toggle_landing_system">
+    <rebind input="js1_button3"/>
+   </action>
+   <action name="v_toggle_quantum_mode">
+    <rebind input="js1_button4"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>"#;
+
+    #[test]
+    fn test_apply_resolutions_are_recorded_and_reapplied() {
+        let before = ActionMaps::from_xml(BEFORE).unwrap();
+        // The import moved button 3 to the mining laser and also put button 4 on it
+        let after = ActionMaps::from_xml(
+            &BEFORE
+                .replace(
+                    r#"<action name="v_toggle_landing_system">
+    <rebind input="js1_button3"/>"#,
+                    r#"<action name="v_toggle_landing_system">"#,
+                )
+                .replace(
+                    "  </actionmap>\n",
+                    "  </actionmap>\n  <actionmap name=\"spaceship_mining\">\n   <action name=\"v_toggle_mining_laser_fire\">\n    <rebind input=\"js1_button3\"/>\n    <rebind input=\"js1_button4\"/>\n   </action>\n  </actionmap>\n",
+                ),
+        )
+        .unwrap();
+
+        let decisions = resolved_by_apply("import", &before, &after);
+        let summary: Vec<(&str, &str, ResolutionChoice)> = decisions
+            .iter()
+            .map(|d| (d.input.as_str(), d.existing_action.as_str(), d.choice))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "js1_button3",
+                    "v_toggle_landing_system",
+                    ResolutionChoice::UseIncoming
+                ),
+                (
+                    "js1_button4",
+                    "v_toggle_quantum_mode",
+                    ResolutionChoice::KeepBoth
+                ),
+            ]
+        );
+
+        // Recorded, the same decision takes button 3 away again after a reset
+        let mut transcript = ResolutionTranscript::default();
+        for decision in decisions {
+            transcript.record(decision);
+        }
+        let mut conflicted = ActionMaps::from_xml(BEFORE).unwrap();
+        conflicted.action_maps.push(after.action_maps[1].clone());
+        assert_eq!(transcript.reapply(&mut conflicted).len(), 1);
+        assert!(!has_input(
+            &conflicted,
+            "spaceship_general",
+            "v_toggle_landing_system",
+            "js1_button3"
+        ));
+    }
+}
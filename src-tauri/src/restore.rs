@@ -0,0 +1,138 @@
+//! Restoring actionmaps.xml from a backup or snapshot
+//!
+//! Restoring used to mean finding a backup file and copying it over actionmaps.xml by
+//! hand, without knowing what it would undo. It's now two steps: a preview comparing the
+//! backup with the current file (per device and option, and per actionmap for bindings),
+//! then the restore with the token from that preview. Like apply confirmations, the token
+//! is derived from both documents, so a restore is refused if either changed after the
+//! preview was shown. The file being replaced is backed up first.
+
+use crate::apply_guard;
+use crate::backups::{self, BackupLocation};
+use crate::diff::{self, DiffModel};
+use crate::game_process::{self, GameProcess};
+use crate::modification_log;
+use serde::Serialize;
+
+/// What restoring a backup over actionmaps.xml would change
+#[derive(Debug, Serialize, Clone)]
+pub struct RestorePreview {
+    pub target_path: String,
+    /// The backup matches the current file; restoring changes nothing
+    pub identical: bool,
+    /// From the current file to the backup
+    pub changes: DiffModel,
+    /// Pass back to restore exactly this change
+    pub token: String,
+    /// Star Citizen running from the target's installation; it would overwrite the
+    /// restored file when it exits
+    pub game_running: Option<GameProcess>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RestoreResult {
+    /// Backup of the file that was replaced
+    pub backup_path: String,
+    pub changed_options: usize,
+    pub changed_bindings: usize,
+}
+
+fn read_current(target_path: &str) -> Result<String, String> {
+    std::fs::read_to_string(target_path)
+        .map_err(|e| format!("Failed to read {}: {}", target_path, e))
+}
+
+/// Compare `backup_xml` with the file at `target_path`
+pub fn preview(target_path: &str, backup_xml: &str) -> Result<RestorePreview, String> {
+    let current = read_current(target_path)?;
+    let diff = diff::diff_actionmaps(&current, backup_xml)?;
+    Ok(RestorePreview {
+        target_path: target_path.to_string(),
+        identical: diff.is_empty(),
+        changes: DiffModel::from_diff(diff, true),
+        token: apply_guard::token(&current, backup_xml),
+        game_running: game_process::running_for(target_path),
+    })
+}
+
+/// Write `backup_xml` over `target_path`, if the file is still as it was when `token`
+/// was previewed
+pub fn restore(
+    target_path: &str,
+    backup_xml: &str,
+    token: &str,
+    backup_location: &BackupLocation,
+) -> Result<RestoreResult, String> {
+    let current = read_current(target_path)?;
+    if apply_guard::token(&current, backup_xml) != token {
+        return Err(
+            "actionmaps.xml or the backup changed since the preview. Preview the restore again."
+                .to_string(),
+        );
+    }
+    let diff = diff::diff_actionmaps(&current, backup_xml)?;
+
+    let backup_path = backups::create_backup(backup_location, target_path)?;
+    modification_log::note_own_write(backup_xml);
+    std::fs::write(target_path, backup_xml)
+        .map_err(|e| format!("Failed to write {}: {}", target_path, e))?;
+
+    Ok(RestoreResult {
+        backup_path,
+        changed_options: diff.option_changes.len(),
+        changed_bindings: diff.binding_changes.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::TempDir;
+
+    fn actionmaps(invert: &str) -> String {
+        format!(
+            "<ActionMaps>\n <ActionProfiles profileName=\"default\">\n  <options type=\"joystick\" instance=\"1\" Product=\"Stick\">\n   <flight_move_pitch invert=\"{}\"/>\n  </options>\n </ActionProfiles>\n</ActionMaps>",
+            invert
+        )
+    }
+
+    #[test]
+    fn test_restore_needs_current_preview() {
+        let temp = TempDir::new("restore");
+        let dir = temp.path();
+        let target = dir.join("actionmaps.xml");
+        let target_path = target.to_string_lossy().to_string();
+        std::fs::write(&target, actionmaps("0")).unwrap();
+        let location = BackupLocation {
+            path: dir.join("backups").to_string_lossy().to_string(),
+            configured_path: None,
+            using_fallback: false,
+            unavailable_reason: None,
+        };
+
+        let backup = actionmaps("1");
+        let shown = preview(&target_path, &backup).unwrap();
+        assert!(!shown.identical);
+        assert_eq!(shown.changes.devices.len(), 1);
+        assert_eq!(
+            shown.changes.devices[0].options[0].option,
+            "flight_move_pitch"
+        );
+
+        // The game rewrote the file after the preview
+        std::fs::write(
+            &target,
+            actionmaps("0").replace("  </options>", "  </options>\n"),
+        )
+        .unwrap();
+        assert!(restore(&target_path, &backup, &shown.token, &location).is_err());
+
+        let shown = preview(&target_path, &backup).unwrap();
+        let result = restore(&target_path, &backup, &shown.token, &location).unwrap();
+        assert_eq!(result.changed_options, 1);
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), backup);
+        assert!(std::fs::read_to_string(&result.backup_path)
+            .unwrap()
+            .contains("invert=\"0\""));
+    }
+}
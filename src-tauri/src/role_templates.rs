@@ -0,0 +1,505 @@
+//! Gameplay role templates
+//!
+//! A profile tuned for dogfighting is a poor start for mining: the laser wants fine aim
+//! near center and its power on an axis, not missiles under the thumb. A role template
+//! lists the actions a role leans on, binds the ones with an obvious home on the
+//! devices' roles (the profile's stick, throttle, ...) and sets the option tweaks the
+//! role wants. It is merged over an existing profile, so everything the role doesn't
+//! care about stays as the user had it; where the two disagree the role wins.
+
+use crate::controls::{ControlsFile, DeviceInstanceSettings};
+use crate::device_roles::DeviceRole;
+use crate::keybindings::{Action, ActionMaps, Rebind};
+use serde::Serialize;
+
+/// A device a template binds to: the n-th joystick (by instance) with a role. HOSAS
+/// setups have two sticks; the lower-numbered one is stick 0.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct DeviceSlot {
+    pub role: DeviceRole,
+    pub index: usize,
+}
+
+const fn slot(role: DeviceRole, index: usize) -> DeviceSlot {
+    DeviceSlot { role, index }
+}
+
+const STICK: DeviceSlot = slot(DeviceRole::Stick, 0);
+const SECOND_STICK: DeviceSlot = slot(DeviceRole::Stick, 1);
+const THROTTLE: DeviceSlot = slot(DeviceRole::Throttle, 0);
+
+/// Where a role binding goes
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RoleInput {
+    /// SC keyboard input without the kb1_ prefix, e.g. "m"
+    Keyboard { input: &'static str },
+    /// SC joystick input without the jsN_ prefix, e.g. "button3"
+    Device {
+        slot: DeviceSlot,
+        input: &'static str,
+    },
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct RoleBinding {
+    pub action_map: &'static str,
+    pub action: &'static str,
+    pub input: RoleInput,
+}
+
+/// An option the role sets on a device
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct RoleOption {
+    pub slot: DeviceSlot,
+    pub option: &'static str,
+    pub deadzone: Option<f64>,
+    pub exponent: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct RoleTemplate {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The actions the role leans on, most important first (actionmap, action)
+    pub priorities: &'static [(&'static str, &'static str)],
+    pub bindings: &'static [RoleBinding],
+    pub options: &'static [RoleOption],
+}
+
+const fn on(
+    slot: DeviceSlot,
+    input: &'static str,
+    action_map: &'static str,
+    action: &'static str,
+) -> RoleBinding {
+    RoleBinding {
+        action_map,
+        action,
+        input: RoleInput::Device { slot, input },
+    }
+}
+
+const fn key(input: &'static str, action_map: &'static str, action: &'static str) -> RoleBinding {
+    RoleBinding {
+        action_map,
+        action,
+        input: RoleInput::Keyboard { input },
+    }
+}
+
+const fn option(
+    slot: DeviceSlot,
+    option: &'static str,
+    deadzone: Option<f64>,
+    exponent: Option<f64>,
+) -> RoleOption {
+    RoleOption {
+        slot,
+        option,
+        deadzone,
+        exponent,
+    }
+}
+
+const MOVEMENT: &str = "spaceship_movement";
+const WEAPONS: &str = "spaceship_weapons";
+const MISSILES: &str = "spaceship_missiles";
+const DEFENSIVE: &str = "spaceship_defensive";
+const MINING: &str = "spaceship_mining";
+const SEAT: &str = "seat_general";
+const TURRET: &str = "turret_movement";
+
+pub const ROLE_TEMPLATES: &[RoleTemplate] = &[
+    RoleTemplate {
+        id: "combat_hosas",
+        name: "Combat (HOSAS)",
+        description: "Two sticks: rotation on the first, strafing on the second, guns, missiles and countermeasures under the fingers",
+        priorities: &[
+            (MOVEMENT, "v_pitch"),
+            (MOVEMENT, "v_yaw"),
+            (MOVEMENT, "v_roll"),
+            (MOVEMENT, "v_strafe_lateral"),
+            (MOVEMENT, "v_strafe_vertical"),
+            (MOVEMENT, "v_strafe_longitudinal"),
+            (WEAPONS, "v_attack_group1"),
+            (WEAPONS, "v_attack_group2"),
+            (MISSILES, "v_weapon_launch_missile"),
+            (DEFENSIVE, "v_weapon_countermeasure_decoy_launch"),
+            (MOVEMENT, "v_ifcs_vector_decoupling_toggle"),
+        ],
+        bindings: &[
+            on(STICK, "x", MOVEMENT, "v_roll"),
+            on(STICK, "y", MOVEMENT, "v_pitch"),
+            on(STICK, "rotz", MOVEMENT, "v_yaw"),
+            on(STICK, "button1", WEAPONS, "v_attack_group1"),
+            on(STICK, "button2", WEAPONS, "v_attack_group2"),
+            on(SECOND_STICK, "x", MOVEMENT, "v_strafe_lateral"),
+            on(SECOND_STICK, "y", MOVEMENT, "v_strafe_longitudinal"),
+            on(SECOND_STICK, "rotz", MOVEMENT, "v_strafe_vertical"),
+            on(SECOND_STICK, "button1", MISSILES, "v_weapon_launch_missile"),
+            on(
+                SECOND_STICK,
+                "button2",
+                DEFENSIVE,
+                "v_weapon_countermeasure_decoy_launch",
+            ),
+        ],
+        options: &[
+            option(STICK, "flight_move_pitch", Some(0.02), Some(1.5)),
+            option(STICK, "flight_move_yaw", Some(0.02), Some(1.5)),
+            option(STICK, "flight_move_roll", Some(0.02), Some(1.3)),
+            option(SECOND_STICK, "flight_move_strafe_lateral", Some(0.05), None),
+            option(SECOND_STICK, "flight_move_strafe_vertical", Some(0.05), None),
+        ],
+    },
+    RoleTemplate {
+        id: "mining",
+        name: "Mining",
+        description: "Fine aim near center, laser power on the throttle's slider and the mining controls on the stick",
+        priorities: &[
+            (SEAT, "v_toggle_mining_mode"),
+            (MINING, "v_toggle_mining_laser_fire"),
+            (MINING, "v_mining_throttle"),
+            (MINING, "v_toggle_mining_laser_type"),
+            (MINING, "v_mining_use_consumable1"),
+            (MOVEMENT, "v_pitch"),
+            (MOVEMENT, "v_yaw"),
+        ],
+        bindings: &[
+            key("m", SEAT, "v_toggle_mining_mode"),
+            on(STICK, "button1", MINING, "v_toggle_mining_laser_fire"),
+            on(STICK, "button2", MINING, "v_toggle_mining_laser_type"),
+            on(THROTTLE, "slider1", MINING, "v_mining_throttle"),
+        ],
+        options: &[
+            option(STICK, "flight_move_pitch", Some(0.03), Some(2.2)),
+            option(STICK, "flight_move_yaw", Some(0.03), Some(2.2)),
+        ],
+    },
+    RoleTemplate {
+        id: "racing",
+        name: "Racing",
+        description: "Linear, immediate response with boost and brake on the throttle",
+        priorities: &[
+            (MOVEMENT, "v_strafe_longitudinal"),
+            (MOVEMENT, "v_afterburner"),
+            (MOVEMENT, "v_space_brake"),
+            (MOVEMENT, "v_pitch"),
+            (MOVEMENT, "v_roll"),
+            (MOVEMENT, "v_yaw"),
+        ],
+        bindings: &[
+            on(STICK, "x", MOVEMENT, "v_roll"),
+            on(STICK, "y", MOVEMENT, "v_pitch"),
+            on(STICK, "rotz", MOVEMENT, "v_yaw"),
+            on(THROTTLE, "z", MOVEMENT, "v_strafe_longitudinal"),
+            on(THROTTLE, "button1", MOVEMENT, "v_afterburner"),
+            on(THROTTLE, "button2", MOVEMENT, "v_space_brake"),
+        ],
+        options: &[
+            option(STICK, "flight_move_pitch", Some(0.01), Some(1.0)),
+            option(STICK, "flight_move_yaw", Some(0.01), Some(1.0)),
+            option(STICK, "flight_move_roll", Some(0.01), Some(1.0)),
+        ],
+    },
+    RoleTemplate {
+        id: "turret",
+        name: "Turret gunner",
+        description: "Turret aim on the stick with a soft center for tracking, firing on the trigger",
+        priorities: &[
+            (TURRET, "turret_pitch"),
+            (TURRET, "turret_yaw"),
+            (WEAPONS, "v_attack_group1"),
+        ],
+        bindings: &[
+            on(STICK, "y", TURRET, "turret_pitch"),
+            on(STICK, "x", TURRET, "turret_yaw"),
+            on(STICK, "button1", WEAPONS, "v_attack_group1"),
+        ],
+        options: &[
+            option(STICK, "turret_aim_pitch", Some(0.05), Some(1.6)),
+            option(STICK, "turret_aim_yaw", Some(0.05), Some(1.6)),
+        ],
+    },
+];
+
+pub fn find(id: &str) -> Option<&'static RoleTemplate> {
+    ROLE_TEMPLATES.iter().find(|t| t.id == id)
+}
+
+/// A template binding that couldn't be made
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SkippedRoleBinding {
+    pub action: String,
+    pub reason: String,
+}
+
+/// A priority action and whether the merged profile binds it
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RolePriority {
+    pub action_map: String,
+    pub action: String,
+    pub bound: bool,
+}
+
+pub struct RoleProfile {
+    pub bindings: ActionMaps,
+    pub controls: ControlsFile,
+    pub skipped: Vec<SkippedRoleBinding>,
+    /// Actions that lost an input to a role binding, as "action (input)"
+    pub displaced: Vec<String>,
+    pub priorities: Vec<RolePriority>,
+}
+
+/// The joystick instance filling a slot, from the roles set in the profile
+fn instance_for(controls: &ControlsFile, slot: DeviceSlot) -> Option<String> {
+    let mut instances: Vec<(&String, &DeviceInstanceSettings)> = controls
+        .devices
+        .joystick
+        .iter()
+        .flatten()
+        .filter(|(_, device)| device.role == Some(slot.role))
+        .collect();
+    instances.sort_by_key(|(instance, _)| instance.parse::<usize>().unwrap_or(usize::MAX));
+    instances
+        .get(slot.index)
+        .map(|(instance, _)| instance.to_string())
+}
+
+fn slot_label(slot: DeviceSlot) -> String {
+    match slot.index {
+        0 => slot.role.label().to_string(),
+        n => format!("{} {}", slot.role.label(), n + 1),
+    }
+}
+
+/// Merge `template` over a base profile. `known_action` says whether the game has an
+/// action, so bindings for actions this game version doesn't have are skipped.
+pub fn merge(
+    template: &RoleTemplate,
+    profile_name: &str,
+    base_controls: &ControlsFile,
+    base_bindings: &ActionMaps,
+    known_action: impl Fn(&str, &str) -> bool,
+) -> RoleProfile {
+    let mut controls = base_controls.clone();
+    controls.profile_name = profile_name.to_string();
+    let mut bindings = base_bindings.clone();
+    bindings.profile_name = profile_name.to_string();
+    let mut skipped = Vec::new();
+    let mut displaced = Vec::new();
+
+    for role_option in template.options {
+        let Some(instance) = instance_for(base_controls, role_option.slot) else {
+            continue;
+        };
+        let Ok(device) = controls.device_mut("joystick", &instance) else {
+            continue;
+        };
+        let settings = device
+            .options
+            .entry(role_option.option.to_string())
+            .or_default();
+        if role_option.deadzone.is_some() {
+            settings.deadzone = role_option.deadzone;
+        }
+        if role_option.exponent.is_some() {
+            settings.curve_mode = Some("exponent".to_string());
+            settings.exponent = role_option.exponent;
+            settings.curve = None;
+        }
+    }
+
+    for binding in template.bindings {
+        if !known_action(binding.action_map, binding.action) {
+            skipped.push(SkippedRoleBinding {
+                action: binding.action.to_string(),
+                reason: format!("{} isn't an action in this game version", binding.action),
+            });
+            continue;
+        }
+        let (prefix, input) = match binding.input {
+            RoleInput::Keyboard { input } => ("kb1_".to_string(), input),
+            RoleInput::Device { slot, input } => match instance_for(base_controls, slot) {
+                Some(instance) => (format!("js{}_", instance), input),
+                None => {
+                    skipped.push(SkippedRoleBinding {
+                        action: binding.action.to_string(),
+                        reason: format!("The base profile has no {}", slot_label(slot)),
+                    });
+                    continue;
+                }
+            },
+        };
+        let full_input = format!("{}{}", prefix, input);
+
+        let map = match bindings
+            .action_maps
+            .iter()
+            .position(|m| m.name == binding.action_map)
+        {
+            Some(index) => &mut bindings.action_maps[index],
+            None => {
+                bindings.action_maps.push(ActionMaps::new_empty_action_map(
+                    binding.action_map.to_string(),
+                    Vec::new(),
+                ));
+                bindings.action_maps.last_mut().unwrap()
+            }
+        };
+
+        // The role's input is taken from whatever else in the map used it
+        for action in map.actions.iter_mut().filter(|a| a.name != binding.action) {
+            let before = action.rebinds.len();
+            action.rebinds.retain(|r| r.input != full_input);
+            if action.rebinds.len() < before {
+                displaced.push(format!("{} ({})", action.name, full_input));
+            }
+        }
+
+        // and replaces the action's own binding on the same device
+        let rebind = Rebind {
+            input: full_input,
+            multi_tap: None,
+            activation_mode: String::new(),
+        };
+        match map.actions.iter_mut().find(|a| a.name == binding.action) {
+            Some(action) => {
+                action.rebinds.retain(|r| !r.input.starts_with(&prefix));
+                action.rebinds.push(rebind);
+            }
+            None => map.actions.push(Action {
+                name: binding.action.to_string(),
+                rebinds: vec![rebind],
+            }),
+        }
+    }
+
+    let priorities = template
+        .priorities
+        .iter()
+        .map(|(action_map, action)| RolePriority {
+            action_map: action_map.to_string(),
+            action: action.to_string(),
+            bound: bindings
+                .action_maps
+                .iter()
+                .filter(|m| m.name == *action_map)
+                .flat_map(|m| &m.actions)
+                .any(|a| a.name == *action && !a.rebinds.is_empty()),
+        })
+        .collect();
+
+    RoleProfile {
+        bindings,
+        controls,
+        skipped,
+        displaced,
+        priorities,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybindings::DeviceInfo;
+    use crate::sc_sim::is_bundled_action;
+
+    #[test]
+    fn test_role_merges_over_base() {
+        let mut base = ControlsFile::new("Base".to_string());
+        base.device_mut("joystick", "1").unwrap().role = Some(DeviceRole::Throttle);
+        base.device_mut("joystick", "2").unwrap().role = Some(DeviceRole::Stick);
+        let base_bindings = ActionMaps {
+            profile_name: "Base".to_string(),
+            action_maps: vec![ActionMaps::new_empty_action_map(
+                MOVEMENT.to_string(),
+                vec![
+                    Action {
+                        name: "v_pitch".to_string(),
+                        rebinds: vec![Rebind {
+                            input: "js2_rotx".to_string(),
+                            multi_tap: None,
+                            activation_mode: String::new(),
+                        }],
+                    },
+                    Action {
+                        name: "v_strafe_up".to_string(),
+                        rebinds: vec![Rebind {
+                            input: "js2_rotz".to_string(),
+                            multi_tap: None,
+                            activation_mode: String::new(),
+                        }],
+                    },
+                ],
+            )],
+            categories: Vec::new(),
+            devices: DeviceInfo {
+                keyboards: Vec::new(),
+                mice: Vec::new(),
+                joysticks: Vec::new(),
+                device_options: Vec::new(),
+            },
+        };
+
+        let combat = find("combat_hosas").unwrap();
+        let merged = merge(combat, "Combat", &base, &base_bindings, |_, action| {
+            action != "v_attack_group2"
+        });
+
+        let movement = &merged.bindings.action_maps[0];
+        let inputs = |name: &str| -> Vec<String> {
+            movement
+                .actions
+                .iter()
+                .find(|a| a.name == name)
+                .map(|a| a.rebinds.iter().map(|r| r.input.clone()).collect())
+                .unwrap_or_default()
+        };
+        assert_eq!(inputs("v_pitch"), vec!["js2_y".to_string()]);
+        assert_eq!(inputs("v_yaw"), vec!["js2_rotz".to_string()]);
+        assert!(inputs("v_strafe_up").is_empty());
+        assert_eq!(merged.displaced, vec!["v_strafe_up (js2_rotz)".to_string()]);
+
+        // One stick only, so the second stick's bindings are skipped, as is the unknown action
+        assert_eq!(merged.skipped.len(), 6);
+        assert!(merged
+            .skipped
+            .iter()
+            .any(|s| s.action == "v_attack_group2" && s.reason.contains("game version")));
+        assert!(merged.skipped.iter().any(|s| s.reason.contains("Stick 2")));
+
+        let stick = merged.controls.device("joystick", "2").unwrap();
+        assert_eq!(stick.options["flight_move_pitch"].exponent, Some(1.5));
+        assert!(!merged
+            .controls
+            .device("joystick", "1")
+            .unwrap()
+            .options
+            .contains_key("flight_move_pitch"));
+
+        let priority = |action: &str| merged.priorities.iter().find(|p| p.action == action);
+        assert!(priority("v_pitch").unwrap().bound);
+        assert!(!priority("v_strafe_lateral").unwrap().bound);
+        assert_eq!(merged.controls.profile_name, "Combat");
+    }
+
+    #[test]
+    fn test_templates_use_known_actions() {
+        let unknown: Vec<(&str, &str, &str)> = ROLE_TEMPLATES
+            .iter()
+            .flat_map(|t| {
+                let bound = t.bindings.iter().map(|b| (b.action_map, b.action));
+                t.priorities
+                    .iter()
+                    .copied()
+                    .chain(bound)
+                    .map(move |(map, action)| (t.id, map, action))
+            })
+            .filter(|(_, map, action)| !is_bundled_action(map, action))
+            .collect();
+        assert!(unknown.is_empty(), "not in AllBinds.xml: {:?}", unknown);
+    }
+}
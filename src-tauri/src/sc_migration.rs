@@ -0,0 +1,343 @@
+//! Carry bindings and profiles across Star Citizen patches
+//!
+//! Now and then a patch renames an action or an option, or drops one. SC silently ignores
+//! identifiers it doesn't know, so a binding made before the patch just stops working.
+//! The installed defaultProfile tells which identifiers the current version has; anything
+//! a profile uses that it doesn't is looked up in a table of known changes. Renames are
+//! only applied once the defaultProfile has the new name and not the old one, so the
+//! table is harmless on versions from before the change.
+
+use crate::controls::{ControlsFile, DeviceInstanceSettings};
+use crate::keybindings::{ActionMaps, AllBinds};
+use crate::option_catalog::OptionCatalog;
+use serde::Serialize;
+use std::collections::HashSet;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentifierKind {
+    Action,
+    Option,
+}
+
+enum Change {
+    Renamed(&'static str),
+    /// Gone from the game, with why and what to use instead
+    Removed(&'static str),
+}
+
+struct IdentifierChange {
+    kind: IdentifierKind,
+    name: &'static str,
+    change: Change,
+}
+
+/// Identifiers CIG changed between patches
+const IDENTIFIER_CHANGES: &[IdentifierChange] = &[
+    IdentifierChange {
+        kind: IdentifierKind::Action,
+        name: "v_ifcs_toggle_vector_decoupling",
+        change: Change::Renamed("v_ifcs_vector_decoupling_toggle"),
+    },
+    IdentifierChange {
+        kind: IdentifierKind::Action,
+        name: "v_ifcs_toggle_cruise_control",
+        change: Change::Removed(
+            "Cruise control was removed; the speed limiter (v_ifcs_speed_limiter_toggle) is the closest replacement",
+        ),
+    },
+];
+
+/// Action and option names the installed defaultProfile has
+pub struct KnownIdentifiers {
+    actions: HashSet<String>,
+    options: HashSet<String>,
+}
+
+impl KnownIdentifiers {
+    pub fn new(all_binds: &AllBinds, catalog: &OptionCatalog) -> Self {
+        KnownIdentifiers {
+            actions: all_binds
+                .action_maps
+                .iter()
+                .flat_map(|action_map| action_map.actions.iter())
+                .map(|action| action.name.clone())
+                .collect(),
+            options: catalog
+                .options
+                .iter()
+                .map(|entry| entry.name.clone())
+                .collect(),
+        }
+    }
+
+    pub fn knows(&self, kind: IdentifierKind, name: &str) -> bool {
+        match kind {
+            IdentifierKind::Action => self.actions.contains(name),
+            IdentifierKind::Option => self.options.contains(name),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RenamedIdentifier {
+    pub kind: IdentifierKind,
+    /// Actionmap of an action, device of an option (e.g. "joystick 1")
+    pub context: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DroppedIdentifier {
+    pub kind: IdentifierKind,
+    pub context: String,
+    pub name: String,
+    pub reason: String,
+}
+
+/// An identifier the defaultProfile doesn't have and that couldn't be migrated; it's left
+/// as it is for the user to rebind or set again
+#[derive(Debug, Serialize, Clone)]
+pub struct UnresolvedIdentifier {
+    pub kind: IdentifierKind,
+    pub context: String,
+    pub name: String,
+    pub note: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct MigrationReport {
+    pub renamed: Vec<RenamedIdentifier>,
+    pub dropped: Vec<DroppedIdentifier>,
+    pub needs_attention: Vec<UnresolvedIdentifier>,
+}
+
+impl MigrationReport {
+    /// Whether anything was renamed or dropped
+    pub fn changed(&self) -> bool {
+        !self.renamed.is_empty() || !self.dropped.is_empty()
+    }
+}
+
+/// What to do with one identifier
+enum Outcome {
+    Keep,
+    Rename(String),
+    Drop(String),
+    Attention(String),
+}
+
+fn resolve(known: &KnownIdentifiers, kind: IdentifierKind, name: &str) -> Outcome {
+    if known.knows(kind, name) {
+        return Outcome::Keep;
+    }
+    let entry = IDENTIFIER_CHANGES
+        .iter()
+        .find(|c| c.kind == kind && c.name == name);
+    match entry.map(|e| &e.change) {
+        Some(Change::Renamed(to)) if known.knows(kind, to) => Outcome::Rename(to.to_string()),
+        Some(Change::Renamed(to)) => Outcome::Attention(format!(
+            "Renamed to {}, which the installed defaultProfile doesn't have either",
+            to
+        )),
+        Some(Change::Removed(reason)) => Outcome::Drop(reason.to_string()),
+        None => Outcome::Attention("Not in the installed defaultProfile".to_string()),
+    }
+}
+
+/// Rename, drop or flag the actions of `bindings` the defaultProfile doesn't know
+pub fn migrate_bindings(
+    bindings: &mut ActionMaps,
+    known: &KnownIdentifiers,
+    report: &mut MigrationReport,
+) {
+    let kind = IdentifierKind::Action;
+    for action_map in &mut bindings.action_maps {
+        let existing: HashSet<String> = action_map.actions.iter().map(|a| a.name.clone()).collect();
+        let context = action_map.name.clone();
+        action_map
+            .actions
+            .retain_mut(|action| match resolve(known, kind, &action.name) {
+                Outcome::Keep => true,
+                Outcome::Rename(to) if existing.contains(&to) => {
+                    report.dropped.push(DroppedIdentifier {
+                        kind,
+                        context: context.clone(),
+                        name: action.name.clone(),
+                        reason: format!("Renamed to {}, which is already bound", to),
+                    });
+                    false
+                }
+                Outcome::Rename(to) => {
+                    report.renamed.push(RenamedIdentifier {
+                        kind,
+                        context: context.clone(),
+                        from: std::mem::replace(&mut action.name, to.clone()),
+                        to,
+                    });
+                    true
+                }
+                Outcome::Drop(reason) => {
+                    report.dropped.push(DroppedIdentifier {
+                        kind,
+                        context: context.clone(),
+                        name: action.name.clone(),
+                        reason,
+                    });
+                    false
+                }
+                Outcome::Attention(note) => {
+                    report.needs_attention.push(UnresolvedIdentifier {
+                        kind,
+                        context: context.clone(),
+                        name: action.name.clone(),
+                        note,
+                    });
+                    true
+                }
+            });
+    }
+}
+
+fn migrate_device(
+    context: String,
+    device: &mut DeviceInstanceSettings,
+    known: &KnownIdentifiers,
+    report: &mut MigrationReport,
+) {
+    let kind = IdentifierKind::Option;
+    let mut names: Vec<String> = device.options.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        match resolve(known, kind, &name) {
+            Outcome::Keep => {}
+            Outcome::Rename(to) if device.options.contains_key(&to) => {
+                device.options.remove(&name);
+                report.dropped.push(DroppedIdentifier {
+                    kind,
+                    context: context.clone(),
+                    name,
+                    reason: format!("Renamed to {}, which is already set", to),
+                });
+            }
+            Outcome::Rename(to) => {
+                if let Some(settings) = device.options.remove(&name) {
+                    device.options.insert(to.clone(), settings);
+                }
+                report.renamed.push(RenamedIdentifier {
+                    kind,
+                    context: context.clone(),
+                    from: name,
+                    to,
+                });
+            }
+            Outcome::Drop(reason) => {
+                device.options.remove(&name);
+                report.dropped.push(DroppedIdentifier {
+                    kind,
+                    context: context.clone(),
+                    name,
+                    reason,
+                });
+            }
+            Outcome::Attention(note) => report.needs_attention.push(UnresolvedIdentifier {
+                kind,
+                context: context.clone(),
+                name,
+                note,
+            }),
+        }
+    }
+}
+
+/// Rename, drop or flag the options of `controls` the defaultProfile doesn't know
+pub fn migrate_controls(
+    controls: &mut ControlsFile,
+    known: &KnownIdentifiers,
+    report: &mut MigrationReport,
+) {
+    let devices = &mut controls.devices;
+    for (device_type, device) in [
+        ("keyboard", devices.keyboard.as_mut()),
+        ("mouse", devices.mouse.as_mut()),
+        ("gamepad", devices.gamepad.as_mut()),
+    ] {
+        if let Some(device) = device {
+            migrate_device(format!("{} 1", device_type), device, known, report);
+        }
+    }
+    let mut joysticks: Vec<_> = devices.joystick.iter_mut().flatten().collect();
+    joysticks.sort_by(|a, b| a.0.cmp(b.0));
+    for (instance, device) in joysticks {
+        migrate_device(format!("joystick {}", instance), device, known, report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controls::ControlOptionSettings;
+
+    #[test]
+    fn test_migrate_renamed_and_unknown() {
+        let known = KnownIdentifiers {
+            actions: ["v_ifcs_vector_decoupling_toggle", "v_eject"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            options: ["flight_move_pitch"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        };
+
+        let mut bindings = ActionMaps::from_xml(
+            r#"<ActionMaps>
+ <actionmap name="spaceship_movement">
+  <action name="v_ifcs_toggle_vector_decoupling">
+   <rebind input="js1_button5"/>
+  </action>
+  <action name="v_ifcs_toggle_cruise_control">
+   <rebind input="js1_button6"/>
+  </action>
+  <action name="v_eject">
+   <rebind input="js1_button7"/>
+  </action>
+ </actionmap>
+</ActionMaps>"#,
+        )
+        .unwrap();
+        let mut controls = ControlsFile::new("Old".to_string());
+        let stick = controls.device_mut("joystick", "1").unwrap();
+        for name in ["flight_move_pitch", "flight_move_warp"] {
+            stick.options.insert(
+                name.to_string(),
+                ControlOptionSettings {
+                    invert: Some(true),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let mut report = MigrationReport::default();
+        migrate_bindings(&mut bindings, &known, &mut report);
+        migrate_controls(&mut controls, &known, &mut report);
+
+        let names: Vec<&str> = bindings.action_maps[0]
+            .actions
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["v_ifcs_vector_decoupling_toggle", "v_eject"]);
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.dropped[0].name, "v_ifcs_toggle_cruise_control");
+        let attention: Vec<&str> = report
+            .needs_attention
+            .iter()
+            .map(|u| u.name.as_str())
+            .collect();
+        assert_eq!(attention, vec!["flight_move_warp"]);
+        assert_eq!(report.needs_attention[0].context, "joystick 1");
+    }
+}
@@ -0,0 +1,369 @@
+//! Test support: Star Citizen's file behavior, without the game
+//!
+//! The watcher, curve re-apply, rebase and essentials features all exist because of
+//! things the game does to actionmaps.xml: it rewrites the file on exit, drops curve
+//! data it doesn't keep, lists devices in whatever order Windows enumerated them, and
+//! resets everything after some patches. `ScSim` does the same to a real file in a
+//! temp directory, so regression tests for those features can run anywhere.
+//! `TempDir` gives other file-based tests a unique directory of their own.
+
+use crate::keybindings::AllBinds;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// Whether the AllBinds.xml shipped with the app has `action` in `action_map`, for
+/// checking built-in bindings against the actions the game actually has
+pub fn is_bundled_action(action_map: &str, action: &str) -> bool {
+    static BUNDLED: OnceLock<AllBinds> = OnceLock::new();
+    BUNDLED
+        .get_or_init(|| AllBinds::from_xml(include_str!("../../AllBinds.xml")).unwrap())
+        .has_action(action_map, action)
+}
+
+/// An actionmaps.xml with a range of things the game touches: two sticks, a curve, an
+/// exponent and a few rebinds
+pub const SAMPLE_ACTIONMAPS: &str = r#"<ActionMaps>
+ <ActionProfiles version="1" optionsVersion="2" rebindVersion="2" profileName="default">
+  <deviceoptions name=" VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}">
+   <option input="x" deadzone="0.02"/>
+  </deviceoptions>
+  <options type="keyboard" instance="1" Product="Keyboard  {6F1D2B61-D5A0-11CF-BFC7-444553540000}"/>
+  <options type="joystick" instance="1" Product=" VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}">
+   <flight_move_pitch invert="1">
+    <nonlinearity_curve>
+     <point in="0.5" out="0.25"/>
+    </nonlinearity_curve>
+   </flight_move_pitch>
+   <flight_move_yaw exponent="1.5"/>
+  </options>
+  <options type="joystick" instance="2" Product=" Thrustmaster T.16000M    {B10A044F-0000-0000-0000-504944564944}">
+   <flight_throttle_abs invert="0"/>
+  </options>
+  <modifiers />
+  <actionmap name="spaceship_general">
+   <action name="v_toggle_landing_system">
+    <rebind input="js1_button3"/>
+   </action>
+  </actionmap>
+  <actionmap name="spaceship_movement">
+   <action name="v_ifcs_toggle_vector_decoupling">
+    <rebind input="kb1_lalt+c"/>
+   </action>
+  </actionmap>
+ </ActionProfiles>
+</ActionMaps>
+"#;
+
+/// What the game writes after a "reset to defaults"
+pub const WIPED_ACTIONMAPS: &str = r#"<ActionMaps>
+ <ActionProfiles version="1" optionsVersion="2" rebindVersion="2" profileName="default">
+  <options type="keyboard" instance="1" Product="Keyboard  {6F1D2B61-D5A0-11CF-BFC7-444553540000}"/>
+  <options type="joystick" instance="1" Product=" VKB-Sim Gladiator NXT R    {0200231D-0000-0000-0000-504944564944}"/>
+  <options type="joystick" instance="2" Product=" Thrustmaster T.16000M    {B10A044F-0000-0000-0000-504944564944}"/>
+  <modifiers />
+ </ActionProfiles>
+</ActionMaps>
+"#;
+
+/// Drop curve data the way the game does: `<nonlinearity_curve>` children and
+/// `exponent` attributes disappear, everything else stays
+pub fn strip_curves(xml: &str) -> String {
+    let mut result = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(start) = rest.find("<nonlinearity_curve>") {
+        let end = rest[start..]
+            .find("</nonlinearity_curve>")
+            .map(|i| start + i + "</nonlinearity_curve>".len())
+            .expect("unterminated nonlinearity_curve");
+        // Take the indentation and line break with it
+        let line_start = rest[..start].rfind('\n').map(|i| i + 1).unwrap_or(start);
+        let line_end = rest[end..].find('\n').map(|i| end + i + 1).unwrap_or(end);
+        result.push_str(&rest[..line_start]);
+        rest = &rest[line_end..];
+    }
+    result.push_str(rest);
+
+    let mut without_exponents = String::with_capacity(result.len());
+    let mut rest = result.as_str();
+    while let Some(start) = rest.find(" exponent=\"") {
+        let value_start = start + " exponent=\"".len();
+        let end = value_start
+            + rest[value_start..]
+                .find('"')
+                .expect("unterminated exponent");
+        without_exponents.push_str(&rest[..start]);
+        rest = &rest[end + 1..];
+    }
+    without_exponents.push_str(rest);
+    without_exponents
+}
+
+/// Byte ranges of every `<options ...>` block, self-closing or not
+fn options_blocks(xml: &str) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut from = 0;
+    while let Some(i) = xml[from..].find("<options ") {
+        let start = from + i;
+        let tag_end = start + xml[start..].find('>').expect("unterminated options tag");
+        let end = if xml[..tag_end].ends_with('/') {
+            tag_end + 1
+        } else {
+            tag_end
+                + xml[tag_end..]
+                    .find("</options>")
+                    .expect("unterminated options block")
+                + "</options>".len()
+        };
+        blocks.push((start, end));
+        from = end;
+    }
+    blocks
+}
+
+/// List the `<options>` blocks in reverse order, as when Windows enumerates the devices
+/// differently. Instance numbers stay with their blocks.
+pub fn reorder_devices(xml: &str) -> String {
+    let blocks = options_blocks(xml);
+    let texts: Vec<&str> = blocks.iter().map(|&(s, e)| &xml[s..e]).collect();
+
+    let mut result = String::with_capacity(xml.len());
+    let mut last = 0;
+    for (&(start, end), text) in blocks.iter().zip(texts.iter().rev()) {
+        result.push_str(&xml[last..start]);
+        result.push_str(text);
+        last = end;
+    }
+    result.push_str(&xml[last..]);
+    result
+}
+
+static NEXT_TEMP_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A fresh, empty directory under the system temp directory, removed on drop. Each one is
+/// unique, so tests running in parallel in the same process never share a fixture.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(label: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "boxxy-binder-{}-{}-{}",
+            label,
+            std::process::id(),
+            NEXT_TEMP_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        // Left over from an earlier run that died before cleaning up
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// An actionmaps.xml in its own temp directory, removed on drop
+pub struct ScSim {
+    dir: TempDir,
+    path: PathBuf,
+}
+
+impl ScSim {
+    pub fn new(xml: &str) -> Self {
+        let dir = TempDir::new("sim");
+        let path = dir.path().join("actionmaps.xml");
+        std::fs::write(&path, xml).unwrap();
+        ScSim { dir, path }
+    }
+
+    /// The sim's temp directory, for lock files and backup stores next to the file
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn path_str(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+
+    pub fn read(&self) -> String {
+        std::fs::read_to_string(&self.path).unwrap()
+    }
+
+    /// The game saving the file: written to a temp file and renamed over the original
+    fn save(&self, xml: &str) {
+        let temp = self.dir().join("actionmaps.xml.tmp");
+        std::fs::write(&temp, xml).unwrap();
+        std::fs::rename(&temp, &self.path).unwrap();
+    }
+
+    /// The game exiting: it writes the file back without the curve data it doesn't keep
+    pub fn exit_game(&self) {
+        self.save(&strip_curves(&self.read()));
+    }
+
+    /// The game starting with the devices enumerated in a different order
+    pub fn reorder_devices(&self) {
+        self.save(&reorder_devices(&self.read()));
+    }
+
+    /// A patch or "reset to defaults" wiping all customization
+    pub fn wipe(&self) {
+        self.save(WIPED_ACTIONMAPS);
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::controls::{
+        self, ControlOptionSettings, ControlsFile, CurveData, CurveInterpolation, CurvePoint,
+    };
+    use crate::essentials::{self, EssentialBinding, Essentials};
+    use crate::modification_log::{self, Attribution};
+    use crate::watcher::FileWatcher;
+    use crate::{apply_rebase, curve_watchdog, diff};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn curve_profile() -> ControlsFile {
+        let mut profile = ControlsFile::new("Curves".to_string());
+        profile.device_mut("joystick", "1").unwrap().options.insert(
+            "flight_move_pitch".to_string(),
+            ControlOptionSettings {
+                curve_mode: Some("curve".to_string()),
+                curve: Some(CurveData {
+                    points: vec![CurvePoint {
+                        input: 0.5,
+                        output: 0.25,
+                    }],
+                    interpolation: CurveInterpolation::Linear,
+                }),
+                ..Default::default()
+            },
+        );
+        profile
+    }
+
+    #[test]
+    fn test_exit_strips_curves_only() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        sim.exit_game();
+        let after = sim.read();
+
+        assert!(!after.contains("nonlinearity_curve"));
+        assert!(!after.contains("exponent"));
+        let changes = diff::diff_actionmaps(SAMPLE_ACTIONMAPS, &after).unwrap();
+        assert!(changes.binding_changes.is_empty());
+        assert_eq!(changes.option_changes.len(), 2);
+
+        let entry =
+            modification_log::entry_for(sim.path_str(), SAMPLE_ACTIONMAPS, &after, &changes);
+        assert_eq!(entry.attribution, Attribution::Game);
+    }
+
+    #[test]
+    fn test_reordered_devices_are_not_a_change() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        sim.reorder_devices();
+        let after = sim.read();
+
+        assert_ne!(after, SAMPLE_ACTIONMAPS);
+        assert_eq!(controls::parse_actionmaps_options(&after).unwrap().len(), 3);
+        assert!(diff::diff_actionmaps(SAMPLE_ACTIONMAPS, &after)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_curves_reapplied_after_exit() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        sim.exit_game();
+
+        let restored = curve_watchdog::reapply_curves(&sim.read(), &curve_profile(), true, &[])
+            .unwrap()
+            .expect("curves should be re-applied");
+        assert!(restored.contains("<point in=\"0.5\" out=\"0.25\"/>"));
+
+        // Nothing to do once they're back
+        assert!(
+            curve_watchdog::reapply_curves(&restored, &curve_profile(), true, &[])
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_apply_rebases_over_game_exit() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        let base = sim.read();
+        // The game exits between our read and our write
+        sim.exit_game();
+
+        let pending = controls::controls_to_actionmaps(&curve_profile(), true);
+        let result = apply_rebase::write_rebased(sim.path_str(), base, pending, &[]).unwrap();
+        assert_eq!(result.attempts, 2);
+
+        let written = sim.read();
+        assert!(written.contains("<point in=\"0.5\" out=\"0.25\"/>"));
+        // The game's change to another option survives
+        assert!(!written.contains("exponent"));
+    }
+
+    #[test]
+    fn test_essentials_restored_after_wipe() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        let essentials = Essentials {
+            bindings: vec![EssentialBinding {
+                action_map: "spaceship_general".to_string(),
+                action: "v_toggle_landing_system".to_string(),
+                input: "js1_button3".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert!(essentials::check(&sim.read(), &essentials)
+            .unwrap()
+            .is_complete());
+
+        sim.wipe();
+        let (restored, summary) = essentials::restore(&sim.read(), &essentials, &[])
+            .unwrap()
+            .expect("landing gear should be missing");
+        assert_eq!(summary.bindings_restored, 1);
+        assert!(essentials::check(&restored, &essentials)
+            .unwrap()
+            .is_complete());
+    }
+
+    #[test]
+    fn test_watcher_sees_game_exit_but_not_own_write() {
+        let sim = ScSim::new(SAMPLE_ACTIONMAPS);
+        let (tx, rx) = mpsc::channel();
+        let path = sim.path_str().to_string();
+        let _watcher = FileWatcher::start(PathBuf::from(&path), move |xml| {
+            if !modification_log::is_own_write(xml) {
+                let _ = tx.send(xml.to_string());
+            }
+            None
+        })
+        .unwrap();
+
+        let own = SAMPLE_ACTIONMAPS.replace("profileName=\"default\"", "profileName=\"own\"");
+        modification_log::note_own_write(&own);
+        std::fs::write(&path, &own).unwrap();
+        assert!(rx.recv_timeout(Duration::from_secs(2)).is_err());
+
+        sim.exit_game();
+        let seen = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("watcher should report the game's rewrite");
+        assert!(!seen.contains("nonlinearity_curve"));
+    }
+}
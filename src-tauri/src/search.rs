@@ -0,0 +1,321 @@
+//! Fuzzy search over actions and their bindings
+//!
+//! The bindings pages used to filter the whole merged model in JS on every keystroke,
+//! with plain substring matches. Searching here ranks every action against the query
+//! instead: each word of the query has to match one of the action's name, its label
+//! (localized when a global.ini is loaded), its actionmap's label, a bound input or the
+//! name of a bound device, either as a substring or as letters in order ("qtm" finds
+//! "Quantum Travel Mode"). Matches on the action itself rank above matches on its
+//! bindings, and whole-word matches above scattered letters.
+
+use crate::cheat_sheet;
+use crate::device_nicknames::DeviceNicknames;
+use crate::keybindings::{MergedAction, MergedBindings};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Results returned when the caller doesn't ask for a number
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Where a query word matched
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchField {
+    Label,
+    Action,
+    ActionMap,
+    Input,
+    Device,
+}
+
+impl MatchField {
+    /// How much a match here counts, in percent
+    fn weight(self) -> u32 {
+        match self {
+            MatchField::Label => 100,
+            MatchField::Action => 90,
+            MatchField::ActionMap => 60,
+            MatchField::Input => 80,
+            MatchField::Device => 50,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SearchResult {
+    pub action_map: String,
+    pub action: String,
+    pub label: String,
+    pub action_map_label: String,
+    /// Bound inputs, e.g. "js1_button3"
+    pub inputs: Vec<String>,
+    pub score: u32,
+    /// Fields the query words matched, best first
+    pub matched: Vec<MatchField>,
+}
+
+/// What the search needs to name things
+pub struct SearchContext<'a> {
+    /// global.ini keys to text; empty when none is loaded
+    pub localization: &'a HashMap<String, String>,
+    /// Product name of each joystick instance, js1 first
+    pub joysticks: &'a [String],
+    pub nicknames: &'a DeviceNicknames,
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    i == 0
+        || !chars[i - 1].is_alphanumeric()
+        || (chars[i].is_uppercase() && chars[i - 1].is_lowercase())
+}
+
+/// How well `pattern` (lowercase) matches `text`, or None. A substring scores higher
+/// than letters spread out, and more so at the start of a word; the whole text scores
+/// highest.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<u32> {
+    if pattern.is_empty() {
+        return None;
+    }
+    let lower = text.to_lowercase();
+    if lower == pattern {
+        return Some(1000);
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let lower_chars: Vec<char> = lower.chars().collect();
+    if lower_chars.len() != chars.len() {
+        // Lowercasing changed the length; only substring matching is safe
+        return lower.contains(pattern).then_some(400);
+    }
+
+    if let Some(byte_at) = lower.find(pattern) {
+        let at = lower[..byte_at].chars().count();
+        let bonus = if is_word_start(&chars, at) { 200 } else { 0 };
+        return Some(500 + bonus - (at as u32).min(50));
+    }
+
+    // Letters in order. Jumping to word starts usually finds the better match ("qtm" in
+    // "Quantum Travel Mode"), but can run out of text where taking each first letter wouldn't.
+    let pattern: Vec<char> = pattern.chars().collect();
+    [true, false]
+        .into_iter()
+        .filter_map(|word_starts| letters_in_order(&pattern, &chars, &lower_chars, word_starts))
+        .max()
+        // Scattered matches never outrank a substring
+        .map(|score| score.min(399))
+}
+
+fn letters_in_order(
+    pattern: &[char],
+    chars: &[char],
+    lower_chars: &[char],
+    prefer_word_starts: bool,
+) -> Option<u32> {
+    let mut score = 0u32;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for &wanted in pattern {
+        let mut candidates = (next..lower_chars.len()).filter(|&i| lower_chars[i] == wanted);
+        let at = if prefer_word_starts {
+            (next..lower_chars.len())
+                .find(|&i| lower_chars[i] == wanted && is_word_start(chars, i))
+                .or_else(|| candidates.next())?
+        } else {
+            candidates.next()?
+        };
+        score += 10;
+        if is_word_start(chars, at) {
+            score += 20;
+        }
+        if previous == Some(at.wrapping_sub(1)) {
+            score += 15;
+        }
+        previous = Some(at);
+        next = at + 1;
+    }
+    Some(score)
+}
+
+fn joystick_instance(input: &str) -> Option<usize> {
+    let device = input
+        .split('+')
+        .map(str::trim)
+        .find(|part| part.starts_with("js"))?;
+    device[2..].split('_').next()?.parse().ok()
+}
+
+/// Text to search in for one action, with the field each came from
+fn fields(
+    map_name: &str,
+    map_label: &str,
+    action: &MergedAction,
+    label: &str,
+    context: &SearchContext,
+) -> Vec<(MatchField, String)> {
+    let mut fields = vec![
+        (MatchField::Label, label.to_string()),
+        (MatchField::Action, action.name.replace('_', " ")),
+        (MatchField::ActionMap, map_label.to_string()),
+        (MatchField::ActionMap, map_name.replace('_', " ")),
+    ];
+    for binding in action
+        .bindings
+        .iter()
+        .filter(|b| !b.input.trim().is_empty())
+    {
+        fields.push((MatchField::Input, binding.input.clone()));
+        fields.push((MatchField::Input, binding.display_name.clone()));
+        if let Some(product) = joystick_instance(&binding.input)
+            .and_then(|instance| instance.checked_sub(1))
+            .and_then(|index| context.joysticks.get(index))
+        {
+            fields.push((MatchField::Device, product.clone()));
+            if let Some(nickname) = context.nicknames.get(product) {
+                fields.push((MatchField::Device, nickname.to_string()));
+            }
+        }
+    }
+    fields
+}
+
+/// Actions matching every word of `query`, best first
+pub fn search(
+    merged: &MergedBindings,
+    query: &str,
+    limit: usize,
+    context: &SearchContext,
+) -> Vec<SearchResult> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    let label = |ui_label: &str, name: &str| {
+        let ui_label = Some(ui_label).filter(|l| !l.is_empty());
+        cheat_sheet::resolve_label(ui_label, name, context.localization)
+    };
+
+    let mut results = Vec::new();
+    for map in &merged.action_maps {
+        let map_label = label(&map.ui_label, &map.name);
+        for action in &map.actions {
+            let action_label = label(&action.ui_label, &action.name);
+            let fields = fields(&map.name, &map_label, action, &action_label, context);
+
+            let mut score = 0;
+            let mut matched: Vec<(u32, MatchField)> = Vec::new();
+            let all_matched = words.iter().all(|word| {
+                let best = fields
+                    .iter()
+                    .filter_map(|(field, text)| {
+                        Some((fuzzy_score(word, text)? * field.weight() / 100, *field))
+                    })
+                    .max_by_key(|(score, _)| *score);
+                if let Some((word_score, field)) = best {
+                    score += word_score;
+                    matched.push((word_score, field));
+                }
+                best.is_some()
+            });
+            if !all_matched {
+                continue;
+            }
+
+            matched.sort_by(|a, b| b.0.cmp(&a.0));
+            let mut matched: Vec<MatchField> = matched.into_iter().map(|(_, f)| f).collect();
+            matched.dedup();
+            results.push(SearchResult {
+                action_map: map.name.clone(),
+                action: action.name.clone(),
+                label: action_label,
+                action_map_label: map_label.clone(),
+                inputs: action
+                    .bindings
+                    .iter()
+                    .map(|b| b.input.clone())
+                    .filter(|input| !input.trim().is_empty())
+                    .collect(),
+                score,
+                matched,
+            });
+        }
+    }
+
+    results.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.label.cmp(&b.label)));
+    results.truncate(limit);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybindings::{MergedActionMap, MergedBinding};
+
+    fn action(name: &str, ui_label: &str, input: &str) -> MergedAction {
+        MergedAction {
+            name: name.to_string(),
+            ui_label: ui_label.to_string(),
+            ui_description: String::new(),
+            category: String::new(),
+            is_customized: false,
+            on_hold: false,
+            bindings: vec![MergedBinding {
+                input: input.to_string(),
+                display_name: String::new(),
+                input_type: String::new(),
+                is_default: true,
+                multi_tap: None,
+                activation_mode: String::new(),
+                original_default: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_ranked_fuzzy_search() {
+        let merged = MergedBindings {
+            action_maps: vec![MergedActionMap {
+                name: "spaceship_quantum".to_string(),
+                ui_label: "@ui_CGQuantum".to_string(),
+                ui_category: String::new(),
+                actions: vec![
+                    action(
+                        "v_toggle_quantum_mode",
+                        "@ui_CIToggleQuantumMode",
+                        "js2_button3",
+                    ),
+                    action("v_quantum_calibration", "", "kb1_b"),
+                ],
+            }],
+            device_options: Vec::new(),
+        };
+        let localization = HashMap::from([
+            (
+                "ui_CIToggleQuantumMode".to_string(),
+                "Quantum Travel Mode".to_string(),
+            ),
+            ("ui_CGQuantum".to_string(), "Quantum Drive".to_string()),
+        ]);
+        let joysticks = vec!["VKB Gladiator".to_string(), "VPC Throttle".to_string()];
+        let nicknames = DeviceNicknames::default();
+        let context = SearchContext {
+            localization: &localization,
+            joysticks: &joysticks,
+            nicknames: &nicknames,
+        };
+
+        // Letters in order: the label's word starts beat the actionmap name
+        let results = search(&merged, "qtm", 10, &context);
+        assert_eq!(results[0].label, "Quantum Travel Mode");
+        assert_eq!(results[0].matched, vec![MatchField::Label]);
+
+        let results = search(&merged, "quantum", 10, &context);
+        assert_eq!(results.len(), 2);
+
+        let results = search(&merged, "quantum throttle", 10, &context);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].action, "v_toggle_quantum_mode");
+        assert!(results[0].matched.contains(&MatchField::Device));
+
+        assert!(search(&merged, "xyzzy", 10, &context).is_empty());
+    }
+}
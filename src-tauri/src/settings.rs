@@ -0,0 +1,97 @@
+//! Application settings for Boxxy Binder
+//!
+//! Machine-level preferences (things that don't belong in a .sccontrols profile)
+//! are stored as JSON in the app config directory. Missing fields fall back to
+//! their defaults so older settings files keep loading as new options are added.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the settings file inside the app config directory
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// Command line flag passed by the autostart entry so we know we were launched at login
+pub const AUTOSTART_ARG: &str = "--minimized";
+
+/// Persistent application settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Launch Boxxy Binder automatically when the user logs in
+    pub autostart: bool,
+
+    /// Start minimized when launched at login
+    pub start_minimized: bool,
+
+    /// Profile variables bound to devices, e.g. RIGHT_STICK_INSTANCE -> "231d:0200"
+    pub device_variables: HashMap<String, String>,
+
+    /// Where backups of game files go; None uses the default inside the app data directory
+    pub backup_dir: Option<String>,
+
+    /// Disable everything that writes game files or profiles, e.g. while demoing or streaming
+    pub read_only: bool,
+
+    /// The .sccontrols profile pinned to each environment, e.g. "PTU" -> path
+    pub pinned_profiles: HashMap<String, String>,
+
+    /// Option names never written to actionmaps.xml or imported into profiles
+    pub excluded_options: Vec<String>,
+
+    /// Daily check of the environments against their pinned profiles; None when off
+    pub drift_audit: Option<crate::drift_audit::DriftAuditConfig>,
+
+    /// When an apply is large enough to need an explicit confirmation
+    pub apply_confirmation: crate::apply_guard::ConfirmationPolicy,
+
+    /// Local HTTP API for companion tools; None when off
+    pub local_api: Option<crate::local_api::LocalApiConfig>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            autostart: false,
+            start_minimized: true,
+            device_variables: HashMap::new(),
+            backup_dir: None,
+            read_only: false,
+            pinned_profiles: HashMap::new(),
+            excluded_options: Vec::new(),
+            drift_audit: None,
+            apply_confirmation: crate::apply_guard::ConfirmationPolicy::default(),
+            local_api: None,
+        }
+    }
+}
+
+fn settings_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(SETTINGS_FILE_NAME)
+}
+
+/// Load settings from the config directory, returning defaults if no file exists yet
+pub fn load_settings(config_dir: &Path) -> Result<AppSettings, String> {
+    let path = settings_path(config_dir);
+
+    if !path.exists() {
+        return Ok(AppSettings::default());
+    }
+
+    let json =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read settings: {}", e))?;
+
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse settings: {}", e))
+}
+
+/// Save settings to the config directory, creating it if needed
+pub fn save_settings(config_dir: &Path, settings: &AppSettings) -> Result<(), String> {
+    std::fs::create_dir_all(config_dir)
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+
+    let json = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    std::fs::write(settings_path(config_dir), json)
+        .map_err(|e| format!("Failed to write settings: {}", e))
+}
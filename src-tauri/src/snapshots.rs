@@ -0,0 +1,320 @@
+//! Snapshots of actionmaps.xml
+//!
+//! A snapshot is a copy of the game's actionmaps.xml plus a small JSON metadata file.
+//! Users typically take one before and after a play session so they can see what the
+//! game changed, with a short note to remember what they were testing. Every apply also
+//! takes one of the file it's about to change, named after what's being tried ("before
+//! 3.24 curves test") and recording the profile and game build, so any earlier state can
+//! be restored.
+
+use crate::default_profile_cache;
+use crate::diff::{self, ActionmapsDiff};
+use crate::game_process;
+use crate::modification_log;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Why a snapshot was taken
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotKind {
+    PreGame,
+    PostGame,
+    Manual,
+    /// Taken automatically before an apply wrote to the file
+    PreApply,
+    /// The file as it was before a snapshot was restored over it
+    PreRestore,
+}
+
+/// Metadata stored alongside each snapshot
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotMeta {
+    pub id: String,
+    /// ISO timestamp of when the snapshot was taken
+    pub created_at: String,
+    /// The actionmaps.xml the snapshot was taken from
+    pub source_path: String,
+    pub kind: SnapshotKind,
+    /// Short label, e.g. "before 3.24 curves test"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Profile being applied when the snapshot was taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_name: Option<String>,
+    /// Build id of the installation the file belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_build: Option<String>,
+    /// Free-form session note, e.g. "testing new yaw curve"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Comparison between two snapshots, including both notes for context
+#[derive(Debug, Serialize)]
+pub struct SnapshotDiff {
+    pub from: SnapshotMeta,
+    pub to: SnapshotMeta,
+    pub identical: bool,
+    pub diff: ActionmapsDiff,
+}
+
+/// Snapshot ids become file names, so only allow the characters we generate
+fn validate_id(id: &str) -> Result<(), String> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!("Invalid snapshot id: {}", id));
+    }
+    Ok(())
+}
+
+/// The snapshot store inside the app data directory
+pub fn store_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("snapshots")
+}
+
+fn meta_path(store_dir: &Path, id: &str) -> PathBuf {
+    store_dir.join(format!("{}.json", id))
+}
+
+fn xml_path(store_dir: &Path, id: &str) -> PathBuf {
+    store_dir.join(format!("{}.xml", id))
+}
+
+/// Trim a name or note and treat blank ones as none
+fn clean_note(note: Option<String>) -> Option<String> {
+    note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty())
+}
+
+fn write_meta(store_dir: &Path, meta: &SnapshotMeta) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(meta)
+        .map_err(|e| format!("Failed to serialize snapshot metadata: {}", e))?;
+    std::fs::write(meta_path(store_dir, &meta.id), json)
+        .map_err(|e| format!("Failed to write snapshot metadata: {}", e))
+}
+
+/// Copy the given actionmaps.xml into the snapshot store
+pub fn take_snapshot(
+    store_dir: &Path,
+    source_path: &str,
+    kind: SnapshotKind,
+    note: Option<String>,
+) -> Result<SnapshotMeta, String> {
+    store(store_dir, source_path, kind, None, None, note)
+}
+
+/// Snapshot actionmaps.xml before an apply of `profile_name` overwrites it. Without a
+/// name one is made up from the profile.
+pub fn take_before_apply(
+    store_dir: &Path,
+    source_path: &str,
+    name: Option<String>,
+    profile_name: &str,
+) -> Result<SnapshotMeta, String> {
+    let name = clean_note(name).unwrap_or_else(|| format!("Before applying {}", profile_name));
+    store(
+        store_dir,
+        source_path,
+        SnapshotKind::PreApply,
+        Some(name),
+        Some(profile_name.to_string()),
+        None,
+    )
+}
+
+fn store(
+    store_dir: &Path,
+    source_path: &str,
+    kind: SnapshotKind,
+    name: Option<String>,
+    profile_name: Option<String>,
+    note: Option<String>,
+) -> Result<SnapshotMeta, String> {
+    std::fs::create_dir_all(store_dir)
+        .map_err(|e| format!("Failed to create snapshot directory: {}", e))?;
+
+    let now = chrono::Local::now();
+    let stamp = now.format("%Y%m%d_%H%M%S_%3f").to_string();
+    // A restore snapshots the file it replaces straight away, possibly in the same ms
+    let id = (0..)
+        .map(|n| match n {
+            0 => stamp.clone(),
+            n => format!("{}_{}", stamp, n),
+        })
+        .find(|id| !meta_path(store_dir, id).exists())
+        .unwrap();
+
+    std::fs::copy(source_path, xml_path(store_dir, &id))
+        .map_err(|e| format!("Failed to copy actionmaps.xml: {}", e))?;
+
+    let meta = SnapshotMeta {
+        id,
+        created_at: now.to_rfc3339(),
+        source_path: source_path.to_string(),
+        kind,
+        name: clean_note(name),
+        profile_name,
+        game_build: game_process::installation_of(Path::new(source_path))
+            .and_then(|installation| default_profile_cache::read_build_id(&installation)),
+        note: clean_note(note),
+    };
+    write_meta(store_dir, &meta)?;
+
+    Ok(meta)
+}
+
+/// Load the metadata for a single snapshot
+pub fn load_meta(store_dir: &Path, id: &str) -> Result<SnapshotMeta, String> {
+    validate_id(id)?;
+    let json = std::fs::read_to_string(meta_path(store_dir, id))
+        .map_err(|e| format!("Snapshot {} not found: {}", id, e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse snapshot metadata: {}", e))
+}
+
+/// Read the actionmaps.xml content stored in a snapshot
+pub fn load_xml(store_dir: &Path, id: &str) -> Result<String, String> {
+    validate_id(id)?;
+    std::fs::read_to_string(xml_path(store_dir, id))
+        .map_err(|e| format!("Failed to read snapshot {}: {}", id, e))
+}
+
+/// List all snapshots, newest first
+pub fn list_snapshots(store_dir: &Path) -> Result<Vec<SnapshotMeta>, String> {
+    if !store_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = std::fs::read_dir(store_dir)
+        .map_err(|e| format!("Failed to read snapshot directory: {}", e))?;
+
+    let mut snapshots: Vec<SnapshotMeta> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let json = std::fs::read_to_string(entry.path()).ok()?;
+            serde_json::from_str(&json).ok()
+        })
+        .collect();
+
+    // Ids are timestamps, so reverse alphabetical order = newest first
+    snapshots.sort_by(|a, b| b.id.cmp(&a.id));
+
+    Ok(snapshots)
+}
+
+/// Attach (or replace) the session note on an existing snapshot
+pub fn set_note(store_dir: &Path, id: &str, note: Option<String>) -> Result<SnapshotMeta, String> {
+    let mut meta = load_meta(store_dir, id)?;
+    meta.note = clean_note(note);
+    write_meta(store_dir, &meta)?;
+    Ok(meta)
+}
+
+/// Copy a snapshot back over `target_path` (by default the file it was taken from),
+/// snapshotting the file being replaced first so the restore can be undone. Returns the
+/// restored snapshot and the one taken of the replaced file.
+pub fn restore(
+    store_dir: &Path,
+    id: &str,
+    target_path: Option<&str>,
+) -> Result<(SnapshotMeta, Option<SnapshotMeta>), String> {
+    let meta = load_meta(store_dir, id)?;
+    let xml = load_xml(store_dir, id)?;
+    let target = target_path.unwrap_or(&meta.source_path);
+
+    let replaced = if Path::new(target).exists() {
+        let label = meta.name.as_deref().unwrap_or(&meta.id);
+        Some(store(
+            store_dir,
+            target,
+            SnapshotKind::PreRestore,
+            Some(format!("Before restoring {}", label)),
+            None,
+            None,
+        )?)
+    } else {
+        None
+    };
+
+    modification_log::note_own_write(&xml);
+    std::fs::write(target, xml).map_err(|e| format!("Failed to restore {}: {}", target, e))?;
+    Ok((meta, replaced))
+}
+
+/// Remove a snapshot's file and metadata
+pub fn delete(store_dir: &Path, id: &str) -> Result<(), String> {
+    validate_id(id)?;
+    let meta = meta_path(store_dir, id);
+    if !meta.exists() {
+        return Err(format!("Snapshot {} not found", id));
+    }
+    let xml = xml_path(store_dir, id);
+    if xml.exists() {
+        std::fs::remove_file(&xml)
+            .map_err(|e| format!("Failed to delete snapshot {}: {}", id, e))?;
+    }
+    std::fs::remove_file(&meta).map_err(|e| format!("Failed to delete snapshot {}: {}", id, e))
+}
+
+/// Compare two snapshots
+pub fn diff_snapshots(
+    store_dir: &Path,
+    from_id: &str,
+    to_id: &str,
+) -> Result<SnapshotDiff, String> {
+    let from = load_meta(store_dir, from_id)?;
+    let to = load_meta(store_dir, to_id)?;
+
+    let diff = diff::diff_actionmaps(&load_xml(store_dir, from_id)?, &load_xml(store_dir, to_id)?)?;
+
+    Ok(SnapshotDiff {
+        from,
+        to,
+        identical: diff.is_empty(),
+        diff,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::TempDir;
+
+    #[test]
+    fn test_restore_and_delete() {
+        let temp = TempDir::new("snapshots");
+        let dir = temp.path();
+        let store_dir = dir.join("snapshots");
+        let actionmaps = dir.join("actionmaps.xml");
+        let actionmaps_path = actionmaps.to_string_lossy().to_string();
+        std::fs::write(&actionmaps, "<ActionMaps version=\"1\"/>").unwrap();
+
+        let before = take_before_apply(&store_dir, &actionmaps_path, None, "Global").unwrap();
+        assert_eq!(before.kind, SnapshotKind::PreApply);
+        assert_eq!(before.name.as_deref(), Some("Before applying Global"));
+        assert_eq!(before.profile_name.as_deref(), Some("Global"));
+
+        std::fs::write(&actionmaps, "<ActionMaps version=\"2\"/>").unwrap();
+        let (restored, replaced) = restore(&store_dir, &before.id, None).unwrap();
+        assert_eq!(restored.id, before.id);
+        assert_eq!(
+            std::fs::read_to_string(&actionmaps).unwrap(),
+            "<ActionMaps version=\"1\"/>"
+        );
+        let replaced = replaced.unwrap();
+        assert_eq!(replaced.kind, SnapshotKind::PreRestore);
+        assert_eq!(
+            load_xml(&store_dir, &replaced.id).unwrap(),
+            "<ActionMaps version=\"2\"/>"
+        );
+
+        delete(&store_dir, &before.id).unwrap();
+        let remaining = list_snapshots(&store_dir).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, replaced.id);
+        assert!(delete(&store_dir, &before.id).is_err());
+    }
+}
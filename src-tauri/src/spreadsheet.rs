@@ -0,0 +1,559 @@
+//! CSV export and import of bindings and device options
+//!
+//! Some squadrons curate their layouts in a spreadsheet. Bindings and options go to two
+//! separate tables, since their columns have nothing in common. Columns are found by
+//! their header, so they can be reordered and note columns added without breaking the
+//! import. Every row of an import is checked against the installed defaultProfile first:
+//! if any row is wrong nothing changes, and the problems come back with line numbers.
+
+use crate::controls::{
+    ControlOptionSettings, ControlsFile, CurveData, CurveInterpolation, CurvePoint,
+    DeviceInstanceSettings, EXPONENT_RANGE, UNIT_RANGE,
+};
+use crate::identifier_check::{self, DefaultInputs};
+use crate::keybindings::{Action, ActionMap, ActionMaps, Rebind};
+use crate::sc_migration::{IdentifierKind, KnownIdentifiers};
+use serde::Serialize;
+use std::collections::HashMap;
+
+const BINDING_COLUMNS: [&str; 5] = [
+    "actionmap",
+    "action",
+    "input",
+    "multi_tap",
+    "activation_mode",
+];
+
+const OPTION_COLUMNS: [&str; 11] = [
+    "device_type",
+    "instance",
+    "option",
+    "invert",
+    "deadzone",
+    "saturation",
+    "sensitivity",
+    "smoothing",
+    "curve_mode",
+    "exponent",
+    "curve",
+];
+
+/// A row that can't be imported
+#[derive(Debug, Serialize, Clone)]
+pub struct RowProblem {
+    /// Line in the file the row starts on, counting the header as line 1
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct CsvImportReport {
+    pub rows: usize,
+    /// Actions or options updated; 0 when there were problems
+    pub imported: usize,
+    pub problems: Vec<RowProblem>,
+}
+
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) || field.starts_with(' ') || field.ends_with(' ') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn push_row(csv: &mut String, fields: &[String]) {
+    let escaped: Vec<String> = fields.iter().map(|f| escape_field(f)).collect();
+    csv.push_str(&escaped.join(","));
+    csv.push_str("\r\n");
+}
+
+/// Split CSV text into rows of fields, each with the line it starts on. Quoted fields may
+/// hold commas, doubled quotes and line breaks. Blank lines are skipped.
+fn parse_csv(text: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                if fields.iter().any(|f| !f.is_empty()) {
+                    rows.push((row_line, std::mem::take(&mut fields)));
+                }
+                fields.clear();
+                line += 1;
+                row_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(format!(
+            "Unterminated quoted field starting on line {}",
+            row_line
+        ));
+    }
+    fields.push(field);
+    if fields.iter().any(|f| !f.is_empty()) {
+        rows.push((row_line, fields));
+    }
+    Ok(rows)
+}
+
+/// Rows of a CSV file with columns looked up by header name
+struct Table {
+    columns: HashMap<String, usize>,
+    rows: Vec<(usize, Vec<String>)>,
+}
+
+impl Table {
+    fn parse(text: &str, required: &[&str]) -> Result<Self, String> {
+        let mut rows = parse_csv(text)?.into_iter();
+        let (_, header) = rows.next().ok_or("The CSV file is empty")?;
+        let columns: HashMap<String, usize> = header
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.trim().to_lowercase(), i))
+            .collect();
+        let missing: Vec<&str> = required
+            .iter()
+            .filter(|c| !columns.contains_key(**c))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("Missing column(s): {}", missing.join(", ")));
+        }
+        Ok(Table {
+            columns,
+            rows: rows.collect(),
+        })
+    }
+
+    /// A cell, or "" when the row is short or the column is absent
+    fn raw<'a>(&self, row: &'a [String], column: &str) -> &'a str {
+        self.columns
+            .get(column)
+            .and_then(|&i| row.get(i))
+            .map_or("", String::as_str)
+    }
+
+    fn get<'a>(&self, row: &'a [String], column: &str) -> &'a str {
+        self.raw(row, column).trim()
+    }
+}
+
+/// The loaded bindings, one row per binding
+pub fn bindings_to_csv(bindings: &ActionMaps) -> String {
+    let mut csv = String::new();
+    push_row(&mut csv, &BINDING_COLUMNS.map(str::to_string));
+    for action_map in &bindings.action_maps {
+        for action in &action_map.actions {
+            for rebind in &action.rebinds {
+                push_row(
+                    &mut csv,
+                    &[
+                        action_map.name.clone(),
+                        action.name.clone(),
+                        rebind.input.clone(),
+                        rebind.multi_tap.map(|n| n.to_string()).unwrap_or_default(),
+                        rebind.activation_mode.clone(),
+                    ],
+                );
+            }
+        }
+    }
+    csv
+}
+
+/// Replace the bindings of every action listed in `text` with its rows. Actions not in
+/// the file keep their bindings.
+pub fn import_bindings_csv(
+    text: &str,
+    bindings: &mut ActionMaps,
+    known: &KnownIdentifiers,
+    defaults: &DefaultInputs,
+) -> Result<CsvImportReport, String> {
+    let table = Table::parse(text, &BINDING_COLUMNS[..3])?;
+    let mut report = CsvImportReport {
+        rows: table.rows.len(),
+        ..Default::default()
+    };
+    // (actionmap, action) in the order first seen, with their rebinds
+    let mut actions: Vec<(String, Action)> = Vec::new();
+
+    for (line, row) in &table.rows {
+        let mut problem = |message: String| {
+            report.problems.push(RowProblem {
+                line: *line,
+                message,
+            })
+        };
+        let action_map = table.get(row, "actionmap");
+        let action_name = table.get(row, "action");
+        if action_map.is_empty() || action_name.is_empty() {
+            problem("actionmap and action are required".to_string());
+            continue;
+        }
+        let multi_tap = match table.get(row, "multi_tap") {
+            "" => None,
+            n => match n.parse::<u32>() {
+                Ok(n) if n >= 2 => Some(n),
+                _ => {
+                    problem(format!("multi_tap must be 2 or more, not '{}'", n));
+                    continue;
+                }
+            },
+        };
+        // Not trimmed: a cleared binding is the device prefix and a space, e.g. "js1_ "
+        let rebind = Rebind {
+            input: table.raw(row, "input").trim_start().to_string(),
+            multi_tap,
+            activation_mode: table.get(row, "activation_mode").to_string(),
+        };
+        let single = Action {
+            name: action_name.to_string(),
+            rebinds: vec![rebind.clone()],
+        };
+        let warnings = identifier_check::check_action(action_map, &single, known, defaults);
+        if !warnings.is_empty() {
+            for warning in warnings {
+                problem(format!("{}: {}", warning.identifier, warning.message));
+            }
+            continue;
+        }
+
+        match actions
+            .iter_mut()
+            .find(|(map, action)| map == action_map && action.name == action_name)
+        {
+            Some((_, action)) => action.rebinds.push(rebind),
+            None => actions.push((action_map.to_string(), single)),
+        }
+    }
+
+    if !report.problems.is_empty() {
+        return Ok(report);
+    }
+    report.imported = actions.len();
+    for (map_name, action) in actions {
+        let action_map = match bindings.action_maps.iter().position(|m| m.name == map_name) {
+            Some(i) => &mut bindings.action_maps[i],
+            None => {
+                bindings.action_maps.push(ActionMap {
+                    name: map_name,
+                    actions: Vec::new(),
+                });
+                bindings.action_maps.last_mut().unwrap()
+            }
+        };
+        match action_map
+            .actions
+            .iter_mut()
+            .find(|a| a.name == action.name)
+        {
+            Some(existing) => existing.rebinds = action.rebinds,
+            None => action_map.actions.push(action),
+        }
+    }
+    Ok(report)
+}
+
+/// Device type, instance and settings of every device in a profile, in a stable order
+fn devices(controls: &ControlsFile) -> Vec<(&'static str, String, &DeviceInstanceSettings)> {
+    let mut devices: Vec<(&'static str, String, &DeviceInstanceSettings)> = [
+        ("keyboard", controls.devices.keyboard.as_ref()),
+        ("mouse", controls.devices.mouse.as_ref()),
+        ("gamepad", controls.devices.gamepad.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(device_type, device)| Some((device_type, "1".to_string(), device?)))
+    .collect();
+    let mut joysticks: Vec<_> = controls.devices.joystick.iter().flatten().collect();
+    joysticks.sort_by(|a, b| a.0.cmp(b.0));
+    devices.extend(
+        joysticks
+            .into_iter()
+            .map(|(instance, device)| ("joystick", instance.clone(), device)),
+    );
+    devices
+}
+
+fn number(value: Option<f64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// "in:out;in:out"
+fn format_curve(curve: &CurveData) -> String {
+    curve
+        .points
+        .iter()
+        .map(|p| format!("{}:{}", p.input, p.output))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// A profile's device options, one row per option
+pub fn options_to_csv(controls: &ControlsFile) -> String {
+    let mut csv = String::new();
+    push_row(&mut csv, &OPTION_COLUMNS.map(str::to_string));
+    for (device_type, instance, device) in devices(controls) {
+        let mut names: Vec<&String> = device.options.keys().collect();
+        names.sort();
+        for name in names {
+            let option = &device.options[name];
+            push_row(
+                &mut csv,
+                &[
+                    device_type.to_string(),
+                    instance.clone(),
+                    name.clone(),
+                    option.invert.map(|i| i.to_string()).unwrap_or_default(),
+                    number(option.deadzone),
+                    number(option.saturation),
+                    number(option.sensitivity),
+                    number(option.smoothing),
+                    option.curve_mode.clone().unwrap_or_default(),
+                    number(option.exponent),
+                    option.curve.as_ref().map(format_curve).unwrap_or_default(),
+                ],
+            );
+        }
+    }
+    csv
+}
+
+fn parse_bool(value: &str) -> Result<Option<bool>, String> {
+    match value.to_lowercase().as_str() {
+        "" => Ok(None),
+        "true" | "1" | "yes" => Ok(Some(true)),
+        "false" | "0" | "no" => Ok(Some(false)),
+        _ => Err(format!("'{}' is not true or false", value)),
+    }
+}
+
+fn parse_number(column: &str, value: &str, (min, max): (f64, f64)) -> Result<Option<f64>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match value.parse::<f64>() {
+        Ok(n) if (min..=max).contains(&n) => Ok(Some(n)),
+        Ok(n) => Err(format!("{} {} is outside {} to {}", column, n, min, max)),
+        Err(_) => Err(format!("{} '{}' is not a number", column, value)),
+    }
+}
+
+fn parse_curve(value: &str) -> Result<Option<CurveData>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let points = value
+        .split(';')
+        .map(|point| {
+            let (input, output) = point
+                .split_once(':')
+                .ok_or_else(|| format!("curve point '{}' is not in:out", point.trim()))?;
+            Ok(CurvePoint {
+                input: parse_number("curve in", input.trim(), UNIT_RANGE)?.unwrap_or(0.0),
+                output: parse_number("curve out", output.trim(), UNIT_RANGE)?.unwrap_or(0.0),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Some(CurveData {
+        points,
+        interpolation: CurveInterpolation::Linear,
+    }))
+}
+
+/// The settings of one options row
+fn option_settings(table: &Table, row: &[String]) -> Result<ControlOptionSettings, String> {
+    let curve_mode = match table.get(row, "curve_mode") {
+        "" => None,
+        mode @ ("exponent" | "curve") => Some(mode.to_string()),
+        mode => {
+            return Err(format!(
+                "curve_mode must be exponent or curve, not '{}'",
+                mode
+            ))
+        }
+    };
+    Ok(ControlOptionSettings {
+        invert: parse_bool(table.get(row, "invert"))?,
+        deadzone: parse_number("deadzone", table.get(row, "deadzone"), UNIT_RANGE)?,
+        saturation: parse_number("saturation", table.get(row, "saturation"), UNIT_RANGE)?,
+        sensitivity: parse_number(
+            "sensitivity",
+            table.get(row, "sensitivity"),
+            (0.0, f64::MAX),
+        )?,
+        smoothing: parse_number("smoothing", table.get(row, "smoothing"), UNIT_RANGE)?,
+        curve_mode,
+        exponent: parse_number("exponent", table.get(row, "exponent"), EXPONENT_RANGE)?,
+        curve: parse_curve(table.get(row, "curve"))?,
+        ..Default::default()
+    })
+}
+
+/// Set every option listed in `text` to its row; an empty cell leaves that setting unset.
+/// Options not in the file, and settings the table has no column for, are kept.
+pub fn import_options_csv(
+    text: &str,
+    controls: &mut ControlsFile,
+    known: &KnownIdentifiers,
+) -> Result<CsvImportReport, String> {
+    let table = Table::parse(text, &OPTION_COLUMNS[..3])?;
+    let mut report = CsvImportReport {
+        rows: table.rows.len(),
+        ..Default::default()
+    };
+    let mut updates: Vec<(String, String, String, ControlOptionSettings)> = Vec::new();
+
+    for (line, row) in &table.rows {
+        let device_type = table.get(row, "device_type").to_lowercase();
+        let instance = match table.get(row, "instance") {
+            "" => "1",
+            instance => instance,
+        };
+        let name = table.get(row, "option");
+        let checked =
+            if !["keyboard", "mouse", "gamepad", "joystick"].contains(&device_type.as_str()) {
+                Err(format!("Unknown device type '{}'", device_type))
+            } else if device_type == "joystick" && !instance.parse::<u32>().is_ok_and(|n| n > 0) {
+                Err(format!(
+                    "Joystick instance '{}' is not a number from 1",
+                    instance
+                ))
+            } else if !known.knows(IdentifierKind::Option, name) {
+                Err(format!("The installed game has no option '{}'", name))
+            } else {
+                option_settings(&table, row)
+            };
+        match checked {
+            Ok(settings) => updates.push((
+                device_type,
+                instance.to_string(),
+                name.to_string(),
+                settings,
+            )),
+            Err(message) => report.problems.push(RowProblem {
+                line: *line,
+                message,
+            }),
+        }
+    }
+
+    if !report.problems.is_empty() {
+        return Ok(report);
+    }
+    report.imported = updates.len();
+    for (device_type, instance, name, settings) in updates {
+        let device = controls.device_mut(&device_type, &instance)?;
+        let option = device.options.entry(name).or_default();
+        *option = ControlOptionSettings {
+            gamepad_attributes: std::mem::take(&mut option.gamepad_attributes),
+            extra: std::mem::take(&mut option.extra),
+            ..settings
+        };
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybindings::AllBinds;
+    use crate::option_catalog::parse_option_catalog;
+
+    const DEFAULT_PROFILE: &str = r#"<profile version="1">
+ <optiontree type="joystick" instances="8" name="root">
+  <optiongroup name="flight_move_pitch"/>
+  <optiongroup name="flight_move_yaw"/>
+ </optiontree>
+ <actionmap name="spaceship_general" version="2" UILabel="@ui_CGSpaceFlightCockpit">
+  <action name="v_eject" activationMode="press" keyboard="ralt+y"/>
+  <action name="v_exit" activationMode="press" keyboard="y"/>
+ </actionmap>
+</profile>"#;
+
+    #[test]
+    fn test_csv_round_trip_and_validation() {
+        let all_binds = AllBinds::from_xml(DEFAULT_PROFILE).unwrap();
+        let known =
+            KnownIdentifiers::new(&all_binds, &parse_option_catalog(DEFAULT_PROFILE).unwrap());
+        let defaults = DefaultInputs::new(&all_binds);
+
+        let mut controls = ControlsFile::new("Sheet".to_string());
+        let csv = "Note,device_type,instance,option,invert,deadzone,curve\r\n\
+                   \"pitch, softer\",joystick,1,flight_move_pitch,true,0.05,\"0.5:0.25;0.8:0.6\"\r\n";
+        let report = import_options_csv(csv, &mut controls, &known).unwrap();
+        assert_eq!(report.imported, 1);
+        let pitch = &controls.device("joystick", "1").unwrap().options["flight_move_pitch"];
+        assert_eq!(pitch.invert, Some(true));
+        assert_eq!(pitch.curve.as_ref().unwrap().points.len(), 2);
+
+        let exported = options_to_csv(&controls);
+        let mut reimported = ControlsFile::new("Again".to_string());
+        import_options_csv(&exported, &mut reimported, &known).unwrap();
+        assert_eq!(options_to_csv(&reimported), exported);
+
+        // One bad row: nothing is imported
+        let csv = "device_type,instance,option,deadzone\n\
+                   joystick,1,flight_move_yaw,0.1\n\
+                   joystick,1,flight_move_pitchh,0.1\n\
+                   joystick,2,flight_move_yaw,1.5\n";
+        let mut untouched = ControlsFile::new("Untouched".to_string());
+        let report = import_options_csv(csv, &mut untouched, &known).unwrap();
+        assert_eq!(report.imported, 0);
+        let lines: Vec<usize> = report.problems.iter().map(|p| p.line).collect();
+        assert_eq!(lines, vec![3, 4]);
+        assert!(untouched.devices.joystick.is_none());
+
+        let mut bindings = ActionMaps::from_xml(
+            r#"<ActionMaps>
+ <actionmap name="spaceship_general">
+  <action name="v_exit">
+   <rebind input="js1_button3"/>
+  </action>
+ </actionmap>
+</ActionMaps>"#,
+        )
+        .unwrap();
+        let csv = "actionmap,action,input,multi_tap\n\
+                   spaceship_general,v_eject,js1_button1,\n\
+                   spaceship_general,v_eject,js2_button1,2\n";
+        let report = import_bindings_csv(csv, &mut bindings, &known, &defaults).unwrap();
+        assert_eq!(report.imported, 1);
+        let actions = &bindings.action_maps[0].actions;
+        assert_eq!(actions[0].rebinds[0].input, "js1_button3");
+        assert_eq!(actions[1].rebinds[1].multi_tap, Some(2));
+        assert!(bindings_to_csv(&bindings).contains("spaceship_general,v_eject,js2_button1,2,\r\n"));
+
+        let csv = "actionmap,action,input\nspaceship_general,v_ejcet,js1_button1\n";
+        let report = import_bindings_csv(csv, &mut bindings, &known, &defaults).unwrap();
+        assert_eq!(report.problems[0].line, 2);
+    }
+}
@@ -0,0 +1,203 @@
+//! Apply-time substitution variables
+//!
+//! Shared profiles can use placeholders like `js${RIGHT_STICK_INSTANCE}_button3` instead of
+//! hard-coded instance numbers. Variables are resolved when the profile is applied, from the
+//! user's device variable registry (variable name -> device uuid) and the current device order.
+
+use crate::directinput::JoystickInfo;
+use crate::product_names;
+use std::collections::HashMap;
+
+/// Every `${ … }` placeholder in the text as (byte range of the whole placeholder, trimmed
+/// name), in order. Placeholders with an empty name are skipped.
+fn scan(text: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = text[offset..].find("${") {
+        let open = offset + start;
+        let Some(end) = text[open + 2..].find('}') else {
+            break;
+        };
+        let close = open + 2 + end;
+        let name = text[open + 2..close].trim();
+        if !name.is_empty() {
+            spans.push((open..close + 1, name));
+        }
+        offset = close + 1;
+    }
+
+    spans
+}
+
+/// Names of all `${NAME}` variables in the text, in order of first appearance
+pub fn find_variables(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, name) in scan(text) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Replace every `${NAME}` with its value. Fails listing all unresolved variables,
+/// so a profile is never applied half-substituted.
+pub fn substitute(text: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    let spans = scan(text);
+    if spans.is_empty() {
+        return Ok(text.to_string());
+    }
+
+    let missing: Vec<String> = find_variables(text)
+        .into_iter()
+        .filter(|n| !values.contains_key(n))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Unresolved profile variables: {}. Assign them to a device before applying.",
+            missing
+                .iter()
+                .map(|n| format!("${{{}}}", n))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    // Replace exactly the spans the scan matched, so any whitespace inside the braces
+    // (e.g. `${ RIGHT_STICK_INSTANCE }`) is handled the same way find_variables sees it
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, name) in spans {
+        result.push_str(&text[last..range.start]);
+        result.push_str(&values[name]);
+        last = range.end;
+    }
+    result.push_str(&text[last..]);
+
+    Ok(result)
+}
+
+/// Resolve device variables to SC joystick instance numbers.
+///
+/// `assignments` maps a variable name to a device uuid ("vid:pid") or product name.
+/// SC numbers joysticks in detection order, skipping gamepads.
+pub fn resolve_device_variables(
+    assignments: &HashMap<String, String>,
+    devices: &[JoystickInfo],
+) -> HashMap<String, String> {
+    let joysticks: Vec<&JoystickInfo> = devices
+        .iter()
+        .filter(|d| d.device_type == "Joystick")
+        .collect();
+
+    assignments
+        .iter()
+        .filter_map(|(name, device)| {
+            let position = joysticks.iter().position(|js| {
+                js.uuid
+                    .as_deref()
+                    .is_some_and(|uuid| uuid.eq_ignore_ascii_case(device))
+                    || js
+                        .product_name
+                        .as_deref()
+                        .is_some_and(|p| product_names::same_name(p, device))
+                    || product_names::same_name(&js.name, device)
+            })?;
+            Some((name.clone(), (position + 1).to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn device(name: &str, uuid: Option<&str>, device_type: &str) -> JoystickInfo {
+        JoystickInfo {
+            id: 0,
+            name: name.to_string(),
+            product_name: Some(name.to_string()),
+            is_connected: true,
+            button_count: 32,
+            axis_count: 6,
+            hat_count: 1,
+            device_type: device_type.to_string(),
+            uuid: uuid.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_find_variables_in_order_without_duplicates() {
+        let text = "js${RIGHT}_button1 js${ LEFT }_x js${RIGHT}_button2 ${} ${unterminated";
+        assert_eq!(find_variables(text), vec!["RIGHT", "LEFT"]);
+        assert!(find_variables("js1_button3").is_empty());
+    }
+
+    #[test]
+    fn test_substitute_replaces_all_occurrences() {
+        let text = r#"{"input":"js${RIGHT}_button3","other":"js${LEFT}_x","again":"js${RIGHT}_y"}"#;
+        let result = substitute(text, &values(&[("RIGHT", "2"), ("LEFT", "1")])).unwrap();
+        assert_eq!(
+            result,
+            r#"{"input":"js2_button3","other":"js1_x","again":"js2_y"}"#
+        );
+    }
+
+    #[test]
+    fn test_substitute_handles_any_whitespace_inside_braces() {
+        let text = "js${RIGHT }_a js${  RIGHT}_b js${ RIGHT }_c js${\tRIGHT\t}_d";
+        let result = substitute(text, &values(&[("RIGHT", "2")])).unwrap();
+        assert_eq!(result, "js2_a js2_b js2_c js2_d");
+        assert!(find_variables(&result).is_empty());
+    }
+
+    #[test]
+    fn test_substitute_lists_every_missing_variable() {
+        let err = substitute(
+            "js${RIGHT}_x js${LEFT}_y js${ THROTTLE }_z",
+            &values(&[("LEFT", "1")]),
+        )
+        .unwrap_err();
+        assert!(err.contains("${RIGHT}"));
+        assert!(err.contains("${THROTTLE}"));
+        assert!(!err.contains("${LEFT}"));
+    }
+
+    #[test]
+    fn test_substitute_without_variables_is_unchanged() {
+        let text = "js1_button3 $notavariable {braces}";
+        assert_eq!(substitute(text, &HashMap::new()).unwrap(), text);
+    }
+
+    #[test]
+    fn test_resolve_device_variables_skips_gamepads() {
+        let devices = vec![
+            device("Xbox Controller", None, "Gamepad"),
+            device("VKB Gladiator EVO L", Some("231d:0200"), "Joystick"),
+            device("Virpil Throttle", Some("3344:8194"), "Joystick"),
+            device("VKB Gladiator EVO R", Some("231d:0201"), "Joystick"),
+        ];
+        let assignments = values(&[
+            ("LEFT_STICK", "231D:0200"),
+            ("RIGHT_STICK", "VKB Gladiator EVO R"),
+            ("THROTTLE", "3344:8194"),
+            ("PAD", "Xbox Controller"),
+            ("GONE", "dead:beef"),
+        ]);
+
+        let resolved = resolve_device_variables(&assignments, &devices);
+        assert_eq!(resolved.get("LEFT_STICK").map(String::as_str), Some("1"));
+        assert_eq!(resolved.get("THROTTLE").map(String::as_str), Some("2"));
+        assert_eq!(resolved.get("RIGHT_STICK").map(String::as_str), Some("3"));
+        assert!(!resolved.contains_key("PAD"));
+        assert!(!resolved.contains_key("GONE"));
+    }
+}
@@ -0,0 +1,189 @@
+//! Flip one device's vertical axes everywhere at once
+//!
+//! Players who fly "inverted" want pitch reversed in the cockpit, in turrets, in ground
+//! vehicles and for the on-foot view alike, which is half a dozen options spread over
+//! the options tree. This flips every pitch option of one device in one go, on the
+//! profile in memory so the caller writes it once.
+//!
+//! Two kinds of option are treated specially:
+//! - locked ones aren't touched: options whose inversion SC takes from a cvar (e.g.
+//!   cl_invertController) and options marked as essentials, which would be put back;
+//! - linked ones, the pitch options of the same context (a turret's aim, VJoy and
+//!   relative modes), end up agreeing: the group takes the flipped state of its first
+//!   explicitly set member, so a flip can't leave one mode upright and another not.
+
+use crate::controls::{ControlsFile, DeviceInstanceSettings, OptionContext};
+use crate::option_catalog::{OptionCatalog, OptionCatalogEntry};
+use serde::Serialize;
+
+/// Axis words treated as vertical
+const VERTICAL_AXES: [&str; 1] = ["pitch"];
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FlippedOption {
+    pub name: String,
+    pub context: OptionContext,
+    pub inverted: bool,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct LockedOption {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct FlipReport {
+    pub flipped: Vec<FlippedOption>,
+    pub locked: Vec<LockedOption>,
+}
+
+fn is_vertical(name: &str) -> bool {
+    name.split('_').any(|word| {
+        VERTICAL_AXES
+            .iter()
+            .any(|axis| axis.eq_ignore_ascii_case(word))
+    })
+}
+
+/// The inversion the profile sets explicitly, if any
+fn current_invert(device: &DeviceInstanceSettings, entry: &OptionCatalogEntry) -> Option<bool> {
+    device
+        .options
+        .get(&entry.name)
+        .and_then(|settings| settings.invert)
+}
+
+/// Flip the vertical axes of one device. `essentials` are option names of this device
+/// pinned as essentials.
+pub fn flip_vertical_axes(
+    controls: &mut ControlsFile,
+    catalog: &OptionCatalog,
+    device_type: &str,
+    instance: &str,
+    essentials: &[String],
+) -> Result<FlipReport, String> {
+    let mut report = FlipReport::default();
+
+    // Leaf pitch options of this device type, grouped by context in catalog order
+    let mut groups: Vec<(OptionContext, Vec<&OptionCatalogEntry>)> = Vec::new();
+    for entry in catalog.options.iter().filter(|entry| {
+        entry.device_type == device_type
+            && entry.show_invert
+            && is_vertical(&entry.name)
+            && !catalog.options.iter().any(|child| {
+                child.device_type == device_type && child.parent.as_ref() == Some(&entry.name)
+            })
+    }) {
+        if let Some(cvar) = &entry.invert_cvar {
+            report.locked.push(LockedOption {
+                name: entry.name.clone(),
+                reason: format!("Inverted by the {} setting", cvar),
+            });
+            continue;
+        }
+        if essentials.contains(&entry.name) {
+            report.locked.push(LockedOption {
+                name: entry.name.clone(),
+                reason: "Marked as essential".to_string(),
+            });
+            continue;
+        }
+        match groups
+            .iter_mut()
+            .find(|(context, _)| *context == entry.context)
+        {
+            Some((_, members)) => members.push(entry),
+            None => groups.push((entry.context, vec![entry])),
+        }
+    }
+
+    let device = controls.device_mut(device_type, instance)?;
+    for (_, members) in groups {
+        let leader = members
+            .iter()
+            .find_map(|entry| current_invert(device, entry))
+            .unwrap_or_else(|| members[0].default_invert.unwrap_or(false));
+        let inverted = !leader;
+
+        for entry in members {
+            let was =
+                current_invert(device, entry).unwrap_or(entry.default_invert.unwrap_or(false));
+            if was != inverted {
+                device.options.entry(entry.name.clone()).or_default().invert = Some(inverted);
+                report.flipped.push(FlippedOption {
+                    name: entry.name.clone(),
+                    context: entry.context,
+                    inverted,
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option_catalog::parse_option_catalog;
+
+    #[test]
+    fn test_flip_vertical_axes() {
+        let catalog = parse_option_catalog(
+            r#"<profile>
+ <optiontree type="joystick" instances="8" name="root" UIShowInvert="1">
+  <optiongroup name="flight_move_pitch"/>
+  <optiongroup name="flight_move_yaw"/>
+  <optiongroup name="flight_view_pitch" invert_cvar="cl_invertController"/>
+  <optiongroup name="turret_vj_mode">
+   <optiongroup name="turret_vj_mode_pitch"/>
+   <optiongroup name="turret_vj_mode_yaw"/>
+  </optiongroup>
+  <optiongroup name="turret_relative_mode">
+   <optiongroup name="turret_relative_mode_pitch"/>
+  </optiongroup>
+  <optiongroup name="fps_view_pitch"/>
+  <optiongroup name="mgv_move_pitch"/>
+ </optiontree>
+</profile>"#,
+        )
+        .unwrap();
+
+        let mut controls = ControlsFile::new("Test".to_string());
+        let stick = controls.device_mut("joystick", "1").unwrap();
+        stick
+            .options
+            .entry("flight_move_pitch".to_string())
+            .or_default()
+            .invert = Some(true);
+        stick
+            .options
+            .entry("turret_relative_mode_pitch".to_string())
+            .or_default()
+            .invert = Some(true);
+
+        let report = flip_vertical_axes(
+            &mut controls,
+            &catalog,
+            "joystick",
+            "1",
+            &["mgv_move_pitch".to_string()],
+        )
+        .unwrap();
+
+        let locked: Vec<&str> = report.locked.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(locked, vec!["flight_view_pitch", "mgv_move_pitch"]);
+
+        let stick = controls.device("joystick", "1").unwrap();
+        let invert = |name: &str| stick.options.get(name).and_then(|s| s.invert);
+        assert_eq!(invert("flight_move_pitch"), Some(false));
+        assert_eq!(invert("fps_view_pitch"), Some(true));
+        assert_eq!(invert("flight_move_yaw"), None);
+        assert_eq!(invert("mgv_move_pitch"), None);
+        // The turret's relative mode was inverted, its VJoy mode wasn't: both end up upright
+        assert_eq!(invert("turret_relative_mode_pitch"), Some(false));
+        assert_eq!(invert("turret_vj_mode_pitch"), None);
+        assert_eq!(report.flipped.len(), 3);
+    }
+}
@@ -0,0 +1,318 @@
+//! vJoy driver integration
+//!
+//! Loads vJoyInterface.dll at runtime (so the app works without vJoy installed) and reports
+//! which virtual devices exist and how they are configured. Used to warn when a profile
+//! binds to a vJoy device that isn't set up on this machine, and to feed a device from
+//! the vJoy feeder (see vjoy_feeder).
+
+use crate::product_names;
+use serde::{Deserialize, Serialize};
+
+/// One configured vJoy device
+#[derive(Debug, Serialize, Clone)]
+pub struct VJoyDevice {
+    pub id: u32,
+    /// "own", "free", "busy" or "unknown" (as reported by GetVJDStatus)
+    pub status: String,
+    pub axes: Vec<String>,
+    pub button_count: i32,
+    pub pov_count: i32,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct VJoyStatus {
+    /// vJoyInterface.dll could be loaded
+    pub installed: bool,
+    /// The driver is enabled
+    pub enabled: bool,
+    pub version: Option<String>,
+    pub devices: Vec<VJoyDevice>,
+    /// vJoy Product strings from the active profile with no configured device behind them
+    pub missing_profile_devices: Vec<String>,
+}
+
+/// Is this SC Product string a vJoy device?
+pub fn is_vjoy_product(product: &str) -> bool {
+    product_names::normalize(product).contains("vjoy")
+        || product_names::uuid_from_product(product).as_deref() == Some("1234:bead")
+}
+
+/// Query the vJoy driver. Returns a default (not installed) status if vJoy is missing.
+pub fn query_status(profile_products: &[String]) -> VJoyStatus {
+    let mut status = query_driver();
+
+    // A profile's vJoy products map to vJoy devices in order
+    let expected: Vec<&String> = profile_products
+        .iter()
+        .filter(|p| is_vjoy_product(p))
+        .collect();
+    status.missing_profile_devices = expected
+        .into_iter()
+        .skip(status.devices.len())
+        .cloned()
+        .collect();
+
+    status
+}
+
+#[cfg(windows)]
+const DLL_PATHS: [&str; 2] = [
+    "vJoyInterface.dll",
+    r"C:\Program Files\vJoy\x64\vJoyInterface.dll",
+];
+
+#[cfg(windows)]
+fn load_library() -> Option<libloading::Library> {
+    // SAFETY: loading the vJoy interface DLL, which has no initialization side effects
+    DLL_PATHS
+        .iter()
+        .find_map(|path| unsafe { libloading::Library::new(path) }.ok())
+}
+
+#[cfg(windows)]
+fn query_driver() -> VJoyStatus {
+    use libloading::Symbol;
+
+    /// vJoy supports up to 16 virtual devices
+    const MAX_DEVICES: u32 = 16;
+
+    /// Axis HID usages vJoy can be configured with, and their names
+    const AXES: [(u32, &str); 8] = [
+        (0x30, "X"),
+        (0x31, "Y"),
+        (0x32, "Z"),
+        (0x33, "Rx"),
+        (0x34, "Ry"),
+        (0x35, "Rz"),
+        (0x36, "Slider"),
+        (0x37, "Dial"),
+    ];
+
+    let Some(lib) = load_library() else {
+        return VJoyStatus::default();
+    };
+
+    let mut status = VJoyStatus {
+        installed: true,
+        ..Default::default()
+    };
+
+    // SAFETY: signatures match vJoyInterface.h (BOOL = i32, SHORT = i16, UINT = u32)
+    unsafe {
+        let enabled: Result<Symbol<unsafe extern "C" fn() -> i32>, _> = lib.get(b"vJoyEnabled");
+        status.enabled = enabled.map(|f| f() != 0).unwrap_or(false);
+
+        if let Ok(get_version) = lib.get::<unsafe extern "C" fn() -> i16>(b"GetvJoyVersion") {
+            let version = get_version() as u16;
+            status.version = Some(format!(
+                "{}.{}.{}",
+                (version >> 8) & 0xF,
+                (version >> 4) & 0xF,
+                version & 0xF
+            ));
+        }
+
+        if !status.enabled {
+            return status;
+        }
+
+        let (
+            Ok(exists),
+            Ok(get_status),
+            Ok(buttons),
+            Ok(disc_povs),
+            Ok(cont_povs),
+            Ok(axis_exists),
+        ) = (
+            lib.get::<unsafe extern "C" fn(u32) -> i32>(b"isVJDExists"),
+            lib.get::<unsafe extern "C" fn(u32) -> i32>(b"GetVJDStatus"),
+            lib.get::<unsafe extern "C" fn(u32) -> i32>(b"GetVJDButtonNumber"),
+            lib.get::<unsafe extern "C" fn(u32) -> i32>(b"GetVJDDiscPovNumber"),
+            lib.get::<unsafe extern "C" fn(u32) -> i32>(b"GetVJDContPovNumber"),
+            lib.get::<unsafe extern "C" fn(u32, u32) -> i32>(b"GetVJDAxisExist"),
+        )
+        else {
+            eprintln!("[vJoy] vJoyInterface.dll is missing expected functions");
+            return status;
+        };
+
+        for id in 1..=MAX_DEVICES {
+            if exists(id) == 0 {
+                continue;
+            }
+
+            let device_status = match get_status(id) {
+                0 => "own",
+                1 => "free",
+                2 => "busy",
+                3 => continue, // VJD_STAT_MISS: not configured
+                _ => "unknown",
+            };
+
+            status.devices.push(VJoyDevice {
+                id,
+                status: device_status.to_string(),
+                axes: AXES
+                    .iter()
+                    .filter(|(usage, _)| axis_exists(id, *usage) != 0)
+                    .map(|(_, name)| name.to_string())
+                    .collect(),
+                button_count: buttons(id),
+                pov_count: disc_povs(id) + cont_povs(id),
+            });
+        }
+    }
+
+    status
+}
+
+// vJoy is Windows-only
+#[cfg(not(windows))]
+fn query_driver() -> VJoyStatus {
+    VJoyStatus::default()
+}
+
+/// An axis of a vJoy device
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum VJoyAxis {
+    X,
+    Y,
+    Z,
+    Rx,
+    Ry,
+    Rz,
+    Slider0,
+    Slider1,
+}
+
+#[cfg(windows)]
+impl VJoyAxis {
+    /// HID usage vJoy identifies the axis by (HID_USAGE_X...)
+    fn usage(self) -> u32 {
+        match self {
+            VJoyAxis::X => 0x30,
+            VJoyAxis::Y => 0x31,
+            VJoyAxis::Z => 0x32,
+            VJoyAxis::Rx => 0x33,
+            VJoyAxis::Ry => 0x34,
+            VJoyAxis::Rz => 0x35,
+            VJoyAxis::Slider0 => 0x36,
+            VJoyAxis::Slider1 => 0x37,
+        }
+    }
+}
+
+/// vJoy axis values run from 0 to 0x8000
+#[cfg(windows)]
+const AXIS_MAX: f64 = 32768.0;
+
+/// `BOOL SetAxis(LONG Value, UINT rID, UINT Axis)`; LONG is a signed 32-bit value
+#[cfg(windows)]
+type SetAxisFn = unsafe extern "C" fn(value: i32, id: u32, axis: u32) -> i32;
+
+/// A vJoy device acquired for feeding. Released (with its buttons reset) on drop.
+#[cfg(windows)]
+pub struct VJoyOutput {
+    id: u32,
+    set_button: unsafe extern "C" fn(i32, u32, u8) -> i32,
+    set_axis: SetAxisFn,
+    reset: unsafe extern "C" fn(u32) -> i32,
+    relinquish: unsafe extern "C" fn(u32),
+    // Keeps the function pointers above valid
+    _lib: libloading::Library,
+}
+
+#[cfg(windows)]
+impl VJoyOutput {
+    /// Take ownership of vJoy device `id` so its buttons can be set
+    pub fn acquire(id: u32) -> Result<Self, String> {
+        let lib = load_library().ok_or("vJoy is not installed")?;
+
+        // SAFETY: signatures match vJoyInterface.h (BOOL = LONG = i32, UCHAR = u8, UINT = u32)
+        unsafe {
+            let (Ok(acquire), Ok(set_button), Ok(set_axis), Ok(reset), Ok(relinquish)) = (
+                lib.get::<unsafe extern "C" fn(u32) -> i32>(b"AcquireVJD"),
+                lib.get::<unsafe extern "C" fn(i32, u32, u8) -> i32>(b"SetBtn"),
+                lib.get::<SetAxisFn>(b"SetAxis"),
+                lib.get::<unsafe extern "C" fn(u32) -> i32>(b"ResetVJD"),
+                lib.get::<unsafe extern "C" fn(u32)>(b"RelinquishVJD"),
+            ) else {
+                return Err("vJoyInterface.dll is missing expected functions".to_string());
+            };
+            if acquire(id) == 0 {
+                return Err(format!(
+                    "Could not acquire vJoy device {}; it may be missing or fed by another program",
+                    id
+                ));
+            }
+            reset(id);
+            Ok(VJoyOutput {
+                id,
+                set_button: *set_button,
+                set_axis: *set_axis,
+                reset: *reset,
+                relinquish: *relinquish,
+                _lib: lib,
+            })
+        }
+    }
+
+    /// Press or release a button (1-based)
+    pub fn set_button(&self, button: u32, pressed: bool) -> Result<(), String> {
+        let button = u8::try_from(button).map_err(|_| format!("No vJoy button {}", button))?;
+        // SAFETY: the device is acquired and the function comes from the loaded DLL
+        match unsafe { (self.set_button)(pressed as i32, self.id, button) } {
+            0 => Err(format!(
+                "vJoy device {} rejected button {}",
+                self.id, button
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Move an axis; `value` runs from -1.0 to 1.0, 0.0 being the center
+    pub fn set_axis(&self, axis: VJoyAxis, value: f64) -> Result<(), String> {
+        let scaled = ((value.clamp(-1.0, 1.0) + 1.0) / 2.0 * AXIS_MAX).round();
+        // Within 0..=0x8000, so the conversion to LONG is exact
+        let raw: i32 = scaled as i32;
+        // SAFETY: as in set_button
+        match unsafe { (self.set_axis)(raw, self.id, axis.usage()) } {
+            0 => Err(format!(
+                "vJoy device {} rejected axis {:?}; is it enabled in vJoyConf?",
+                self.id, axis
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for VJoyOutput {
+    fn drop(&mut self) {
+        // SAFETY: as in set_button; the device is released exactly once
+        unsafe {
+            (self.reset)(self.id);
+            (self.relinquish)(self.id);
+        }
+    }
+}
+
+/// vJoy is Windows-only; elsewhere nothing can be acquired
+#[cfg(not(windows))]
+pub struct VJoyOutput;
+
+#[cfg(not(windows))]
+impl VJoyOutput {
+    pub fn acquire(_id: u32) -> Result<Self, String> {
+        Err("vJoy is only available on Windows".to_string())
+    }
+
+    pub fn set_button(&self, _button: u32, _pressed: bool) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn set_axis(&self, _axis: VJoyAxis, _value: f64) -> Result<(), String> {
+        Ok(())
+    }
+}
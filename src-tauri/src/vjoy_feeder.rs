@@ -0,0 +1,631 @@
+//! Shift layers through a vJoy feeder
+//!
+//! A stick has only so many buttons. With shift layers, one physical button is held as a
+//! modifier and the other buttons then press different vJoy buttons, so each layer adds
+//! a full set of bindings. The feeder reads the physical device over HID and presses the
+//! vJoy button for the current layer; SC binds the vJoy buttons. The layer is taken when
+//! a button goes down, so releasing the shift button before the other one still
+//! releases the vJoy button that was pressed.
+//!
+//! Chords get rarely used actions onto a stick without giving them a button: pressing
+//! two physical buttons together presses one vJoy button instead of either. A button that
+//! is part of a chord is held back for the chord's window, and only passed on (to the
+//! layers) if its partner doesn't follow in time.
+//!
+//! The feeder also plays macros (see vjoy_macros). The layers, chords and macros are
+//! defined in the profile (`feeder` in the .sccontrols file) and the feeder only runs
+//! while started.
+
+use crate::hid_reader;
+use crate::vjoy::VJoyOutput;
+use crate::vjoy_macros::{self, Macro, MacroEvent, MacroPlayer};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time between reads of the physical device
+const POLL_INTERVAL: Duration = Duration::from_millis(4);
+
+/// vJoy devices have at most 128 buttons
+pub const MAX_VJOY_BUTTONS: u32 = 128;
+
+/// Longest gap between the two presses of a chord, unless the chord sets its own
+const DEFAULT_CHORD_WINDOW_MS: u64 = 150;
+
+/// How long a chord button tapped and released within the window is still passed on,
+/// so the game sees the press
+const TAP_MS: u64 = 50;
+
+/// What the feeder does, as stored in the profile
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct FeederConfig {
+    /// Physical device read, by identity ("vid:pid#n", see device_identity)
+    pub device: String,
+    /// vJoy device fed, 1-based
+    pub vjoy_device: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift_layers: Option<ShiftLayers>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chords: Vec<Chord>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub macros: Vec<Macro>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ShiftLayers {
+    /// Physical buttons that press a different vJoy button on each layer
+    pub buttons: Vec<u32>,
+    /// The first is the base layer, used while no shift button is held
+    pub layers: Vec<ShiftLayer>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ShiftLayer {
+    pub name: String,
+    /// Physical button held to select this layer; None for the base layer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift_button: Option<u32>,
+    /// vJoy button the first of `buttons` presses on this layer; the rest follow on
+    pub first_vjoy_button: u32,
+}
+
+/// Two physical buttons pressed together, pressing one vJoy button
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Chord {
+    pub name: String,
+    pub buttons: [u32; 2],
+    pub vjoy_button: u32,
+    /// Longest gap between the two presses, in ms; None for the default (150)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_ms: Option<u64>,
+    /// How long both have to stay down before the vJoy button is pressed, in ms, so
+    /// brushing past the pair doesn't fire it
+    pub debounce_ms: u64,
+}
+
+impl Chord {
+    fn window(&self) -> u64 {
+        self.window_ms.unwrap_or(DEFAULT_CHORD_WINDOW_MS)
+    }
+}
+
+impl ShiftLayers {
+    /// vJoy buttons `layer` uses
+    pub fn range(&self, layer: &ShiftLayer) -> std::ops::RangeInclusive<u32> {
+        let count = self.buttons.len() as u32;
+        layer.first_vjoy_button..=layer.first_vjoy_button + count.saturating_sub(1)
+    }
+
+    /// Everything wrong with the layers, empty when they can be fed
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.buttons.is_empty() {
+            problems.push("No buttons are remapped by the shift layers".to_string());
+        }
+        let unique: BTreeSet<u32> = self.buttons.iter().copied().collect();
+        if unique.len() != self.buttons.len() {
+            problems.push("A button is listed more than once".to_string());
+        }
+        match self.layers.first() {
+            None => problems.push("No layers are defined".to_string()),
+            Some(base) if base.shift_button.is_some() => {
+                problems.push(format!("The base layer '{}' has a shift button", base.name))
+            }
+            _ => {}
+        }
+        for layer in self.layers.iter().skip(1) {
+            match layer.shift_button {
+                None => problems.push(format!("Layer '{}' has no shift button", layer.name)),
+                Some(shift) if unique.contains(&shift) => problems.push(format!(
+                    "Button {} is both remapped and the shift button of '{}'",
+                    shift, layer.name
+                )),
+                _ => {}
+            }
+        }
+
+        for (i, layer) in self.layers.iter().enumerate() {
+            let range = self.range(layer);
+            if *range.start() == 0 || *range.end() > MAX_VJOY_BUTTONS {
+                problems.push(format!(
+                    "Layer '{}' needs vJoy buttons {}-{}, outside 1-{}",
+                    layer.name,
+                    range.start(),
+                    range.end(),
+                    MAX_VJOY_BUTTONS
+                ));
+            }
+            for other in &self.layers[..i] {
+                let other_range = self.range(other);
+                if range.start() <= other_range.end() && other_range.start() <= range.end() {
+                    problems.push(format!(
+                        "Layers '{}' and '{}' share vJoy buttons",
+                        other.name, layer.name
+                    ));
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// Everything wrong with the chords, given the layers they share the device with
+fn chord_problems(config: &FeederConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    let layers = config.shift_layers.as_ref();
+    let shift_buttons: BTreeSet<u32> = layers
+        .into_iter()
+        .flat_map(|layers| layers.layers.iter().filter_map(|layer| layer.shift_button))
+        .collect();
+
+    for (i, chord) in config.chords.iter().enumerate() {
+        let [first, second] = chord.buttons;
+        if first == second {
+            problems.push(format!(
+                "Chord '{}' uses button {} twice",
+                chord.name, first
+            ));
+        }
+        if let Some(shift) = chord.buttons.iter().find(|b| shift_buttons.contains(b)) {
+            problems.push(format!(
+                "Chord '{}' uses shift button {}",
+                chord.name, shift
+            ));
+        }
+        if chord.window_ms == Some(0) {
+            problems.push(format!("Chord '{}' has no time window", chord.name));
+        }
+        if chord.vjoy_button == 0 || chord.vjoy_button > MAX_VJOY_BUTTONS {
+            problems.push(format!(
+                "Chord '{}' presses vJoy button {}, outside 1-{}",
+                chord.name, chord.vjoy_button, MAX_VJOY_BUTTONS
+            ));
+        }
+        if let Some(layer) = layers.and_then(|layers| {
+            layers
+                .layers
+                .iter()
+                .find(|layer| layers.range(layer).contains(&chord.vjoy_button))
+        }) {
+            problems.push(format!(
+                "Chord '{}' and layer '{}' share vJoy button {}",
+                chord.name, layer.name, chord.vjoy_button
+            ));
+        }
+        for other in &config.chords[..i] {
+            if other.vjoy_button == chord.vjoy_button {
+                problems.push(format!(
+                    "Chords '{}' and '{}' share vJoy button {}",
+                    other.name, chord.name, chord.vjoy_button
+                ));
+            }
+            let mut pair = chord.buttons;
+            let mut other_pair = other.buttons;
+            pair.sort_unstable();
+            other_pair.sort_unstable();
+            if pair == other_pair {
+                problems.push(format!(
+                    "Chords '{}' and '{}' use the same buttons",
+                    other.name, chord.name
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Check a feeder configuration before it's saved or started
+pub fn validate(config: &FeederConfig) -> Result<(), String> {
+    let mut problems = Vec::new();
+    if config.device.trim().is_empty() {
+        problems.push("No physical device is selected".to_string());
+    }
+    if config.vjoy_device == 0 {
+        problems.push("No vJoy device is selected".to_string());
+    }
+    if let Some(layers) = &config.shift_layers {
+        problems.extend(layers.problems());
+    }
+    problems.extend(chord_problems(config));
+    problems.extend(vjoy_macros::problems(config));
+    if !problems.is_empty() {
+        return Err(problems.join("; "));
+    }
+    Ok(())
+}
+
+/// Turns pressed physical buttons into pressed vJoy buttons
+#[derive(Debug, Default)]
+pub struct LayerMapper {
+    /// vJoy button each held physical button pressed, fixed when it went down
+    latched: BTreeMap<u32, u32>,
+}
+
+impl LayerMapper {
+    /// The layer selected by the held shift buttons; the last listed wins
+    fn active_layer<'a>(layers: &'a ShiftLayers, pressed: &[u32]) -> Option<&'a ShiftLayer> {
+        layers
+            .layers
+            .iter()
+            .rev()
+            .find(|layer| layer.shift_button.is_some_and(|b| pressed.contains(&b)))
+            .or_else(|| layers.layers.first())
+    }
+
+    /// vJoy buttons that should be down while `pressed` physical buttons are held
+    pub fn update(&mut self, layers: &ShiftLayers, pressed: &[u32]) -> BTreeSet<u32> {
+        self.latched.retain(|button, _| pressed.contains(button));
+        let layer = Self::active_layer(layers, pressed);
+        for (position, button) in layers.buttons.iter().enumerate() {
+            if !pressed.contains(button) || self.latched.contains_key(button) {
+                continue;
+            }
+            if let Some(layer) = layer {
+                self.latched
+                    .insert(*button, layer.first_vjoy_button + position as u32);
+            }
+        }
+        self.latched.values().copied().collect()
+    }
+
+    /// Name of the layer in use for `pressed`
+    pub fn layer_name(layers: &ShiftLayers, pressed: &[u32]) -> Option<String> {
+        Self::active_layer(layers, pressed).map(|layer| layer.name.clone())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChordButton {
+    /// Down since this time, waiting for a partner
+    Pending(u64),
+    /// No partner came in time; passed on as a plain button
+    Passed,
+    /// Part of a chord; held back until released
+    Chorded,
+}
+
+/// Turns pressed physical buttons into pressed chords, passing on the buttons that
+/// aren't part of one
+#[derive(Debug, Default)]
+pub struct ChordMapper {
+    held: BTreeMap<u32, ChordButton>,
+    /// Buttons released while pending, passed on until this time
+    taps: BTreeMap<u32, u64>,
+    /// Chords whose buttons are both down, by index, since when
+    down: BTreeMap<usize, u64>,
+}
+
+/// What the chords leave of the pressed buttons
+#[derive(Debug, Default, PartialEq)]
+pub struct ChordOutput {
+    /// Physical buttons to treat as pressed, for the layers
+    pub passed: Vec<u32>,
+    /// vJoy buttons pressed by chords
+    pub vjoy: BTreeSet<u32>,
+}
+
+impl ChordMapper {
+    /// Update with the buttons `pressed` at `now` (ms, any fixed start)
+    pub fn update(&mut self, chords: &[Chord], pressed: &[u32], now: u64) -> ChordOutput {
+        let members: BTreeSet<u32> = chords.iter().flat_map(|c| c.buttons).collect();
+
+        let released: Vec<u32> = self
+            .held
+            .keys()
+            .filter(|b| !pressed.contains(b))
+            .copied()
+            .collect();
+        for button in released {
+            if let Some(ChordButton::Pending(_)) = self.held.remove(&button) {
+                self.taps.insert(button, now + TAP_MS);
+            }
+        }
+        self.down
+            .retain(|i, _| chords[*i].buttons.iter().all(|b| pressed.contains(b)));
+        for button in pressed.iter().filter(|b| members.contains(b)) {
+            self.held
+                .entry(*button)
+                .or_insert(ChordButton::Pending(now));
+        }
+
+        for (i, chord) in chords.iter().enumerate() {
+            let [first, second] = chord.buttons;
+            if let (Some(&ChordButton::Pending(a)), Some(&ChordButton::Pending(b))) =
+                (self.held.get(&first), self.held.get(&second))
+            {
+                if a.abs_diff(b) <= chord.window() {
+                    self.held.insert(first, ChordButton::Chorded);
+                    self.held.insert(second, ChordButton::Chorded);
+                    self.down.insert(i, now);
+                }
+            }
+        }
+
+        // A button waits for the longest window of the chords it's in
+        for (button, state) in self.held.iter_mut() {
+            if let ChordButton::Pending(since) = *state {
+                let window = chords
+                    .iter()
+                    .filter(|c| c.buttons.contains(button))
+                    .map(Chord::window)
+                    .max()
+                    .unwrap_or(0);
+                if now.saturating_sub(since) > window {
+                    *state = ChordButton::Passed;
+                }
+            }
+        }
+        self.taps.retain(|_, until| *until > now);
+
+        let mut passed: Vec<u32> = pressed
+            .iter()
+            .filter(|b| !members.contains(b) || self.held.get(b) == Some(&ChordButton::Passed))
+            .copied()
+            .collect();
+        let tapped: Vec<u32> = self
+            .taps
+            .keys()
+            .filter(|b| !passed.contains(b))
+            .copied()
+            .collect();
+        passed.extend(tapped);
+        ChordOutput {
+            passed,
+            vjoy: self
+                .down
+                .iter()
+                .filter(|(i, since)| now.saturating_sub(**since) >= chords[**i].debounce_ms)
+                .map(|(i, _)| chords[*i].vjoy_button)
+                .collect(),
+        }
+    }
+}
+
+/// What the frontend shows about the feeder
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct FeederStatus {
+    pub running: bool,
+    pub vjoy_device: u32,
+    /// Layer in use right now
+    pub layer: Option<String>,
+    /// Macros playing right now
+    pub macros: Vec<String>,
+    /// Why the feeder stopped, if it failed
+    pub error: Option<String>,
+}
+
+/// A running feeder. Dropping it stops feeding and releases the vJoy device.
+pub struct VJoyFeeder {
+    stop: Arc<AtomicBool>,
+    status: Arc<Mutex<FeederStatus>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl VJoyFeeder {
+    /// Start feeding vJoy from the HID device at `device_path`
+    pub fn start(device_path: String, config: FeederConfig) -> Result<Self, String> {
+        validate(&config)?;
+        let device = hid_reader::OpenedHidDevice::open(&device_path)?;
+        let descriptor = hid_reader::get_hid_descriptor_bytes(&device_path)?;
+        let output = VJoyOutput::acquire(config.vjoy_device)?;
+        let vjoy_device = config.vjoy_device;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let status = Arc::new(Mutex::new(FeederStatus {
+            running: true,
+            vjoy_device,
+            ..Default::default()
+        }));
+        let thread_stop = stop.clone();
+        let thread_status = status.clone();
+
+        let thread = thread::spawn(move || {
+            let result = feed(
+                &device,
+                &descriptor,
+                &output,
+                &config,
+                &thread_stop,
+                &thread_status,
+            );
+            let mut status = thread_status.lock().unwrap();
+            status.running = false;
+            status.layer = None;
+            if let Err(e) = result {
+                log::error!("vJoy feeder stopped: {}", e);
+                status.error = Some(e);
+            }
+        });
+
+        log::info!("Feeding vJoy device {} from {}", vjoy_device, device_path);
+        Ok(VJoyFeeder {
+            stop,
+            status,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn status(&self) -> FeederStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+impl Drop for VJoyFeeder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wait for the vJoy device to be released, so it can be acquired again right away
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn feed(
+    device: &hid_reader::OpenedHidDevice,
+    descriptor: &[u8],
+    output: &VJoyOutput,
+    config: &FeederConfig,
+    stop: &AtomicBool,
+    status: &Mutex<FeederStatus>,
+) -> Result<(), String> {
+    let mut chord_mapper = ChordMapper::default();
+    let mut mapper = LayerMapper::default();
+    let mut player = MacroPlayer::default();
+    let mut macro_buttons: BTreeSet<u32> = BTreeSet::new();
+    let mut pressed: Vec<u32> = Vec::new();
+    let mut fed: BTreeSet<u32> = BTreeSet::new();
+    let started = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        // Reports only arrive when something changes; keep the last state between them
+        let bytes = device.read(0)?;
+        if !bytes.is_empty() {
+            if let Ok(report) = hid_reader::parse_hid_full_report(&bytes, descriptor) {
+                pressed = report.pressed_buttons;
+            }
+        }
+
+        let now = started.elapsed().as_millis() as u64;
+        let chorded = chord_mapper.update(&config.chords, &pressed, now);
+        let mut wanted = chorded.vjoy;
+        if let Some(layers) = &config.shift_layers {
+            wanted.extend(mapper.update(layers, &chorded.passed));
+            status.lock().unwrap().layer = LayerMapper::layer_name(layers, &chorded.passed);
+        }
+
+        for event in player.update(&config.macros, &pressed, now) {
+            match event {
+                MacroEvent::Button(button, true) => {
+                    macro_buttons.insert(button);
+                }
+                MacroEvent::Button(button, false) => {
+                    macro_buttons.remove(&button);
+                }
+                MacroEvent::Axis(axis, value) => output.set_axis(axis, value)?,
+            }
+        }
+        wanted.extend(macro_buttons.iter().copied());
+        status.lock().unwrap().macros = player.playing(&config.macros);
+
+        for button in fed.difference(&wanted) {
+            output.set_button(*button, false)?;
+        }
+        for button in wanted.difference(&fed) {
+            output.set_button(*button, true)?;
+        }
+        fed = wanted;
+
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layers() -> ShiftLayers {
+        ShiftLayers {
+            buttons: vec![1, 2, 3],
+            layers: vec![
+                ShiftLayer {
+                    name: "Base".to_string(),
+                    shift_button: None,
+                    first_vjoy_button: 1,
+                },
+                ShiftLayer {
+                    name: "Shift".to_string(),
+                    shift_button: Some(10),
+                    first_vjoy_button: 11,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_layers_latch_on_press() {
+        let layers = layers();
+        assert!(validate(&FeederConfig {
+            device: "231d:0200#1".to_string(),
+            vjoy_device: 1,
+            shift_layers: Some(layers.clone()),
+            ..Default::default()
+        })
+        .is_ok());
+
+        let mut mapper = LayerMapper::default();
+        assert_eq!(mapper.update(&layers, &[2]), BTreeSet::from([2]));
+        assert_eq!(mapper.update(&layers, &[]), BTreeSet::new());
+
+        // Shift held: button 2 presses the second button of the shift range
+        assert_eq!(mapper.update(&layers, &[10, 2]), BTreeSet::from([12]));
+        // Shift released first: the shifted button stays down until button 2 is let go
+        assert_eq!(mapper.update(&layers, &[2]), BTreeSet::from([12]));
+        assert_eq!(mapper.update(&layers, &[2, 3]), BTreeSet::from([12, 3]));
+        assert_eq!(mapper.update(&layers, &[]), BTreeSet::new());
+
+        let mut overlapping = layers.clone();
+        overlapping.layers[1].first_vjoy_button = 3;
+        overlapping.layers[1].shift_button = Some(2);
+        let problems = overlapping.problems();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_chords_hold_back_their_buttons() {
+        let chords = vec![Chord {
+            name: "Landing gear".to_string(),
+            buttons: [2, 3],
+            vjoy_button: 20,
+            window_ms: Some(100),
+            debounce_ms: 30,
+        }];
+        let mut config = FeederConfig {
+            device: "231d:0200#1".to_string(),
+            vjoy_device: 1,
+            shift_layers: Some(layers()),
+            chords: chords.clone(),
+            ..Default::default()
+        };
+        assert!(validate(&config).is_ok());
+        config.chords[0].vjoy_button = 12;
+        assert!(validate(&config).is_err());
+
+        let mut mapper = ChordMapper::default();
+        // Pressed together: neither button is passed on, the chord fires after debounce
+        let output = mapper.update(&chords, &[2], 0);
+        assert!(output.passed.is_empty() && output.vjoy.is_empty());
+        let output = mapper.update(&chords, &[2, 3, 1], 50);
+        assert_eq!(output.passed, vec![1]);
+        assert!(output.vjoy.is_empty());
+        assert_eq!(
+            mapper.update(&chords, &[2, 3], 80).vjoy,
+            BTreeSet::from([20])
+        );
+        // Letting one go ends the chord; the other stays held back until released
+        let output = mapper.update(&chords, &[3], 90);
+        assert!(output.passed.is_empty() && output.vjoy.is_empty());
+        assert_eq!(mapper.update(&chords, &[], 100), ChordOutput::default());
+
+        // Held alone past the window: passed on as itself
+        assert!(mapper.update(&chords, &[2], 200).passed.is_empty());
+        assert_eq!(mapper.update(&chords, &[2], 301).passed, vec![2]);
+        // The partner coming late doesn't make a chord
+        let output = mapper.update(&chords, &[2, 3], 310);
+        assert_eq!(output.passed, vec![2]);
+        assert!(output.vjoy.is_empty());
+        mapper.update(&chords, &[], 320);
+
+        // Tapped and released within the window: still passed on briefly
+        mapper.update(&chords, &[3], 400);
+        assert_eq!(mapper.update(&chords, &[], 420).passed, vec![3]);
+        assert!(mapper.update(&chords, &[], 480).passed.is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+//! Macros played through the vJoy feeder
+//!
+//! Some things take a fixed sequence of inputs every time, like getting a ship ready to
+//! fly (power, engines, doors, gear). A macro binds such a sequence to one physical
+//! button: pressing it plays timed vJoy button presses and axis moves, which SC sees as
+//! if they had been made by hand. Macros live in the profile next to the layers and
+//! chords, and are played by the feeder without holding up its other buttons.
+
+use crate::vjoy::VJoyAxis;
+use crate::vjoy_feeder::{FeederConfig, MAX_VJOY_BUTTONS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Longest a macro may run, so a typo in a wait can't leave the feeder busy for hours
+const MAX_MACRO_MS: u64 = 60_000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Macro {
+    pub name: String,
+    /// Physical button that starts the macro
+    pub trigger: u32,
+    pub steps: Vec<MacroStep>,
+}
+
+/// One step of a macro. Steps run in order; only `press` and `wait` take time.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MacroStep {
+    /// Press a vJoy button and release it `hold_ms` later
+    Press {
+        button: u32,
+        hold_ms: u64,
+    },
+    /// Press a vJoy button and keep it down until an `up` step
+    Down {
+        button: u32,
+    },
+    Up {
+        button: u32,
+    },
+    /// Move a vJoy axis, from -1.0 to 1.0
+    Axis {
+        axis: VJoyAxis,
+        value: f64,
+    },
+    Wait {
+        ms: u64,
+    },
+}
+
+/// What a macro does to the vJoy device at one moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacroEvent {
+    Button(u32, bool),
+    Axis(VJoyAxis, f64),
+}
+
+impl Macro {
+    /// Events with the time (ms from the trigger) they're due
+    fn timeline(&self) -> Vec<(u64, MacroEvent)> {
+        let mut at = 0;
+        let mut events = Vec::new();
+        for step in &self.steps {
+            match *step {
+                MacroStep::Press { button, hold_ms } => {
+                    events.push((at, MacroEvent::Button(button, true)));
+                    at += hold_ms;
+                    events.push((at, MacroEvent::Button(button, false)));
+                }
+                MacroStep::Down { button } => events.push((at, MacroEvent::Button(button, true))),
+                MacroStep::Up { button } => events.push((at, MacroEvent::Button(button, false))),
+                MacroStep::Axis { axis, value } => events.push((at, MacroEvent::Axis(axis, value))),
+                MacroStep::Wait { ms } => at += ms,
+            }
+        }
+        events
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.trigger == 0 {
+            problems.push(format!("Macro '{}' has no trigger button", self.name));
+        }
+        if self.steps.is_empty() {
+            problems.push(format!("Macro '{}' has no steps", self.name));
+        }
+
+        let mut held = BTreeSet::new();
+        let mut duration = 0u64;
+        for step in &self.steps {
+            let button = match *step {
+                MacroStep::Press { button, hold_ms } => {
+                    duration = duration.saturating_add(hold_ms);
+                    if held.contains(&button) {
+                        problems.push(format!(
+                            "Macro '{}' presses vJoy button {} while it's already down",
+                            self.name, button
+                        ));
+                    }
+                    Some(button)
+                }
+                MacroStep::Down { button } => {
+                    if !held.insert(button) {
+                        problems.push(format!(
+                            "Macro '{}' puts vJoy button {} down twice",
+                            self.name, button
+                        ));
+                    }
+                    Some(button)
+                }
+                MacroStep::Up { button } => {
+                    if !held.remove(&button) {
+                        problems.push(format!(
+                            "Macro '{}' releases vJoy button {} without putting it down",
+                            self.name, button
+                        ));
+                    }
+                    Some(button)
+                }
+                MacroStep::Axis { axis, value } => {
+                    if !(-1.0..=1.0).contains(&value) {
+                        problems.push(format!(
+                            "Macro '{}' moves axis {:?} to {}, outside -1 to 1",
+                            self.name, axis, value
+                        ));
+                    }
+                    None
+                }
+                MacroStep::Wait { ms } => {
+                    duration = duration.saturating_add(ms);
+                    None
+                }
+            };
+            if let Some(button) = button.filter(|b| *b == 0 || *b > MAX_VJOY_BUTTONS) {
+                problems.push(format!(
+                    "Macro '{}' uses vJoy button {}, outside 1-{}",
+                    self.name, button, MAX_VJOY_BUTTONS
+                ));
+            }
+        }
+        if !held.is_empty() {
+            problems.push(format!(
+                "Macro '{}' leaves vJoy button(s) {:?} down",
+                self.name, held
+            ));
+        }
+        if duration > MAX_MACRO_MS {
+            problems.push(format!(
+                "Macro '{}' runs for {} ms, longer than {} ms",
+                self.name, duration, MAX_MACRO_MS
+            ));
+        }
+        problems
+    }
+
+    fn vjoy_buttons(&self) -> BTreeSet<u32> {
+        self.steps
+            .iter()
+            .filter_map(|step| match *step {
+                MacroStep::Press { button, .. }
+                | MacroStep::Down { button }
+                | MacroStep::Up { button } => Some(button),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Everything wrong with the macros, given the layers and chords they share the
+/// devices with
+pub fn problems(config: &FeederConfig) -> Vec<String> {
+    let mut physical = BTreeSet::new();
+    let mut vjoy = BTreeSet::new();
+    if let Some(layers) = &config.shift_layers {
+        physical.extend(layers.buttons.iter().copied());
+        physical.extend(layers.layers.iter().filter_map(|layer| layer.shift_button));
+        vjoy.extend(layers.layers.iter().flat_map(|layer| layers.range(layer)));
+    }
+    for chord in &config.chords {
+        physical.extend(chord.buttons);
+        vjoy.insert(chord.vjoy_button);
+    }
+
+    let mut problems = Vec::new();
+    let mut triggers = BTreeSet::new();
+    for m in &config.macros {
+        problems.extend(m.problems());
+        if physical.contains(&m.trigger) {
+            problems.push(format!(
+                "Macro '{}' is triggered by button {}, which the layers or chords use",
+                m.name, m.trigger
+            ));
+        }
+        if !triggers.insert(m.trigger) {
+            problems.push(format!(
+                "More than one macro is triggered by button {}",
+                m.trigger
+            ));
+        }
+        if let Some(button) = m.vjoy_buttons().intersection(&vjoy).next() {
+            problems.push(format!(
+                "Macro '{}' presses vJoy button {}, which the layers or chords press",
+                m.name, button
+            ));
+        }
+    }
+    problems
+}
+
+struct Playing {
+    index: usize,
+    started: u64,
+    timeline: Vec<(u64, MacroEvent)>,
+    next: usize,
+}
+
+/// Starts macros when their trigger goes down and hands out their events as they fall due
+#[derive(Default)]
+pub struct MacroPlayer {
+    triggers_down: BTreeSet<u32>,
+    playing: Vec<Playing>,
+}
+
+impl MacroPlayer {
+    /// Events due with `pressed` physical buttons at `now` (ms, any fixed start). A macro
+    /// already playing isn't restarted by pressing its trigger again.
+    pub fn update(&mut self, macros: &[Macro], pressed: &[u32], now: u64) -> Vec<MacroEvent> {
+        for (index, m) in macros.iter().enumerate() {
+            let down = pressed.contains(&m.trigger);
+            let newly = down && self.triggers_down.insert(m.trigger);
+            if !down {
+                self.triggers_down.remove(&m.trigger);
+            }
+            if newly && !self.playing.iter().any(|p| p.index == index) {
+                log::info!("Playing macro '{}'", m.name);
+                self.playing.push(Playing {
+                    index,
+                    started: now,
+                    timeline: m.timeline(),
+                    next: 0,
+                });
+            }
+        }
+
+        let mut events = Vec::new();
+        for playing in &mut self.playing {
+            let elapsed = now.saturating_sub(playing.started);
+            let mut touched = BTreeSet::new();
+            while let Some((at, event)) = playing.timeline.get(playing.next).copied() {
+                if at > elapsed {
+                    break;
+                }
+                // Pressed and released in one update, the device would never see the
+                // press (or the release between two presses); leave it for the next one
+                if let MacroEvent::Button(button, _) = event {
+                    if !touched.insert(button) {
+                        break;
+                    }
+                }
+                events.push(event);
+                playing.next += 1;
+            }
+        }
+        self.playing.retain(|p| p.next < p.timeline.len());
+        events
+    }
+
+    /// Names of the macros playing
+    pub fn playing(&self, macros: &[Macro]) -> Vec<String> {
+        self.playing
+            .iter()
+            .filter_map(|p| macros.get(p.index))
+            .map(|m| m.name.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_macro_plays_on_trigger() {
+        let flight_ready = Macro {
+            name: "Flight ready".to_string(),
+            trigger: 5,
+            steps: vec![
+                MacroStep::Press {
+                    button: 30,
+                    hold_ms: 100,
+                },
+                MacroStep::Press {
+                    button: 30,
+                    hold_ms: 100,
+                },
+                MacroStep::Axis {
+                    axis: VJoyAxis::Z,
+                    value: -1.0,
+                },
+                MacroStep::Wait { ms: 500 },
+                MacroStep::Down { button: 31 },
+                MacroStep::Wait { ms: 50 },
+                MacroStep::Up { button: 31 },
+            ],
+        };
+        let macros = vec![flight_ready.clone()];
+        let mut config = FeederConfig {
+            device: "231d:0200#1".to_string(),
+            vjoy_device: 1,
+            macros: macros.clone(),
+            ..Default::default()
+        };
+        assert!(problems(&config).is_empty());
+
+        config.macros[0].steps.pop();
+        config.macros[0].trigger = 0;
+        assert_eq!(problems(&config).len(), 2, "{:?}", problems(&config));
+
+        let mut player = MacroPlayer::default();
+        assert_eq!(
+            player.update(&macros, &[5], 0),
+            vec![MacroEvent::Button(30, true)]
+        );
+        // Held trigger doesn't restart it
+        assert!(player.update(&macros, &[5], 50).is_empty());
+        // The second press waits for the release to be sent
+        assert_eq!(
+            player.update(&macros, &[], 100),
+            vec![MacroEvent::Button(30, false)]
+        );
+        assert_eq!(
+            player.update(&macros, &[], 104),
+            vec![MacroEvent::Button(30, true)]
+        );
+        assert_eq!(
+            player.update(&macros, &[], 210),
+            vec![
+                MacroEvent::Button(30, false),
+                MacroEvent::Axis(VJoyAxis::Z, -1.0)
+            ]
+        );
+        assert_eq!(player.playing(&macros), vec!["Flight ready".to_string()]);
+        assert_eq!(
+            player.update(&macros, &[], 800),
+            vec![MacroEvent::Button(31, true)]
+        );
+        assert_eq!(
+            player.update(&macros, &[], 804),
+            vec![MacroEvent::Button(31, false)]
+        );
+        assert!(player.playing(&macros).is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+//! VoiceAttack profile export
+//!
+//! Voice command users drive SC through VoiceAttack: each spoken phrase presses the key
+//! an action is bound to. Keeping those profiles in step with the keybinds by hand is
+//! tedious, so we generate one command per keyboard-bound action, named after the
+//! action, that presses its key with the modifiers held for as long as the activation
+//! mode needs. Re-importing the exported profile after a rebind updates the commands in
+//! place, since their ids are derived from the action rather than random.
+
+use crate::binding_string::BindingInput;
+use crate::cheat_sheet::resolve_label;
+use crate::keybindings::{InputType, MergedAction, MergedBindings};
+use crate::keyboard_capture::virtual_key;
+use quick_xml::escape::escape;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// VoiceAttack's own default for a key press, in seconds
+const TAP_SECONDS: f64 = 0.1;
+/// Gap between the presses of a double tap, in seconds
+const TAP_GAP_SECONDS: f64 = 0.1;
+
+/// An action that couldn't become a command
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SkippedAction {
+    pub action_map: String,
+    pub action: String,
+    pub reason: String,
+}
+
+/// Result of exporting a VoiceAttack profile
+#[derive(Debug, Serialize, Clone)]
+pub struct VoiceAttackExport {
+    /// Profile XML, importable through VoiceAttack's "Import Profile"
+    #[serde(skip)]
+    pub xml: String,
+    pub commands: usize,
+    /// Actions bound to keys VoiceAttack can't press
+    pub skipped: Vec<SkippedAction>,
+}
+
+struct Command {
+    id: String,
+    phrase: String,
+    category: String,
+    key_codes: Vec<u16>,
+    hold_seconds: f64,
+    taps: u32,
+}
+
+/// How long to hold a key so SC sees the activation mode, in seconds
+fn hold_seconds(activation_mode: &str, on_hold: bool) -> f64 {
+    match activation_mode {
+        mode if mode.contains("long") => 1.6,
+        mode if mode.contains("medium") => 0.6,
+        mode if mode.starts_with("delayed") || mode.starts_with("hold") => 0.35,
+        _ if on_hold => 0.35,
+        _ => TAP_SECONDS,
+    }
+}
+
+/// GUID-shaped id from a stable hash of `text` (FNV-1a, run twice for 128 bits)
+fn stable_guid(text: &str) -> String {
+    let fnv = |seed: u64| {
+        text.bytes().fold(seed, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    };
+    let high = fnv(0xcbf2_9ce4_8422_2325);
+    let low = fnv(0x8422_2325_cbf2_9ce4);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// VoiceAttack phrases can't contain its own syntax characters ([ ] ; etc.)
+fn spoken_phrase(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '\'' || c == '-' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Key codes, hold time and taps for the first keyboard binding of an action, or None
+/// if it has no keyboard binding
+fn keyboard_binding(action: &MergedAction) -> Option<Result<(Vec<u16>, f64, u32), String>> {
+    let mut unpressable = None;
+    for binding in &action.bindings {
+        let Ok(input) = BindingInput::parse(&binding.input, binding.multi_tap) else {
+            continue;
+        };
+        if input.device != InputType::Keyboard || input.is_cleared() {
+            continue;
+        }
+        let key_codes: Option<Vec<u16>> = input
+            .modifiers
+            .iter()
+            .map(|m| m.sc_name())
+            .chain(std::iter::once(input.key.as_str()))
+            .map(virtual_key)
+            .collect();
+        match key_codes {
+            Some(key_codes) => {
+                let taps = if binding.activation_mode.starts_with("double_tap") {
+                    input.taps.max(2)
+                } else {
+                    input.taps.max(1)
+                };
+                return Some(Ok((
+                    key_codes,
+                    hold_seconds(&binding.activation_mode, action.on_hold),
+                    taps,
+                )));
+            }
+            None => unpressable = Some(binding.input.clone()),
+        }
+    }
+    unpressable.map(|input| Err(format!("No key code for '{}'", input)))
+}
+
+/// Build a VoiceAttack profile with a command for every keyboard-bound action.
+/// `localization` resolves "@ui_" labels into the phrases users speak.
+pub fn export_profile(
+    profile_name: &str,
+    bindings: &MergedBindings,
+    localization: &HashMap<String, String>,
+) -> VoiceAttackExport {
+    let mut commands = Vec::new();
+    let mut skipped = Vec::new();
+    let mut phrases = HashSet::new();
+
+    for map in &bindings.action_maps {
+        let category = resolve_label(Some(&map.ui_label), &map.name, localization);
+        for action in &map.actions {
+            let (key_codes, hold_seconds, taps) = match keyboard_binding(action) {
+                Some(Ok(found)) => found,
+                Some(Err(reason)) => {
+                    skipped.push(SkippedAction {
+                        action_map: map.name.clone(),
+                        action: action.name.clone(),
+                        reason,
+                    });
+                    continue;
+                }
+                None => continue,
+            };
+
+            let label = resolve_label(Some(&action.ui_label), &action.name, localization);
+            let mut phrase = spoken_phrase(&label);
+            if phrase.is_empty() {
+                phrase = spoken_phrase(&action.name);
+            }
+            // The same label is used in several maps ("Exit Seat"); VoiceAttack needs
+            // every phrase to be unique
+            if !phrases.insert(phrase.to_lowercase()) {
+                phrase = format!("{} {}", phrase, spoken_phrase(&category));
+                if !phrases.insert(phrase.to_lowercase()) {
+                    skipped.push(SkippedAction {
+                        action_map: map.name.clone(),
+                        action: action.name.clone(),
+                        reason: format!("Phrase '{}' is already used", phrase),
+                    });
+                    continue;
+                }
+            }
+
+            commands.push(Command {
+                id: stable_guid(&format!("{}/{}/{}", profile_name, map.name, action.name)),
+                phrase,
+                category: category.clone(),
+                key_codes,
+                hold_seconds,
+                taps,
+            });
+        }
+    }
+
+    VoiceAttackExport {
+        xml: render_xml(profile_name, &commands),
+        commands: commands.len(),
+        skipped,
+    }
+}
+
+fn render_action(xml: &mut String, id: &str, ordinal: usize, kind: &str, body: &str) {
+    xml.push_str("        <CommandAction>\n");
+    xml.push_str(&format!("          <Ordinal>{}</Ordinal>\n", ordinal));
+    xml.push_str("          <ConditionMet xsi:nil=\"true\" />\n");
+    xml.push_str(&format!(
+        "          <Id>{}</Id>\n",
+        stable_guid(&format!("{}#{}", id, ordinal))
+    ));
+    xml.push_str(&format!("          <ActionType>{}</ActionType>\n", kind));
+    xml.push_str(body);
+    xml.push_str("        </CommandAction>\n");
+}
+
+fn render_xml(profile_name: &str, commands: &[Command]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str(
+        "<Profile xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\">\n",
+    );
+    xml.push_str("  <HasMB>false</HasMB>\n");
+    xml.push_str(&format!("  <Id>{}</Id>\n", stable_guid(profile_name)));
+    xml.push_str(&format!("  <Name>{}</Name>\n", escape(profile_name)));
+    xml.push_str("  <Commands>\n");
+
+    for command in commands {
+        xml.push_str("    <Command>\n");
+        xml.push_str("      <Referrer xsi:nil=\"true\" />\n");
+        xml.push_str(&format!("      <BaseId>{}</BaseId>\n", command.id));
+        xml.push_str(&format!("      <Id>{}</Id>\n", command.id));
+        xml.push_str(&format!(
+            "      <CommandString>{}</CommandString>\n",
+            escape(command.phrase.as_str())
+        ));
+        xml.push_str("      <ActionSequence>\n");
+
+        let key_codes: String = command
+            .key_codes
+            .iter()
+            .map(|code| format!("            <unsignedShort>{}</unsignedShort>\n", code))
+            .collect();
+        let press = format!(
+            "          <Duration>{}</Duration>\n          <Delay>0</Delay>\n          \
+             <KeyCodes>\n{}          </KeyCodes>\n",
+            command.hold_seconds, key_codes
+        );
+        let pause = format!(
+            "          <Duration>{}</Duration>\n          <Delay>0</Delay>\n          \
+             <KeyCodes />\n",
+            TAP_GAP_SECONDS
+        );
+        let mut ordinal = 0;
+        for tap in 0..command.taps {
+            if tap > 0 {
+                render_action(&mut xml, &command.id, ordinal, "Pause", &pause);
+                ordinal += 1;
+            }
+            render_action(&mut xml, &command.id, ordinal, "PressKey", &press);
+            ordinal += 1;
+        }
+
+        xml.push_str("      </ActionSequence>\n");
+        xml.push_str("      <Async>true</Async>\n");
+        xml.push_str("      <Enabled>true</Enabled>\n");
+        xml.push_str(&format!(
+            "      <Category>{}</Category>\n",
+            escape(command.category.as_str())
+        ));
+        xml.push_str("      <UseShortcut>false</UseShortcut>\n");
+        xml.push_str("      <UseSpokenPhrase>true</UseSpokenPhrase>\n");
+        xml.push_str("    </Command>\n");
+    }
+
+    xml.push_str("  </Commands>\n");
+    xml.push_str("</Profile>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keybindings::{MergedActionMap, MergedBinding};
+
+    fn binding(input: &str, activation_mode: &str, multi_tap: Option<u32>) -> MergedBinding {
+        MergedBinding {
+            input: input.to_string(),
+            display_name: input.to_string(),
+            input_type: String::new(),
+            is_default: true,
+            multi_tap,
+            activation_mode: activation_mode.to_string(),
+            original_default: None,
+        }
+    }
+
+    fn action(name: &str, label: &str, bindings: Vec<MergedBinding>) -> MergedAction {
+        MergedAction {
+            name: name.to_string(),
+            ui_label: label.to_string(),
+            ui_description: String::new(),
+            category: String::new(),
+            is_customized: false,
+            on_hold: false,
+            bindings,
+        }
+    }
+
+    #[test]
+    fn test_keyboard_actions_become_commands() {
+        let bindings = MergedBindings {
+            action_maps: vec![
+                MergedActionMap {
+                    name: "spaceship_general".to_string(),
+                    ui_label: "@ui_CGSpaceFlight".to_string(),
+                    ui_category: String::new(),
+                    actions: vec![
+                        action(
+                            "v_toggle_landing_system",
+                            "@ui_CIToggleLandingGear",
+                            vec![binding("js1_button4", "", None), binding("kb1_n", "", None)],
+                        ),
+                        action(
+                            "v_eject",
+                            "Eject [Hold]",
+                            vec![binding("kb1_ralt+y", "delayed_press_long", None)],
+                        ),
+                        action(
+                            "v_exit",
+                            "Exit Seat",
+                            vec![binding("kb1_y", "delayed_press_medium", None)],
+                        ),
+                        action(
+                            "v_flightready",
+                            "Flight Ready",
+                            vec![binding("kb1_r", "", Some(2))],
+                        ),
+                        action("v_unbound", "Unbound", vec![binding("kb1_ ", "", None)]),
+                    ],
+                },
+                MergedActionMap {
+                    name: "vehicle_general".to_string(),
+                    ui_label: "Vehicles".to_string(),
+                    ui_category: String::new(),
+                    actions: vec![action(
+                        "v_exit",
+                        "Exit Seat",
+                        vec![binding("kb1_y", "delayed_press_medium", None)],
+                    )],
+                },
+            ],
+            device_options: Vec::new(),
+        };
+        let localization = HashMap::from([
+            (
+                "ui_CGSpaceFlight".to_string(),
+                "Flight - General".to_string(),
+            ),
+            (
+                "ui_CIToggleLandingGear".to_string(),
+                "Landing Gear (Toggle)".to_string(),
+            ),
+        ]);
+
+        let export = export_profile("SC keybinds", &bindings, &localization);
+        assert_eq!(export.commands, 5);
+        assert!(export.skipped.is_empty(), "{:?}", export.skipped);
+
+        let xml = &export.xml;
+        assert!(xml.contains("<CommandString>Landing Gear Toggle</CommandString>"));
+        assert!(xml.contains("<Category>Flight - General</Category>"));
+        // Right Alt + Y held long enough for a long delayed press
+        assert!(xml.contains("<CommandString>Eject Hold</CommandString>"));
+        assert!(xml.contains(
+            "<Duration>1.6</Duration>\n          <Delay>0</Delay>\n          <KeyCodes>\n            \
+             <unsignedShort>165</unsignedShort>\n            <unsignedShort>89</unsignedShort>"
+        ));
+        // The second "Exit Seat" is told apart by its map
+        assert!(xml.contains("<CommandString>Exit Seat</CommandString>"));
+        assert!(xml.contains("<CommandString>Exit Seat Vehicles</CommandString>"));
+        // Double tap: press, pause, press
+        assert_eq!(xml.matches("<ActionType>Pause</ActionType>").count(), 1);
+
+        // Ids don't change between exports
+        let again = export_profile("SC keybinds", &bindings, &localization);
+        assert_eq!(again.xml, export.xml);
+    }
+}
@@ -0,0 +1,133 @@
+//! File watching for actionmaps.xml
+//!
+//! Star Citizen rewrites actionmaps.xml whenever it exits (and sometimes while running).
+//! `FileWatcher` watches the file's directory, debounces the burst of events a single
+//! save produces, and only reports a change when the file content actually differs.
+
+use log::{error, info};
+use notify::{RecursiveMode, Watcher};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for the game to finish writing before reading the file
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// How often the worker thread checks whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Hash file content so we can tell real changes from touch-only events
+pub fn content_hash(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Watches a single file and calls back when its content changes.
+/// The watcher stops when dropped.
+pub struct FileWatcher {
+    stop: Arc<AtomicBool>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl FileWatcher {
+    /// Start watching `path`.
+    ///
+    /// `on_change` receives the new file content. If it writes the file itself it should
+    /// return the hash of what it wrote so that write isn't reported back as a change.
+    pub fn start<F>(path: PathBuf, mut on_change: F) -> Result<Self, String>
+    where
+        F: FnMut(&str) -> Option<u64> + Send + 'static,
+    {
+        let parent = path
+            .parent()
+            .ok_or_else(|| format!("Invalid path: {}", path.display()))?
+            .to_path_buf();
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| format!("Invalid path: {}", path.display()))?
+            .to_os_string();
+
+        let (tx, rx) = mpsc::channel::<notify::Result<notify::Event>>();
+        let mut watcher = notify::recommended_watcher(tx)
+            .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+
+        // Watch the directory rather than the file: the game replaces the file on save,
+        // which would silently end a watch on the file itself on some platforms
+        watcher
+            .watch(&parent, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", parent.display(), e))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread_path = path.clone();
+
+        thread::spawn(move || {
+            let mut last_hash = std::fs::read(&thread_path)
+                .ok()
+                .map(|content| content_hash(&content));
+
+            let is_our_file = |event: &notify::Event| {
+                event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == Some(file_name.as_os_str()))
+            };
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                let event = match rx.recv_timeout(POLL_INTERVAL) {
+                    Ok(Ok(event)) => event,
+                    Ok(Err(e)) => {
+                        error!("File watcher error: {}", e);
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+
+                if !is_our_file(&event) {
+                    continue;
+                }
+
+                // A single save produces several events; drain them before reading
+                while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+                let content = match std::fs::read(&thread_path) {
+                    Ok(content) => content,
+                    Err(_) => continue, // File may be mid-replace, we'll get another event
+                };
+
+                let hash = content_hash(&content);
+                if last_hash == Some(hash) {
+                    continue;
+                }
+                last_hash = Some(hash);
+
+                info!("Detected change to {}", thread_path.display());
+                let text = String::from_utf8_lossy(&content);
+                if let Some(written_hash) = on_change(&text) {
+                    last_hash = Some(written_hash);
+                }
+            }
+
+            info!("Stopped watching {}", thread_path.display());
+        });
+
+        info!("Watching {} for changes", path.display());
+
+        Ok(FileWatcher {
+            stop,
+            _watcher: watcher,
+        })
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
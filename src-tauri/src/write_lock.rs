@@ -0,0 +1,158 @@
+//! Single-writer lock shared by every running instance of the app (and the CLI)
+//!
+//! Writes to actionmaps.xml and the profile store take an exclusive OS file lock on
+//! `write.lock` in the app data directory. The lock is released when the guard drops,
+//! and the OS releases it if the process dies, so a crash can't leave it stuck.
+//! Who holds the lock is recorded next to it so the other instance can say why it's
+//! waiting.
+//!
+//! The lock is not reentrant: a second acquire in the same process, while a guard is
+//! still alive, waits and times out like any other instance would. Take it once per
+//! write and pass the guard down rather than acquiring it again in a callee.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LOCK_FILE_NAME: &str = "write.lock";
+const OWNER_FILE_NAME: &str = "write.lock.json";
+
+/// How long to wait for another instance to finish before giving up
+pub const DEFAULT_WAIT: Duration = Duration::from_secs(3);
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Who holds the lock
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// "gui" or "cli"
+    pub kind: String,
+    pub acquired_at: String,
+}
+
+/// Held while writing. Dropping it releases the lock.
+pub struct WriteLock {
+    file: File,
+    owner_path: PathBuf,
+}
+
+fn read_owner(dir: &Path) -> Option<LockOwner> {
+    let json = std::fs::read_to_string(dir.join(OWNER_FILE_NAME)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+impl WriteLock {
+    /// Take the write lock, waiting up to `wait` for another instance to release it.
+    /// Must not be called again while this process already holds it (see module docs).
+    pub fn acquire(dir: &Path, kind: &str, wait: Duration) -> Result<WriteLock, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create lock directory: {}", e))?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK_FILE_NAME))
+            .map_err(|e| format!("Failed to open write lock: {}", e))?;
+
+        let deadline = Instant::now() + wait;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(RETRY_INTERVAL);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    let holder = read_owner(dir)
+                        .map(|o| format!(" ({} instance, pid {})", o.kind, o.pid))
+                        .unwrap_or_default();
+                    return Err(format!(
+                        "Another Boxxy Binder instance{} is writing right now. Try again once it has finished.",
+                        holder
+                    ));
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(format!("Failed to take write lock: {}", e));
+                }
+            }
+        }
+
+        let owner = LockOwner {
+            pid: std::process::id(),
+            kind: kind.to_string(),
+            acquired_at: chrono::Local::now().to_rfc3339(),
+        };
+        let owner_path = dir.join(OWNER_FILE_NAME);
+        if let Ok(json) = serde_json::to_string(&owner) {
+            let _ = std::fs::write(&owner_path, json);
+        }
+
+        Ok(WriteLock { file, owner_path })
+    }
+}
+
+impl Drop for WriteLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.owner_path);
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sc_sim::TempDir;
+
+    const SHORT_WAIT: Duration = Duration::from_millis(300);
+
+    #[test]
+    fn test_second_acquire_times_out_naming_the_holder() {
+        let temp = TempDir::new("write-lock-held");
+        let held = WriteLock::acquire(temp.path(), "cli", SHORT_WAIT).unwrap();
+
+        let started = Instant::now();
+        let error = match WriteLock::acquire(temp.path(), "gui", SHORT_WAIT) {
+            Ok(_) => panic!("the lock is already held"),
+            Err(e) => e,
+        };
+        assert!(started.elapsed() >= SHORT_WAIT);
+        assert!(
+            error.contains(&format!("(cli instance, pid {})", std::process::id())),
+            "{}",
+            error
+        );
+
+        // Free again once the guard is dropped, and the owner record goes with it
+        drop(held);
+        assert!(read_owner(temp.path()).is_none());
+        let again = WriteLock::acquire(temp.path(), "gui", SHORT_WAIT).unwrap();
+        assert_eq!(read_owner(temp.path()).unwrap().kind, "gui");
+        drop(again);
+    }
+
+    #[test]
+    fn test_stale_lock_from_dead_process_is_recovered() {
+        let temp = TempDir::new("write-lock-stale");
+        // A crashed instance leaves both files behind; the OS already dropped its lock
+        std::fs::write(temp.path().join(LOCK_FILE_NAME), "").unwrap();
+        let stale = LockOwner {
+            pid: u32::MAX,
+            kind: "cli".to_string(),
+            acquired_at: "2024-01-01T00:00:00+00:00".to_string(),
+        };
+        std::fs::write(
+            temp.path().join(OWNER_FILE_NAME),
+            serde_json::to_string(&stale).unwrap(),
+        )
+        .unwrap();
+
+        let lock = WriteLock::acquire(temp.path(), "gui", SHORT_WAIT).unwrap();
+        let owner = read_owner(temp.path()).unwrap();
+        assert_eq!(owner.pid, std::process::id());
+        assert_eq!(owner.kind, "gui");
+        drop(lock);
+    }
+}
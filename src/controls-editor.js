@@ -6,7 +6,7 @@
  * - Sensitivity curves (nonlinearity_curve)
  * - Exponent values
  * 
- * Supports keyboard, gamepad, and joystick option trees.
+ * Supports keyboard, gamepad, and joystick option trees; mouse options are carried through.
  */
 
 const { invoke } = window.__TAURI__.core;
@@ -632,12 +632,33 @@ async function applyControlsToSC()
             ? currentControlsFilePath.split(/[/\\]/).pop().replace(/\.[^.]+$/, '')
             : 'SC Joy Mapper';
 
-        const result = await invoke('apply_controls_to_actionmaps', {
+        let result = await invoke('apply_controls_to_actionmaps', {
             actionmapsPath,
             settings,
             profileName
         });
 
+        // Large changes are held back until confirmed with the token the backend returns
+        if (result.confirmation)
+        {
+            const summary = result.confirmation.summary;
+            const confirmedLarge = await window.showConfirmation(
+                `${result.message}\n\n` +
+                `${summary.option_changes.length} option change(s), ` +
+                `${summary.binding_changes.length} binding change(s).\n\n` +
+                'Apply anyway?',
+                'Confirm Large Change'
+            );
+            if (!confirmedLarge) return;
+
+            result = await invoke('apply_controls_to_actionmaps', {
+                actionmapsPath,
+                settings,
+                profileName,
+                confirmationToken: result.confirmation.token
+            });
+        }
+
         if (result.success)
         {
             if (window.toast)
@@ -648,6 +669,17 @@ async function applyControlsToSC()
             {
                 await window.showAlert(result.message, 'Success');
             }
+
+            // Options the installed game version doesn't know were written but will be ignored
+            if (result.warnings && result.warnings.length > 0 && window.showAlert)
+            {
+                const lines = result.warnings.map(w => `${w.context}: ${w.identifier}`);
+                await window.showAlert(
+                    `${result.warnings.length} option(s) aren't known to the installed game ` +
+                    `version and will have no effect:\n\n${lines.join('\n')}`,
+                    'Unknown Options'
+                );
+            }
         }
         else
         {
@@ -2858,6 +2890,7 @@ window.getControlsForSaving = function ()
     // Convert userSettings to the format expected by the backend
     const devices = {
         keyboard: null,
+        mouse: null,
         gamepad: null,
         joystick: null
     };
@@ -2868,6 +2901,12 @@ window.getControlsForSaving = function ()
         devices.keyboard = convertSettingsForSave(userSettings.keyboard);
     }
 
+    // Mouse options have no tree in the editor; pass them through as loaded
+    if (userSettings.mouse && Object.keys(userSettings.mouse).length > 0)
+    {
+        devices.mouse = userSettings.mouse;
+    }
+
     // Convert gamepad settings
     if (userSettings.gamepad && Object.keys(userSettings.gamepad).length > 0)
     {
@@ -2945,6 +2984,7 @@ window.loadControlsFromFile = function (loadedData)
     // Clear existing settings
     userSettings = {
         keyboard: {},
+        mouse: {},
         gamepad: {},
         joystick: {}
     };
@@ -2955,6 +2995,12 @@ window.loadControlsFromFile = function (loadedData)
         userSettings.keyboard = convertLoadedSettings(loadedData.devices.keyboard);
     }
 
+    // Load mouse settings (invert, sensitivity, smoothing) untouched
+    if (loadedData.devices && loadedData.devices.mouse)
+    {
+        userSettings.mouse = { ...loadedData.devices.mouse };
+    }
+
     // Load gamepad settings
     if (loadedData.devices && loadedData.devices.gamepad)
     {